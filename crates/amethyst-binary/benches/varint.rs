@@ -0,0 +1,99 @@
+use amethyst_binary::io::{BinaryReader, BinaryRef, BinaryWriter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// Values per iteration, so the fixed cost of setting up a writer or reader is amortized.
+const COUNT: usize = 1024;
+
+/// Values that encode to 1, 3 and 5 bytes.
+const CASES: &[(&str, u32)] = &[("1 byte", 100), ("3 bytes", 1 << 20), ("5 bytes", u32::MAX)];
+
+/// Lengths vary from value to value in real packets, which defeats branch prediction in a
+/// byte-at-a-time decoder.
+fn mixed() -> Vec<u32> {
+    let mut state: u32 = 0x9e37_79b9;
+    (0..COUNT)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state >> (state % 32)
+        })
+        .collect()
+}
+
+fn encode(values: &[u32]) -> BinaryWriter {
+    let mut writer = BinaryWriter::with_capacity(values.len() * 5);
+    for &value in values {
+        writer.write_var_u32(value).unwrap();
+    }
+    writer
+}
+
+fn var_u32(c: &mut Criterion) {
+    let mut group = c.benchmark_group("var_u32");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    let cases = CASES
+        .iter()
+        .map(|&(name, value)| (name, vec![value; COUNT]))
+        .chain([("mixed", mixed())]);
+    for (name, values) in cases {
+        let encoded = encode(&values).freeze();
+
+        group.bench_with_input(BenchmarkId::new("encode", name), &values, |b, values| {
+            b.iter(|| encode(black_box(values)))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, encoded| {
+            b.iter(|| {
+                let mut reader = BinaryReader::new(encoded.clone());
+                for _ in 0..COUNT {
+                    black_box(reader.read_var_u32().unwrap());
+                }
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decode_borrowed", name),
+            &encoded,
+            |b, encoded| {
+                b.iter(|| {
+                    let mut reader = BinaryRef::new(encoded);
+                    for _ in 0..COUNT {
+                        black_box(reader.read_var_u32().unwrap());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn var_u64(c: &mut Criterion) {
+    let mut group = c.benchmark_group("var_u64");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    let values = vec![u64::MAX; COUNT];
+    let mut writer = BinaryWriter::with_capacity(COUNT * 10);
+    for &value in &values {
+        writer.write_var_u64(value).unwrap();
+    }
+    let encoded = writer.freeze();
+
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            let mut writer = BinaryWriter::with_capacity(COUNT * 10);
+            for &value in black_box(&values) {
+                writer.write_var_u64(value).unwrap();
+            }
+            writer
+        })
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut reader = BinaryReader::new(encoded.clone());
+            for _ in 0..COUNT {
+                black_box(reader.read_var_u64().unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, var_u32, var_u64);
+criterion_main!(benches);
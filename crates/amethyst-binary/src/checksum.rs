@@ -0,0 +1,82 @@
+use crate::error::BinaryError;
+use crate::io::{BinaryReader, BinaryWriter};
+use bytes::Bytes;
+
+/// Checksum algorithms that can guard a region of binary data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-32 (IEEE), written as a big-endian u32.
+    Crc32,
+    /// xxHash64 with a seed of 0, written as a big-endian u64.
+    XxHash64,
+}
+
+impl Checksum {
+    /// Number of bytes the checksum occupies on the wire.
+    pub fn size(&self) -> usize {
+        match self {
+            Checksum::Crc32 => 4,
+            Checksum::XxHash64 => 8,
+        }
+    }
+
+    /// Computes the checksum of `data`, widened to u64.
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        match self {
+            Checksum::Crc32 => crc32(data) as u64,
+            Checksum::XxHash64 => xxhash64(data, 0),
+        }
+    }
+}
+
+#[inline]
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+#[inline]
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    xxhash_rust::xxh64::xxh64(data, seed)
+}
+
+impl BinaryWriter {
+    /// Writes a region as `var_u32 length | data | checksum(data)`.
+    ///
+    /// The region's contents are produced by `f`, which writes into a scratch writer.
+    pub fn write_checksummed<F>(&mut self, checksum: Checksum, f: F) -> Result<(), BinaryError>
+    where
+        F: FnOnce(&mut BinaryWriter) -> Result<(), BinaryError>,
+    {
+        let mut region = BinaryWriter::new();
+        f(&mut region)?;
+        let data = region.as_bytes();
+        let len = u32::try_from(data.len())
+            .map_err(|_| BinaryError::InvalidData("Checksummed region too large".to_string()))?;
+
+        self.write_var_u32(len)?;
+        self.write_bytes(data)?;
+        let sum = checksum.compute(data);
+        match checksum {
+            Checksum::Crc32 => self.write_u32(sum as u32),
+            Checksum::XxHash64 => self.write_u64(sum),
+        }
+    }
+}
+
+impl BinaryReader {
+    /// Reads a region written by [`BinaryWriter::write_checksummed`], verifying its checksum.
+    pub fn read_checksummed(&mut self, checksum: Checksum) -> Result<Bytes, BinaryError> {
+        let len = self.read_var_u32()? as usize;
+        self.ensure_remaining(len.saturating_add(checksum.size()))?;
+        let data = self.read_bytes(len)?;
+        let expected = match checksum {
+            Checksum::Crc32 => self.read_u32()? as u64,
+            Checksum::XxHash64 => self.read_u64()?,
+        };
+        let actual = checksum.compute(&data);
+        if expected != actual {
+            return Err(BinaryError::ChecksumMismatch { expected, actual });
+        }
+        Ok(data)
+    }
+}
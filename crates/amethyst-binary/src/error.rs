@@ -10,6 +10,8 @@ pub enum BinaryError {
     UnexpectedEOF,
     #[error("Invalid data: {0}")]
     InvalidData(String),
+    #[error("Checksum mismatch: expected {expected:#x}, got {actual:#x}")]
+    ChecksumMismatch { expected: u64, actual: u64 },
 }
 
 pub type Result<T> = std::result::Result<T, BinaryError>;
\ No newline at end of file
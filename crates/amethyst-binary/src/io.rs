@@ -1,6 +1,7 @@
 use crate::error::BinaryError;
 use crate::error::BinaryError::{InvalidData, UnexpectedEOF};
-use crate::traits::{Readable, Writable};
+use crate::traits::{Readable, ReadableRef, Writable};
+use crate::varint;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
@@ -50,6 +51,23 @@ impl BinaryReader {
         self.buffer.remaining()
     }
 
+    /// Fails unless at least `len` bytes remain. Decoders call this with a declared length
+    /// before allocating for it, so a forged length cannot make them reserve more memory than
+    /// the input could fill.
+    #[inline]
+    pub fn ensure_remaining(&self, len: usize) -> Result<(), BinaryError> {
+        if self.remaining() >= len {
+            Ok(())
+        } else {
+            Err(UnexpectedEOF)
+        }
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        self.buffer.chunk()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
@@ -212,20 +230,11 @@ impl BinaryReader {
         self.buffer.split_off(0)
     }
 
+    #[inline]
     pub fn read_var_u32(&mut self) -> Result<u32, BinaryError> {
-        let mut value: u32 = 0;
-        let mut shift: u32 = 0;
-        loop {
-            let byte = self.read_u8()?;
-            value |= ((byte & 0x7F) as u32) << shift;
-            if byte & 0x80 == 0 {
-                return Ok(value);
-            }
-            shift += 7;
-            if shift >= 35 {
-                return Err(InvalidData("VarInt overflow u32".to_string()));
-            }
-        }
+        let (value, length) = varint::decode_u32(self.as_slice())?;
+        self.buffer.advance(length);
+        Ok(value)
     }
 
     pub fn read_var_i32(&mut self) -> Result<i32, BinaryError> {
@@ -233,20 +242,11 @@ impl BinaryReader {
         Ok((unsigned >> 1) as i32 ^ -((unsigned & 1) as i32))
     }
 
+    #[inline]
     pub fn read_var_u64(&mut self) -> Result<u64, BinaryError> {
-        let mut value: u64 = 0;
-        let mut shift: u32 = 0;
-        loop {
-            let byte = self.read_u8()?;
-            value |= ((byte & 0x7F) as u64) << shift;
-            if byte & 0x80 == 0 {
-                return Ok(value);
-            }
-            shift += 7;
-            if shift >= 70 {
-                return Err(InvalidData("VarLong overflow u64".to_string()));
-            }
-        }
+        let (value, length) = varint::decode_u64(self.as_slice())?;
+        self.buffer.advance(length);
+        Ok(value)
     }
 
     pub fn read_var_i64(&mut self) -> Result<i64, BinaryError> {
@@ -257,7 +257,7 @@ impl BinaryReader {
     pub fn read_string(&mut self) -> Result<String, BinaryError> {
         let len = self.read_var_u32()? as usize;
         if len == 0 {
-            Ok::<String, BinaryError>(String::new()).expect("TODO: panic message");
+            return Ok(String::new());
         }
         
         let str_bytes = self.read_bytes(len)?;
@@ -335,6 +335,226 @@ impl BinaryReader {
     pub fn read<T: Readable>(&mut self) -> Result<T, BinaryError> {
         T::read(self)
     }
+
+    /// Decodes a [`ReadableRef`] type in place and advances past the bytes it consumed.
+    pub fn read_borrowed<T: ReadableRef>(&mut self) -> Result<T, BinaryError> {
+        let mut borrowed = BinaryRef::new(self.as_slice());
+        let value = T::read_ref(&mut borrowed)?;
+        let consumed = self.remaining() - borrowed.remaining();
+        self.advance(consumed)?;
+        Ok(value)
+    }
+}
+
+/// A reader that borrows its input instead of owning a `Bytes`.
+///
+/// Used on hot paths (e.g. offline RakNet packets) where the datagram is decoded straight
+/// out of the receive buffer and never needs to outlive it.
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryRef<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> BinaryRef<'a> {
+    #[inline]
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// See [`BinaryReader::ensure_remaining`].
+    #[inline]
+    pub fn ensure_remaining(&self, len: usize) -> Result<(), BinaryError> {
+        if self.remaining() >= len {
+            Ok(())
+        } else {
+            Err(UnexpectedEOF)
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.buffer
+    }
+
+    pub fn advance(&mut self, cnt: usize) -> Result<(), BinaryError> {
+        read_primitive!(self, cnt, advance, void)
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, BinaryError> {
+        self.buffer.first().copied().ok_or(UnexpectedEOF)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, BinaryError> {
+        read_primitive!(self, 1, get_u8)
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8, BinaryError> {
+        read_primitive!(self, 1, get_i8)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, BinaryError> {
+        read_primitive!(self, 2, get_u16)
+    }
+
+    pub fn read_u16_le(&mut self) -> Result<u16, BinaryError> {
+        read_primitive!(self, 2, get_u16_le)
+    }
+
+    pub fn read_i16(&mut self) -> Result<i16, BinaryError> {
+        read_primitive!(self, 2, get_i16)
+    }
+
+    pub fn read_i16_le(&mut self) -> Result<i16, BinaryError> {
+        read_primitive!(self, 2, get_i16_le)
+    }
+
+    pub fn read_u24(&mut self) -> Result<u32, BinaryError> {
+        let bytes = self.read_bytes(3)?;
+        Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    pub fn read_u24_le(&mut self) -> Result<u32, BinaryError> {
+        let bytes = self.read_bytes(3)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, BinaryError> {
+        read_primitive!(self, 4, get_u32)
+    }
+
+    pub fn read_u32_le(&mut self) -> Result<u32, BinaryError> {
+        read_primitive!(self, 4, get_u32_le)
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, BinaryError> {
+        read_primitive!(self, 4, get_i32)
+    }
+
+    pub fn read_i32_le(&mut self) -> Result<i32, BinaryError> {
+        read_primitive!(self, 4, get_i32_le)
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, BinaryError> {
+        read_primitive!(self, 8, get_u64)
+    }
+
+    pub fn read_u64_le(&mut self) -> Result<u64, BinaryError> {
+        read_primitive!(self, 8, get_u64_le)
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, BinaryError> {
+        read_primitive!(self, 8, get_i64)
+    }
+
+    pub fn read_i64_le(&mut self) -> Result<i64, BinaryError> {
+        read_primitive!(self, 8, get_i64_le)
+    }
+
+    pub fn read_f32_le(&mut self) -> Result<f32, BinaryError> {
+        read_primitive!(self, 4, get_f32_le)
+    }
+
+    pub fn read_f64_le(&mut self) -> Result<f64, BinaryError> {
+        read_primitive!(self, 8, get_f64_le)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, BinaryError> {
+        self.read_u8().map(|v| v != 0)
+    }
+
+    pub fn read_exact(&mut self, dst: &mut [u8]) -> Result<(), BinaryError> {
+        dst.copy_from_slice(self.read_bytes(dst.len())?);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        if self.remaining() >= len {
+            let (head, tail) = self.buffer.split_at(len);
+            self.buffer = tail;
+            Ok(head)
+        } else {
+            Err(UnexpectedEOF)
+        }
+    }
+
+    #[inline]
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.buffer)
+    }
+
+    #[inline]
+    pub fn read_var_u32(&mut self) -> Result<u32, BinaryError> {
+        let (value, length) = varint::decode_u32(self.buffer)?;
+        self.buffer = &self.buffer[length..];
+        Ok(value)
+    }
+
+    #[inline]
+    pub fn read_var_u64(&mut self) -> Result<u64, BinaryError> {
+        let (value, length) = varint::decode_u64(self.buffer)?;
+        self.buffer = &self.buffer[length..];
+        Ok(value)
+    }
+
+    pub fn read_str(&mut self) -> Result<&'a str, BinaryError> {
+        let len = self.read_var_u32()? as usize;
+        let str_bytes = self.read_bytes(len)?;
+        std::str::from_utf8(str_bytes)
+            .map_err(|e| InvalidData(format!("Invalid UTF-8 string: {}", e)))
+    }
+
+    pub fn read_str_u16(&mut self) -> Result<&'a str, BinaryError> {
+        let len = self.read_u16()? as usize;
+        let str_bytes = self.read_bytes(len)?;
+        std::str::from_utf8(str_bytes)
+            .map_err(|e| InvalidData(format!("Invalid UTF-8 string: {}", e)))
+    }
+
+    pub fn read_raknet_address(&mut self) -> Result<SocketAddr, BinaryError> {
+        let ip_ver = self.read_u8()?;
+        if ip_ver == 4 {
+            let bytes = self.read_bytes(4)?;
+            let ip = Ipv4Addr::new(!bytes[0], !bytes[1], !bytes[2], !bytes[3]);
+            let port = self.read_u16()?;
+            Ok(SocketAddr::new(IpAddr::V4(ip), port))
+        } else if ip_ver == 6 {
+            if self.remaining() < 28 {
+                return Err(UnexpectedEOF);
+            }
+
+            let _family = self.read_i16_le()?;
+            let port = self.read_u16()?;
+            let flowinfo = self.read_u32()?;
+            let mut addr_buf = [0; 16];
+            self.read_exact(&mut addr_buf)?;
+            let scope_id = self.read_u32()?;
+
+            let ip = Ipv6Addr::from(addr_buf);
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                ip, port, flowinfo, scope_id,
+            )))
+        } else {
+            Err(InvalidData(
+                "Invalid RakNet SocketAddr IP version".to_string(),
+            ))
+        }
+    }
+
+    #[inline]
+    pub fn read<T: ReadableRef>(&mut self) -> Result<T, BinaryError> {
+        T::read_ref(self)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -572,18 +792,17 @@ impl BinaryWriter {
         Ok(())
     }
 
-    pub fn write_var_u32(&mut self, mut value: u32) -> Result<(), BinaryError> {
-        loop {
-            let mut byte = (value & 0x7F) as u8;
-            value >>= 7;
-            if value != 0 {
-                byte |= 0x80;
-            }
-            self.write_u8(byte)?;
-            if value == 0 {
-                return Ok(());
-            }
+    /// Encodes into a stack buffer and appends it in one go, instead of a byte at a time.
+    #[inline]
+    pub fn write_var_u32(&mut self, value: u32) -> Result<(), BinaryError> {
+        if value < 0x80 {
+            self.buffer.put_u8(value as u8);
+            return Ok(());
         }
+        let mut encoded = [0u8; varint::MAX_U32_LENGTH];
+        let length = varint::encode(value as u64, &mut encoded);
+        self.buffer.put_slice(&encoded[..length]);
+        Ok(())
     }
 
     pub fn write_var_i32(&mut self, value: i32) -> Result<(), BinaryError> {
@@ -591,18 +810,16 @@ impl BinaryWriter {
         self.write_var_u32(unsigned as u32)
     }
 
-    pub fn write_var_u64(&mut self, mut value: u64) -> Result<(), BinaryError> {
-        loop {
-            let mut byte = (value & 0x7F) as u8;
-            value >>= 7;
-            if value != 0 {
-                byte |= 0x80;
-            }
-            self.write_u8(byte)?;
-            if value == 0 {
-                return Ok(());
-            }
+    #[inline]
+    pub fn write_var_u64(&mut self, value: u64) -> Result<(), BinaryError> {
+        if value < 0x80 {
+            self.buffer.put_u8(value as u8);
+            return Ok(());
         }
+        let mut encoded = [0u8; varint::MAX_U64_LENGTH];
+        let length = varint::encode(value, &mut encoded);
+        self.buffer.put_slice(&encoded[..length]);
+        Ok(())
     }
 
     pub fn write_var_i64(&mut self, value: i64) -> Result<(), BinaryError> {
@@ -688,6 +905,13 @@ impl From<BinaryWriter> for BinaryReader {
     }
 }
 
+impl<'a> From<&'a [u8]> for BinaryRef<'a> {
+    #[inline]
+    fn from(slice: &'a [u8]) -> Self {
+        Self::new(slice)
+    }
+}
+
 impl From<Vec<u8>> for BinaryWriter {
     #[inline]
     fn from(vec: Vec<u8>) -> Self {
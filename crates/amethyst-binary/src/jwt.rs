@@ -0,0 +1,123 @@
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use p384::ecdsa::Signature;
+use p384::ecdsa::signature::Verifier;
+use p384::pkcs8::DecodePublicKey;
+use serde_json::Value;
+use thiserror::Error;
+
+pub use p384::ecdsa::VerifyingKey;
+
+#[derive(Error, Debug)]
+pub enum JwtError {
+    #[error("Malformed JWT: {0}")]
+    Malformed(String),
+    #[error("Base64 decode error: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported JWT algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("Invalid public key: {0}")]
+    InvalidKey(String),
+    #[error("JWT signature verification failed")]
+    InvalidSignature,
+}
+
+/// Decodes unpadded (or padded) base64url, as used by JWT segments.
+pub fn base64url_decode(input: &str) -> Result<Vec<u8>, JwtError> {
+    Ok(URL_SAFE_NO_PAD.decode(input.trim_end_matches('='))?)
+}
+
+pub fn base64url_encode(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Decodes standard base64, as used by Bedrock for `x5u` and `identityPublicKey` values.
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, JwtError> {
+    Ok(STANDARD.decode(input)?)
+}
+
+/// Parses a base64-encoded DER `SubjectPublicKeyInfo` into a P-384 verifying key.
+pub fn parse_public_key(der_base64: &str) -> Result<VerifyingKey, JwtError> {
+    let der = base64_decode(der_base64)?;
+    VerifyingKey::from_public_key_der(&der).map_err(|e| JwtError::InvalidKey(e.to_string()))
+}
+
+/// A compact-serialized JWT split into its three segments, borrowed from the original token.
+#[derive(Debug, Clone, Copy)]
+pub struct Jwt<'a> {
+    raw: &'a str,
+    header: &'a str,
+    payload: &'a str,
+    signature: &'a str,
+}
+
+impl<'a> Jwt<'a> {
+    pub fn split(token: &'a str) -> Result<Self, JwtError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(JwtError::Malformed(
+                "Expected exactly three '.'-separated segments".to_string(),
+            ));
+        };
+        if header.is_empty() || payload.is_empty() {
+            return Err(JwtError::Malformed(
+                "Header and payload segments must not be empty".to_string(),
+            ));
+        }
+        Ok(Self {
+            raw: token,
+            header,
+            payload,
+            signature,
+        })
+    }
+
+    pub fn header(&self) -> Result<Value, JwtError> {
+        Ok(serde_json::from_slice(&base64url_decode(self.header)?)?)
+    }
+
+    pub fn payload(&self) -> Result<Value, JwtError> {
+        Ok(serde_json::from_slice(&base64url_decode(self.payload)?)?)
+    }
+
+    pub fn signature(&self) -> Result<Vec<u8>, JwtError> {
+        base64url_decode(self.signature)
+    }
+
+    /// The `header.payload` bytes covered by the signature.
+    pub fn signing_input(&self) -> &'a str {
+        &self.raw[..self.header.len() + 1 + self.payload.len()]
+    }
+
+    /// The `x5u` header value, which Bedrock uses to carry the signer's public key.
+    pub fn x5u(&self) -> Result<String, JwtError> {
+        self.header()?
+            .get("x5u")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| JwtError::Malformed("Missing 'x5u' header".to_string()))
+    }
+
+    /// Verifies an ES384 signature, accepting both raw `r || s` and DER encodings.
+    pub fn verify_es384(&self, key: &VerifyingKey) -> Result<(), JwtError> {
+        let alg = self.header()?
+            .get("alg")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if alg != "ES384" {
+            return Err(JwtError::UnsupportedAlgorithm(alg));
+        }
+
+        let raw_signature = self.signature()?;
+        let signature = Signature::from_slice(&raw_signature)
+            .or_else(|_| Signature::from_der(&raw_signature))
+            .map_err(|_| JwtError::InvalidSignature)?;
+        key.verify(self.signing_input().as_bytes(), &signature)
+            .map_err(|_| JwtError::InvalidSignature)
+    }
+}
@@ -1,3 +1,7 @@
+pub mod checksum;
 pub mod error;
 pub mod io;
-pub mod traits;
\ No newline at end of file
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod traits;
+mod varint;
\ No newline at end of file
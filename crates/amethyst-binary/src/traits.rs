@@ -1,5 +1,5 @@
 use crate::error::BinaryError;
-use crate::io::{BinaryReader, BinaryWriter};
+use crate::io::{BinaryReader, BinaryRef, BinaryWriter};
 use std::net::SocketAddr;
 
 /// Trait for types that can be read from a `BinaryReader`.
@@ -8,6 +8,14 @@ pub trait Readable: Sized {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError>;
 }
 
+/// Trait for types that can be read from a borrowed `BinaryRef` without copying the input.
+///
+/// Implementors can also be read from a `BinaryReader` via `BinaryReader::read_borrowed`.
+pub trait ReadableRef: Sized {
+    /// Reads an instance of `Self` from the borrowed reader.
+    fn read_ref(reader: &mut BinaryRef<'_>) -> Result<Self, BinaryError>;
+}
+
 /// Trait for types that can be written to a `BinaryWriter`.
 pub trait Writable {
     /// Writes this instance to the writer.
@@ -114,8 +122,11 @@ impl<T: Writable> Writable for Option<T> {
 
 // Vec<T> (using VarUInt32 for length)
 impl<T: Readable> Readable for Vec<T> {
+    /// Every element takes at least one byte, so a length beyond the remaining bytes is
+    /// rejected before anything is allocated.
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
         let len = reader.read_var_u32()? as usize;
+        reader.ensure_remaining(len)?;
         let mut vec = Vec::with_capacity(len);
         for _ in 0..len {
             vec.push(T::read(reader)?);
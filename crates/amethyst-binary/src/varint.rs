@@ -0,0 +1,88 @@
+//! LEB128 varint encoding and decoding shared by [`BinaryReader`](crate::io::BinaryReader) and
+//! [`BinaryRef`](crate::io::BinaryRef).
+//!
+//! Single-byte varints, the most common kind, take a shortcut. Longer ones are decoded from
+//! a fixed-size chunk whenever enough bytes follow, so the per-byte bounds checks go away.
+
+use crate::error::BinaryError;
+use crate::error::BinaryError::{InvalidData, UnexpectedEOF};
+
+pub(crate) const MAX_U32_LENGTH: usize = 5;
+pub(crate) const MAX_U64_LENGTH: usize = 10;
+
+/// Decodes a varint of at most 5 bytes from the start of `buf`, returning it and its length.
+/// Bits beyond the 32nd are dropped.
+#[inline]
+pub(crate) fn decode_u32(buf: &[u8]) -> Result<(u32, usize), BinaryError> {
+    if let Some(&byte) = buf.first()
+        && byte < 0x80
+    {
+        return Ok((byte as u32, 1));
+    }
+    let decoded = match buf.first_chunk::<MAX_U32_LENGTH>() {
+        Some(chunk) => decode_chunk(chunk),
+        None => decode_slow(buf, MAX_U32_LENGTH)?,
+    };
+    let (value, length) = decoded.ok_or_else(|| InvalidData("VarInt overflow u32".to_string()))?;
+    Ok((value as u32, length))
+}
+
+/// Decodes a varint of at most 10 bytes from the start of `buf`, returning it and its
+/// length. Bits beyond the 64th are dropped.
+#[inline]
+pub(crate) fn decode_u64(buf: &[u8]) -> Result<(u64, usize), BinaryError> {
+    if let Some(&byte) = buf.first()
+        && byte < 0x80
+    {
+        return Ok((byte as u64, 1));
+    }
+    let decoded = match buf.first_chunk::<MAX_U64_LENGTH>() {
+        Some(chunk) => decode_chunk(chunk),
+        None => decode_slow(buf, MAX_U64_LENGTH)?,
+    };
+    decoded.ok_or_else(|| InvalidData("VarLong overflow u64".to_string()))
+}
+
+/// Decodes the varint at the start of `chunk`, or returns `None` if it does not end within
+/// it. The length is known at compile time, so the loop is unrolled without bounds checks.
+#[inline(always)]
+fn decode_chunk<const N: usize>(chunk: &[u8; N]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, &byte) in chunk.iter().enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte < 0x80 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// For varints near the end of the buffer, which may be cut off.
+fn decode_slow(buf: &[u8], max_length: usize) -> Result<Option<(u64, usize)>, BinaryError> {
+    let mut value: u64 = 0;
+    for (i, &byte) in buf.iter().take(max_length).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte < 0x80 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    if buf.len() >= max_length {
+        Ok(None)
+    } else {
+        Err(UnexpectedEOF)
+    }
+}
+
+/// Encodes `value` into the start of `buf`, which must have room for it, and returns its
+/// length.
+#[inline]
+pub(crate) fn encode(mut value: u64, buf: &mut [u8]) -> usize {
+    let mut length = 0;
+    while value >= 0x80 {
+        buf[length] = value as u8 | 0x80;
+        value >>= 7;
+        length += 1;
+    }
+    buf[length] = value as u8;
+    length + 1
+}
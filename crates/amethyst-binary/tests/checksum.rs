@@ -0,0 +1,82 @@
+use amethyst_binary::checksum::{crc32, xxhash64, Checksum};
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+
+const CHECKSUMS: [Checksum; 2] = [Checksum::Crc32, Checksum::XxHash64];
+
+fn checksummed(checksum: Checksum, data: &[u8]) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(0xAA).unwrap();
+    writer
+        .write_checksummed(checksum, |region| region.write_bytes(data))
+        .unwrap();
+    writer.write_u8(0xBB).unwrap();
+    writer.freeze().to_vec()
+}
+
+#[test]
+fn matches_known_vectors() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(Checksum::Crc32.compute(b"123456789"), 0xCBF4_3926);
+    assert_eq!(xxhash64(b"", 0), 0xEF46_DB37_51D8_E999);
+    assert_eq!(Checksum::XxHash64.compute(b""), 0xEF46_DB37_51D8_E999);
+}
+
+#[test]
+fn regions_round_trip() {
+    for checksum in CHECKSUMS {
+        for data in [&b""[..], b"123456789", &[0xFF; 300]] {
+            let bytes = checksummed(checksum, data);
+            // Marker, length, data, checksum, marker.
+            let len_size = if data.len() < 128 { 1 } else { 2 };
+            assert_eq!(bytes.len(), 2 + len_size + data.len() + checksum.size());
+
+            let mut reader = BinaryReader::from(bytes);
+            assert_eq!(reader.read_u8().unwrap(), 0xAA);
+            assert_eq!(reader.read_checksummed(checksum).unwrap(), data);
+            assert_eq!(reader.read_u8().unwrap(), 0xBB);
+            assert_eq!(reader.remaining(), 0);
+        }
+    }
+}
+
+#[test]
+fn the_checksum_is_written_big_endian() {
+    let bytes = checksummed(Checksum::Crc32, b"123456789");
+    assert_eq!(bytes[11..15], [0xCB, 0xF4, 0x39, 0x26]);
+}
+
+#[test]
+fn a_corrupted_byte_is_a_mismatch() {
+    for checksum in CHECKSUMS {
+        let clean = checksummed(checksum, b"123456789");
+        // Every byte of the data and of the checksum itself.
+        for index in 2..clean.len() - 1 {
+            let mut bytes = clean.clone();
+            bytes[index] ^= 0x01;
+            let mut reader = BinaryReader::from(bytes);
+            reader.read_u8().unwrap();
+            let result = reader.read_checksummed(checksum);
+            assert!(
+                matches!(result, Err(BinaryError::ChecksumMismatch { .. })),
+                "{:?}: flipping byte {} gave {:?}",
+                checksum,
+                index,
+                result
+            );
+        }
+    }
+}
+
+#[test]
+fn the_wrong_algorithm_is_a_mismatch() {
+    let mut bytes = checksummed(Checksum::XxHash64, b"123456789");
+    // Drop the marker so the region starts the input, then read it as CRC-32.
+    bytes.remove(0);
+    let result = BinaryReader::from(bytes).read_checksummed(Checksum::Crc32);
+    let Err(BinaryError::ChecksumMismatch { expected, actual }) = result else {
+        panic!("{:?}", result);
+    };
+    assert_eq!(actual, 0xCBF4_3926);
+    assert_ne!(expected, actual);
+}
@@ -0,0 +1,128 @@
+use amethyst_binary::jwt::{base64url_encode, parse_public_key, Jwt, JwtError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey};
+use p384::pkcs8::EncodePublicKey;
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::from_slice(&[seed; 48]).unwrap()
+}
+
+fn public(key: &SigningKey) -> String {
+    STANDARD.encode(key.verifying_key().to_public_key_der().unwrap().as_bytes())
+}
+
+fn signing_input(header: &str, payload: &str) -> String {
+    format!(
+        "{}.{}",
+        base64url_encode(header.as_bytes()),
+        base64url_encode(payload.as_bytes())
+    )
+}
+
+/// A token signed by `key` with a raw `r || s` signature, as Bedrock clients send them.
+fn token(key: &SigningKey, header: &str, payload: &str) -> String {
+    let input = signing_input(header, payload);
+    let signature: Signature = key.sign(input.as_bytes());
+    format!("{}.{}", input, base64url_encode(&signature.to_bytes()))
+}
+
+const HEADER: &str = r#"{"alg":"ES384","x5u":"key"}"#;
+const PAYLOAD: &str = r#"{"displayName":"Steve"}"#;
+
+#[test]
+fn accepts_a_good_raw_signature() {
+    let key = key(1);
+    let token = token(&key, HEADER, PAYLOAD);
+    let jwt = Jwt::split(&token).unwrap();
+    jwt.verify_es384(key.verifying_key()).unwrap();
+    assert_eq!(jwt.payload().unwrap()["displayName"], "Steve");
+    assert_eq!(jwt.x5u().unwrap(), "key");
+}
+
+#[test]
+fn accepts_a_der_signature() {
+    let key = key(1);
+    let input = signing_input(HEADER, PAYLOAD);
+    let signature: Signature = key.sign(input.as_bytes());
+    let token = format!("{}.{}", input, base64url_encode(signature.to_der().as_bytes()));
+    Jwt::split(&token)
+        .unwrap()
+        .verify_es384(key.verifying_key())
+        .unwrap();
+}
+
+#[test]
+fn rejects_a_tampered_payload() {
+    let key = key(1);
+    let token = token(&key, HEADER, PAYLOAD);
+    let signature = token.rsplit('.').next().unwrap();
+    let forged = format!(
+        "{}.{}",
+        signing_input(HEADER, r#"{"displayName":"Alex"}"#),
+        signature
+    );
+    let result = Jwt::split(&forged).unwrap().verify_es384(key.verifying_key());
+    assert!(matches!(result, Err(JwtError::InvalidSignature)));
+}
+
+#[test]
+fn rejects_another_key() {
+    let token = token(&key(1), HEADER, PAYLOAD);
+    let result = Jwt::split(&token).unwrap().verify_es384(key(2).verifying_key());
+    assert!(matches!(result, Err(JwtError::InvalidSignature)));
+}
+
+#[test]
+fn rejects_other_algorithms() {
+    let key = key(1);
+    for header in [r#"{"alg":"none"}"#, r#"{"alg":"ES256"}"#, "{}"] {
+        let token = token(&key, header, PAYLOAD);
+        let result = Jwt::split(&token).unwrap().verify_es384(key.verifying_key());
+        assert!(
+            matches!(result, Err(JwtError::UnsupportedAlgorithm(_))),
+            "{} was accepted",
+            header
+        );
+    }
+}
+
+#[test]
+fn rejects_a_garbled_signature() {
+    let key = key(1);
+    let token = format!("{}.{}", signing_input(HEADER, PAYLOAD), base64url_encode(&[7; 20]));
+    let result = Jwt::split(&token).unwrap().verify_es384(key.verifying_key());
+    assert!(matches!(result, Err(JwtError::InvalidSignature)));
+}
+
+#[test]
+fn rejects_malformed_segments() {
+    for token in ["", "a.b", "a.b.c.d", ".b.c", "a..c"] {
+        assert!(
+            matches!(Jwt::split(token), Err(JwtError::Malformed(_))),
+            "'{}' was accepted",
+            token
+        );
+    }
+    let jwt = Jwt::split("!!!.e30.").unwrap();
+    assert!(matches!(jwt.header(), Err(JwtError::Base64(_))));
+    let jwt = Jwt::split("e30.bm90IGpzb24.").unwrap();
+    assert!(matches!(jwt.payload(), Err(JwtError::Json(_))));
+    let jwt = Jwt::split("e30.e30.").unwrap();
+    assert!(matches!(jwt.x5u(), Err(JwtError::Malformed(_))));
+}
+
+#[test]
+fn parses_der_public_keys() {
+    let key = key(1);
+    assert_eq!(&parse_public_key(&public(&key)).unwrap(), key.verifying_key());
+    assert!(matches!(
+        parse_public_key("not base64!"),
+        Err(JwtError::Base64(_))
+    ));
+    assert!(matches!(
+        parse_public_key(&STANDARD.encode([1, 2, 3])),
+        Err(JwtError::InvalidKey(_))
+    ));
+}
@@ -0,0 +1,51 @@
+use amethyst_binary::checksum::Checksum;
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::Readable;
+use bytes::Bytes;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// A declared length followed by far fewer bytes than it claims.
+fn forged_length(len: u32) -> BinaryReader {
+    let mut writer = BinaryWriter::new();
+    writer.write_var_u32(len).unwrap();
+    writer.write_bytes(&[0; 8]).unwrap();
+    BinaryReader::from(writer)
+}
+
+#[test]
+fn vec_length_beyond_input_is_rejected_before_allocating() {
+    // Reserving u32::MAX u64s up front would abort the process.
+    let result = Vec::<u64>::read(&mut forged_length(u32::MAX));
+    assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+    let result = Vec::<u8>::read(&mut forged_length(9));
+    assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+    assert_eq!(Vec::<u8>::read(&mut forged_length(8)).unwrap(), [0; 8]);
+}
+
+#[test]
+fn string_length_beyond_input_is_rejected() {
+    let result = forged_length(u32::MAX).read_string();
+    assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+}
+
+#[test]
+fn checksummed_length_beyond_input_is_rejected() {
+    for checksum in [Checksum::Crc32, Checksum::XxHash64] {
+        let result = forged_length(u32::MAX).read_checksummed(checksum);
+        assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+    }
+}
+
+proptest! {
+    /// Arbitrary input may fail to decode, but must never panic or over-allocate.
+    #[test]
+    fn arbitrary_input_does_not_panic(data in vec(any::<u8>(), 0..64)) {
+        let data = Bytes::from(data);
+        let _ = Vec::<u64>::read(&mut BinaryReader::new(data.clone()));
+        let _ = Vec::<Vec<String>>::read(&mut BinaryReader::new(data.clone()));
+        let _ = BinaryReader::new(data.clone()).read_checksummed(Checksum::Crc32);
+        let _ = BinaryReader::new(data).read_socket_addr();
+    }
+}
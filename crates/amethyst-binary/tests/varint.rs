@@ -0,0 +1,103 @@
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryRef, BinaryWriter};
+use bytes::Bytes;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// Byte-at-a-time decoding, which the unrolled decoder must agree with.
+fn reference(data: &[u8], max_length: usize) -> Result<(u64, usize), ()> {
+    let mut value: u64 = 0;
+    for i in 0..max_length {
+        let byte = *data.get(i).ok_or(())?;
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(())
+}
+
+/// Arbitrary bytes biased towards continuation bits, so long and overlong varints come up.
+fn varint_bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(prop_oneof![0x80u8..=0xff, any::<u8>()], 0..20)
+}
+
+proptest! {
+    #[test]
+    fn var_u32_roundtrip(value: u32, trailing in vec(any::<u8>(), 0..12)) {
+        let mut writer = BinaryWriter::new();
+        writer.write_var_u32(value).unwrap();
+        let length = writer.len();
+        writer.write_bytes(&trailing).unwrap();
+        let encoded = writer.freeze();
+
+        let mut reader = BinaryReader::new(encoded.clone());
+        prop_assert_eq!(reader.read_var_u32().unwrap(), value);
+        prop_assert_eq!(reader.remaining(), trailing.len());
+
+        let mut reader = BinaryRef::new(&encoded);
+        prop_assert_eq!(reader.read_var_u32().unwrap(), value);
+        prop_assert_eq!(encoded.len() - reader.remaining(), length);
+    }
+
+    #[test]
+    fn var_u64_roundtrip(value: u64, trailing in vec(any::<u8>(), 0..20)) {
+        let mut writer = BinaryWriter::new();
+        writer.write_var_u64(value).unwrap();
+        writer.write_bytes(&trailing).unwrap();
+        let encoded = writer.freeze();
+
+        let mut reader = BinaryReader::new(encoded.clone());
+        prop_assert_eq!(reader.read_var_u64().unwrap(), value);
+        prop_assert_eq!(reader.remaining(), trailing.len());
+
+        let mut reader = BinaryRef::new(&encoded);
+        prop_assert_eq!(reader.read_var_u64().unwrap(), value);
+        prop_assert_eq!(reader.remaining(), trailing.len());
+    }
+
+    #[test]
+    fn var_u32_matches_reference(data in varint_bytes()) {
+        let mut reader = BinaryReader::new(Bytes::from(data.clone()));
+        match reference(&data, 5) {
+            Ok((value, length)) => {
+                prop_assert_eq!(reader.read_var_u32().unwrap(), value as u32);
+                prop_assert_eq!(reader.remaining(), data.len() - length);
+            }
+            Err(()) => prop_assert!(reader.read_var_u32().is_err()),
+        }
+    }
+
+    #[test]
+    fn var_u64_matches_reference(data in varint_bytes()) {
+        let mut reader = BinaryRef::new(&data);
+        match reference(&data, 10) {
+            Ok((value, length)) => {
+                prop_assert_eq!(reader.read_var_u64().unwrap(), value);
+                prop_assert_eq!(reader.remaining(), data.len() - length);
+            }
+            Err(()) => prop_assert!(reader.read_var_u64().is_err()),
+        }
+    }
+}
+
+#[test]
+fn overlong_and_truncated_varints() {
+    let overlong = [0xff; 16];
+    assert!(matches!(
+        BinaryRef::new(&overlong).read_var_u32(),
+        Err(BinaryError::InvalidData(_))
+    ));
+    assert!(matches!(
+        BinaryRef::new(&overlong).read_var_u64(),
+        Err(BinaryError::InvalidData(_))
+    ));
+    assert!(matches!(
+        BinaryRef::new(&overlong[..3]).read_var_u32(),
+        Err(BinaryError::UnexpectedEOF)
+    ));
+    assert!(matches!(
+        BinaryRef::new(&[]).read_var_u64(),
+        Err(BinaryError::UnexpectedEOF)
+    ));
+}
@@ -0,0 +1,15 @@
+use amethyst_codegen::Generator;
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=protocol");
+    let output = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("protocol.rs");
+    let result = Generator::new()
+        .with_packet_trait("crate::game::GamePacket")
+        .load(Path::new("protocol"))
+        .and_then(|generator| generator.write(&output));
+    if let Err(e) = result {
+        panic!("{}", e);
+    }
+}
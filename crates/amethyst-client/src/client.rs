@@ -0,0 +1,302 @@
+//! The connection to a server: the RakNet handshake, frames in both directions and the game
+//! packets on top.
+
+use crate::game::{self, GamePacket, NetworkSettings, RequestNetworkSettings, GAME_PACKET};
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout_at, Instant};
+
+/// MTU the client asks for, the same as the Bedrock client on most networks.
+const MTU: u16 = 1400;
+/// IP and UDP headers, which the first request's padding leaves room for.
+const UDP_HEADER_SIZE: usize = 28;
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+/// A step of joining a server, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// `OPEN_CONNECTION_REQUEST_1` and `_2`, which agree on the MTU.
+    OpenConnection,
+    /// `CONNECTION_REQUEST`, after which the RakNet connection is up.
+    ConnectionRequest,
+    /// A connected ping, answered with a pong.
+    Ping,
+    /// `RequestNetworkSettings`, the first game packet.
+    NetworkSettings,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::OpenConnection => "open connection",
+            Stage::ConnectionRequest => "connection request",
+            Stage::Ping => "connected ping",
+            Stage::NetworkSettings => "network settings",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("no reply from the server to the {0}")]
+    Timeout(Stage),
+    #[error("malformed reply to the {0}: {1}")]
+    Malformed(Stage, BinaryError),
+}
+
+pub struct Client {
+    socket: UdpSocket,
+    guid: u64,
+    mtu: u16,
+    reply_timeout: Duration,
+    /// When the client was created. Pings carry the time since then.
+    epoch: Instant,
+    next_sequence_number: SeqNum,
+    next_reliable_index: SeqNum,
+    next_ordering_index: SeqNum,
+}
+
+impl Client {
+    /// Opens a RakNet connection to `target`, waiting at most `reply_timeout` for each reply.
+    pub async fn connect(target: SocketAddr, reply_timeout: Duration) -> Result<Self, ClientError> {
+        let bind_address = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(bind_address).await?;
+        socket.connect(target).await?;
+        let mut client = Client {
+            socket,
+            guid: rand::random(),
+            mtu: MTU,
+            reply_timeout,
+            epoch: Instant::now(),
+            next_sequence_number: SeqNum::ZERO,
+            next_reliable_index: SeqNum::ZERO,
+            next_ordering_index: SeqNum::ZERO,
+        };
+        client.handshake(target).await?;
+        Ok(client)
+    }
+
+    pub fn guid(&self) -> u64 {
+        self.guid
+    }
+
+    /// The MTU the server agreed to.
+    pub fn mtu(&self) -> u16 {
+        self.mtu
+    }
+
+    async fn handshake(&mut self, target: SocketAddr) -> Result<(), ClientError> {
+        let request = OpenConnectionRequest1 {
+            protocol_version: RAKNET_PROTOCOL_VERSION,
+        };
+        let mut data = encode(OPEN_CONNECTION_REQUEST_1, &request).to_vec();
+        data.resize(MTU as usize - UDP_HEADER_SIZE, 0);
+        self.socket.send(&data).await?;
+        let reply: OpenConnectionReply1 = self
+            .expect(OPEN_CONNECTION_REPLY_1, Stage::OpenConnection)
+            .await?;
+
+        let request = OpenConnectionRequest2 {
+            cookie: reply.cookie,
+            server_addr: target,
+            mtu: reply.mtu_size,
+            client_guid: self.guid,
+        };
+        self.socket
+            .send(&encode(OPEN_CONNECTION_REQUEST_2, &request))
+            .await?;
+        let reply: OpenConnectionReply2 = self
+            .expect(OPEN_CONNECTION_REPLY_2, Stage::OpenConnection)
+            .await?;
+        self.mtu = reply.mtu;
+
+        let request = ConnectionRequest {
+            client_guid: self.guid,
+            time: self.now_millis(),
+            use_security: false,
+        };
+        self.socket
+            .send(&encode(CONNECTION_REQUEST, &request))
+            .await?;
+        let _: ConnectionRequestAccepted = self
+            .expect(CONNECTION_REQUEST_ACCEPTED, Stage::ConnectionRequest)
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for the datagram with packet `id`, skipping anything else the server sends.
+    async fn expect<T: Readable>(&self, id: u8, stage: Stage) -> Result<T, ClientError> {
+        let deadline = Instant::now() + self.reply_timeout;
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let len = timeout_at(deadline, self.socket.recv(&mut buf))
+                .await
+                .map_err(|_| ClientError::Timeout(stage))??;
+            if len > 0 && buf[0] == id {
+                return decode(&buf[1..len]).map_err(|e| ClientError::Malformed(stage, e));
+            }
+        }
+    }
+
+    /// Waits for a frame that `accept` returns something for, acknowledging every frame set
+    /// that arrives meanwhile.
+    async fn expect_frame<T>(
+        &mut self,
+        stage: Stage,
+        mut accept: impl FnMut(Bytes) -> Result<Option<T>, BinaryError>,
+    ) -> Result<T, ClientError> {
+        let deadline = Instant::now() + self.reply_timeout;
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let len = timeout_at(deadline, self.socket.recv(&mut buf))
+                .await
+                .map_err(|_| ClientError::Timeout(stage))??;
+            if len == 0 || !(0x80..=0x8f).contains(&buf[0]) {
+                continue;
+            }
+            let frame_set: FrameSetPacket =
+                decode(&buf[1..len]).map_err(|e| ClientError::Malformed(stage, e))?;
+            self.acknowledge(frame_set.sequence_number).await?;
+            for frame in frame_set.packets {
+                if frame.is_split {
+                    continue;
+                }
+                if let Some(found) =
+                    accept(frame.payload).map_err(|e| ClientError::Malformed(stage, e))?
+                {
+                    return Ok(found);
+                }
+            }
+        }
+    }
+
+    async fn acknowledge(&self, sequence_number: SeqNum) -> Result<(), ClientError> {
+        let ack = AckNackPacket {
+            records: vec![AckNackRecord::Single(sequence_number)],
+        };
+        self.socket.send(&encode(ACK, &ack)).await?;
+        Ok(())
+    }
+
+    /// Sends a connected ping and returns the round trip time.
+    pub async fn ping(&mut self) -> Result<Duration, ClientError> {
+        let sent = Instant::now();
+        let time = self.now_millis();
+        self.send_frame(
+            encode(CONNECTED_PING, &ConnectedPing { time }),
+            Reliability::Unreliable,
+        )
+        .await?;
+        self.expect_frame(Stage::Ping, |payload| {
+            if payload.first() != Some(&CONNECTED_PONG) {
+                return Ok(None);
+            }
+            let pong: ConnectedPong = decode(&payload[1..])?;
+            Ok((pong.ping_time == time).then_some(()))
+        })
+        .await?;
+        Ok(sent.elapsed())
+    }
+
+    /// Sends `RequestNetworkSettings` for `protocol_version` and waits for the answer.
+    pub async fn request_network_settings(
+        &mut self,
+        protocol_version: u32,
+    ) -> Result<NetworkSettings, ClientError> {
+        self.send_game_packet(&RequestNetworkSettings { protocol_version })
+            .await?;
+        self.expect_frame(Stage::NetworkSettings, |payload| {
+            if payload.first() != Some(&GAME_PACKET) {
+                return Ok(None);
+            }
+            for packet in game::decode_batch(payload.slice(1..))? {
+                if let Some(settings) = game::decode::<NetworkSettings>(packet)? {
+                    return Ok(Some(settings));
+                }
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    /// Sends `packet` in a batch of its own, reliably and in order like the game client.
+    pub async fn send_game_packet<P: GamePacket>(&mut self, packet: &P) -> Result<(), ClientError> {
+        let batch = game::encode(packet).and_then(|packet| game::encode_batch(&[packet]));
+        let batch = batch.expect("game packets always encode");
+        self.send_frame(batch, Reliability::ReliableOrdered).await
+    }
+
+    /// Tells the server the client is leaving.
+    pub async fn disconnect(mut self) -> Result<(), ClientError> {
+        // The server does not acknowledge reliable frames yet, so this is sent unreliably.
+        let notification = Bytes::from_static(&[DISCONNECTION_NOTIFICATION]);
+        self.send_frame(notification, Reliability::Unreliable).await
+    }
+
+    /// Sends `payload` in a frame set of its own, on ordering channel 0 if ordered.
+    async fn send_frame(
+        &mut self,
+        payload: Bytes,
+        reliability: Reliability,
+    ) -> Result<(), ClientError> {
+        let mut frame = EncapsulatedPacket {
+            reliability,
+            is_split: false,
+            sequence_number: None,
+            ordering_index: None,
+            ordering_channel: None,
+            split_count: None,
+            split_id: None,
+            split_index: None,
+            payload,
+        };
+        if reliability.is_reliable() {
+            frame.sequence_number = Some(self.next_reliable_index);
+            self.next_reliable_index = self.next_reliable_index.next();
+        }
+        if reliability.is_sequenced() {
+            frame.ordering_index = Some(self.next_ordering_index);
+            frame.ordering_channel = Some(0);
+            self.next_ordering_index = self.next_ordering_index.next();
+        }
+        let frame_set = FrameSetPacket {
+            sequence_number: self.next_sequence_number,
+            packets: vec![frame],
+        };
+        self.next_sequence_number = self.next_sequence_number.next();
+        self.socket.send(&encode(FRAME_SET, &frame_set)).await?;
+        Ok(())
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+}
+
+fn encode(id: u8, packet: &impl Writable) -> Bytes {
+    let mut writer = BinaryWriter::new();
+    writer
+        .write_u8(id)
+        .and_then(|()| packet.write(&mut writer))
+        .expect("packets always encode");
+    writer.freeze()
+}
+
+fn decode<T: Readable>(data: &[u8]) -> Result<T, BinaryError> {
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(data)))
+}
@@ -0,0 +1,66 @@
+//! The game packets the client sends and understands, and the batches they travel in.
+//!
+//! Until `NetworkSettings` enables compression, a batch is the `0xfe` ID followed by each
+//! packet with a varint length in front.
+//!
+//! The packets themselves are generated from the definitions in `protocol/` by the build
+//! script.
+
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+
+/// RakNet packet ID of a batch of game packets.
+pub const GAME_PACKET: u8 = 0xfe;
+
+/// The packet ID takes the low 10 bits of the header, the sub-client IDs the rest.
+const PACKET_ID_MASK: u32 = 0x3ff;
+
+include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+
+pub use v662::{NetworkSettings, RequestNetworkSettings};
+
+pub trait GamePacket: Readable + Writable {
+    const ID: u32;
+}
+
+/// Encodes `packet` with its header, as sent by the main client.
+pub fn encode<P: GamePacket>(packet: &P) -> Result<Bytes, BinaryError> {
+    let mut writer = BinaryWriter::new();
+    writer.write_var_u32(P::ID)?;
+    packet.write(&mut writer)?;
+    Ok(writer.freeze())
+}
+
+/// Decodes `data` as a `P`, or returns `None` if it is another packet.
+pub fn decode<P: GamePacket>(data: Bytes) -> Result<Option<P>, BinaryError> {
+    let mut reader = BinaryReader::new(data);
+    if reader.read_var_u32()? & PACKET_ID_MASK != P::ID {
+        return Ok(None);
+    }
+    P::read(&mut reader).map(Some)
+}
+
+/// Wraps encoded packets in an uncompressed batch.
+pub fn encode_batch(packets: &[Bytes]) -> Result<Bytes, BinaryError> {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(GAME_PACKET)?;
+    for packet in packets {
+        writer.write_var_u32(packet.len() as u32)?;
+        writer.write_bytes(packet)?;
+    }
+    Ok(writer.freeze())
+}
+
+/// Splits an uncompressed batch, starting after its ID, into its packets.
+pub fn decode_batch(data: Bytes) -> Result<Vec<Bytes>, BinaryError> {
+    let mut reader = BinaryReader::new(data);
+    let mut packets = Vec::new();
+    while reader.remaining() > 0 {
+        let len = reader.read_var_u32()? as usize;
+        reader.ensure_remaining(len)?;
+        packets.push(reader.read_bytes(len)?);
+    }
+    Ok(packets)
+}
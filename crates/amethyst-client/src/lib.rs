@@ -0,0 +1,12 @@
+//! A headless Bedrock client, for smoke tests against a running server and as the base of
+//! bots.
+//!
+//! The client gets as far as the server lets it. Amethyst answers the RakNet connection and
+//! connected pings, but has no reliability layer or game packets yet, so
+//! [`Client::request_network_settings`] times out against it. Login, compression and
+//! encryption come after `NetworkSettings` and are added once the server gets there.
+
+pub mod client;
+pub mod game;
+
+pub use client::{Client, ClientError, Stage};
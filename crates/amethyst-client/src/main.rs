@@ -0,0 +1,89 @@
+use amethyst_client::Client;
+use clap::{Parser, ValueEnum};
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Headless Bedrock client. Joins a server as far as the given stage and exits non-zero if it
+/// cannot, for smoke tests in CI.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Server to connect to.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:19132")]
+    target: String,
+
+    /// How far to get before disconnecting.
+    #[arg(long, value_enum, default_value_t = Until::Raknet)]
+    until: Until,
+
+    /// Seconds to wait for each reply from the server.
+    #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: u64,
+
+    /// Game protocol version to announce.
+    #[arg(long, default_value_t = rakethyst::motd::PROTOCOL_VERSION)]
+    protocol: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Until {
+    /// The RakNet connection and a connected ping.
+    Raknet,
+    /// `NetworkSettings` in reply to `RequestNetworkSettings`.
+    NetworkSettings,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let target = match tokio::net::lookup_host(&cli.target)
+        .await
+        .map(|mut a| a.next())
+    {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            eprintln!("{} did not resolve to any address", cli.target);
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("Failed to resolve {}: {}", cli.target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut client = match Client::connect(target, Duration::from_secs(cli.timeout)).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to connect to {}: {}", target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("Connected to {} with MTU {}", target, client.mtu());
+
+    match client.ping().await {
+        Ok(rtt) => println!("Ping: {} ms", rtt.as_millis()),
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    if cli.until == Until::NetworkSettings {
+        match client.request_network_settings(cli.protocol).await {
+            Ok(settings) => println!(
+                "Network settings: compression threshold {}, algorithm {}",
+                settings.compression_threshold, settings.compression_algorithm
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Err(e) = client.disconnect().await {
+        eprintln!("Failed to disconnect: {}", e);
+    }
+    status
+}
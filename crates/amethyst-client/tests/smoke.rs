@@ -0,0 +1,68 @@
+//! Runs the client against a listener on an ephemeral port, the way CI smoke tests run it
+//! against a server.
+
+use amethyst_client::{Client, ClientError, Stage};
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::{Motd, PROTOCOL_VERSION};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn start_server() -> (SocketAddr, JoinHandle<()>) {
+    rakethyst::utils::init_time();
+    let motd = Motd {
+        motd: "Amethyst".to_string(),
+        world_name: "World".to_string(),
+        game_mode: "Survival".to_string(),
+        max_players: 10,
+    };
+    let server_info = Arc::new(ServerInfo::new(1, motd));
+    let listener = RakNetListener::bind("127.0.0.1:0", server_info)
+        .await
+        .expect("failed to bind the listener");
+    let address = listener.local_addr().unwrap();
+    let task = tokio::spawn(async move {
+        let _ = listener.run().await;
+    });
+    (address, task)
+}
+
+#[tokio::test]
+async fn connects_and_pings() {
+    let (address, server) = start_server().await;
+    let mut client = Client::connect(address, REPLY_TIMEOUT).await.unwrap();
+    assert_eq!(client.mtu(), 1400);
+    client.ping().await.unwrap();
+    client.ping().await.unwrap();
+    client.disconnect().await.unwrap();
+    server.abort();
+}
+
+#[tokio::test]
+async fn network_settings_are_not_answered_yet() {
+    let (address, server) = start_server().await;
+    let mut client = Client::connect(address, Duration::from_millis(200))
+        .await
+        .unwrap();
+    let result = client.request_network_settings(PROTOCOL_VERSION).await;
+    assert!(
+        matches!(result, Err(ClientError::Timeout(Stage::NetworkSettings))),
+        "{:?}",
+        result
+    );
+    server.abort();
+}
+
+#[tokio::test]
+async fn connecting_to_nothing_times_out() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    let result = Client::connect(address, Duration::from_millis(100)).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::Timeout(Stage::OpenConnection))
+    ));
+}
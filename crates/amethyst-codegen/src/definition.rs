@@ -0,0 +1,203 @@
+//! The protocol definition format, deserialized from JSON.
+
+use crate::error::CodegenError;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The packets of one protocol version.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtocolDefinition {
+    pub version: u32,
+    pub minecraft_version: String,
+    pub packets: Vec<PacketDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PacketDefinition {
+    /// Struct name, in `UpperCamelCase`.
+    pub name: String,
+    pub id: u32,
+    /// Doc comment for the struct.
+    #[serde(default)]
+    pub doc: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<FieldDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldDefinition {
+    /// Field name, in `snake_case`.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: FieldType,
+    #[serde(default)]
+    pub doc: Option<String>,
+}
+
+/// A field's wire type: a primitive name such as `"u16_le"`, `{"array": <type>, "count":
+/// <primitive>}` for a list with a length prefix, or `{"optional": <type>}` for a value with
+/// a `bool` in front saying whether it is there.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FieldType {
+    Primitive(Primitive),
+    Array {
+        array: Box<FieldType>,
+        count: Primitive,
+    },
+    Optional {
+        optional: Box<FieldType>,
+    },
+}
+
+/// A type `amethyst-binary` reads and writes directly. The names follow its `read_*` and
+/// `write_*` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Primitive {
+    Bool,
+    U8,
+    I8,
+    U16,
+    U16Le,
+    I16,
+    I16Le,
+    U32,
+    U32Le,
+    I32,
+    I32Le,
+    U64,
+    U64Le,
+    I64,
+    I64Le,
+    F32,
+    F32Le,
+    F64,
+    F64Le,
+    VarU32,
+    VarI32,
+    VarU64,
+    VarI64,
+    /// UTF-8 with a `var_u32` length.
+    String,
+    /// Raw bytes with a `var_u32` length.
+    Bytes,
+    /// Everything left in the packet. Only allowed as the last field.
+    Remaining,
+}
+
+impl Primitive {
+    /// The Rust type the field is generated as.
+    pub fn rust_type(self) -> &'static str {
+        match self {
+            Primitive::Bool => "bool",
+            Primitive::U8 => "u8",
+            Primitive::I8 => "i8",
+            Primitive::U16 | Primitive::U16Le => "u16",
+            Primitive::I16 | Primitive::I16Le => "i16",
+            Primitive::U32 | Primitive::U32Le | Primitive::VarU32 => "u32",
+            Primitive::I32 | Primitive::I32Le | Primitive::VarI32 => "i32",
+            Primitive::U64 | Primitive::U64Le | Primitive::VarU64 => "u64",
+            Primitive::I64 | Primitive::I64Le | Primitive::VarI64 => "i64",
+            Primitive::F32 | Primitive::F32Le => "f32",
+            Primitive::F64 | Primitive::F64Le => "f64",
+            Primitive::String => "String",
+            Primitive::Bytes | Primitive::Remaining => "Bytes",
+        }
+    }
+
+    /// The suffix of the reader and writer methods, e.g. `u16_le` for `read_u16_le`.
+    pub fn method_suffix(self) -> &'static str {
+        match self {
+            Primitive::Bool => "bool",
+            Primitive::U8 => "u8",
+            Primitive::I8 => "i8",
+            Primitive::U16 => "u16",
+            Primitive::U16Le => "u16_le",
+            Primitive::I16 => "i16",
+            Primitive::I16Le => "i16_le",
+            Primitive::U32 => "u32",
+            Primitive::U32Le => "u32_le",
+            Primitive::I32 => "i32",
+            Primitive::I32Le => "i32_le",
+            Primitive::U64 => "u64",
+            Primitive::U64Le => "u64_le",
+            Primitive::I64 => "i64",
+            Primitive::I64Le => "i64_le",
+            Primitive::F32 => "f32",
+            Primitive::F32Le => "f32_le",
+            Primitive::F64 => "f64",
+            Primitive::F64Le => "f64_le",
+            Primitive::VarU32 => "var_u32",
+            Primitive::VarI32 => "var_i32",
+            Primitive::VarU64 => "var_u64",
+            Primitive::VarI64 => "var_i64",
+            Primitive::String => "string",
+            Primitive::Bytes => "bytes",
+            Primitive::Remaining => "remaining",
+        }
+    }
+
+    /// Whether the type can carry the length of an array.
+    pub fn is_count(self) -> bool {
+        matches!(
+            self,
+            Primitive::U8
+                | Primitive::U16
+                | Primitive::U16Le
+                | Primitive::U32
+                | Primitive::U32Le
+                | Primitive::U64
+                | Primitive::U64Le
+                | Primitive::VarU32
+                | Primitive::VarU64
+        )
+    }
+
+    pub fn is_float(self) -> bool {
+        self.rust_type().starts_with('f')
+    }
+}
+
+impl FieldType {
+    pub fn rust_type(&self) -> String {
+        match self {
+            FieldType::Primitive(primitive) => primitive.rust_type().to_string(),
+            FieldType::Array { array, .. } => format!("Vec<{}>", array.rust_type()),
+            FieldType::Optional { optional } => format!("Option<{}>", optional.rust_type()),
+        }
+    }
+
+    fn any(&self, predicate: &impl Fn(Primitive) -> bool) -> bool {
+        match self {
+            FieldType::Primitive(primitive) => predicate(*primitive),
+            FieldType::Array { array, .. } => array.any(predicate),
+            FieldType::Optional { optional } => optional.any(predicate),
+        }
+    }
+
+    pub fn has_float(&self) -> bool {
+        self.any(&|primitive| primitive.is_float())
+    }
+
+    pub fn has_bytes(&self) -> bool {
+        self.any(&|primitive| primitive.rust_type() == "Bytes")
+    }
+}
+
+impl ProtocolDefinition {
+    pub fn load(path: &Path) -> Result<Self, CodegenError> {
+        let json = fs::read_to_string(path).map_err(|source| CodegenError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&json).map_err(|source| CodegenError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,18 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CodegenError {
+    #[error("Failed to access {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Protocol {version}: {message}")]
+    Invalid { version: u32, message: String },
+    #[error("Protocol {0} is defined twice")]
+    DuplicateVersion(u32),
+}
@@ -0,0 +1,543 @@
+//! Turns protocol definitions into Rust source.
+
+use crate::definition::{
+    FieldDefinition, FieldType, PacketDefinition, Primitive, ProtocolDefinition,
+};
+use crate::error::CodegenError;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Game packet IDs are 10 bits; the rest of the header holds the sub-client IDs.
+const MAX_PACKET_ID: u32 = 0x3ff;
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+/// Keywords that cannot be raw identifiers either.
+const RESERVED: &[&str] = &["crate", "self", "super"];
+
+/// Generates one module per protocol version, named `v<version>`, holding a struct with
+/// `Readable` and `Writable` impls and an ID constant for each packet.
+///
+/// The generated code uses `amethyst_binary` and, for byte fields, `bytes`, which the crate
+/// including it has to depend on.
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    packet_trait: Option<String>,
+    protocols: Vec<ProtocolDefinition>,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also implements the trait at `path`, e.g. `crate::game::GamePacket`, for every packet.
+    /// The trait must have a `const ID: u32`.
+    pub fn with_packet_trait(mut self, path: impl Into<String>) -> Self {
+        self.packet_trait = Some(path.into());
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: ProtocolDefinition) -> Self {
+        self.protocols.push(protocol);
+        self
+    }
+
+    /// Adds the definition at `path`, or every `.json` definition in it if it is a directory.
+    pub fn load(mut self, path: &Path) -> Result<Self, CodegenError> {
+        let io_error = |source| CodegenError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if !path.is_dir() {
+            self.protocols.push(ProtocolDefinition::load(path)?);
+            return Ok(self);
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).map_err(io_error)? {
+            let file = entry.map_err(io_error)?.path();
+            if file
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                files.push(file);
+            }
+        }
+        files.sort();
+        for file in files {
+            self.protocols.push(ProtocolDefinition::load(&file)?);
+        }
+        Ok(self)
+    }
+
+    pub fn generate(&self) -> Result<String, CodegenError> {
+        let mut protocols: Vec<&ProtocolDefinition> = self.protocols.iter().collect();
+        protocols.sort_by_key(|protocol| protocol.version);
+        for pair in protocols.windows(2) {
+            if pair[0].version == pair[1].version {
+                return Err(CodegenError::DuplicateVersion(pair[0].version));
+            }
+        }
+        for protocol in &protocols {
+            validate(protocol).map_err(|message| CodegenError::Invalid {
+                version: protocol.version,
+                message,
+            })?;
+        }
+
+        let mut code = Code::default();
+        code.line("// @generated by amethyst-codegen. Edit the protocol definitions instead.");
+        code.blank();
+        let versions: Vec<String> = protocols.iter().map(|p| p.version.to_string()).collect();
+        code.line(&format!(
+            "pub const PROTOCOL_VERSIONS: &[u32] = &[{}];",
+            versions.join(", ")
+        ));
+        for protocol in protocols {
+            code.blank();
+            self.module(&mut code, protocol);
+        }
+        Ok(code.finish())
+    }
+
+    /// Generates the code into `path`, leaving the file alone if it is already up to date so
+    /// that build scripts do not cause rebuilds.
+    pub fn write(&self, path: &Path) -> Result<(), CodegenError> {
+        let code = self.generate()?;
+        if fs::read_to_string(path).is_ok_and(|existing| existing == code) {
+            return Ok(());
+        }
+        fs::write(path, code).map_err(|source| CodegenError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn module(&self, code: &mut Code, protocol: &ProtocolDefinition) {
+        code.line(&format!("/// Minecraft {}.", protocol.minecraft_version));
+        code.open(&format!("pub mod v{} {{", protocol.version));
+        code.line("use amethyst_binary::error::BinaryError;");
+        code.line("use amethyst_binary::io::{BinaryReader, BinaryWriter};");
+        code.line("use amethyst_binary::traits::{Readable, Writable};");
+        let uses_bytes = protocol
+            .packets
+            .iter()
+            .flat_map(|packet| &packet.fields)
+            .any(|field| field.ty.has_bytes());
+        if uses_bytes {
+            code.line("use bytes::Bytes;");
+        }
+        code.blank();
+        code.line(&format!(
+            "pub const PROTOCOL_VERSION: u32 = {};",
+            protocol.version
+        ));
+        code.line(&format!(
+            "pub const MINECRAFT_VERSION: &str = {:?};",
+            protocol.minecraft_version
+        ));
+        code.blank();
+        for packet in &protocol.packets {
+            code.line(&format!(
+                "pub const {}: u32 = {:#04x};",
+                constant_name(&packet.name),
+                packet.id
+            ));
+        }
+        for packet in &protocol.packets {
+            code.blank();
+            self.packet(code, packet);
+        }
+        code.close("}");
+    }
+
+    fn packet(&self, code: &mut Code, packet: &PacketDefinition) {
+        let name = &packet.name;
+        doc(code, packet.doc.as_deref());
+        let has_float = packet.fields.iter().any(|field| field.ty.has_float());
+        code.line(if has_float {
+            "#[derive(Clone, Debug, PartialEq)]"
+        } else {
+            "#[derive(Clone, Debug, PartialEq, Eq)]"
+        });
+        if packet.fields.is_empty() {
+            code.line(&format!("pub struct {};", name));
+        } else {
+            code.open(&format!("pub struct {} {{", name));
+            for field in &packet.fields {
+                doc(code, field.doc.as_deref());
+                code.line(&format!(
+                    "pub {}: {},",
+                    field_name(field),
+                    field.ty.rust_type()
+                ));
+            }
+            code.close("}");
+        }
+
+        if let Some(packet_trait) = &self.packet_trait {
+            code.blank();
+            code.open(&format!("impl {} for {} {{", packet_trait, name));
+            code.line(&format!("const ID: u32 = {};", constant_name(name)));
+            code.close("}");
+        }
+
+        code.blank();
+        code.open(&format!("impl Writable for {} {{", name));
+        let writer = if packet.fields.is_empty() {
+            "_writer"
+        } else {
+            "writer"
+        };
+        code.open(&format!(
+            "fn write(&self, {}: &mut BinaryWriter) -> Result<(), BinaryError> {{",
+            writer
+        ));
+        for field in &packet.fields {
+            write_field(code, &field.ty, &Place::Field(field_name(field)));
+        }
+        code.line("Ok(())");
+        code.close("}");
+        code.close("}");
+
+        code.blank();
+        code.open(&format!("impl Readable for {} {{", name));
+        if packet.fields.is_empty() {
+            code.open("fn read(_reader: &mut BinaryReader) -> Result<Self, BinaryError> {");
+            code.line("Ok(Self)");
+        } else {
+            code.open("fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {");
+            code.open("Ok(Self {");
+            for field in &packet.fields {
+                let mut lines = read_expression(&field.ty);
+                lines[0] = format!("{}: {}", field_name(field), lines[0]);
+                let last = lines.len() - 1;
+                lines[last].push(',');
+                code.lines(&lines);
+            }
+            code.close("})");
+        }
+        code.close("}");
+        code.close("}");
+    }
+}
+
+/// Where a value being written lives: a field of `self`, or a reference bound by a loop or a
+/// match.
+enum Place {
+    Field(String),
+    /// A binding, and the field it is part of.
+    Binding(&'static str, String),
+}
+
+impl Place {
+    /// The value, for `Copy` types.
+    fn value(&self) -> String {
+        match self {
+            Place::Field(name) => format!("self.{}", name),
+            Place::Binding(name, _) => format!("*{}", name),
+        }
+    }
+
+    /// A reference to the value.
+    fn reference(&self) -> String {
+        match self {
+            Place::Field(name) => format!("&self.{}", name),
+            Place::Binding(name, _) => name.to_string(),
+        }
+    }
+
+    /// The value as the receiver of a method call.
+    fn receiver(&self) -> String {
+        match self {
+            Place::Field(name) => format!("self.{}", name),
+            Place::Binding(name, _) => name.to_string(),
+        }
+    }
+
+    fn describe(&self) -> &str {
+        match self {
+            Place::Field(name) => name.trim_start_matches("r#"),
+            Place::Binding(_, field) => field,
+        }
+    }
+}
+
+fn write_field(code: &mut Code, ty: &FieldType, place: &Place) {
+    match ty {
+        FieldType::Primitive(Primitive::String) => {
+            code.line(&format!("writer.write_string({})?;", place.reference()));
+        }
+        FieldType::Primitive(Primitive::Bytes) => {
+            code.line(&format!(
+                "writer.write_var_u32({}.len() as u32)?;",
+                place.receiver()
+            ));
+            code.line(&format!("writer.write_bytes({})?;", place.reference()));
+        }
+        FieldType::Primitive(Primitive::Remaining) => {
+            code.line(&format!("writer.write_bytes({})?;", place.reference()));
+        }
+        FieldType::Primitive(primitive) => {
+            code.line(&format!(
+                "writer.write_{}({})?;",
+                primitive.method_suffix(),
+                place.value()
+            ));
+        }
+        FieldType::Array { array, count } => {
+            code.open(&format!(
+                "let count = {}::try_from({}.len()).map_err(|_| {{",
+                count.rust_type(),
+                place.receiver()
+            ));
+            code.line(&format!(
+                "BinaryError::InvalidData(\"Too many items in {}\".to_string())",
+                place.describe()
+            ));
+            code.close("})?;");
+            code.line(&format!("writer.write_{}(count)?;", count.method_suffix()));
+            code.open(&format!("for item in {} {{", place.reference()));
+            write_field(
+                code,
+                array,
+                &Place::Binding("item", place.describe().to_string()),
+            );
+            code.close("}");
+        }
+        FieldType::Optional { optional } => {
+            code.open(&format!("match {} {{", place.reference()));
+            code.open("Some(value) => {");
+            code.line("writer.write_bool(true)?;");
+            write_field(
+                code,
+                optional,
+                &Place::Binding("value", place.describe().to_string()),
+            );
+            code.close("}");
+            code.line("None => writer.write_bool(false)?,");
+            code.close("}");
+        }
+    }
+}
+
+/// The expression reading a `ty`, as lines indented relative to the first.
+fn read_expression(ty: &FieldType) -> Vec<String> {
+    match ty {
+        FieldType::Primitive(Primitive::Bytes) => vec![
+            "{".to_string(),
+            "    let len = reader.read_var_u32()? as usize;".to_string(),
+            "    reader.read_bytes(len)?".to_string(),
+            "}".to_string(),
+        ],
+        FieldType::Primitive(Primitive::Remaining) => vec!["reader.read_remaining()".to_string()],
+        FieldType::Primitive(primitive) => {
+            vec![format!("reader.read_{}()?", primitive.method_suffix())]
+        }
+        FieldType::Array { array, count } => {
+            let mut item = read_expression(array);
+            item[0] = format!("items.push({}", item[0]);
+            let last = item.len() - 1;
+            item[last].push_str(");");
+            let mut lines = vec![
+                "{".to_string(),
+                format!(
+                    "    let count = reader.read_{}()? as usize;",
+                    count.method_suffix()
+                ),
+                "    let mut items = Vec::with_capacity(count.min(reader.remaining()));"
+                    .to_string(),
+                "    for _ in 0..count {".to_string(),
+            ];
+            lines.extend(indent(item, 2));
+            lines.extend([
+                "    }".to_string(),
+                "    items".to_string(),
+                "}".to_string(),
+            ]);
+            lines
+        }
+        FieldType::Optional { optional } => {
+            let mut value = read_expression(optional);
+            value[0] = format!("Some({}", value[0]);
+            let last = value.len() - 1;
+            value[last].push(')');
+            let mut lines = vec!["if reader.read_bool()? {".to_string()];
+            lines.extend(indent(value, 1));
+            lines.extend([
+                "} else {".to_string(),
+                "    None".to_string(),
+                "}".to_string(),
+            ]);
+            lines
+        }
+    }
+}
+
+fn indent(lines: Vec<String>, levels: usize) -> impl Iterator<Item = String> {
+    lines
+        .into_iter()
+        .map(move |line| format!("{:width$}{}", "", line, width = levels * 4))
+}
+
+fn validate(protocol: &ProtocolDefinition) -> Result<(), String> {
+    let mut names = HashSet::new();
+    let mut ids = HashSet::new();
+    for packet in &protocol.packets {
+        let name = &packet.name;
+        if !is_type_name(name) {
+            return Err(format!(
+                "packet name {:?} is not an UpperCamelCase identifier",
+                name
+            ));
+        }
+        if !names.insert(name) {
+            return Err(format!("packet {} is defined twice", name));
+        }
+        if packet.id > MAX_PACKET_ID {
+            return Err(format!(
+                "packet {} has ID {:#x}, above {:#x}",
+                name, packet.id, MAX_PACKET_ID
+            ));
+        }
+        if !ids.insert(packet.id) {
+            return Err(format!("packet {} reuses ID {:#x}", name, packet.id));
+        }
+        let mut fields = HashSet::new();
+        for (i, field) in packet.fields.iter().enumerate() {
+            let field_name = &field.name;
+            if !is_field_name(field_name) {
+                return Err(format!(
+                    "packet {}: field name {:?} is not a snake_case identifier",
+                    name, field_name
+                ));
+            }
+            if !fields.insert(field_name) {
+                return Err(format!(
+                    "packet {}: field {} is defined twice",
+                    name, field_name
+                ));
+            }
+            let last = i + 1 == packet.fields.len();
+            validate_type(&field.ty, last)
+                .map_err(|message| format!("packet {}: field {}: {}", name, field_name, message))?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_type(ty: &FieldType, top_level_last: bool) -> Result<(), String> {
+    match ty {
+        FieldType::Primitive(Primitive::Remaining) if !top_level_last => {
+            Err("\"remaining\" is only allowed as the last field".to_string())
+        }
+        FieldType::Primitive(_) => Ok(()),
+        FieldType::Array { array, count } => {
+            if !count.is_count() {
+                return Err(format!("{:?} cannot be an array count", count));
+            }
+            validate_type(array, false)
+        }
+        FieldType::Optional { optional } => validate_type(optional, false),
+    }
+}
+
+fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_field_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && name != "_"
+        && !RESERVED.contains(&name)
+}
+
+fn field_name(field: &FieldDefinition) -> String {
+    if KEYWORDS.contains(&field.name.as_str()) {
+        format!("r#{}", field.name)
+    } else {
+        field.name.clone()
+    }
+}
+
+/// `NetworkSettings` to `NETWORK_SETTINGS`.
+fn constant_name(name: &str) -> String {
+    let mut constant = String::with_capacity(name.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase()
+            && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+        {
+            constant.push('_');
+        }
+        constant.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    constant
+}
+
+fn doc(code: &mut Code, doc: Option<&str>) {
+    for line in doc.into_iter().flat_map(str::lines) {
+        if line.is_empty() {
+            code.line("///");
+        } else {
+            code.line(&format!("/// {}", line));
+        }
+    }
+}
+
+/// Indented Rust source, built a line at a time.
+#[derive(Default)]
+struct Code {
+    source: String,
+    indent: usize,
+}
+
+impl Code {
+    fn line(&mut self, line: &str) {
+        let _ = writeln!(
+            self.source,
+            "{:width$}{}",
+            "",
+            line,
+            width = self.indent * 4
+        );
+    }
+
+    fn lines(&mut self, lines: &[String]) {
+        for line in lines {
+            self.line(line);
+        }
+    }
+
+    fn blank(&mut self) {
+        self.source.push('\n');
+    }
+
+    /// Writes `line` and indents what follows.
+    fn open(&mut self, line: &str) {
+        self.line(line);
+        self.indent += 1;
+    }
+
+    /// Ends the indentation of an [`open`](Self::open) with `line`.
+    fn close(&mut self, line: &str) {
+        self.indent -= 1;
+        self.line(line);
+    }
+
+    fn finish(self) -> String {
+        self.source
+    }
+}
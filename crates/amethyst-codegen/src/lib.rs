@@ -0,0 +1,36 @@
+//! Generates Bedrock game packet code from protocol definition files, so that packets are
+//! described once as data rather than written out by hand for every protocol version.
+//!
+//! A definition is a JSON file per protocol version:
+//!
+//! ```json
+//! {
+//!     "version": 662,
+//!     "minecraft_version": "1.20.80",
+//!     "packets": [
+//!         {
+//!             "name": "PlayStatus",
+//!             "id": 2,
+//!             "doc": "Tells the client how its login went.",
+//!             "fields": [
+//!                 { "name": "status", "type": "i32" }
+//!             ]
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Field types are the primitives of [`Primitive`](definition::Primitive), arrays with a
+//! length prefix (`{"array": "string", "count": "var_u32"}`) and optional values behind a
+//! `bool` (`{"optional": "u64_le"}`), nested as needed.
+//!
+//! The [`Generator`] is meant to run from a build script, writing into `OUT_DIR` for the
+//! crate to `include!`. The `amethyst-codegen` binary does the same from the command line.
+
+pub mod definition;
+pub mod error;
+pub mod generate;
+
+pub use definition::ProtocolDefinition;
+pub use error::CodegenError;
+pub use generate::Generator;
@@ -0,0 +1,51 @@
+use amethyst_codegen::Generator;
+use clap::Parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Generates Rust packet structs with Readable and Writable impls from protocol definition
+/// files.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Definition files, or directories of them.
+    #[arg(required = true)]
+    definitions: Vec<PathBuf>,
+
+    /// File to write. The code goes to standard output if omitted.
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Trait with a `const ID: u32` to implement for every packet, e.g.
+    /// crate::game::GamePacket.
+    #[arg(long, value_name = "PATH")]
+    packet_trait: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut generator = Generator::new();
+    if let Some(packet_trait) = cli.packet_trait {
+        generator = generator.with_packet_trait(packet_trait);
+    }
+    for path in &cli.definitions {
+        generator = match generator.load(path) {
+            Ok(generator) => generator,
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+    }
+    let result = match &cli.output {
+        Some(output) => generator.write(output),
+        None => generator.generate().map(|code| print!("{}", code)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
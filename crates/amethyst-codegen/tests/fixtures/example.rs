@@ -0,0 +1,162 @@
+// @generated by amethyst-codegen. Edit the protocol definitions instead.
+
+pub const PROTOCOL_VERSIONS: &[u32] = &[1];
+
+/// Minecraft 0.0.1.
+pub mod v1 {
+    use amethyst_binary::error::BinaryError;
+    use amethyst_binary::io::{BinaryReader, BinaryWriter};
+    use amethyst_binary::traits::{Readable, Writable};
+    use bytes::Bytes;
+
+    pub const PROTOCOL_VERSION: u32 = 1;
+    pub const MINECRAFT_VERSION: &str = "0.0.1";
+
+    pub const PING: u32 = 0x01;
+    pub const PLAYER_LIST: u32 = 0x3f;
+
+    /// A packet without fields.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Ping;
+
+    impl crate::Packet for Ping {
+        const ID: u32 = PING;
+    }
+
+    impl Writable for Ping {
+        fn write(&self, _writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+            Ok(())
+        }
+    }
+
+    impl Readable for Ping {
+        fn read(_reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+            Ok(Self)
+        }
+    }
+
+    /// Every field shape the generator supports.
+    ///
+    /// Arrays and options nest.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct PlayerList {
+        /// A keyword as a field name.
+        pub r#type: u8,
+        pub names: Vec<String>,
+        pub scores: Vec<Vec<i64>>,
+        pub xuid: Option<String>,
+        pub positions: Option<Vec<f32>>,
+        pub skin: Bytes,
+        pub guid: u64,
+        pub extra: Bytes,
+    }
+
+    impl crate::Packet for PlayerList {
+        const ID: u32 = PLAYER_LIST;
+    }
+
+    impl Writable for PlayerList {
+        fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+            writer.write_u8(self.r#type)?;
+            let count = u32::try_from(self.names.len()).map_err(|_| {
+                BinaryError::InvalidData("Too many items in names".to_string())
+            })?;
+            writer.write_var_u32(count)?;
+            for item in &self.names {
+                writer.write_string(item)?;
+            }
+            let count = u16::try_from(self.scores.len()).map_err(|_| {
+                BinaryError::InvalidData("Too many items in scores".to_string())
+            })?;
+            writer.write_u16_le(count)?;
+            for item in &self.scores {
+                let count = u8::try_from(item.len()).map_err(|_| {
+                    BinaryError::InvalidData("Too many items in scores".to_string())
+                })?;
+                writer.write_u8(count)?;
+                for item in item {
+                    writer.write_var_i64(*item)?;
+                }
+            }
+            match &self.xuid {
+                Some(value) => {
+                    writer.write_bool(true)?;
+                    writer.write_string(value)?;
+                }
+                None => writer.write_bool(false)?,
+            }
+            match &self.positions {
+                Some(value) => {
+                    writer.write_bool(true)?;
+                    let count = u8::try_from(value.len()).map_err(|_| {
+                        BinaryError::InvalidData("Too many items in positions".to_string())
+                    })?;
+                    writer.write_u8(count)?;
+                    for item in value {
+                        writer.write_f32_le(*item)?;
+                    }
+                }
+                None => writer.write_bool(false)?,
+            }
+            writer.write_var_u32(self.skin.len() as u32)?;
+            writer.write_bytes(&self.skin)?;
+            writer.write_u64_le(self.guid)?;
+            writer.write_bytes(&self.extra)?;
+            Ok(())
+        }
+    }
+
+    impl Readable for PlayerList {
+        fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+            Ok(Self {
+                r#type: reader.read_u8()?,
+                names: {
+                    let count = reader.read_var_u32()? as usize;
+                    let mut items = Vec::with_capacity(count.min(reader.remaining()));
+                    for _ in 0..count {
+                        items.push(reader.read_string()?);
+                    }
+                    items
+                },
+                scores: {
+                    let count = reader.read_u16_le()? as usize;
+                    let mut items = Vec::with_capacity(count.min(reader.remaining()));
+                    for _ in 0..count {
+                        items.push({
+                            let count = reader.read_u8()? as usize;
+                            let mut items = Vec::with_capacity(count.min(reader.remaining()));
+                            for _ in 0..count {
+                                items.push(reader.read_var_i64()?);
+                            }
+                            items
+                        });
+                    }
+                    items
+                },
+                xuid: if reader.read_bool()? {
+                    Some(reader.read_string()?)
+                } else {
+                    None
+                },
+                positions: if reader.read_bool()? {
+                    Some({
+                        let count = reader.read_u8()? as usize;
+                        let mut items = Vec::with_capacity(count.min(reader.remaining()));
+                        for _ in 0..count {
+                            items.push(reader.read_f32_le()?);
+                        }
+                        items
+                    })
+                } else {
+                    None
+                },
+                skin: {
+                    let len = reader.read_var_u32()? as usize;
+                    reader.read_bytes(len)?
+                },
+                guid: reader.read_u64_le()?,
+                extra: reader.read_remaining(),
+            })
+        }
+    }
+}
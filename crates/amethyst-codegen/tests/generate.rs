@@ -0,0 +1,179 @@
+//! Checks the generated code against a committed copy, and round-trips packets through that
+//! copy, which is compiled into this test.
+
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use amethyst_codegen::{CodegenError, Generator, ProtocolDefinition};
+use bytes::Bytes;
+use std::path::Path;
+
+#[allow(dead_code)]
+mod generated {
+    include!("fixtures/example.rs");
+}
+
+use generated::v1::{Ping, PlayerList};
+
+trait Packet {
+    const ID: u32;
+}
+
+fn fixture() -> &'static Path {
+    Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/example.json"
+    ))
+}
+
+fn protocol(json: &str) -> ProtocolDefinition {
+    serde_json::from_str(json).unwrap()
+}
+
+fn round_trip<T: Readable + Writable>(packet: &T) -> T {
+    let mut writer = BinaryWriter::new();
+    packet.write(&mut writer).unwrap();
+    let mut reader = BinaryReader::new(writer.freeze());
+    let read = T::read(&mut reader).unwrap();
+    assert_eq!(reader.remaining(), 0);
+    read
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let code = Generator::new()
+        .with_packet_trait("crate::Packet")
+        .load(fixture())
+        .unwrap()
+        .generate()
+        .unwrap();
+    assert!(
+        code == include_str!("fixtures/example.rs"),
+        "tests/fixtures/example.rs is stale; regenerate it with `cargo run -p amethyst-codegen \
+         -- tests/fixtures/example.json --packet-trait crate::Packet -o tests/fixtures/example.rs`"
+    );
+}
+
+#[test]
+fn generated_packets_round_trip() {
+    assert_eq!(<Ping as Packet>::ID, 0x01);
+    assert_eq!(<PlayerList as Packet>::ID, generated::v1::PLAYER_LIST);
+    assert_eq!(generated::PROTOCOL_VERSIONS, &[1]);
+    assert_eq!(round_trip(&Ping), Ping);
+
+    let packet = PlayerList {
+        r#type: 2,
+        names: vec!["Steve".to_string(), "Alex".to_string()],
+        scores: vec![vec![-1, 1 << 40], vec![]],
+        xuid: Some("2535".to_string()),
+        positions: None,
+        skin: Bytes::from_static(&[1, 2, 3]),
+        guid: u64::MAX,
+        extra: Bytes::from_static(b"tail"),
+    };
+    assert_eq!(round_trip(&packet), packet);
+    let packet = PlayerList {
+        xuid: None,
+        positions: Some(vec![0.5, -3.25]),
+        extra: Bytes::new(),
+        ..packet
+    };
+    assert_eq!(round_trip(&packet), packet);
+}
+
+#[test]
+fn array_counts_are_checked_when_writing() {
+    let packet = PlayerList {
+        r#type: 0,
+        names: Vec::new(),
+        scores: vec![vec![0; 256]],
+        xuid: None,
+        positions: None,
+        skin: Bytes::new(),
+        guid: 0,
+        extra: Bytes::new(),
+    };
+    assert!(packet.write(&mut BinaryWriter::new()).is_err());
+}
+
+#[test]
+fn invalid_definitions_are_rejected() {
+    let cases = [
+        (
+            r#"{"name": "A", "id": 1}, {"name": "B", "id": 1}"#,
+            "packet B reuses ID 0x1",
+        ),
+        (
+            r#"{"name": "A", "id": 1024}"#,
+            "packet A has ID 0x400, above 0x3ff",
+        ),
+        (r#"{"name": "lower", "id": 1}"#, "not an UpperCamelCase"),
+        (
+            r#"{"name": "A", "id": 1, "fields": [{"name": "self", "type": "u8"}]}"#,
+            "not a snake_case identifier",
+        ),
+        (
+            r#"{"name": "A", "id": 1, "fields": [{"name": "a", "type": {"array": "u8", "count": "i32"}}]}"#,
+            "I32 cannot be an array count",
+        ),
+        (
+            r#"{"name": "A", "id": 1, "fields": [{"name": "a", "type": "remaining"}, {"name": "b", "type": "u8"}]}"#,
+            "only allowed as the last field",
+        ),
+    ];
+    for (packets, expected) in cases {
+        let json = format!(
+            r#"{{"version": 7, "minecraft_version": "1", "packets": [{}]}}"#,
+            packets
+        );
+        let error = Generator::new()
+            .with_protocol(protocol(&json))
+            .generate()
+            .unwrap_err();
+        assert!(
+            matches!(&error, CodegenError::Invalid { version: 7, message } if message.contains(expected)),
+            "{}: {}",
+            packets,
+            error
+        );
+    }
+}
+
+#[test]
+fn versions_get_a_module_each() {
+    let empty = |version| {
+        protocol(&format!(
+            r#"{{"version": {}, "minecraft_version": "1", "packets": []}}"#,
+            version
+        ))
+    };
+    let code = Generator::new()
+        .with_protocol(empty(671))
+        .with_protocol(empty(662))
+        .generate()
+        .unwrap();
+    assert!(code.contains("pub const PROTOCOL_VERSIONS: &[u32] = &[662, 671];"));
+    assert!(code.find("pub mod v662").unwrap() < code.find("pub mod v671").unwrap());
+
+    let error = Generator::new()
+        .with_protocol(empty(662))
+        .with_protocol(empty(662))
+        .generate()
+        .unwrap_err();
+    assert!(matches!(error, CodegenError::DuplicateVersion(662)));
+}
+
+#[test]
+fn unreadable_definitions_are_reported() {
+    let error = Generator::new()
+        .load(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/missing.json"
+        )))
+        .unwrap_err();
+    assert!(matches!(error, CodegenError::Io { .. }));
+
+    let json = r#"{"version": 1, "minecraft_version": "1", "packets": [
+        {"name": "A", "id": 1, "fields": [{"name": "a", "type": "u7"}]}
+    ]}"#;
+    assert!(serde_json::from_str::<ProtocolDefinition>(json).is_err());
+}
@@ -0,0 +1,226 @@
+//! A simulated client: the offline handshake and a connection request, then connected pings
+//! at a fixed rate until told to stop.
+
+use crate::report::Metrics;
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio::time::{timeout_at, Instant, MissedTickBehavior};
+
+/// MTU the clients ask for, the same as the Bedrock client on most networks.
+const MTU: u16 = 1400;
+/// IP and UDP headers, which the first request's padding leaves room for.
+const UDP_HEADER_SIZE: usize = 28;
+/// How long each handshake step may take before the client gives up.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a stopping client waits for pongs to pings it already sent.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("no {0} from the server")]
+    Timeout(&'static str),
+    #[error("malformed {0}: {1}")]
+    Malformed(&'static str, BinaryError),
+}
+
+pub struct Client {
+    socket: UdpSocket,
+    guid: u64,
+    next_sequence_number: SeqNum,
+    /// Pings sent without a pong so far.
+    unanswered: u64,
+}
+
+impl Client {
+    /// Connects to `target` and records how long the handshake took.
+    pub async fn connect(target: SocketAddr, metrics: &Metrics) -> Result<Self, ClientError> {
+        let bind_address = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(bind_address).await?;
+        socket.connect(target).await?;
+        let mut client = Client {
+            socket,
+            guid: rand::random(),
+            next_sequence_number: SeqNum::ZERO,
+            unanswered: 0,
+        };
+        let started = Instant::now();
+        client.handshake(target).await?;
+        metrics.record_handshake(started.elapsed());
+        Ok(client)
+    }
+
+    async fn handshake(&mut self, target: SocketAddr) -> Result<(), ClientError> {
+        let request = OpenConnectionRequest1 {
+            protocol_version: RAKNET_PROTOCOL_VERSION,
+        };
+        let mut data = encode(OPEN_CONNECTION_REQUEST_1, &request).to_vec();
+        data.resize(MTU as usize - UDP_HEADER_SIZE, 0);
+        self.socket.send(&data).await?;
+        let reply: OpenConnectionReply1 = self
+            .expect(OPEN_CONNECTION_REPLY_1, "OPEN_CONNECTION_REPLY_1")
+            .await?;
+
+        let request = OpenConnectionRequest2 {
+            cookie: reply.cookie,
+            server_addr: target,
+            mtu: reply.mtu_size,
+            client_guid: self.guid,
+        };
+        self.socket
+            .send(&encode(OPEN_CONNECTION_REQUEST_2, &request))
+            .await?;
+        let _: OpenConnectionReply2 = self
+            .expect(OPEN_CONNECTION_REPLY_2, "OPEN_CONNECTION_REPLY_2")
+            .await?;
+
+        let request = ConnectionRequest {
+            client_guid: self.guid,
+            time: 0,
+            use_security: false,
+        };
+        self.socket
+            .send(&encode(CONNECTION_REQUEST, &request))
+            .await?;
+        let _: ConnectionRequestAccepted = self
+            .expect(CONNECTION_REQUEST_ACCEPTED, "CONNECTION_REQUEST_ACCEPTED")
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for packet `id`, skipping anything else the server sends meanwhile.
+    async fn expect<T: Readable>(&self, id: u8, name: &'static str) -> Result<T, ClientError> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let len = timeout_at(deadline, self.socket.recv(&mut buf))
+                .await
+                .map_err(|_| ClientError::Timeout(name))??;
+            if len > 0 && buf[0] == id {
+                return decode(&buf[1..len]).map_err(|e| ClientError::Malformed(name, e));
+            }
+        }
+    }
+
+    /// Sends `rate` connected pings per second until `stop` is set, then disconnects. Pings
+    /// carry the time they were sent in microseconds, which the server echoes back, so round
+    /// trips are measured without keeping track of each ping.
+    pub async fn run(
+        mut self,
+        rate: f64,
+        metrics: &Metrics,
+        mut stop: watch::Receiver<bool>,
+    ) -> Result<(), ClientError> {
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        let result = loop {
+            tokio::select! {
+                // The flag is only ever set, so any change, or the sender going away, means stop.
+                _ = stop.changed() => break Ok(()),
+                _ = interval.tick() => {
+                    let ping = ConnectedPing { time: metrics.now_micros() };
+                    if let Err(e) = self.send_framed(encode(CONNECTED_PING, &ping)).await {
+                        break Err(e);
+                    }
+                    metrics.record_ping_sent();
+                    self.unanswered += 1;
+                }
+                received = self.socket.recv(&mut buf) => match received {
+                    Ok(len) => self.handle(&buf[..len], metrics),
+                    Err(e) => break Err(e.into()),
+                },
+            }
+        };
+        if result.is_ok() {
+            self.drain(&mut buf, metrics).await;
+        }
+        // The server does not acknowledge reliable frames yet, so this is sent unreliably.
+        let notification = Bytes::from_static(&[DISCONNECTION_NOTIFICATION]);
+        self.send_framed(notification).await?;
+        result
+    }
+
+    /// Waits a little for the pongs still on their way, so they do not count as lost.
+    async fn drain(&mut self, buf: &mut [u8], metrics: &Metrics) {
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        while self.unanswered > 0 {
+            match timeout_at(deadline, self.socket.recv(buf)).await {
+                Ok(Ok(len)) => self.handle(&buf[..len], metrics),
+                _ => break,
+            }
+        }
+    }
+
+    fn handle(&mut self, data: &[u8], metrics: &Metrics) {
+        if data.first() != Some(&FRAME_SET) {
+            return;
+        }
+        let Ok(frame_set) = decode::<FrameSetPacket>(&data[1..]) else {
+            metrics.record_malformed();
+            return;
+        };
+        for packet in frame_set.packets {
+            if packet.payload.first() != Some(&CONNECTED_PONG) {
+                continue;
+            }
+            match decode::<ConnectedPong>(&packet.payload[1..]) {
+                Ok(pong) => {
+                    let rtt = metrics.now_micros().saturating_sub(pong.ping_time);
+                    metrics.record_pong(rtt);
+                    self.unanswered = self.unanswered.saturating_sub(1);
+                }
+                Err(_) => metrics.record_malformed(),
+            }
+        }
+    }
+
+    /// Sends `payload` unreliably in a frame set of its own.
+    async fn send_framed(&mut self, payload: Bytes) -> Result<(), ClientError> {
+        let frame_set = FrameSetPacket {
+            sequence_number: self.next_sequence_number,
+            packets: vec![EncapsulatedPacket {
+                reliability: Reliability::Unreliable,
+                is_split: false,
+                sequence_number: None,
+                ordering_index: None,
+                ordering_channel: None,
+                split_count: None,
+                split_id: None,
+                split_index: None,
+                payload,
+            }],
+        };
+        self.next_sequence_number = self.next_sequence_number.next();
+        self.socket.send(&encode(FRAME_SET, &frame_set)).await?;
+        Ok(())
+    }
+}
+
+fn encode(id: u8, packet: &impl Writable) -> Bytes {
+    let mut writer = BinaryWriter::new();
+    writer
+        .write_u8(id)
+        .and_then(|()| packet.write(&mut writer))
+        .expect("packets always encode");
+    writer.freeze()
+}
+
+fn decode<T: Readable>(data: &[u8]) -> Result<T, BinaryError> {
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(data)))
+}
@@ -0,0 +1,171 @@
+use crate::client::Client;
+use crate::report::{Metrics, Progress};
+use crate::resources::ServerSampler;
+use clap::Parser;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Instant, MissedTickBehavior};
+
+pub mod client;
+pub mod report;
+pub mod resources;
+
+/// Load generator for Amethyst. Connects simulated RakNet clients that ping the server at a
+/// fixed rate, and reports latency and, optionally, the server's resource use.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Server to connect to.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:19132")]
+    target: String,
+
+    /// Number of simulated clients.
+    #[arg(short = 'n', long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    clients: u32,
+
+    /// Seconds over which the clients are started, evenly spaced.
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    ramp_up: u64,
+
+    /// Seconds to keep running once every client has been started.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    duration: u64,
+
+    /// Connected pings each client sends per second.
+    #[arg(long, default_value_t = 20.0, value_parser = positive_rate)]
+    rate: f64,
+
+    /// Seconds between progress reports.
+    #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    report_interval: u64,
+
+    /// Process ID of a server on this machine, to report its CPU, memory, thread and file
+    /// descriptor use. Linux only.
+    #[arg(long, value_name = "PID")]
+    server_pid: Option<u32>,
+}
+
+fn positive_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let target = match tokio::net::lookup_host(&cli.target)
+        .await
+        .map(|mut a| a.next())
+    {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            eprintln!("{} did not resolve to any address", cli.target);
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("Failed to resolve {}: {}", cli.target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut sampler = match cli.server_pid.map(ServerSampler::new).transpose() {
+        Ok(sampler) => sampler,
+        Err(e) => {
+            eprintln!("Cannot sample the server process: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "Connecting {} clients to {} over {}s, {} pings/s each, for {}s",
+        cli.clients, target, cli.ramp_up, cli.rate, cli.duration
+    );
+    let metrics = Arc::new(Metrics::new());
+    let (stop, stopped) = watch::channel(false);
+    let clients = tokio::spawn(spawn_clients(
+        target,
+        cli.clients,
+        Duration::from_secs(cli.ramp_up),
+        cli.rate,
+        Arc::clone(&metrics),
+        stopped,
+    ));
+
+    let end = Instant::now() + Duration::from_secs(cli.ramp_up + cli.duration);
+    let mut progress = Progress::new();
+    let mut reports = tokio::time::interval(Duration::from_secs(cli.report_interval));
+    reports.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    reports.tick().await;
+    loop {
+        tokio::select! {
+            _ = reports.tick() => {
+                let server = sampler.as_mut().map(ServerSampler::sample);
+                progress.print(&metrics, server.as_deref());
+            }
+            _ = sleep_until(end) => break,
+            _ = tokio::signal::ctrl_c() => {
+                println!("Interrupted, disconnecting the clients");
+                break;
+            }
+        }
+    }
+
+    let _ = stop.send(true);
+    if clients.await.is_err() {
+        eprintln!("A client task panicked");
+    }
+    if let Some(sampler) = &mut sampler {
+        sampler.sample();
+    }
+    report::print_summary(
+        &metrics,
+        sampler.as_ref().map(ServerSampler::summary).as_deref(),
+    );
+    if metrics.connected() == 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Starts `count` clients spread evenly over `ramp_up`, then waits for all of them to
+/// disconnect.
+async fn spawn_clients(
+    target: SocketAddr,
+    count: u32,
+    ramp_up: Duration,
+    rate: f64,
+    metrics: Arc<Metrics>,
+    stopped: watch::Receiver<bool>,
+) {
+    let start = Instant::now();
+    let mut tasks = JoinSet::new();
+    for i in 0..count {
+        sleep_until(start + ramp_up * i / count).await;
+        if *stopped.borrow() {
+            break;
+        }
+        let metrics = Arc::clone(&metrics);
+        let stopped = stopped.clone();
+        tasks.spawn(async move {
+            let client = match Client::connect(target, &metrics).await {
+                Ok(client) => client,
+                Err(e) => {
+                    metrics.record_failure(format!("handshake: {}", e));
+                    return;
+                }
+            };
+            if let Err(e) = client.run(rate, &metrics, stopped).await {
+                metrics.record_failure(e.to_string());
+            }
+            metrics.record_disconnect();
+        });
+    }
+    tasks.join_all().await;
+}
@@ -0,0 +1,273 @@
+//! Counters and latency histograms shared by all clients, and the reports printed from them.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Histogram buckets per power of two, which keeps every bucket within about 3% of the
+/// values in it.
+const SUB_BUCKETS: u64 = 32;
+const SUB_BUCKET_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+const BUCKETS: usize = ((64 - SUB_BUCKET_BITS as u64 + 1) * SUB_BUCKETS) as usize;
+
+/// A log-linear histogram of microseconds. Its size is fixed, so long soak tests do not grow
+/// it, and recording is a single atomic add.
+pub struct Histogram {
+    buckets: Box<[AtomicU64]>,
+    max: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, micros: u64) {
+        self.buckets[bucket_of(micros)].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// The value at quantile `q` (0 to 1), rounded down to its bucket, or `None` if nothing
+    /// was recorded.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Some(Duration::from_micros(bucket_floor(index)));
+            }
+        }
+        Some(self.max())
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max.load(Ordering::Relaxed))
+    }
+
+    /// `p50/p90/p99/max` in milliseconds.
+    fn summary(&self) -> String {
+        match self.quantile(0.5) {
+            None => "-".to_string(),
+            Some(p50) => format!(
+                "p50 {} / p90 {} / p99 {} / max {}",
+                millis(p50),
+                millis(self.quantile(0.9).unwrap_or_default()),
+                millis(self.quantile(0.99).unwrap_or_default()),
+                millis(self.max()),
+            ),
+        }
+    }
+}
+
+/// Values below `SUB_BUCKETS` get a bucket each; above that, each power of two is split into
+/// `SUB_BUCKETS` buckets by the bits after the leading one.
+fn bucket_of(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let exponent = 63 - value.leading_zeros();
+    let shift = exponent - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) & (SUB_BUCKETS - 1);
+    ((shift as u64 + 1) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+/// The smallest value in bucket `index`.
+fn bucket_floor(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    (SUB_BUCKETS + index % SUB_BUCKETS) << shift
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Everything the clients measure.
+pub struct Metrics {
+    epoch: Instant,
+    connected: AtomicU64,
+    active: AtomicU64,
+    pings_sent: AtomicU64,
+    pongs_received: AtomicU64,
+    malformed: AtomicU64,
+    handshake: Histogram,
+    round_trip: Histogram,
+    /// How many clients failed for each reason.
+    failures: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            epoch: Instant::now(),
+            connected: AtomicU64::new(0),
+            active: AtomicU64::new(0),
+            pings_sent: AtomicU64::new(0),
+            pongs_received: AtomicU64::new(0),
+            malformed: AtomicU64::new(0),
+            handshake: Histogram::new(),
+            round_trip: Histogram::new(),
+            failures: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Microseconds since the test started.
+    pub fn now_micros(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    pub fn record_handshake(&self, duration: Duration) {
+        self.handshake.record(duration.as_micros() as u64);
+        self.connected.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_disconnect(&self) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, reason: String) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        *failures.entry(reason).or_default() += 1;
+    }
+
+    pub fn record_ping_sent(&self) {
+        self.pings_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_pong(&self, round_trip_micros: u64) {
+        self.round_trip.record(round_trip_micros);
+        self.pongs_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_malformed(&self) {
+        self.malformed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connected(&self) -> u64 {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    fn failed(&self) -> u64 {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.values().sum()
+    }
+
+    fn counts(&self) -> (u64, u64) {
+        (
+            self.pings_sent.load(Ordering::Relaxed),
+            self.pongs_received.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prints one progress line per interval, with rates since the previous one.
+pub struct Progress {
+    last: Instant,
+    last_counts: (u64, u64),
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress {
+            last: Instant::now(),
+            last_counts: (0, 0),
+        }
+    }
+
+    pub fn print(&mut self, metrics: &Metrics, server: Option<&str>) {
+        let now = Instant::now();
+        let seconds = now
+            .duration_since(self.last)
+            .as_secs_f64()
+            .max(f64::EPSILON);
+        let (sent, received) = metrics.counts();
+        let (last_sent, last_received) = self.last_counts;
+        let mut line = format!(
+            "[{:>6.1}s] clients {} active, {} failed | pings {:.0}/s, pongs {:.0}/s | rtt {}",
+            metrics.epoch.elapsed().as_secs_f64(),
+            metrics.active.load(Ordering::Relaxed),
+            metrics.failed(),
+            (sent - last_sent) as f64 / seconds,
+            (received - last_received) as f64 / seconds,
+            metrics.round_trip.summary(),
+        );
+        if let Some(server) = server {
+            line.push_str(" | server ");
+            line.push_str(server);
+        }
+        println!("{}", line);
+        self.last = now;
+        self.last_counts = (sent, received);
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn print_summary(metrics: &Metrics, server: Option<&str>) {
+    let (sent, received) = metrics.counts();
+    let lost = sent.saturating_sub(received);
+    let loss = if sent == 0 {
+        0.0
+    } else {
+        lost as f64 * 100.0 / sent as f64
+    };
+    println!();
+    println!(
+        "Summary after {:.1}s",
+        metrics.epoch.elapsed().as_secs_f64()
+    );
+    println!(
+        "  clients:    {} connected, {} failed",
+        metrics.connected(),
+        metrics.failed()
+    );
+    for (reason, count) in metrics
+        .failures
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+    {
+        println!("    {} x {}", count, reason);
+    }
+    println!("  handshake:  {}", metrics.handshake.summary());
+    println!(
+        "  pings:      {} sent, {} answered, {} lost ({:.2}%)",
+        sent, received, lost, loss
+    );
+    println!("  round trip: {}", metrics.round_trip.summary());
+    let malformed = metrics.malformed.load(Ordering::Relaxed);
+    if malformed > 0 {
+        println!("  malformed:  {} packets from the server", malformed);
+    }
+    if let Some(server) = server {
+        println!("  server:     {}", server);
+    }
+}
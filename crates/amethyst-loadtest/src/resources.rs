@@ -0,0 +1,121 @@
+//! Samples the server process from `/proc`, so a run shows what the load costs the server and
+//! whether memory, threads or file descriptors keep growing.
+
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+/// `/proc` reports CPU time in clock ticks of `USER_HZ`, which Linux fixes at 100.
+const TICKS_PER_SECOND: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    cpu_ticks: u64,
+    rss_kib: u64,
+    threads: u64,
+    fds: usize,
+}
+
+pub struct ServerSampler {
+    pid: u32,
+    first: Sample,
+    last: Sample,
+    peak_rss_kib: u64,
+}
+
+impl ServerSampler {
+    /// Fails if the process cannot be read, e.g. because it does not exist or this is not
+    /// Linux.
+    pub fn new(pid: u32) -> io::Result<Self> {
+        let sample = sample(pid)?;
+        Ok(ServerSampler {
+            pid,
+            first: sample,
+            last: sample,
+            peak_rss_kib: sample.rss_kib,
+        })
+    }
+
+    /// Takes a new sample and describes it, with the CPU use since the previous one.
+    pub fn sample(&mut self) -> String {
+        match sample(self.pid) {
+            Ok(sample) => {
+                let cpu = cpu_percent(&self.last, &sample);
+                self.last = sample;
+                self.peak_rss_kib = self.peak_rss_kib.max(sample.rss_kib);
+                format!(
+                    "cpu {:.0}%, rss {}, {} threads, {} fds",
+                    cpu,
+                    mib(sample.rss_kib),
+                    sample.threads,
+                    sample.fds
+                )
+            }
+            Err(e) => format!("unavailable ({})", e),
+        }
+    }
+
+    /// Compares the last sample with the first one.
+    pub fn summary(&self) -> String {
+        let (first, last) = (&self.first, &self.last);
+        format!(
+            "cpu {:.0}% on average, rss {} -> {} (peak {}), threads {} -> {}, fds {} -> {}",
+            cpu_percent(first, last),
+            mib(first.rss_kib),
+            mib(last.rss_kib),
+            mib(self.peak_rss_kib),
+            first.threads,
+            last.threads,
+            first.fds,
+            last.fds
+        )
+    }
+}
+
+fn sample(pid: u32) -> io::Result<Sample> {
+    let at = Instant::now();
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    // The command name can contain spaces, so fields are counted from after it.
+    let fields: Vec<&str> = stat
+        .rsplit_once(')')
+        .map(|(_, rest)| rest.split_whitespace().collect())
+        .unwrap_or_default();
+    let field = |index: usize| -> io::Result<u64> {
+        fields
+            .get(index)
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed stat"))
+    };
+    // utime and stime are the 14th and 15th fields, num_threads the 20th.
+    let cpu_ticks = field(11)? + field(12)?;
+    let threads = field(17)?;
+
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    let rss_kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap_or(0);
+    let fds = fs::read_dir(format!("/proc/{}/fd", pid))?.count();
+    Ok(Sample {
+        at,
+        cpu_ticks,
+        rss_kib,
+        threads,
+        fds,
+    })
+}
+
+fn cpu_percent(from: &Sample, to: &Sample) -> f64 {
+    let seconds = to.at.duration_since(from.at).as_secs_f64();
+    if seconds <= 0.0 {
+        return 0.0;
+    }
+    let cpu_seconds = to.cpu_ticks.saturating_sub(from.cpu_ticks) as f64 / TICKS_PER_SECOND;
+    cpu_seconds * 100.0 / seconds
+}
+
+fn mib(kib: u64) -> String {
+    format!("{:.1}MiB", kib as f64 / 1024.0)
+}
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Write};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+thread_local! {
+    static CURRENT: RefCell<Vec<Arc<LogContext>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Key/value fields attached to every record logged while the context is entered.
+///
+/// Contexts nest: entering a context while another is active renders both, outermost first.
+#[derive(Debug, Clone, Default)]
+pub struct LogContext {
+    fields: Vec<(&'static str, String)>,
+}
+
+impl LogContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: &'static str, value: impl Display) -> Self {
+        self.fields.push((key, value.to_string()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Makes this context current on this thread until the guard is dropped.
+    pub fn enter(self: &Arc<Self>) -> ContextGuard {
+        CURRENT.with(|current| current.borrow_mut().push(Arc::clone(self)));
+        ContextGuard { _private: () }
+    }
+
+    /// Runs `f` with this context entered.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        let context = Arc::new(self);
+        let _guard = context.enter();
+        f()
+    }
+}
+
+/// Leaves the context entered by [`LogContext::enter`] when dropped.
+pub struct ContextGuard {
+    _private: (),
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| {
+            current.borrow_mut().pop();
+        });
+    }
+}
+
+/// Renders the fields of all entered contexts as `key=value key=value`, or `None` if there
+/// are none.
+pub(crate) fn render_current() -> Option<String> {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let mut rendered = String::new();
+        for (key, value) in current.iter().flat_map(|c| c.fields.iter()) {
+            if !rendered.is_empty() {
+                rendered.push(' ');
+            }
+            let _ = write!(rendered, "{}={}", key, value);
+        }
+        (!rendered.is_empty()).then_some(rendered)
+    })
+}
+
+/// A future that enters its [`LogContext`] every time it is polled, so the context follows
+/// the task across executor threads.
+pub struct WithContext<F> {
+    inner: Pin<Box<F>>,
+    context: Arc<LogContext>,
+}
+
+impl<F: Future> Future for WithContext<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let _guard = self.context.enter();
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+pub trait WithLogContext: Future + Sized {
+    fn with_log_context(self, context: LogContext) -> WithContext<Self> {
+        WithContext {
+            inner: Box::pin(self),
+            context: Arc::new(context),
+        }
+    }
+}
+
+impl<F: Future> WithLogContext for F {}
@@ -0,0 +1,88 @@
+use log::{LevelFilter, Metadata};
+
+/// Decides which records are emitted, with optional per-target overrides.
+///
+/// A directive for `"rakethyst"` applies to the `rakethyst` target and every target nested
+/// below it (`rakethyst::listener`, ...). When several directives match, the most specific
+/// (longest) one wins; targets without a matching directive use the default level.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    default: LevelFilter,
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            directives: Vec::new(),
+        }
+    }
+
+    pub fn with_target(mut self, target: impl Into<String>, level: LevelFilter) -> Self {
+        self.set_target(target, level);
+        self
+    }
+
+    pub fn default_level(&self) -> LevelFilter {
+        self.default
+    }
+
+    pub fn set_default_level(&mut self, level: LevelFilter) {
+        self.default = level;
+    }
+
+    pub fn set_target(&mut self, target: impl Into<String>, level: LevelFilter) {
+        let target = target.into();
+        match self.directives.iter_mut().find(|(t, _)| *t == target) {
+            Some((_, existing)) => *existing = level,
+            None => {
+                self.directives.push((target, level));
+                self.directives.sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+            }
+        }
+    }
+
+    pub fn remove_target(&mut self, target: &str) -> bool {
+        let before = self.directives.len();
+        self.directives.retain(|(t, _)| t != target);
+        before != self.directives.len()
+    }
+
+    pub fn directives(&self) -> impl Iterator<Item = (&str, LevelFilter)> {
+        self.directives.iter().map(|(t, l)| (t.as_str(), *l))
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|(prefix, _)| target_matches(target, prefix))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    /// The most verbose level any target can reach, used for `log::set_max_level`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::Info)
+    }
+}
+
+fn target_matches(target: &str, prefix: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
@@ -0,0 +1,91 @@
+use crate::context;
+use chrono::Local;
+use log::{Level, Record};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+
+/// Whether console output should use ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color only when stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn should_color(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!(
+                "Invalid color choice: '{}'. Expected one of auto, always, never.",
+                other
+            )),
+        }
+    }
+}
+
+fn level_color(level: Level) -> &'static str {
+    match level {
+        Level::Error => "\x1b[1;31m",
+        Level::Warn => "\x1b[33m",
+        Level::Info => "\x1b[32m",
+        Level::Debug => "\x1b[36m",
+        Level::Trace => "\x1b[35m",
+    }
+}
+
+/// Formats a record as `timestamp LEVEL [target] message\n`, with the level padded so
+/// messages line up regardless of whether colors are enabled.
+///
+/// Fields of the current [`LogContext`](crate::context::LogContext) are rendered as
+/// `[key=value ...]` in front of the message.
+pub fn format_record(record: &Record, color: bool) -> String {
+    match context::render_current() {
+        Some(fields) => format_line(
+            record.level(),
+            record.target(),
+            format_args!("[{}] {}", fields, record.args()),
+            color,
+        ),
+        None => format_line(record.level(), record.target(), record.args(), color),
+    }
+}
+
+pub fn format_line(level: Level, target: &str, message: impl Display, color: bool) -> String {
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+    if color {
+        format!(
+            "{DIM}{}{RESET} {}{:<5}{RESET} {DIM}[{}]{RESET} {}\n",
+            timestamp,
+            level_color(level),
+            level,
+            target,
+            message
+        )
+    } else {
+        format!("{} {:<5} [{}] {}\n", timestamp, level, target, message)
+    }
+}
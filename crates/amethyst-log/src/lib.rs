@@ -1,87 +1,294 @@
-use chrono::Local;
 use log::{set_boxed_logger, set_max_level, Level, Log, SetLoggerError};
-use std::io::{stdout, BufWriter, Write};
-use std::sync::mpsc;
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{mpsc, Arc, Mutex, OnceLock, RwLock};
+use std::thread::{self, JoinHandle};
+use throttle::{Throttle, Verdict};
+use std::time::{Duration, Instant};
+
+pub mod context;
+pub mod filter;
+pub mod format;
+pub mod queue;
+pub mod throttle;
+mod writer;
+
+pub use context::{LogContext, WithLogContext};
+pub use filter::LogFilter;
+pub use format::ColorChoice;
+pub use queue::{LogQueue, OverflowPolicy};
+
+
+static SHARED: OnceLock<Arc<Shared>> = OnceLock::new();
+
+/// Number of recently written lines kept for crash reports.
+const RECENT_LINES: usize = 100;
+
+/// Logger settings that can be changed after the logger has been installed.
+struct Shared {
+    filter: RwLock<LogFilter>,
+    color: AtomicBool,
+    queue: Arc<LogQueue>,
+    recent: Mutex<VecDeque<String>>,
+    throttle: Throttle,
+    /// Replacement for the current output, picked up by the writer thread.
+    output: Mutex<Option<Box<dyn Write + Send>>>,
+    hook: RwLock<Option<(Level, RecordHook)>>,
+    /// The writer thread, taken by [`AmethystLogger::shutdown`].
+    writer: Mutex<Option<JoinHandle<()>>>,
+    /// Set once the writer thread has been told to stop. Records logged after that are
+    /// written to stderr directly.
+    terminated: AtomicBool,
+}
+
+/// Called with records as they are logged, see [`AmethystLogger::set_hook`].
+pub type RecordHook = Box<dyn Fn(&log::Record) + Send + Sync>;
 
 pub enum LogCommand {
     Record(String),
     Flush,
+    /// Flushes and then acknowledges on the channel, so callers can wait for output.
+    Sync(mpsc::Sender<()>),
     Terminate,
 }
 
+pub struct LoggerOptions {
+    pub max_level: Level,
+    pub buffer_size: usize,
+    pub color: ColorChoice,
+    pub overflow: OverflowPolicy,
+    /// Maximum time a written record may sit in the output buffer before being flushed.
+    pub flush_interval: Duration,
+    /// Flush as soon as this many bytes have been written since the last flush.
+    pub flush_bytes: usize,
+    /// Number of identical records written per `rate_limit_window` before further copies are
+    /// suppressed. Zero disables rate limiting.
+    pub rate_limit_burst: u32,
+    pub rate_limit_window: Duration,
+}
+
+impl Default for LoggerOptions {
+    fn default() -> Self {
+        Self {
+            max_level: Level::Info,
+            buffer_size: 1024,
+            color: ColorChoice::Auto,
+            overflow: OverflowPolicy::default(),
+            flush_interval: Duration::from_millis(250),
+            flush_bytes: 8 * 1024,
+            rate_limit_burst: 10,
+            rate_limit_window: Duration::from_secs(1),
+        }
+    }
+}
+
 pub struct AmethystLogger {
-    max_level: Level,
-    sender: mpsc::SyncSender<LogCommand>,
+    shared: Arc<Shared>,
 }
 
 impl AmethystLogger {
-    pub fn new(max_level: Level, buffer_size: usize) -> (Self, mpsc::Receiver<LogCommand>) {
-        let (sender, receiver) = mpsc::sync_channel(buffer_size);
-
-        let logger = AmethystLogger { max_level, sender };
-        (logger, receiver)
+    pub fn new(options: &LoggerOptions) -> Self {
+        let shared = Arc::new(Shared {
+            filter: RwLock::new(LogFilter::new(options.max_level.to_level_filter())),
+            color: AtomicBool::new(options.color.should_color()),
+            queue: Arc::new(LogQueue::new(options.buffer_size, options.overflow)),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_LINES)),
+            throttle: Throttle::new(options.rate_limit_burst, options.rate_limit_window),
+            output: Mutex::new(None),
+            hook: RwLock::new(None),
+            writer: Mutex::new(None),
+            terminated: AtomicBool::new(false),
+        });
+        AmethystLogger { shared }
     }
 
     pub fn init(max_level: Level, buffer_size: usize) -> Result<(), SetLoggerError> {
-        let (logger, receiver) = AmethystLogger::new(max_level, buffer_size);
+        Self::init_with(LoggerOptions {
+            max_level,
+            buffer_size,
+            ..LoggerOptions::default()
+        })
+    }
+
+    pub fn init_with(options: LoggerOptions) -> Result<(), SetLoggerError> {
+        let logger = AmethystLogger::new(&options);
+        let shared = Arc::clone(&logger.shared);
+        let writer_shared = Arc::clone(&logger.shared);
 
-        let _handle = thread::Builder::new()
+        let flush_interval = options.flush_interval;
+        let flush_bytes = options.flush_bytes;
+        let handle = thread::Builder::new()
             .name("amethyst-log-writer".into())
-            .spawn(move || {
-                let mut writer = BufWriter::new(stdout());
-                while let Ok(command) = receiver.recv() {
-                    match command {
-                        LogCommand::Record(message) => {
-                            if let Err(e) = writer.write_all(message.as_bytes()) {
-                                eprintln!("[AmethystLogger] Failed to write log record: {}", e);
-                            }
-                        }
-                        LogCommand::Flush => {
-                            if let Err(e) = writer.flush() {
-                                eprintln!("[AmethystLogger] Failed to flush log: {}", e);
-                            }
-                        }
-                        LogCommand::Terminate => {
-                            let _ = writer.flush();
-                            break; // Exit the loop
-                        }
-                    }
-                }
-                // Channel closed or termination requested. Ensure final flush.
-                let _ = writer.flush();
-            })
+            .spawn(move || writer::run(writer_shared, flush_interval, flush_bytes))
             .expect("Failed to spawn logger thread");
+        *shared.writer.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
 
         set_boxed_logger(Box::new(logger))?;
-        set_max_level(max_level.to_level_filter());
+        set_max_level(options.max_level.to_level_filter());
+        let _ = SHARED.set(shared);
         Ok(())
     }
+
+    /// Redirects log output, e.g. to an interactive console that redraws its prompt around
+    /// log lines. The previous output is flushed, and records not written yet go to the new one.
+    pub fn set_output(output: Box<dyn Write + Send>) {
+        if let Some(shared) = SHARED.get() {
+            *shared.output.lock().unwrap_or_else(|e| e.into_inner()) = Some(output);
+            shared.queue.push(LogCommand::Flush);
+        }
+    }
+
+    /// Replaces the active filter of the installed logger.
+    pub fn set_filter(filter: LogFilter) {
+        Self::update_filter(|current| *current = filter);
+    }
+
+    /// Mutates the active filter of the installed logger in place.
+    ///
+    /// Does nothing if `init` has not been called.
+    pub fn update_filter<F: FnOnce(&mut LogFilter)>(f: F) {
+        if let Some(shared) = SHARED.get() {
+            let mut filter = shared.filter.write().unwrap_or_else(|e| e.into_inner());
+            f(&mut filter);
+            set_max_level(filter.max_level());
+        }
+    }
+
+    /// Returns a snapshot of the active filter, if the logger is installed.
+    pub fn filter() -> Option<LogFilter> {
+        SHARED.get().map(|shared| {
+            shared
+                .filter
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        })
+    }
+
+    /// Changes what happens to records logged while the queue is full.
+    pub fn set_overflow_policy(policy: OverflowPolicy) {
+        if let Some(shared) = SHARED.get() {
+            shared.queue.set_policy(policy);
+        }
+    }
+
+    /// Changes how many identical records are written per `window` before the rest are
+    /// collapsed into a "last message repeated N times" line. A `burst` of zero disables it.
+    pub fn set_rate_limit(burst: u32, window: Duration) {
+        if let Some(shared) = SHARED.get() {
+            shared.throttle.configure(burst, window);
+        }
+    }
+
+    /// Flushes the installed logger and waits up to `timeout` for the writer thread to finish.
+    ///
+    /// Returns `false` if the logger is not installed or the writer did not respond in time.
+    pub fn flush_blocking(timeout: Duration) -> bool {
+        let Some(shared) = SHARED.get() else {
+            return false;
+        };
+        if shared.terminated.load(Ordering::Acquire) {
+            return false;
+        }
+        let (ack, done) = mpsc::channel();
+        shared.queue.push(LogCommand::Sync(ack));
+        done.recv_timeout(timeout).is_ok()
+    }
+
+    /// Stops the writer thread once it has written and flushed every queued record, and waits
+    /// up to `timeout` for it to finish. Records logged afterwards go to stderr unbuffered.
+    ///
+    /// Returns `false` if the logger is not installed, was already shut down, or the writer did
+    /// not finish in time.
+    pub fn shutdown(timeout: Duration) -> bool {
+        let Some(shared) = SHARED.get() else {
+            return false;
+        };
+        let Some(handle) = shared.writer.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return false;
+        };
+        shared.terminated.store(true, Ordering::Release);
+        shared.queue.push(LogCommand::Terminate);
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.join().is_ok()
+    }
+
+    /// Calls `hook` with every record at `level` or more severe that is written, e.g. to report
+    /// errors elsewhere. It runs on the thread that logged the record, so it has to be quick,
+    /// and must not log at `level` itself.
+    pub fn set_hook(level: Level, hook: RecordHook) {
+        if let Some(shared) = SHARED.get() {
+            *shared.hook.write().unwrap_or_else(|e| e.into_inner()) = Some((level, hook));
+        }
+    }
+
+    /// Returns the most recently written log lines, oldest first.
+    pub fn recent_lines() -> Vec<String> {
+        SHARED
+            .get()
+            .map(|shared| {
+                let recent = shared.recent.lock().unwrap_or_else(|e| e.into_inner());
+                recent.iter().cloned().collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Switches ANSI colors of the installed logger on or off.
+    pub fn set_color(choice: ColorChoice) {
+        if let Some(shared) = SHARED.get() {
+            shared.color.store(choice.should_color(), Ordering::Relaxed);
+        }
+    }
+
+    /// Hands `message` to the writer thread, or writes it to stderr once that has stopped.
+    fn write(&self, message: String) {
+        if self.shared.terminated.load(Ordering::Acquire) {
+            eprint!("{}", message);
+        } else {
+            self.shared.queue.push(LogCommand::Record(message));
+        }
+    }
 }
 
 impl Log for AmethystLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= self.max_level
+        self.shared
+            .filter
+            .read()
+            .map(|filter| filter.enabled(metadata))
+            .unwrap_or(true)
     }
 
     fn log(&self, record: &log::Record) {
         if self.enabled(record.metadata()) {
-            let now = Local::now();
-            let message = format!(
-                "{} {} [{}] {}\n",
-                now.format("%Y-%m-%d %H:%M:%S%.3f"),
-                record.level(),
-                record.target(),
-                record.args()
-            );
-
-            if let Err(e) = self.sender.try_send(LogCommand::Record(message)) {
-                eprintln!("[AmethystLogger] Failed to send log message: {}", e);
+            let color = self.shared.color.load(Ordering::Relaxed);
+            match self.shared.throttle.check(record) {
+                Verdict::Suppress => return,
+                Verdict::Allow(Some(repeated)) => {
+                    self.write(repeated.format(color));
+                }
+                Verdict::Allow(None) => {}
+            }
+            let message = format::format_record(record, color);
+
+            self.write(message);
+            let hook = self.shared.hook.read().unwrap_or_else(|e| e.into_inner());
+            if let Some((level, hook)) = &*hook
+                && record.level() <= *level
+            {
+                hook(record);
             }
         }
     }
 
     fn flush(&self) {
-        let _ = self.sender.send(LogCommand::Flush);
+        self.shared.queue.push(LogCommand::Flush);
     }
 }
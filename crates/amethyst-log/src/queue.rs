@@ -0,0 +1,164 @@
+use crate::LogCommand;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// What to do with a record when the writer thread has fallen behind and the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum OverflowPolicy {
+    /// Wait for the writer thread to make room. Never loses records, but can stall callers.
+    Block = 0,
+    /// Evict the oldest queued record to make room for the new one.
+    DropOldest = 1,
+    /// Discard the new record.
+    #[default]
+    DropNewest = 2,
+}
+
+impl OverflowPolicy {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => OverflowPolicy::Block,
+            1 => OverflowPolicy::DropOldest,
+            _ => OverflowPolicy::DropNewest,
+        }
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop-newest" => Ok(OverflowPolicy::DropNewest),
+            other => Err(format!(
+                "Invalid overflow policy: '{}'. Expected one of block, drop-oldest, drop-newest.",
+                other
+            )),
+        }
+    }
+}
+
+struct Inner {
+    commands: VecDeque<LogCommand>,
+    records: usize,
+}
+
+impl Inner {
+    fn pop(&mut self) -> Option<LogCommand> {
+        let command = self.commands.pop_front()?;
+        if matches!(command, LogCommand::Record(_)) {
+            self.records -= 1;
+        }
+        Some(command)
+    }
+
+    fn evict_oldest_record(&mut self) {
+        if let Some(index) = self
+            .commands
+            .iter()
+            .position(|c| matches!(c, LogCommand::Record(_)))
+        {
+            self.commands.remove(index);
+            self.records -= 1;
+        }
+    }
+}
+
+/// Bounded queue between logging call sites and the writer thread.
+///
+/// Only `Record`s count against the capacity; control commands (`Flush`, `Terminate`) are
+/// always accepted so a full queue can never prevent shutdown.
+pub struct LogQueue {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    policy: AtomicU8,
+    dropped: AtomicU64,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl LogQueue {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                commands: VecDeque::with_capacity(capacity),
+                records: 0,
+            }),
+            capacity: capacity.max(1),
+            policy: AtomicU8::new(policy as u8),
+            dropped: AtomicU64::new(0),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        OverflowPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub fn set_policy(&self, policy: OverflowPolicy) {
+        self.policy.store(policy as u8, Ordering::Relaxed);
+        self.not_full.notify_all();
+    }
+
+    /// Returns and resets the number of records dropped since the last call.
+    pub fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn push(&self, command: LogCommand) {
+        let mut inner = self.lock();
+        let is_record = matches!(command, LogCommand::Record(_));
+        if is_record {
+            while inner.records >= self.capacity {
+                match self.policy() {
+                    OverflowPolicy::Block => {
+                        inner = self.not_full.wait(inner).unwrap_or_else(|e| e.into_inner());
+                    }
+                    OverflowPolicy::DropOldest => {
+                        inner.evict_oldest_record();
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            }
+            inner.records += 1;
+        }
+        inner.commands.push_back(command);
+        drop(inner);
+        self.not_empty.notify_one();
+    }
+
+    /// Waits up to `timeout` for at least one command, then moves up to `max` queued commands
+    /// into `out` under a single lock.
+    pub fn pop_batch(&self, timeout: Duration, max: usize, out: &mut Vec<LogCommand>) {
+        let inner = self.lock();
+        let (mut inner, _) = self
+            .not_empty
+            .wait_timeout_while(inner, timeout, |i| i.commands.is_empty())
+            .unwrap_or_else(|e| e.into_inner());
+        while out.len() < max {
+            match inner.pop() {
+                Some(command) => out.push(command),
+                None => break,
+            }
+        }
+        drop(inner);
+        if !out.is_empty() {
+            self.not_full.notify_all();
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::format;
+use log::{Level, Record};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Repeated records are keyed by the file and line of the call site that produced them, so
+/// every invocation of the same `warn!(...)` counts as the same message regardless of its
+/// arguments, and looking one up allocates nothing.
+type Key = (&'static str, u32);
+
+struct Entry {
+    level: Level,
+    target: String,
+    /// The first message of the current window, repeated in the summary line.
+    message: String,
+    window_start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+struct State {
+    window: Duration,
+    entries: HashMap<Key, Entry>,
+}
+
+/// A summary of records suppressed during a finished window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeated {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub count: u64,
+}
+
+impl Repeated {
+    pub fn format(&self, color: bool) -> String {
+        format::format_line(
+            self.level,
+            &self.target,
+            format_args!(
+                "last message repeated {} times: {}",
+                self.count, self.message
+            ),
+            color,
+        )
+    }
+}
+
+/// What the logger should do with a record.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Write the record, preceded by a summary if earlier copies were suppressed.
+    Allow(Option<Repeated>),
+    Suppress,
+}
+
+/// Rate limits identical log records.
+///
+/// The first `burst` records of a message within each `window` are written; the rest are
+/// counted and reported as a single "last message repeated N times" line once the window ends.
+/// A `burst` of zero disables throttling. Records without a static file and line, which the
+/// `log` macros always set, are never throttled.
+pub struct Throttle {
+    /// Read before taking the lock, so a disabled throttle costs nothing.
+    burst: AtomicU32,
+    state: Mutex<State>,
+}
+
+impl Throttle {
+    pub fn new(burst: u32, window: Duration) -> Self {
+        Self {
+            burst: AtomicU32::new(burst),
+            state: Mutex::new(State {
+                window,
+                entries: HashMap::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn configure(&self, burst: u32, window: Duration) {
+        let mut state = self.lock();
+        self.burst.store(burst, Ordering::Relaxed);
+        state.window = window;
+        if burst == 0 {
+            state.entries.clear();
+        }
+    }
+
+    pub fn check(&self, record: &Record) -> Verdict {
+        self.check_at(record, Instant::now())
+    }
+
+    /// [`check`](Self::check) as of `now`.
+    pub fn check_at(&self, record: &Record, now: Instant) -> Verdict {
+        let burst = self.burst.load(Ordering::Relaxed);
+        if burst == 0 {
+            return Verdict::Allow(None);
+        }
+        let (Some(file), Some(line)) = (record.file_static(), record.line()) else {
+            return Verdict::Allow(None);
+        };
+
+        let mut state = self.lock();
+        let window = state.window;
+        let entry = state.entries.entry((file, line)).or_insert_with(|| Entry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            window_start: now,
+            count: 0,
+            suppressed: 0,
+        });
+
+        let mut repeated = None;
+        if now.duration_since(entry.window_start) >= window {
+            if entry.suppressed > 0 {
+                repeated = Some(Repeated {
+                    level: entry.level,
+                    target: entry.target.clone(),
+                    message: std::mem::replace(&mut entry.message, record.args().to_string()),
+                    count: entry.suppressed,
+                });
+            } else {
+                entry.message = record.args().to_string();
+            }
+            entry.window_start = now;
+            entry.count = 0;
+            entry.suppressed = 0;
+        }
+
+        if entry.count < burst {
+            entry.count += 1;
+            Verdict::Allow(repeated)
+        } else {
+            entry.suppressed += 1;
+            Verdict::Suppress
+        }
+    }
+
+    /// Forgets messages whose window has ended, returning summaries for those that had
+    /// suppressed records. Called periodically so a burst that simply stops is still reported.
+    pub fn sweep(&self) -> Vec<Repeated> {
+        self.sweep_at(Instant::now())
+    }
+
+    /// [`sweep`](Self::sweep) as of `now`.
+    pub fn sweep_at(&self, now: Instant) -> Vec<Repeated> {
+        let mut state = self.lock();
+        let window = state.window;
+        let mut repeated = Vec::new();
+        state.entries.retain(|_, entry| {
+            if now.duration_since(entry.window_start) < window {
+                return true;
+            }
+            if entry.suppressed > 0 {
+                repeated.push(Repeated {
+                    level: entry.level,
+                    target: std::mem::take(&mut entry.target),
+                    message: std::mem::take(&mut entry.message),
+                    count: entry.suppressed,
+                });
+            }
+            false
+        });
+        repeated
+    }
+}
@@ -0,0 +1,122 @@
+use crate::format;
+use crate::{LogCommand, Shared, RECENT_LINES};
+use log::Level;
+use std::io::{stdout, BufWriter, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often the writer thread reports records lost to a full queue.
+const DROPPED_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// Upper bound on commands taken from the queue per write.
+const MAX_BATCH: usize = 256;
+/// How often the writer thread reports messages suppressed by the rate limiter.
+const THROTTLE_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Body of the `amethyst-log-writer` thread.
+///
+/// Records are drained from the queue in batches and written with a single `write_all`.
+/// Output is flushed on request, once `flush_bytes` have accumulated, or when
+/// `flush_interval` has passed since the last flush, whichever comes first.
+pub(crate) fn run(shared: Arc<Shared>, flush_interval: Duration, flush_bytes: usize) {
+    let queue = Arc::clone(&shared.queue);
+    let mut writer: BufWriter<Box<dyn Write + Send>> = BufWriter::new(Box::new(stdout()));
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    let mut buffer = String::new();
+    let mut unflushed = 0usize;
+    let mut last_flush = Instant::now();
+    let mut last_report = Instant::now();
+    let mut last_sweep = Instant::now();
+
+    loop {
+        let wait = flush_interval.saturating_sub(last_flush.elapsed());
+        queue.pop_batch(wait, MAX_BATCH, &mut batch);
+
+        let mut flush_requested = false;
+        let mut terminate = false;
+        let mut acks = Vec::new();
+        if !batch.is_empty() {
+            let mut recent = shared.recent.lock().unwrap_or_else(|e| e.into_inner());
+            for command in batch.drain(..) {
+                match command {
+                    LogCommand::Record(message) => {
+                        buffer.push_str(&message);
+                        if recent.len() == RECENT_LINES {
+                            recent.pop_front();
+                        }
+                        recent.push_back(message);
+                    }
+                    LogCommand::Flush => flush_requested = true,
+                    LogCommand::Sync(ack) => {
+                        flush_requested = true;
+                        acks.push(ack);
+                    }
+                    LogCommand::Terminate => terminate = true,
+                }
+            }
+        }
+
+        if last_report.elapsed() >= DROPPED_REPORT_INTERVAL {
+            last_report = Instant::now();
+            let dropped = queue.take_dropped();
+            if dropped > 0 {
+                buffer.push_str(&format::format_line(
+                    Level::Warn,
+                    module_path!(),
+                    format_args!(
+                        "Dropped {} log records because the log queue was full",
+                        dropped
+                    ),
+                    shared.color.load(Ordering::Relaxed),
+                ));
+            }
+        }
+
+        if last_sweep.elapsed() >= THROTTLE_SWEEP_INTERVAL {
+            last_sweep = Instant::now();
+            let color = shared.color.load(Ordering::Relaxed);
+            for repeated in shared.throttle.sweep() {
+                buffer.push_str(&repeated.format(color));
+            }
+        }
+
+        if let Some(output) = shared
+            .output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            if let Err(e) = writer.flush() {
+                eprintln!("[AmethystLogger] Failed to flush log: {}", e);
+            }
+            writer = BufWriter::new(output);
+        }
+
+        if !buffer.is_empty() {
+            if let Err(e) = writer.write_all(buffer.as_bytes()) {
+                eprintln!("[AmethystLogger] Failed to write log records: {}", e);
+            }
+            unflushed += buffer.len();
+            buffer.clear();
+        }
+
+        let interval_elapsed = last_flush.elapsed() >= flush_interval;
+        if flush_requested || terminate || unflushed >= flush_bytes || interval_elapsed {
+            if (unflushed > 0 || flush_requested)
+                && let Err(e) = writer.flush()
+            {
+                eprintln!("[AmethystLogger] Failed to flush log: {}", e);
+            }
+            unflushed = 0;
+            last_flush = Instant::now();
+        }
+
+        for ack in acks {
+            let _ = ack.send(());
+        }
+
+        if terminate {
+            break;
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use amethyst_log::LogFilter;
+use log::{Level, LevelFilter, Metadata};
+
+#[test]
+fn targets_without_a_directive_use_the_default() {
+    let filter = LogFilter::new(LevelFilter::Warn);
+    assert_eq!(filter.level_for("amethyst"), LevelFilter::Warn);
+    assert_eq!(filter.max_level(), LevelFilter::Warn);
+}
+
+#[test]
+fn directives_apply_to_nested_targets_only() {
+    let filter = LogFilter::new(LevelFilter::Info).with_target("rakethyst", LevelFilter::Debug);
+    assert_eq!(filter.level_for("rakethyst"), LevelFilter::Debug);
+    assert_eq!(filter.level_for("rakethyst::listener"), LevelFilter::Debug);
+    assert_eq!(filter.level_for("rakethyst_extra"), LevelFilter::Info);
+    assert_eq!(filter.level_for("amethyst::rakethyst"), LevelFilter::Info);
+}
+
+#[test]
+fn the_most_specific_directive_wins() {
+    let filter = LogFilter::new(LevelFilter::Info)
+        .with_target("rakethyst::listener", LevelFilter::Trace)
+        .with_target("rakethyst", LevelFilter::Error);
+    assert_eq!(filter.level_for("rakethyst::listener::session"), LevelFilter::Trace);
+    assert_eq!(filter.level_for("rakethyst::socket"), LevelFilter::Error);
+    assert_eq!(filter.max_level(), LevelFilter::Trace);
+}
+
+#[test]
+fn directives_can_be_replaced_and_removed() {
+    let mut filter = LogFilter::new(LevelFilter::Info).with_target("amethyst", LevelFilter::Debug);
+    filter.set_target("amethyst", LevelFilter::Off);
+    assert_eq!(filter.directives().collect::<Vec<_>>(), [("amethyst", LevelFilter::Off)]);
+    assert!(filter.remove_target("amethyst"));
+    assert!(!filter.remove_target("amethyst"));
+    assert_eq!(filter.level_for("amethyst"), LevelFilter::Info);
+}
+
+#[test]
+fn enabled_compares_the_record_level() {
+    let filter = LogFilter::new(LevelFilter::Warn).with_target("amethyst", LevelFilter::Info);
+    let metadata = |level, target| Metadata::builder().level(level).target(target).build();
+    assert!(filter.enabled(&metadata(Level::Info, "amethyst::tick")));
+    assert!(!filter.enabled(&metadata(Level::Debug, "amethyst::tick")));
+    assert!(!filter.enabled(&metadata(Level::Info, "rakethyst")));
+}
@@ -0,0 +1,102 @@
+use amethyst_log::{LogCommand, LogQueue, OverflowPolicy};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn push_records(queue: &LogQueue, records: &[&str]) {
+    for record in records {
+        queue.push(LogCommand::Record(record.to_string()));
+    }
+}
+
+/// Everything queued, with records as their text and control commands by name.
+fn drain(queue: &LogQueue) -> Vec<String> {
+    let mut out = Vec::new();
+    queue.pop_batch(Duration::ZERO, usize::MAX, &mut out);
+    out.into_iter()
+        .map(|command| match command {
+            LogCommand::Record(record) => record,
+            LogCommand::Flush => "<flush>".to_string(),
+            LogCommand::Sync(_) => "<sync>".to_string(),
+            LogCommand::Terminate => "<terminate>".to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn drop_newest_keeps_the_first_records() {
+    let queue = LogQueue::new(2, OverflowPolicy::DropNewest);
+    push_records(&queue, &["a", "b", "c", "d"]);
+    assert_eq!(queue.take_dropped(), 2);
+    assert_eq!(queue.take_dropped(), 0);
+    assert_eq!(drain(&queue), ["a", "b"]);
+}
+
+#[test]
+fn drop_oldest_keeps_the_last_records() {
+    let queue = LogQueue::new(2, OverflowPolicy::DropOldest);
+    push_records(&queue, &["a", "b", "c", "d"]);
+    assert_eq!(queue.take_dropped(), 2);
+    assert_eq!(drain(&queue), ["c", "d"]);
+}
+
+#[test]
+fn drop_oldest_evicts_records_but_not_control_commands() {
+    let queue = LogQueue::new(2, OverflowPolicy::DropOldest);
+    queue.push(LogCommand::Flush);
+    push_records(&queue, &["a", "b", "c"]);
+    assert_eq!(queue.take_dropped(), 1);
+    assert_eq!(drain(&queue), ["<flush>", "b", "c"]);
+}
+
+#[test]
+fn control_commands_do_not_count_against_the_capacity() {
+    let queue = LogQueue::new(2, OverflowPolicy::DropNewest);
+    push_records(&queue, &["a", "b"]);
+    queue.push(LogCommand::Flush);
+    queue.push(LogCommand::Terminate);
+    assert_eq!(queue.take_dropped(), 0);
+    assert_eq!(drain(&queue), ["a", "b", "<flush>", "<terminate>"]);
+}
+
+#[test]
+fn block_waits_for_room_and_loses_nothing() {
+    let queue = Arc::new(LogQueue::new(2, OverflowPolicy::Block));
+    push_records(&queue, &["a", "b"]);
+    let pusher = {
+        let queue = Arc::clone(&queue);
+        thread::spawn(move || push_records(&queue, &["c"]))
+    };
+    thread::sleep(Duration::from_millis(50));
+    assert!(!pusher.is_finished());
+
+    let mut out = Vec::new();
+    queue.pop_batch(Duration::ZERO, 1, &mut out);
+    pusher.join().unwrap();
+    assert_eq!(queue.take_dropped(), 0);
+    assert_eq!(drain(&queue), ["b", "c"]);
+}
+
+#[test]
+fn leaving_block_releases_waiting_callers() {
+    let queue = Arc::new(LogQueue::new(2, OverflowPolicy::Block));
+    push_records(&queue, &["a", "b"]);
+    let pusher = {
+        let queue = Arc::clone(&queue);
+        thread::spawn(move || push_records(&queue, &["c"]))
+    };
+    thread::sleep(Duration::from_millis(50));
+    queue.set_policy(OverflowPolicy::DropNewest);
+    pusher.join().unwrap();
+    assert_eq!(queue.policy(), OverflowPolicy::DropNewest);
+    assert_eq!(queue.take_dropped(), 1);
+    assert_eq!(drain(&queue), ["a", "b"]);
+}
+
+#[test]
+fn policies_parse_from_their_config_names() {
+    assert_eq!("block".parse(), Ok(OverflowPolicy::Block));
+    assert_eq!("Drop-Oldest".parse(), Ok(OverflowPolicy::DropOldest));
+    assert_eq!("drop-newest".parse(), Ok(OverflowPolicy::DropNewest));
+    assert!("drop".parse::<OverflowPolicy>().is_err());
+}
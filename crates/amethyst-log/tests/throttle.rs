@@ -0,0 +1,102 @@
+use amethyst_log::throttle::{Repeated, Throttle, Verdict};
+use log::{Level, Record};
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(10);
+
+fn check(throttle: &Throttle, line: u32, message: &str, now: Instant) -> Verdict {
+    throttle.check_at(
+        &Record::builder()
+            .level(Level::Warn)
+            .target("rakethyst::listener")
+            .file_static(Some("src/listener.rs"))
+            .line(Some(line))
+            .args(format_args!("{}", message))
+            .build(),
+        now,
+    )
+}
+
+fn repeated(message: &str, count: u64) -> Repeated {
+    Repeated {
+        level: Level::Warn,
+        target: "rakethyst::listener".to_string(),
+        message: message.to_string(),
+        count,
+    }
+}
+
+#[test]
+fn allows_a_burst_per_window_then_suppresses() {
+    let throttle = Throttle::new(2, WINDOW);
+    let start = Instant::now();
+    assert_eq!(check(&throttle, 1, "a", start), Verdict::Allow(None));
+    assert_eq!(check(&throttle, 1, "b", start), Verdict::Allow(None));
+    assert_eq!(check(&throttle, 1, "c", start), Verdict::Suppress);
+    assert_eq!(check(&throttle, 1, "d", start + WINDOW / 2), Verdict::Suppress);
+}
+
+#[test]
+fn reports_the_suppressed_count_when_the_window_ends() {
+    let throttle = Throttle::new(1, WINDOW);
+    let start = Instant::now();
+    check(&throttle, 1, "first", start);
+    for _ in 0..3 {
+        assert_eq!(check(&throttle, 1, "again", start), Verdict::Suppress);
+    }
+    assert_eq!(
+        check(&throttle, 1, "later", start + WINDOW),
+        Verdict::Allow(Some(repeated("first", 3)))
+    );
+    // The window restarted without suppressed records, so the next one reports nothing.
+    assert_eq!(
+        check(&throttle, 1, "much later", start + WINDOW * 2),
+        Verdict::Allow(None)
+    );
+}
+
+#[test]
+fn call_sites_are_throttled_separately() {
+    let throttle = Throttle::new(1, WINDOW);
+    let now = Instant::now();
+    assert_eq!(check(&throttle, 1, "a", now), Verdict::Allow(None));
+    assert_eq!(check(&throttle, 2, "a", now), Verdict::Allow(None));
+    assert_eq!(check(&throttle, 1, "a", now), Verdict::Suppress);
+}
+
+#[test]
+fn sweep_reports_bursts_that_stopped() {
+    let throttle = Throttle::new(1, WINDOW);
+    let start = Instant::now();
+    check(&throttle, 1, "first", start);
+    check(&throttle, 1, "again", start);
+    check(&throttle, 2, "quiet", start);
+    assert!(throttle.sweep_at(start + WINDOW / 2).is_empty());
+    assert_eq!(throttle.sweep_at(start + WINDOW), [repeated("first", 1)]);
+    // Both sites were forgotten, so they start a new window.
+    assert_eq!(check(&throttle, 1, "x", start + WINDOW), Verdict::Allow(None));
+    assert_eq!(check(&throttle, 2, "x", start + WINDOW), Verdict::Allow(None));
+}
+
+#[test]
+fn a_zero_burst_disables_throttling() {
+    let throttle = Throttle::new(1, WINDOW);
+    throttle.configure(0, WINDOW);
+    let now = Instant::now();
+    for _ in 0..5 {
+        assert_eq!(check(&throttle, 1, "a", now), Verdict::Allow(None));
+    }
+}
+
+#[test]
+fn records_without_a_call_site_are_not_throttled() {
+    let throttle = Throttle::new(1, WINDOW);
+    let record = Record::builder()
+        .level(Level::Warn)
+        .args(format_args!("bridged"))
+        .build();
+    let now = Instant::now();
+    for _ in 0..3 {
+        assert_eq!(throttle.check_at(&record, now), Verdict::Allow(None));
+    }
+}
@@ -0,0 +1,17 @@
+use std::env;
+use std::process::Command;
+
+/// Records the compiler version so the server can refuse plugins built with a different one.
+/// Rust has no stable ABI, so trait objects can only cross the library boundary when both
+/// sides were compiled by the same `rustc`.
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=AMETHYST_PLUGIN_RUSTC_VERSION={}", version.trim());
+    println!("cargo:rerun-if-env-changed=RUSTC");
+}
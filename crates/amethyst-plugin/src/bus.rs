@@ -0,0 +1,118 @@
+use crate::event::{AnyEvent, Event, EventKind, EventPriority};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Identifies a handler so it can be removed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+type Callback = Box<dyn Fn(&mut AnyEvent) + Send + Sync>;
+
+struct Handler {
+    id: HandlerId,
+    priority: EventPriority,
+    /// Plugin that registered the handler, `None` for the server itself.
+    owner: Option<String>,
+    callback: Callback,
+}
+
+/// Delivers events to handlers registered by the server and by plugins.
+///
+/// Handlers of an event run in [`EventPriority`] order, and in registration order within a
+/// priority. Each handler sees the changes made by the ones before it, including
+/// cancellation, and may undo them.
+#[derive(Default)]
+pub struct EventBus {
+    handlers: RwLock<HashMap<EventKind, Vec<Arc<Handler>>>>,
+    next_id: AtomicU64,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe<E: Event>(
+        &self,
+        priority: EventPriority,
+        handler: impl Fn(&mut E) + Send + Sync + 'static,
+    ) -> HandlerId {
+        self.subscribe_as(None, priority, handler)
+    }
+
+    /// Registers a handler on behalf of `owner`, so all of its handlers can be removed with
+    /// [`unsubscribe_owner`](Self::unsubscribe_owner).
+    pub fn subscribe_as<E: Event>(
+        &self,
+        owner: Option<&str>,
+        priority: EventPriority,
+        handler: impl Fn(&mut E) + Send + Sync + 'static,
+    ) -> HandlerId {
+        let id = HandlerId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let handler = Arc::new(Handler {
+            id,
+            priority,
+            owner: owner.map(str::to_string),
+            callback: Box::new(move |event| {
+                if let Some(event) = E::downcast_mut(event) {
+                    handler(event);
+                }
+            }),
+        });
+        let mut handlers = self.handlers.write().unwrap_or_else(|e| e.into_inner());
+        let list = handlers.entry(E::KIND).or_default();
+        let index = list.partition_point(|existing| existing.priority <= priority);
+        list.insert(index, handler);
+        id
+    }
+
+    pub fn unsubscribe(&self, id: HandlerId) -> bool {
+        let mut handlers = self.handlers.write().unwrap_or_else(|e| e.into_inner());
+        let mut removed = false;
+        for list in handlers.values_mut() {
+            let before = list.len();
+            list.retain(|handler| handler.id != id);
+            removed |= list.len() != before;
+        }
+        removed
+    }
+
+    /// Removes every handler registered by `owner`.
+    pub fn unsubscribe_owner(&self, owner: &str) {
+        let mut handlers = self.handlers.write().unwrap_or_else(|e| e.into_inner());
+        for list in handlers.values_mut() {
+            list.retain(|handler| handler.owner.as_deref() != Some(owner));
+        }
+    }
+
+    /// Whether anything listens for `E`, to skip building events nobody handles.
+    pub fn has_handlers<E: Event>(&self) -> bool {
+        self.handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&E::KIND)
+            .is_some_and(|list| !list.is_empty())
+    }
+
+    /// Runs the handlers of `event` and returns it as they left it.
+    ///
+    /// Handlers may post events and register or remove handlers themselves; changes to the
+    /// handler list take effect from the next post.
+    pub fn post<E: Event>(&self, event: E) -> E {
+        let handlers: Vec<Arc<Handler>> = match self
+            .handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&E::KIND)
+        {
+            Some(list) if !list.is_empty() => list.clone(),
+            _ => return event,
+        };
+        let mut event = event.into_any();
+        for handler in handlers {
+            (handler.callback)(&mut event);
+        }
+        E::from_any(event).expect("handlers cannot change the type of an event")
+    }
+}
@@ -0,0 +1,92 @@
+use crate::permission::{PermissionSubject, Permissions};
+use std::sync::Arc;
+
+/// A command invocation as seen by a plugin.
+#[derive(Debug, Clone)]
+pub struct CommandInput {
+    /// Name of whoever ran the command, "Server" for the console.
+    pub sender: String,
+    /// Set when a player ran the command. The console and remote administration hold every
+    /// permission.
+    pub player: bool,
+    pub xuid: Option<String>,
+    /// Permission level of the sender: 0 for regular players, up to 4 for the console.
+    pub permission_level: u8,
+    /// Arguments after the command name, with quotes removed.
+    pub args: Vec<String>,
+    pub permissions: Arc<Permissions>,
+}
+
+impl CommandInput {
+    /// Whether the sender holds permission node `node`.
+    pub fn has_permission(&self, node: &str) -> bool {
+        if !self.player {
+            return true;
+        }
+        let subject = PermissionSubject {
+            name: &self.sender,
+            xuid: self.xuid.as_deref(),
+            op_level: self.permission_level,
+        };
+        self.permissions.has(&subject, node)
+    }
+}
+
+pub type CommandHandler = Box<dyn Fn(&CommandInput) -> Result<String, String> + Send + Sync>;
+
+/// A command a plugin adds to the server. The handler returns the feedback shown to the
+/// sender, or an error message.
+pub struct PluginCommand {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub usage: String,
+    pub description: String,
+    /// Operator level players need unless `permissions.json` says otherwise.
+    pub permission: u8,
+    /// Permission node of the command, `<plugin>.command.<name>` if not set.
+    pub node: Option<String>,
+    pub handler: CommandHandler,
+}
+
+impl PluginCommand {
+    /// A command everyone may use, without aliases or usage text.
+    pub fn new(
+        name: impl Into<String>,
+        handler: impl Fn(&CommandInput) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            usage: String::new(),
+            description: String::new(),
+            permission: 0,
+            node: None,
+            handler: Box::new(handler),
+        }
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn permission(mut self, permission: u8) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    pub fn node(mut self, node: impl Into<String>) -> Self {
+        self.node = Some(node.into());
+        self
+    }
+}
@@ -0,0 +1,243 @@
+use std::net::SocketAddr;
+
+/// An event type that can be posted on the [`EventBus`](crate::EventBus).
+///
+/// Events are carried as [`AnyEvent`] inside the bus instead of `dyn Any`, because type ids
+/// are not guaranteed to match between the server and a separately built plugin.
+pub trait Event: Sized + Send + 'static {
+    const KIND: EventKind;
+
+    fn into_any(self) -> AnyEvent;
+
+    fn from_any(event: AnyEvent) -> Option<Self>;
+
+    fn downcast_mut(event: &mut AnyEvent) -> Option<&mut Self>;
+}
+
+/// An event that handlers can cancel, e.g. to stop a chat message from being sent.
+pub trait Cancellable {
+    fn is_cancelled(&self) -> bool;
+
+    fn set_cancelled(&mut self, cancelled: bool);
+}
+
+/// Order in which handlers run, lowest first, so higher priorities get the last word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum EventPriority {
+    Lowest,
+    Low,
+    #[default]
+    Normal,
+    High,
+    Highest,
+    /// Runs last, to observe the outcome. Handlers at this priority should not modify or
+    /// cancel the event.
+    Monitor,
+}
+
+macro_rules! events {
+    ($($name:ident),* $(,)?) => {
+        /// Any event known to the bus.
+        #[derive(Debug, Clone)]
+        #[non_exhaustive]
+        pub enum AnyEvent {
+            $($name($name),)*
+        }
+
+        /// The type of an event, used to look up its handlers.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[non_exhaustive]
+        pub enum EventKind {
+            $($name,)*
+        }
+
+        impl AnyEvent {
+            pub fn kind(&self) -> EventKind {
+                match self {
+                    $(AnyEvent::$name(_) => EventKind::$name,)*
+                }
+            }
+        }
+
+        $(
+            impl Event for $name {
+                const KIND: EventKind = EventKind::$name;
+
+                fn into_any(self) -> AnyEvent {
+                    AnyEvent::$name(self)
+                }
+
+                fn from_any(event: AnyEvent) -> Option<Self> {
+                    match event {
+                        AnyEvent::$name(event) => Some(event),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
+                }
+
+                fn downcast_mut(event: &mut AnyEvent) -> Option<&mut Self> {
+                    match event {
+                        AnyEvent::$name(event) => Some(event),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! cancellable {
+    ($($name:ident),* $(,)?) => {
+        $(
+            impl Cancellable for $name {
+                fn is_cancelled(&self) -> bool {
+                    self.cancelled
+                }
+
+                fn set_cancelled(&mut self, cancelled: bool) {
+                    self.cancelled = cancelled;
+                }
+            }
+        )*
+    };
+}
+
+events!(
+    ServerStarted,
+    ServerStopping,
+    ConfigReloaded,
+    PlayerJoin,
+    PlayerLoginDenied,
+    PlayerQuit,
+    PlayerChat,
+    BlockBreak,
+    BlockPlace,
+    PacketReceive,
+);
+
+cancellable!(
+    PlayerJoin,
+    PlayerChat,
+    BlockBreak,
+    BlockPlace,
+    PacketReceive
+);
+
+/// Startup finished and the server accepts connections.
+#[derive(Debug, Clone, Default)]
+pub struct ServerStarted;
+
+/// The server is about to shut down. Plugins are disabled right after.
+#[derive(Debug, Clone, Default)]
+pub struct ServerStopping;
+
+/// `config.toml` was changed and reloaded.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigReloaded;
+
+/// A player finished logging in. Cancelling it disconnects the player with `kick_message`.
+#[derive(Debug, Clone)]
+pub struct PlayerJoin {
+    pub name: String,
+    pub xuid: Option<String>,
+    /// Identity UUID from the login chain, hyphenated.
+    pub uuid: String,
+    /// Whether Xbox Live vouched for the player. Players who join a server in offline mode
+    /// are not, and have no XUID.
+    pub authenticated: bool,
+    pub address: SocketAddr,
+    pub kick_message: String,
+    pub cancelled: bool,
+}
+
+/// A player was refused at login, once every [`PlayerJoin`] handler has run and the join
+/// stayed cancelled. It is posted for logging and webhooks and cannot be undone.
+#[derive(Debug, Clone)]
+pub struct PlayerLoginDenied {
+    pub name: String,
+    pub xuid: Option<String>,
+    pub address: SocketAddr,
+    /// Why, e.g. `"not_whitelisted"`, `"banned"`, `"ip_banned"`, or `"plugin"` when a handler
+    /// cancelled the join for reasons of its own.
+    pub reason: String,
+    /// The disconnect message shown to the player.
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerQuit {
+    pub name: String,
+    pub address: SocketAddr,
+    pub reason: String,
+}
+
+/// A chat message about to be broadcast, as [`PlayerChat::render`] formats it. Handlers may
+/// rewrite `message` and `format`. Messages from muted players never get here.
+#[derive(Debug, Clone)]
+pub struct PlayerChat {
+    pub name: String,
+    /// `None` for messages from the console or remote administration.
+    pub xuid: Option<String>,
+    pub message: String,
+    /// Template the message is broadcast with, in which `{name}` and `{message}` are
+    /// replaced, e.g. `<{name}> {message}`.
+    pub format: String,
+    pub cancelled: bool,
+}
+
+impl PlayerChat {
+    /// The line broadcast to players. Placeholders in the name and message are left as they
+    /// are.
+    pub fn render(&self) -> String {
+        let mut line = String::with_capacity(self.format.len() + self.message.len());
+        let mut rest = self.format.as_str();
+        while let Some(start) = rest.find('{') {
+            line.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("{name}") {
+                line.push_str(&self.name);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{message}") {
+                line.push_str(&self.message);
+                rest = after;
+            } else {
+                line.push('{');
+                rest = &rest[1..];
+            }
+        }
+        line.push_str(rest);
+        line
+    }
+}
+
+/// A player is breaking a block. Cancelling it keeps the block and resends it to the player.
+#[derive(Debug, Clone)]
+pub struct BlockBreak {
+    pub player: String,
+    pub xuid: Option<String>,
+    pub world: String,
+    pub position: (i32, i32, i32),
+    pub cancelled: bool,
+}
+
+/// A player is placing a block at `position`. Cancelling it leaves the position as it was and
+/// resends it to the player.
+#[derive(Debug, Clone)]
+pub struct BlockPlace {
+    pub player: String,
+    pub xuid: Option<String>,
+    pub world: String,
+    pub position: (i32, i32, i32),
+    pub cancelled: bool,
+}
+
+/// A datagram received from a connected client, before RakNet handles it. Cancelling it
+/// drops the datagram; `payload` is a copy, so changes to it are not applied.
+#[derive(Debug, Clone)]
+pub struct PacketReceive {
+    pub address: SocketAddr,
+    pub packet_id: u8,
+    pub payload: Vec<u8>,
+    pub cancelled: bool,
+}
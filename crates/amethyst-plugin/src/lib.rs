@@ -0,0 +1,210 @@
+//! Interface between the Amethyst server and native plugins.
+//!
+//! A plugin is a `cdylib` crate that depends on this crate, implements [`Plugin`] and exports
+//! it with [`declare_plugin!`]:
+//!
+//! ```ignore
+//! use amethyst_plugin::event::{EventPriority, PlayerChat};
+//! use amethyst_plugin::{declare_plugin, Plugin, PluginCommand, PluginContext, PluginError};
+//!
+//! #[derive(Default)]
+//! struct Hello;
+//!
+//! impl Plugin for Hello {
+//!     fn name(&self) -> &str {
+//!         "hello"
+//!     }
+//!
+//!     fn on_enable(&mut self, context: &mut PluginContext) -> Result<(), PluginError> {
+//!         context.register_command(PluginCommand::new("hello", |_| Ok("Hello!".into())));
+//!         context.subscribe(EventPriority::Normal, |chat: &mut PlayerChat| {
+//!             chat.cancelled = chat.message.contains("spam");
+//!         });
+//!         context.scheduler().run_repeating(0, 20 * 60, || log::info!("Still here"));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! declare_plugin!(Hello::default);
+//! ```
+//!
+//! Rust has no stable ABI, so plugins must be built with the same compiler and the same
+//! version of this crate as the server. The server checks both before enabling a plugin.
+
+pub mod bus;
+pub mod command;
+pub mod event;
+pub mod permission;
+pub mod player;
+pub mod scheduler;
+
+pub use bus::{EventBus, HandlerId};
+pub use command::{CommandInput, PluginCommand};
+pub use event::{AnyEvent, Cancellable, Event, EventKind, EventPriority};
+pub use permission::{PermissionDefault, PermissionNode, Permissions};
+pub use player::{PlayerDirectory, PlayerProfile};
+pub use scheduler::{PluginScheduler, RunningTask, Scheduler, TICKS_PER_SECOND, TaskId};
+
+use log::{LevelFilter, Log};
+use std::fmt;
+use std::sync::Arc;
+
+/// Bumped whenever [`Plugin`], [`PluginContext`] or the types they use change.
+pub const API_VERSION: u32 = 10;
+
+/// Version of the compiler this crate was built with.
+pub const RUSTC_VERSION: &str = env!("AMETHYST_PLUGIN_RUSTC_VERSION");
+
+/// Name of the symbol [`declare_plugin!`] exports.
+pub const DECLARATION_SYMBOL: &[u8] = b"AMETHYST_PLUGIN\0";
+
+/// Error returned by a plugin that cannot be enabled.
+#[derive(Debug, Clone)]
+pub struct PluginError(pub String);
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+impl From<String> for PluginError {
+    fn from(message: String) -> Self {
+        Self(message)
+    }
+}
+
+impl From<&str> for PluginError {
+    fn from(message: &str) -> Self {
+        Self(message.to_string())
+    }
+}
+
+pub trait Plugin: Send {
+    /// Unique name, used in logs and to resolve command name clashes.
+    fn name(&self) -> &str;
+
+    fn version(&self) -> &str {
+        "0.0.0"
+    }
+
+    /// Called once after loading. Subscribe to events and register commands here; a plugin
+    /// that returns an error is not enabled.
+    fn on_enable(&mut self, context: &mut PluginContext) -> Result<(), PluginError>;
+
+    /// Called once when the server shuts down, after which its event handlers and scheduled
+    /// tasks are removed.
+    fn on_disable(&mut self) {}
+}
+
+/// What a plugin sets up while it is being enabled.
+pub struct PluginContext {
+    owner: String,
+    events: Arc<EventBus>,
+    scheduler: PluginScheduler,
+    permissions: Arc<Permissions>,
+    players: Arc<PlayerDirectory>,
+    commands: Vec<PluginCommand>,
+}
+
+impl PluginContext {
+    pub fn new(
+        owner: impl Into<String>,
+        events: Arc<EventBus>,
+        scheduler: Arc<Scheduler>,
+        permissions: Arc<Permissions>,
+        players: Arc<PlayerDirectory>,
+    ) -> Self {
+        let owner = owner.into();
+        Self {
+            scheduler: PluginScheduler::new(&owner, scheduler),
+            owner,
+            events,
+            permissions,
+            players,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Registers an event handler owned by the plugin.
+    pub fn subscribe<E: Event>(
+        &mut self,
+        priority: EventPriority,
+        handler: impl Fn(&mut E) + Send + Sync + 'static,
+    ) -> HandlerId {
+        self.events
+            .subscribe_as(Some(&self.owner), priority, handler)
+    }
+
+    /// The server's event bus, for posting events or keeping it to subscribe later.
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    /// The server's scheduler. Keep a clone to schedule tasks after enabling.
+    pub fn scheduler(&self) -> &PluginScheduler {
+        &self.scheduler
+    }
+
+    /// The server's permissions. Keep a clone to check nodes after enabling.
+    pub fn permissions(&self) -> &Arc<Permissions> {
+        &self.permissions
+    }
+
+    /// Every player that has joined the server. Keep a clone to look players up after
+    /// enabling.
+    pub fn players(&self) -> &Arc<PlayerDirectory> {
+        &self.players
+    }
+
+    /// Registers a permission node owned by the plugin. Returns `false` if the node is
+    /// already registered.
+    pub fn register_permission(&mut self, mut node: PermissionNode) -> bool {
+        node.owner = Some(self.owner.clone());
+        self.permissions.register(node)
+    }
+
+    pub fn register_command(&mut self, command: PluginCommand) {
+        self.commands.push(command);
+    }
+
+    /// Hands the registered commands over to the server.
+    pub fn into_commands(self) -> Vec<PluginCommand> {
+        self.commands
+    }
+}
+
+/// Exported by every plugin under [`DECLARATION_SYMBOL`]. The version fields come first and
+/// the layout is fixed so the server can read them from plugins built differently.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub api_version: u32,
+    pub rustc_version: &'static str,
+    /// Installs the server's logger in the plugin, whose copy of the `log` crate has its own
+    /// global logger, and creates the plugin.
+    pub create: fn(logger: &'static dyn Log, level: LevelFilter) -> Box<dyn Plugin>,
+}
+
+/// Exports a plugin created by `$constructor`, a `fn() -> impl Plugin`.
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:path) => {
+        #[unsafe(no_mangle)]
+        pub static AMETHYST_PLUGIN: $crate::PluginDeclaration = $crate::PluginDeclaration {
+            api_version: $crate::API_VERSION,
+            rustc_version: $crate::RUSTC_VERSION,
+            create: __amethyst_plugin_create,
+        };
+
+        fn __amethyst_plugin_create(
+            logger: &'static dyn ::log::Log,
+            level: ::log::LevelFilter,
+        ) -> ::std::boxed::Box<dyn $crate::Plugin> {
+            let _ = ::log::set_logger(logger);
+            ::log::set_max_level(level);
+            ::std::boxed::Box::new($constructor())
+        }
+    };
+}
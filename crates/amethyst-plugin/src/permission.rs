@@ -0,0 +1,270 @@
+//! Permission nodes: dotted names such as `amethyst.command.kick` that players are granted
+//! through groups or directly, in the server's `permissions.json`.
+//!
+//! A granted pattern is a node, a prefix ending in `.*` that matches every node below it, or
+//! `*` for every node. A leading `-` denies instead. When several patterns match, the most
+//! specific one decides, and a denial beats a grant that is just as specific. A player's own
+//! patterns come before those of their groups, earlier groups before later ones, and a group
+//! before the groups it inherits from. Every player is in the `default` group.
+//!
+//! Nodes nobody was granted or denied fall back to their default, which usually is an
+//! operator level. The console and remote administration hold every node.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{RwLock, RwLockReadGuard};
+
+/// Group every player is in.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// How deep group inheritance is followed, which also stops inheritance cycles.
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+/// Who holds a node that was neither granted nor denied to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionDefault {
+    /// Players whose operator level is at least this, so 0 means everyone.
+    OpLevel(u8),
+    /// Only players the node is granted to.
+    Nobody,
+}
+
+/// A node registered by the server or a plugin, so it can be listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionNode {
+    pub node: String,
+    pub description: String,
+    pub default: PermissionDefault,
+    /// Plugin that registered the node, `None` for the server.
+    pub owner: Option<String>,
+}
+
+impl PermissionNode {
+    pub fn new(node: impl Into<String>, default: PermissionDefault) -> Self {
+        Self {
+            node: node.into(),
+            description: String::new(),
+            default,
+            owner: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    /// Patterns granted, or denied with a leading `-`.
+    pub permissions: Vec<String>,
+    /// Groups whose patterns apply after this group's own.
+    pub inherits: Vec<String>,
+}
+
+/// Groups and patterns of one player. The XUID, when known, identifies the player even after
+/// a name change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerPermissions {
+    pub name: String,
+    pub xuid: Option<String>,
+    pub groups: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl PlayerPermissions {
+    fn matches(&self, subject: &PermissionSubject) -> bool {
+        match (self.xuid.as_deref(), subject.xuid) {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => self.name.eq_ignore_ascii_case(subject.name),
+        }
+    }
+}
+
+/// The contents of `permissions.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PermissionConfig {
+    /// Groups by name, which is case-insensitive.
+    pub groups: HashMap<String, Group>,
+    pub players: Vec<PlayerPermissions>,
+}
+
+impl PermissionConfig {
+    fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.get(name).or_else(|| {
+            self.groups
+                .iter()
+                .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+                .map(|(_, group)| group)
+        })
+    }
+
+    /// Groups that are inherited from or assigned to a player but not defined.
+    pub fn undefined_groups(&self) -> Vec<String> {
+        let mut undefined: Vec<String> = self
+            .groups
+            .values()
+            .flat_map(|group| &group.inherits)
+            .chain(self.players.iter().flat_map(|player| &player.groups))
+            .filter(|name| self.group(name).is_none())
+            .cloned()
+            .collect();
+        undefined.sort();
+        undefined.dedup();
+        undefined
+    }
+
+    /// Whether the patterns for `subject` grant or deny `node`, or `None` if none matches.
+    fn resolve(&self, subject: &PermissionSubject, node: &str) -> Option<bool> {
+        let player = self.players.iter().find(|player| player.matches(subject));
+        if let Some(granted) = player.and_then(|player| best_match(&player.permissions, node)) {
+            return Some(granted);
+        }
+        player
+            .into_iter()
+            .flat_map(|player| &player.groups)
+            .map(String::as_str)
+            .chain(std::iter::once(DEFAULT_GROUP))
+            .find_map(|group| self.resolve_group(group, node, 0))
+    }
+
+    fn resolve_group(&self, name: &str, node: &str, depth: usize) -> Option<bool> {
+        if depth > MAX_INHERITANCE_DEPTH {
+            return None;
+        }
+        let group = self.group(name)?;
+        best_match(&group.permissions, node).or_else(|| {
+            group
+                .inherits
+                .iter()
+                .find_map(|parent| self.resolve_group(parent, node, depth + 1))
+        })
+    }
+}
+
+/// Whoever a node is checked for: a player, with their operator level, or 0 if they are not
+/// an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionSubject<'a> {
+    pub name: &'a str,
+    pub xuid: Option<&'a str>,
+    pub op_level: u8,
+}
+
+/// Whether `pattern`, without a leading `-`, matches `node`, and how specifically: the length
+/// of what it matched.
+fn specificity(pattern: &str, node: &str) -> Option<usize> {
+    if pattern == "*" {
+        return Some(0);
+    }
+    if let Some(prefix) = pattern.strip_suffix(".*") {
+        let below = node.len() > prefix.len()
+            && node.as_bytes()[prefix.len()] == b'.'
+            && node[..prefix.len()].eq_ignore_ascii_case(prefix);
+        return below.then_some(prefix.len() + 1);
+    }
+    pattern.eq_ignore_ascii_case(node).then_some(usize::MAX)
+}
+
+/// Whether `pattern` matches `node`, ignoring a leading `-`.
+pub fn matches(pattern: &str, node: &str) -> bool {
+    specificity(pattern.strip_prefix('-').unwrap_or(pattern), node).is_some()
+}
+
+/// Whether the most specific of `patterns` that matches `node` grants it.
+fn best_match(patterns: &[String], node: &str) -> Option<bool> {
+    patterns
+        .iter()
+        .filter_map(|pattern| {
+            let (granted, pattern) = match pattern.strip_prefix('-') {
+                Some(pattern) => (false, pattern),
+                None => (true, pattern.as_str()),
+            };
+            specificity(pattern, node).map(|specificity| (specificity, !granted))
+        })
+        .max()
+        .map(|(_, denied)| !denied)
+}
+
+/// The nodes registered by the server and plugins, and who holds them.
+#[derive(Debug, Default)]
+pub struct Permissions {
+    nodes: RwLock<BTreeMap<String, PermissionNode>>,
+    config: RwLock<PermissionConfig>,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `node`. Returns `false` without registering anything if it is already
+    /// registered.
+    pub fn register(&self, node: PermissionNode) -> bool {
+        let mut nodes = self.nodes.write().unwrap_or_else(|e| e.into_inner());
+        let key = node.node.to_ascii_lowercase();
+        if nodes.contains_key(&key) {
+            return false;
+        }
+        nodes.insert(key, node);
+        true
+    }
+
+    /// Removes the nodes registered by plugin `owner`.
+    pub fn unregister_owner(&self, owner: &str) {
+        self.nodes
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, node| node.owner.as_deref() != Some(owner));
+    }
+
+    /// Registered nodes, sorted by name.
+    pub fn nodes(&self) -> Vec<PermissionNode> {
+        self.read_nodes().values().cloned().collect()
+    }
+
+    pub fn node(&self, node: &str) -> Option<PermissionNode> {
+        self.read_nodes().get(&node.to_ascii_lowercase()).cloned()
+    }
+
+    fn read_nodes(&self) -> RwLockReadGuard<'_, BTreeMap<String, PermissionNode>> {
+        self.nodes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn config(&self) -> PermissionConfig {
+        self.read_config().clone()
+    }
+
+    /// Replaces the groups and players, e.g. after `permissions.json` changed.
+    pub fn set_config(&self, config: PermissionConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    fn read_config(&self) -> RwLockReadGuard<'_, PermissionConfig> {
+        self.config.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether `subject` holds `node`, falling back to the default it was registered with.
+    /// Nodes that are not registered default to [`PermissionDefault::Nobody`].
+    pub fn has(&self, subject: &PermissionSubject, node: &str) -> bool {
+        let default = self
+            .node(node)
+            .map_or(PermissionDefault::Nobody, |node| node.default);
+        self.check(subject, node, default)
+    }
+
+    /// Whether `subject` holds `node`, falling back to `default`.
+    pub fn check(
+        &self,
+        subject: &PermissionSubject,
+        node: &str,
+        default: PermissionDefault,
+    ) -> bool {
+        self.read_config()
+            .resolve(subject, node)
+            .unwrap_or(match default {
+                PermissionDefault::OpLevel(level) => subject.op_level >= level,
+                PermissionDefault::Nobody => false,
+            })
+    }
+}
@@ -0,0 +1,176 @@
+//! Every player that has joined the server, by UUID, XUID and name, kept in the server's
+//! `players.json`.
+//!
+//! Bedrock names can change, so the XUID identifies an account when it is known, and the
+//! UUID of its login identity otherwise. A profile remembers the names its player had before,
+//! so commands and plugins can still find someone by the name they were banned under.
+
+use std::sync::{RwLock, RwLockReadGuard};
+
+/// A name a player had before their current one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PastName {
+    pub name: String,
+    /// When the player was last seen with it, in seconds since the Unix epoch.
+    pub until: u64,
+}
+
+/// What the server knows about one player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerProfile {
+    /// Identity UUID from the login chain, hyphenated.
+    pub uuid: String,
+    pub xuid: Option<String>,
+    /// Name the player last joined with.
+    pub name: String,
+    /// Older names, oldest first.
+    pub past_names: Vec<PastName>,
+    /// In seconds since the Unix epoch.
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+impl PlayerProfile {
+    /// Whether this is the account that logged in with `uuid` and `xuid`.
+    fn is_account(&self, uuid: &str, xuid: Option<&str>) -> bool {
+        match (self.xuid.as_deref(), xuid) {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => self.uuid.eq_ignore_ascii_case(uuid),
+        }
+    }
+
+    /// When the player was last seen named `name`, if they ever were.
+    fn named_until(&self, name: &str) -> Option<u64> {
+        if self.name.eq_ignore_ascii_case(name) {
+            return Some(self.last_seen);
+        }
+        self.past_names
+            .iter()
+            .rev()
+            .find(|past| past.name.eq_ignore_ascii_case(name))
+            .map(|past| past.until)
+    }
+}
+
+/// How a join changed the directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    /// The player had not joined before.
+    New,
+    /// A known player joined with the same name.
+    Seen,
+    /// A known player joined under a new name; holds the previous one.
+    Renamed(String),
+}
+
+/// The profiles of every known player.
+#[derive(Debug, Default)]
+pub struct PlayerDirectory {
+    profiles: RwLock<Vec<PlayerProfile>>,
+}
+
+impl PlayerDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces every profile, e.g. with the contents of the file.
+    pub fn set_profiles(&self, profiles: Vec<PlayerProfile>) {
+        *self.profiles.write().unwrap_or_else(|e| e.into_inner()) = profiles;
+    }
+
+    /// Every profile, in the order players first joined.
+    pub fn profiles(&self) -> Vec<PlayerProfile> {
+        self.read().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Records a player joining at `now`, in seconds since the Unix epoch. A known account
+    /// that joins without its XUID keeps the one it had.
+    pub fn record(&self, uuid: &str, xuid: Option<&str>, name: &str, now: u64) -> Recorded {
+        let mut profiles = self.profiles.write().unwrap_or_else(|e| e.into_inner());
+        let Some(profile) = profiles
+            .iter_mut()
+            .find(|profile| profile.is_account(uuid, xuid))
+        else {
+            profiles.push(PlayerProfile {
+                uuid: uuid.to_string(),
+                xuid: xuid.map(str::to_string),
+                name: name.to_string(),
+                past_names: Vec::new(),
+                first_seen: now,
+                last_seen: now,
+            });
+            return Recorded::New;
+        };
+
+        // A UUID is derived from the device's identity key, so it changes when the account
+        // logs in from another device.
+        profile.uuid = uuid.to_string();
+        if let Some(xuid) = xuid {
+            profile.xuid = Some(xuid.to_string());
+        }
+        let recorded = if profile.name == name {
+            Recorded::Seen
+        } else {
+            let previous = std::mem::replace(&mut profile.name, name.to_string());
+            profile.past_names.push(PastName {
+                name: previous.clone(),
+                until: profile.last_seen,
+            });
+            Recorded::Renamed(previous)
+        };
+        profile.last_seen = now;
+        recorded
+    }
+
+    pub fn by_uuid(&self, uuid: &str) -> Option<PlayerProfile> {
+        self.read()
+            .iter()
+            .find(|profile| profile.uuid.eq_ignore_ascii_case(uuid))
+            .cloned()
+    }
+
+    pub fn by_xuid(&self, xuid: &str) -> Option<PlayerProfile> {
+        self.read()
+            .iter()
+            .find(|profile| profile.xuid.as_deref() == Some(xuid))
+            .cloned()
+    }
+
+    /// The player currently named `name`, ignoring case, or else the one that had the name
+    /// most recently.
+    pub fn by_name(&self, name: &str) -> Option<PlayerProfile> {
+        let profiles = self.read();
+        profiles
+            .iter()
+            .filter(|profile| profile.name.eq_ignore_ascii_case(name))
+            .max_by_key(|profile| profile.last_seen)
+            .or_else(|| {
+                profiles
+                    .iter()
+                    .filter_map(|profile| Some((profile, profile.named_until(name)?)))
+                    .max_by_key(|(_, until)| *until)
+                    .map(|(profile, _)| profile)
+            })
+            .cloned()
+    }
+
+    /// Finds a player by UUID, XUID or name, in that order.
+    pub fn lookup(&self, query: &str) -> Option<PlayerProfile> {
+        self.by_uuid(query)
+            .or_else(|| self.by_xuid(query))
+            .or_else(|| self.by_name(query))
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<PlayerProfile>> {
+        self.profiles.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Ticks the server runs per second.
+pub const TICKS_PER_SECOND: u64 = 20;
+
+/// Time between two ticks.
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+
+/// Identifies a scheduled task so it can be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(u64);
+
+/// Runs async tasks away from the tick thread.
+pub type Executor = Box<dyn Fn(Box<dyn FnOnce() + Send>) + Send + Sync>;
+
+type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// A sync task [`Scheduler::tick`] is in the middle of running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningTask {
+    pub id: TaskId,
+    /// Plugin that scheduled the task, `None` for the server itself.
+    pub owner: Option<Arc<str>>,
+}
+
+struct Task {
+    id: TaskId,
+    /// Plugin that scheduled the task, `None` for the server itself.
+    owner: Option<Arc<str>>,
+    next_run: u64,
+    period: Option<u64>,
+    is_async: bool,
+    /// Set while an async run is in progress, so a slow repeating task does not overlap
+    /// itself.
+    running: Arc<AtomicBool>,
+    callback: Callback,
+}
+
+/// Runs delayed and repeating tasks in step with server ticks.
+///
+/// Sync tasks run on the thread that calls [`tick`](Self::tick), one after another, so they
+/// should return quickly. Async tasks are handed to the executor instead and may block.
+/// Delays and periods are in ticks, see [`TICKS_PER_SECOND`].
+pub struct Scheduler {
+    tasks: Mutex<Vec<Task>>,
+    current_tick: AtomicU64,
+    next_id: AtomicU64,
+    executor: Executor,
+    /// Kept apart from `tasks` so it can be read while a task holds the tick thread.
+    running: Mutex<Option<RunningTask>>,
+    /// Time spent in sync tasks per owner, since [`take_task_times`](Self::take_task_times).
+    task_times: Mutex<HashMap<Option<Arc<str>>, Duration>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::with_executor(|job| {
+            std::thread::spawn(job);
+        })
+    }
+}
+
+impl Scheduler {
+    /// A scheduler that runs each async task on a new thread.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_executor(
+        executor: impl Fn(Box<dyn FnOnce() + Send>) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            tasks: Mutex::new(Vec::new()),
+            current_tick: AtomicU64::new(0),
+            next_id: AtomicU64::new(0),
+            executor: Box::new(executor),
+            running: Mutex::new(None),
+            task_times: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of ticks run so far.
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick.load(Ordering::Relaxed)
+    }
+
+    /// Runs `task` once, `delay` ticks from now. A delay of 0 runs it on the next tick.
+    pub fn run_later(&self, delay: u64, task: impl Fn() + Send + Sync + 'static) -> TaskId {
+        self.schedule(None, delay, None, false, Arc::new(task))
+    }
+
+    /// Runs `task` every `period` ticks, starting `delay` ticks from now, until cancelled.
+    pub fn run_repeating(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl Fn() + Send + Sync + 'static,
+    ) -> TaskId {
+        self.schedule(None, delay, Some(period), false, Arc::new(task))
+    }
+
+    /// Like [`run_later`](Self::run_later), but runs `task` on the executor.
+    pub fn run_later_async(&self, delay: u64, task: impl Fn() + Send + Sync + 'static) -> TaskId {
+        self.schedule(None, delay, None, true, Arc::new(task))
+    }
+
+    /// Like [`run_repeating`](Self::run_repeating), but runs `task` on the executor. A run is
+    /// skipped while the previous one is still in progress.
+    pub fn run_repeating_async(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl Fn() + Send + Sync + 'static,
+    ) -> TaskId {
+        self.schedule(None, delay, Some(period), true, Arc::new(task))
+    }
+
+    fn schedule(
+        &self,
+        owner: Option<Arc<str>>,
+        delay: u64,
+        period: Option<u64>,
+        is_async: bool,
+        callback: Callback,
+    ) -> TaskId {
+        let id = TaskId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.lock().push(Task {
+            id,
+            owner,
+            next_run: self.current_tick() + delay.max(1),
+            period: period.map(|period| period.max(1)),
+            is_async,
+            running: Arc::new(AtomicBool::new(false)),
+            callback,
+        });
+        id
+    }
+
+    /// Cancels a task. Returns `false` if it already ran or was cancelled.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        let mut tasks = self.lock();
+        let before = tasks.len();
+        tasks.retain(|task| task.id != id);
+        tasks.len() != before
+    }
+
+    /// Cancels every task scheduled by `owner`.
+    pub fn cancel_owner(&self, owner: &str) {
+        self.lock()
+            .retain(|task| task.owner.as_deref() != Some(owner));
+    }
+
+    /// Number of tasks waiting to run.
+    pub fn pending(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Tick the earliest waiting task is due on, or `None` if nothing is scheduled. Lets the
+    /// caller sleep through ticks on which nothing would run.
+    pub fn next_due(&self) -> Option<u64> {
+        self.lock().iter().map(|task| task.next_run).min()
+    }
+
+    /// Advances to the next tick and runs the tasks due on it, in the order they were
+    /// scheduled.
+    ///
+    /// Tasks may schedule and cancel tasks themselves, including their own repetitions;
+    /// tasks scheduled while ticking run on a later tick.
+    pub fn tick(&self) {
+        let tick = self.current_tick.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut due = Vec::new();
+        {
+            let mut tasks = self.lock();
+            tasks.retain_mut(|task| {
+                if task.next_run > tick {
+                    return true;
+                }
+                due.push((
+                    task.id,
+                    task.owner.clone(),
+                    task.is_async,
+                    Arc::clone(&task.running),
+                    Arc::clone(&task.callback),
+                ));
+                match task.period {
+                    Some(period) => {
+                        task.next_run = tick + period;
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+        due.sort_by_key(|(id, ..)| id.0);
+
+        for (id, owner, is_async, running, callback) in due {
+            if !is_async {
+                self.set_running(Some(RunningTask {
+                    id,
+                    owner: owner.clone(),
+                }));
+                let start = Instant::now();
+                callback();
+                let elapsed = start.elapsed();
+                self.set_running(None);
+                *self
+                    .task_times
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .entry(owner)
+                    .or_default() += elapsed;
+            } else if !running.swap(true, Ordering::Acquire) {
+                (self.executor)(Box::new(move || {
+                    callback();
+                    running.store(false, Ordering::Release);
+                }));
+            }
+        }
+    }
+
+    /// The sync task running right now, if any, to tell what a slow tick is stuck on.
+    pub fn running_task(&self) -> Option<RunningTask> {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Time spent in sync tasks since the last call, per owner: `None` for the server
+    /// itself. Owners whose tasks did not run are left out.
+    pub fn take_task_times(&self) -> Vec<(Option<Arc<str>>, Duration)> {
+        self.task_times
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .collect()
+    }
+
+    fn set_running(&self, task: Option<RunningTask>) {
+        *self.running.lock().unwrap_or_else(|e| e.into_inner()) = task;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Task>> {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A plugin's view of the [`Scheduler`]. Tasks scheduled through it are cancelled when the
+/// plugin is disabled.
+#[derive(Clone)]
+pub struct PluginScheduler {
+    owner: Arc<str>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl PluginScheduler {
+    pub fn new(owner: &str, scheduler: Arc<Scheduler>) -> Self {
+        Self {
+            owner: Arc::from(owner),
+            scheduler,
+        }
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.scheduler.current_tick()
+    }
+
+    /// See [`Scheduler::run_later`].
+    pub fn run_later(&self, delay: u64, task: impl Fn() + Send + Sync + 'static) -> TaskId {
+        self.schedule(delay, None, false, Arc::new(task))
+    }
+
+    /// See [`Scheduler::run_repeating`].
+    pub fn run_repeating(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl Fn() + Send + Sync + 'static,
+    ) -> TaskId {
+        self.schedule(delay, Some(period), false, Arc::new(task))
+    }
+
+    /// See [`Scheduler::run_later_async`].
+    pub fn run_later_async(&self, delay: u64, task: impl Fn() + Send + Sync + 'static) -> TaskId {
+        self.schedule(delay, None, true, Arc::new(task))
+    }
+
+    /// See [`Scheduler::run_repeating_async`].
+    pub fn run_repeating_async(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl Fn() + Send + Sync + 'static,
+    ) -> TaskId {
+        self.schedule(delay, Some(period), true, Arc::new(task))
+    }
+
+    pub fn cancel(&self, id: TaskId) -> bool {
+        self.scheduler.cancel(id)
+    }
+
+    fn schedule(
+        &self,
+        delay: u64,
+        period: Option<u64>,
+        is_async: bool,
+        callback: Callback,
+    ) -> TaskId {
+        self.scheduler.schedule(
+            Some(Arc::clone(&self.owner)),
+            delay,
+            period,
+            is_async,
+            callback,
+        )
+    }
+}
@@ -0,0 +1,96 @@
+use amethyst_plugin::event::{PlayerChat, ServerStarted};
+use amethyst_plugin::{EventBus, EventPriority};
+use std::sync::{Arc, Mutex};
+
+fn chat(message: &str) -> PlayerChat {
+    PlayerChat {
+        name: "Steve".to_string(),
+        xuid: None,
+        message: message.to_string(),
+        format: "<{name}> {message}".to_string(),
+        cancelled: false,
+    }
+}
+
+#[test]
+fn handlers_run_in_priority_order() {
+    let bus = EventBus::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for (priority, label) in [
+        (EventPriority::Monitor, "monitor"),
+        (EventPriority::Low, "low"),
+        (EventPriority::Highest, "highest"),
+        (EventPriority::Normal, "normal-1"),
+        (EventPriority::Normal, "normal-2"),
+    ] {
+        let order = Arc::clone(&order);
+        bus.subscribe(priority, move |_: &mut PlayerChat| {
+            order.lock().unwrap().push(label)
+        });
+    }
+
+    bus.post(chat("hi"));
+    assert_eq!(
+        *order.lock().unwrap(),
+        ["low", "normal-1", "normal-2", "highest", "monitor"]
+    );
+}
+
+#[test]
+fn handlers_can_mutate_and_cancel() {
+    let bus = EventBus::new();
+    bus.subscribe(EventPriority::Low, |chat: &mut PlayerChat| {
+        chat.message = chat.message.replace("darn", "****");
+    });
+    bus.subscribe(EventPriority::Normal, |chat: &mut PlayerChat| {
+        chat.cancelled = chat.message.contains("spam");
+    });
+
+    let event = bus.post(chat("darn it"));
+    assert_eq!(event.message, "**** it");
+    assert!(!event.cancelled);
+    assert!(bus.post(chat("spam")).cancelled);
+}
+
+#[test]
+fn handlers_only_receive_their_event_type() {
+    let bus = EventBus::new();
+    let calls = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&calls);
+    bus.subscribe(EventPriority::Normal, move |_: &mut ServerStarted| {
+        *counter.lock().unwrap() += 1
+    });
+
+    bus.post(chat("hi"));
+    assert_eq!(*calls.lock().unwrap(), 0);
+    bus.post(ServerStarted);
+    assert_eq!(*calls.lock().unwrap(), 1);
+}
+
+#[test]
+fn owned_handlers_are_removed_together() {
+    let bus = EventBus::new();
+    bus.subscribe_as(
+        Some("plugin"),
+        EventPriority::Normal,
+        |chat: &mut PlayerChat| chat.cancelled = true,
+    );
+    let id = bus.subscribe(EventPriority::Normal, |_: &mut ServerStarted| {});
+    assert!(bus.has_handlers::<PlayerChat>());
+
+    bus.unsubscribe_owner("plugin");
+    assert!(!bus.has_handlers::<PlayerChat>());
+    assert!(!bus.post(chat("hi")).cancelled);
+    assert!(bus.unsubscribe(id));
+    assert!(!bus.has_handlers::<ServerStarted>());
+}
+
+#[test]
+fn chat_is_rendered_with_its_format() {
+    let mut chat = chat("hi {name}");
+    chat.name = "{message}".to_string();
+    assert_eq!(chat.render(), "<{message}> hi {name}");
+
+    chat.format = "{unknown} {name: {message}".to_string();
+    assert_eq!(chat.render(), "{unknown} {name: hi {name}");
+}
@@ -0,0 +1,177 @@
+use amethyst_plugin::permission::{
+    matches, Group, PermissionConfig, PermissionDefault, PermissionNode, PermissionSubject,
+    Permissions, PlayerPermissions,
+};
+use std::collections::HashMap;
+
+fn steve(op_level: u8) -> PermissionSubject<'static> {
+    PermissionSubject {
+        name: "Steve",
+        xuid: None,
+        op_level,
+    }
+}
+
+fn group(permissions: &[&str], inherits: &[&str]) -> Group {
+    Group {
+        permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        inherits: inherits.iter().map(|g| g.to_string()).collect(),
+    }
+}
+
+fn permissions(groups: HashMap<String, Group>, players: Vec<PlayerPermissions>) -> Permissions {
+    let permissions = Permissions::new();
+    permissions.set_config(PermissionConfig { groups, players });
+    permissions
+}
+
+fn player(groups: &[&str], permissions: &[&str]) -> PlayerPermissions {
+    PlayerPermissions {
+        name: "steve".to_string(),
+        xuid: None,
+        groups: groups.iter().map(|g| g.to_string()).collect(),
+        permissions: permissions.iter().map(|p| p.to_string()).collect(),
+    }
+}
+
+#[test]
+fn wildcards_match_the_nodes_below_them() {
+    assert!(matches("*", "amethyst.command.kick"));
+    assert!(matches("amethyst.*", "amethyst.command.kick"));
+    assert!(matches("Amethyst.Command.*", "amethyst.command.kick"));
+    assert!(matches("-amethyst.command.kick", "amethyst.command.kick"));
+    assert!(!matches("amethyst.command.*", "amethyst.command"));
+    assert!(!matches("amethyst.command.*", "amethyst.commands.kick"));
+    assert!(!matches(
+        "amethyst.command.kick",
+        "amethyst.command.kickall"
+    ));
+}
+
+#[test]
+fn the_most_specific_pattern_decides() {
+    let permissions = permissions(
+        HashMap::new(),
+        vec![player(
+            &[],
+            &["amethyst.command.*", "-amethyst.command.ban", "-*"],
+        )],
+    );
+    let default = PermissionDefault::OpLevel(0);
+    assert!(permissions.check(&steve(0), "amethyst.command.kick", default));
+    assert!(!permissions.check(&steve(0), "amethyst.command.ban", default));
+    assert!(!permissions.check(&steve(0), "other.node", default));
+}
+
+#[test]
+fn a_denial_beats_an_equally_specific_grant() {
+    let permissions = permissions(
+        HashMap::new(),
+        vec![player(
+            &[],
+            &["amethyst.command.kick", "-amethyst.command.kick"],
+        )],
+    );
+    assert!(!permissions.check(
+        &steve(4),
+        "amethyst.command.kick",
+        PermissionDefault::OpLevel(0)
+    ));
+}
+
+#[test]
+fn players_override_groups_and_groups_override_what_they_inherit() {
+    let groups = HashMap::from([
+        (
+            "default".to_string(),
+            group(&["amethyst.command.list"], &[]),
+        ),
+        (
+            "moderator".to_string(),
+            group(
+                &["amethyst.command.kick", "-amethyst.command.list"],
+                &["helper"],
+            ),
+        ),
+        (
+            "helper".to_string(),
+            group(&["-amethyst.command.kick", "amethyst.command.tp"], &[]),
+        ),
+    ]);
+    let permissions = permissions(
+        groups,
+        vec![player(&["Moderator"], &["-amethyst.command.tp"])],
+    );
+    let nobody = PermissionDefault::Nobody;
+    assert!(permissions.check(&steve(0), "amethyst.command.kick", nobody));
+    assert!(!permissions.check(&steve(0), "amethyst.command.list", nobody));
+    assert!(!permissions.check(&steve(0), "amethyst.command.tp", nobody));
+
+    let alex = PermissionSubject {
+        name: "Alex",
+        xuid: None,
+        op_level: 0,
+    };
+    assert!(permissions.check(&alex, "amethyst.command.list", nobody));
+    assert!(!permissions.check(&alex, "amethyst.command.kick", nobody));
+}
+
+#[test]
+fn inheritance_cycles_end() {
+    let groups = HashMap::from([
+        ("a".to_string(), group(&[], &["b"])),
+        ("b".to_string(), group(&[], &["a"])),
+    ]);
+    let permissions = permissions(groups, vec![player(&["a"], &[])]);
+    assert!(!permissions.check(&steve(0), "node", PermissionDefault::Nobody));
+}
+
+#[test]
+fn unmatched_nodes_fall_back_to_their_default() {
+    let permissions = Permissions::new();
+    assert!(permissions.register(
+        PermissionNode::new("plugin.fly", PermissionDefault::OpLevel(2)).description("Fly")
+    ));
+    assert!(!permissions.register(PermissionNode::new("Plugin.Fly", PermissionDefault::Nobody)));
+    assert!(!permissions.has(&steve(1), "plugin.fly"));
+    assert!(permissions.has(&steve(2), "plugin.fly"));
+    assert!(!permissions.has(&steve(4), "plugin.unregistered"));
+}
+
+#[test]
+fn nodes_are_unregistered_with_their_owner() {
+    let permissions = Permissions::new();
+    let mut node = PermissionNode::new("plugin.fly", PermissionDefault::Nobody);
+    node.owner = Some("plugin".to_string());
+    permissions.register(node);
+    permissions.register(PermissionNode::new(
+        "amethyst.fly",
+        PermissionDefault::Nobody,
+    ));
+    permissions.unregister_owner("plugin");
+    let nodes: Vec<String> = permissions.nodes().into_iter().map(|n| n.node).collect();
+    assert_eq!(nodes, ["amethyst.fly"]);
+}
+
+#[test]
+fn xuids_identify_renamed_players() {
+    let mut entry = player(&[], &["plugin.fly"]);
+    entry.name = "OldName".to_string();
+    entry.xuid = Some("2535".to_string());
+    let permissions = permissions(HashMap::new(), vec![entry]);
+    let renamed = PermissionSubject {
+        name: "NewName",
+        xuid: Some("2535"),
+        op_level: 0,
+    };
+    assert!(permissions.has(&renamed, "plugin.fly"));
+}
+
+#[test]
+fn undefined_groups_are_reported() {
+    let config = PermissionConfig {
+        groups: HashMap::from([("moderator".to_string(), group(&[], &["helper"]))]),
+        players: vec![player(&["admin", "Moderator"], &[])],
+    };
+    assert_eq!(config.undefined_groups(), ["admin", "helper"]);
+}
@@ -0,0 +1,82 @@
+use amethyst_plugin::player::{PlayerDirectory, Recorded};
+
+const STEVE_UUID: &str = "8d1f2e3a-0000-4000-8000-000000000001";
+const STEVE_XUID: &str = "2535412345678901";
+
+#[test]
+fn first_join_creates_a_profile() {
+    let directory = PlayerDirectory::new();
+    assert_eq!(
+        directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100),
+        Recorded::New
+    );
+    let profile = directory.by_xuid(STEVE_XUID).unwrap();
+    assert_eq!(profile.name, "Steve");
+    assert_eq!(profile.uuid, STEVE_UUID);
+    assert_eq!((profile.first_seen, profile.last_seen), (100, 100));
+    assert_eq!(
+        directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 200),
+        Recorded::Seen
+    );
+    assert_eq!(directory.len(), 1);
+    assert_eq!(directory.by_uuid(STEVE_UUID).unwrap().last_seen, 200);
+}
+
+#[test]
+fn renames_keep_the_previous_name() {
+    let directory = PlayerDirectory::new();
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100);
+    assert_eq!(
+        directory.record(STEVE_UUID, Some(STEVE_XUID), "Alex", 200),
+        Recorded::Renamed("Steve".to_string())
+    );
+    let profile = directory.by_name("steve").unwrap();
+    assert_eq!(profile.name, "Alex");
+    assert_eq!(profile.past_names.len(), 1);
+    assert_eq!(profile.past_names[0].name, "Steve");
+    assert_eq!(profile.past_names[0].until, 100);
+}
+
+#[test]
+fn xuid_identifies_the_account_across_devices() {
+    let directory = PlayerDirectory::new();
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100);
+    let other_device = "8d1f2e3a-0000-4000-8000-000000000002";
+    assert_eq!(
+        directory.record(other_device, Some(STEVE_XUID), "Steve", 200),
+        Recorded::Seen
+    );
+    assert_eq!(directory.len(), 1);
+    assert_eq!(directory.by_xuid(STEVE_XUID).unwrap().uuid, other_device);
+}
+
+#[test]
+fn current_name_wins_over_a_past_one() {
+    let directory = PlayerDirectory::new();
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100);
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Alex", 200);
+    let other_uuid = "8d1f2e3a-0000-4000-8000-000000000003";
+    directory.record(other_uuid, Some("2535400000000002"), "Steve", 300);
+    assert_eq!(directory.by_name("Steve").unwrap().uuid, other_uuid);
+}
+
+#[test]
+fn lookup_accepts_uuid_xuid_or_name() {
+    let directory = PlayerDirectory::new();
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100);
+    for query in [STEVE_UUID, STEVE_XUID, "STEVE"] {
+        assert_eq!(directory.lookup(query).unwrap().name, "Steve");
+    }
+    assert!(directory.lookup("Herobrine").is_none());
+}
+
+#[test]
+fn offline_join_keeps_the_known_xuid() {
+    let directory = PlayerDirectory::new();
+    directory.record(STEVE_UUID, Some(STEVE_XUID), "Steve", 100);
+    directory.record(STEVE_UUID, None, "Steve", 200);
+    assert_eq!(
+        directory.by_uuid(STEVE_UUID).unwrap().xuid.as_deref(),
+        Some(STEVE_XUID)
+    );
+}
@@ -0,0 +1,138 @@
+use amethyst_plugin::{PluginScheduler, Scheduler};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Log = Arc<Mutex<Vec<(&'static str, u64)>>>;
+
+/// A scheduler that runs async tasks inline, so tests stay deterministic.
+fn scheduler() -> Arc<Scheduler> {
+    Arc::new(Scheduler::with_executor(|job| job()))
+}
+
+/// A task that records its label and the tick it ran on.
+fn record(
+    scheduler: &Arc<Scheduler>,
+    log: &Log,
+    label: &'static str,
+) -> impl Fn() + Send + Sync + 'static {
+    let scheduler = Arc::clone(scheduler);
+    let log = Arc::clone(log);
+    move || log.lock().unwrap().push((label, scheduler.current_tick()))
+}
+
+fn run_ticks(scheduler: &Scheduler, ticks: u64) {
+    for _ in 0..ticks {
+        scheduler.tick();
+    }
+}
+
+#[test]
+fn delayed_tasks_run_once_on_their_tick() {
+    let scheduler = scheduler();
+    let log = Log::default();
+    scheduler.run_later(3, record(&scheduler, &log, "later"));
+    scheduler.run_later(0, record(&scheduler, &log, "next"));
+    scheduler.run_later_async(2, record(&scheduler, &log, "async"));
+
+    run_ticks(&scheduler, 10);
+    assert_eq!(
+        *log.lock().unwrap(),
+        [("next", 1), ("async", 2), ("later", 3)]
+    );
+    assert_eq!(scheduler.pending(), 0);
+}
+
+#[test]
+fn repeating_tasks_run_until_cancelled() {
+    let scheduler = scheduler();
+    let log = Log::default();
+    let id = scheduler.run_repeating(2, 3, record(&scheduler, &log, "repeat"));
+
+    run_ticks(&scheduler, 8);
+    assert!(scheduler.cancel(id));
+    run_ticks(&scheduler, 10);
+    assert_eq!(
+        *log.lock().unwrap(),
+        [("repeat", 2), ("repeat", 5), ("repeat", 8)]
+    );
+    assert!(!scheduler.cancel(id));
+}
+
+#[test]
+fn tasks_due_together_run_in_scheduling_order() {
+    let scheduler = scheduler();
+    let log = Log::default();
+    scheduler.run_repeating(1, 1, record(&scheduler, &log, "first"));
+    scheduler.run_later(2, record(&scheduler, &log, "second"));
+
+    run_ticks(&scheduler, 2);
+    assert_eq!(
+        *log.lock().unwrap(),
+        [("first", 1), ("first", 2), ("second", 2)]
+    );
+}
+
+#[test]
+fn tasks_can_schedule_more_tasks() {
+    let scheduler = scheduler();
+    let log = Log::default();
+    let inner = record(&scheduler, &log, "inner");
+    let inner = Arc::new(inner);
+    let handle = Arc::clone(&scheduler);
+    scheduler.run_later(1, move || {
+        let inner = Arc::clone(&inner);
+        handle.run_later(1, move || inner());
+    });
+
+    run_ticks(&scheduler, 3);
+    assert_eq!(*log.lock().unwrap(), [("inner", 2)]);
+}
+
+#[test]
+fn plugin_tasks_are_cancelled_with_their_owner() {
+    let scheduler = scheduler();
+    let log = Log::default();
+    let plugin = PluginScheduler::new("plugin", Arc::clone(&scheduler));
+    plugin.run_repeating(1, 1, record(&scheduler, &log, "plugin"));
+    plugin.run_later_async(5, record(&scheduler, &log, "plugin-async"));
+    scheduler.run_later(2, record(&scheduler, &log, "server"));
+
+    run_ticks(&scheduler, 1);
+    scheduler.cancel_owner("plugin");
+    run_ticks(&scheduler, 10);
+    assert_eq!(*log.lock().unwrap(), [("plugin", 1), ("server", 2)]);
+}
+
+#[test]
+fn next_due_is_the_earliest_waiting_task() {
+    let scheduler = scheduler();
+    assert_eq!(scheduler.next_due(), None);
+    scheduler.run_later(5, || {});
+    let id = scheduler.run_repeating(2, 4, || {});
+    assert_eq!(scheduler.next_due(), Some(2));
+
+    run_ticks(&scheduler, 2);
+    assert_eq!(scheduler.next_due(), Some(5));
+    scheduler.cancel(id);
+    run_ticks(&scheduler, 3);
+    assert_eq!(scheduler.next_due(), None);
+}
+
+#[test]
+fn sync_task_time_is_counted_per_owner() {
+    let scheduler = scheduler();
+    let plugin = PluginScheduler::new("plugin", Arc::clone(&scheduler));
+    plugin.run_later(1, || std::thread::sleep(Duration::from_millis(5)));
+    plugin.run_later_async(1, || std::thread::sleep(Duration::from_millis(50)));
+    scheduler.run_later(1, || {});
+
+    run_ticks(&scheduler, 1);
+    let mut times = scheduler.take_task_times();
+    times.sort();
+    let owners: Vec<_> = times.iter().map(|(owner, _)| owner.as_deref()).collect();
+    assert_eq!(owners, [None, Some("plugin")]);
+    let plugin_time = times[1].1;
+    assert!(plugin_time >= Duration::from_millis(5));
+    assert!(plugin_time < Duration::from_millis(50));
+    assert!(scheduler.take_task_times().is_empty());
+}
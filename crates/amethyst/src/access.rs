@@ -0,0 +1,606 @@
+use crate::trace;
+use amethyst_plugin::event::{PlayerJoin, PlayerLoginDenied};
+use amethyst_plugin::{EventBus, EventPriority};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const WHITELIST_FILE_NAME: &str = "whitelist.json";
+pub const OPS_FILE_NAME: &str = "ops.json";
+pub const BANNED_PLAYERS_FILE_NAME: &str = "banned-players.json";
+pub const BANNED_IPS_FILE_NAME: &str = "banned-ips.json";
+pub const MUTED_PLAYERS_FILE_NAME: &str = "muted-players.json";
+/// How often expired bans and mutes are removed from their lists.
+const BAN_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum AccessError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("JSON error in {path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Failed to watch access lists: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+/// A player as stored in the access lists. Bedrock gamertags are case-insensitive; the XUID,
+/// when known, identifies the account even after a name change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerEntry {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xuid: Option<String>,
+}
+
+impl PlayerEntry {
+    /// Whether the entry is the player joining as `name` with `xuid`. The XUID decides when
+    /// both are known, the name otherwise.
+    pub fn matches(&self, name: &str, xuid: Option<&str>) -> bool {
+        match (self.xuid.as_deref(), xuid) {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => self.name.eq_ignore_ascii_case(name),
+        }
+    }
+
+    /// Whether an entry is meant by `player`, whose name or XUID may be the one it was
+    /// added under.
+    fn refers_to(&self, player: &PlayerEntry) -> bool {
+        self.name.eq_ignore_ascii_case(&player.name)
+            || (self.xuid.is_some() && self.xuid == player.xuid)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpEntry {
+    #[serde(flatten)]
+    pub player: PlayerEntry,
+    #[serde(default = "default_op_level")]
+    pub level: u8,
+}
+
+fn default_op_level() -> u8 {
+    4
+}
+
+/// Details shared by player bans, IP bans and mutes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanDetails {
+    pub created: DateTime<Utc>,
+    /// Who issued the ban, e.g. an operator name or "Server".
+    pub source: String,
+    /// When the ban lifts, or `None` for a permanent ban.
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+    pub reason: String,
+}
+
+impl BanDetails {
+    pub fn new(source: String, reason: String, expires: Option<DateTime<Utc>>) -> Self {
+        Self {
+            created: Utc::now(),
+            source,
+            expires,
+            reason,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.expires.is_none_or(|expires| expires > Utc::now())
+    }
+
+    /// Appends the reason and expiry, if any, to a message shown to the player.
+    pub fn describe(&self, message: &mut String) {
+        if !self.reason.is_empty() {
+            message.push_str(&format!("\nReason: {}", self.reason));
+        }
+        if let Some(expires) = self.expires {
+            message.push_str(&format!("\nExpires: {}", expires.format("%Y-%m-%d %H:%M UTC")));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerBan {
+    #[serde(flatten)]
+    pub player: PlayerEntry,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+/// A player whose chat messages are not broadcast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerMute {
+    #[serde(flatten)]
+    pub player: PlayerEntry,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpBan {
+    pub ip: IpAddr,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+/// Why a player was refused at login.
+#[derive(Debug, Clone)]
+pub enum LoginDenied {
+    Banned(BanDetails),
+    IpBanned(BanDetails),
+    /// Not on the whitelist while it is enabled, with the configured disconnect message.
+    NotWhitelisted(String),
+}
+
+impl LoginDenied {
+    /// Disconnect message shown to the player.
+    pub fn message(&self) -> String {
+        let (mut message, details) = match self {
+            LoginDenied::Banned(details) => ("You are banned from this server".to_string(), details),
+            LoginDenied::IpBanned(details) => {
+                ("Your IP address is banned from this server".to_string(), details)
+            }
+            LoginDenied::NotWhitelisted(message) => return message.clone(),
+        };
+        details.describe(&mut message);
+        message
+    }
+}
+
+impl LoginDenied {
+    /// Short, stable name of the reason, as used in [`PlayerLoginDenied::reason`].
+    pub fn reason(&self) -> &'static str {
+        match self {
+            LoginDenied::Banned(_) => "banned",
+            LoginDenied::IpBanned(_) => "ip_banned",
+            LoginDenied::NotWhitelisted(_) => "not_whitelisted",
+        }
+    }
+}
+
+/// A JSON array of entries backed by a file.
+pub struct ListFile<T> {
+    path: PathBuf,
+    entries: RwLock<Vec<T>>,
+}
+
+impl<T: Serialize + DeserializeOwned + Clone> ListFile<T> {
+    /// Loads the list at `path`, creating an empty one if the file does not exist.
+    fn open(path: PathBuf) -> Result<Self, AccessError> {
+        let list = Self {
+            path,
+            entries: RwLock::new(Vec::new()),
+        };
+        if list.path.exists() {
+            list.reload()?;
+        } else {
+            list.save()?;
+        }
+        Ok(list)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> Vec<T> {
+        self.read().clone()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<T>> {
+        self.entries.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Replaces the in-memory entries with the file contents. On error the previous entries
+    /// are kept.
+    pub fn reload(&self) -> Result<(), AccessError> {
+        let content = fs::read_to_string(&self.path)?;
+        let entries = serde_json::from_str(&content).map_err(|source| AccessError::Json {
+            path: self.path.clone(),
+            source,
+        })?;
+        *self.entries.write().unwrap_or_else(|e| e.into_inner()) = entries;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), AccessError> {
+        let entries = self.read();
+        let content =
+            serde_json::to_string_pretty(&*entries).map_err(|source| AccessError::Json {
+                path: self.path.clone(),
+                source,
+            })?;
+        // Write to a temporary file and rename it over the list so the watcher never sees a
+        // half-written file.
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, content)?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    /// Applies `f` to the entries and saves the file if it reports a change.
+    fn update(&self, f: impl FnOnce(&mut Vec<T>) -> bool) -> Result<bool, AccessError> {
+        let changed = f(&mut self.entries.write().unwrap_or_else(|e| e.into_inner()));
+        if changed {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+}
+
+/// The whitelist, operator and ban lists, kept in sync with their JSON files.
+///
+/// Edits made through this API are saved immediately; edits made to the files by hand are
+/// picked up by [`AccessLists::watch`].
+pub struct AccessLists {
+    pub whitelist: ListFile<PlayerEntry>,
+    pub ops: ListFile<OpEntry>,
+    pub banned_players: ListFile<PlayerBan>,
+    pub banned_ips: ListFile<IpBan>,
+    pub muted_players: ListFile<PlayerMute>,
+    whitelist_enabled: AtomicBool,
+    whitelist_message: RwLock<String>,
+}
+
+impl AccessLists {
+    pub fn load(
+        dir: &Path,
+        whitelist_enabled: bool,
+        whitelist_message: String,
+    ) -> Result<Self, AccessError> {
+        Ok(Self {
+            whitelist: ListFile::open(dir.join(WHITELIST_FILE_NAME))?,
+            ops: ListFile::open(dir.join(OPS_FILE_NAME))?,
+            banned_players: ListFile::open(dir.join(BANNED_PLAYERS_FILE_NAME))?,
+            banned_ips: ListFile::open(dir.join(BANNED_IPS_FILE_NAME))?,
+            muted_players: ListFile::open(dir.join(MUTED_PLAYERS_FILE_NAME))?,
+            whitelist_enabled: AtomicBool::new(whitelist_enabled),
+            whitelist_message: RwLock::new(whitelist_message),
+        })
+    }
+
+    /// Parses the lists in `dir` without creating missing files, returning the number of
+    /// entries in each existing file.
+    pub fn check(dir: &Path) -> Vec<(PathBuf, Result<Option<usize>, AccessError>)> {
+        [
+            (WHITELIST_FILE_NAME, count_entries::<PlayerEntry> as fn(&Path) -> _),
+            (OPS_FILE_NAME, count_entries::<OpEntry>),
+            (BANNED_PLAYERS_FILE_NAME, count_entries::<PlayerBan>),
+            (BANNED_IPS_FILE_NAME, count_entries::<IpBan>),
+            (MUTED_PLAYERS_FILE_NAME, count_entries::<PlayerMute>),
+        ]
+        .into_iter()
+        .map(|(name, count)| {
+            let path = dir.join(name);
+            let result = count(&path);
+            (path, result)
+        })
+        .collect()
+    }
+
+    /// Reloads a list whenever its file changes on disk. Watching stops when the returned
+    /// watcher is dropped.
+    pub fn watch(self: &Arc<Self>, dir: &Path) -> Result<RecommendedWatcher, AccessError> {
+        let lists = Arc::downgrade(self);
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let (Ok(event), Some(lists)) = (res, lists.upgrade()) else {
+                return;
+            };
+            if !(event.kind.is_create() || event.kind.is_modify()) {
+                return;
+            }
+            for path in &event.paths {
+                let result = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(WHITELIST_FILE_NAME) => lists.whitelist.reload(),
+                    Some(OPS_FILE_NAME) => lists.ops.reload(),
+                    Some(BANNED_PLAYERS_FILE_NAME) => lists.banned_players.reload(),
+                    Some(BANNED_IPS_FILE_NAME) => lists.banned_ips.reload(),
+                    Some(MUTED_PLAYERS_FILE_NAME) => lists.muted_players.reload(),
+                    _ => continue,
+                };
+                match result {
+                    Ok(()) => debug!("Reloaded {}", path.display()),
+                    Err(e) => error!("Failed to reload {}: {}", path.display(), e),
+                }
+            }
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+
+    /// Registers the login checks on `events`, at the lowest priority so plugins see the
+    /// outcome and can still override it.
+    ///
+    /// A join that is still cancelled once every handler has run is logged and followed by a
+    /// [`PlayerLoginDenied`] event.
+    pub fn register_events(self: &Arc<Self>, events: &Arc<EventBus>) {
+        let access = Arc::clone(self);
+        events.subscribe(EventPriority::Lowest, move |join: &mut PlayerJoin| {
+            if let Err(denied) =
+                access.check_login(&join.name, join.xuid.as_deref(), join.address.ip())
+            {
+                join.cancelled = true;
+                join.kick_message = denied.message();
+            }
+        });
+
+        let access = Arc::clone(self);
+        // The bus owns the handler, so a strong reference would keep it alive forever.
+        let bus = Arc::downgrade(events);
+        events.subscribe(EventPriority::Monitor, move |join: &mut PlayerJoin| {
+            if !join.cancelled {
+                return;
+            }
+            let reason =
+                match access.check_login(&join.name, join.xuid.as_deref(), join.address.ip()) {
+                    Err(denied) => denied.reason(),
+                    Ok(()) => "plugin",
+                };
+            info!("Refused login of {} ({}): {}", join.name, join.address, reason);
+            if let Some(bus) = bus.upgrade() {
+                bus.post(PlayerLoginDenied {
+                    name: join.name.clone(),
+                    xuid: join.xuid.clone(),
+                    address: join.address,
+                    reason: reason.to_string(),
+                    message: join.kick_message.clone(),
+                });
+            }
+        });
+    }
+
+    pub fn whitelist_enabled(&self) -> bool {
+        self.whitelist_enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_whitelist_enabled(&self, enabled: bool) {
+        self.whitelist_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the disconnect message for players who are not whitelisted.
+    pub fn set_whitelist_message(&self, message: String) {
+        *self.whitelist_message.write().unwrap_or_else(|e| e.into_inner()) = message;
+    }
+
+    /// Decides whether a player may join. Operators bypass the whitelist but not bans.
+    pub fn check_login(
+        &self,
+        name: &str,
+        xuid: Option<&str>,
+        ip: IpAddr,
+    ) -> Result<(), LoginDenied> {
+        trace::span!("login_check", player = name, %ip);
+        if let Some(ban) = self.player_ban(name, xuid) {
+            return Err(LoginDenied::Banned(ban));
+        }
+        if let Some(ban) = self.ip_ban(ip) {
+            return Err(LoginDenied::IpBanned(ban));
+        }
+        if self.whitelist_enabled() && !self.is_whitelisted(name, xuid) && !self.is_op(name, xuid) {
+            let message = self.whitelist_message.read().unwrap_or_else(|e| e.into_inner());
+            return Err(LoginDenied::NotWhitelisted(message.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn is_whitelisted(&self, name: &str, xuid: Option<&str>) -> bool {
+        self.whitelist
+            .read()
+            .iter()
+            .any(|entry| entry.matches(name, xuid))
+    }
+
+    pub fn add_to_whitelist(&self, player: PlayerEntry) -> Result<bool, AccessError> {
+        self.whitelist.update(|entries| {
+            if entries
+                .iter()
+                .any(|e| e.matches(&player.name, player.xuid.as_deref()))
+            {
+                return false;
+            }
+            entries.push(player);
+            true
+        })
+    }
+
+    pub fn remove_from_whitelist(&self, player: &PlayerEntry) -> Result<bool, AccessError> {
+        self.whitelist
+            .update(|entries| remove_where(entries, |e| e.refers_to(player)))
+    }
+
+    /// The operator level of a player, or `None` if they are not an operator.
+    pub fn op_level(&self, name: &str, xuid: Option<&str>) -> Option<u8> {
+        self.ops
+            .read()
+            .iter()
+            .find(|entry| entry.player.matches(name, xuid))
+            .map(|entry| entry.level)
+    }
+
+    pub fn is_op(&self, name: &str, xuid: Option<&str>) -> bool {
+        self.op_level(name, xuid).is_some()
+    }
+
+    pub fn op(&self, player: PlayerEntry, level: u8) -> Result<bool, AccessError> {
+        self.ops.update(|entries| {
+            remove_where(entries, |e| {
+                e.player.matches(&player.name, player.xuid.as_deref())
+            });
+            entries.push(OpEntry { player, level });
+            true
+        })
+    }
+
+    pub fn deop(&self, name: &str) -> Result<bool, AccessError> {
+        self.ops
+            .update(|entries| remove_where(entries, |e| e.player.name.eq_ignore_ascii_case(name)))
+    }
+
+    /// The active ban of a player, if any. Expired bans are ignored.
+    pub fn player_ban(&self, name: &str, xuid: Option<&str>) -> Option<BanDetails> {
+        self.banned_players
+            .read()
+            .iter()
+            .find(|ban| ban.player.matches(name, xuid) && ban.details.is_active())
+            .map(|ban| ban.details.clone())
+    }
+
+    pub fn ban_player(
+        &self,
+        player: PlayerEntry,
+        details: BanDetails,
+    ) -> Result<bool, AccessError> {
+        self.banned_players.update(|entries| {
+            remove_where(entries, |e| {
+                e.player.matches(&player.name, player.xuid.as_deref())
+            });
+            entries.push(PlayerBan { player, details });
+            true
+        })
+    }
+
+    pub fn pardon_player(&self, player: &PlayerEntry) -> Result<bool, AccessError> {
+        self.banned_players
+            .update(|entries| remove_where(entries, |e| e.player.refers_to(player)))
+    }
+
+    /// The active ban of an IP address, if any. Expired bans are ignored.
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<BanDetails> {
+        self.banned_ips
+            .read()
+            .iter()
+            .find(|ban| ban.ip == ip && ban.details.is_active())
+            .map(|ban| ban.details.clone())
+    }
+
+    pub fn ban_ip(&self, ip: IpAddr, details: BanDetails) -> Result<bool, AccessError> {
+        self.banned_ips.update(|entries| {
+            remove_where(entries, |e| e.ip == ip);
+            entries.push(IpBan { ip, details });
+            true
+        })
+    }
+
+    pub fn pardon_ip(&self, ip: IpAddr) -> Result<bool, AccessError> {
+        self.banned_ips
+            .update(|entries| remove_where(entries, |e| e.ip == ip))
+    }
+
+    /// The active mute of a player, if any. Expired mutes are ignored.
+    pub fn player_mute(&self, name: &str, xuid: Option<&str>) -> Option<BanDetails> {
+        self.muted_players
+            .read()
+            .iter()
+            .find(|mute| mute.player.matches(name, xuid) && mute.details.is_active())
+            .map(|mute| mute.details.clone())
+    }
+
+    pub fn mute_player(
+        &self,
+        player: PlayerEntry,
+        details: BanDetails,
+    ) -> Result<bool, AccessError> {
+        self.muted_players.update(|entries| {
+            remove_where(entries, |e| {
+                e.player.matches(&player.name, player.xuid.as_deref())
+            });
+            entries.push(PlayerMute { player, details });
+            true
+        })
+    }
+
+    pub fn unmute_player(&self, player: &PlayerEntry) -> Result<bool, AccessError> {
+        self.muted_players
+            .update(|entries| remove_where(entries, |e| e.player.refers_to(player)))
+    }
+
+    /// Removes expired bans and mutes from their lists, saving the files that changed, and
+    /// returns how many were removed. Expired entries are already ignored; this keeps them
+    /// from piling up in the files and in listings.
+    pub fn remove_expired_bans(&self) -> Result<usize, AccessError> {
+        let mut removed = 0;
+        self.banned_players.update(|entries| {
+            let before = entries.len();
+            entries.retain(|ban| {
+                let active = ban.details.is_active();
+                if !active {
+                    info!("Ban of {} expired", ban.player.name);
+                }
+                active
+            });
+            removed += before - entries.len();
+            entries.len() != before
+        })?;
+        self.banned_ips.update(|entries| {
+            let before = entries.len();
+            entries.retain(|ban| {
+                let active = ban.details.is_active();
+                if !active {
+                    info!("Ban of {} expired", ban.ip);
+                }
+                active
+            });
+            removed += before - entries.len();
+            entries.len() != before
+        })?;
+        self.muted_players.update(|entries| {
+            let before = entries.len();
+            entries.retain(|mute| {
+                let active = mute.details.is_active();
+                if !active {
+                    info!("Mute of {} expired", mute.player.name);
+                }
+                active
+            });
+            removed += before - entries.len();
+            entries.len() != before
+        })?;
+        Ok(removed)
+    }
+
+    /// Calls [`remove_expired_bans`](Self::remove_expired_bans) now and then every minute.
+    pub fn spawn_ban_expiry(self: &Arc<Self>) {
+        let access = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(BAN_EXPIRY_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = access.remove_expired_bans() {
+                    error!("Failed to remove expired bans: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn count_entries<T: DeserializeOwned>(path: &Path) -> Result<Option<usize>, AccessError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let entries: Vec<T> = serde_json::from_str(&content).map_err(|source| AccessError::Json {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(Some(entries.len()))
+}
+
+/// Removes all entries matching `predicate`, returning whether any were removed.
+fn remove_where<T>(entries: &mut Vec<T>, predicate: impl Fn(&T) -> bool) -> bool {
+    let before = entries.len();
+    entries.retain(|entry| !predicate(entry));
+    entries.len() != before
+}
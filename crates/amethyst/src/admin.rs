@@ -0,0 +1,409 @@
+use crate::access::{AccessError, AccessLists, BanDetails, PlayerEntry};
+use crate::build_info;
+use crate::commands::{CommandContext, CommandError, CommandRegistry, CommandSender};
+use crate::players;
+use crate::protocol::version::ProtocolVersion;
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use log::{error, info};
+use rakethyst::connection::Connection;
+use rakethyst::listener::ServerInfo;
+use rakethyst::motd::MINECRAFT_VERSION;
+use rakethyst::protocol::packet_name;
+use rakethyst::stats::ListenerStats;
+use rakethyst::traffic::{packet_name_in_frame, DirectionTraffic, PacketTraffic};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Everything the admin API reads from or acts on.
+#[derive(Clone)]
+pub struct AdminState {
+    pub token: Arc<str>,
+    pub started: Instant,
+    pub server_info: Arc<ServerInfo>,
+    pub connections: Arc<DashMap<SocketAddr, Connection>>,
+    pub access: Arc<AccessLists>,
+    pub stats: Arc<ListenerStats>,
+    pub commands: Arc<CommandRegistry>,
+    pub command_context: CommandContext,
+}
+
+/// Serves the HTTP admin API on `address` until the task is dropped.
+///
+/// Every request must carry `Authorization: Bearer <token>`.
+pub async fn serve(address: String, state: AdminState) {
+    let routes = Router::new()
+        .route("/status", get(status))
+        .route("/players", get(players))
+        .route("/players/{query}", get(player_profile))
+        .route("/stats", get(stats))
+        .route("/timings", get(timings))
+        .route("/kick", post(kick))
+        .route("/ban", get(bans).post(ban))
+        .route("/ban/{name}", delete(pardon))
+        .route("/ban-ip", post(ban_ip))
+        .route("/ban-ip/{ip}", delete(pardon_ip))
+        .route("/whitelist", get(whitelist).post(whitelist_add))
+        .route("/whitelist/{name}", delete(whitelist_remove))
+        .route("/save", post(save))
+        .route("/command", post(command));
+    #[cfg(all(feature = "profiling", unix))]
+    let routes = routes.route("/profile", get(crate::profiling::profile));
+    let app = routes
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+
+    let listener = match tokio::net::TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind admin API to {}: {}", address, e);
+            return;
+        }
+    };
+    info!("Admin API listening on http://{}", address);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("Admin API stopped: {}", e);
+    }
+}
+
+async fn authorize(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), state.token.as_bytes()));
+    if !authorized {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token");
+    }
+    next.run(request).await
+}
+
+/// Compares two byte strings without returning early, so response timing does not reveal
+/// how much of the token was correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub(crate) fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+fn access_result(result: Result<bool, AccessError>, missing: &str) -> Response {
+    match result {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, missing),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn status(State(state): State<AdminState>) -> Response {
+    let motd = state.server_info.motd();
+    Json(json!({
+        "brand": build_info::BRAND,
+        "version": build_info::VERSION,
+        "build": {
+            "git_hash": build_info::GIT_HASH,
+            "date": build_info::BUILD_DATE,
+            "minecraft_version": MINECRAFT_VERSION,
+            "minecraft_versions": ProtocolVersion::all()
+                .map(ProtocolVersion::minecraft_version)
+                .collect::<Vec<_>>(),
+            "protocols": {
+                "min": build_info::SUPPORTED_PROTOCOLS.start(),
+                "max": build_info::SUPPORTED_PROTOCOLS.end(),
+            },
+        },
+        "guid": state.server_info.guid().to_string(),
+        "motd": motd.motd,
+        "world_name": motd.world_name,
+        "connections": state.connections.len(),
+        "players": state.server_info.player_count(),
+        "max_players": motd.max_players,
+        "uptime_secs": state.started.elapsed().as_secs(),
+    }))
+    .into_response()
+}
+
+/// Tick times and the time spent in each profiled section since the last report or reset.
+async fn timings(State(state): State<AdminState>) -> Response {
+    let report = state.command_context.timings.report();
+    let sections: Vec<_> = report
+        .sections
+        .iter()
+        .map(|(name, times)| {
+            json!({
+                "name": name,
+                "total_ms": times.total.as_secs_f64() * 1000.0,
+                "mean_ms": report.per_tick(times).as_secs_f64() * 1000.0,
+                "max_ms": times.longest.as_secs_f64() * 1000.0,
+                "percent": report.share(times),
+            })
+        })
+        .collect();
+    Json(json!({
+        "period_secs": report.period.as_secs_f64(),
+        "ticks": report.ticks,
+        "tick_time_ms": report.tick_time.as_secs_f64() * 1000.0,
+        "max_mspt": report.longest_tick.as_secs_f64() * 1000.0,
+        "long_ticks": report.long_ticks,
+        "sections": sections,
+    }))
+    .into_response()
+}
+
+/// Server list pings versus accepted connections, to compare how often the server is listed
+/// with how often it is joined, how well the tick loop keeps up, and which packets take up
+/// the traffic.
+async fn stats(State(state): State<AdminState>) -> Response {
+    let stats = state.stats.snapshot();
+    let ticks = state.command_context.tick_stats.report();
+    let traffic = state.command_context.traffic.snapshot();
+    Json(json!({
+        "pings": {
+            "total": stats.total_pings,
+            "last_minute": stats.pings_last_minute,
+            "last_hour": stats.pings_last_hour,
+            "unique_sources_last_hour": stats.unique_sources_last_hour,
+            "per_minute": stats.pings_per_minute,
+        },
+        "connections": {
+            "total": stats.total_connections,
+            "last_hour": stats.connections_last_hour,
+        },
+        "ticks": {
+            "tps_1m": ticks.tps[0],
+            "tps_5m": ticks.tps[1],
+            "tps_15m": ticks.tps[2],
+            "mean_mspt": ticks.mean_tick_time.as_secs_f64() * 1000.0,
+            "max_mspt": ticks.max_tick_time.as_secs_f64() * 1000.0,
+        },
+        "traffic": {
+            "received": direction_json(&traffic.received),
+            "sent": direction_json(&traffic.sent),
+        },
+    }))
+    .into_response()
+}
+
+fn direction_json(traffic: &DirectionTraffic) -> serde_json::Value {
+    json!({
+        "datagrams": traffic_json(&traffic.datagrams, packet_name),
+        "packets": traffic_json(&traffic.packets, packet_name_in_frame),
+    })
+}
+
+fn traffic_json(
+    traffic: &[PacketTraffic],
+    name: fn(u8) -> Option<&'static str>,
+) -> Vec<serde_json::Value> {
+    traffic
+        .iter()
+        .map(|entry| {
+            json!({
+                "id": entry.id,
+                "name": name(entry.id),
+                "count": entry.count,
+                "bytes": entry.bytes,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct PlayerInfo {
+    address: SocketAddr,
+    guid: String,
+    state: String,
+}
+
+/// Lists connected RakNet clients. Player names become available once the login sequence is
+/// implemented.
+async fn players(State(state): State<AdminState>) -> Json<Vec<PlayerInfo>> {
+    Json(
+        state
+            .connections
+            .iter()
+            .map(|entry| PlayerInfo {
+                address: entry.address,
+                guid: entry.client_guid.to_string(),
+                state: format!("{:?}", entry.state),
+            })
+            .collect(),
+    )
+}
+
+/// A player who has joined before, found by UUID, XUID or current or past name.
+async fn player_profile(State(state): State<AdminState>, Path(query): Path<String>) -> Response {
+    let Some(profile) = state.command_context.players.lookup(&query) else {
+        return error_response(StatusCode::NOT_FOUND, "Player has never joined");
+    };
+    let past_names: Vec<_> = profile
+        .past_names
+        .iter()
+        .map(|past| json!({ "name": past.name, "until": players::date_time(past.until) }))
+        .collect();
+    Json(json!({
+        "uuid": profile.uuid,
+        "xuid": profile.xuid,
+        "name": profile.name,
+        "past_names": past_names,
+        "first_seen": players::date_time(profile.first_seen),
+        "last_seen": players::date_time(profile.last_seen),
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct KickRequest {
+    address: SocketAddr,
+}
+
+/// Drops the connection from the connection table. Without the reliability layer the client
+/// cannot be sent a disconnect notification and will time out on its side.
+async fn kick(State(state): State<AdminState>, Json(request): Json<KickRequest>) -> Response {
+    match state.connections.remove(&request.address) {
+        Some(_) => {
+            state.server_info.update_player_count(&state.connections);
+            info!("Kicked {} via the admin API", request.address);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        None => error_response(StatusCode::NOT_FOUND, "No connection from that address"),
+    }
+}
+
+#[derive(Deserialize)]
+struct BanRequest {
+    name: String,
+    #[serde(default)]
+    xuid: Option<String>,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    expires: Option<DateTime<Utc>>,
+}
+
+async fn bans(State(state): State<AdminState>) -> Response {
+    Json(state.access.banned_players.entries()).into_response()
+}
+
+async fn ban(State(state): State<AdminState>, Json(request): Json<BanRequest>) -> Response {
+    let player = match request.xuid {
+        Some(xuid) => PlayerEntry {
+            name: request.name,
+            xuid: Some(xuid),
+        },
+        None => players::resolve_entry(&state.command_context.players, &request.name),
+    };
+    let details = BanDetails::new("Admin API".to_string(), request.reason, request.expires);
+    access_result(state.access.ban_player(player, details), "")
+}
+
+async fn pardon(State(state): State<AdminState>, Path(name): Path<String>) -> Response {
+    let player = players::resolve_entry(&state.command_context.players, &name);
+    access_result(state.access.pardon_player(&player), "Player is not banned")
+}
+
+#[derive(Deserialize)]
+struct BanIpRequest {
+    ip: IpAddr,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    expires: Option<DateTime<Utc>>,
+}
+
+async fn ban_ip(State(state): State<AdminState>, Json(request): Json<BanIpRequest>) -> Response {
+    let details = BanDetails::new("Admin API".to_string(), request.reason, request.expires);
+    let result = state.access.ban_ip(request.ip, details);
+    let kicked: Vec<SocketAddr> = state
+        .connections
+        .iter()
+        .map(|entry| entry.address)
+        .filter(|address| address.ip() == request.ip)
+        .collect();
+    for address in kicked {
+        state.connections.remove(&address);
+    }
+    state.server_info.update_player_count(&state.connections);
+    access_result(result, "")
+}
+
+async fn pardon_ip(State(state): State<AdminState>, Path(ip): Path<IpAddr>) -> Response {
+    access_result(state.access.pardon_ip(ip), "IP address is not banned")
+}
+
+async fn whitelist(State(state): State<AdminState>) -> Response {
+    Json(json!({
+        "enabled": state.access.whitelist_enabled(),
+        "players": state.access.whitelist.entries(),
+    }))
+    .into_response()
+}
+
+async fn whitelist_add(
+    State(state): State<AdminState>,
+    Json(player): Json<PlayerEntry>,
+) -> Response {
+    let player = match player.xuid {
+        Some(_) => player,
+        None => players::resolve_entry(&state.command_context.players, &player.name),
+    };
+    access_result(
+        state.access.add_to_whitelist(player),
+        "Player is already whitelisted",
+    )
+}
+
+async fn whitelist_remove(State(state): State<AdminState>, Path(name): Path<String>) -> Response {
+    let player = players::resolve_entry(&state.command_context.players, &name);
+    access_result(
+        state.access.remove_from_whitelist(&player),
+        "Player is not whitelisted",
+    )
+}
+
+#[derive(Deserialize)]
+struct CommandRequest {
+    command: String,
+}
+
+/// Runs a console command with full permissions and returns its output.
+async fn command(
+    State(state): State<AdminState>,
+    Json(request): Json<CommandRequest>,
+) -> Response {
+    let sender = CommandSender::Remote("Admin API".to_string());
+    info!("Admin API issued command: {}", request.command);
+    match state
+        .commands
+        .dispatch(&state.command_context, &sender, &request.command)
+    {
+        Ok(output) => Json(json!({ "output": output })).into_response(),
+        Err(e @ (CommandError::Unknown(_) | CommandError::Usage(_))) => {
+            error_response(StatusCode::BAD_REQUEST, e.to_string())
+        }
+        Err(e @ CommandError::Access(_)) => {
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+        Err(e) => error_response(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    }
+}
+
+/// Access lists are saved on every change, and there is no world or player data to persist
+/// yet.
+async fn save() -> Response {
+    error_response(
+        StatusCode::NOT_IMPLEMENTED,
+        "There is no world or player data to save yet",
+    )
+}
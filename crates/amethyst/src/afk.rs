@@ -0,0 +1,138 @@
+//! Players who have sent no `PlayerAuthInput` for `afk.timeout` minutes, configured by
+//! [`AfkConfig`]. Clients keep their RakNet connection alive with pings even when the game has
+//! stalled, so only input counts here. Idle players are kicked or marked AFK, see
+//! [`AfkAction`].
+
+use crate::config::{AfkAction, AfkConfig};
+use amethyst_plugin::event::{EventPriority, PlayerJoin, PlayerQuit};
+use amethyst_plugin::{EventBus, Scheduler, TICKS_PER_SECOND};
+use dashmap::DashMap;
+use log::info;
+use rakethyst::connection::Connection;
+use rakethyst::listener::ServerInfo;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+pub struct AfkTracker {
+    config: RwLock<AfkConfig>,
+    players: DashMap<SocketAddr, Activity>,
+}
+
+struct Activity {
+    name: String,
+    last_input: Instant,
+    afk: bool,
+}
+
+impl AfkTracker {
+    pub fn new(config: AfkConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            players: DashMap::new(),
+        }
+    }
+
+    pub fn set_config(&self, config: AfkConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    fn config(&self) -> AfkConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Starts the clock for players as they join and forgets them as they leave.
+    pub fn register_events(self: &Arc<Self>, events: &EventBus) {
+        let tracker = Arc::clone(self);
+        events.subscribe(EventPriority::Monitor, move |join: &mut PlayerJoin| {
+            if join.cancelled {
+                return;
+            }
+            tracker.players.insert(
+                join.address,
+                Activity {
+                    name: join.name.clone(),
+                    last_input: Instant::now(),
+                    afk: false,
+                },
+            );
+        });
+        let tracker = Arc::clone(self);
+        events.subscribe(EventPriority::Monitor, move |quit: &mut PlayerQuit| {
+            tracker.players.remove(&quit.address);
+        });
+    }
+
+    /// Records a `PlayerAuthInput` from `address`, bringing the player back if they were AFK.
+    pub fn record_input(&self, address: SocketAddr) {
+        if let Some(mut activity) = self.players.get_mut(&address) {
+            activity.last_input = Instant::now();
+            if activity.afk {
+                activity.afk = false;
+                info!("{} is no longer AFK", activity.name);
+            }
+        }
+    }
+
+    pub fn is_afk(&self, address: SocketAddr) -> bool {
+        self.players
+            .get(&address)
+            .is_some_and(|activity| activity.afk)
+    }
+
+    /// Checks for idle players once a second, kicking them from `connections` or marking them
+    /// AFK.
+    pub fn schedule(
+        self: &Arc<Self>,
+        scheduler: &Scheduler,
+        connections: Arc<DashMap<SocketAddr, Connection>>,
+        server_info: Arc<ServerInfo>,
+    ) {
+        let tracker = Arc::clone(self);
+        scheduler.run_repeating(TICKS_PER_SECOND, TICKS_PER_SECOND, move || {
+            let config = tracker.config();
+            if !config.enabled {
+                return;
+            }
+            // Connections can also go without a PlayerQuit, e.g. when kicked or timed out.
+            tracker
+                .players
+                .retain(|address, _| connections.contains_key(address));
+            let timeout = Duration::from_secs(config.timeout * 60);
+            for (address, name) in tracker.idle(timeout, config.action) {
+                match config.action {
+                    AfkAction::Kick => {
+                        tracker.players.remove(&address);
+                        if connections.remove(&address).is_some() {
+                            server_info.update_player_count(&connections);
+                            info!(
+                                "Kicked {} ({}) for being idle: {}",
+                                name, address, config.kick_message
+                            );
+                        }
+                    }
+                    AfkAction::Mark => {
+                        if let Some(mut activity) = tracker.players.get_mut(&address) {
+                            activity.afk = true;
+                        }
+                        info!("{} is now AFK", name);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Players without input for `timeout` that `action` has not been taken on yet. Players
+    /// marked before the action was changed to kicking are kicked too.
+    fn idle(&self, timeout: Duration, action: AfkAction) -> Vec<(SocketAddr, String)> {
+        self.players
+            .iter()
+            .filter(|activity| action == AfkAction::Kick || !activity.afk)
+            .filter(|activity| activity.last_input.elapsed() >= timeout)
+            .map(|activity| (*activity.key(), activity.name.clone()))
+            .collect()
+    }
+}
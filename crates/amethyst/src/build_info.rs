@@ -0,0 +1,49 @@
+//! What this binary is, as recorded by `build.rs` at compile time.
+
+use crate::protocol::version::{ProtocolVersion, NEWEST_PROTOCOL, OLDEST_PROTOCOL};
+use std::ops::RangeInclusive;
+
+/// Name the server reports itself under.
+pub const BRAND: &str = "Amethyst";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Abbreviated hash of the commit the binary was built from, or "unknown".
+pub const GIT_HASH: &str = env!("AMETHYST_GIT_HASH");
+/// UTC date of the build, as `YYYY-MM-DD`.
+pub const BUILD_DATE: &str = env!("AMETHYST_BUILD_DATE");
+/// Game protocol versions clients may join with, translated by [`crate::protocol::version`].
+/// Not every number in the range is a released version.
+pub const SUPPORTED_PROTOCOLS: RangeInclusive<u32> = OLDEST_PROTOCOL..=NEWEST_PROTOCOL;
+
+/// `--version` output, e.g. `0.1.0 (3f2a9c1b7d4e, 2026-10-17)`.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("AMETHYST_GIT_HASH"),
+    ", ",
+    env!("AMETHYST_BUILD_DATE"),
+    ")"
+);
+
+/// One line describing the build and the game versions it supports.
+pub fn summary() -> String {
+    format!(
+        "{} {} (git {}, built {}) for Minecraft: Bedrock Edition {} to {}, {}",
+        BRAND,
+        VERSION,
+        GIT_HASH,
+        BUILD_DATE,
+        ProtocolVersion::OLDEST.minecraft_version(),
+        ProtocolVersion::LATEST.minecraft_version(),
+        protocols()
+    )
+}
+
+/// The supported protocol versions, e.g. `protocol 662` or `protocols 649-662`.
+pub fn protocols() -> String {
+    let (min, max) = (SUPPORTED_PROTOCOLS.start(), SUPPORTED_PROTOCOLS.end());
+    if min == max {
+        format!("protocol {}", min)
+    } else {
+        format!("protocols {}-{}", min, max)
+    }
+}
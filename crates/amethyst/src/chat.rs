@@ -0,0 +1,103 @@
+//! The chat pipeline. Every message is checked against the mute list, then posted as a
+//! [`PlayerChat`] event so plugins and scripts can rewrite or cancel it, and finally rendered
+//! with its format and broadcast.
+
+use crate::access::{AccessLists, BanDetails};
+use crate::commands::CommandSender;
+use crate::config::ChatConfig;
+use amethyst_plugin::event::PlayerChat;
+use amethyst_plugin::EventBus;
+use log::info;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChatError {
+    #[error("{}", muted_message(.0))]
+    Muted(BanDetails),
+    #[error("Your message was not sent")]
+    Cancelled,
+}
+
+fn muted_message(details: &BanDetails) -> String {
+    let mut message = "You are muted".to_string();
+    details.describe(&mut message);
+    message
+}
+
+pub struct Chat {
+    events: Arc<EventBus>,
+    access: Arc<AccessLists>,
+    config: RwLock<ChatConfig>,
+}
+
+impl Chat {
+    pub fn new(events: Arc<EventBus>, access: Arc<AccessLists>, config: ChatConfig) -> Self {
+        Self {
+            events,
+            access,
+            config: RwLock::new(config),
+        }
+    }
+
+    /// Applies the formats of a reloaded configuration.
+    pub fn set_config(&self, config: ChatConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    fn config(&self) -> ChatConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Broadcasts a message typed by a player and returns the line that was broadcast.
+    pub fn send(&self, name: &str, xuid: Option<&str>, message: &str) -> Result<String, ChatError> {
+        self.check_mute(name, xuid)?;
+        self.broadcast(name, xuid, message, self.config().format)
+    }
+
+    /// Broadcasts a message from `say`. Only players can be muted.
+    pub fn say(&self, sender: &CommandSender, message: &str) -> Result<String, ChatError> {
+        let format = self.config().broadcast_format;
+        match sender {
+            CommandSender::Player { name, xuid } => {
+                self.check_mute(name, xuid.as_deref())?;
+                self.broadcast(name, xuid.as_deref(), message, format)
+            }
+            _ => self.broadcast(sender.name(), None, message, format),
+        }
+    }
+
+    fn check_mute(&self, name: &str, xuid: Option<&str>) -> Result<(), ChatError> {
+        match self.access.player_mute(name, xuid) {
+            Some(mute) => Err(ChatError::Muted(mute)),
+            None => Ok(()),
+        }
+    }
+
+    fn broadcast(
+        &self,
+        name: &str,
+        xuid: Option<&str>,
+        message: &str,
+        format: String,
+    ) -> Result<String, ChatError> {
+        let chat = self.events.post(PlayerChat {
+            name: name.to_string(),
+            xuid: xuid.map(str::to_string),
+            message: message.to_string(),
+            format,
+            cancelled: false,
+        });
+        if chat.cancelled {
+            return Err(ChatError::Cancelled);
+        }
+        let line = chat.render();
+        // Sending chat to players needs the RakNet reliability layer; until then the console
+        // is the only place to show it.
+        info!("{}", line);
+        Ok(line)
+    }
+}
@@ -0,0 +1,124 @@
+use crate::access::AccessLists;
+use crate::cli::Cli;
+use crate::config;
+use crate::config::world;
+use crate::identity::ServerIdentity;
+use crate::permissions;
+use crate::players;
+use std::fs;
+use std::path::Path;
+
+/// Runs `amethyst check`: loads and validates everything the server would read at startup
+/// without creating or modifying any file, and prints the effective configuration.
+///
+/// Returns `false` if any problem was found.
+pub fn run(cli: &Cli) -> bool {
+    let mut problems = 0usize;
+    let mut report = |result: Result<String, String>| match result {
+        Ok(line) => println!("  ok    {}", line),
+        Err(line) => {
+            problems += 1;
+            println!("  error {}", line);
+        }
+    };
+
+    println!("Checking {}", cli.config.display());
+    let config = match config::check(&cli.config, &cli.overrides()) {
+        Ok((config, migrated_from)) => {
+            report(Ok(format!("{} is valid", cli.config.display())));
+            if let Some(version) = migrated_from {
+                println!(
+                    "  note  config_version {} will be upgraded to {} on the next start",
+                    version,
+                    config::migrate::CURRENT_VERSION
+                );
+            }
+            config
+        }
+        Err(e) => {
+            report(Err(format!("{}: {}", cli.config.display(), e)));
+            println!("\n1 problem found");
+            return false;
+        }
+    };
+
+    let worlds_dir = Path::new(&config.worlds.directory);
+    for name in &config.worlds.load {
+        let path = worlds_dir.join(format!("{}.toml", name));
+        report(if !path.exists() {
+            Ok(format!("{} will be created with defaults", path.display()))
+        } else {
+            world::read_world(&path)
+                .map(|world| {
+                    format!(
+                        "{} (generator: {:?}, seed: {}, gamemode: {})",
+                        path.display(),
+                        world.generator,
+                        world.seed,
+                        world.gamemode.name()
+                    )
+                })
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        });
+    }
+
+    for (path, result) in AccessLists::check(Path::new(".")) {
+        report(match result {
+            Ok(Some(count)) => Ok(format!("{} ({} entries)", path.display(), count)),
+            Ok(None) => Ok(format!("{} will be created empty", path.display())),
+            Err(e) => Err(e.to_string()),
+        });
+    }
+
+    let (path, result) = permissions::check(Path::new("."));
+    report(match result {
+        Ok(Some((groups, players))) => Ok(format!(
+            "{} ({} groups, {} players)",
+            path.display(),
+            groups,
+            players
+        )),
+        Ok(None) => Ok(format!("{} will be created", path.display())),
+        Err(e) => Err(e.to_string()),
+    });
+
+    let (path, result) = players::check(Path::new("."));
+    report(match result {
+        Ok(Some(count)) => Ok(format!("{} ({} players)", path.display(), count)),
+        Ok(None) => Ok(format!("{} will be created empty", path.display())),
+        Err(e) => Err(e.to_string()),
+    });
+
+    if config.resource_packs.enabled {
+        let packs_dir = Path::new(&config.resource_packs.directory);
+        for pack in &config.resource_packs.packs {
+            let path = packs_dir.join(&pack.file);
+            report(
+                fs::metadata(&path)
+                    .map(|metadata| format!("{} ({} bytes)", path.display(), metadata.len()))
+                    .map_err(|e| format!("{}: {}", path.display(), e)),
+            );
+        }
+    }
+
+    let keys_path = Path::new(&config.server.keys_file);
+    report(if !keys_path.exists() {
+        Ok(format!("{} will be generated", keys_path.display()))
+    } else {
+        ServerIdentity::load(keys_path)
+            .map(|identity| format!("{} (GUID {})", keys_path.display(), identity.guid))
+            .map_err(|e| format!("{}: {}", keys_path.display(), e))
+    });
+
+    match toml::to_string_pretty(&config.redacted()) {
+        Ok(effective) => println!("\nEffective configuration:\n{}", effective),
+        Err(e) => report(Err(format!("Failed to print the configuration: {}", e))),
+    }
+
+    match problems {
+        0 => println!("No problems found"),
+        1 => println!("1 problem found"),
+        n => println!("{} problems found", n),
+    }
+    problems == 0
+}
@@ -0,0 +1,82 @@
+use crate::build_info::LONG_VERSION;
+use crate::config::{ConfigOverrides, CONFIG_FILE_NAME};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Amethyst, a Minecraft: Bedrock Edition server.
+#[derive(Debug, Parser)]
+#[command(version = LONG_VERSION, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the configuration file. It is created with default values if missing.
+    #[arg(long, global = true, value_name = "PATH", default_value = CONFIG_FILE_NAME)]
+    pub config: PathBuf,
+
+    /// Address to listen on, e.g. 0.0.0.0:19132. Replaces network.addresses.
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub bind: Option<String>,
+
+    /// Port to listen on. Overrides the port of every address in network.addresses.
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// Default log level (off, error, warn, info, debug, trace). Overrides logging.level.
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Disable colored log output.
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Handoff file of the previous server process, passed on a hot restart.
+    #[cfg(unix)]
+    #[arg(long, hide = true, value_name = "PATH", requires = "handoff_sockets")]
+    pub handoff: Option<PathBuf>,
+
+    /// Sockets inherited from the previous server process, passed on a hot restart.
+    #[cfg(unix)]
+    #[arg(
+        long,
+        hide = true,
+        value_name = "FDS",
+        value_delimiter = ',',
+        requires = "handoff"
+    )]
+    pub handoff_sockets: Vec<i32>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Validate the configuration, world files, access lists and keys without starting the
+    /// server. Exits with a non-zero status if anything is wrong.
+    Check,
+    /// Replay a session recorded with network.record_sessions into a fresh session, with the
+    /// recorded timing, logging what the server does with it.
+    Replay {
+        /// The recording to replay.
+        file: PathBuf,
+    },
+    /// Ping a server the way the server list does and print what it advertises: name,
+    /// version, protocol, player counts and the round trip time.
+    Ping {
+        /// The server, as HOST:PORT. The port defaults to 19132.
+        target: String,
+
+        /// Seconds to wait for the reply.
+        #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: u64,
+    },
+}
+
+impl Cli {
+    pub fn overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            address: self.bind.clone(),
+            port: self.port,
+            log_level: self.log_level.clone(),
+            no_color: self.no_color,
+        }
+    }
+}
@@ -0,0 +1,924 @@
+use super::{Args, CommandError, CommandSender, CommandSpec, Invocation};
+use crate::access::BanDetails;
+use crate::build_info;
+use crate::config::{GameMode, DEFAULT_PACKET_TRACE_FILE};
+use crate::permissions::PERMISSIONS_FILE_NAME;
+use crate::players;
+use crate::protocol::version::ProtocolVersion;
+use crate::protocol::{self, Transfer};
+use amethyst_log::AmethystLogger;
+use amethyst_plugin::permission::{PermissionDefault, PermissionSubject};
+use chrono::{TimeDelta, Utc};
+use log::{info, LevelFilter};
+use rakethyst::protocol::packet_name;
+use rakethyst::traffic::{packet_name_in_frame, DirectionTraffic, PacketTraffic};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The commands every server has.
+pub fn commands() -> Vec<CommandSpec> {
+    vec![
+        CommandSpec {
+            name: "help",
+            aliases: &["?"],
+            usage: "[command]",
+            description: "Lists commands or shows how to use one",
+            permission: 0,
+            node: "amethyst.command.help",
+            handler: Box::new(help),
+        },
+        CommandSpec {
+            name: "stop",
+            aliases: &[],
+            usage: "",
+            description: "Stops the server",
+            permission: 4,
+            node: "amethyst.command.stop",
+            handler: Box::new(stop),
+        },
+        CommandSpec {
+            name: "restart",
+            aliases: &[],
+            usage: "",
+            description: "Restarts the server without disconnecting players, e.g. after an upgrade",
+            permission: 4,
+            node: "amethyst.command.restart",
+            handler: Box::new(restart),
+        },
+        CommandSpec {
+            name: "list",
+            aliases: &[],
+            usage: "",
+            description: "Lists connected players",
+            permission: 0,
+            node: "amethyst.command.list",
+            handler: Box::new(list),
+        },
+        CommandSpec {
+            name: "version",
+            aliases: &["ver", "about"],
+            usage: "",
+            description: "Shows the server version and the game versions it supports",
+            permission: 0,
+            node: "amethyst.command.version",
+            handler: Box::new(version),
+        },
+        CommandSpec {
+            name: "tps",
+            aliases: &["mspt"],
+            usage: "",
+            description: "Shows ticks per second and tick times",
+            permission: 0,
+            node: "amethyst.command.tps",
+            handler: Box::new(tps),
+        },
+        CommandSpec {
+            name: "timings",
+            aliases: &[],
+            usage: "[reset]",
+            description: "Shows where tick time goes, or starts measuring from scratch",
+            permission: 2,
+            node: "amethyst.command.timings",
+            handler: Box::new(timings),
+        },
+        CommandSpec {
+            name: "say",
+            aliases: &[],
+            usage: "<message>",
+            description: "Broadcasts a message to all players",
+            permission: 1,
+            node: "amethyst.command.say",
+            handler: Box::new(say),
+        },
+        CommandSpec {
+            name: "kick",
+            aliases: &[],
+            usage: "<player|address> [reason]",
+            description: "Disconnects a player",
+            permission: 3,
+            node: "amethyst.command.kick",
+            handler: Box::new(kick),
+        },
+        CommandSpec {
+            name: "ban",
+            aliases: &[],
+            usage: "<player> [duration] [reason]",
+            description: "Bans a player, for a duration such as 30m, 12h or 7d if given",
+            permission: 3,
+            node: "amethyst.command.ban",
+            handler: Box::new(ban),
+        },
+        CommandSpec {
+            name: "pardon",
+            aliases: &["unban"],
+            usage: "<player>",
+            description: "Removes a player's ban",
+            permission: 3,
+            node: "amethyst.command.pardon",
+            handler: Box::new(pardon),
+        },
+        CommandSpec {
+            name: "mute",
+            aliases: &[],
+            usage: "<player> [duration] [reason]",
+            description:
+                "Stops a player from chatting, for a duration such as 30m, 12h or 7d if given",
+            permission: 3,
+            node: "amethyst.command.mute",
+            handler: Box::new(mute),
+        },
+        CommandSpec {
+            name: "unmute",
+            aliases: &[],
+            usage: "<player>",
+            description: "Lets a muted player chat again",
+            permission: 3,
+            node: "amethyst.command.unmute",
+            handler: Box::new(unmute),
+        },
+        CommandSpec {
+            name: "whitelist",
+            aliases: &[],
+            usage: "<on|off|list|add|remove> [player]",
+            description: "Manages the whitelist",
+            permission: 3,
+            node: "amethyst.command.whitelist",
+            handler: Box::new(whitelist),
+        },
+        CommandSpec {
+            name: "tp",
+            aliases: &["teleport"],
+            usage: "<player> (<target>|<x> <y> <z>)",
+            description: "Teleports a player to another player or to coordinates",
+            permission: 2,
+            node: "amethyst.command.tp",
+            handler: Box::new(teleport),
+        },
+        CommandSpec {
+            name: "transfer",
+            aliases: &[],
+            usage: "<player|address> <host> [port]",
+            description: "Sends a player to another server",
+            permission: 3,
+            node: "amethyst.command.transfer",
+            handler: Box::new(transfer),
+        },
+        CommandSpec {
+            name: "gamemode",
+            aliases: &["gm"],
+            usage: "<survival|creative|adventure|spectator> [player]",
+            description: "Changes a player's game mode",
+            permission: 2,
+            node: "amethyst.command.gamemode",
+            handler: Box::new(gamemode),
+        },
+        CommandSpec {
+            name: "packettrace",
+            aliases: &[],
+            usage: "<on|off|status> [file]",
+            description: "Writes a hex dump of every packet to a file",
+            permission: 4,
+            node: "amethyst.command.packettrace",
+            handler: Box::new(packet_trace),
+        },
+        CommandSpec {
+            name: "loglevel",
+            aliases: &[],
+            usage: "[<target|*> <level|reset>]",
+            description: "Shows or changes the log level of a target, or the default with *",
+            permission: 4,
+            node: "amethyst.command.loglevel",
+            handler: Box::new(log_level),
+        },
+        CommandSpec {
+            name: "permissions",
+            aliases: &["perms"],
+            usage: "<nodes|check <player> <node>|reload>",
+            description:
+                "Lists permission nodes, checks one for a player, or reloads permissions.json",
+            permission: 4,
+            node: "amethyst.command.permissions",
+            handler: Box::new(permissions),
+        },
+        CommandSpec {
+            name: "debug",
+            aliases: &[],
+            usage: "packets [reset]",
+            description: "Shows which packets take up the most traffic",
+            permission: 4,
+            node: "amethyst.command.debug",
+            handler: Box::new(debug),
+        },
+        CommandSpec {
+            name: "resourcepacks",
+            aliases: &["packs"],
+            usage: "",
+            description: "Lists the resource packs served to players",
+            permission: 2,
+            node: "amethyst.command.resourcepacks",
+            handler: Box::new(resource_packs),
+        },
+        CommandSpec {
+            name: "viewdistance",
+            aliases: &["vd"],
+            usage: "[player|address]",
+            description: "Shows the view distance of each player, or of one",
+            permission: 2,
+            node: "amethyst.command.viewdistance",
+            handler: Box::new(view_distance),
+        },
+        CommandSpec {
+            name: "whois",
+            aliases: &["seen"],
+            usage: "<player|uuid|xuid>",
+            description: "Shows the UUID, XUID and past names of a player who has joined",
+            permission: 2,
+            node: "amethyst.command.whois",
+            handler: Box::new(whois),
+        },
+    ]
+}
+
+fn help(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let registry = invocation.registry;
+    let allowed = |spec: &&CommandSpec| invocation.context.may_run(invocation.sender, spec);
+    if let Some(name) = args.optional() {
+        let spec = registry
+            .get(&name)
+            .filter(allowed)
+            .ok_or(CommandError::Unknown(name))?;
+        return Ok(format!("{}\nUsage: {}", spec.description, spec.usage()));
+    }
+    let mut output = String::from("Available commands:");
+    for spec in registry.commands().filter(allowed) {
+        let _ = write!(output, "\n  {} - {}", spec.usage(), spec.description);
+    }
+    Ok(output)
+}
+
+fn stop(invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    info!("{} requested a shutdown", invocation.sender.name());
+    invocation.context.shutdown.request();
+    Ok("Stopping the server".to_string())
+}
+
+/// Replaces the server process with a new start of the executable, which may have been
+/// upgraded in the meantime.
+fn restart(invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    if !cfg!(unix) {
+        return Err(CommandError::Failed(
+            "Hot restarts are only supported on Unix".to_string(),
+        ));
+    }
+    info!("{} requested a restart", invocation.sender.name());
+    invocation.context.shutdown.request_restart();
+    Ok("Restarting the server".to_string())
+}
+
+/// Lists RakNet sessions by address until the login sequence provides player names.
+fn list(invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    let mut addresses: Vec<SocketAddr> = invocation
+        .context
+        .connections
+        .iter()
+        .map(|entry| entry.address)
+        .collect();
+    addresses.sort();
+    let mut output = format!(
+        "There are {} of a max of {} players online",
+        addresses.len(),
+        invocation.context.server_info.motd().max_players
+    );
+    for address in addresses {
+        let _ = write!(output, "\n  {}", address);
+        if invocation.context.offline_players.contains(address) {
+            output.push_str(" (offline)");
+        }
+        if invocation.context.afk.is_afk(address) {
+            output.push_str(" (AFK)");
+        }
+    }
+    Ok(output)
+}
+
+fn version(_invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    Ok(build_info::summary())
+}
+
+fn tps(invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    let report = invocation.context.tick_stats.report();
+    let [one, five, fifteen] = report.tps;
+    Ok(format!(
+        "TPS from last 1m, 5m, 15m: {:.1}, {:.1}, {:.1}\n\
+         Tick time over the last ticks: {:.2} ms mean, {:.2} ms max",
+        one,
+        five,
+        fifteen,
+        report.mean_tick_time.as_secs_f64() * 1000.0,
+        report.max_tick_time.as_secs_f64() * 1000.0
+    ))
+}
+
+fn timings(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let timings = &invocation.context.timings;
+    match args
+        .optional()
+        .map(|arg| arg.to_ascii_lowercase())
+        .as_deref()
+    {
+        None => Ok(timings.report().render()),
+        Some("reset") => {
+            timings.reset();
+            Ok("Timings reset".to_string())
+        }
+        Some(_) => Err(args.usage_error()),
+    }
+}
+
+fn say(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let message = args.rest();
+    if message.is_empty() {
+        return Err(args.usage_error());
+    }
+    invocation
+        .context
+        .chat
+        .say(invocation.sender, &message)
+        .map_err(|e| CommandError::Failed(e.to_string()))
+}
+
+fn kick(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let target = args.required()?;
+    let reason = args.rest();
+    let address = match target.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(_) => find_player(&target)?,
+    };
+    if invocation.context.connections.remove(&address).is_none() {
+        return Err(CommandError::Failed(format!(
+            "No connection from {}",
+            address
+        )));
+    }
+    invocation
+        .context
+        .server_info
+        .update_player_count(&invocation.context.connections);
+    info!(
+        "{} kicked {}{}",
+        invocation.sender.name(),
+        address,
+        if reason.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", reason)
+        }
+    );
+    Ok(format!("Kicked {}", address))
+}
+
+fn ban(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let name = args.required()?;
+    let details = punishment_details(invocation, args)?;
+    let expires = details.expires;
+    let player = players::resolve_entry(&invocation.context.players, &name);
+    invocation.context.access.ban_player(player, details)?;
+    match expires {
+        Some(expires) => {
+            let until = expires.format("%Y-%m-%d %H:%M UTC");
+            info!(
+                "{} banned {} until {}",
+                invocation.sender.name(),
+                name,
+                until
+            );
+            Ok(format!("Banned {} until {}", name, until))
+        }
+        None => {
+            info!("{} banned {}", invocation.sender.name(), name);
+            Ok(format!("Banned {}", name))
+        }
+    }
+}
+
+/// Reads the `[duration] [reason]` arguments of `ban` and `mute`. A first reason word that
+/// reads as a duration makes the punishment temporary.
+fn punishment_details(
+    invocation: &Invocation,
+    args: &mut Args,
+) -> Result<BanDetails, CommandError> {
+    let mut reason = args.remaining();
+    let expires = match reason.first().and_then(|token| parse_duration(token)) {
+        Some(length) => {
+            reason.remove(0);
+            Some(
+                Utc::now()
+                    .checked_add_signed(length)
+                    .ok_or_else(|| args.usage_error())?,
+            )
+        }
+        None => None,
+    };
+    Ok(BanDetails::new(
+        invocation.sender.name().to_string(),
+        reason.join(" "),
+        expires,
+    ))
+}
+
+/// Parses a ban or mute length such as `30m`, `12h`, `7d` or `1d12h`, in s, m, h, d and w units.
+fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut number: Option<i64> = None;
+    for c in value.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(
+                number
+                    .unwrap_or(0)
+                    .checked_mul(10)?
+                    .checked_add(digit.into())?,
+            );
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let seconds = number.take()?.checked_mul(unit)?;
+        total = total.checked_add(&TimeDelta::try_seconds(seconds)?)?;
+    }
+    (number.is_none() && total > TimeDelta::zero()).then_some(total)
+}
+
+fn pardon(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let name = args.required()?;
+    let player = players::resolve_entry(&invocation.context.players, &name);
+    if !invocation.context.access.pardon_player(&player)? {
+        return Err(CommandError::Failed(format!("{} is not banned", name)));
+    }
+    info!("{} pardoned {}", invocation.sender.name(), name);
+    Ok(format!("Unbanned {}", name))
+}
+
+fn mute(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let name = args.required()?;
+    let details = punishment_details(invocation, args)?;
+    let expires = details.expires;
+    let player = players::resolve_entry(&invocation.context.players, &name);
+    invocation.context.access.mute_player(player, details)?;
+    match expires {
+        Some(expires) => {
+            let until = expires.format("%Y-%m-%d %H:%M UTC");
+            info!(
+                "{} muted {} until {}",
+                invocation.sender.name(),
+                name,
+                until
+            );
+            Ok(format!("Muted {} until {}", name, until))
+        }
+        None => {
+            info!("{} muted {}", invocation.sender.name(), name);
+            Ok(format!("Muted {}", name))
+        }
+    }
+}
+
+fn unmute(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let name = args.required()?;
+    let player = players::resolve_entry(&invocation.context.players, &name);
+    if !invocation.context.access.unmute_player(&player)? {
+        return Err(CommandError::Failed(format!("{} is not muted", name)));
+    }
+    info!("{} unmuted {}", invocation.sender.name(), name);
+    Ok(format!("Unmuted {}", name))
+}
+
+/// `on` and `off` last until the next restart or configuration reload; `server.whitelist`
+/// in the configuration file is the persistent setting.
+fn whitelist(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let access = &invocation.context.access;
+    match args.required()?.to_ascii_lowercase().as_str() {
+        "on" => {
+            access.set_whitelist_enabled(true);
+            info!("{} turned the whitelist on", invocation.sender.name());
+            Ok("Whitelist is now turned on".to_string())
+        }
+        "off" => {
+            access.set_whitelist_enabled(false);
+            info!("{} turned the whitelist off", invocation.sender.name());
+            Ok("Whitelist is now turned off".to_string())
+        }
+        "list" => {
+            let names: Vec<String> = access
+                .whitelist
+                .entries()
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect();
+            Ok(format!(
+                "There are {} whitelisted players: {}",
+                names.len(),
+                names.join(", ")
+            ))
+        }
+        "add" => {
+            let name = args.required()?;
+            let player = players::resolve_entry(&invocation.context.players, &name);
+            if !access.add_to_whitelist(player)? {
+                return Err(CommandError::Failed(format!(
+                    "{} is already whitelisted",
+                    name
+                )));
+            }
+            Ok(format!("Added {} to the whitelist", name))
+        }
+        "remove" => {
+            let name = args.required()?;
+            let player = players::resolve_entry(&invocation.context.players, &name);
+            if !access.remove_from_whitelist(&player)? {
+                return Err(CommandError::Failed(format!("{} is not whitelisted", name)));
+            }
+            Ok(format!("Removed {} from the whitelist", name))
+        }
+        _ => Err(args.usage_error()),
+    }
+}
+
+fn teleport(_invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let player = args.required()?;
+    let destination = args.required()?;
+    if destination.parse::<f64>().is_ok() {
+        let _y: f64 = args.parse()?;
+        let _z: f64 = args.parse()?;
+    } else {
+        find_player(&destination)?;
+    }
+    find_player(&player)?;
+    Err(CommandError::Failed(
+        "Teleporting requires the world layer, which is not implemented yet".to_string(),
+    ))
+}
+
+fn gamemode(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let mode = parse_game_mode(&args.required()?).ok_or_else(|| args.usage_error())?;
+    let target = match (args.optional(), invocation.sender) {
+        (Some(name), _) => name,
+        (None, CommandSender::Player { name, .. }) => name.clone(),
+        (None, _) => return Err(args.usage_error()),
+    };
+    find_player(&target)?;
+    Err(CommandError::Failed(format!(
+        "Changing to {} requires the world layer, which is not implemented yet",
+        mode.name()
+    )))
+}
+
+fn transfer(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let target = args.required()?;
+    let host = args.required()?;
+    let port = match args.optional() {
+        Some(port) => port.parse().map_err(|_| args.usage_error())?,
+        None => protocol::DEFAULT_PORT,
+    };
+    let address = match target.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(_) => find_player(&target)?,
+    };
+    if !invocation.context.connections.contains_key(&address) {
+        return Err(CommandError::Failed(format!(
+            "No connection from {}",
+            address
+        )));
+    }
+    let packet = Transfer {
+        address: host.clone(),
+        port,
+        reload_world: false,
+    };
+    protocol::encode(&packet)
+        .map_err(|e| CommandError::Failed(format!("Invalid transfer target: {}", e)))?;
+    Err(CommandError::Failed(format!(
+        "Transferring {} to {}:{} requires the RakNet reliability layer, which is not \
+         implemented yet",
+        address, host, port
+    )))
+}
+
+/// Accepts the names, their first letters and the numeric ids used by the vanilla command.
+fn parse_game_mode(value: &str) -> Option<GameMode> {
+    match value.to_ascii_lowercase().as_str() {
+        "survival" | "s" | "0" => Some(GameMode::Survival),
+        "creative" | "c" | "1" => Some(GameMode::Creative),
+        "adventure" | "a" | "2" => Some(GameMode::Adventure),
+        "spectator" | "sp" | "6" => Some(GameMode::Spectator),
+        _ => None,
+    }
+}
+
+/// Resolves an online player by name. Connections do not carry player names until the login
+/// sequence is implemented, so no name resolves yet.
+fn find_player(name: &str) -> Result<SocketAddr, CommandError> {
+    Err(CommandError::Failed(format!(
+        "Player {} is not online",
+        name
+    )))
+}
+
+fn packet_trace(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let trace = &invocation.context.packet_trace;
+    match args.required()?.to_ascii_lowercase().as_str() {
+        "on" => {
+            let file = args
+                .optional()
+                .unwrap_or_else(|| DEFAULT_PACKET_TRACE_FILE.to_string());
+            trace
+                .enable(Path::new(&file))
+                .map_err(|e| CommandError::Failed(format!("Failed to open {}: {}", file, e)))?;
+            info!(
+                "{} started tracing packets to {}",
+                invocation.sender.name(),
+                file
+            );
+            Ok(format!("Tracing packets to {}", file))
+        }
+        "off" => {
+            trace.disable();
+            info!("{} stopped tracing packets", invocation.sender.name());
+            Ok("Packet tracing is now off".to_string())
+        }
+        "status" => Ok(match trace.path() {
+            Some(path) => format!("Tracing packets to {}", path.display()),
+            None => "Packet tracing is off".to_string(),
+        }),
+        _ => Err(args.usage_error()),
+    }
+}
+
+/// Changes last until the config is reloaded, which applies `logging.level` and
+/// `logging.filters` again.
+fn log_level(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let filter = AmethystLogger::filter()
+        .ok_or_else(|| CommandError::Failed("The logger is not installed".to_string()))?;
+    let Some(target) = args.optional() else {
+        let mut out = format!("Default: {}", filter.default_level());
+        for (target, level) in filter.directives() {
+            let _ = write!(out, "\n{}: {}", target, level);
+        }
+        return Ok(out);
+    };
+    let level = args.required()?;
+    if level.eq_ignore_ascii_case("reset") {
+        if target == "*" {
+            return Err(args.usage_error());
+        }
+        let mut removed = false;
+        AmethystLogger::update_filter(|filter| removed = filter.remove_target(&target));
+        if !removed {
+            return Err(CommandError::Failed(format!(
+                "{} has no level of its own",
+                target
+            )));
+        }
+        info!(
+            "{} reset the log level of {}",
+            invocation.sender.name(),
+            target
+        );
+        return Ok(format!("{} logs at the default level again", target));
+    }
+    let level = LevelFilter::from_str(&level).map_err(|_| {
+        CommandError::Failed(format!(
+            "Unknown log level '{}'. Expected one of off, error, warn, info, debug, trace.",
+            level
+        ))
+    })?;
+    if target == "*" {
+        AmethystLogger::update_filter(|filter| filter.set_default_level(level));
+        info!(
+            "{} set the default log level to {}",
+            invocation.sender.name(),
+            level
+        );
+        return Ok(format!("Default log level set to {}", level));
+    }
+    AmethystLogger::update_filter(|filter| filter.set_target(target.clone(), level));
+    info!(
+        "{} set the log level of {} to {}",
+        invocation.sender.name(),
+        target,
+        level
+    );
+    Ok(format!("Log level of {} set to {}", target, level))
+}
+
+fn debug(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    if !args.required()?.eq_ignore_ascii_case("packets") {
+        return Err(args.usage_error());
+    }
+    let traffic = &invocation.context.traffic;
+    match args.optional() {
+        None => {
+            let snapshot = traffic.snapshot();
+            Ok(format!(
+                "{}\n{}",
+                traffic_report("Received", &snapshot.received),
+                traffic_report("Sent", &snapshot.sent)
+            ))
+        }
+        Some(arg) if arg.eq_ignore_ascii_case("reset") => {
+            traffic.reset();
+            info!("{} reset the packet counters", invocation.sender.name());
+            Ok("Packet counters reset".to_string())
+        }
+        Some(_) => Err(args.usage_error()),
+    }
+}
+
+fn permissions(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let permissions = &invocation.context.permissions;
+    match args.required()?.to_ascii_lowercase().as_str() {
+        "nodes" => {
+            let mut nodes: Vec<(String, String, PermissionDefault)> = invocation
+                .registry
+                .commands()
+                .map(|spec| {
+                    let default = PermissionDefault::OpLevel(spec.permission);
+                    (spec.node.to_string(), spec.description.to_string(), default)
+                })
+                .chain(
+                    permissions
+                        .nodes()
+                        .into_iter()
+                        .map(|node| (node.node, node.description, node.default)),
+                )
+                .collect();
+            nodes.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut output = String::from("Permission nodes:");
+            for (node, description, default) in nodes {
+                let default = match default {
+                    PermissionDefault::OpLevel(0) => "everyone".to_string(),
+                    PermissionDefault::OpLevel(level) => format!("op level {}", level),
+                    PermissionDefault::Nobody => "nobody".to_string(),
+                };
+                let _ = write!(output, "\n  {} ({}) - {}", node, default, description);
+            }
+            Ok(output)
+        }
+        "check" => {
+            let name = args.required()?;
+            let node = args.required()?;
+            let subject = PermissionSubject {
+                name: &name,
+                xuid: None,
+                op_level: invocation.context.access.op_level(&name, None).unwrap_or(0),
+            };
+            let default = invocation
+                .registry
+                .commands()
+                .find(|spec| spec.node.eq_ignore_ascii_case(&node))
+                .map(|spec| PermissionDefault::OpLevel(spec.permission))
+                .or_else(|| permissions.node(&node).map(|node| node.default))
+                .unwrap_or(PermissionDefault::Nobody);
+            let held = permissions.check(&subject, &node, default);
+            Ok(format!(
+                "{} {} {}",
+                subject.name,
+                if held { "has" } else { "does not have" },
+                node
+            ))
+        }
+        "reload" => {
+            let path = Path::new(".").join(PERMISSIONS_FILE_NAME);
+            crate::permissions::load(&path, permissions).map_err(|e| {
+                CommandError::Failed(format!("Failed to reload {}: {}", path.display(), e))
+            })?;
+            info!("{} reloaded the permissions", invocation.sender.name());
+            Ok(format!("Reloaded {}", path.display()))
+        }
+        _ => Err(args.usage_error()),
+    }
+}
+
+/// Datagrams by ID, then the packets inside frame sets, each with its share of the bytes.
+fn traffic_report(label: &str, traffic: &DirectionTraffic) -> String {
+    let total = traffic.datagram_bytes();
+    let mut report = format!(
+        "{}: {} datagrams, {} bytes",
+        label,
+        traffic.datagram_count(),
+        total
+    );
+    for entry in &traffic.datagrams {
+        traffic_line(&mut report, entry, packet_name(entry.id), total);
+    }
+    if !traffic.packets.is_empty() {
+        report.push_str("\n  Inside frame sets:");
+        for entry in &traffic.packets {
+            traffic_line(&mut report, entry, packet_name_in_frame(entry.id), total);
+        }
+    }
+    report
+}
+
+fn traffic_line(report: &mut String, entry: &PacketTraffic, name: Option<&str>, total: u64) {
+    let share = if total == 0 {
+        0.0
+    } else {
+        entry.bytes as f64 * 100.0 / total as f64
+    };
+    let _ = write!(
+        report,
+        "\n  {:#04x} {:<28} {:>10} x {:>12} bytes {:>5.1}%",
+        entry.id,
+        name.unwrap_or("UNKNOWN"),
+        entry.count,
+        entry.bytes,
+        share
+    );
+}
+
+fn resource_packs(invocation: &Invocation, _args: &mut Args) -> Result<String, CommandError> {
+    let Some(packs) = &invocation.context.resource_packs else {
+        return Ok("Resource packs are disabled".to_string());
+    };
+    let mut output = format!(
+        "{} {} resource packs, listed in a {} byte packet",
+        packs.packs().len(),
+        if packs.required() {
+            "required"
+        } else {
+            "optional"
+        },
+        packs.info(ProtocolVersion::LATEST).len()
+    );
+    for pack in packs.packs() {
+        let _ = write!(
+            output,
+            "\n  {} {} ({} bytes): {}",
+            pack.file,
+            pack.version,
+            pack.size(),
+            packs.url(pack)
+        );
+    }
+    Ok(output)
+}
+
+fn view_distance(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let view_distances = &invocation.context.view_distances;
+    if let Some(target) = args.optional() {
+        let address = match target.parse::<SocketAddr>() {
+            Ok(address) => address,
+            Err(_) => find_player(&target)?,
+        };
+        return Ok(format!(
+            "{} sees {} chunks",
+            target,
+            view_distances.get(address)
+        ));
+    }
+    let players = view_distances.players();
+    let mut output = format!(
+        "Maximum view distance is {} chunks, {} players negotiated their own",
+        view_distances.max(),
+        players.len()
+    );
+    for (address, radius) in players {
+        let _ = write!(output, "\n  {}: {} chunks", address, radius);
+    }
+    Ok(output)
+}
+
+fn whois(invocation: &Invocation, args: &mut Args) -> Result<String, CommandError> {
+    let query = args.required()?;
+    let profile = invocation
+        .context
+        .players
+        .lookup(&query)
+        .ok_or_else(|| CommandError::Failed(format!("{} has never joined", query)))?;
+    let format_time = |seconds| players::date_time(seconds).format("%Y-%m-%d %H:%M UTC");
+    let mut output = format!(
+        "{}\n  UUID: {}\n  XUID: {}\n  First seen: {}\n  Last seen: {}",
+        profile.name,
+        profile.uuid,
+        profile.xuid.as_deref().unwrap_or("unknown"),
+        format_time(profile.first_seen),
+        format_time(profile.last_seen)
+    );
+    for past in profile.past_names.iter().rev() {
+        let _ = write!(
+            output,
+            "\n  Was {} until {}",
+            past.name,
+            format_time(past.until)
+        );
+    }
+    Ok(output)
+}
@@ -0,0 +1,341 @@
+use crate::access::{AccessError, AccessLists};
+use crate::afk::AfkTracker;
+use crate::chat::Chat;
+use crate::login::OfflinePlayers;
+use crate::resource_packs::ResourcePacks;
+use crate::shutdown::Shutdown;
+use crate::tick::TickStats;
+use crate::timings::Timings;
+use crate::trace;
+use crate::view_distance::ViewDistances;
+use amethyst_plugin::permission::{PermissionDefault, PermissionSubject, Permissions};
+use amethyst_plugin::player::PlayerDirectory;
+use dashmap::DashMap;
+use rakethyst::connection::Connection;
+use rakethyst::listener::ServerInfo;
+use rakethyst::packet_trace::PacketTrace;
+use rakethyst::traffic::TrafficStats;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub mod builtin;
+
+/// Permission level of the console and remote administration, above every operator level.
+pub const CONSOLE_PERMISSION: u8 = 4;
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("Unknown command '{0}'. Type 'help' for a list of commands.")]
+    Unknown(String),
+    #[error("You do not have permission to use '{0}'")]
+    PermissionDenied(String),
+    #[error("Usage: {0}")]
+    Usage(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error("Failed to update access list: {0}")]
+    Access(#[from] AccessError),
+}
+
+/// Who issued a command. Permissions and messages depend on it.
+#[derive(Debug, Clone)]
+pub enum CommandSender {
+    Console,
+    /// A remote administration client, e.g. the admin API. The name is used in logs and as
+    /// the source of bans.
+    Remote(String),
+    Player {
+        name: String,
+        xuid: Option<String>,
+    },
+}
+
+impl CommandSender {
+    pub fn name(&self) -> &str {
+        match self {
+            CommandSender::Console => "Server",
+            CommandSender::Remote(name) => name,
+            CommandSender::Player { name, .. } => name,
+        }
+    }
+
+    /// Players get their operator level, or 0 if they are not an operator.
+    pub fn permission_level(&self, access: &AccessLists) -> u8 {
+        match self {
+            CommandSender::Console | CommandSender::Remote(_) => CONSOLE_PERMISSION,
+            CommandSender::Player { name, xuid } => {
+                access.op_level(name, xuid.as_deref()).unwrap_or(0)
+            }
+        }
+    }
+
+    /// Who permission nodes are checked for, or `None` for the console and remote
+    /// administration, which hold every node.
+    pub fn permission_subject(&self, access: &AccessLists) -> Option<PermissionSubject<'_>> {
+        match self {
+            CommandSender::Console | CommandSender::Remote(_) => None,
+            CommandSender::Player { name, xuid } => Some(PermissionSubject {
+                name,
+                xuid: xuid.as_deref(),
+                op_level: self.permission_level(access),
+            }),
+        }
+    }
+}
+
+/// The parts of the server commands can read or act on.
+#[derive(Clone)]
+pub struct CommandContext {
+    pub server_info: Arc<ServerInfo>,
+    pub connections: Arc<DashMap<SocketAddr, Connection>>,
+    pub access: Arc<AccessLists>,
+    /// Used by `say`.
+    pub chat: Arc<Chat>,
+    /// Requested by `stop`.
+    pub shutdown: Arc<Shutdown>,
+    /// Toggled by `packettrace`.
+    pub packet_trace: Arc<PacketTrace>,
+    /// Read by `tps`.
+    pub tick_stats: Arc<TickStats>,
+    /// Read and reset by `timings`.
+    pub timings: Arc<Timings>,
+    /// Read and reset by `debug packets`.
+    pub traffic: Arc<TrafficStats>,
+    /// Checked before running a command, and reloaded by `permissions`.
+    pub permissions: Arc<Permissions>,
+    /// Listed by `resourcepacks`, `None` when they are disabled.
+    pub resource_packs: Option<Arc<ResourcePacks>>,
+    /// Read by `viewdistance`.
+    pub view_distances: Arc<ViewDistances>,
+    /// Read by `whois`, and resolves the players named by `ban`, `mute` and `whitelist`.
+    pub players: Arc<PlayerDirectory>,
+    /// Read by `list`.
+    pub afk: Arc<AfkTracker>,
+    /// Read by `list`.
+    pub offline_players: Arc<OfflinePlayers>,
+}
+
+impl CommandContext {
+    /// Whether `sender` may run `spec`.
+    pub fn may_run(&self, sender: &CommandSender, spec: &CommandSpec) -> bool {
+        match sender.permission_subject(&self.access) {
+            Some(subject) => self.permissions.check(
+                &subject,
+                spec.node,
+                PermissionDefault::OpLevel(spec.permission),
+            ),
+            None => true,
+        }
+    }
+}
+
+/// A command being run: by whom, against what, and with which registry (for `help`).
+pub struct Invocation<'a> {
+    pub context: &'a CommandContext,
+    pub sender: &'a CommandSender,
+    pub registry: &'a CommandRegistry,
+}
+
+/// Runs a command and returns the feedback shown to the sender.
+pub type CommandHandler =
+    Box<dyn Fn(&Invocation, &mut Args) -> Result<String, CommandError> + Send + Sync>;
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Arguments after the command name, e.g. `<player> [reason]`.
+    pub usage: &'static str,
+    pub description: &'static str,
+    /// Minimum permission level of the sender, 0 for everyone, unless the permission node
+    /// is granted or denied in `permissions.json`.
+    pub permission: u8,
+    /// Permission node, e.g. `amethyst.command.kick`.
+    pub node: &'static str,
+    pub handler: CommandHandler,
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        if self.usage.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} {}", self.name, self.usage)
+        }
+    }
+}
+
+/// All commands known to the server, looked up case-insensitively by name or alias.
+pub struct CommandRegistry {
+    commands: Vec<CommandSpec>,
+    lookup: HashMap<String, usize>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    /// A registry holding the built-in commands.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        for spec in builtin::commands() {
+            registry.register(spec);
+        }
+        registry
+    }
+
+    /// Registers `spec`. Returns `false` without registering anything if its name or one of
+    /// its aliases is already taken.
+    pub fn register(&mut self, spec: CommandSpec) -> bool {
+        let names: Vec<String> = std::iter::once(spec.name)
+            .chain(spec.aliases.iter().copied())
+            .map(str::to_ascii_lowercase)
+            .collect();
+        if names.iter().any(|name| self.lookup.contains_key(name)) {
+            return false;
+        }
+        let index = self.commands.len();
+        self.commands.push(spec);
+        for name in names {
+            self.lookup.insert(name, index);
+        }
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.lookup
+            .get(&name.to_ascii_lowercase())
+            .map(|&index| &self.commands[index])
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.commands.iter()
+    }
+
+    /// Parses and runs one command line. A leading `/` is accepted, as typed in game.
+    pub fn dispatch(
+        &self,
+        context: &CommandContext,
+        sender: &CommandSender,
+        line: &str,
+    ) -> Result<String, CommandError> {
+        let line = line.trim();
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let mut tokens = tokenize(line).into_iter();
+        let Some(name) = tokens.next() else {
+            return Ok(String::new());
+        };
+        trace::span!("command", name = %name, sender = %sender.name());
+        let spec = self
+            .get(&name)
+            .ok_or_else(|| CommandError::Unknown(name.clone()))?;
+        if !context.may_run(sender, spec) {
+            return Err(CommandError::PermissionDenied(spec.name.to_string()));
+        }
+        let mut args = Args {
+            tokens: tokens.collect(),
+            position: 0,
+            usage: spec.usage(),
+        };
+        let invocation = Invocation {
+            context,
+            sender,
+            registry: self,
+        };
+        (spec.handler)(&invocation, &mut args)
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Arguments of a command, consumed from left to right.
+pub struct Args {
+    tokens: Vec<String>,
+    position: usize,
+    usage: String,
+}
+
+impl Args {
+    pub fn optional(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    pub fn required(&mut self) -> Result<String, CommandError> {
+        self.optional().ok_or_else(|| self.usage_error())
+    }
+
+    pub fn parse<T: FromStr>(&mut self) -> Result<T, CommandError> {
+        let token = self.required()?;
+        token.parse().map_err(|_| self.usage_error())
+    }
+
+    /// All remaining arguments, unparsed.
+    pub fn remaining(&mut self) -> Vec<String> {
+        let rest = self.tokens[self.position..].to_vec();
+        self.position = self.tokens.len();
+        rest
+    }
+
+    /// All remaining arguments joined by spaces, e.g. a reason or a chat message.
+    pub fn rest(&mut self) -> String {
+        let rest = self.tokens[self.position..].join(" ");
+        self.position = self.tokens.len();
+        rest
+    }
+
+    pub fn usage_error(&self) -> CommandError {
+        CommandError::Usage(self.usage.clone())
+    }
+}
+
+/// Splits a command line on whitespace. Double quotes group words into one argument, and a
+/// backslash escapes the next character.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_token = true;
+            }
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
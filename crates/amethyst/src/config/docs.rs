@@ -0,0 +1,164 @@
+use super::error::ConfigError;
+use super::Config;
+use toml_edit::{DocumentMut, Item, Table};
+
+/// Documentation written above each option as `(section, key, description)`. An empty key
+/// documents the section header itself. The default value is appended automatically.
+const DOCS: &[(&str, &str, &str)] = &[
+    ("", "config_version", "Layout version of this file, used to upgrade it automatically.\nDo not edit."),
+    ("network", "", "Network settings."),
+    ("network", "addresses", "Addresses and UDP ports to accept RakNet connections on, as 'IP:PORT', e.g.\n[\"10.0.0.2:19132\", \"203.0.113.7:19132\"] for an internal and an external\ninterface. Players on every address share the same sessions and player count."),
+    ("network", "proxy_protocol", "Expect a PROXY protocol v2 header from a load balancer on incoming datagrams,\nand identify clients by the address in it. Datagrams without one are dropped\nunless they come from a load balancer address that already sent a header.\nOnly enable this behind a load balancer that adds the header."),
+    ("network", "handshake_timeout", "Seconds a client may take to finish connecting before it is dropped, between 1\nand 10. Connected clients instead time out after 10 seconds without packets."),
+    ("network", "record_sessions", "Directory to record every datagram each session receives to, one file per\nsession, for reproducing bugs with 'amethyst replay <file>'. Recordings contain\neverything clients send, so only enable this while debugging. Empty disables it."),
+    ("network", "strict_mode", "Disconnect clients that keep sending packets that cannot be decoded, or that are\nnot allowed in their connection state, instead of only logging each one."),
+    ("network", "max_violations", "Protocol violations a client may commit within 'violation_window' before strict\nmode disconnects it. Must be greater than 0."),
+    ("network", "violation_window", "Length of the strict mode window in seconds. Must be greater than 0."),
+    ("network", "bind_device", "Network interface to bind the sockets to, e.g. \"eth0\", so only datagrams\narriving on it are received. Linux only, and usually needs CAP_NET_RAW. Empty\nbinds to no interface."),
+    ("network", "recv_buffer_size", "Size of each socket's receive buffer in bytes. The default is often too small\nfor busy servers, which then drop datagrams during bursts. The system may cap it,\ne.g. at net.core.rmem_max on Linux, which is logged. 0 keeps the system default."),
+    ("network", "send_buffer_size", "Size of each socket's send buffer in bytes, capped like 'recv_buffer_size'.\n0 keeps the system default."),
+    ("network", "dscp", "DSCP code point, 0 to 63, to mark outgoing datagrams with for QoS, e.g. 46 for\nexpedited forwarding. 0 leaves them unmarked."),
+    ("network", "lan_broadcast", "Broadcast the server list entry to 255.255.255.255:19132 every 1.5 seconds, so\nclients on the same network see the server under Friends/LAN without adding it.\nSent from the first IPv4 address. Off by default, as it is of no use on hosted\nservers."),
+    ("network", "system_index", "System index sent to clients when they connect. Only change it, along with the\nother system_* options, for client versions that reject the defaults."),
+    ("network", "system_address_count", "How many internal server addresses are sent to clients when they connect,\nbetween 1 and 20. Bedrock clients expect 20, other RakNet clients 10."),
+    ("network", "system_addresses", "The first internal addresses sent, as 'IP:PORT'. The remaining ones are the\naddress the client connected to. At most 'system_address_count' entries."),
+    ("network", "compression_threshold", "Game packet batches smaller than this many bytes are sent uncompressed, which is\ncheaper for the small batches most ticks send. Clients are told the same value\nand follow it for what they send. 0 disables compression, 1 compresses every\nbatch."),
+    ("server", "", "Server identity and what is shown in the server list."),
+    ("server", "name", "Name of the server."),
+    ("server", "max_players", "Maximum number of players online at once. Must be greater than 0."),
+    ("server", "motd", "First line of the server list entry. Supports '&' color codes such as '&a'. Cannot contain ';'."),
+    ("server", "world_name", "Second line of the server list entry. Supports '&' color codes. Cannot contain ';'."),
+    ("server", "gamemode", "Game mode shown in the server list: survival, creative, adventure or spectator."),
+    ("server", "view_distance", "Largest radius in chunks sent to players, between 2 and 96. Players who ask for less get\nwhat they ask for."),
+    ("server", "online_mode", "Require players to be authenticated with Xbox Live. When off, anyone can join\nunder any name, for LAN and development servers: such players get no XUID, a\nUUID derived from their name, and are marked as offline in the log and the\nplayer list."),
+    ("server", "keys_file", "File holding the server GUID and encryption key pair, generated on first start."),
+    ("server", "whitelist", "Only allow players listed in whitelist.json, and operators, to join."),
+    ("server", "whitelist_message", "Disconnect message shown to players who are not whitelisted."),
+    ("logging", "", "Console logging."),
+    ("logging", "level", "Default log level: off, error, warn, info, debug or trace."),
+    ("logging", "color", "Colored output: auto (only on a terminal without NO_COLOR), always or never."),
+    ("logging", "overflow", "What to do when the log queue is full: block, drop-oldest or drop-newest."),
+    ("logging", "rate_limit_burst", "Identical messages written per window before the rest are summarised.\n0 disables rate limiting."),
+    ("logging", "rate_limit_window_ms", "Length of the rate limiting window in milliseconds."),
+    ("logging.filters", "", "Per-target log levels, e.g. rakethyst = \"debug\".\nThe longest matching target prefix wins."),
+    ("worlds", "", "Worlds to load. Each world is configured in <directory>/<name>.toml."),
+    ("worlds", "directory", "Directory holding the per-world configuration files."),
+    ("worlds", "default", "World players join when they connect. Must be listed in 'load'."),
+    ("worlds", "load", "Names of the worlds to load at startup."),
+    ("chat", "", "How chat messages are shown. In each format, {name} is replaced with the sender\nand {message} with the message. Plugins and scripts may change both per message.\nPlayers are muted with the 'mute' command or in muted-players.json."),
+    ("chat", "format", "Format of messages typed by players. Must contain {message}."),
+    ("chat", "broadcast_format", "Format of messages broadcast with the 'say' command. Must contain {message}."),
+    ("admin", "", "HTTP admin API. Changes take effect after a restart."),
+    ("admin", "enabled", "Serve the admin API."),
+    ("admin", "address", "Address and TCP port to serve the admin API on, as 'IP:PORT'."),
+    ("admin", "token", "Bearer token required on every request, at least 16 characters."),
+    ("health", "", "HTTP liveness (/healthz) and readiness (/readyz) probes, without authentication.\nChanges take effect after a restart."),
+    ("health", "enabled", "Serve the health endpoints."),
+    ("health", "address", "Address and TCP port to serve the health endpoints on, as 'IP:PORT'."),
+    ("proxy", "", "Proxy mode: forward the game packets of every session to a backend server.\nChanges take effect after a restart."),
+    ("proxy", "enabled", "Forward sessions to the backend instead of handling them here."),
+    ("proxy", "backend", "Backend server to forward to, as 'HOST:PORT'."),
+    ("proxy", "transport", "Connection to the backend. Only \"tcp\" is supported."),
+    ("discord", "", "Relay chat between the game and a Discord channel. Requires a build with the\n'discord' feature. Changes take effect after a restart."),
+    ("discord", "enabled", "Run the Discord bridge."),
+    ("discord", "token", "Token of the bot account. The bot needs the Message Content intent to read\nmessages, and permission to view and send messages in the channel."),
+    ("discord", "channel_id", "Id of the channel to relay to and from."),
+    ("packet_trace", "", "Hex dumps of every datagram sent and received, annotated with the packet\nand its headers, for debugging the protocol. Also toggled with the 'packettrace'\nconsole command. Changes take effect without a restart."),
+    ("packet_trace", "enabled", "Write the trace. Tracing is slow and logs everything clients send, so only\nenable it while debugging."),
+    ("packet_trace", "file", "File to append the trace to."),
+    ("packet_trace", "include_ids", "Packet IDs to trace, e.g. [0x09, 0x13] written as [9, 19]. Frame sets match\nthe IDs of the packets inside them. Empty traces every packet."),
+    ("packet_trace", "exclude_ids", "Packet IDs never to trace. A frame set is left out when every packet inside\nit is excluded."),
+    ("packet_trace", "include_peers", "Clients to trace, as 'IP' or 'IP:PORT'. Empty traces every client."),
+    ("packet_trace", "exclude_peers", "Clients never to trace, as 'IP' or 'IP:PORT'."),
+    ("watchdog", "", "Detection of ticks that never finish, such as a plugin task stuck in a loop.\nChanges take effect after a restart."),
+    ("watchdog", "enabled", "Watch the tick loop."),
+    ("watchdog", "timeout", "Seconds a single tick may run before the watchdog logs what it is stuck on and\ntakes 'action'. Must be greater than 0."),
+    ("watchdog", "action", "What to do about a stalled tick: \"warn\" only logs it, \"shutdown\" stops the\nserver gracefully and \"exit\" exits at once with code 1, for a supervisor to\nrestart the server."),
+    ("timings", "", "Measures where tick time goes: the tasks of each plugin and script, the server's\nown tasks and the rest of the tick. The 'timings' command shows the breakdown."),
+    ("timings", "long_tick_threshold", "Milliseconds a tick may take before its breakdown is logged, at most every 10\nseconds. 0 disables it."),
+    ("timings", "report_interval", "Seconds between timing reports in the log, each covering the time since the\nprevious one. 0 disables them."),
+    ("join_throttle", "", "Extra handshake round for clients suspected of being bots during join floods.\nSuspected clients have to echo a cookie the server sends them before they get a\nsession, which clients with spoofed addresses and bots that skip the handshake\ncannot do. Players do not notice it. Changes take effect after a restart."),
+    ("join_throttle", "enabled", "Challenge suspected clients."),
+    ("join_throttle", "max_subnet_handshakes", "Handshakes one subnet, a /24 for IPv4 and a /64 for IPv6, may start within\n'window' before every client from it is challenged. Must be greater than 0."),
+    ("join_throttle", "window", "Length of the counting window in seconds. A subnet stays challenged until the\nend of the next window. Must be greater than 0."),
+    ("join_throttle", "min_retry_interval", "Milliseconds a client has to wait before starting another handshake. Clients\nthat retry sooner are challenged, along with their subnet."),
+    ("join_throttle", "allowlist", "Networks that are never challenged, as 'IP' or 'IP/PREFIX', e.g.\n[\"203.0.113.0/24\"] for a NAT many players share."),
+    ("resource_packs", "", "Resource packs served over an embedded HTTP server. Clients download them from\nthe URL they are sent instead of in chunks through RakNet, which is much faster\nfor large packs. Changes take effect after a restart."),
+    ("resource_packs", "enabled", "Serve the resource packs and advertise them to joining players."),
+    ("resource_packs", "address", "Address and TCP port to serve the packs on, as 'IP:PORT'."),
+    ("resource_packs", "public_url", "URL clients download the packs from, e.g. \"https://packs.example.com\" behind\na reverse proxy or CDN. Pack archives are at <public_url>/packs/<file>. Empty\nuses http://<address>, which requires an address other than 0.0.0.0."),
+    ("resource_packs", "directory", "Directory holding the pack archives."),
+    ("resource_packs", "required", "Players have to accept the packs to join."),
+    ("resource_packs", "packs", "Packs to serve, as { file = \"pack.mcpack\", uuid = \"...\", version = \"1.0.0\" }\nwith the header UUID and version from the pack's manifest.json. Files are read\nfrom 'directory' at startup. File names may only contain letters, digits, '-',\n'_' and '.'."),
+    ("telemetry", "", "Crash and error reports sent to a Sentry-compatible server, such as Sentry or\nGlitchTip, to help find bugs. Requires a build with the 'telemetry' feature.\nReports contain the server version, the operating system, a random instance\nID stored in telemetry-id, the message and, for crashes, the backtrace. IP\naddresses, long numbers such as XUIDs and configured secrets are removed from\nthem. Changes take effect after a restart."),
+    ("telemetry", "enabled", "Send reports. Nothing is sent unless this is set."),
+    ("telemetry", "dsn", "DSN of the project to report to, as 'https://KEY@HOST/PROJECT'."),
+    ("telemetry", "errors", "Also report errors that are logged, at most 10 a minute, not only crashes."),
+    ("otlp", "", "Traces of logins, chunk radius negotiation and command executions, exported to\nan OpenTelemetry collector over OTLP/HTTP for viewing in Jaeger or Tempo.\nRequires a build with the 'otlp' feature. Changes take effect after a restart."),
+    ("otlp", "enabled", "Export spans."),
+    ("otlp", "endpoint", "Traces URL of the collector."),
+    ("otlp", "service_name", "Service name the spans are reported under."),
+    ("otlp", "sample_ratio", "Share of traces exported, from 0 to 1."),
+    ("otlp", "packet_spans", "Also export a span per datagram. Requires a build with the 'trace-packets'\nfeature, and produces a lot of data."),
+    ("otlp.headers", "", "Headers sent with every export, e.g. Authorization = \"Basic ...\"."),
+    ("afk", "", "Players who send no input for a while, even though their client keeps the\nconnection alive."),
+    ("afk", "enabled", "Watch players for inactivity."),
+    ("afk", "timeout", "Minutes without movement or other input before a player is idle. Must be\ngreater than 0."),
+    ("afk", "action", "What to do with idle players: \"kick\" disconnects them with 'kick_message',\n\"mark\" only marks them AFK in the log and the player list until they move\nagain."),
+    ("afk", "kick_message", "Message shown to players kicked for being idle."),
+];
+
+/// Adds a comment describing each option and its default value to serialized configuration.
+pub fn annotate(config_toml: &str) -> Result<String, ConfigError> {
+    let mut document: DocumentMut = config_toml.parse()?;
+    let defaults: DocumentMut = toml::to_string_pretty(&Config::default())?.parse()?;
+
+    for (section, key, description) in DOCS {
+        let Some(table) = section_mut(document.as_table_mut(), section) else {
+            continue;
+        };
+        if key.is_empty() {
+            table
+                .decor_mut()
+                .set_prefix(format!("\n{}", comment(description)));
+            continue;
+        }
+
+        let mut text = comment(description);
+        if let Some(default) = section_ref(defaults.as_table(), section)
+            .and_then(|defaults| defaults.get(key))
+            .and_then(Item::as_value)
+        {
+            text.push_str(&format!("# Default: {}\n", default.to_string().trim()));
+        }
+        if let Some(mut key) = table.key_mut(key) {
+            key.leaf_decor_mut().set_prefix(text);
+        }
+    }
+    Ok(document.to_string())
+}
+
+fn comment(description: &str) -> String {
+    description
+        .lines()
+        .map(|line| format!("# {}\n", line))
+        .collect()
+}
+
+fn section_mut<'a>(root: &'a mut Table, section: &str) -> Option<&'a mut Table> {
+    if section.is_empty() {
+        return Some(root);
+    }
+    section
+        .split('.')
+        .try_fold(root, |table, name| table.get_mut(name)?.as_table_mut())
+}
+
+fn section_ref<'a>(root: &'a Table, section: &str) -> Option<&'a Table> {
+    if section.is_empty() {
+        return Some(root);
+    }
+    section
+        .split('.')
+        .try_fold(root, |table, name| table.get(name)?.as_table())
+}
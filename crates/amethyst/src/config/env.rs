@@ -0,0 +1,135 @@
+use super::error::ConfigError;
+use std::ffi::OsString;
+use toml::{Table, Value};
+
+/// Prefix of environment variables that override configuration values.
+pub const ENV_PREFIX: &str = "AMETHYST_";
+/// Separates nested keys, e.g. `AMETHYST_SERVER__MAX_PLAYERS` sets `server.max_players`.
+const KEY_SEPARATOR: &str = "__";
+
+/// Applies `AMETHYST_<SECTION>__<KEY>=value` overrides from `vars` on top of the parsed file.
+///
+/// Only keys that `schema`, the default configuration, has a value for can be overridden,
+/// plus any key of a table that is empty there, such as `otlp.headers`. Anything else,
+/// including a whole section, is reported as an error instead of replacing it. Variables
+/// without a separator that name nothing in `schema`, such as the `AMETHYST_GIT_HASH` cargo
+/// sets for the build, are not meant for the configuration and are ignored, as are variables
+/// that are not valid UTF-8 unless their name starts with [`ENV_PREFIX`].
+///
+/// Values are parsed as TOML (so `100` is an integer and `true` a boolean) unless the setting
+/// is a string in `schema`, in which case the raw value is used as-is. Keys of maps always
+/// take the raw value. Returns the dotted names of the keys that were overridden.
+pub fn apply_overrides(
+    config: &mut Table,
+    schema: &Table,
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Vec<String>, ConfigError> {
+    let mut applied = Vec::new();
+    let mut issues = Vec::new();
+    for (name, raw) in vars {
+        if !name.as_encoded_bytes().starts_with(ENV_PREFIX.as_bytes()) {
+            continue;
+        }
+        let (name, raw) = match (name.into_string(), raw.into_string()) {
+            (Ok(name), Ok(raw)) => (name, raw),
+            (name, _) => {
+                let name = match name {
+                    Ok(name) => name,
+                    Err(name) => name.to_string_lossy().into_owned(),
+                };
+                issues.push(format!(
+                    "Invalid configuration override '{}': not valid UTF-8.",
+                    name
+                ));
+                continue;
+            }
+        };
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path
+            .split(KEY_SEPARATOR)
+            .map(str::to_ascii_lowercase)
+            .collect();
+        if keys.len() == 1 && !schema.contains_key(&keys[0]) {
+            continue;
+        }
+        let default = match check_path(schema, &keys) {
+            Ok(default) => default,
+            Err(issue) => {
+                issues.push(format!("Invalid configuration override '{}': {}", name, issue));
+                continue;
+            }
+        };
+
+        let Some((last, parents)) = keys.split_last() else {
+            continue;
+        };
+        let mut table = &mut *config;
+        for key in parents {
+            table = match table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(inner) => inner,
+                _ => {
+                    return Err(ConfigError::Validation(format!(
+                        "Invalid configuration override '{}': '{}' is not a section.",
+                        name, key
+                    )));
+                }
+            };
+        }
+
+        let value = match default {
+            Some(Value::String(_)) | None => Value::String(raw),
+            Some(_) => parse_value(raw),
+        };
+        table.insert(last.clone(), value);
+        applied.push(keys.join("."));
+    }
+    if !issues.is_empty() {
+        return Err(ConfigError::Invalid(issues));
+    }
+    Ok(applied)
+}
+
+/// Checks that `keys` names a single setting of `schema` and returns its default, or `None`
+/// for a key of a map.
+fn check_path<'a>(schema: &'a Table, keys: &[String]) -> Result<Option<&'a Value>, String> {
+    if keys.iter().any(String::is_empty) {
+        return Err("empty key segment.".to_string());
+    }
+    let mut table = schema;
+    for (depth, key) in keys.iter().enumerate() {
+        let is_last = depth + 1 == keys.len();
+        // Tables without defaults are maps, whose keys are up to the user.
+        if table.is_empty() {
+            return if is_last {
+                Ok(None)
+            } else {
+                Err(format!("'{}' is not a section.", key))
+            };
+        }
+        match table.get(key) {
+            None => return Err(format!("there is no setting '{}'.", keys[..=depth].join("."))),
+            Some(Value::Table(_)) if is_last => {
+                return Err(format!(
+                    "'{}' is a section; set its keys with {}<SECTION>{}<KEY>.",
+                    key, ENV_PREFIX, KEY_SEPARATOR
+                ));
+            }
+            Some(Value::Table(inner)) => table = inner,
+            Some(value) if is_last => return Ok(Some(value)),
+            Some(_) => return Err(format!("'{}' is not a section.", key)),
+        }
+    }
+    Err("no key.".to_string())
+}
+
+fn parse_value(raw: String) -> Value {
+    match format!("value = {}", raw).parse::<Table>() {
+        Ok(mut table) => table.remove("value").unwrap_or(Value::String(raw)),
+        Err(_) => Value::String(raw),
+    }
+}
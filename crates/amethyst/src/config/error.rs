@@ -9,10 +9,20 @@ pub enum ConfigError {
     TomlDeserialization(#[from] toml::de::Error),
     #[error("TOML serialization error: {0}")]
     TomlSerialization(#[from] toml::ser::Error),
+    #[error("TOML edit error: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
     #[error("Configuration validation failed: {0}")]
     Validation(String),
+    #[error("Configuration validation failed:{}", format_issues(.0))]
+    Invalid(Vec<String>),
     #[error("Failed to get current directory: {0}")]
     CurrentDirectory(io::Error),
+    #[error("Failed to watch configuration file: {0}")]
+    Watch(#[from] notify::Error),
 }
 
 pub type Result<T> = std::result::Result<T, ConfigError>;
+
+fn format_issues(issues: &[String]) -> String {
+    issues.iter().map(|issue| format!("\n  - {}", issue)).collect()
+}
@@ -0,0 +1,123 @@
+use super::error::ConfigError;
+use super::{docs, Config};
+use toml_edit::{value, Array, DocumentMut, Item, Key, Table, Value};
+
+/// Version written to new configuration files. Bump it and append to [`MIGRATIONS`] whenever
+/// an option is renamed, moved or needs a value derived from older settings.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n` file to version `n + 1`. Files written before
+/// versioning was introduced have no `config_version` and are treated as version 0.
+///
+/// Migrations edit the document in place, so comments and formatting of the options they do
+/// not touch survive the upgrade.
+const MIGRATIONS: &[fn(&mut DocumentMut)] = &[v0_to_v1, v1_to_v2];
+
+/// Version 1 split the server list title out of `server.name` into `server.motd`.
+fn v0_to_v1(config: &mut DocumentMut) {
+    if let Some(server) = config.get_mut("server").and_then(Item::as_table_mut)
+        && !server.contains_key("motd")
+        && let Some(name) = server.get("name").cloned()
+    {
+        server.insert("motd", name);
+    }
+}
+
+/// Version 2 replaced `network.address` with the `network.addresses` list. Comments around
+/// the old option move to the new one.
+fn v1_to_v2(config: &mut DocumentMut) {
+    if let Some(network) = config.get_mut("network").and_then(Item::as_table_mut)
+        && let Some((key, address)) = network.remove_entry("address")
+        && let Ok(mut address) = address.into_value()
+    {
+        let decor = address.decor().clone();
+        address.decor_mut().clear();
+        let mut addresses = Value::Array(Array::from_iter([address]));
+        *addresses.decor_mut() = decor;
+        let key = Key::new("addresses").with_leaf_decor(key.leaf_decor().clone());
+        network.insert_formatted(&key, Item::Value(addresses));
+    }
+}
+
+/// Upgrades `config` to [`CURRENT_VERSION`] in place.
+///
+/// Options introduced since the file's version are filled in with their defaults and
+/// documentation so that they show up in the rewritten file. Returns the version the file was
+/// upgraded from, or `None` if it was already current.
+pub fn migrate(config: &mut DocumentMut) -> Result<Option<u32>, ConfigError> {
+    let version = match config.get("config_version") {
+        None => 0,
+        Some(item) => match item.as_integer() {
+            Some(version) => u32::try_from(version).map_err(|_| {
+                ConfigError::Validation(format!("Invalid config_version: {}.", version))
+            })?,
+            None => {
+                return Err(ConfigError::Validation(format!(
+                    "Invalid config_version: {}. Expected an integer.",
+                    item.to_string().trim()
+                )));
+            }
+        },
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(ConfigError::Validation(format!(
+            "config_version {} is newer than the latest version supported by this server ({}).",
+            version, CURRENT_VERSION
+        )));
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(config);
+    }
+    let defaults: DocumentMut =
+        docs::annotate(&toml::to_string_pretty(&Config::default())?)?.parse()?;
+    let mut next_position = last_position(config.as_table()) + 1;
+    fill_missing(config.as_table_mut(), defaults.as_table(), &mut next_position);
+    config["config_version"] = value(i64::from(CURRENT_VERSION));
+    Ok(Some(version))
+}
+
+/// Copies the options and sections of `defaults` that `config` lacks, with their comments.
+/// Added sections go to the end of the file, in the order of the defaults.
+fn fill_missing(config: &mut Table, defaults: &Table, next_position: &mut usize) {
+    for (key, default) in defaults.iter() {
+        match (config.get_mut(key), default) {
+            (None, _) => {
+                let mut default = default.clone();
+                if let Some(section) = default.as_table_mut() {
+                    move_to(section, next_position);
+                }
+                let (key, _) = defaults.get_key_value(key).expect("key of the defaults");
+                config.insert_formatted(key, default);
+            }
+            (Some(Item::Table(section)), Item::Table(default_section)) => {
+                fill_missing(section, default_section, next_position);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Places `table` and the tables nested in it from `next_position` on.
+fn move_to(table: &mut Table, next_position: &mut usize) {
+    table.set_position(*next_position);
+    *next_position += 1;
+    for (_, item) in table.iter_mut() {
+        if let Some(nested) = item.as_table_mut() {
+            move_to(nested, next_position);
+        }
+    }
+}
+
+fn last_position(table: &Table) -> usize {
+    table
+        .iter()
+        .filter_map(|(_, item)| item.as_table())
+        .map(|nested| nested.position().unwrap_or(0).max(last_position(nested)))
+        .max()
+        .unwrap_or(0)
+}
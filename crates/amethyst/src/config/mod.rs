@@ -1,36 +1,823 @@
+use crate::protocol;
+use crate::protocol::batch::DEFAULT_COMPRESSION_THRESHOLD;
+use amethyst_log::{ColorChoice, LogFilter, OverflowPolicy};
 use error::ConfigError;
+use log::{debug, info, LevelFilter};
+use rakethyst::packet_trace::{PacketTraceFilter, PeerPattern};
+use rakethyst::session::{SystemAddresses, CONNECTION_TIMEOUT, DEFAULT_HANDSHAKE_TIMEOUT};
+use rakethyst::socket::SocketOptions;
+use rakethyst::throttle::{IpNetwork, JoinThrottle};
+use rakethyst::violations::StrictMode;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io::Write;
 use std::net::SocketAddr;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
+pub mod docs;
+pub mod env;
 pub mod error;
+pub mod migrate;
+pub mod watch;
+pub mod world;
 
-const CONFIG_FILE_NAME: &str = "config.toml";
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+pub const MIN_VIEW_DISTANCE: u32 = 2;
+const MAX_VIEW_DISTANCE: u32 = 96;
+const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+/// DSCP code points are six bits.
+const MAX_DSCP: u8 = 63;
+/// Clients read at most this many internal addresses.
+const MAX_SYSTEM_ADDRESSES: usize = 20;
+pub const DEFAULT_PACKET_TRACE_FILE: &str = "packet-trace.log";
+pub const DEFAULT_WHITELIST_MESSAGE: &str = "You are not whitelisted on this server";
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Config {
+    /// Layout version of the file, used to upgrade older files. See [`migrate`].
+    pub config_version: u32,
     pub network: NetworkConfig,
     pub server: ServerConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub worlds: WorldsConfig,
+    #[serde(default)]
+    pub chat: ChatConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub discord: DiscordConfig,
+    #[serde(default)]
+    pub packet_trace: PacketTraceConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub timings: TimingsConfig,
+    #[serde(default)]
+    pub join_throttle: JoinThrottleConfig,
+    #[serde(default)]
+    pub resource_packs: ResourcePacksConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+    #[serde(default)]
+    pub afk: AfkConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct NetworkConfig {
-    pub address: String,
+    /// Addresses to accept connections on, as `IP:PORT`, all serving the same players.
+    pub addresses: Vec<String>,
+    /// Expect PROXY protocol v2 headers from a load balancer in front of the server.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// Seconds a client may take from its connection request to its first data frame.
+    #[serde(default = "default_handshake_timeout")]
+    pub handshake_timeout: u64,
+    /// Directory to record the datagrams of every session to, for `amethyst replay`. Empty
+    /// disables recording.
+    #[serde(default)]
+    pub record_sessions: String,
+    /// Close sessions that commit `max_violations` protocol violations within
+    /// `violation_window` seconds.
+    #[serde(default)]
+    pub strict_mode: bool,
+    #[serde(default = "default_max_violations")]
+    pub max_violations: u32,
+    #[serde(default = "default_violation_window")]
+    pub violation_window: u64,
+    /// Network interface to bind to, e.g. `eth0`. Empty binds to none.
+    #[serde(default)]
+    pub bind_device: String,
+    /// Socket buffer sizes in bytes, 0 for the system default.
+    #[serde(default)]
+    pub recv_buffer_size: usize,
+    #[serde(default)]
+    pub send_buffer_size: usize,
+    /// DSCP code point to mark outgoing datagrams with, 0 to leave them unmarked.
+    #[serde(default)]
+    pub dscp: u8,
+    /// Advertise the server to clients on the local network under Friends/LAN.
+    #[serde(default)]
+    pub lan_broadcast: bool,
+    /// What `CONNECTION_REQUEST_ACCEPTED` tells clients about the server.
+    #[serde(default)]
+    pub system_index: u16,
+    #[serde(default = "default_system_address_count")]
+    pub system_address_count: usize,
+    /// The first internal addresses, as `IP:PORT`. The rest are the address clients
+    /// connected to.
+    #[serde(default)]
+    pub system_addresses: Vec<String>,
+    /// Game packet batches smaller than this many bytes are sent uncompressed. 0 disables
+    /// compression.
+    #[serde(default = "default_compression_threshold")]
+    pub compression_threshold: u16,
+}
+
+fn default_handshake_timeout() -> u64 {
+    DEFAULT_HANDSHAKE_TIMEOUT.as_secs()
+}
+
+fn default_system_address_count() -> usize {
+    SystemAddresses::default().count
+}
+
+fn default_max_violations() -> u32 {
+    10
+}
+
+fn default_violation_window() -> u64 {
+    10
+}
+
+fn default_compression_threshold() -> u16 {
+    DEFAULT_COMPRESSION_THRESHOLD
+}
+
+impl NetworkConfig {
+    /// The strict mode sessions run in, if enabled.
+    pub fn strict_mode(&self) -> Option<StrictMode> {
+        self.strict_mode.then(|| StrictMode {
+            max_violations: self.max_violations,
+            window: Duration::from_secs(self.violation_window),
+        })
+    }
+
+    pub fn socket_options(&self) -> SocketOptions {
+        SocketOptions {
+            bind_device: (!self.bind_device.is_empty()).then(|| self.bind_device.clone()),
+            recv_buffer_size: (self.recv_buffer_size > 0).then_some(self.recv_buffer_size),
+            send_buffer_size: (self.send_buffer_size > 0).then_some(self.send_buffer_size),
+            dscp: (self.dscp > 0).then_some(self.dscp),
+        }
+    }
+
+    /// Invalid addresses are skipped, as validation reports them.
+    pub fn system_addresses(&self) -> SystemAddresses {
+        SystemAddresses {
+            system_index: self.system_index,
+            count: self.system_address_count,
+            addresses: self
+                .system_addresses
+                .iter()
+                .filter_map(|address| address.parse().ok())
+                .collect(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct ServerConfig {
     pub name: String,
     pub max_players: u32,
+    /// First line of the server list entry.
+    pub motd: String,
+    /// Second line of the server list entry.
+    pub world_name: String,
+    pub gamemode: GameMode,
+    /// Largest radius in chunks sent to players; each player negotiates their own up to it.
+    pub view_distance: u32,
+    /// Require players to be authenticated with Xbox Live.
+    pub online_mode: bool,
+    /// File holding the server GUID and encryption key pair, generated on first start.
+    pub keys_file: String,
+    /// Only allow players listed in whitelist.json (and operators) to join.
+    pub whitelist: bool,
+    /// Disconnect message for players who are not whitelisted.
+    pub whitelist_message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "Survival",
+            GameMode::Creative => "Creative",
+            GameMode::Adventure => "Adventure",
+            GameMode::Spectator => "Spectator",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub level: String,
+    pub color: String,
+    pub overflow: String,
+    /// Identical messages written per window before the rest are suppressed; 0 disables.
+    pub rate_limit_burst: u32,
+    pub rate_limit_window_ms: u64,
+    pub filters: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_version: migrate::CURRENT_VERSION,
+            network: NetworkConfig::default(),
+            server: ServerConfig::default(),
+            logging: LoggingConfig::default(),
+            worlds: WorldsConfig::default(),
+            chat: ChatConfig::default(),
+            admin: AdminConfig::default(),
+            health: HealthConfig::default(),
+            proxy: ProxyConfig::default(),
+            discord: DiscordConfig::default(),
+            packet_trace: PacketTraceConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            timings: TimingsConfig::default(),
+            join_throttle: JoinThrottleConfig::default(),
+            resource_packs: ResourcePacksConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            otlp: OtlpConfig::default(),
+            afk: AfkConfig::default(),
+        }
+    }
+}
+
+/// Which worlds to load. Each world's own settings live in `<directory>/<name>.toml`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WorldsConfig {
+    pub directory: String,
+    /// World players join when they connect. Must be one of `load`.
+    pub default: String,
+    pub load: Vec<String>,
+}
+
+impl Default for WorldsConfig {
+    fn default() -> Self {
+        Self {
+            directory: "worlds".to_string(),
+            default: "world".to_string(),
+            load: vec!["world".to_string()],
+        }
+    }
+}
+
+/// How chat messages are shown. `{name}` and `{message}` are replaced in each format.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChatConfig {
+    /// Messages typed by players.
+    pub format: String,
+    /// Messages broadcast with `say`.
+    pub broadcast_format: String,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            format: "<{name}> {message}".to_string(),
+            broadcast_format: "[{name}] {message}".to_string(),
+        }
+    }
+}
+
+impl ChatConfig {
+    fn validate(&self, issues: &mut Vec<String>) {
+        for (key, format) in [
+            ("format", &self.format),
+            ("broadcast_format", &self.broadcast_format),
+        ] {
+            if !format.contains("{message}") {
+                issues.push(format!(
+                    "Chat {} '{}' must contain {{message}}.",
+                    key, format
+                ));
+            }
+        }
+    }
+}
+
+/// The optional HTTP admin API.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    pub address: String,
+    /// Bearer token required on every request.
+    pub token: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:19180".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+/// Unauthenticated liveness and readiness probes for orchestrators.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HealthConfig {
+    pub enabled: bool,
+    pub address: String,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0.0.0.0:19181".to_string(),
+        }
+    }
+}
+
+/// Resource packs served over HTTP, which clients download from the URL they are sent instead
+/// of in chunks through RakNet.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ResourcePacksConfig {
+    pub enabled: bool,
+    pub address: String,
+    /// URL clients reach `address` at, e.g. behind a reverse proxy. Empty uses
+    /// `http://<address>`.
+    pub public_url: String,
+    pub directory: String,
+    /// Whether players have to accept the packs to join.
+    pub required: bool,
+    pub packs: Vec<ResourcePackEntry>,
+}
+
+impl Default for ResourcePacksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0.0.0.0:19182".to_string(),
+            public_url: String::new(),
+            directory: "resource_packs".to_string(),
+            required: false,
+            packs: Vec::new(),
+        }
+    }
+}
+
+/// One archive in [`ResourcePacksConfig::directory`], with the header UUID and version of its
+/// `manifest.json`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ResourcePackEntry {
+    pub file: String,
+    pub uuid: String,
+    pub version: String,
+}
+
+impl ResourcePacksConfig {
+    /// Base URL the packs are advertised under, without a trailing `/`.
+    pub fn public_url(&self) -> String {
+        if self.public_url.is_empty() {
+            format!("http://{}", self.address)
+        } else {
+            self.public_url.trim_end_matches('/').to_string()
+        }
+    }
+
+    fn validate(&self, issues: &mut Vec<String>) {
+        if !self.enabled {
+            return;
+        }
+        match SocketAddr::from_str(&self.address) {
+            Ok(address) if self.public_url.is_empty() && address.ip().is_unspecified() => {
+                issues.push(format!(
+                    "Resource pack public_url is required when the address '{}' does not \
+                     name the host clients can reach.",
+                    self.address
+                ));
+            }
+            Ok(_) => {}
+            Err(_) => issues.push(format!(
+                "Invalid resource pack address format: '{}'. Expected format like 'IP:PORT'.",
+                self.address
+            )),
+        }
+        let valid_url = self.public_url.is_empty()
+            || ["http://", "https://"]
+                .iter()
+                .any(|scheme| self.public_url.starts_with(scheme));
+        if !valid_url {
+            issues.push(format!(
+                "Invalid resource pack public_url: '{}'. Expected an http:// or https:// URL.",
+                self.public_url
+            ));
+        }
+        let mut uuids = HashSet::new();
+        for pack in &self.packs {
+            let valid_file = !pack.file.is_empty()
+                && !pack.file.starts_with('.')
+                && pack
+                    .file
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+            if !valid_file {
+                issues.push(format!(
+                    "Invalid resource pack file name: '{}'. Only letters, digits, '-', '_' and \
+                     '.' are allowed.",
+                    pack.file
+                ));
+            }
+            match protocol::parse_uuid(&pack.uuid) {
+                Some(uuid) if !uuids.insert(uuid) => issues.push(format!(
+                    "Resource pack UUID '{}' is listed twice.",
+                    pack.uuid
+                )),
+                Some(_) => {}
+                None => issues.push(format!(
+                    "Invalid UUID '{}' for resource pack '{}'.",
+                    pack.uuid, pack.file
+                )),
+            }
+            let version: Vec<&str> = pack.version.split('.').collect();
+            if version.len() != 3 || version.iter().any(|part| part.parse::<u32>().is_err()) {
+                issues.push(format!(
+                    "Invalid version '{}' for resource pack '{}'. Expected format like '1.0.0'.",
+                    pack.version, pack.file
+                ));
+            }
+        }
+    }
+}
+
+/// Proxy mode, in which game packets are forwarded to another server instead of being
+/// handled here.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub enabled: bool,
+    /// Backend server as `HOST:PORT`.
+    pub backend: String,
+    pub transport: ProxyTransport,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyTransport {
+    #[default]
+    Tcp,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: "127.0.0.1:19133".to_string(),
+            transport: ProxyTransport::Tcp,
+        }
+    }
+}
+
+/// Chat relay to a Discord channel. Only available in builds with the `discord` feature.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    /// Token of the bot account that posts and reads messages.
+    pub token: String,
+    pub channel_id: String,
+}
+
+/// Crash and error reports sent to a Sentry-compatible server. Only available in builds with
+/// the `telemetry` feature, and never sent unless enabled here.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    /// Project DSN, as `https://<key>@<host>/<project>`.
+    pub dsn: String,
+    /// Also report errors that were logged, not only panics.
+    pub errors: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dsn: String::new(),
+            errors: true,
+        }
+    }
+}
+
+/// Spans exported to an OpenTelemetry collector over OTLP/HTTP. Only available in builds with
+/// the `otlp` feature.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct OtlpConfig {
+    pub enabled: bool,
+    /// Traces URL of the collector, e.g. `http://localhost:4318/v1/traces`.
+    pub endpoint: String,
+    /// `service.name` of every span.
+    pub service_name: String,
+    /// Share of traces exported, decided when a trace starts.
+    pub sample_ratio: f64,
+    /// Also export the per-datagram spans of builds with the `trace-packets` feature.
+    pub packet_spans: bool,
+    /// Sent with every export, e.g. for authentication.
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "amethyst".to_string(),
+            sample_ratio: 1.0,
+            packet_spans: false,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+/// Where a [`TelemetryConfig::dsn`] sends events to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentryDsn {
+    pub store_url: String,
+    pub public_key: String,
+}
+
+impl TelemetryConfig {
+    pub fn dsn(&self) -> Result<SentryDsn, ConfigError> {
+        let invalid = || {
+            ConfigError::Validation(format!(
+                "Invalid telemetry DSN: '{}'. Expected format like \
+                 'https://KEY@HOST/PROJECT'.",
+                self.dsn
+            ))
+        };
+        let (scheme, rest) = self.dsn.split_once("://").ok_or_else(invalid)?;
+        let (key, address) = rest.split_once('@').ok_or_else(invalid)?;
+        let (host, project) = address.rsplit_once('/').ok_or_else(invalid)?;
+        // Older DSNs carry a secret key after the public one, which is no longer needed.
+        let public_key = key.split(':').next().unwrap_or_default();
+        if !matches!(scheme, "http" | "https")
+            || public_key.is_empty()
+            || host.is_empty()
+            || project.is_empty()
+        {
+            return Err(invalid());
+        }
+        Ok(SentryDsn {
+            store_url: format!("{}://{}/api/{}/store/", scheme, host, project),
+            public_key: public_key.to_string(),
+        })
+    }
+}
+
+/// Hex dumps of every datagram, for debugging the protocol. Also toggled with the
+/// `packettrace` command.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PacketTraceConfig {
+    pub enabled: bool,
+    pub file: String,
+    pub include_ids: Vec<u8>,
+    pub exclude_ids: Vec<u8>,
+    /// Client addresses, as `IP` or `IP:PORT`.
+    pub include_peers: Vec<String>,
+    pub exclude_peers: Vec<String>,
+}
+
+impl Default for PacketTraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: DEFAULT_PACKET_TRACE_FILE.to_string(),
+            include_ids: Vec::new(),
+            exclude_ids: Vec::new(),
+            include_peers: Vec::new(),
+            exclude_peers: Vec::new(),
+        }
+    }
+}
+
+/// Detection of ticks that never finish, like the vanilla watchdog.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// Seconds a single tick may run before it counts as stalled.
+    pub timeout: u64,
+    pub action: WatchdogAction,
+}
+
+/// What the watchdog does about a stalled tick, after logging it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchdogAction {
+    /// Nothing, in case the tick finishes after all.
+    Warn,
+    /// Stop the server gracefully.
+    #[default]
+    Shutdown,
+    /// Exit at once, for a supervisor to restart the server.
+    Exit,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: 60,
+            action: WatchdogAction::Shutdown,
+        }
+    }
+}
+
+/// Players who stop sending input, as opposed to the RakNet timeout, which keepalives reset
+/// even for a client that has stalled.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AfkConfig {
+    pub enabled: bool,
+    /// Minutes without a `PlayerAuthInput` before a player counts as idle.
+    pub timeout: u64,
+    pub action: AfkAction,
+    /// Shown to players kicked for being idle.
+    pub kick_message: String,
+}
+
+/// What happens to a player once they are idle.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AfkAction {
+    /// Disconnect them with `kick_message`.
+    #[default]
+    Kick,
+    /// Mark them AFK in the log and the player list until they send input again.
+    Mark,
+}
+
+impl Default for AfkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: 10,
+            action: AfkAction::Kick,
+            kick_message: "You have been idle for too long".to_string(),
+        }
+    }
+}
+
+/// Where tick time goes, reported by `timings`, for long ticks and periodically.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TimingsConfig {
+    /// Milliseconds a tick may take before its breakdown is logged. 0 disables it.
+    pub long_tick_threshold: u64,
+    /// Seconds between timing reports in the log. 0 disables them.
+    pub report_interval: u64,
+}
+
+impl Default for TimingsConfig {
+    fn default() -> Self {
+        Self {
+            long_tick_threshold: 100,
+            report_interval: 0,
+        }
+    }
+}
+
+/// Extra handshake round for clients suspected of being bots during join floods.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct JoinThrottleConfig {
+    pub enabled: bool,
+    /// Handshakes one /24 or /64 subnet may start within `window` before it is challenged.
+    pub max_subnet_handshakes: u32,
+    /// Seconds.
+    pub window: u64,
+    /// Milliseconds a client has to wait before retrying a handshake.
+    pub min_retry_interval: u64,
+    /// Networks never challenged, as `IP` or `IP/PREFIX`.
+    pub allowlist: Vec<String>,
+}
+
+impl Default for JoinThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_subnet_handshakes: 10,
+            window: 10,
+            min_retry_interval: 100,
+            allowlist: Vec::new(),
+        }
+    }
+}
+
+impl JoinThrottleConfig {
+    /// The throttle the listener applies, if enabled. Invalid allowlist entries are skipped,
+    /// as validation reports them.
+    pub fn throttle(&self) -> Option<JoinThrottle> {
+        self.enabled.then(|| JoinThrottle {
+            max_subnet_handshakes: self.max_subnet_handshakes,
+            window: Duration::from_secs(self.window),
+            min_retry_interval: Duration::from_millis(self.min_retry_interval),
+            allowlist: self
+                .allowlist
+                .iter()
+                .filter_map(|network| network.parse().ok())
+                .collect(),
+        })
+    }
+
+    fn validate(&self, issues: &mut Vec<String>) {
+        for network in &self.allowlist {
+            if let Err(e) = IpNetwork::from_str(network) {
+                issues.push(format!(
+                    "Invalid join throttle allowlist entry '{}': {}. Expected format like \
+                     'IP' or 'IP/PREFIX'.",
+                    network, e
+                ));
+            }
+        }
+        if self.enabled {
+            if self.max_subnet_handshakes == 0 {
+                issues.push("Join throttle subnet handshakes must be greater than 0.".to_string());
+            }
+            if self.window == 0 {
+                issues.push("Join throttle window must be greater than 0.".to_string());
+            }
+        }
+    }
+}
+
+impl PacketTraceConfig {
+    pub fn filter(&self) -> Result<PacketTraceFilter, ConfigError> {
+        Ok(PacketTraceFilter {
+            include_ids: self.include_ids.clone(),
+            exclude_ids: self.exclude_ids.clone(),
+            include_peers: parse_peers(&self.include_peers)?,
+            exclude_peers: parse_peers(&self.exclude_peers)?,
+        })
+    }
+
+    fn validate(&self, issues: &mut Vec<String>) {
+        if let Err(ConfigError::Validation(issue)) = self.filter() {
+            issues.push(issue);
+        }
+        if self.enabled && self.file.trim().is_empty() {
+            issues.push("Packet trace file cannot be empty.".to_string());
+        }
+    }
+}
+
+fn parse_peers(peers: &[String]) -> Result<Vec<PeerPattern>, ConfigError> {
+    peers
+        .iter()
+        .map(|peer| {
+            PeerPattern::from_str(peer).map_err(|_| {
+                ConfigError::Validation(format!(
+                    "Invalid packet trace peer: '{}'. Expected format like 'IP' or 'IP:PORT'.",
+                    peer
+                ))
+            })
+        })
+        .collect()
 }
 
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
-            address: "0.0.0.0:19132".to_string(),
+            addresses: vec!["0.0.0.0:19132".to_string()],
+            proxy_protocol: false,
+            handshake_timeout: default_handshake_timeout(),
+            record_sessions: String::new(),
+            strict_mode: false,
+            max_violations: default_max_violations(),
+            violation_window: default_violation_window(),
+            bind_device: String::new(),
+            recv_buffer_size: 0,
+            send_buffer_size: 0,
+            dscp: 0,
+            lan_broadcast: false,
+            system_index: 0,
+            system_address_count: default_system_address_count(),
+            system_addresses: Vec::new(),
+            compression_threshold: default_compression_threshold(),
         }
     }
 }
@@ -40,60 +827,392 @@ impl Default for ServerConfig {
         Self {
             name: "Amethyst".to_string(),
             max_players: 50,
+            motd: "Amethyst".to_string(),
+            world_name: "Amethyst World".to_string(),
+            gamemode: GameMode::Survival,
+            view_distance: 10,
+            online_mode: true,
+            keys_file: "server-keys.toml".to_string(),
+            whitelist: false,
+            whitelist_message: DEFAULT_WHITELIST_MESSAGE.to_string(),
         }
     }
 }
 
-impl Default for Config {
+impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
-            network: NetworkConfig::default(),
-            server: ServerConfig::default(),
+            level: "info".to_string(),
+            color: "auto".to_string(),
+            overflow: "drop-newest".to_string(),
+            rate_limit_burst: 10,
+            rate_limit_window_ms: 1000,
+            filters: BTreeMap::new(),
         }
     }
 }
 
+impl LoggingConfig {
+    /// Builds the logger filter from the configured default level and per-target overrides.
+    pub fn filter(&self) -> Result<LogFilter, ConfigError> {
+        let mut filter = LogFilter::new(parse_level(&self.level)?);
+        for (target, level) in &self.filters {
+            filter.set_target(target.clone(), parse_level(level)?);
+        }
+        Ok(filter)
+    }
+
+    fn validate(&self, issues: &mut Vec<String>) {
+        let levels = std::iter::once(&self.level).chain(self.filters.values());
+        issues.extend(levels.filter_map(|level| match parse_level(level) {
+            Err(ConfigError::Validation(issue)) => Some(issue),
+            _ => None,
+        }));
+        if let Err(ConfigError::Validation(issue)) = self.color() {
+            issues.push(issue);
+        }
+        if let Err(ConfigError::Validation(issue)) = self.overflow() {
+            issues.push(issue);
+        }
+        if self.rate_limit_burst > 0 && self.rate_limit_window_ms == 0 {
+            issues.push(
+                "Log rate limit window must be greater than 0 when rate limiting is enabled."
+                    .to_string(),
+            );
+        }
+    }
+
+    pub fn color(&self) -> Result<ColorChoice, ConfigError> {
+        ColorChoice::from_str(&self.color).map_err(ConfigError::Validation)
+    }
+
+    pub fn overflow(&self) -> Result<OverflowPolicy, ConfigError> {
+        OverflowPolicy::from_str(&self.overflow).map_err(ConfigError::Validation)
+    }
+
+    pub fn rate_limit_window(&self) -> Duration {
+        Duration::from_millis(self.rate_limit_window_ms)
+    }
+}
+
+fn redact(secret: &str) -> String {
+    if secret.is_empty() {
+        String::new()
+    } else {
+        "<redacted>".to_string()
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, ConfigError> {
+    LevelFilter::from_str(level).map_err(|_| {
+        ConfigError::Validation(format!(
+            "Invalid log level: '{}'. Expected one of off, error, warn, info, debug, trace.",
+            level
+        ))
+    })
+}
+
 impl Config {
+    /// A copy with tokens, the telemetry DSN and OTLP header values replaced, for printing
+    /// the configuration where others may read it.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        config.admin.token = redact(&config.admin.token);
+        config.discord.token = redact(&config.discord.token);
+        config.telemetry.dsn = redact(&config.telemetry.dsn);
+        for value in config.otlp.headers.values_mut() {
+            *value = redact(value);
+        }
+        config
+    }
+
+    /// Checks every setting and reports all problems at once, so they can be fixed in a
+    /// single pass.
     pub fn validate(&self) -> Result<(), ConfigError> {
-        if SocketAddr::from_str(&self.network.address).is_err() {
-            return Err(ConfigError::Validation(format!(
-                "Invalid network address format: '{}'. Expected format like 'IP:PORT'.",
-                self.network.address
-            )));
+        let mut issues = Vec::new();
+
+        if self.network.addresses.is_empty() {
+            issues.push("At least one network address is required.".to_string());
+        }
+        let mut addresses = HashSet::new();
+        for address in &self.network.addresses {
+            match SocketAddr::from_str(address) {
+                Ok(parsed) if !addresses.insert(parsed) => {
+                    issues.push(format!("Network address '{}' is listed twice.", address));
+                }
+                Ok(_) => {}
+                Err(_) => issues.push(format!(
+                    "Invalid network address format: '{}'. Expected format like 'IP:PORT'.",
+                    address
+                )),
+            }
         }
 
-        if self.server.name.trim().is_empty() {
-            return Err(ConfigError::Validation(
-                "Server name cannot be empty.".to_string(),
+        let max_handshake_timeout = CONNECTION_TIMEOUT.as_secs();
+        if !(1..=max_handshake_timeout).contains(&self.network.handshake_timeout) {
+            issues.push(format!(
+                "Handshake timeout must be between 1 and {} seconds.",
+                max_handshake_timeout
             ));
         }
 
+        if self.network.strict_mode {
+            if self.network.max_violations == 0 {
+                issues.push("Maximum protocol violations must be greater than 0.".to_string());
+            }
+            if self.network.violation_window == 0 {
+                issues.push("Protocol violation window must be greater than 0.".to_string());
+            }
+        }
+
+        if self.network.dscp > MAX_DSCP {
+            issues.push(format!("DSCP must be between 0 and {}.", MAX_DSCP));
+        }
+
+        if !(1..=MAX_SYSTEM_ADDRESSES).contains(&self.network.system_address_count) {
+            issues.push(format!(
+                "System address count must be between 1 and {}.",
+                MAX_SYSTEM_ADDRESSES
+            ));
+        }
+        if self.network.system_addresses.len() > self.network.system_address_count {
+            issues.push("More system addresses are listed than system_address_count.".to_string());
+        }
+        for address in &self.network.system_addresses {
+            if SocketAddr::from_str(address).is_err() {
+                issues.push(format!(
+                    "Invalid system address format: '{}'. Expected format like 'IP:PORT'.",
+                    address
+                ));
+            }
+        }
+
+        if self.server.name.trim().is_empty() {
+            issues.push("Server name cannot be empty.".to_string());
+        }
+
         if self.server.max_players == 0 {
-            return Err(ConfigError::Validation(
-                "Maximum players must be greater than 0.".to_string(),
+            issues.push("Maximum players must be greater than 0.".to_string());
+        }
+
+        if !(MIN_VIEW_DISTANCE..=MAX_VIEW_DISTANCE).contains(&self.server.view_distance) {
+            issues.push(format!(
+                "View distance must be between {} and {}.",
+                MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE
+            ));
+        }
+
+        if self.server.motd.contains(';') || self.server.world_name.contains(';') {
+            issues.push("MOTD and world name cannot contain ';'.".to_string());
+        }
+
+        for name in self
+            .worlds
+            .load
+            .iter()
+            .filter(|name| !world::is_valid_world_name(name))
+        {
+            issues.push(format!(
+                "Invalid world name: '{}'. Only letters, digits, '_' and '-' are allowed.",
+                name
+            ));
+        }
+
+        if !self.worlds.load.contains(&self.worlds.default) {
+            issues.push(format!(
+                "Default world '{}' is not listed in worlds.load.",
+                self.worlds.default
+            ));
+        }
+
+        self.logging.validate(&mut issues);
+        self.chat.validate(&mut issues);
+        self.packet_trace.validate(&mut issues);
+        self.join_throttle.validate(&mut issues);
+        self.resource_packs.validate(&mut issues);
+
+        if self.watchdog.enabled && self.watchdog.timeout == 0 {
+            issues.push("Watchdog timeout must be greater than 0.".to_string());
+        }
+
+        if self.afk.enabled && self.afk.timeout == 0 {
+            issues.push("AFK timeout must be greater than 0.".to_string());
+        }
+
+        if self.health.enabled && SocketAddr::from_str(&self.health.address).is_err() {
+            issues.push(format!(
+                "Invalid health endpoint address format: '{}'. Expected format like 'IP:PORT'.",
+                self.health.address
             ));
         }
 
+        if self.proxy.enabled
+            && !self
+                .proxy
+                .backend
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        {
+            issues.push(format!(
+                "Invalid proxy backend: '{}'. Expected format like 'HOST:PORT'.",
+                self.proxy.backend
+            ));
+        }
+
+        if self.telemetry.enabled
+            && let Err(ConfigError::Validation(issue)) = self.telemetry.dsn()
+        {
+            issues.push(issue);
+        }
+
+        if self.otlp.enabled {
+            if !(self.otlp.endpoint.starts_with("http://")
+                || self.otlp.endpoint.starts_with("https://"))
+            {
+                issues.push(format!(
+                    "Invalid OTLP endpoint: '{}'. Expected an http:// or https:// URL.",
+                    self.otlp.endpoint
+                ));
+            }
+            if !(0.0..=1.0).contains(&self.otlp.sample_ratio) {
+                issues.push(format!(
+                    "OTLP sample ratio must be between 0 and 1, got {}.",
+                    self.otlp.sample_ratio
+                ));
+            }
+        }
+
+        if self.discord.enabled {
+            if self.discord.token.trim().is_empty() {
+                issues.push("Discord bot token cannot be empty.".to_string());
+            }
+            if self.discord.channel_id.parse::<u64>().is_err() {
+                issues.push(format!(
+                    "Invalid Discord channel id: '{}'. Expected a numeric id.",
+                    self.discord.channel_id
+                ));
+            }
+        }
+
+        if self.admin.enabled {
+            if SocketAddr::from_str(&self.admin.address).is_err() {
+                issues.push(format!(
+                    "Invalid admin API address format: '{}'. Expected format like 'IP:PORT'.",
+                    self.admin.address
+                ));
+            }
+            if self.admin.token.len() < MIN_ADMIN_TOKEN_LENGTH {
+                issues.push(format!(
+                    "Admin API token must be at least {} characters long.",
+                    MIN_ADMIN_TOKEN_LENGTH
+                ));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(issues))
+        }
+    }
+}
+
+/// Values given on the command line, applied on top of the file and environment overrides.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub log_level: Option<String>,
+    pub no_color: bool,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut Config) -> Result<(), ConfigError> {
+        if let Some(address) = &self.address {
+            config.network.addresses = vec![address.clone()];
+        }
+        if let Some(port) = self.port {
+            for address in &mut config.network.addresses {
+                let mut parsed = SocketAddr::from_str(address).map_err(|_| {
+                    ConfigError::Validation(format!(
+                        "Cannot override the port of invalid network address '{}'.",
+                        address
+                    ))
+                })?;
+                parsed.set_port(port);
+                *address = parsed.to_string();
+            }
+        }
+        if let Some(level) = &self.log_level {
+            config.logging.level = level.clone();
+        }
+        if self.no_color {
+            config.logging.color = "never".to_string();
+        }
         Ok(())
     }
 }
 
-pub fn handle() -> Result<Config, ConfigError> {
-    let config_path = PathBuf::from(CONFIG_FILE_NAME);
-    if config_path.exists() {
-        let config_content = fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&config_content)?;
-        config.validate()?;
-        Ok(config)
-    } else {
-        let config = Config::default();
-        save(&config, &config_path)?;
-        Ok(config)
+/// Loads the configuration at `path` at startup, writing the defaults there first if it does
+/// not exist. Files written for an older `config_version` are upgraded in place, keeping a
+/// backup and the comments in the file.
+pub fn handle(path: &Path, overrides: &ConfigOverrides) -> Result<Config, ConfigError> {
+    if !path.exists() {
+        save(&Config::default(), path)?;
+    }
+    read(path, overrides, true).map(|(config, _)| config)
+}
+
+/// Reads the configuration at `path`, applies environment and command-line overrides and
+/// validates the result. Older files are upgraded in memory only, so reloads never rewrite
+/// the file.
+pub fn load(path: &Path, overrides: &ConfigOverrides) -> Result<Config, ConfigError> {
+    read(path, overrides, false).map(|(config, _)| config)
+}
+
+/// Like [`load`], but also returns the `config_version` the file would be upgraded from, if
+/// it is outdated.
+pub fn check(path: &Path, overrides: &ConfigOverrides) -> Result<(Config, Option<u32>), ConfigError> {
+    read(path, overrides, false)
+}
+
+fn read(
+    path: &Path,
+    overrides: &ConfigOverrides,
+    upgrade_in_place: bool,
+) -> Result<(Config, Option<u32>), ConfigError> {
+    let config_content = fs::read_to_string(path)?;
+    let mut document: toml_edit::DocumentMut = config_content.parse()?;
+    let migrated_from = migrate::migrate(&mut document)?;
+    let mut table: toml::Table = toml::from_str(&document.to_string())?;
+    if let Some(from_version) = migrated_from
+        && upgrade_in_place
+    {
+        // Fail before touching the file if the upgraded configuration does not parse.
+        let _: Config = table.clone().try_into()?;
+        let backup = path.with_extension(format!("toml.v{}.bak", from_version));
+        fs::copy(path, &backup)?;
+        fs::write(path, document.to_string())?;
+        info!(
+            "Upgraded {} from config_version {} to {} (backup saved to {})",
+            path.display(),
+            from_version,
+            migrate::CURRENT_VERSION,
+            backup.display()
+        );
+    }
+    let schema = toml::Table::try_from(Config::default())?;
+    for key in env::apply_overrides(&mut table, &schema, std::env::vars_os())? {
+        debug!("Configuration value '{}' overridden from the environment", key);
     }
+    let mut config: Config = table.try_into()?;
+    overrides.apply(&mut config)?;
+    config.validate()?;
+    Ok((config, migrated_from))
 }
 
 fn save(config: &Config, path: &Path) -> Result<(), ConfigError> {
-    let config_content = toml::to_string_pretty(config)?;
+    let config_content = docs::annotate(&toml::to_string_pretty(config)?)?;
     let mut file = fs::File::create(path)?;
     file.write_all(config_content.as_bytes())?;
     Ok(())
@@ -0,0 +1,129 @@
+use super::error::ConfigError;
+use super::{load, Config, ConfigOverrides};
+use log::{error, info, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+/// Editors often save a file in several steps, so reloading waits until events stop arriving
+/// for this long.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Broadcast to subscribers whenever the configuration file changed and the new version
+/// passed validation.
+#[derive(Debug, Clone)]
+pub struct ConfigChanged {
+    pub old: Arc<Config>,
+    pub new: Arc<Config>,
+}
+
+/// Watches the configuration file and reloads it when it changes.
+///
+/// Invalid edits are logged and ignored, leaving the previous configuration in effect.
+/// Watching stops when this is dropped.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    sender: broadcast::Sender<ConfigChanged>,
+}
+
+impl ConfigWatcher {
+    pub fn spawn(
+        path: PathBuf,
+        overrides: ConfigOverrides,
+        initial: Arc<Config>,
+    ) -> Result<Self, ConfigError> {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let file_name = path.file_name().map(ToOwned::to_owned);
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+            Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                if event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == file_name.as_deref())
+                {
+                    let _ = events_tx.send(());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Configuration watcher error: {}", e),
+        })?;
+
+        // Watch the directory rather than the file itself: editors that save by renaming a
+        // temporary file over the original would otherwise detach the watch.
+        let dir = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        info!("Watching {} for changes", path.display());
+
+        let (sender, _) = broadcast::channel(16);
+        tokio::spawn(reload_loop(path, overrides, initial, events_rx, sender.clone()));
+        Ok(Self {
+            _watcher: watcher,
+            sender,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfigChanged> {
+        self.sender.subscribe()
+    }
+}
+
+async fn reload_loop(
+    path: PathBuf,
+    overrides: ConfigOverrides,
+    mut current: Arc<Config>,
+    mut events: mpsc::UnboundedReceiver<()>,
+    sender: broadcast::Sender<ConfigChanged>,
+) {
+    while events.recv().await.is_some() {
+        loop {
+            match tokio::time::timeout(DEBOUNCE, events.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        let new = match load(&path, &overrides) {
+            Ok(config) => Arc::new(config),
+            Err(e) => {
+                error!(
+                    "Ignoring invalid change to {}, keeping the previous configuration: {}",
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        if new == current {
+            continue;
+        }
+
+        if new.network.addresses != current.network.addresses {
+            warn!(
+                "network.addresses changed to {:?}; this only takes effect after a restart",
+                new.network.addresses
+            );
+        }
+        if new.network.socket_options() != current.network.socket_options() {
+            warn!("Network socket options changed; this only takes effect after a restart");
+        }
+        if new.network.system_addresses() != current.network.system_addresses() {
+            warn!("Network system addresses changed; this only takes effect after a restart");
+        }
+        if new.network.lan_broadcast != current.network.lan_broadcast {
+            warn!("network.lan_broadcast changed; this only takes effect after a restart");
+        }
+        info!("Reloaded configuration from {}", path.display());
+
+        let change = ConfigChanged {
+            old: std::mem::replace(&mut current, Arc::clone(&new)),
+            new,
+        };
+        let _ = sender.send(change);
+    }
+}
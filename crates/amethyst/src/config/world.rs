@@ -0,0 +1,76 @@
+use super::error::ConfigError;
+use super::GameMode;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Settings of a single world, stored as `<worlds.directory>/<name>.toml`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WorldConfig {
+    pub generator: Generator,
+    pub seed: i64,
+    pub gamemode: GameMode,
+    /// Radius in blocks around spawn that only operators can modify. 0 disables it.
+    pub spawn_protection: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Generator {
+    #[default]
+    Normal,
+    Flat,
+    Void,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            generator: Generator::Normal,
+            seed: 0,
+            gamemode: GameMode::Survival,
+            spawn_protection: 16,
+        }
+    }
+}
+
+/// Loads the configuration of every world in `names` from `dir`, creating a file with
+/// default settings and a random seed for worlds that do not have one yet.
+pub fn load_worlds(
+    dir: &Path,
+    names: &[String],
+) -> Result<BTreeMap<String, WorldConfig>, ConfigError> {
+    fs::create_dir_all(dir)?;
+    let mut worlds = BTreeMap::new();
+    for name in names {
+        let path = dir.join(format!("{}.toml", name));
+        let world = if path.exists() {
+            read_world(&path)?
+        } else {
+            let world = WorldConfig {
+                seed: rand::random(),
+                ..WorldConfig::default()
+            };
+            fs::write(&path, toml::to_string_pretty(&world)?)?;
+            info!("Created world configuration {}", path.display());
+            world
+        };
+        worlds.insert(name.clone(), world);
+    }
+    Ok(worlds)
+}
+
+pub fn read_world(path: &Path) -> Result<WorldConfig, ConfigError> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// World names are used as file names, so they are restricted to a portable character set.
+pub fn is_valid_world_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
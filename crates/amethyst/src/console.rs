@@ -0,0 +1,225 @@
+use crate::commands::{CommandContext, CommandRegistry, CommandSender};
+use amethyst_log::AmethystLogger;
+use log::{error, info, warn};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, ExternalPrinter, Helper};
+use std::io::{self, stdout, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// File the command history is kept in between restarts.
+pub const HISTORY_FILE: &str = "console_history.txt";
+const MAX_HISTORY: usize = 1000;
+const PROMPT: &str = "> ";
+
+/// Starts reading commands from stdin on the `amethyst-console` thread.
+///
+/// On a terminal, log output is routed through the line editor so records are printed above
+/// the prompt instead of over the line being typed.
+pub fn spawn(commands: Arc<CommandRegistry>, context: CommandContext) -> io::Result<()> {
+    #[cfg(unix)]
+    terminal::save();
+    thread::Builder::new()
+        .name("amethyst-console".into())
+        .spawn(move || run(commands, context))?;
+    Ok(())
+}
+
+/// Puts the terminal back into the mode it was in before the console started. The console
+/// thread may still be waiting for input in raw mode when the server exits.
+pub fn restore_terminal() {
+    #[cfg(unix)]
+    terminal::restore();
+}
+
+fn run(commands: Arc<CommandRegistry>, context: CommandContext) {
+    let config = match Config::builder()
+        .max_history_size(MAX_HISTORY)
+        .and_then(|builder| builder.history_ignore_dups(true))
+    {
+        Ok(builder) => builder
+            .auto_add_history(true)
+            .completion_type(CompletionType::List)
+            .build(),
+        Err(e) => {
+            error!("Failed to configure the console: {}", e);
+            return;
+        }
+    };
+    let mut editor: Editor<ConsoleHelper, DefaultHistory> = match Editor::with_config(config) {
+        Ok(editor) => editor,
+        Err(e) => {
+            error!("Failed to start the console: {}", e);
+            return;
+        }
+    };
+    editor.set_helper(Some(ConsoleHelper {
+        commands: Arc::clone(&commands),
+        context: context.clone(),
+    }));
+    if Path::new(HISTORY_FILE).exists()
+        && let Err(e) = editor.load_history(HISTORY_FILE)
+    {
+        warn!("Failed to load console history from {}: {}", HISTORY_FILE, e);
+    }
+    if let Ok(printer) = editor.create_external_printer() {
+        AmethystLogger::set_output(Box::new(LogPrinter {
+            printer,
+            pending: String::new(),
+        }));
+    }
+
+    let sender = CommandSender::Console;
+    while !context.shutdown.is_requested() {
+        match editor.readline(PROMPT) {
+            Ok(line) => match commands.dispatch(&context, &sender, &line) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => println!("{}", e),
+            },
+            Err(ReadlineError::Interrupted) => {
+                info!("Ctrl+C received on the console");
+                context.shutdown.request();
+            }
+            Err(ReadlineError::Eof) => {
+                info!("Console input closed");
+                break;
+            }
+            Err(e) => {
+                error!("Failed to read console input: {}", e);
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = editor.save_history(HISTORY_FILE) {
+        warn!("Failed to save console history to {}: {}", HISTORY_FILE, e);
+    }
+    AmethystLogger::set_output(Box::new(stdout()));
+}
+
+/// Completes command names in the first word and connected players after it.
+struct ConsoleHelper {
+    commands: Arc<CommandRegistry>,
+    context: CommandContext,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let prefix = &line[start..];
+        let preceding: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let candidates: Vec<String> = match preceding.as_slice() {
+            [] => self
+                .commands
+                .commands()
+                .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+                .map(str::to_string)
+                .collect(),
+            [command] if self.commands.get(command).is_some_and(|spec| spec.name == "help") => {
+                self.commands
+                    .commands()
+                    .map(|spec| spec.name.to_string())
+                    .collect()
+            }
+            // Connections are only known by address until the login sequence provides names.
+            _ => self
+                .context
+                .connections
+                .iter()
+                .map(|entry| entry.address.to_string())
+                .collect(),
+        };
+
+        let mut matches: Vec<Pair> = candidates
+            .into_iter()
+            .filter(|candidate| {
+                candidate
+                    .to_ascii_lowercase()
+                    .starts_with(&prefix.to_ascii_lowercase())
+            })
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: format!("{} ", candidate),
+            })
+            .collect();
+        matches.sort_by(|a, b| a.display.cmp(&b.display));
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+/// Log output that the line editor prints above the prompt. Only whole lines are passed on,
+/// since each print is followed by a redraw of the prompt.
+struct LogPrinter<P> {
+    printer: P,
+    pending: String,
+}
+
+impl<P: ExternalPrinter + Send> Write for LogPrinter<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.push_str(&String::from_utf8_lossy(buf));
+        if let Some(end) = self.pending.rfind('\n') {
+            let lines: String = self.pending.drain(..=end).collect();
+            self.printer.print(lines).map_err(io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::sync::OnceLock;
+
+    static SAVED: OnceLock<libc::termios> = OnceLock::new();
+
+    pub fn save() {
+        // SAFETY: `termios` is plain data and `tcgetattr` only writes to it.
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return;
+            }
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                let _ = SAVED.set(termios);
+            }
+        }
+    }
+
+    pub fn restore() {
+        if let Some(termios) = SAVED.get() {
+            // SAFETY: `termios` was filled in by `tcgetattr` for the same descriptor.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            }
+        }
+    }
+}
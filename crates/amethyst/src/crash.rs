@@ -0,0 +1,99 @@
+use chrono::Local;
+use log::error;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+use amethyst_log::AmethystLogger;
+use crate::build_info;
+use crate::config::Config;
+
+const CRASH_REPORT_DIR: &str = "crash-reports";
+/// Exit code used after a panic, matching the one Rust uses for an unwinding main thread.
+const PANIC_EXIT_CODE: i32 = 101;
+
+static CONFIG_SUMMARY: RwLock<Option<String>> = RwLock::new(None);
+
+/// Installs a panic hook that logs the panic, writes a crash report and exits the process.
+///
+/// Panics inside tokio tasks would otherwise only kill the task, leaving the server running
+/// in an unknown state.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let backtrace = Backtrace::force_capture();
+        let thread = std::thread::current();
+        let thread_name = thread.name().unwrap_or("<unnamed>");
+
+        error!("Thread '{}' panicked: {}\n{}", thread_name, info, backtrace);
+        AmethystLogger::flush_blocking(Duration::from_secs(2));
+
+        match write_report(info, thread_name, &backtrace) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write crash report: {}", e),
+        }
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::report_panic(&info.to_string(), thread_name, &backtrace);
+
+        std::process::exit(PANIC_EXIT_CODE);
+    }));
+}
+
+/// Records the active configuration so it can be included in crash reports. Secrets are
+/// left out, as reports tend to get shared.
+pub fn set_config(config: &Config) {
+    let summary = toml::to_string_pretty(&config.redacted())
+        .unwrap_or_else(|e| format!("<failed to serialize configuration: {}>", e));
+    *CONFIG_SUMMARY.write().unwrap_or_else(|e| e.into_inner()) = Some(summary);
+}
+
+fn write_report(
+    info: &PanicHookInfo,
+    thread_name: &str,
+    backtrace: &Backtrace,
+) -> std::io::Result<PathBuf> {
+    let now = Local::now();
+    let mut report = String::new();
+    let _ = writeln!(report, "---- Amethyst Crash Report ----");
+    let _ = writeln!(report, "Time: {}", now.format("%Y-%m-%d %H:%M:%S%.3f %z"));
+    let _ = writeln!(report, "Version: {}", build_info::summary());
+    let _ = writeln!(report, "Thread: {}", thread_name);
+    let _ = writeln!(report, "Panic: {}", info);
+    let _ = writeln!(report, "\n-- Backtrace --\n{}", backtrace);
+    let config = CONFIG_SUMMARY.read().unwrap_or_else(|e| e.into_inner());
+    let _ = writeln!(
+        report,
+        "\n-- Configuration --\n{}",
+        config.as_deref().unwrap_or("<not loaded>")
+    );
+    drop(config);
+    let _ = writeln!(report, "-- Last log lines --");
+    for line in AmethystLogger::recent_lines() {
+        report.push_str(&strip_ansi(&line));
+    }
+
+    fs::create_dir_all(CRASH_REPORT_DIR)?;
+    let path = PathBuf::from(CRASH_REPORT_DIR)
+        .join(format!("crash-{}.txt", now.format("%Y-%m-%d_%H.%M.%S")));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
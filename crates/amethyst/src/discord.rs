@@ -0,0 +1,215 @@
+//! Bridge between in-game chat and a Discord channel, through a bot account.
+//!
+//! Chat messages are picked up from [`PlayerChat`] events and posted to the channel. New
+//! messages in the channel are polled over the REST API, so no gateway connection is kept open.
+
+use crate::config::DiscordConfig;
+use amethyst_plugin::event::{EventPriority, PlayerChat};
+use amethyst_plugin::{EventBus, HandlerId};
+use log::{debug, info, warn};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+const API_BASE: &str = "https://discord.com/api/v10";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Chat messages waiting to be posted before new ones are dropped.
+const QUEUE_CAPACITY: usize = 256;
+/// Longest message Discord accepts, in characters.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+/// Most messages Discord returns per request.
+const FETCH_LIMIT: usize = 50;
+
+#[derive(Debug, Error)]
+pub enum DiscordError {
+    #[error("Invalid bot token")]
+    Token,
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Discord responded with {0}")]
+    Status(StatusCode),
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    id: String,
+    content: String,
+    author: Author,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    username: String,
+    global_name: Option<String>,
+    #[serde(default)]
+    bot: bool,
+}
+
+/// Relays chat for as long as it is alive.
+pub struct DiscordBridge {
+    events: Arc<EventBus>,
+    handler: HandlerId,
+    task: JoinHandle<()>,
+}
+
+impl DiscordBridge {
+    pub fn spawn(config: &DiscordConfig, events: Arc<EventBus>) -> Result<Self, DiscordError> {
+        let mut token = HeaderValue::from_str(&format!("Bot {}", config.token))
+            .map_err(|_| DiscordError::Token)?;
+        token.set_sensitive(true);
+        let client = Client::builder()
+            .default_headers(HeaderMap::from_iter([(AUTHORIZATION, token)]))
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        let (outbound, queue) = mpsc::channel(QUEUE_CAPACITY);
+        let handler = events.subscribe(EventPriority::Monitor, move |chat: &mut PlayerChat| {
+            if chat.cancelled {
+                return;
+            }
+            let content = format!("**{}**: {}", escape(&chat.name), escape(&chat.message));
+            if outbound.try_send(content).is_err() {
+                debug!("Dropping chat message for Discord: the queue is full");
+            }
+        });
+        let channel = Channel {
+            client,
+            url: format!("{}/channels/{}/messages", API_BASE, config.channel_id),
+        };
+        let task = tokio::spawn(run(channel, queue));
+        Ok(Self {
+            events,
+            handler,
+            task,
+        })
+    }
+}
+
+impl Drop for DiscordBridge {
+    fn drop(&mut self) {
+        self.events.unsubscribe(self.handler);
+        self.task.abort();
+    }
+}
+
+struct Channel {
+    client: Client,
+    url: String,
+}
+
+impl Channel {
+    async fn send(&self, content: &str) -> Result<(), DiscordError> {
+        let content: String = content.chars().take(MAX_MESSAGE_LENGTH).collect();
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "content": content,
+                "allowed_mentions": { "parse": [] },
+            }))
+            .send()
+            .await?;
+        check(response.status())
+    }
+
+    /// Messages posted after `after`, oldest first, or the latest message if `after` is
+    /// `None`.
+    async fn fetch(&self, after: Option<u64>) -> Result<Vec<Message>, DiscordError> {
+        let query = match after {
+            Some(after) => vec![
+                ("after", after.to_string()),
+                ("limit", FETCH_LIMIT.to_string()),
+            ],
+            None => vec![("limit", "1".to_string())],
+        };
+        let response = self.client.get(&self.url).query(&query).send().await?;
+        check(response.status())?;
+        let mut messages: Vec<Message> = response.json().await?;
+        messages.sort_by_key(|message| message_id(message).unwrap_or(0));
+        Ok(messages)
+    }
+}
+
+fn check(status: StatusCode) -> Result<(), DiscordError> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(DiscordError::Status(status))
+    }
+}
+
+fn message_id(message: &Message) -> Option<u64> {
+    message.id.parse().ok()
+}
+
+async fn run(channel: Channel, mut queue: mpsc::Receiver<String>) {
+    // Only messages posted after startup are relayed, so history is not replayed.
+    let mut last_seen = None;
+    // Polling keeps failing while Discord is unreachable, so only the first failure is logged.
+    let mut reachable = true;
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            content = queue.recv() => {
+                let Some(content) = content else {
+                    return;
+                };
+                if let Err(e) = channel.send(&content).await {
+                    warn!("Failed to relay chat to Discord: {}", e);
+                }
+            }
+            _ = poll.tick() => match channel.fetch(last_seen).await {
+                Ok(messages) => {
+                    let newest = messages.iter().filter_map(message_id).max();
+                    if last_seen.is_some() {
+                        messages.into_iter().for_each(relay);
+                    }
+                    last_seen = last_seen.max(newest).or(Some(0));
+                    if !reachable {
+                        info!("Reading messages from Discord again");
+                        reachable = true;
+                    }
+                }
+                Err(e) if reachable => {
+                    warn!("Failed to read messages from Discord: {}", e);
+                    reachable = false;
+                }
+                Err(e) => debug!("Failed to read messages from Discord: {}", e),
+            },
+        }
+    }
+}
+
+/// Shows a message from the channel, skipping those from bots including this one.
+fn relay(message: Message) {
+    if message.author.bot || message.content.is_empty() {
+        return;
+    }
+    let name = message
+        .author
+        .global_name
+        .unwrap_or(message.author.username);
+    // Broadcasting to players needs the RakNet reliability layer; until then the console
+    // is the only place to show it.
+    info!("[Discord] {}: {}", name, message.content);
+}
+
+/// Escapes Discord markdown, so names and messages are shown as typed.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
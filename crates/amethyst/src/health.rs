@@ -0,0 +1,123 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use log::{error, info};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The server is reported as not live once the last tick finished longer ago than this. Even
+/// an idle server ticks at least once a second.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `last_heartbeat` until the first tick, while the server is still starting.
+const NO_HEARTBEAT: u64 = u64::MAX;
+
+/// Startup progress and liveness of the server, as reported on `/healthz` and `/readyz`.
+pub struct Health {
+    started: Instant,
+    /// Milliseconds since `started` at the end of the last tick.
+    last_heartbeat: AtomicU64,
+    listener_bound: AtomicBool,
+    worlds_loaded: AtomicBool,
+}
+
+#[derive(Serialize)]
+struct HealthReport {
+    live: bool,
+    ready: bool,
+    listener_bound: bool,
+    worlds_loaded: bool,
+    heartbeat_age_ms: u64,
+    uptime_secs: u64,
+}
+
+impl Health {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            last_heartbeat: AtomicU64::new(NO_HEARTBEAT),
+            listener_bound: AtomicBool::new(false),
+            worlds_loaded: AtomicBool::new(false),
+        })
+    }
+
+    pub fn set_listener_bound(&self, bound: bool) {
+        self.listener_bound.store(bound, Ordering::Relaxed);
+    }
+
+    pub fn set_worlds_loaded(&self, loaded: bool) {
+        self.worlds_loaded.store(loaded, Ordering::Relaxed);
+    }
+
+    /// Records that the tick loop finished a tick. Once it stops advancing, whether stuck in a
+    /// tick or stopped, `/healthz` starts failing.
+    pub fn beat(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        self.last_heartbeat.store(now, Ordering::Relaxed);
+    }
+
+    fn report(&self) -> HealthReport {
+        let elapsed = self.started.elapsed();
+        let last_heartbeat = self.last_heartbeat.load(Ordering::Relaxed);
+        let heartbeat_age_ms = match last_heartbeat {
+            NO_HEARTBEAT => 0,
+            last => (elapsed.as_millis() as u64).saturating_sub(last),
+        };
+        let listener_bound = self.listener_bound.load(Ordering::Relaxed);
+        let worlds_loaded = self.worlds_loaded.load(Ordering::Relaxed);
+        // A server that has not started ticking yet is still starting, which readiness covers.
+        let live = heartbeat_age_ms < HEARTBEAT_TIMEOUT.as_millis() as u64;
+        HealthReport {
+            live,
+            ready: live && listener_bound && worlds_loaded,
+            listener_bound,
+            worlds_loaded,
+            heartbeat_age_ms,
+            uptime_secs: elapsed.as_secs(),
+        }
+    }
+}
+
+/// Serves unauthenticated `/healthz` (liveness) and `/readyz` (readiness) probes on
+/// `address`. Both answer 200 when healthy and 503 otherwise, with a JSON report.
+pub async fn serve(address: String, health: Arc<Health>) {
+    let app = Router::new()
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+        .with_state(health);
+
+    let listener = match tokio::net::TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind health endpoint to {}: {}", address, e);
+            return;
+        }
+    };
+    info!("Health endpoint listening on http://{}", address);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("Health endpoint stopped: {}", e);
+    }
+}
+
+fn respond(healthy: bool, report: HealthReport) -> Response {
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
+
+async fn liveness(State(health): State<Arc<Health>>) -> Response {
+    let report = health.report();
+    respond(report.live, report)
+}
+
+async fn readiness(State(health): State<Arc<Health>>) -> Response {
+    let report = health.report();
+    respond(report.ready, report)
+}
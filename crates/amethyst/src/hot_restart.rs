@@ -0,0 +1,140 @@
+//! Hot restarts: replacing the server process with a fresh start of its executable, for
+//! upgrades, without disconnecting players. Unix only.
+//!
+//! The old process detaches its sessions instead of closing them, writes their state to a
+//! handoff file and execs the executable again with the listener's sockets left open. The new
+//! process listens on the inherited sockets and restores the sessions, and datagrams that
+//! arrive in between wait in the socket buffers. Since `exec` keeps the PID, supervisors such
+//! as systemd see the same process throughout.
+
+use log::warn;
+use rakethyst::handoff::{Handoff, HandoffError};
+use rakethyst::listener::RakNetListener;
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::os::fd::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+/// Command line options the new process is started with. They are hidden from `--help`.
+pub const HANDOFF_ARG: &str = "--handoff";
+pub const HANDOFF_SOCKETS_ARG: &str = "--handoff-sockets";
+
+#[derive(Debug, Error)]
+pub enum HotRestartError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Handoff(#[from] HandoffError),
+    #[error("failed to encode the handoff: {0}")]
+    Encode(#[from] amethyst_binary::error::BinaryError),
+}
+
+/// Replaces this process with a new start of the server that takes over `listener`'s sockets
+/// and the sessions in `handoff`. Only returns if that fails.
+pub fn exec(listener: &RakNetListener, handoff: &Handoff) -> Result<Infallible, HotRestartError> {
+    let path = handoff_path();
+    write_handoff(&path, &handoff.encode()?)?;
+    let mut fds = Vec::new();
+    for socket in listener.try_clone_sockets()? {
+        let fd = socket.into_raw_fd();
+        set_inheritable(fd, true)?;
+        fds.push(fd.to_string());
+    }
+    let exe = std::env::current_exe()?;
+    let error = Command::new(exe)
+        .args(restart_args(std::env::args_os().skip(1)))
+        .arg(HANDOFF_ARG)
+        .arg(&path)
+        .arg(HANDOFF_SOCKETS_ARG)
+        .arg(fds.join(","))
+        .exec();
+    let _ = fs::remove_file(&path);
+    Err(error.into())
+}
+
+/// In the server directory rather than the shared temporary directory, where other users
+/// could read the sessions or plant a file of their own for the new process to restore.
+fn handoff_path() -> PathBuf {
+    PathBuf::from(format!(".amethyst-handoff-{}", std::process::id()))
+}
+
+/// Writes `data` to a new file only the server's user can read. A file left behind by an
+/// earlier restart that failed is replaced, never written through.
+fn write_handoff(path: &Path, data: &[u8]) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)
+}
+
+/// The arguments of this process without the handoff options it may have been started with.
+fn restart_args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut kept = Vec::new();
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == HANDOFF_ARG || arg == HANDOFF_SOCKETS_ARG {
+            args.next();
+        } else {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// Takes over the sockets `fds` and the sessions in the handoff file at `path` from the
+/// previous server process, and removes the file.
+pub fn take(
+    path: &Path,
+    fds: &[RawFd],
+) -> Result<(Vec<std::net::UdpSocket>, Handoff), HotRestartError> {
+    let data = fs::read(path)?;
+    if let Err(e) = fs::remove_file(path) {
+        warn!(
+            "Failed to remove the handoff file {}: {}",
+            path.display(),
+            e
+        );
+    }
+    let handoff = Handoff::decode(data.into())?;
+    let mut sockets = Vec::with_capacity(fds.len());
+    for &fd in fds {
+        set_inheritable(fd, false)?;
+        // SAFETY: the previous process passed these descriptors on to this one as its
+        // listener's sockets, and nothing else in this process owns them.
+        sockets.push(unsafe { std::net::UdpSocket::from_raw_fd(fd) });
+    }
+    Ok((sockets, handoff))
+}
+
+/// Whether `fd` stays open across `exec`.
+fn set_inheritable(fd: RawFd, inheritable: bool) -> io::Result<()> {
+    // SAFETY: fcntl only reads and sets the flags of the descriptor and fails cleanly if it
+    // is not open.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if inheritable {
+            flags & !libc::FD_CLOEXEC
+        } else {
+            flags | libc::FD_CLOEXEC
+        };
+        if libc::fcntl(fd, libc::F_SETFD, flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use log::info;
+use p384::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use p384::SecretKey;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("TOML deserialization error: {0}")]
+    TomlDeserialization(#[from] toml::de::Error),
+    #[error("TOML serialization error: {0}")]
+    TomlSerialization(#[from] toml::ser::Error),
+    #[error("Invalid GUID: '{0}'")]
+    InvalidGuid(String),
+    #[error("Invalid private key: {0}")]
+    InvalidKey(String),
+}
+
+/// On-disk form of [`ServerIdentity`].
+#[derive(Serialize, Deserialize)]
+struct KeysFile {
+    /// Stored as a string because TOML integers cannot hold the full `u64` range.
+    guid: String,
+    /// PKCS#8 PEM encoded P-384 private key.
+    private_key: String,
+}
+
+/// The server's RakNet GUID and the P-384 key pair used for the encryption handshake.
+///
+/// Both are generated on first start and persisted so that clients see the same server
+/// across restarts.
+pub struct ServerIdentity {
+    pub guid: u64,
+    pub secret_key: SecretKey,
+}
+
+impl ServerIdentity {
+    /// Loads the identity from `path`, generating and saving a new one if it does not exist.
+    pub fn load_or_create(path: &Path) -> Result<Self, IdentityError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let identity = Self {
+            guid: rand::random(),
+            secret_key: generate_secret_key(),
+        };
+        identity.save(path)?;
+        info!("Generated new server identity in {}", path.display());
+        Ok(identity)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, IdentityError> {
+        let keys: KeysFile = toml::from_str(&fs::read_to_string(path)?)?;
+        let secret_key = SecretKey::from_pkcs8_pem(&keys.private_key)
+            .map_err(|e| IdentityError::InvalidKey(e.to_string()))?;
+        Ok(Self {
+            guid: keys
+                .guid
+                .parse()
+                .map_err(|_| IdentityError::InvalidGuid(keys.guid))?,
+            secret_key,
+        })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), IdentityError> {
+        let private_key = self
+            .secret_key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|e| IdentityError::InvalidKey(e.to_string()))?;
+        let content = toml::to_string_pretty(&KeysFile {
+            guid: self.guid.to_string(),
+            private_key: private_key.to_string(),
+        })?;
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// DER encoded `SubjectPublicKeyInfo`, as sent to clients in the handshake JWT.
+    pub fn public_key_der(&self) -> Result<Vec<u8>, IdentityError> {
+        self.secret_key
+            .public_key()
+            .to_public_key_der()
+            .map(|der| der.into_vec())
+            .map_err(|e| IdentityError::InvalidKey(e.to_string()))
+    }
+}
+
+/// Generates a private key from `rand`'s OS-seeded generator. Only fails for the vanishingly
+/// unlikely scalars outside the curve order, in which case it simply draws again.
+fn generate_secret_key() -> SecretKey {
+    loop {
+        let bytes: [u8; 48] = rand::random();
+        if let Ok(key) = SecretKey::from_slice(&bytes) {
+            return key;
+        }
+    }
+}
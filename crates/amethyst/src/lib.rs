@@ -0,0 +1,44 @@
+pub mod access;
+pub mod admin;
+pub mod afk;
+pub mod build_info;
+pub mod chat;
+pub mod check;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod console;
+pub mod crash;
+#[cfg(feature = "discord")]
+pub mod discord;
+pub mod health;
+#[cfg(unix)]
+pub mod hot_restart;
+pub mod identity;
+pub mod lock;
+pub mod login;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod permissions;
+pub mod ping;
+pub mod players;
+pub mod plugins;
+#[cfg(all(feature = "profiling", unix))]
+pub mod profiling;
+pub mod protocol;
+pub mod proxy;
+pub mod replay;
+pub mod resource_packs;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod shutdown;
+pub mod spawn_protection;
+#[cfg(all(feature = "systemd", unix))]
+pub mod systemd;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+pub mod tick;
+pub mod timings;
+pub mod trace;
+pub mod view_distance;
+pub mod watchdog;
@@ -0,0 +1,81 @@
+//! The lock that keeps a second server from using the same worlds directory.
+
+use log::warn;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the lock file inside the worlds directory.
+pub const LOCK_FILE: &str = "session.lock";
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{} is locked by another server{}", path.display(), pid_suffix(*pid))]
+    Locked { path: PathBuf, pid: Option<u32> },
+}
+
+/// An exclusive lock on a worlds directory, held until dropped.
+///
+/// The lock is taken with the operating system, so it goes away with the process even if the
+/// server crashes. The file holds the PID of the server while it runs and is emptied on a
+/// clean shutdown, so a PID found in an unlocked file means the last server did not stop
+/// cleanly.
+pub struct ServerLock {
+    file: File,
+}
+
+impl ServerLock {
+    /// Locks `dir`, creating it if needed. Fails if another process holds the lock.
+    pub fn acquire(dir: &Path) -> Result<Self, LockError> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = read_pid(&mut file);
+                return Err(LockError::Locked { path, pid });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        if let Some(pid) = read_pid(&mut file) {
+            warn!(
+                "Removing the stale lock of a server (PID {}) that did not shut down cleanly",
+                pid
+            );
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(ServerLock { file })
+    }
+}
+
+impl Drop for ServerLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.set_len(0) {
+            warn!("Failed to clear the lock file: {}", e);
+        }
+    }
+}
+
+fn pid_suffix(pid: Option<u32>) -> String {
+    pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default()
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
@@ -0,0 +1,207 @@
+//! The identity chain a client sends with its `Login` packet: JWTs that each sign the key of
+//! the next, starting at Mojang's key for players signed in to Xbox Live. The last one names
+//! the player and the key their client data is signed with.
+//!
+//! With `server.online_mode = false` the chain is read without verifying it, so anyone can
+//! join under any name. Such players get no XUID, since it could be made up, and a UUID
+//! derived from their name with [`offline_uuid`], so they keep it across joins.
+
+use crate::protocol::format_uuid;
+use amethyst_binary::jwt::{parse_public_key, Jwt, JwtError};
+use amethyst_plugin::event::{EventPriority, PlayerJoin, PlayerQuit};
+use amethyst_plugin::EventBus;
+use dashmap::DashSet;
+use log::info;
+use md5::{Digest, Md5};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Key Mojang signs the chains of Xbox Live players with, as base64 DER.
+pub const MOJANG_PUBLIC_KEY: &str = "MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAECRXueJeTDqNRRgJi/vlRufByu/2G0i2Ebt6YMar5QX/R0DIIyrJMcUpruK4QveTfJSTp3Shlq4Gk34cD/4GUWwkv0DVuzeuB+tXija7HBxii03NHDbPAD0AKnLr2wdAp";
+
+/// Tokens a chain may have: the client's own, Mojang's and the identity token.
+const MAX_CHAIN_LENGTH: usize = 3;
+
+/// Seconds a token may be used before `nbf` or after `exp`, for clocks that are a bit off.
+const CLOCK_SKEW: i64 = 60;
+
+#[derive(Debug, Error)]
+pub enum LoginError {
+    #[error("Invalid token: {0}")]
+    Jwt(#[from] JwtError),
+    #[error("The chain is empty")]
+    EmptyChain,
+    #[error("The chain has {0} tokens, more than any client sends")]
+    ChainTooLong(usize),
+    #[error("A token is signed by a key the previous one did not name")]
+    BrokenChain,
+    #[error("The identity is not signed by a key Xbox Live certified")]
+    NotAuthenticated,
+    #[error("A token has expired or is not valid yet")]
+    Expired,
+    #[error("The chain has no '{0}'")]
+    MissingClaim(&'static str),
+}
+
+/// Who a client is, according to its chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginIdentity {
+    pub name: String,
+    /// `None` for players who joined in offline mode.
+    pub xuid: Option<String>,
+    /// Hyphenated.
+    pub uuid: String,
+    /// Base64 DER key the client data of the `Login` packet has to be signed with.
+    pub identity_public_key: String,
+    /// Whether Xbox Live vouched for the player, which it never does in offline mode.
+    pub authenticated: bool,
+}
+
+impl LoginIdentity {
+    /// The name for logs, marked if the player is not authenticated.
+    pub fn display_name(&self) -> String {
+        if self.authenticated {
+            self.name.clone()
+        } else {
+            format!("{} (offline)", self.name)
+        }
+    }
+}
+
+/// Reads the identity from `chain`, verifying every signature and that Mojang signed it if
+/// `online_mode` is set. `now` is in seconds since the Unix epoch.
+pub fn verify_chain(
+    chain: &[&str],
+    online_mode: bool,
+    now: i64,
+) -> Result<LoginIdentity, LoginError> {
+    verify_chain_signed_by(chain, MOJANG_PUBLIC_KEY, online_mode, now)
+}
+
+/// [`verify_chain`] with `root_key` in place of Mojang's key, for chains signed by another
+/// authority.
+pub fn verify_chain_signed_by(
+    chain: &[&str],
+    root_key: &str,
+    online_mode: bool,
+    now: i64,
+) -> Result<LoginIdentity, LoginError> {
+    if chain.is_empty() {
+        return Err(LoginError::EmptyChain);
+    }
+    if chain.len() > MAX_CHAIN_LENGTH {
+        return Err(LoginError::ChainTooLong(chain.len()));
+    }
+    let mut next_key: Option<String> = None;
+    let mut signed_by_root = None;
+    let mut claims = Value::Null;
+    for (index, token) in chain.iter().enumerate() {
+        let jwt = Jwt::split(token)?;
+        claims = jwt.payload()?;
+        if !online_mode {
+            continue;
+        }
+        // The first token is signed by the key it names itself, which only means something
+        // if that key is the root or it is followed by a token the root signed.
+        let key = jwt.x5u()?;
+        if next_key.as_ref().is_some_and(|expected| *expected != key) {
+            return Err(LoginError::BrokenChain);
+        }
+        jwt.verify_es384(&parse_public_key(&key)?)?;
+        if key == root_key {
+            signed_by_root = Some(index);
+        }
+        check_validity(&claims, now)?;
+        next_key = Some(claim(&claims, "identityPublicKey")?.to_string());
+    }
+    // The identity is only vouched for if the root certified the key that signed it. Any
+    // token after that one is signed by a key the client holds, and could claim anything.
+    if online_mode && (chain.len() < 2 || signed_by_root != Some(chain.len() - 2)) {
+        return Err(LoginError::NotAuthenticated);
+    }
+
+    let identity_public_key = claim(&claims, "identityPublicKey")?.to_string();
+    let extra_data = claims.get("extraData").unwrap_or(&Value::Null);
+    let name = claim(extra_data, "displayName")?.to_string();
+    if !online_mode {
+        return Ok(LoginIdentity {
+            uuid: offline_uuid(&name),
+            name,
+            xuid: None,
+            identity_public_key,
+            authenticated: false,
+        });
+    }
+    Ok(LoginIdentity {
+        name,
+        xuid: Some(claim(extra_data, "XUID")?.to_string()),
+        uuid: claim(extra_data, "identity")?.to_string(),
+        identity_public_key,
+        authenticated: true,
+    })
+}
+
+fn claim<'a>(claims: &'a Value, name: &'static str) -> Result<&'a str, LoginError> {
+    claims
+        .get(name)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .ok_or(LoginError::MissingClaim(name))
+}
+
+fn check_validity(claims: &Value, now: i64) -> Result<(), LoginError> {
+    let not_before = claims.get("nbf").and_then(Value::as_i64);
+    let expires = claims.get("exp").and_then(Value::as_i64);
+    if not_before.is_some_and(|nbf| nbf > now + CLOCK_SKEW)
+        || expires.is_some_and(|exp| exp < now - CLOCK_SKEW)
+    {
+        return Err(LoginError::Expired);
+    }
+    Ok(())
+}
+
+/// The UUID of an offline-mode player: the name-based (version 3) UUID of
+/// `OfflinePlayer:<name>`, as Java Edition servers derive it.
+pub fn offline_uuid(name: &str) -> String {
+    let mut uuid: [u8; 16] = Md5::digest(format!("OfflinePlayer:{}", name)).into();
+    uuid[6] = (uuid[6] & 0x0f) | 0x30;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    format_uuid(&uuid)
+}
+
+/// Players connected without authentication, marked in the player list.
+#[derive(Default)]
+pub struct OfflinePlayers {
+    players: DashSet<SocketAddr>,
+}
+
+impl OfflinePlayers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notes players that join unauthenticated and forgets them as they leave.
+    pub fn register_events(self: &Arc<Self>, events: &EventBus) {
+        let offline = Arc::clone(self);
+        events.subscribe(EventPriority::Monitor, move |join: &mut PlayerJoin| {
+            if join.cancelled || join.authenticated {
+                return;
+            }
+            info!(
+                "{} (offline) joined from {} without authentication",
+                join.name, join.address
+            );
+            offline.players.insert(join.address);
+        });
+        let offline = Arc::clone(self);
+        events.subscribe(EventPriority::Monitor, move |quit: &mut PlayerQuit| {
+            offline.players.remove(&quit.address);
+        });
+    }
+
+    pub fn contains(&self, address: SocketAddr) -> bool {
+        self.players.contains(&address)
+    }
+}
@@ -1,28 +1,76 @@
 use std::error::Error;
+use std::path::Path;
 use std::sync::Arc;
-use log::{error, info, logger, Level};
+use log::{error, info, logger, warn, Level};
 use tokio::time::{Instant, Duration};
-use amethyst_log::AmethystLogger;
-use crate::config::Config;
+use tokio::sync::broadcast;
+use amethyst_log::{AmethystLogger, ColorChoice};
+use amethyst_plugin::event::{ConfigReloaded, PacketReceive, ServerStarted, ServerStopping};
+use amethyst_plugin::{EventBus, Permissions, PlayerDirectory, Scheduler};
+use clap::Parser;
+use amethyst::{
+    admin, build_info, check, config, console, crash, health, permissions, ping, players, plugins,
+    proxy, replay, resource_packs, tick, watchdog,
+};
+use amethyst::access::AccessLists;
+use amethyst::admin::AdminState;
+use amethyst::afk::AfkTracker;
+use amethyst::chat::Chat;
+use amethyst::cli::{Cli, Command};
+use amethyst::commands::{CommandContext, CommandRegistry};
+use amethyst::health::Health;
+use amethyst::login::OfflinePlayers;
+use amethyst::plugins::PluginManager;
+use amethyst::proxy::ProxyLink;
+use amethyst::resource_packs::ResourcePacks;
+use amethyst::shutdown::Shutdown;
+use amethyst::spawn_protection::SpawnProtection;
+use amethyst::tick::{IdleWaker, TickStats};
+use amethyst::timings::Timings;
+use amethyst::view_distance::ViewDistances;
+use amethyst::identity::ServerIdentity;
+use amethyst::lock::ServerLock;
+use amethyst::config::watch::{ConfigChanged, ConfigWatcher};
+use amethyst::config::{world, Config, LoggingConfig, PacketTraceConfig};
 use tokio::signal;
-use rakethyst::listener::RakNetListener;
-
-pub mod config;
+use rakethyst::handoff::Handoff;
+use rakethyst::listener::{PacketFilter, RakNetListener, ServerInfo, SessionHook};
+use rakethyst::motd::Motd;
+use rakethyst::packet_trace::PacketTrace;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if let Some(Command::Check) = cli.command {
+        std::process::exit(if check::run(&cli) { 0 } else { 1 });
+    }
+    if let Some(Command::Ping { target, timeout }) = &cli.command {
+        let ok = ping::run(target, Duration::from_secs(*timeout)).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
     rakethyst::utils::init_time();
-    
+
     if let Err(e) = AmethystLogger::init(Level::Trace, 1024) {
         eprintln!("Failed to initialize logger: {}", e);
         std::process::exit(1);
     }
+    if cli.no_color {
+        AmethystLogger::set_color(ColorChoice::Never);
+    }
+    if let Some(Command::Replay { file }) = &cli.command {
+        std::process::exit(if replay::run(&cli, file).await { 0 } else { 1 });
+    }
+    crash::install_panic_hook();
 
     let start_time = Instant::now();
+    info!("Starting {}", build_info::summary());
 
-    let config: Arc<Config> = match config::handle() {
+    let overrides = cli.overrides();
+    let config: Arc<Config> = match config::handle(&cli.config, &overrides) {
         Ok(config) => {
             info!("Configuration loaded successfully.");
+            crash::set_config(&config);
+            apply_logging(&config.logging);
             logger().flush();
             Arc::new(config)
         },
@@ -32,40 +80,630 @@ async fn main() -> Result<(), Box<dyn Error>> {
             return Err(e.into());
         }
     };
-    let listener = match RakNetListener::bind(&config.network.address, config.server.name.clone()).await {
-        Ok(listener) => listener,
+
+    #[cfg(feature = "telemetry")]
+    if config.telemetry.enabled {
+        match amethyst::telemetry::init(&config) {
+            Ok(()) => info!("Reporting crashes to the telemetry server"),
+            Err(e) => warn!("Failed to start telemetry: {}", e),
+        }
+    }
+    #[cfg(not(feature = "telemetry"))]
+    if config.telemetry.enabled {
+        warn!("telemetry.enabled is set, but this build does not include telemetry");
+    }
+    #[cfg(feature = "otlp")]
+    if config.otlp.enabled {
+        match amethyst::otlp::init(&config) {
+            Ok(()) => info!("Exporting spans to {}", config.otlp.endpoint),
+            Err(e) => warn!("Failed to start span export: {}", e),
+        }
+    }
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp.enabled {
+        warn!("otlp.enabled is set, but this build does not include span export");
+    }
+
+    let health = Health::new();
+    let health_task = config
+        .health
+        .enabled
+        .then(|| tokio::spawn(health::serve(config.health.address.clone(), Arc::clone(&health))));
+
+    let server_lock = match ServerLock::acquire(Path::new(&config.worlds.directory)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            error!("Refusing to start: {}", e);
+            return Err(e.into());
+        }
+    };
+    let worlds = match world::load_worlds(Path::new(&config.worlds.directory), &config.worlds.load) {
+        Ok(worlds) => worlds,
+        Err(e) => {
+            error!("Failed to load world configuration: {}", e);
+            return Err(e.into());
+        }
+    };
+    for (name, world) in &worlds {
+        info!(
+            "Loaded world '{}' (generator: {:?}, seed: {}, gamemode: {})",
+            name,
+            world.generator,
+            world.seed,
+            world.gamemode.name()
+        );
+    }
+    health.set_worlds_loaded(true);
+
+    let resource_packs = if config.resource_packs.enabled {
+        match ResourcePacks::load(&config.resource_packs) {
+            Ok(packs) => Some(Arc::new(packs)),
+            Err(e) => {
+                error!("Failed to load resource packs: {}", e);
+                return Err(e.into());
+            }
+        }
+    } else {
+        None
+    };
+    let resource_pack_task = resource_packs.as_ref().map(|packs| {
+        info!("Serving {} resource packs", packs.packs().len());
+        tokio::spawn(resource_packs::serve(
+            config.resource_packs.address.clone(),
+            Arc::clone(packs),
+        ))
+    });
+
+    let identity = match ServerIdentity::load_or_create(Path::new(&config.server.keys_file)) {
+        Ok(identity) => identity,
+        Err(e) => {
+            error!(
+                "Failed to load server identity from {}: {}",
+                config.server.keys_file, e
+            );
+            return Err(e.into());
+        }
+    };
+    let access = match AccessLists::load(
+        Path::new("."),
+        config.server.whitelist,
+        config.server.whitelist_message.clone(),
+    ) {
+        Ok(access) => Arc::new(access),
+        Err(e) => {
+            error!("Failed to load access lists: {}", e);
+            return Err(e.into());
+        }
+    };
+    let access_watcher = match access.watch(Path::new(".")) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!("Access list hot reload is disabled: {}", e);
+            None
+        }
+    };
+
+    access.spawn_ban_expiry();
+
+    let permissions = Arc::new(Permissions::new());
+    let permissions_path = Path::new(".").join(permissions::PERMISSIONS_FILE_NAME);
+    if let Err(e) = permissions::load(&permissions_path, &permissions) {
+        error!("Failed to load permissions: {}", e);
+        return Err(e.into());
+    }
+    let permissions_watcher = match permissions::watch(&permissions, Path::new(".")) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!("Permissions hot reload is disabled: {}", e);
+            None
+        }
+    };
+
+    let players = Arc::new(PlayerDirectory::new());
+    let players_path = Path::new(".").join(players::PLAYERS_FILE_NAME);
+    if let Err(e) = players::load(&players_path, &players) {
+        error!("Failed to load the player index: {}", e);
+        return Err(e.into());
+    }
+
+    let events = Arc::new(EventBus::new());
+    access.register_events(&events);
+    let spawn_protection = Arc::new(SpawnProtection::new(
+        &worlds,
+        Arc::clone(&access),
+        Arc::clone(&permissions),
+    ));
+    spawn_protection.register_events(&events);
+    if !config.server.online_mode {
+        warn!(
+            "Offline mode: players are not authenticated with Xbox Live, so anyone can join \
+             under any name, including that of an operator"
+        );
+    }
+    let offline_players = Arc::new(OfflinePlayers::new());
+    offline_players.register_events(&events);
+    players::register_events(&players, players_path, &events);
+    let chat = Arc::new(Chat::new(
+        Arc::clone(&events),
+        Arc::clone(&access),
+        config.chat.clone(),
+    ));
+    let view_distances = Arc::new(ViewDistances::new(config.server.view_distance));
+    view_distances.register_events(&events);
+    let afk = Arc::new(AfkTracker::new(config.afk.clone()));
+    afk.register_events(&events);
+
+    let server_info = Arc::new(ServerInfo::new(identity.guid, motd(&config)));
+    let idle_waker = Arc::new(IdleWaker::new());
+    let addresses: Vec<&str> = config.network.addresses.iter().map(String::as_str).collect();
+    let socket_options = config.network.socket_options();
+    #[cfg(unix)]
+    let (inherited_sockets, handoff) = match &cli.handoff {
+        Some(path) => match amethyst::hot_restart::take(path, &cli.handoff_sockets) {
+            Ok((sockets, handoff)) => (Some(sockets), handoff),
+            Err(e) => {
+                error!("Failed to take over from the previous server process: {}", e);
+                return Err(e.into());
+            }
+        },
+        None => (None, Handoff::default()),
+    };
+    #[cfg(not(unix))]
+    let (inherited_sockets, handoff) = (None, Handoff::default());
+    let bound = match inherited_sockets {
+        Some(sockets) => RakNetListener::from_std_sockets(sockets, server_info),
+        None => RakNetListener::bind_with_options(&addresses, &socket_options, server_info).await,
+    };
+    let listener = match bound {
+        Ok(listener) => {
+            let listener = listener
+                .with_packet_filter(packet_filter(Arc::clone(&events)))
+                .with_session_hook(session_hook(Arc::clone(&idle_waker)));
+            let mut listener = listener.with_handshake_timeout(Duration::from_secs(
+                config.network.handshake_timeout,
+            ));
+            if !config.network.record_sessions.is_empty() {
+                warn!(
+                    "Recording every session to {}. Recordings contain everything clients send",
+                    config.network.record_sessions
+                );
+                listener =
+                    listener.with_session_recording(Path::new(&config.network.record_sessions));
+            }
+            if let Some(mode) = config.network.strict_mode() {
+                listener = listener.with_strict_mode(mode);
+            }
+            listener = listener.with_system_addresses(config.network.system_addresses());
+            if let Some(throttle) = config.join_throttle.throttle() {
+                listener = listener.with_join_throttle(throttle);
+            }
+            if config.network.proxy_protocol {
+                info!("Expecting PROXY protocol headers on the RakNet listener");
+                listener.with_proxy_protocol()
+            } else {
+                listener
+            }
+        }
         Err(e) => {
             error!(
                 "Failed to bind RakNet listener to {}: {}",
-                config.network.address, e
+                addresses.join(", "),
+                e
             );
-            return Err(e);
+            return Err(e.into());
         }
     };
+    health.set_listener_bound(true);
+    let restored = listener.restore_sessions(&handoff);
+    if restored > 0 {
+        info!("Took over {} sessions from the previous server process", restored);
+    }
+    let tick_stats = Arc::new(TickStats::new());
+    let timings = Arc::new(Timings::new(config.timings.clone()));
+    let packet_trace = listener.packet_trace();
+    apply_packet_trace(&packet_trace, &config.packet_trace);
+
+    let shutdown = Arc::new(Shutdown::new());
+    let runtime = tokio::runtime::Handle::current();
+    let scheduler = Arc::new(Scheduler::with_executor(move |job| {
+        runtime.spawn_blocking(job);
+    }));
+    afk.schedule(&scheduler, listener.connections(), listener.server_info());
+    let command_context = CommandContext {
+        server_info: listener.server_info(),
+        connections: listener.connections(),
+        access: Arc::clone(&access),
+        chat: Arc::clone(&chat),
+        shutdown: Arc::clone(&shutdown),
+        packet_trace: Arc::clone(&packet_trace),
+        tick_stats: Arc::clone(&tick_stats),
+        timings: Arc::clone(&timings),
+        traffic: listener.traffic(),
+        permissions: Arc::clone(&permissions),
+        resource_packs,
+        view_distances: Arc::clone(&view_distances),
+        players: Arc::clone(&players),
+        afk,
+        offline_players,
+    };
+    let mut registry = CommandRegistry::with_builtins();
+    let plugins = Arc::new(PluginManager::load(
+        Path::new(plugins::PLUGINS_DIR),
+        &mut registry,
+        Arc::clone(&events),
+        Arc::clone(&scheduler),
+        Arc::clone(&permissions),
+        Arc::clone(&players),
+    ));
+    #[cfg(feature = "scripting")]
+    let scripts = amethyst::scripting::ScriptManager::load(
+        Path::new(amethyst::scripting::SCRIPTS_DIR),
+        &mut registry,
+        Arc::clone(&events),
+        Arc::clone(&scheduler),
+        command_context.clone(),
+    );
+    let commands = Arc::new(registry);
+
+    if let Err(e) = console::spawn(Arc::clone(&commands), command_context.clone()) {
+        warn!("Failed to start the console: {}", e);
+    }
+
+    let lan_broadcast_task = config
+        .network
+        .lan_broadcast
+        .then(|| listener.lan_broadcast())
+        .flatten()
+        .map(|broadcast| {
+            info!("Advertising the server on the local network");
+            tokio::spawn(broadcast)
+        });
+
+    let admin_task = config.admin.enabled.then(|| {
+        tokio::spawn(admin::serve(
+            config.admin.address.clone(),
+            AdminState {
+                token: Arc::from(config.admin.token.as_str()),
+                started: start_time.into_std(),
+                server_info: listener.server_info(),
+                connections: listener.connections(),
+                access: Arc::clone(&access),
+                stats: listener.stats(),
+                commands: Arc::clone(&commands),
+                command_context: command_context.clone(),
+            },
+        ))
+    });
+
+    let config_watcher =
+        match ConfigWatcher::spawn(cli.config.clone(), overrides, Arc::clone(&config)) {
+            Ok(watcher) => {
+                tokio::spawn(apply_config_changes(
+                    watcher.subscribe(),
+                    command_context.clone(),
+                    Arc::clone(&events),
+                ));
+                Some(watcher)
+            }
+            Err(e) => {
+                warn!("Configuration hot reload is disabled: {}", e);
+                None
+            }
+        };
+
+    let proxy_link = config.proxy.enabled.then(|| {
+        let (inbound, frames) = tokio::sync::mpsc::channel(1024);
+        let link = Arc::new(ProxyLink::spawn(config.proxy.backend.clone(), inbound));
+        proxy::mirror_sessions(Arc::clone(&link), listener.connections(), &scheduler);
+        tokio::spawn(proxy::handle_inbound(
+            frames,
+            listener.connections(),
+            listener.server_info(),
+        ));
+        warn!(
+            "Proxy mode forwards sessions to {}, but not their game packets until the RakNet \
+             reliability layer is implemented",
+            config.proxy.backend
+        );
+        link
+    });
+
+    #[cfg(feature = "discord")]
+    let discord_bridge = if config.discord.enabled {
+        match amethyst::discord::DiscordBridge::spawn(&config.discord, Arc::clone(&events)) {
+            Ok(bridge) => {
+                info!("Relaying chat to Discord channel {}", config.discord.channel_id);
+                Some(bridge)
+            }
+            Err(e) => {
+                warn!("Failed to start the Discord bridge: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(not(feature = "discord"))]
+    if config.discord.enabled {
+        warn!("discord.enabled is set, but this build does not include the Discord bridge");
+    }
+
+    let tick_thread = match tick::spawn(
+        Arc::clone(&scheduler),
+        Arc::clone(&tick_stats),
+        Arc::clone(&timings),
+        Arc::clone(&health),
+        Arc::clone(&shutdown),
+        listener.connections(),
+        idle_waker,
+    ) {
+        Ok(thread) => thread,
+        Err(e) => {
+            error!("Failed to start the tick thread: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    let watchdog_thread = if config.watchdog.enabled {
+        match watchdog::spawn(
+            config.watchdog.clone(),
+            Arc::clone(&scheduler),
+            Arc::clone(&tick_stats),
+            Arc::clone(&shutdown),
+            tokio::runtime::Handle::current(),
+        ) {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                error!("Failed to start the watchdog thread: {}", e);
+                return Err(e.into());
+            }
+        }
+    } else {
+        None
+    };
 
     let elapsed_duration = start_time.elapsed();
     info!(
         "Server startup complete in {:.3}s. Listening on {}",
         elapsed_duration.as_secs_f64(),
-        config.network.address
+        addresses.join(", ")
     );
     logger().flush();
+    events.post(ServerStarted);
+    #[cfg(all(feature = "systemd", unix))]
+    amethyst::systemd::notify_ready(&format!("Listening on {}", addresses.join(", ")));
 
+    let mut listener_task = Box::pin(listener.run());
     tokio::select! {
-        res = listener.run() => {
+        res = &mut listener_task => {
+            health.set_listener_bound(false);
             if let Err(e) = res {
                 error!("RakNet listener exited with error: {}", e);
             } else {
                 info!("RakNet listener stopped gracefully.");
             }
         }
+        _ = shutdown.wait() => {
+            info!("Shutdown requested, initiating shutdown...");
+        }
         _ = signal::ctrl_c() => {
             info!("Ctrl+C received, initiating shutdown...");
-            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+        _ = terminate_signal() => {
+            info!("SIGTERM received, initiating shutdown...");
         }
     }
 
-    info!("Shutting down server.");
-    logger().flush();
+    // New connections go first, then the players, then whatever could still change their
+    // data, and the listener and logs last.
+    // A restart keeps the clients connected and hands them over to the new process instead.
+    let restart = cfg!(unix) && shutdown.is_restart_requested();
+    shutdown.request();
+    #[cfg(all(feature = "systemd", unix))]
+    if !restart {
+        amethyst::systemd::notify_stopping();
+    }
+    listener.stop_accepting();
+    events.post(ServerStopping);
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    let handoff = if restart {
+        let handoff = listener.detach_sessions(SESSION_CLOSE_TIMEOUT).await;
+        info!("Handing {} sessions over to the new server process", handoff.sessions.len());
+        handoff
+    } else {
+        let closed = listener.close_all(SESSION_CLOSE_TIMEOUT).await;
+        if closed > 0 {
+            info!("Disconnected {} clients", closed);
+        }
+        Handoff::default()
+    };
+    join_tick_thread(tick_thread).await;
+    if let Some(thread) = watchdog_thread {
+        let _ = thread.join();
+    }
+    #[cfg(feature = "scripting")]
+    scripts.unload_all();
+    plugins.disable_all();
+    // World and player data is saved here once the server keeps any.
+    for task in [admin_task, health_task, resource_pack_task, lan_broadcast_task]
+        .into_iter()
+        .flatten()
+    {
+        task.abort();
+    }
+    drop(proxy_link);
+    #[cfg(feature = "discord")]
+    drop(discord_bridge);
+    drop(config_watcher);
+    drop(access_watcher);
+    drop(permissions_watcher);
+    drop(listener_task);
+    drop(server_lock);
+    info!("{}", if restart { "Restarting server." } else { "Shutting down server." });
+    AmethystLogger::flush_blocking(Duration::from_secs(1));
+    console::restore_terminal();
+    #[cfg(unix)]
+    if restart {
+        let Err(e) = amethyst::hot_restart::exec(&listener, &handoff);
+        error!("Failed to restart the server: {}", e);
+        AmethystLogger::shutdown(LOGGER_STOP_TIMEOUT);
+        return Err(e.into());
+    }
+    AmethystLogger::shutdown(LOGGER_STOP_TIMEOUT);
     Ok(())
+}
+
+/// How long shutdown waits for clients to be disconnected, or for their sessions to be
+/// detached on a restart.
+const SESSION_CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long shutdown waits for the log writer thread to write out the last records.
+const LOGGER_STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long shutdown waits for the tick thread to finish its tick.
+const TICK_THREAD_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolves when the process is asked to terminate, as container runtimes and service
+/// managers do to stop it. Never resolves where there is no such signal.
+async fn terminate_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+                return;
+            }
+            Err(e) => warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    std::future::pending::<()>().await
+}
+
+/// Waits for the tick thread to finish its tick, but not forever: the shutdown may have been
+/// requested by the watchdog because a tick never finishes.
+async fn join_tick_thread(thread: std::thread::JoinHandle<()>) {
+    let deadline = Instant::now() + TICK_THREAD_STOP_TIMEOUT;
+    while !thread.is_finished() {
+        if Instant::now() >= deadline {
+            warn!("The tick thread did not stop, shutting down without it");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    if thread.join().is_err() {
+        error!("The tick thread panicked");
+    }
+}
+
+fn motd(config: &Config) -> Motd {
+    Motd {
+        motd: config.server.motd.clone(),
+        world_name: config.server.world_name.clone(),
+        game_mode: config.server.gamemode.name().to_string(),
+        max_players: config.server.max_players,
+    }
+}
+
+fn apply_logging(logging: &LoggingConfig) {
+    if let Ok(filter) = logging.filter() {
+        AmethystLogger::set_filter(filter);
+    }
+    if let Ok(color) = logging.color() {
+        AmethystLogger::set_color(color);
+    }
+    if let Ok(policy) = logging.overflow() {
+        AmethystLogger::set_overflow_policy(policy);
+    }
+    AmethystLogger::set_rate_limit(logging.rate_limit_burst, logging.rate_limit_window());
+}
+
+fn apply_packet_trace(trace: &PacketTrace, config: &PacketTraceConfig) {
+    if let Ok(filter) = config.filter() {
+        trace.set_filter(filter);
+    }
+    if !config.enabled {
+        trace.disable();
+        return;
+    }
+    let path = Path::new(&config.file);
+    if trace.path().as_deref() == Some(path) {
+        return;
+    }
+    match trace.enable(path) {
+        Ok(()) => warn!(
+            "Tracing packets to {}. The trace contains everything clients send",
+            config.file
+        ),
+        Err(e) => error!("Failed to open packet trace file {}: {}", config.file, e),
+    }
+}
+
+/// Players above a lowered maximum would be sent a
+/// [`amethyst::protocol::ChunkRadiusUpdated`], which needs the RakNet reliability layer; until
+/// then their radius only shrinks on our side.
+fn apply_view_distance(view_distances: &ViewDistances, max: u32) {
+    let lowered = view_distances.set_max(max);
+    info!("View distance set to {} chunks", max);
+    if !lowered.is_empty() {
+        info!("Lowered the view distance of {} players", lowered.len());
+    }
+}
+
+/// Posts a [`PacketReceive`] for each datagram from a connected client, while anything
+/// listens for it.
+fn packet_filter(events: Arc<EventBus>) -> PacketFilter {
+    Arc::new(move |address, data| {
+        if !events.has_handlers::<PacketReceive>() {
+            return true;
+        }
+        let event = events.post(PacketReceive {
+            address,
+            packet_id: data[0],
+            payload: data.to_vec(),
+            cancelled: false,
+        });
+        !event.cancelled
+    })
+}
+
+/// Brings the tick loop out of idle mode when a client starts connecting, before its
+/// connection is even accepted.
+fn session_hook(idle_waker: Arc<IdleWaker>) -> SessionHook {
+    Arc::new(move |_| idle_waker.wake())
+}
+
+/// Applies the runtime-changeable parts of each reloaded configuration to the services in
+/// `context`.
+async fn apply_config_changes(
+    mut changes: broadcast::Receiver<ConfigChanged>,
+    context: CommandContext,
+    events: Arc<EventBus>,
+) {
+    loop {
+        let change = match changes.recv().await {
+            Ok(change) => change,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        crash::set_config(&change.new);
+        if change.new.logging != change.old.logging {
+            apply_logging(&change.new.logging);
+        }
+        if change.new.packet_trace != change.old.packet_trace {
+            apply_packet_trace(&context.packet_trace, &change.new.packet_trace);
+        }
+        context.server_info.set_motd(motd(&change.new));
+        context.access.set_whitelist_enabled(change.new.server.whitelist);
+        context
+            .access
+            .set_whitelist_message(change.new.server.whitelist_message.clone());
+        context.chat.set_config(change.new.chat.clone());
+        context.timings.set_config(change.new.timings.clone());
+        context.afk.set_config(change.new.afk.clone());
+        if change.new.server.view_distance != change.old.server.view_distance {
+            apply_view_distance(&context.view_distances, change.new.server.view_distance);
+        }
+        events.post(ConfigReloaded);
+    }
 }
\ No newline at end of file
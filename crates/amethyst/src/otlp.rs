@@ -0,0 +1,322 @@
+//! Export of [`trace`](crate::trace) spans to an OpenTelemetry collector over OTLP/HTTP with
+//! the JSON encoding, configured by [`OtlpConfig`](crate::config::OtlpConfig).
+//!
+//! Only spans of this server are exported, and those of `rakethyst` when `packet_spans` is set.
+//! Finished spans are queued and sent in batches every few seconds; spans that finish while
+//! the queue is full, or in the last seconds before the process exits, are dropped.
+
+use crate::build_info;
+use crate::config::Config;
+use log::{info, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Registry;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// Spans sent in one request at most.
+const MAX_BATCH: usize = 512;
+/// Finished spans waiting to be sent before new ones are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+/// `SPAN_KIND_INTERNAL`: every span is work inside the server.
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+#[derive(Debug, Error)]
+pub enum OtlpError {
+    #[error("Invalid header '{0}'")]
+    Header(String),
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("The collector responded with {0}")]
+    Status(StatusCode),
+    #[error("Another tracing subscriber is already installed")]
+    SubscriberSet,
+}
+
+/// Starts exporting spans. Has to be called from within the runtime.
+pub fn init(config: &Config) -> Result<(), OtlpError> {
+    let otlp = &config.otlp;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &otlp.headers {
+        let header = HeaderName::try_from(name.as_str())
+            .ok()
+            .zip(HeaderValue::try_from(value.as_str()).ok())
+            .ok_or_else(|| OtlpError::Header(name.clone()))?;
+        headers.insert(header.0, header.1);
+    }
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .default_headers(headers)
+        .build()?;
+
+    let (queue, spans) = mpsc::channel(QUEUE_CAPACITY);
+    let layer = OtlpLayer {
+        queue,
+        sample_ratio: otlp.sample_ratio,
+        packet_spans: otlp.packet_spans,
+    };
+    tracing::subscriber::set_global_default(Registry::default().with(layer))
+        .map_err(|_| OtlpError::SubscriberSet)?;
+    let resource = json!({
+        "attributes": [
+            attribute("service.name", json!({ "stringValue": otlp.service_name })),
+            attribute("service.version", json!({ "stringValue": build_info::VERSION })),
+        ],
+    });
+    tokio::spawn(export(client, otlp.endpoint.clone(), resource, spans));
+    Ok(())
+}
+
+/// Sends queued spans every [`EXPORT_INTERVAL`], or as soon as a batch is full.
+async fn export(
+    client: Client,
+    endpoint: String,
+    resource: Value,
+    mut spans: mpsc::Receiver<Value>,
+) {
+    let mut batch = Vec::new();
+    let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+    // Only the first of a run of failures is a warning, so a collector that is down does not
+    // fill the log.
+    let mut failing = false;
+    loop {
+        tokio::select! {
+            span = spans.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    if batch.len() < MAX_BATCH {
+                        continue;
+                    }
+                }
+                None => return,
+            },
+            _ = interval.tick() => {
+                if batch.is_empty() {
+                    continue;
+                }
+            }
+        }
+        let request = json!({
+            "resourceSpans": [{
+                "resource": resource,
+                "scopeSpans": [{
+                    "scope": { "name": "amethyst", "version": build_info::VERSION },
+                    "spans": batch,
+                }],
+            }],
+        });
+        let count = batch.len();
+        batch = Vec::new();
+        match send(&client, &endpoint, &request).await {
+            Ok(()) if failing => {
+                failing = false;
+                info!("Exporting spans to {} again", endpoint);
+            }
+            Ok(()) => {}
+            Err(e) if !failing => {
+                failing = true;
+                warn!("Failed to export {} spans to {}: {}", count, endpoint, e);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+async fn send(client: &Client, endpoint: &str, request: &Value) -> Result<(), OtlpError> {
+    let response = client.post(endpoint).json(request).send().await?;
+    if !response.status().is_success() {
+        return Err(OtlpError::Status(response.status()));
+    }
+    Ok(())
+}
+
+/// Records spans and hands them to [`export`] as they close.
+struct OtlpLayer {
+    queue: mpsc::Sender<Value>,
+    sample_ratio: f64,
+    packet_spans: bool,
+}
+
+/// A span being recorded, kept in the span's extensions.
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    /// Decided at the root of the trace and inherited by every span in it.
+    sampled: bool,
+    start: u64,
+    attributes: Vec<Value>,
+    events: Vec<Value>,
+    error: bool,
+}
+
+impl OtlpLayer {
+    fn exported(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        target.starts_with("amethyst") || (self.packet_spans && target.starts_with("rakethyst"))
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if !self.exported(span.metadata()) {
+            return;
+        }
+        let parent = span.scope().skip(1).find_map(|ancestor| {
+            let extensions = ancestor.extensions();
+            let data = extensions.get::<SpanData>()?;
+            Some((data.trace_id, data.span_id, data.sampled))
+        });
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), sampled),
+            None => (
+                rand::random(),
+                None,
+                rand::random::<f64>() < self.sample_ratio,
+            ),
+        };
+        let mut visitor = AttributeVisitor::default();
+        attributes.record(&mut visitor);
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: rand::random(),
+            parent_span_id,
+            sampled,
+            start: unix_nanos(),
+            attributes: visitor.attributes,
+            events: Vec::new(),
+            error: false,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            let mut visitor = AttributeVisitor::default();
+            values.record(&mut visitor);
+            data.attributes.extend(visitor.attributes);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        let mut visitor = AttributeVisitor::default();
+        event.record(&mut visitor);
+        data.error |= *event.metadata().level() == Level::ERROR;
+        data.events.push(json!({
+            "timeUnixNano": unix_nanos().to_string(),
+            "name": visitor.message.unwrap_or_else(|| event.metadata().name().to_string()),
+            "attributes": visitor.attributes,
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        if !data.sampled {
+            return;
+        }
+        let mut exported = json!({
+            "traceId": hex::encode(data.trace_id),
+            "spanId": hex::encode(data.span_id),
+            "name": span.name(),
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": data.start.to_string(),
+            "endTimeUnixNano": unix_nanos().to_string(),
+            "attributes": data.attributes,
+            "events": data.events,
+        });
+        if let Some(parent) = data.parent_span_id {
+            exported["parentSpanId"] = json!(hex::encode(parent));
+        }
+        if data.error {
+            exported["status"] = json!({ "code": STATUS_CODE_ERROR });
+        }
+        let _ = self.queue.try_send(exported);
+    }
+}
+
+/// Collects fields as OTLP attributes, and the `message` of events separately.
+#[derive(Default)]
+struct AttributeVisitor {
+    attributes: Vec<Value>,
+    message: Option<String>,
+}
+
+impl AttributeVisitor {
+    fn add(&mut self, field: &Field, value: Value) {
+        self.attributes.push(attribute(field.name(), value));
+    }
+}
+
+impl Visit for AttributeVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+            return;
+        }
+        self.add(field, json!({ "stringValue": value }));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        // 64-bit integers are strings in the JSON encoding.
+        self.add(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.record_i64(field, value),
+            Err(_) => self.add(field, json!({ "stringValue": value.to_string() })),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.add(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.add(field, json!({ "boolValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
@@ -0,0 +1,151 @@
+//! `permissions.json`: permission groups and the players in them, see
+//! [`amethyst_plugin::permission`] for how nodes are matched.
+//!
+//! ```json
+//! {
+//!   "groups": {
+//!     "default": { "permissions": ["amethyst.command.help"] },
+//!     "moderator": { "inherits": ["default"], "permissions": ["amethyst.command.kick"] }
+//!   },
+//!   "players": [
+//!     { "name": "Steve", "groups": ["moderator"], "permissions": ["-amethyst.command.ban"] }
+//!   ]
+//! }
+//! ```
+
+use crate::access::{AccessError, PlayerEntry};
+use amethyst_plugin::permission::{
+    Group, PermissionConfig, Permissions, PlayerPermissions, DEFAULT_GROUP,
+};
+use log::{debug, error, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const PERMISSIONS_FILE_NAME: &str = "permissions.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PermissionsFile {
+    #[serde(default)]
+    groups: BTreeMap<String, GroupEntry>,
+    #[serde(default)]
+    players: Vec<PlayerPermissionsEntry>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GroupEntry {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inherits: Vec<String>,
+    #[serde(default)]
+    permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlayerPermissionsEntry {
+    #[serde(flatten)]
+    player: PlayerEntry,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    permissions: Vec<String>,
+}
+
+impl From<PermissionsFile> for PermissionConfig {
+    fn from(file: PermissionsFile) -> Self {
+        PermissionConfig {
+            groups: file
+                .groups
+                .into_iter()
+                .map(|(name, group)| {
+                    let group = Group {
+                        permissions: group.permissions,
+                        inherits: group.inherits,
+                    };
+                    (name, group)
+                })
+                .collect(),
+            players: file
+                .players
+                .into_iter()
+                .map(|entry| PlayerPermissions {
+                    name: entry.player.name,
+                    xuid: entry.player.xuid,
+                    groups: entry.groups,
+                    permissions: entry.permissions,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<PermissionsFile, AccessError> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|source| AccessError::Json {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Loads the file at `path` into `permissions`, creating it with an empty default group if it
+/// does not exist. On error the previous groups are kept.
+pub fn load(path: &Path, permissions: &Permissions) -> Result<(), AccessError> {
+    if !path.exists() {
+        let mut file = PermissionsFile::default();
+        file.groups
+            .insert(DEFAULT_GROUP.to_string(), GroupEntry::default());
+        let content = serde_json::to_string_pretty(&file).map_err(|source| AccessError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        fs::write(path, content)?;
+    }
+    let config = PermissionConfig::from(read(path)?);
+    for group in config.undefined_groups() {
+        warn!("Permission group '{}' is used but not defined", group);
+    }
+    permissions.set_config(config);
+    Ok(())
+}
+
+/// Parses the file in `dir` without creating it, returning its path and the number of groups
+/// and players, or `None` if it does not exist.
+pub fn check(dir: &Path) -> (PathBuf, Result<Option<(usize, usize)>, AccessError>) {
+    let path = dir.join(PERMISSIONS_FILE_NAME);
+    let result = if path.exists() {
+        read(&path).map(|file| Some((file.groups.len(), file.players.len())))
+    } else {
+        Ok(None)
+    };
+    (path, result)
+}
+
+/// Reloads the file in `dir` into `permissions` whenever it changes on disk. Watching stops
+/// when the returned watcher is dropped.
+pub fn watch(
+    permissions: &Arc<Permissions>,
+    dir: &Path,
+) -> Result<RecommendedWatcher, AccessError> {
+    let permissions = Arc::downgrade(permissions);
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let (Ok(event), Some(permissions)) = (res, permissions.upgrade()) else {
+            return;
+        };
+        if !(event.kind.is_create() || event.kind.is_modify()) {
+            return;
+        }
+        for path in &event.paths {
+            if path.file_name().and_then(|name| name.to_str()) != Some(PERMISSIONS_FILE_NAME) {
+                continue;
+            }
+            match load(path, &permissions) {
+                Ok(()) => debug!("Reloaded {}", path.display()),
+                Err(e) => error!("Failed to reload {}: {}", path.display(), e),
+            }
+        }
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
@@ -0,0 +1,127 @@
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use rakethyst::motd::ServerListEntry;
+use rakethyst::protocol::{UnconnectedPing, UnconnectedPong, UNCONNECTED_PING, UNCONNECTED_PONG};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout_at, Instant};
+
+const DEFAULT_PORT: u16 = 19132;
+
+/// Runs `amethyst ping`: sends an `UNCONNECTED_PING` to `target`, the way the server list
+/// does, and prints the server list entry it answers with and the round trip time.
+///
+/// Returns `false` if the server does not answer within `reply_timeout` or answers with
+/// something that is not a server list entry.
+pub async fn run(target: &str, reply_timeout: Duration) -> bool {
+    let address = match resolve(target).await {
+        Ok(address) => address,
+        Err(e) => {
+            eprintln!("Failed to resolve {}: {}", target, e);
+            return false;
+        }
+    };
+    let (pong, latency) = match ping(address, reply_timeout).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("No reply from {}: {}", address, e);
+            return false;
+        }
+    };
+    let entry = match ServerListEntry::parse(&pong.motd) {
+        Ok(entry) => entry,
+        Err(e) => {
+            eprintln!(
+                "{} answered with an unreadable MOTD ({}): {:?}",
+                address, e, pong.motd
+            );
+            return false;
+        }
+    };
+
+    println!("Reply from {} in {} ms", address, latency.as_millis());
+    println!("  name      {}", strip_format_codes(&entry.motd));
+    if let Some(world_name) = &entry.world_name {
+        println!("  world     {}", strip_format_codes(world_name));
+    }
+    println!(
+        "  version   {} (protocol {})",
+        entry.minecraft_version, entry.protocol_version
+    );
+    println!("  players   {}/{}", entry.player_count, entry.max_players);
+    if let Some(game_mode) = &entry.game_mode {
+        println!("  game mode {}", game_mode);
+    }
+    println!("  guid      {}", pong.server_guid);
+    true
+}
+
+/// Resolves `target`, defaulting to the Bedrock port when it has none.
+async fn resolve(target: &str) -> std::io::Result<SocketAddr> {
+    let mut addresses: Vec<SocketAddr> = match tokio::net::lookup_host(target).await {
+        Ok(addresses) => addresses.collect(),
+        Err(_) if !target.contains(':') => tokio::net::lookup_host((target, DEFAULT_PORT))
+            .await?
+            .collect(),
+        Err(e) => return Err(e),
+    };
+    if addresses.is_empty() {
+        return Err(std::io::Error::other("no addresses found"));
+    }
+    Ok(addresses.swap_remove(0))
+}
+
+async fn ping(
+    address: SocketAddr,
+    reply_timeout: Duration,
+) -> std::io::Result<(UnconnectedPong, Duration)> {
+    let bind_address = match address {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(bind_address).await?;
+    socket.connect(address).await?;
+
+    let start = Instant::now();
+    let deadline = start + reply_timeout;
+    let ping = UnconnectedPing {
+        time: 0,
+        client_guid: rand::random(),
+    };
+    let mut writer = BinaryWriter::new();
+    writer
+        .write_u8(UNCONNECTED_PING)
+        .and_then(|()| ping.write(&mut writer))
+        .map_err(std::io::Error::other)?;
+    socket.send(&writer.freeze()).await?;
+
+    let mut buf = [0; 2048];
+    loop {
+        let len = timeout_at(deadline, socket.recv(&mut buf))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        if len == 0 || buf[0] != UNCONNECTED_PONG {
+            continue;
+        }
+        let latency = start.elapsed();
+        let mut reader = BinaryReader::new(Bytes::copy_from_slice(&buf[1..len]));
+        let pong = UnconnectedPong::read(&mut reader).map_err(std::io::Error::other)?;
+        return Ok((pong, latency));
+    }
+}
+
+/// Removes `§` color and formatting codes, which a terminal would print as is.
+fn strip_format_codes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
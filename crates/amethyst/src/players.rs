@@ -0,0 +1,182 @@
+//! `players.json`: the UUID, XUID and names of every player that has joined, see
+//! [`amethyst_plugin::player`] for how accounts are matched.
+//!
+//! ```json
+//! [
+//!   {
+//!     "uuid": "8d1f2e3a-...",
+//!     "xuid": "2535412345678901",
+//!     "name": "Steve",
+//!     "past_names": [{ "name": "Steve2010", "until": "2026-03-01T18:04:11Z" }],
+//!     "first_seen": "2025-11-20T09:12:45Z",
+//!     "last_seen": "2026-10-17T20:31:02Z"
+//!   }
+//! ]
+//! ```
+//!
+//! The server rewrites the file whenever a player joins, so edits made while it runs are lost.
+
+use crate::access::{AccessError, PlayerEntry};
+use amethyst_plugin::event::{EventPriority, PlayerJoin};
+use amethyst_plugin::player::{PastName, PlayerDirectory, PlayerProfile, Recorded};
+use amethyst_plugin::EventBus;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const PLAYERS_FILE_NAME: &str = "players.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProfileEntry {
+    uuid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    xuid: Option<String>,
+    name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    past_names: Vec<PastNameEntry>,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PastNameEntry {
+    name: String,
+    until: DateTime<Utc>,
+}
+
+impl From<ProfileEntry> for PlayerProfile {
+    fn from(entry: ProfileEntry) -> Self {
+        PlayerProfile {
+            uuid: entry.uuid,
+            xuid: entry.xuid,
+            name: entry.name,
+            past_names: entry
+                .past_names
+                .into_iter()
+                .map(|past| PastName {
+                    name: past.name,
+                    until: unix_seconds(past.until),
+                })
+                .collect(),
+            first_seen: unix_seconds(entry.first_seen),
+            last_seen: unix_seconds(entry.last_seen),
+        }
+    }
+}
+
+impl From<PlayerProfile> for ProfileEntry {
+    fn from(profile: PlayerProfile) -> Self {
+        ProfileEntry {
+            uuid: profile.uuid,
+            xuid: profile.xuid,
+            name: profile.name,
+            past_names: profile
+                .past_names
+                .into_iter()
+                .map(|past| PastNameEntry {
+                    name: past.name,
+                    until: date_time(past.until),
+                })
+                .collect(),
+            first_seen: date_time(profile.first_seen),
+            last_seen: date_time(profile.last_seen),
+        }
+    }
+}
+
+fn unix_seconds(time: DateTime<Utc>) -> u64 {
+    u64::try_from(time.timestamp()).unwrap_or(0)
+}
+
+/// Formats a time stored in seconds since the Unix epoch.
+pub fn date_time(seconds: u64) -> DateTime<Utc> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> Result<Vec<ProfileEntry>, AccessError> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|source| AccessError::Json {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Loads the file at `path` into `directory`, creating an empty one if it does not exist.
+pub fn load(path: &Path, directory: &PlayerDirectory) -> Result<(), AccessError> {
+    if !path.exists() {
+        save(path, directory)?;
+    }
+    let profiles = read(path)?.into_iter().map(PlayerProfile::from).collect();
+    directory.set_profiles(profiles);
+    Ok(())
+}
+
+pub fn save(path: &Path, directory: &PlayerDirectory) -> Result<(), AccessError> {
+    let entries: Vec<ProfileEntry> = directory
+        .profiles()
+        .into_iter()
+        .map(ProfileEntry::from)
+        .collect();
+    let content = serde_json::to_string_pretty(&entries).map_err(|source| AccessError::Json {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    // Renamed over the old file so a crash mid-write cannot lose every profile.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Parses the file in `dir` without creating it, returning its path and the number of
+/// players, or `None` if it does not exist.
+pub fn check(dir: &Path) -> (PathBuf, Result<Option<usize>, AccessError>) {
+    let path = dir.join(PLAYERS_FILE_NAME);
+    let result = if path.exists() {
+        read(&path).map(|entries| Some(entries.len()))
+    } else {
+        Ok(None)
+    };
+    (path, result)
+}
+
+/// Records every player that joins, once the join went through, and saves the file at
+/// `path`.
+pub fn register_events(directory: &Arc<PlayerDirectory>, path: PathBuf, events: &EventBus) {
+    let directory = Arc::clone(directory);
+    events.subscribe(EventPriority::Monitor, move |join: &mut PlayerJoin| {
+        if join.cancelled {
+            return;
+        }
+        let now = unix_seconds(Utc::now());
+        let recorded = directory.record(&join.uuid, join.xuid.as_deref(), &join.name, now);
+        if let Recorded::Renamed(previous) = recorded {
+            info!("{} joined, previously known as {}", join.name, previous);
+        }
+        if let Err(e) = save(&path, &directory) {
+            error!("Failed to save {}: {}", path.display(), e);
+        }
+    });
+}
+
+/// The access list entry for a player named on the command line or in an API request, with
+/// the XUID and current name of the account if it has joined before, so the entry still
+/// matches after a name change.
+pub fn resolve_entry(directory: &PlayerDirectory, name: &str) -> PlayerEntry {
+    match directory.by_name(name) {
+        Some(profile) if profile.xuid.is_some() => PlayerEntry {
+            name: profile.name,
+            xuid: profile.xuid,
+        },
+        _ => PlayerEntry {
+            name: name.to_string(),
+            xuid: None,
+        },
+    }
+}
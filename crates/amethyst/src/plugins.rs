@@ -0,0 +1,226 @@
+use crate::commands::{CommandError, CommandRegistry, CommandSender, CommandSpec};
+use amethyst_plugin::{
+    CommandInput, EventBus, Permissions, PlayerDirectory, Plugin, PluginCommand, PluginContext,
+    PluginDeclaration, PluginError, Scheduler, API_VERSION, DECLARATION_SYMBOL, RUSTC_VERSION,
+};
+use libloading::Library;
+use log::{error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Directory plugins are loaded from, relative to the working directory.
+pub const PLUGINS_DIR: &str = "plugins";
+
+#[derive(Debug, Error)]
+pub enum PluginLoadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to load library: {0}")]
+    Library(#[from] libloading::Error),
+    #[error("Plugin API version {found} is not supported, the server uses version {expected}")]
+    ApiVersion { found: u32, expected: u32 },
+    #[error("Built with '{found}', but the server was built with '{expected}'")]
+    Compiler { found: String, expected: String },
+    #[error("A plugin named '{0}' is already loaded")]
+    Duplicate(String),
+    #[error("Failed to enable: {0}")]
+    Enable(#[from] PluginError),
+}
+
+struct LoadedPlugin {
+    name: String,
+    plugin: Mutex<Box<dyn Plugin>>,
+}
+
+/// Native plugins loaded from [`PLUGINS_DIR`] at startup.
+///
+/// Libraries stay loaded until the process exits: commands registered by a plugin run its
+/// code for as long as the command registry lives, and unloading Rust libraries is unsound
+/// if they left thread-local destructors behind.
+pub struct PluginManager {
+    plugins: Vec<LoadedPlugin>,
+    events: Arc<EventBus>,
+    scheduler: Arc<Scheduler>,
+    permissions: Arc<Permissions>,
+    players: Arc<PlayerDirectory>,
+}
+
+impl PluginManager {
+    /// Loads and enables every plugin library in `dir`, registering their commands in
+    /// `commands`, their handlers on `events`, their tasks on `scheduler` and their permission
+    /// nodes in `permissions`. Plugins can look players up in `players`. Plugins that fail to
+    /// load are logged and skipped.
+    pub fn load(
+        dir: &Path,
+        commands: &mut CommandRegistry,
+        events: Arc<EventBus>,
+        scheduler: Arc<Scheduler>,
+        permissions: Arc<Permissions>,
+        players: Arc<PlayerDirectory>,
+    ) -> Self {
+        let mut manager = Self {
+            plugins: Vec::new(),
+            events,
+            scheduler,
+            permissions,
+            players,
+        };
+        let paths = match plugin_files(dir) {
+            Ok(paths) => paths,
+            Err(e) => {
+                error!("Failed to read plugin directory {}: {}", dir.display(), e);
+                return manager;
+            }
+        };
+        for path in paths {
+            match manager.load_plugin(&path, commands) {
+                Ok(plugin) => {
+                    info!("Enabled plugin {} from {}", plugin, path.display());
+                }
+                Err(e) => error!("Failed to load plugin {}: {}", path.display(), e),
+            }
+        }
+        manager
+    }
+
+    fn load_plugin(
+        &mut self,
+        path: &Path,
+        commands: &mut CommandRegistry,
+    ) -> Result<String, PluginLoadError> {
+        // SAFETY: loading a library runs its initializers. Plugins are trusted code placed in
+        // the plugin directory by the operator.
+        let library = unsafe { Library::new(path)? };
+        // SAFETY: the symbol is exported by `declare_plugin!` with this type. The declaration
+        // is `repr(C)` with the versions first, so they can be checked before anything else is
+        // relied on.
+        let declaration: &PluginDeclaration = unsafe {
+            let symbol = library.get::<*const PluginDeclaration>(DECLARATION_SYMBOL)?;
+            &**symbol
+        };
+        if declaration.api_version != API_VERSION {
+            return Err(PluginLoadError::ApiVersion {
+                found: declaration.api_version,
+                expected: API_VERSION,
+            });
+        }
+        if declaration.rustc_version != RUSTC_VERSION {
+            return Err(PluginLoadError::Compiler {
+                found: declaration.rustc_version.to_string(),
+                expected: RUSTC_VERSION.to_string(),
+            });
+        }
+
+        let mut plugin = (declaration.create)(log::logger(), log::max_level());
+        // The plugin's code is referenced from here on, see the type documentation.
+        std::mem::forget(library);
+        let name = plugin.name().to_string();
+        if self.plugins.iter().any(|loaded| loaded.name == name) {
+            return Err(PluginLoadError::Duplicate(name));
+        }
+
+        let mut context = PluginContext::new(
+            &name,
+            Arc::clone(&self.events),
+            Arc::clone(&self.scheduler),
+            Arc::clone(&self.permissions),
+            Arc::clone(&self.players),
+        );
+        if let Err(e) = plugin.on_enable(&mut context) {
+            self.events.unsubscribe_owner(&name);
+            self.scheduler.cancel_owner(&name);
+            self.permissions.unregister_owner(&name);
+            return Err(e.into());
+        }
+        for command in context.into_commands() {
+            let command_name = command.name.clone();
+            if !commands.register(command_spec(&name, command)) {
+                warn!(
+                    "Plugin {} tried to register command '{}', which already exists",
+                    name, command_name
+                );
+            }
+        }
+
+        let description = format!("{} {}", name, plugin.version());
+        self.plugins.push(LoadedPlugin {
+            name,
+            plugin: Mutex::new(plugin),
+        });
+        Ok(description)
+    }
+
+    /// Disables plugins in the reverse order they were enabled.
+    pub fn disable_all(&self) {
+        for loaded in self.plugins.iter().rev() {
+            loaded
+                .plugin
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .on_disable();
+            self.events.unsubscribe_owner(&loaded.name);
+            self.scheduler.cancel_owner(&loaded.name);
+            self.permissions.unregister_owner(&loaded.name);
+            info!("Disabled plugin {}", loaded.name);
+        }
+    }
+}
+
+/// Libraries in `dir` with the platform's extension, in a stable order. A missing directory
+/// has no plugins.
+fn plugin_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path.extension().and_then(|e| e.to_str()) == Some(std::env::consts::DLL_EXTENSION)
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Adapts a command of plugin `owner` to the server's registry. Names are leaked because the
+/// registry keeps them for the lifetime of the process, like the plugin library itself.
+fn command_spec(owner: &str, command: PluginCommand) -> CommandSpec {
+    let handler = command.handler;
+    let node = command
+        .node
+        .unwrap_or_else(|| format!("{}.command.{}", owner, command.name));
+    CommandSpec {
+        name: leak(command.name),
+        aliases: Vec::leak(command.aliases.into_iter().map(leak).collect()),
+        usage: leak(command.usage),
+        description: leak(command.description),
+        permission: command.permission,
+        node: leak(node),
+        handler: Box::new(move |invocation, args| {
+            let (player, xuid) = match invocation.sender {
+                CommandSender::Player { xuid, .. } => (true, xuid.clone()),
+                _ => (false, None),
+            };
+            let input = CommandInput {
+                sender: invocation.sender.name().to_string(),
+                player,
+                xuid,
+                permission_level: invocation
+                    .sender
+                    .permission_level(&invocation.context.access),
+                args: args.remaining(),
+                permissions: Arc::clone(&invocation.context.permissions),
+            };
+            handler(&input).map_err(CommandError::Failed)
+        }),
+    }
+}
+
+pub(crate) fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
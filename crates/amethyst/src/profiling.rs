@@ -0,0 +1,74 @@
+//! CPU profiling of a running server, for finding hotspots in production. Only built with
+//! the `profiling` feature, and only on Unix, where pprof samples with `SIGPROF`.
+
+use crate::admin::error_response;
+use axum::extract::Query;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::info;
+use pprof::ProfilerGuardBuilder;
+use serde::Deserialize;
+use std::thread;
+use std::time::Duration;
+
+/// Samples per second. Deliberately not a divisor of the tick rate, so samples do not keep
+/// landing on the same point of the tick.
+const FREQUENCY: i32 = 99;
+
+/// Longest profile the admin API captures, so a typo cannot keep the profiler running for
+/// hours.
+const MAX_SECONDS: u64 = 60;
+
+/// Samples every thread of the process for `duration`, blocking the calling thread, and
+/// renders the result as an SVG flamegraph. Returns `None` if nothing was sampled, because
+/// the server used no CPU time.
+///
+/// Only one profile can be captured at a time; a second fails with
+/// [`pprof::Error::Running`].
+pub fn flamegraph(duration: Duration) -> Result<Option<Vec<u8>>, pprof::Error> {
+    let guard = ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    thread::sleep(duration);
+    let report = guard.report().build()?;
+    if report.data.is_empty() {
+        return Ok(None);
+    }
+    let mut svg = Vec::new();
+    report.flamegraph(&mut svg)?;
+    Ok(Some(svg))
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    #[serde(default = "default_seconds")]
+    seconds: u64,
+}
+
+fn default_seconds() -> u64 {
+    10
+}
+
+/// `GET /profile` on the admin API. Profiles the server for `?seconds=` (10 by default) and
+/// responds with the flamegraph once done, or with no content if the server was idle.
+pub async fn profile(Query(query): Query<ProfileQuery>) -> Response {
+    if !(1..=MAX_SECONDS).contains(&query.seconds) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("seconds must be between 1 and {}", MAX_SECONDS),
+        );
+    }
+    info!("Admin API started a {}s CPU profile", query.seconds);
+    let duration = Duration::from_secs(query.seconds);
+    match tokio::task::spawn_blocking(move || flamegraph(duration)).await {
+        Ok(Ok(Some(svg))) => ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response(),
+        Ok(Ok(None)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(pprof::Error::Running)) => error_response(
+            StatusCode::CONFLICT,
+            "Another profile is already being captured",
+        ),
+        Ok(Err(e)) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
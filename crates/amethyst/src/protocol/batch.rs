@@ -0,0 +1,176 @@
+//! Batches of game packets, the payload of every [`GAME_PACKET`] frame.
+//!
+//! A batch is the `0xfe` ID followed by each packet with a varint length in front. Once
+//! [`NetworkSettings`] has been sent, a [`Compression`] marker follows the ID, and the packets
+//! after it are deflated if the batch is at least the advertised threshold.
+
+use super::NetworkSettings;
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use bytes::Bytes;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use rakethyst::protocol::GAME_PACKET;
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+/// Compressing batches smaller than this costs more CPU than the bytes it saves.
+pub const DEFAULT_COMPRESSION_THRESHOLD: u16 = 256;
+
+/// Largest batch accepted once decompressed, so a few kilobytes of deflated zeros cannot make
+/// the server allocate gigabytes.
+pub const MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("Not a game packet batch")]
+    NotABatch,
+    #[error("Unsupported compression {0:#04x}")]
+    UnsupportedCompression(u8),
+    #[error("Batch is larger than {MAX_DECOMPRESSED_SIZE} bytes once decompressed")]
+    TooLarge,
+    #[error("Failed to decompress the batch: {0}")]
+    Decompress(io::Error),
+    #[error(transparent)]
+    Binary(#[from] BinaryError),
+}
+
+/// Marker after the ID of a batch, saying how the packets after it are compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Flate,
+    Snappy,
+    /// Sent for batches below the threshold.
+    None,
+}
+
+impl Compression {
+    /// The marker byte.
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::Flate => 0x00,
+            Compression::Snappy => 0x01,
+            Compression::None => 0xff,
+        }
+    }
+
+    /// The algorithm as [`NetworkSettings::compression_algorithm`] names it.
+    pub fn algorithm(self) -> u16 {
+        match self {
+            Compression::None => 0xffff,
+            _ => self.id().into(),
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x00 => Some(Compression::Flate),
+            0x01 => Some(Compression::Snappy),
+            0xff => Some(Compression::None),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes and decodes the batches of one connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchCodec {
+    /// See [`NetworkSettings::compression_threshold`].
+    threshold: u16,
+    /// Whether [`NetworkSettings`] has been sent, so batches carry a compression marker.
+    negotiated: bool,
+}
+
+impl BatchCodec {
+    /// A codec for a new connection, which sends plain batches until
+    /// [`negotiated`](Self::negotiated) is called.
+    pub fn new(threshold: u16) -> Self {
+        Self {
+            threshold,
+            negotiated: false,
+        }
+    }
+
+    /// The settings to send the client, so it uses the same threshold as this codec.
+    pub fn network_settings(&self) -> NetworkSettings {
+        NetworkSettings {
+            compression_threshold: self.threshold,
+            compression_algorithm: Compression::Flate.algorithm(),
+            client_throttle: false,
+            client_throttle_threshold: 0,
+            client_throttle_scalar: 0.0,
+        }
+    }
+
+    /// Switches to compression markers, once [`NetworkSettings`] has been sent.
+    pub fn negotiated(&mut self) {
+        self.negotiated = true;
+    }
+
+    /// Wraps encoded packets in a batch, compressing it if it reaches the threshold.
+    pub fn encode(&self, packets: &[Bytes]) -> Result<Bytes, BatchError> {
+        let mut payload = BinaryWriter::new();
+        for packet in packets {
+            payload.write_var_u32(packet.len() as u32)?;
+            payload.write_bytes(packet)?;
+        }
+        let payload = payload.freeze();
+
+        let mut batch = BinaryWriter::new();
+        batch.write_u8(GAME_PACKET)?;
+        if !self.negotiated {
+            batch.write_bytes(&payload)?;
+            return Ok(batch.freeze());
+        }
+        if self.threshold == 0 || payload.len() < usize::from(self.threshold) {
+            batch.write_u8(Compression::None.id())?;
+            batch.write_bytes(&payload)?;
+            return Ok(batch.freeze());
+        }
+        batch.write_u8(Compression::Flate.id())?;
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        // Writing to a Vec cannot fail.
+        let deflated = encoder
+            .write_all(&payload)
+            .and_then(|()| encoder.finish())
+            .expect("deflating into memory");
+        batch.write_bytes(&deflated)?;
+        Ok(batch.freeze())
+    }
+
+    /// Splits a batch, starting with its ID, into its packets.
+    pub fn decode(&self, batch: &[u8]) -> Result<Vec<Bytes>, BatchError> {
+        let [GAME_PACKET, rest @ ..] = batch else {
+            return Err(BatchError::NotABatch);
+        };
+        let payload = match rest.split_first() {
+            Some((&marker, data)) if self.negotiated => match Compression::from_id(marker) {
+                Some(Compression::None) => Bytes::copy_from_slice(data),
+                Some(Compression::Flate) => inflate(data)?,
+                _ => return Err(BatchError::UnsupportedCompression(marker)),
+            },
+            _ => Bytes::copy_from_slice(rest),
+        };
+
+        let mut reader = BinaryReader::new(payload);
+        let mut packets = Vec::new();
+        while reader.remaining() > 0 {
+            let len = reader.read_var_u32()? as usize;
+            reader.ensure_remaining(len)?;
+            packets.push(reader.read_bytes(len)?);
+        }
+        Ok(packets)
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Bytes, BatchError> {
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(data)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut inflated)
+        .map_err(BatchError::Decompress)?;
+    if inflated.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(BatchError::TooLarge);
+    }
+    Ok(Bytes::from(inflated))
+}
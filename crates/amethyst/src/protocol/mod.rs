@@ -0,0 +1,395 @@
+//! Minecraft: Bedrock Edition game packets, which travel inside RakNet frames.
+
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+
+pub mod batch;
+pub mod version;
+
+/// Port Bedrock servers listen on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 19132;
+
+pub const RESOURCE_PACKS_INFO: u32 = 0x06;
+pub const UPDATE_BLOCK: u32 = 0x15;
+pub const REQUEST_CHUNK_RADIUS: u32 = 0x45;
+pub const CHUNK_RADIUS_UPDATED: u32 = 0x46;
+pub const TRANSFER: u32 = 0x55;
+pub const NETWORK_SETTINGS: u32 = 0x8f;
+pub const REQUEST_NETWORK_SETTINGS: u32 = 0xc1;
+
+/// A packet of the game protocol, identified by its packet id.
+pub trait GamePacket: Writable {
+    const ID: u32;
+}
+
+/// Encodes `packet` with its header, in the layout of the latest version; see
+/// [`version::encode_for`] for the others. Sender and target sub-client ids, packed into the
+/// header next to the packet id, are always 0 for the main client.
+pub fn encode<P: GamePacket>(packet: &P) -> Result<Bytes, BinaryError> {
+    let mut writer = BinaryWriter::new();
+    writer.write_var_u32(P::ID)?;
+    packet.write(&mut writer)?;
+    Ok(writer.freeze())
+}
+
+/// Tells the client to disconnect and join the server at `address` and `port`, which may be a
+/// host name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub address: String,
+    pub port: u16,
+    /// Whether the client reloads the world when the target is the server it is already on.
+    pub reload_world: bool,
+}
+
+impl GamePacket for Transfer {
+    const ID: u32 = TRANSFER;
+}
+
+impl Writable for Transfer {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_string(&self.address)?;
+        writer.write_u16_le(self.port)?;
+        writer.write_bool(self.reload_world)?;
+        Ok(())
+    }
+}
+
+impl Readable for Transfer {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        let address = reader.read_string()?;
+        let port = reader.read_u16_le()?;
+        let reload_world = reader.read_bool()?;
+        Ok(Self {
+            address,
+            port,
+            reload_world,
+        })
+    }
+}
+
+/// Lists the resource packs a client needs before it can join. Packs with a `cdn_url` are
+/// downloaded from it over HTTP instead of in chunks through RakNet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourcePacksInfo {
+    /// Whether the client has to accept the packs to join.
+    pub must_accept: bool,
+    pub has_addons: bool,
+    pub has_scripts: bool,
+    pub world_template_uuid: [u8; 16],
+    pub world_template_version: String,
+    pub resource_packs: Vec<ResourcePackInfoEntry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourcePackInfoEntry {
+    pub uuid: [u8; 16],
+    pub version: String,
+    /// Size of the archive in bytes.
+    pub size: u64,
+    /// Key the pack is encrypted with, empty if it is not.
+    pub content_key: String,
+    pub sub_pack_name: String,
+    pub content_identity: String,
+    pub has_scripts: bool,
+    pub addon_pack: bool,
+    pub raytracing_capable: bool,
+    /// URL to download the archive from, empty to download it through RakNet.
+    pub cdn_url: String,
+}
+
+impl GamePacket for ResourcePacksInfo {
+    const ID: u32 = RESOURCE_PACKS_INFO;
+}
+
+impl Writable for ResourcePacksInfo {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_bool(self.must_accept)?;
+        writer.write_bool(self.has_addons)?;
+        writer.write_bool(self.has_scripts)?;
+        write_uuid(writer, &self.world_template_uuid)?;
+        writer.write_string(&self.world_template_version)?;
+        let count = u16::try_from(self.resource_packs.len())
+            .map_err(|_| BinaryError::InvalidData("Too many resource packs".to_string()))?;
+        writer.write_u16_le(count)?;
+        for pack in &self.resource_packs {
+            pack.write(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Readable for ResourcePacksInfo {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        let must_accept = reader.read_bool()?;
+        let has_addons = reader.read_bool()?;
+        let has_scripts = reader.read_bool()?;
+        let world_template_uuid = read_uuid(reader)?;
+        let world_template_version = reader.read_string()?;
+        let count = reader.read_u16_le()?;
+        let resource_packs = (0..count)
+            .map(|_| ResourcePackInfoEntry::read(reader))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            must_accept,
+            has_addons,
+            has_scripts,
+            world_template_uuid,
+            world_template_version,
+            resource_packs,
+        })
+    }
+}
+
+impl Writable for ResourcePackInfoEntry {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        write_uuid(writer, &self.uuid)?;
+        writer.write_string(&self.version)?;
+        writer.write_u64_le(self.size)?;
+        writer.write_string(&self.content_key)?;
+        writer.write_string(&self.sub_pack_name)?;
+        writer.write_string(&self.content_identity)?;
+        writer.write_bool(self.has_scripts)?;
+        writer.write_bool(self.addon_pack)?;
+        writer.write_bool(self.raytracing_capable)?;
+        writer.write_string(&self.cdn_url)?;
+        Ok(())
+    }
+}
+
+impl Readable for ResourcePackInfoEntry {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(Self {
+            uuid: read_uuid(reader)?,
+            version: reader.read_string()?,
+            size: reader.read_u64_le()?,
+            content_key: reader.read_string()?,
+            sub_pack_name: reader.read_string()?,
+            content_identity: reader.read_string()?,
+            has_scripts: reader.read_bool()?,
+            addon_pack: reader.read_bool()?,
+            raytracing_capable: reader.read_bool()?,
+            cdn_url: reader.read_string()?,
+        })
+    }
+}
+
+/// Sent by the client to ask for a view distance, when it joins and whenever its video
+/// settings change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestChunkRadius {
+    /// Radius in chunks the client would like.
+    pub radius: i32,
+    /// The largest radius the client's settings allow.
+    pub max_radius: u8,
+}
+
+impl GamePacket for RequestChunkRadius {
+    const ID: u32 = REQUEST_CHUNK_RADIUS;
+}
+
+impl Writable for RequestChunkRadius {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_var_i32(self.radius)?;
+        writer.write_u8(self.max_radius)?;
+        Ok(())
+    }
+}
+
+impl Readable for RequestChunkRadius {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        let radius = reader.read_var_i32()?;
+        let max_radius = reader.read_u8()?;
+        Ok(Self { radius, max_radius })
+    }
+}
+
+/// Tells the client the view distance it got, in answer to [`RequestChunkRadius`] or when the
+/// server lowers it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkRadiusUpdated {
+    pub radius: i32,
+}
+
+impl GamePacket for ChunkRadiusUpdated {
+    const ID: u32 = CHUNK_RADIUS_UPDATED;
+}
+
+impl Writable for ChunkRadiusUpdated {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_var_i32(self.radius)
+    }
+}
+
+impl Readable for ChunkRadiusUpdated {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(Self {
+            radius: reader.read_var_i32()?,
+        })
+    }
+}
+
+/// Sets the block at `position` on the client, e.g. to undo a change the server refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateBlock {
+    pub position: (i32, i32, i32),
+    pub block_runtime_id: u32,
+    /// `FLAG_*` bits.
+    pub flags: u32,
+    /// 0 for the block itself, 1 for the liquid layer, e.g. water in a waterlogged block.
+    pub layer: u32,
+}
+
+impl UpdateBlock {
+    pub const FLAG_NEIGHBORS: u32 = 0x1;
+    pub const FLAG_NETWORK: u32 = 0x2;
+    pub const FLAG_NO_GRAPHIC: u32 = 0x4;
+    pub const FLAG_PRIORITY: u32 = 0x8;
+
+    /// Resends the block the server has at `position`, after refusing to break or replace it.
+    pub fn resync(position: (i32, i32, i32), block_runtime_id: u32) -> Self {
+        Self {
+            position,
+            block_runtime_id,
+            flags: Self::FLAG_NETWORK,
+            layer: 0,
+        }
+    }
+}
+
+impl GamePacket for UpdateBlock {
+    const ID: u32 = UPDATE_BLOCK;
+}
+
+impl Writable for UpdateBlock {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        let (x, y, z) = self.position;
+        // Y is unsigned on the wire, so blocks below 0 wrap around.
+        writer.write_var_i32(x)?;
+        writer.write_var_u32(y as u32)?;
+        writer.write_var_i32(z)?;
+        writer.write_var_u32(self.block_runtime_id)?;
+        writer.write_var_u32(self.flags)?;
+        writer.write_var_u32(self.layer)?;
+        Ok(())
+    }
+}
+
+impl Readable for UpdateBlock {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        let x = reader.read_var_i32()?;
+        let y = reader.read_var_u32()? as i32;
+        let z = reader.read_var_i32()?;
+        Ok(Self {
+            position: (x, y, z),
+            block_runtime_id: reader.read_var_u32()?,
+            flags: reader.read_var_u32()?,
+            layer: reader.read_var_u32()?,
+        })
+    }
+}
+
+/// Parses a UUID written as 32 hex digits, optionally split by dashes.
+pub fn parse_uuid(value: &str) -> Option<[u8; 16]> {
+    let digits: String = value.chars().filter(|&c| c != '-').collect();
+    let mut uuid = [0; 16];
+    hex::decode_to_slice(&digits, &mut uuid).ok()?;
+    Some(uuid)
+}
+
+/// The first game packet a client sends, before compression is set up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestNetworkSettings {
+    pub protocol_version: u32,
+}
+
+impl GamePacket for RequestNetworkSettings {
+    const ID: u32 = REQUEST_NETWORK_SETTINGS;
+}
+
+impl Writable for RequestNetworkSettings {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_u32(self.protocol_version)
+    }
+}
+
+impl Readable for RequestNetworkSettings {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(Self {
+            protocol_version: reader.read_u32()?,
+        })
+    }
+}
+
+/// The answer to [`RequestNetworkSettings`]. Every batch after it starts with a
+/// [`batch::Compression`] marker, and both sides compress batches of at least
+/// `compression_threshold` bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkSettings {
+    /// 0 disables compression.
+    pub compression_threshold: u16,
+    pub compression_algorithm: u16,
+    pub client_throttle: bool,
+    pub client_throttle_threshold: u8,
+    pub client_throttle_scalar: f32,
+}
+
+impl GamePacket for NetworkSettings {
+    const ID: u32 = NETWORK_SETTINGS;
+}
+
+impl Writable for NetworkSettings {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_u16_le(self.compression_threshold)?;
+        writer.write_u16_le(self.compression_algorithm)?;
+        writer.write_bool(self.client_throttle)?;
+        writer.write_u8(self.client_throttle_threshold)?;
+        writer.write_f32_le(self.client_throttle_scalar)?;
+        Ok(())
+    }
+}
+
+impl Readable for NetworkSettings {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(Self {
+            compression_threshold: reader.read_u16_le()?,
+            compression_algorithm: reader.read_u16_le()?,
+            client_throttle: reader.read_bool()?,
+            client_throttle_threshold: reader.read_u8()?,
+            client_throttle_scalar: reader.read_f32_le()?,
+        })
+    }
+}
+
+/// Formats a UUID the way it was written in packets before 1.21.40, e.g.
+/// `0fba4063-dba1-4281-9b89-ff9390653530`.
+pub fn format_uuid(uuid: &[u8; 16]) -> String {
+    let digits = hex::encode(uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &digits[..8],
+        &digits[8..12],
+        &digits[12..16],
+        &digits[16..20],
+        &digits[20..]
+    )
+}
+
+/// UUIDs are sent as their two 64-bit halves, each little-endian.
+fn write_uuid(writer: &mut BinaryWriter, uuid: &[u8; 16]) -> Result<(), BinaryError> {
+    let (most, least) = uuid.split_at(8);
+    writer.write_u64_le(u64::from_be_bytes(most.try_into().unwrap()))?;
+    writer.write_u64_le(u64::from_be_bytes(least.try_into().unwrap()))?;
+    Ok(())
+}
+
+fn read_uuid(reader: &mut BinaryReader) -> Result<[u8; 16], BinaryError> {
+    let most = reader.read_u64_le()?;
+    let least = reader.read_u64_le()?;
+    let mut uuid = [0; 16];
+    uuid[..8].copy_from_slice(&most.to_be_bytes());
+    uuid[8..].copy_from_slice(&least.to_be_bytes());
+    Ok(uuid)
+}
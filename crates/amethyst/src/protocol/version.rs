@@ -0,0 +1,221 @@
+//! The game protocol versions one build accepts, and how packets differ between them.
+//!
+//! A client's version is resolved once at login, and every packet to and from it goes through
+//! the [`VersionedPacket`] codec for that version. Packets are defined in the layout of
+//! [`ProtocolVersion::LATEST`]; one that changed within the window overrides
+//! [`VersionedPacket::write_for`] and [`VersionedPacket::read_for`] to translate older layouts.
+
+use super::{
+    format_uuid, write_uuid, GamePacket, ResourcePackInfoEntry, ResourcePacksInfo, Transfer,
+};
+use super::{ChunkRadiusUpdated, RequestChunkRadius, UpdateBlock};
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use std::fmt;
+use thiserror::Error;
+
+/// Protocol numbers where packets in this crate changed layout.
+pub const V1_21_30: u32 = 729;
+pub const V1_21_40: u32 = 748;
+pub const V1_21_50: u32 = 766;
+
+/// Every protocol version clients may join with, oldest first: 1.20.70 to 1.21.50.
+const SUPPORTED: [(u32, &str); 8] = [
+    (662, "1.20.70"),
+    (671, "1.20.80"),
+    (685, "1.21.0"),
+    (686, "1.21.2"),
+    (712, "1.21.20"),
+    (V1_21_30, "1.21.30"),
+    (V1_21_40, "1.21.40"),
+    (V1_21_50, "1.21.50"),
+];
+
+pub const OLDEST_PROTOCOL: u32 = SUPPORTED[0].0;
+pub const NEWEST_PROTOCOL: u32 = SUPPORTED[SUPPORTED.len() - 1].0;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UnsupportedVersion {
+    #[error("Outdated client: protocol {0} is older than {OLDEST_PROTOCOL}")]
+    Outdated(u32),
+    #[error("Outdated server: protocol {0} is newer than {NEWEST_PROTOCOL}")]
+    TooNew(u32),
+    #[error("Unknown protocol {0}")]
+    Unknown(u32),
+}
+
+/// A supported protocol version, as sent by the client in its login request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion(u32);
+
+impl ProtocolVersion {
+    pub const OLDEST: Self = Self(OLDEST_PROTOCOL);
+    pub const LATEST: Self = Self(NEWEST_PROTOCOL);
+
+    /// Resolves the protocol number a client logs in with.
+    pub fn resolve(protocol: u32) -> Result<Self, UnsupportedVersion> {
+        if protocol < OLDEST_PROTOCOL {
+            return Err(UnsupportedVersion::Outdated(protocol));
+        }
+        if protocol > NEWEST_PROTOCOL {
+            return Err(UnsupportedVersion::TooNew(protocol));
+        }
+        SUPPORTED
+            .iter()
+            .any(|&(number, _)| number == protocol)
+            .then_some(Self(protocol))
+            .ok_or(UnsupportedVersion::Unknown(protocol))
+    }
+
+    /// Every supported version, oldest first.
+    pub fn all() -> impl Iterator<Item = Self> {
+        SUPPORTED.iter().map(|&(number, _)| Self(number))
+    }
+
+    pub fn protocol(self) -> u32 {
+        self.0
+    }
+
+    /// The game version, e.g. `1.21.0`.
+    pub fn minecraft_version(self) -> &'static str {
+        SUPPORTED
+            .iter()
+            .find(|&&(number, _)| number == self.0)
+            .map_or("unknown", |&(_, name)| name)
+    }
+
+    /// Whether this version has the changes of protocol `number`.
+    pub fn at_least(self, number: u32) -> bool {
+        self.0 >= number
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (protocol {})", self.minecraft_version(), self.0)
+    }
+}
+
+/// A packet encoded per protocol version. The defaults use the latest layout, for packets that
+/// are the same in every supported version.
+pub trait VersionedPacket: GamePacket + Readable + Sized {
+    fn write_for(
+        &self,
+        writer: &mut BinaryWriter,
+        _version: ProtocolVersion,
+    ) -> Result<(), BinaryError> {
+        self.write(writer)
+    }
+
+    fn read_for(reader: &mut BinaryReader, _version: ProtocolVersion) -> Result<Self, BinaryError> {
+        Self::read(reader)
+    }
+}
+
+/// Encodes `packet` with its header, in the layout `version` expects.
+pub fn encode_for<P: VersionedPacket>(
+    packet: &P,
+    version: ProtocolVersion,
+) -> Result<Bytes, BinaryError> {
+    let mut writer = BinaryWriter::new();
+    writer.write_var_u32(P::ID)?;
+    packet.write_for(&mut writer, version)?;
+    Ok(writer.freeze())
+}
+
+impl VersionedPacket for RequestChunkRadius {}
+
+impl VersionedPacket for ChunkRadiusUpdated {}
+
+impl VersionedPacket for UpdateBlock {}
+
+impl VersionedPacket for Transfer {
+    fn write_for(
+        &self,
+        writer: &mut BinaryWriter,
+        version: ProtocolVersion,
+    ) -> Result<(), BinaryError> {
+        writer.write_string(&self.address)?;
+        writer.write_u16_le(self.port)?;
+        if version.at_least(V1_21_30) {
+            writer.write_bool(self.reload_world)?;
+        }
+        Ok(())
+    }
+
+    fn read_for(reader: &mut BinaryReader, version: ProtocolVersion) -> Result<Self, BinaryError> {
+        Ok(Self {
+            address: reader.read_string()?,
+            port: reader.read_u16_le()?,
+            reload_world: version.at_least(V1_21_30) && reader.read_bool()?,
+        })
+    }
+}
+
+/// Before 1.21.50 pack URLs were a separate list after the packs, keyed by `<uuid>_<version>`.
+/// Before 1.21.40 UUIDs were strings and there was no world template. Before 1.21.30 behaviour
+/// packs had their own list, always empty here, behind a flag forcing server packs. The server
+/// only sends this packet, so older layouts are never read.
+impl VersionedPacket for ResourcePacksInfo {
+    fn write_for(
+        &self,
+        writer: &mut BinaryWriter,
+        version: ProtocolVersion,
+    ) -> Result<(), BinaryError> {
+        if version.at_least(V1_21_50) {
+            return self.write(writer);
+        }
+        writer.write_bool(self.must_accept)?;
+        writer.write_bool(self.has_addons)?;
+        writer.write_bool(self.has_scripts)?;
+        if !version.at_least(V1_21_30) {
+            // Forcing server packs, then the behaviour pack count.
+            writer.write_bool(false)?;
+            writer.write_u16_le(0)?;
+        }
+        if version.at_least(V1_21_40) {
+            write_uuid(writer, &self.world_template_uuid)?;
+            writer.write_string(&self.world_template_version)?;
+        }
+        let count = u16::try_from(self.resource_packs.len())
+            .map_err(|_| BinaryError::InvalidData("Too many resource packs".to_string()))?;
+        writer.write_u16_le(count)?;
+        for pack in &self.resource_packs {
+            write_legacy_entry(writer, pack, version)?;
+        }
+        let urls: Vec<_> = self
+            .resource_packs
+            .iter()
+            .filter(|pack| !pack.cdn_url.is_empty())
+            .collect();
+        writer.write_var_u32(urls.len() as u32)?;
+        for pack in urls {
+            writer.write_string(&format!("{}_{}", format_uuid(&pack.uuid), pack.version))?;
+            writer.write_string(&pack.cdn_url)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_legacy_entry(
+    writer: &mut BinaryWriter,
+    pack: &ResourcePackInfoEntry,
+    version: ProtocolVersion,
+) -> Result<(), BinaryError> {
+    if version.at_least(V1_21_40) {
+        write_uuid(writer, &pack.uuid)?;
+    } else {
+        writer.write_string(&format_uuid(&pack.uuid))?;
+    }
+    writer.write_string(&pack.version)?;
+    writer.write_u64_le(pack.size)?;
+    writer.write_string(&pack.content_key)?;
+    writer.write_string(&pack.sub_pack_name)?;
+    writer.write_string(&pack.content_identity)?;
+    writer.write_bool(pack.has_scripts)?;
+    writer.write_bool(pack.addon_pack)?;
+    writer.write_bool(pack.raytracing_capable)?;
+    Ok(())
+}
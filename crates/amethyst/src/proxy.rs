@@ -0,0 +1,252 @@
+//! Proxy mode: Amethyst terminates RakNet and forwards the game packets of every session to a
+//! backend server, shielding it from raw UDP traffic.
+//!
+//! All sessions share one TCP connection to the backend. Each frame is a big-endian `u32`
+//! length followed by a [`Frame`]: a kind byte, the client address, and the kind's fields.
+
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use amethyst_plugin::Scheduler;
+use bytes::Bytes;
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use rakethyst::connection::{Connection, ConnectionState};
+use rakethyst::listener::ServerInfo;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Largest frame accepted from the backend.
+const MAX_FRAME_LENGTH: u32 = 8 * 1024 * 1024;
+/// Frames waiting to be sent before new ones are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+const OPEN: u8 = 0x01;
+const PACKET: u8 = 0x02;
+const CLOSE: u8 = 0x03;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// A client connected. Only sent to the backend.
+    Open { client: SocketAddr, guid: u64 },
+    /// A decrypted and decompressed game packet, in either direction.
+    Packet { client: SocketAddr, payload: Bytes },
+    /// A client disconnected, or the backend wants it disconnected.
+    Close { client: SocketAddr, reason: String },
+}
+
+impl Writable for Frame {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        match self {
+            Frame::Open { client, guid } => {
+                writer.write_u8(OPEN)?;
+                writer.write_socket_addr(client)?;
+                writer.write_u64(*guid)?;
+            }
+            Frame::Packet { client, payload } => {
+                writer.write_u8(PACKET)?;
+                writer.write_socket_addr(client)?;
+                writer.write_bytes(payload)?;
+            }
+            Frame::Close { client, reason } => {
+                writer.write_u8(CLOSE)?;
+                writer.write_socket_addr(client)?;
+                writer.write_string(reason)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Readable for Frame {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        let kind = reader.read_u8()?;
+        let client = reader.read_socket_addr()?;
+        match kind {
+            OPEN => Ok(Frame::Open {
+                client,
+                guid: reader.read_u64()?,
+            }),
+            PACKET => Ok(Frame::Packet {
+                client,
+                payload: reader.read_remaining(),
+            }),
+            CLOSE => Ok(Frame::Close {
+                client,
+                reason: reader.read_string()?,
+            }),
+            _ => Err(BinaryError::InvalidData(format!(
+                "Unknown proxy frame kind {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+/// Connection to the backend, re-established whenever it drops.
+pub struct ProxyLink {
+    outbound: mpsc::Sender<Frame>,
+    task: JoinHandle<()>,
+}
+
+impl ProxyLink {
+    /// Starts connecting to `backend`. Frames the backend sends are delivered to `inbound`.
+    pub fn spawn(backend: String, inbound: mpsc::Sender<Frame>) -> Self {
+        let (outbound, queue) = mpsc::channel(QUEUE_CAPACITY);
+        let task = tokio::spawn(run(backend, queue, inbound));
+        Self { outbound, task }
+    }
+
+    /// Queues `frame` for the backend. Frames are dropped while the queue is full or the
+    /// backend is unreachable, as sessions cannot survive a backend reconnect anyway.
+    pub fn send(&self, frame: Frame) -> bool {
+        self.outbound.try_send(frame).is_ok()
+    }
+}
+
+impl Drop for ProxyLink {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(backend: String, mut queue: mpsc::Receiver<Frame>, inbound: mpsc::Sender<Frame>) {
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match TcpStream::connect(&backend).await {
+            Ok(stream) => {
+                info!("Connected to proxy backend {}", backend);
+                delay = MIN_RECONNECT_DELAY;
+                let _ = stream.set_nodelay(true);
+                let (reader, writer) = stream.into_split();
+                let result = tokio::select! {
+                    result = read_frames(reader, &inbound) => result,
+                    result = write_frames(writer, &mut queue) => result,
+                };
+                match result {
+                    Ok(()) => warn!("Proxy backend {} closed the connection", backend),
+                    Err(e) => warn!("Lost connection to proxy backend {}: {}", backend, e),
+                }
+            }
+            Err(e) => warn!(
+                "Failed to connect to proxy backend {}: {}. Retrying in {}s",
+                backend,
+                e,
+                delay.as_secs()
+            ),
+        }
+        // Frames queued for the previous connection belong to sessions it no longer knows.
+        while queue.try_recv().is_ok() {}
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn read_frames(reader: OwnedReadHalf, inbound: &mpsc::Sender<Frame>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let length = match reader.read_u32().await {
+            Ok(length) => length,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if length > MAX_FRAME_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds the limit", length),
+            ));
+        }
+        let mut buffer = vec![0; length as usize];
+        reader.read_exact(&mut buffer).await?;
+        let frame = Frame::read(&mut BinaryReader::new(Bytes::from(buffer)))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+        if inbound.send(frame).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn write_frames(
+    mut writer: OwnedWriteHalf,
+    queue: &mut mpsc::Receiver<Frame>,
+) -> std::io::Result<()> {
+    while let Some(frame) = queue.recv().await {
+        let mut body = BinaryWriter::new();
+        frame
+            .write(&mut body)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+        let body = body.freeze();
+        writer.write_u32(body.len() as u32).await?;
+        writer.write_all(&body).await?;
+    }
+    Ok(())
+}
+
+/// Tells the backend about sessions opening and closing, by comparing the connection table
+/// with the previous tick's.
+pub fn mirror_sessions(
+    link: Arc<ProxyLink>,
+    connections: Arc<DashMap<SocketAddr, Connection>>,
+    scheduler: &Scheduler,
+) {
+    let known: Mutex<HashSet<SocketAddr>> = Mutex::new(HashSet::new());
+    scheduler.run_repeating(1, 1, move || {
+        let mut known = known.lock().unwrap_or_else(|e| e.into_inner());
+        known.retain(|&client| {
+            let open = connections.contains_key(&client);
+            if !open {
+                link.send(Frame::Close {
+                    client,
+                    reason: "Disconnected".to_string(),
+                });
+            }
+            open
+        });
+        for connection in connections.iter() {
+            if connection.state == ConnectionState::Connected
+                && known.insert(connection.address)
+            {
+                link.send(Frame::Open {
+                    client: connection.address,
+                    guid: connection.client_guid,
+                });
+            }
+        }
+    });
+}
+
+/// Acts on frames from the backend until the link is gone.
+pub async fn handle_inbound(
+    mut inbound: mpsc::Receiver<Frame>,
+    connections: Arc<DashMap<SocketAddr, Connection>>,
+    server_info: Arc<ServerInfo>,
+) {
+    while let Some(frame) = inbound.recv().await {
+        match frame {
+            Frame::Close { client, reason } => {
+                if connections.remove(&client).is_some() {
+                    server_info.update_player_count(&connections);
+                    info!("Proxy backend disconnected {}: {}", client, reason);
+                }
+            }
+            Frame::Packet { client, payload } => debug!(
+                "Dropping {} byte game packet from the backend for {}: sending game packets \
+                 requires the RakNet reliability layer",
+                payload.len(),
+                client
+            ),
+            Frame::Open { client, .. } => {
+                warn!("Proxy backend sent an unexpected open frame for {}", client)
+            }
+        }
+    }
+}
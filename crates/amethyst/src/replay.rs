@@ -0,0 +1,96 @@
+use crate::cli::Cli;
+use crate::config;
+use amethyst_log::{AmethystLogger, LogFilter};
+use log::{logger, LevelFilter};
+use rakethyst::error::RakNetError;
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::Motd;
+use rakethyst::recording::Recording;
+use rakethyst::session::DEFAULT_HANDSHAKE_TIMEOUT;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Runs `amethyst replay`: feeds a session recording into a fresh session on a listener bound
+/// to localhost, with the session log at debug level unless `--log-level` says otherwise.
+///
+/// Datagrams are fed in their recorded order with their recorded gaps, so the session times
+/// out, or not, as it did when recorded. Returns `false` if the recording cannot be replayed.
+pub async fn run(cli: &Cli, path: &Path) -> bool {
+    let recording = match Recording::load(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let level = cli
+        .log_level
+        .as_deref()
+        .and_then(|level| LevelFilter::from_str(level).ok())
+        .unwrap_or(LevelFilter::Debug);
+    AmethystLogger::set_filter(LogFilter::new(level));
+    // The server the session was recorded on likely used the same configuration.
+    let handshake_timeout = config::check(&cli.config, &cli.overrides())
+        .map(|(config, _)| Duration::from_secs(config.network.handshake_timeout))
+        .unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT);
+
+    println!(
+        "Replaying {} datagrams from {} over {:.3}s",
+        recording.datagrams.len(),
+        recording.address,
+        recording.duration().as_secs_f64()
+    );
+    let result = replay(&recording, handshake_timeout).await;
+    logger().flush();
+    AmethystLogger::flush_blocking(Duration::from_secs(1));
+    match result {
+        Ok((elapsed, replies)) => {
+            println!(
+                "Session ended {:.3}s after its first datagram; the server sent {} replies",
+                elapsed.as_secs_f64(),
+                replies
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Replays `recording` and returns how long the session lasted and how many datagrams it
+/// sent back.
+async fn replay(
+    recording: &Recording,
+    handshake_timeout: Duration,
+) -> Result<(Duration, usize), RakNetError> {
+    let server_info = Arc::new(ServerInfo::new(
+        0,
+        Motd {
+            motd: "Replay".to_string(),
+            world_name: "Replay".to_string(),
+            game_mode: "Survival".to_string(),
+            max_players: 1,
+        },
+    ));
+    let listener = RakNetListener::bind("127.0.0.1:0", server_info)
+        .await?
+        .with_handshake_timeout(handshake_timeout);
+    let replies = UdpSocket::bind("127.0.0.1:0").await?;
+
+    let start = tokio::time::Instant::now();
+    listener.replay(recording, replies.local_addr()?).await;
+    let elapsed = start.elapsed();
+
+    // Replies sent over loopback are already waiting on the socket.
+    let mut count = 0;
+    let mut buf = [0; 2048];
+    while replies.try_recv(&mut buf).is_ok() {
+        count += 1;
+    }
+    Ok((elapsed, count))
+}
@@ -0,0 +1,167 @@
+//! Resource packs served over HTTP. Joining players are sent the URL of each pack in
+//! [`ResourcePacksInfo`] and download it from there, instead of requesting it in chunks through
+//! RakNet.
+
+use crate::config::ResourcePacksConfig;
+use crate::protocol::version::{self, ProtocolVersion};
+use crate::protocol::{self, ResourcePackInfoEntry, ResourcePacksInfo};
+use amethyst_binary::error::BinaryError;
+use axum::extract::{Path as UrlPath, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use bytes::Bytes;
+use log::{error, info};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ResourcePackError {
+    #[error("Failed to read resource pack {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid UUID '{uuid}' for resource pack {file}")]
+    InvalidUuid { file: String, uuid: String },
+    #[error("Failed to encode the resource pack list: {0}")]
+    Encode(#[from] BinaryError),
+}
+
+pub struct ResourcePack {
+    pub file: String,
+    pub uuid: [u8; 16],
+    pub version: String,
+    /// The archive, read once at startup so every download gets the size that was advertised.
+    data: Bytes,
+}
+
+impl ResourcePack {
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// The configured packs and where clients download them from.
+pub struct ResourcePacks {
+    packs: Vec<ResourcePack>,
+    public_url: String,
+    required: bool,
+    /// Encoded [`ResourcePacksInfo`] for each supported version, oldest first.
+    info: Vec<(ProtocolVersion, Bytes)>,
+}
+
+impl ResourcePacks {
+    /// Reads the packs listed in `config` from its directory.
+    pub fn load(config: &ResourcePacksConfig) -> Result<Self, ResourcePackError> {
+        let directory = Path::new(&config.directory);
+        let packs = config
+            .packs
+            .iter()
+            .map(|entry| {
+                let uuid = protocol::parse_uuid(&entry.uuid).ok_or_else(|| {
+                    ResourcePackError::InvalidUuid {
+                        file: entry.file.clone(),
+                        uuid: entry.uuid.clone(),
+                    }
+                })?;
+                let path = directory.join(&entry.file);
+                let data = std::fs::read(&path)
+                    .map_err(|source| ResourcePackError::Io { path, source })?;
+                Ok(ResourcePack {
+                    file: entry.file.clone(),
+                    uuid,
+                    version: entry.version.clone(),
+                    data: Bytes::from(data),
+                })
+            })
+            .collect::<Result<Vec<_>, ResourcePackError>>()?;
+        let mut resource_packs = Self {
+            packs,
+            public_url: config.public_url(),
+            required: config.required,
+            info: Vec::new(),
+        };
+        let packet = resource_packs.info_packet();
+        resource_packs.info = ProtocolVersion::all()
+            .map(|version| Ok((version, version::encode_for(&packet, version)?)))
+            .collect::<Result<_, ResourcePackError>>()?;
+        Ok(resource_packs)
+    }
+
+    pub fn packs(&self) -> &[ResourcePack] {
+        &self.packs
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    pub fn url(&self, pack: &ResourcePack) -> String {
+        format!("{}/packs/{}", self.public_url, pack.file)
+    }
+
+    /// The encoded pack list sent to players joining with `version`.
+    pub fn info(&self, version: ProtocolVersion) -> &Bytes {
+        self.info
+            .iter()
+            .find(|(encoded, _)| *encoded == version)
+            .map(|(_, info)| info)
+            .expect("Every supported version is encoded")
+    }
+
+    fn info_packet(&self) -> ResourcePacksInfo {
+        ResourcePacksInfo {
+            must_accept: self.required,
+            resource_packs: self
+                .packs
+                .iter()
+                .map(|pack| ResourcePackInfoEntry {
+                    uuid: pack.uuid,
+                    version: pack.version.clone(),
+                    size: pack.size(),
+                    cdn_url: self.url(pack),
+                    ..ResourcePackInfoEntry::default()
+                })
+                .collect(),
+            ..ResourcePacksInfo::default()
+        }
+    }
+
+    fn find(&self, file: &str) -> Option<&ResourcePack> {
+        self.packs.iter().find(|pack| pack.file == file)
+    }
+}
+
+/// Serves each pack at `/packs/<file>` on `address`, without authentication.
+pub async fn serve(address: String, packs: Arc<ResourcePacks>) {
+    let app = Router::new()
+        .route("/packs/{file}", get(download))
+        .with_state(packs);
+
+    let listener = match tokio::net::TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind resource pack server to {}: {}", address, e);
+            return;
+        }
+    };
+    info!("Resource pack server listening on http://{}", address);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("Resource pack server stopped: {}", e);
+    }
+}
+
+async fn download(
+    State(packs): State<Arc<ResourcePacks>>,
+    UrlPath(file): UrlPath<String>,
+) -> Response {
+    match packs.find(&file) {
+        Some(pack) => (
+            [(header::CONTENT_TYPE, "application/zip")],
+            pack.data.clone(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
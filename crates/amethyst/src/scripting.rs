@@ -0,0 +1,670 @@
+//! Rhai scripts loaded from [`SCRIPTS_DIR`], for operators who want to customise the server
+//! without building a native plugin.
+//!
+//! A script runs once when it is loaded and uses the functions below to hook into the
+//! server. Handlers are closures or `Fn("name")` pointers to functions in the same script.
+//!
+//! - `on(event, handler)` and `on(event, priority, handler)` call `handler(event)` for every
+//!   posted event, e.g. `"player_chat"`. Fields are read and written as properties, such as
+//!   `event.message` or `event.cancelled = true`.
+//! - `command(name, handler)` and `command(name, options, handler)` add a command, where
+//!   `options` may set `description`, `usage`, `permission`, `node` and `aliases`. The node
+//!   defaults to `scripts.command.<name>`. The handler gets the sender's name and the
+//!   arguments, and returns the feedback.
+//! - `run_later(delay, task)`, `run_repeating(delay, period, task)` and their `_async`
+//!   variants schedule tasks in ticks; `cancel(task)` cancels one.
+//! - `players()`, `player_count()` and `kick(address)` inspect and manage connections.
+//! - `print`, `debug`, `warn` and `error` write to the server log.
+
+use crate::commands::{CommandContext, CommandError, CommandRegistry, CommandSpec};
+use crate::plugins::leak;
+use amethyst_plugin::event::{
+    BlockBreak, BlockPlace, ConfigReloaded, PacketReceive, PlayerChat, PlayerJoin,
+    PlayerLoginDenied, PlayerQuit, ServerStarted, ServerStopping,
+};
+use amethyst_plugin::{Event, EventBus, EventPriority, Scheduler, TaskId};
+use log::{error, info, log, warn, Level};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, FnPtr, FuncArgs, Map, AST, INT};
+use std::cell::RefCell;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use thiserror::Error;
+
+/// Directory scripts are loaded from, relative to the working directory.
+pub const SCRIPTS_DIR: &str = "scripts";
+
+const SCRIPT_EXTENSION: &str = "rhai";
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Compile(#[from] rhai::ParseError),
+    #[error("{0}")]
+    Script(#[from] Box<EvalAltResult>),
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+struct Script {
+    name: String,
+    /// Owner of the script's event handlers and tasks.
+    owner: String,
+    ast: AST,
+}
+
+thread_local! {
+    /// Script whose code is running on this thread, which functions called by it act for.
+    static CURRENT: RefCell<Option<Arc<Script>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `script` as the current script of the thread.
+fn within<T>(script: &Arc<Script>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(Some(Arc::clone(script))));
+    let result = f();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+fn current_script() -> ScriptResult<Arc<Script>> {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .ok_or_else(|| "No script is running".into())
+}
+
+/// Log target of the current script, e.g. `script::motd`.
+fn log_target() -> String {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some(script) => format!("script::{}", script.name),
+        None => "script".to_string(),
+    })
+}
+
+/// State shared by the engine's functions and the handlers they register.
+struct ScriptHost {
+    engine: OnceLock<Engine>,
+    events: Arc<EventBus>,
+    scheduler: Arc<Scheduler>,
+    context: CommandContext,
+    /// Commands registered while scripts load, `None` once they were added to the registry.
+    commands: Mutex<Option<Vec<CommandSpec>>>,
+}
+
+impl ScriptHost {
+    fn call(
+        &self,
+        script: &Arc<Script>,
+        function: &FnPtr,
+        args: impl FuncArgs,
+    ) -> ScriptResult<Dynamic> {
+        let engine = self
+            .engine
+            .get()
+            .expect("engine is set before scripts load");
+        within(script, || function.call(engine, &script.ast, args))
+    }
+}
+
+/// Scripts loaded from [`SCRIPTS_DIR`] at startup.
+pub struct ScriptManager {
+    host: Arc<ScriptHost>,
+    scripts: Vec<Arc<Script>>,
+}
+
+impl ScriptManager {
+    /// Loads and runs every script in `dir`, registering their commands in `commands`.
+    /// Scripts that fail to compile or run are logged and skipped.
+    pub fn load(
+        dir: &Path,
+        commands: &mut CommandRegistry,
+        events: Arc<EventBus>,
+        scheduler: Arc<Scheduler>,
+        context: CommandContext,
+    ) -> Self {
+        let host = Arc::new(ScriptHost {
+            engine: OnceLock::new(),
+            events,
+            scheduler,
+            context,
+            commands: Mutex::new(Some(Vec::new())),
+        });
+        let _ = host.engine.set(engine(Arc::downgrade(&host)));
+        let mut manager = Self {
+            host,
+            scripts: Vec::new(),
+        };
+
+        match script_files(dir) {
+            Ok(paths) => {
+                for path in paths {
+                    match manager.load_script(&path) {
+                        Ok(()) => info!("Loaded script {}", path.display()),
+                        Err(e) => error!("Failed to load script {}: {}", path.display(), e),
+                    }
+                }
+            }
+            Err(e) => error!("Failed to read script directory {}: {}", dir.display(), e),
+        }
+
+        let specs = manager
+            .host
+            .commands
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        for spec in specs.into_iter().flatten() {
+            let name = spec.name;
+            if !commands.register(spec) {
+                warn!(
+                    "A script tried to register command '{}', which already exists",
+                    name
+                );
+            }
+        }
+        manager
+    }
+
+    fn load_script(&mut self, path: &Path) -> Result<(), ScriptError> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = fs::read_to_string(path)?;
+        let engine = self
+            .host
+            .engine
+            .get()
+            .expect("engine is set before scripts load");
+        let script = Arc::new(Script {
+            owner: format!("script:{}", name),
+            ast: engine.compile(source)?,
+            name,
+        });
+        if let Err(e) = within(&script, || engine.run_ast(&script.ast)) {
+            self.unload(&script);
+            return Err(e.into());
+        }
+        self.scripts.push(script);
+        Ok(())
+    }
+
+    fn unload(&self, script: &Script) {
+        self.host.events.unsubscribe_owner(&script.owner);
+        self.host.scheduler.cancel_owner(&script.owner);
+    }
+
+    /// Removes the event handlers and tasks of every script.
+    pub fn unload_all(&self) {
+        for script in &self.scripts {
+            self.unload(script);
+        }
+    }
+}
+
+/// Scripts in `dir`, in a stable order. A missing directory has no scripts.
+fn script_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some(SCRIPT_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn engine(host: Weak<ScriptHost>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| log!(target: &log_target(), Level::Info, "{}", text));
+    engine.on_debug(|text, _, _| log!(target: &log_target(), Level::Debug, "{}", text));
+    engine.register_fn(
+        "warn",
+        |text: &str| log!(target: &log_target(), Level::Warn, "{}", text),
+    );
+    engine.register_fn(
+        "error",
+        |text: &str| log!(target: &log_target(), Level::Error, "{}", text),
+    );
+
+    engine
+        .register_type_with_name::<EventHandle>("Event")
+        .register_indexer_get(EventHandle::get)
+        .register_indexer_set(EventHandle::set);
+    engine.register_type_with_name::<TaskId>("Task");
+
+    let on = host.clone();
+    engine.register_fn("on", move |event: &str, handler: FnPtr| {
+        subscribe(&upgrade(&on)?, event, EventPriority::Normal, handler)
+    });
+    let on = host.clone();
+    engine.register_fn("on", move |event: &str, priority: &str, handler: FnPtr| {
+        subscribe(&upgrade(&on)?, event, parse_priority(priority)?, handler)
+    });
+
+    let command = host.clone();
+    engine.register_fn("command", move |name: &str, handler: FnPtr| {
+        register_command(&upgrade(&command)?, name, Map::new(), handler)
+    });
+    let command = host.clone();
+    engine.register_fn(
+        "command",
+        move |name: &str, options: Map, handler: FnPtr| {
+            register_command(&upgrade(&command)?, name, options, handler)
+        },
+    );
+
+    for (function, is_async) in [("run_later", false), ("run_later_async", true)] {
+        let schedule = host.clone();
+        engine.register_fn(function, move |delay: INT, task: FnPtr| {
+            schedule_task(&upgrade(&schedule)?, delay, None, is_async, task)
+        });
+    }
+    for (function, is_async) in [("run_repeating", false), ("run_repeating_async", true)] {
+        let schedule = host.clone();
+        engine.register_fn(function, move |delay: INT, period: INT, task: FnPtr| {
+            schedule_task(&upgrade(&schedule)?, delay, Some(period), is_async, task)
+        });
+    }
+    let cancel = host.clone();
+    engine.register_fn("cancel", move |task: TaskId| -> ScriptResult<bool> {
+        Ok(upgrade(&cancel)?.scheduler.cancel(task))
+    });
+
+    let players = host.clone();
+    engine.register_fn("players", move || -> ScriptResult<Array> {
+        let host = upgrade(&players)?;
+        Ok(host
+            .context
+            .connections
+            .iter()
+            .map(|connection| {
+                let mut player = Map::new();
+                player.insert("address".into(), connection.address.to_string().into());
+                player.insert("guid".into(), connection.client_guid.to_string().into());
+                player.insert("state".into(), format!("{:?}", connection.state).into());
+                Dynamic::from_map(player)
+            })
+            .collect())
+    });
+    let count = host.clone();
+    engine.register_fn("player_count", move || -> ScriptResult<INT> {
+        Ok(upgrade(&count)?.context.connections.len() as INT)
+    });
+    let kick = host;
+    engine.register_fn("kick", move |address: &str| -> ScriptResult<bool> {
+        let host = upgrade(&kick)?;
+        let address: SocketAddr = address
+            .parse()
+            .map_err(|_| format!("Invalid address '{}'", address))?;
+        if host.context.connections.remove(&address).is_none() {
+            return Ok(false);
+        }
+        host.context
+            .server_info
+            .update_player_count(&host.context.connections);
+        info!("Script {} kicked {}", current_script()?.name, address);
+        Ok(true)
+    });
+    engine
+}
+
+fn upgrade(host: &Weak<ScriptHost>) -> ScriptResult<Arc<ScriptHost>> {
+    host.upgrade()
+        .ok_or_else(|| "The server is shutting down".into())
+}
+
+fn parse_priority(priority: &str) -> ScriptResult<EventPriority> {
+    Ok(match priority.to_ascii_lowercase().as_str() {
+        "lowest" => EventPriority::Lowest,
+        "low" => EventPriority::Low,
+        "normal" => EventPriority::Normal,
+        "high" => EventPriority::High,
+        "highest" => EventPriority::Highest,
+        "monitor" => EventPriority::Monitor,
+        _ => return Err(format!("Unknown event priority '{}'", priority).into()),
+    })
+}
+
+fn subscribe(
+    host: &Arc<ScriptHost>,
+    event: &str,
+    priority: EventPriority,
+    handler: FnPtr,
+) -> ScriptResult<()> {
+    fn add<E: ScriptEvent>(
+        host: &Arc<ScriptHost>,
+        priority: EventPriority,
+        handler: FnPtr,
+    ) -> ScriptResult<()> {
+        let script = current_script()?;
+        let owner = script.owner.clone();
+        let callback_host = Arc::clone(host);
+        host.events
+            .subscribe_as(Some(&owner), priority, move |event: &mut E| {
+                let handle = EventHandle(Arc::new(Mutex::new(event.to_map())));
+                match callback_host.call(&script, &handler, (handle.clone(),)) {
+                    Ok(_) => event.apply(&handle.lock()),
+                    Err(e) => error!("Script {} failed to handle {}: {}", script.name, E::NAME, e),
+                }
+            });
+        Ok(())
+    }
+
+    match event {
+        ServerStarted::NAME => add::<ServerStarted>(host, priority, handler),
+        ServerStopping::NAME => add::<ServerStopping>(host, priority, handler),
+        ConfigReloaded::NAME => add::<ConfigReloaded>(host, priority, handler),
+        PlayerJoin::NAME => add::<PlayerJoin>(host, priority, handler),
+        PlayerLoginDenied::NAME => add::<PlayerLoginDenied>(host, priority, handler),
+        PlayerQuit::NAME => add::<PlayerQuit>(host, priority, handler),
+        PlayerChat::NAME => add::<PlayerChat>(host, priority, handler),
+        BlockBreak::NAME => add::<BlockBreak>(host, priority, handler),
+        BlockPlace::NAME => add::<BlockPlace>(host, priority, handler),
+        PacketReceive::NAME => add::<PacketReceive>(host, priority, handler),
+        _ => Err(format!("Unknown event '{}'", event).into()),
+    }
+}
+
+fn register_command(
+    host: &Arc<ScriptHost>,
+    name: &str,
+    options: Map,
+    handler: FnPtr,
+) -> ScriptResult<()> {
+    let script = current_script()?;
+    let text = |key: &str| -> ScriptResult<&'static str> {
+        match options.get(key) {
+            Some(value) => Ok(leak(value.clone().into_string()?)),
+            None => Ok(""),
+        }
+    };
+    let permission = match options.get("permission") {
+        Some(value) => u8::try_from(value.as_int()?)
+            .map_err(|_| format!("Invalid permission level {}", value))?,
+        None => 0,
+    };
+    let node = match options.get("node") {
+        Some(value) => leak(value.clone().into_string()?),
+        None => leak(format!("scripts.command.{}", name)),
+    };
+    let aliases = match options.get("aliases") {
+        Some(value) => value
+            .clone()
+            .into_array()?
+            .into_iter()
+            .map(|alias| alias.into_string().map(leak))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    let callback_host = Arc::clone(host);
+    let spec = CommandSpec {
+        name: leak(name.to_string()),
+        aliases: Vec::leak(aliases),
+        usage: text("usage")?,
+        description: text("description")?,
+        permission,
+        node,
+        handler: Box::new(move |invocation, args| {
+            let sender = invocation.sender.name().to_string();
+            let args: Array = args.remaining().into_iter().map(Dynamic::from).collect();
+            match callback_host.call(&script, &handler, (sender, args)) {
+                Ok(feedback) if feedback.is_unit() => Ok(String::new()),
+                Ok(feedback) => Ok(feedback.to_string()),
+                Err(e) => Err(CommandError::Failed(e.to_string())),
+            }
+        }),
+    };
+    match host
+        .commands
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        Some(commands) => {
+            commands.push(spec);
+            Ok(())
+        }
+        None => Err("Commands can only be registered while the script loads".into()),
+    }
+}
+
+fn schedule_task(
+    host: &Arc<ScriptHost>,
+    delay: INT,
+    period: Option<INT>,
+    is_async: bool,
+    task: FnPtr,
+) -> ScriptResult<TaskId> {
+    let script = current_script()?;
+    let ticks =
+        |value: INT| u64::try_from(value).map_err(|_| format!("Invalid tick count {}", value));
+    let delay = ticks(delay)?;
+    let period = period.map(ticks).transpose()?;
+
+    let callback_host = Arc::clone(host);
+    let owner = script.owner.clone();
+    let callback = move || {
+        if let Err(e) = callback_host.call(&script, &task, ()) {
+            error!("Script {} task failed: {}", script.name, e);
+        }
+    };
+    let scheduler = amethyst_plugin::PluginScheduler::new(&owner, Arc::clone(&host.scheduler));
+    Ok(match (period, is_async) {
+        (None, false) => scheduler.run_later(delay, callback),
+        (None, true) => scheduler.run_later_async(delay, callback),
+        (Some(period), false) => scheduler.run_repeating(delay, period, callback),
+        (Some(period), true) => scheduler.run_repeating_async(delay, period, callback),
+    })
+}
+
+/// An event as seen by a script. Clones share the fields, so changes a handler makes are
+/// visible to the server after it returns.
+#[derive(Clone)]
+struct EventHandle(Arc<Mutex<Map>>);
+
+impl EventHandle {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Map> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn get(&mut self, field: &str) -> ScriptResult<Dynamic> {
+        self.lock()
+            .get(field)
+            .cloned()
+            .ok_or_else(|| format!("Event has no field '{}'", field).into())
+    }
+
+    fn set(&mut self, field: &str, value: Dynamic) -> ScriptResult<()> {
+        match self.lock().get_mut(field) {
+            Some(existing) => {
+                *existing = value;
+                Ok(())
+            }
+            None => Err(format!("Event has no field '{}'", field).into()),
+        }
+    }
+}
+
+/// An event scripts can handle, converted to a map of fields and back.
+trait ScriptEvent: Event {
+    /// Name scripts subscribe to.
+    const NAME: &'static str;
+
+    fn to_map(&self) -> Map;
+
+    /// Copies back the fields a handler may change.
+    fn apply(&mut self, _fields: &Map) {}
+}
+
+fn string(fields: &Map, key: &str) -> Option<String> {
+    fields
+        .get(key)
+        .and_then(|value| value.clone().into_string().ok())
+}
+
+fn flag(fields: &Map, key: &str) -> Option<bool> {
+    fields.get(key).and_then(|value| value.as_bool().ok())
+}
+
+macro_rules! fields {
+    ($($key:literal => $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut map = Map::new();
+        $(map.insert($key.into(), Dynamic::from($value));)*
+        map
+    }};
+}
+
+impl ScriptEvent for ServerStarted {
+    const NAME: &'static str = "server_started";
+
+    fn to_map(&self) -> Map {
+        fields!()
+    }
+}
+
+impl ScriptEvent for ServerStopping {
+    const NAME: &'static str = "server_stopping";
+
+    fn to_map(&self) -> Map {
+        fields!()
+    }
+}
+
+impl ScriptEvent for ConfigReloaded {
+    const NAME: &'static str = "config_reloaded";
+
+    fn to_map(&self) -> Map {
+        fields!()
+    }
+}
+
+impl ScriptEvent for PlayerJoin {
+    const NAME: &'static str = "player_join";
+
+    fn to_map(&self) -> Map {
+        fields! {
+            "name" => self.name.clone(),
+            "xuid" => self.xuid.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            "uuid" => self.uuid.clone(),
+            "authenticated" => self.authenticated,
+            "address" => self.address.to_string(),
+            "kick_message" => self.kick_message.clone(),
+            "cancelled" => self.cancelled,
+        }
+    }
+
+    fn apply(&mut self, fields: &Map) {
+        self.kick_message = string(fields, "kick_message").unwrap_or_default();
+        self.cancelled = flag(fields, "cancelled").unwrap_or(self.cancelled);
+    }
+}
+
+impl ScriptEvent for PlayerLoginDenied {
+    const NAME: &'static str = "player_login_denied";
+
+    fn to_map(&self) -> Map {
+        fields! {
+            "name" => self.name.clone(),
+            "xuid" => self.xuid.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            "address" => self.address.to_string(),
+            "reason" => self.reason.clone(),
+            "message" => self.message.clone(),
+        }
+    }
+}
+
+impl ScriptEvent for PlayerQuit {
+    const NAME: &'static str = "player_quit";
+
+    fn to_map(&self) -> Map {
+        fields! {
+            "name" => self.name.clone(),
+            "address" => self.address.to_string(),
+            "reason" => self.reason.clone(),
+        }
+    }
+}
+
+impl ScriptEvent for PlayerChat {
+    const NAME: &'static str = "player_chat";
+
+    fn to_map(&self) -> Map {
+        fields! {
+            "name" => self.name.clone(),
+            "xuid" => self.xuid.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            "message" => self.message.clone(),
+            "format" => self.format.clone(),
+            "cancelled" => self.cancelled,
+        }
+    }
+
+    fn apply(&mut self, fields: &Map) {
+        self.message = string(fields, "message").unwrap_or_default();
+        self.format = string(fields, "format").unwrap_or_default();
+        self.cancelled = flag(fields, "cancelled").unwrap_or(self.cancelled);
+    }
+}
+
+impl ScriptEvent for BlockBreak {
+    const NAME: &'static str = "block_break";
+
+    fn to_map(&self) -> Map {
+        let (x, y, z) = self.position;
+        fields! {
+            "player" => self.player.clone(),
+            "xuid" => self.xuid.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            "world" => self.world.clone(),
+            "position" => vec![Dynamic::from(x as INT), Dynamic::from(y as INT), Dynamic::from(z as INT)],
+            "cancelled" => self.cancelled,
+        }
+    }
+
+    fn apply(&mut self, fields: &Map) {
+        self.cancelled = flag(fields, "cancelled").unwrap_or(self.cancelled);
+    }
+}
+
+impl ScriptEvent for BlockPlace {
+    const NAME: &'static str = "block_place";
+
+    fn to_map(&self) -> Map {
+        let (x, y, z) = self.position;
+        fields! {
+            "player" => self.player.clone(),
+            "xuid" => self.xuid.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            "world" => self.world.clone(),
+            "position" => vec![Dynamic::from(x as INT), Dynamic::from(y as INT), Dynamic::from(z as INT)],
+            "cancelled" => self.cancelled,
+        }
+    }
+
+    fn apply(&mut self, fields: &Map) {
+        self.cancelled = flag(fields, "cancelled").unwrap_or(self.cancelled);
+    }
+}
+
+impl ScriptEvent for PacketReceive {
+    const NAME: &'static str = "packet_receive";
+
+    fn to_map(&self) -> Map {
+        fields! {
+            "address" => self.address.to_string(),
+            "packet_id" => self.packet_id as INT,
+            "payload" => self.payload.clone() as Blob,
+            "cancelled" => self.cancelled,
+        }
+    }
+
+    fn apply(&mut self, fields: &Map) {
+        self.cancelled = flag(fields, "cancelled").unwrap_or(self.cancelled);
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Lets any part of the server request a graceful shutdown, which the main task waits for.
+#[derive(Default)]
+pub struct Shutdown {
+    requested: AtomicBool,
+    /// Set when the server is to be replaced by a new process rather than stop.
+    restart: AtomicBool,
+    notify: Notify,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Requests a shutdown that hands the players over to a new start of the server, which
+    /// is only supported on Unix.
+    pub fn request_restart(&self) {
+        self.restart.store(true, Ordering::Relaxed);
+        self.request();
+    }
+
+    pub fn is_restart_requested(&self) -> bool {
+        self.restart.load(Ordering::Relaxed)
+    }
+
+    /// Completes once a shutdown has been requested, including before this was called.
+    pub async fn wait(&self) {
+        if !self.is_requested() {
+            self.notify.notified().await;
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Spawn protection: within `spawn_protection` blocks of a world's spawn, counted along X and Z
+//! like the vanilla server does, only operators and players granted [`BUILD_NODE`] may break or
+//! place blocks. The radius is set per world in its
+//! [`WorldConfig`](crate::config::world::WorldConfig).
+
+use crate::access::AccessLists;
+use crate::config::world::WorldConfig;
+use amethyst_plugin::event::{BlockBreak, BlockPlace, EventPriority};
+use amethyst_plugin::permission::PermissionSubject;
+use amethyst_plugin::{EventBus, PermissionDefault, PermissionNode, Permissions};
+use log::debug;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Lets players who are not operators build near spawn.
+pub const BUILD_NODE: &str = "amethyst.build.spawn";
+
+/// X and Z of the spawn of every world, until worlds store a spawn point of their own.
+pub const DEFAULT_SPAWN: (i32, i32) = (0, 0);
+
+pub struct SpawnProtection {
+    /// Radius of each world that has one.
+    radii: HashMap<String, u32>,
+    access: Arc<AccessLists>,
+    permissions: Arc<Permissions>,
+}
+
+impl SpawnProtection {
+    /// Protects the worlds in `worlds` with a radius above 0, and registers [`BUILD_NODE`].
+    pub fn new(
+        worlds: &BTreeMap<String, WorldConfig>,
+        access: Arc<AccessLists>,
+        permissions: Arc<Permissions>,
+    ) -> Self {
+        permissions.register(
+            PermissionNode::new(BUILD_NODE, PermissionDefault::OpLevel(1))
+                .description("Break and place blocks in spawn protection"),
+        );
+        let radii = worlds
+            .iter()
+            .filter(|(_, world)| world.spawn_protection > 0)
+            .map(|(name, world)| (name.clone(), world.spawn_protection))
+            .collect();
+        Self {
+            radii,
+            access,
+            permissions,
+        }
+    }
+
+    pub fn is_protected(&self, world: &str, position: (i32, i32, i32)) -> bool {
+        let Some(&radius) = self.radii.get(world) else {
+            return false;
+        };
+        let (x, _, z) = position;
+        let (spawn_x, spawn_z) = DEFAULT_SPAWN;
+        x.abs_diff(spawn_x) <= radius && z.abs_diff(spawn_z) <= radius
+    }
+
+    pub fn may_build(
+        &self,
+        player: &str,
+        xuid: Option<&str>,
+        world: &str,
+        position: (i32, i32, i32),
+    ) -> bool {
+        if !self.is_protected(world, position) {
+            return true;
+        }
+        let subject = PermissionSubject {
+            name: player,
+            xuid,
+            op_level: self.access.op_level(player, xuid).unwrap_or(0),
+        };
+        self.permissions.has(&subject, BUILD_NODE)
+    }
+
+    /// Cancels breaking and placing blocks in the protected area. The handlers run early, so
+    /// plugins can still allow a change with a handler of their own.
+    pub fn register_events(self: &Arc<Self>, events: &EventBus) {
+        let protection = Arc::clone(self);
+        events.subscribe(EventPriority::Low, move |event: &mut BlockBreak| {
+            let xuid = event.xuid.as_deref();
+            if !protection.may_build(&event.player, xuid, &event.world, event.position) {
+                debug!("{} may not break blocks near spawn", event.player);
+                event.cancelled = true;
+            }
+        });
+        let protection = Arc::clone(self);
+        events.subscribe(EventPriority::Low, move |event: &mut BlockPlace| {
+            let xuid = event.xuid.as_deref();
+            if !protection.may_build(&event.player, xuid, &event.world, event.position) {
+                debug!("{} may not place blocks near spawn", event.player);
+                event.cancelled = true;
+            }
+        });
+    }
+}
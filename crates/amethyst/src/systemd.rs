@@ -0,0 +1,54 @@
+//! Readiness and watchdog notifications for systemd, for units with `Type=notify` and
+//! `WatchdogSec=`. Only built with the `systemd` feature, and only on Unix. Outside systemd,
+//! where `NOTIFY_SOCKET` is not set, every call does nothing.
+
+use log::{debug, warn};
+use sd_notify::NotifyState;
+use std::time::{Duration, Instant};
+
+/// Tells systemd the server has started, once the listener is bound and the worlds are loaded.
+pub fn notify_ready(status: &str) {
+    notify(&[NotifyState::Ready, NotifyState::Status(status)]);
+}
+
+/// Tells systemd the server is shutting down, so it does not count as a crash.
+pub fn notify_stopping() {
+    notify(&[NotifyState::Stopping, NotifyState::Status("Shutting down")]);
+}
+
+fn notify(states: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+/// Keeps the systemd watchdog from restarting the server, as long as ticks keep running.
+pub struct Watchdog {
+    /// Half the watchdog timeout, as systemd recommends.
+    interval: Duration,
+    last_pet: Instant,
+}
+
+impl Watchdog {
+    /// The watchdog of the unit, or `None` if it has none.
+    pub fn from_env() -> Option<Self> {
+        let mut usec = 0;
+        if !sd_notify::watchdog_enabled(false, &mut usec) {
+            return None;
+        }
+        let interval = Duration::from_micros(usec) / 2;
+        debug!("Petting the systemd watchdog every {:?}", interval);
+        Some(Watchdog {
+            interval,
+            last_pet: Instant::now(),
+        })
+    }
+
+    /// Tells systemd the server is alive, if it has not been told recently.
+    pub fn pet(&mut self) {
+        if self.last_pet.elapsed() >= self.interval {
+            notify(&[NotifyState::Watchdog]);
+            self.last_pet = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Opt-in crash and error reports to a Sentry-compatible server, configured by
+//! [`TelemetryConfig`](crate::config::TelemetryConfig).
+//!
+//! Reports carry the server version, the operating system and a random instance ID, so reports
+//! from the same server can be told apart without identifying it. IP addresses, runs of ten or
+//! more digits such as XUIDs, and the configured secrets are removed from every message and
+//! backtrace before it is sent.
+
+use crate::config::error::ConfigError;
+use crate::config::{Config, SentryDsn};
+use amethyst_log::AmethystLogger;
+use chrono::Utc;
+use log::{warn, Level};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// File holding the random ID of this server instance.
+const INSTANCE_ID_FILE: &str = "telemetry-id";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a crash waits for its report to be sent before the process exits.
+const PANIC_REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Logged errors waiting to be sent before new ones are dropped.
+const QUEUE_CAPACITY: usize = 16;
+/// Logged errors sent per window, so an error logged in a loop does not flood the server.
+const MAX_ERRORS_PER_WINDOW: u32 = 10;
+const ERROR_WINDOW: Duration = Duration::from_secs(60);
+/// Targets whose errors are not reported: the panic hook reports panics itself, and failed
+/// reports would report themselves.
+const IGNORED_TARGETS: &[&str] = &["amethyst::crash", "amethyst::telemetry"];
+const REDACTED: &str = "<redacted>";
+
+static REPORTER: OnceLock<Arc<Reporter>> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("Failed to read or create {INSTANCE_ID_FILE}: {0}")]
+    InstanceId(io::Error),
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("The server responded with {0}")]
+    Status(StatusCode),
+    #[error("Failed to start a runtime for the report: {0}")]
+    Runtime(io::Error),
+}
+
+/// Builds and sends the reports of one server instance.
+pub struct Reporter {
+    dsn: SentryDsn,
+    instance_id: String,
+    /// Removed from every report.
+    secrets: Vec<String>,
+}
+
+/// Starts reporting panics, and logged errors if `config.telemetry.errors` is set. Has to be
+/// called from within the runtime.
+pub fn init(config: &Config) -> Result<(), TelemetryError> {
+    let telemetry = &config.telemetry;
+    let instance_id = instance_id().map_err(TelemetryError::InstanceId)?;
+    let reporter = Arc::new(Reporter::new(config, instance_id)?);
+    if telemetry.errors {
+        let client = client()?;
+        let (queue, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(send_errors(Arc::clone(&reporter), client, receiver));
+        AmethystLogger::set_hook(
+            Level::Error,
+            Box::new(move |record| {
+                let target = record.target();
+                if IGNORED_TARGETS
+                    .iter()
+                    .any(|ignored| target.starts_with(ignored))
+                {
+                    return;
+                }
+                let _ = queue.try_send((target.to_string(), record.args().to_string()));
+            }),
+        );
+    }
+    let _ = REPORTER.set(reporter);
+    Ok(())
+}
+
+/// Reports a panic, waiting a few seconds for it to be sent. Does nothing unless [`init`]
+/// succeeded.
+pub fn report_panic(message: &str, thread_name: &str, backtrace: &Backtrace) {
+    let Some(reporter) = REPORTER.get() else {
+        return;
+    };
+    let event = reporter.panic_event(message, thread_name, &backtrace.to_string());
+
+    // The panicking thread may be a runtime worker, which cannot block on another runtime.
+    let reporter = Arc::clone(reporter);
+    let (done, result) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let sent = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(TelemetryError::Runtime)
+            .and_then(|runtime| {
+                runtime.block_on(async { reporter.send(&client()?, &event).await })
+            });
+        let _ = done.send(sent);
+    });
+    match result.recv_timeout(PANIC_REPORT_TIMEOUT) {
+        Ok(Ok(())) => eprintln!("Crash reported"),
+        Ok(Err(e)) => eprintln!("Failed to report the crash: {}", e),
+        Err(_) => eprintln!("Timed out reporting the crash"),
+    }
+}
+
+fn client() -> Result<Client, TelemetryError> {
+    Ok(Client::builder().timeout(REQUEST_TIMEOUT).build()?)
+}
+
+async fn send_errors(
+    reporter: Arc<Reporter>,
+    client: Client,
+    mut queue: mpsc::Receiver<(String, String)>,
+) {
+    let mut window_start = Instant::now();
+    let mut sent = 0;
+    while let Some((target, message)) = queue.recv().await {
+        if window_start.elapsed() >= ERROR_WINDOW {
+            window_start = Instant::now();
+            sent = 0;
+        }
+        if sent == MAX_ERRORS_PER_WINDOW {
+            continue;
+        }
+        sent += 1;
+        let event = reporter.event("error", &target, &message);
+        if let Err(e) = reporter.send(&client, &event).await {
+            warn!("Failed to report an error: {}", e);
+        }
+    }
+}
+
+/// Reads the instance ID, generating it on first use.
+fn instance_id() -> io::Result<String> {
+    match fs::read_to_string(INSTANCE_ID_FILE) {
+        Ok(id) if !id.trim().is_empty() => Ok(id.trim().to_string()),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => {
+            let id = hex::encode(rand::random::<[u8; 16]>());
+            fs::write(INSTANCE_ID_FILE, &id)?;
+            Ok(id)
+        }
+    }
+}
+
+impl Reporter {
+    /// A reporter for the DSN in `config.telemetry`, which scrubs the secrets of `config`.
+    pub fn new(config: &Config, instance_id: String) -> Result<Self, TelemetryError> {
+        let dsn = config.telemetry.dsn()?;
+        let secrets = [
+            &config.admin.token,
+            &config.discord.token,
+            &config.telemetry.dsn,
+            &dsn.public_key,
+        ]
+        .into_iter()
+        .chain(config.otlp.headers.values())
+        .filter(|secret| !secret.is_empty())
+        .cloned()
+        .collect();
+        Ok(Self {
+            dsn,
+            instance_id,
+            secrets,
+        })
+    }
+
+    /// The report of an error logged by `logger`.
+    pub fn event(&self, level: &str, logger: &str, message: &str) -> Value {
+        json!({
+            "event_id": hex::encode(rand::random::<[u8; 16]>()),
+            "timestamp": Utc::now().to_rfc3339(),
+            "platform": "native",
+            "level": level,
+            "logger": logger,
+            "release": concat!("amethyst@", env!("CARGO_PKG_VERSION")),
+            "message": { "formatted": self.scrub(message) },
+            "tags": { "instance_id": self.instance_id },
+            "contexts": {
+                "os": { "name": std::env::consts::OS },
+                "device": { "arch": std::env::consts::ARCH },
+            },
+        })
+    }
+
+    /// The report of a panic on the thread `thread_name`.
+    pub fn panic_event(&self, message: &str, thread_name: &str, backtrace: &str) -> Value {
+        let mut event = self.event("fatal", "panic", message);
+        event["tags"]["thread"] = json!(thread_name);
+        event["exception"] = json!({
+            "values": [{
+                "type": "panic",
+                "value": self.scrub(message),
+                "mechanism": { "type": "panic", "handled": false },
+            }],
+        });
+        event["extra"] = json!({ "backtrace": self.scrub(backtrace) });
+        event
+    }
+
+    async fn send(&self, client: &Client, event: &Value) -> Result<(), TelemetryError> {
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_client=amethyst/{}, sentry_key={}",
+            env!("CARGO_PKG_VERSION"),
+            self.dsn.public_key
+        );
+        let response = client
+            .post(&self.dsn.store_url)
+            .header("X-Sentry-Auth", auth)
+            .json(event)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(TelemetryError::Status(response.status()));
+        }
+        Ok(())
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        let text = self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        });
+        scrub_addresses(&text)
+    }
+}
+
+/// Replaces IP addresses, with or without a port, and runs of ten or more digits.
+fn scrub_addresses(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_address_char) {
+        scrubbed.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_address_char(c)).unwrap_or(rest.len());
+        let run = &rest[..end];
+        // Addresses at the end of a sentence are followed by punctuation.
+        let token = run.trim_end_matches(['.', ':']);
+        let sensitive = token.parse::<SocketAddr>().is_ok()
+            || token
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| looks_like_address(token, ip))
+            || (token.len() >= 10 && token.bytes().all(|b| b.is_ascii_digit()));
+        scrubbed.push_str(if sensitive { REDACTED } else { token });
+        scrubbed.push_str(&run[token.len()..]);
+        rest = &rest[end..];
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+/// Short IPv6 addresses such as `::c` also match Rust paths like `std::collections`.
+fn looks_like_address(token: &str, ip: IpAddr) -> bool {
+    ip.is_ipv4() || token.matches(':').count() >= 3
+}
+
+fn is_address_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || matches!(c, '.' | ':' | '[' | ']')
+}
@@ -0,0 +1,271 @@
+use crate::health::Health;
+use crate::shutdown::Shutdown;
+use crate::timings::Timings;
+use amethyst_plugin::scheduler::{Scheduler, TICKS_PER_SECOND, TICK_DURATION};
+use dashmap::DashMap;
+use log::{debug, warn};
+use rakethyst::connection::Connection;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How far the tick loop may fall behind before skipping the missed ticks.
+const MAX_LAG: Duration = Duration::from_secs(2);
+
+/// How long the server must have had no clients before it goes idle, so it does not flap
+/// between modes while a client reconnects.
+const IDLE_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest the loop sleeps while idle, which bounds how late it notices a shutdown.
+const IDLE_WAKE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Windows the TPS is averaged over, as in `tps`: 1, 5 and 15 minutes, in seconds.
+pub const TPS_WINDOWS: [u64; 3] = [60, 300, 900];
+
+/// How many of the latest ticks the tick time is averaged over.
+const TICK_TIME_SAMPLES: usize = 100;
+
+/// Ticks run and how long they took, recorded by the tick loop for `tps` and the admin API.
+pub struct TickStats {
+    started: Instant,
+    window: Mutex<TickWindow>,
+}
+
+#[derive(Default)]
+struct TickWindow {
+    /// Ticks run in each second since `started`, oldest first, the last one still counting.
+    per_second: VecDeque<u32>,
+    /// The second since `started` the last entry of `per_second` counts.
+    second: u64,
+    /// Durations of the latest ticks, oldest first.
+    durations: VecDeque<Duration>,
+    /// When the tick in progress started, if one is.
+    running_since: Option<Instant>,
+}
+
+impl TickWindow {
+    /// Starts counting `second`, with no ticks in the seconds skipped to get there.
+    fn advance_to(&mut self, second: u64) {
+        let longest = TPS_WINDOWS[TPS_WINDOWS.len() - 1] as usize;
+        if self.per_second.is_empty() {
+            self.per_second.push_back(0);
+            self.second = second;
+        }
+        let skipped = second.saturating_sub(self.second).min(longest as u64 + 1);
+        for _ in 0..skipped {
+            self.per_second.push_back(0);
+        }
+        self.second = self.second.max(second);
+        // The second still counting is kept on top of the longest window.
+        while self.per_second.len() > longest + 1 {
+            self.per_second.pop_front();
+        }
+    }
+}
+
+/// A snapshot of [`TickStats`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickReport {
+    /// Ticks per second over each of [`TPS_WINDOWS`], or as much of it as the server has run.
+    /// Capped at [`TICKS_PER_SECOND`], which catching up on missed ticks would exceed.
+    pub tps: [f64; 3],
+    /// Mean duration of the latest ticks.
+    pub mean_tick_time: Duration,
+    /// Longest of the latest ticks.
+    pub max_tick_time: Duration,
+}
+
+impl Default for TickStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TickStats {
+    pub fn new() -> Self {
+        TickStats {
+            started: Instant::now(),
+            window: Mutex::default(),
+        }
+    }
+
+    fn start_tick(&self) -> Instant {
+        let start = Instant::now();
+        self.lock().running_since = Some(start);
+        start
+    }
+
+    fn finish_tick(&self, start: Instant) -> Duration {
+        let duration = start.elapsed();
+        let second = start.saturating_duration_since(self.started).as_secs();
+        let mut window = self.lock();
+        window.running_since = None;
+        window.advance_to(second);
+        if let Some(ticks) = window.per_second.back_mut() {
+            *ticks += 1;
+        }
+        window.durations.push_back(duration);
+        if window.durations.len() > TICK_TIME_SAMPLES {
+            window.durations.pop_front();
+        }
+        duration
+    }
+
+    /// How long the tick in progress has been running, `None` between ticks.
+    pub fn running_for(&self) -> Option<Duration> {
+        self.lock().running_since.map(|start| start.elapsed())
+    }
+
+    pub fn report(&self) -> TickReport {
+        let mut window = self.lock();
+        window.advance_to(self.started.elapsed().as_secs());
+        // Only whole seconds count, so leave out the one still counting.
+        let complete = window.per_second.len() - 1;
+        let tps = TPS_WINDOWS.map(|seconds| {
+            let seconds = complete.min(seconds as usize);
+            if seconds == 0 {
+                return TICKS_PER_SECOND as f64;
+            }
+            let ticks: u64 = window
+                .per_second
+                .iter()
+                .take(complete)
+                .skip(complete - seconds)
+                .map(|&ticks| u64::from(ticks))
+                .sum();
+            (ticks as f64 / seconds as f64).min(TICKS_PER_SECOND as f64)
+        });
+        let samples = window.durations.len().max(1) as u32;
+        TickReport {
+            tps,
+            mean_tick_time: window.durations.iter().sum::<Duration>() / samples,
+            max_tick_time: window.durations.iter().copied().max().unwrap_or_default(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TickWindow> {
+        self.window.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Brings the tick loop out of idle mode as soon as a client shows up.
+#[derive(Default)]
+pub struct IdleWaker {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl IdleWaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wake(&self) {
+        *self.woken.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.condvar.notify_one();
+    }
+
+    /// Sleeps for up to `timeout`. Returns `true` if [`wake`](Self::wake) was called
+    /// meanwhile, or since the last sleep.
+    fn sleep(&self, timeout: Duration) -> bool {
+        let woken = self.woken.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut woken, _) = self
+            .condvar
+            .wait_timeout_while(woken, timeout, |woken| !*woken)
+            .unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut *woken)
+    }
+}
+
+/// Runs the scheduler on the `amethyst-tick` thread,
+/// [`TICKS_PER_SECOND`](amethyst_plugin::TICKS_PER_SECOND) times a second, until a shutdown
+/// is requested.
+///
+/// While no client is connected, the thread only wakes when a scheduled task is due, and
+/// then runs the ticks it slept through in one go, so tasks keep their timing. `waker`
+/// returns it to full speed at once.
+///
+/// Every tick is recorded in `stats`, broken down in `timings` and counts as a heartbeat for
+/// `/healthz` in `health`. With the `systemd` feature, the loop also pets the systemd
+/// watchdog, so a stuck tick gets the server restarted.
+pub fn spawn(
+    scheduler: Arc<Scheduler>,
+    stats: Arc<TickStats>,
+    timings: Arc<Timings>,
+    health: Arc<Health>,
+    shutdown: Arc<Shutdown>,
+    connections: Arc<DashMap<SocketAddr, Connection>>,
+    waker: Arc<IdleWaker>,
+) -> io::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("amethyst-tick".into())
+        .spawn(move || {
+            let mut next_tick = Instant::now();
+            let mut last_active = Instant::now();
+            let mut idle = false;
+            #[cfg(all(feature = "systemd", unix))]
+            let mut systemd_watchdog = crate::systemd::Watchdog::from_env();
+            while !shutdown.is_requested() {
+                #[cfg(all(feature = "systemd", unix))]
+                if let Some(watchdog) = &mut systemd_watchdog {
+                    watchdog.pet();
+                }
+                let now = Instant::now();
+                if !connections.is_empty() {
+                    last_active = now;
+                }
+                if now - last_active >= IDLE_DELAY {
+                    if !idle {
+                        debug!("No clients connected, ticking only for scheduled tasks");
+                        idle = true;
+                    }
+                    if waker.sleep(idle_wait(&scheduler, next_tick)) {
+                        last_active = Instant::now();
+                    }
+                    let now = Instant::now();
+                    while next_tick <= now {
+                        run_tick(&scheduler, &stats, &timings, &health);
+                        next_tick += TICK_DURATION;
+                    }
+                    continue;
+                }
+                if idle {
+                    debug!("Client activity, ticking at full speed");
+                    idle = false;
+                }
+
+                if let Some(wait) = next_tick.checked_duration_since(now) {
+                    thread::sleep(wait);
+                } else if now - next_tick > MAX_LAG {
+                    let behind = (now - next_tick).as_millis() / TICK_DURATION.as_millis();
+                    warn!("Can't keep up! Skipping {} ticks", behind);
+                    next_tick = now;
+                }
+                run_tick(&scheduler, &stats, &timings, &health);
+                next_tick += TICK_DURATION;
+            }
+        })
+}
+
+fn run_tick(scheduler: &Scheduler, stats: &TickStats, timings: &Timings, health: &Health) {
+    let start = stats.start_tick();
+    scheduler.tick();
+    let duration = stats.finish_tick(start);
+    timings.record_tick(duration, scheduler.take_task_times());
+    health.beat();
+}
+
+/// How long the idle loop may sleep: until the tick the next task is due on, if that comes
+/// before [`IDLE_WAKE_INTERVAL`]. `next_tick` is when the next tick would run.
+fn idle_wait(scheduler: &Scheduler, next_tick: Instant) -> Duration {
+    let mut wake = Instant::now() + IDLE_WAKE_INTERVAL;
+    if let Some(due) = scheduler.next_due() {
+        let ticks_before = due.saturating_sub(scheduler.current_tick() + 1);
+        let ticks_before = u32::try_from(ticks_before).unwrap_or(u32::MAX);
+        wake = wake.min(next_tick + TICK_DURATION.saturating_mul(ticks_before));
+    }
+    wake.saturating_duration_since(Instant::now())
+}
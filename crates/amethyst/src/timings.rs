@@ -0,0 +1,233 @@
+//! Where tick time goes, broken down into sections: the tasks of each plugin and script, the
+//! server's own tasks, and everything else the tick does. Configured by
+//! [`TimingsConfig`](crate::config::TimingsConfig) and shown by the `timings` command.
+
+use crate::config::TimingsConfig;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Section for the part of the tick outside scheduled tasks.
+pub const OTHER: &str = "other";
+/// Section for tasks scheduled by the server itself.
+pub const SERVER_TASKS: &str = "server tasks";
+
+/// Shortest time between two long tick breakdowns in the log, so a server that keeps lagging
+/// does not flood it.
+const LONG_TICK_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Timings {
+    config: RwLock<TimingsConfig>,
+    window: Mutex<TimingsWindow>,
+}
+
+/// Everything recorded since the last report or reset.
+struct TimingsWindow {
+    since: Instant,
+    ticks: u64,
+    tick_time: Duration,
+    longest_tick: Duration,
+    long_ticks: u64,
+    sections: HashMap<String, SectionTimes>,
+    last_long_tick_log: Option<Instant>,
+}
+
+impl TimingsWindow {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            ticks: 0,
+            tick_time: Duration::ZERO,
+            longest_tick: Duration::ZERO,
+            long_ticks: 0,
+            sections: HashMap::new(),
+            last_long_tick_log: None,
+        }
+    }
+}
+
+/// Time one section took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SectionTimes {
+    pub total: Duration,
+    /// Longest the section took in a single tick.
+    pub longest: Duration,
+}
+
+/// A snapshot of [`Timings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingsReport {
+    /// How long the report covers.
+    pub period: Duration,
+    pub ticks: u64,
+    pub tick_time: Duration,
+    pub longest_tick: Duration,
+    /// Ticks that took `long_tick_threshold` or longer.
+    pub long_ticks: u64,
+    /// Most time-consuming first.
+    pub sections: Vec<(String, SectionTimes)>,
+}
+
+impl TimingsReport {
+    /// Mean time per tick spent in `times`.
+    pub fn per_tick(&self, times: &SectionTimes) -> Duration {
+        times.total / self.ticks.max(1) as u32
+    }
+
+    /// Share of the tick time spent in `times`, in percent.
+    pub fn share(&self, times: &SectionTimes) -> f64 {
+        if self.tick_time.is_zero() {
+            return 0.0;
+        }
+        times.total.as_secs_f64() / self.tick_time.as_secs_f64() * 100.0
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = format!(
+            "Timings over the last {} s: {} ticks, {:.2} ms mean, {:.2} ms longest, {} long",
+            self.period.as_secs(),
+            self.ticks,
+            millis(self.tick_time / self.ticks.max(1) as u32),
+            millis(self.longest_tick),
+            self.long_ticks
+        );
+        for (name, times) in &self.sections {
+            let _ = write!(
+                output,
+                "\n  {}: {:.3} ms per tick ({:.1}%), {:.2} ms longest",
+                name,
+                millis(self.per_tick(times)),
+                self.share(times),
+                millis(times.longest)
+            );
+        }
+        output
+    }
+}
+
+impl Timings {
+    pub fn new(config: TimingsConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            window: Mutex::new(TimingsWindow::new()),
+        }
+    }
+
+    /// Applies the thresholds of a reloaded configuration.
+    pub fn set_config(&self, config: TimingsConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Records a tick that took `duration`, `task_times` of it in the tasks of each owner as
+    /// returned by [`Scheduler::take_task_times`](amethyst_plugin::Scheduler::take_task_times).
+    /// Logs the breakdown of a long tick, and the report once `report_interval` has passed.
+    pub fn record_tick(&self, duration: Duration, task_times: Vec<(Option<Arc<str>>, Duration)>) {
+        let config = self
+            .config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let in_tasks: Duration = task_times.iter().map(|(_, time)| *time).sum();
+        let mut sections: Vec<(String, Duration)> = task_times
+            .into_iter()
+            .map(|(owner, time)| (section_name(owner.as_deref()), time))
+            .collect();
+        sections.push((OTHER.to_string(), duration.saturating_sub(in_tasks)));
+
+        let threshold = Duration::from_millis(config.long_tick_threshold);
+        let long = config.long_tick_threshold > 0 && duration >= threshold;
+        let mut log_breakdown = false;
+        let mut report = None;
+        {
+            let mut window = self.lock();
+            window.ticks += 1;
+            window.tick_time += duration;
+            window.longest_tick = window.longest_tick.max(duration);
+            for (name, time) in &sections {
+                let times = window.sections.entry(name.clone()).or_default();
+                times.total += *time;
+                times.longest = times.longest.max(*time);
+            }
+            if long {
+                window.long_ticks += 1;
+                log_breakdown = window
+                    .last_long_tick_log
+                    .is_none_or(|last| last.elapsed() >= LONG_TICK_LOG_INTERVAL);
+                if log_breakdown {
+                    window.last_long_tick_log = Some(Instant::now());
+                }
+            }
+            let interval = Duration::from_secs(config.report_interval);
+            if config.report_interval > 0 && window.since.elapsed() >= interval {
+                report = Some(snapshot(&window));
+                let last_long_tick_log = window.last_long_tick_log;
+                *window = TimingsWindow::new();
+                window.last_long_tick_log = last_long_tick_log;
+            }
+        }
+
+        if log_breakdown {
+            sections.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+            let breakdown: Vec<String> = sections
+                .iter()
+                .map(|(name, time)| format!("{} {:.2} ms", name, millis(*time)))
+                .collect();
+            warn!(
+                "Tick took {:.2} ms: {}",
+                millis(duration),
+                breakdown.join(", ")
+            );
+        }
+        if let Some(report) = report {
+            info!("{}", report.render());
+        }
+    }
+
+    /// What was recorded since the last report or reset.
+    pub fn report(&self) -> TimingsReport {
+        snapshot(&self.lock())
+    }
+
+    /// Starts recording from scratch.
+    pub fn reset(&self) {
+        *self.lock() = TimingsWindow::new();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TimingsWindow> {
+        self.window.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn snapshot(window: &TimingsWindow) -> TimingsReport {
+    let mut sections: Vec<(String, SectionTimes)> = window
+        .sections
+        .iter()
+        .map(|(name, times)| (name.clone(), *times))
+        .collect();
+    sections.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+    TimingsReport {
+        period: window.since.elapsed(),
+        ticks: window.ticks,
+        tick_time: window.tick_time,
+        longest_tick: window.longest_tick,
+        long_ticks: window.long_ticks,
+        sections,
+    }
+}
+
+/// Scripts schedule their tasks as `script:<name>`, plugins under their own name.
+fn section_name(owner: Option<&str>) -> String {
+    match owner {
+        Some(owner) => match owner.strip_prefix("script:") {
+            Some(script) => format!("script {}", script),
+            None => format!("plugin {}", owner),
+        },
+        None => SERVER_TASKS.to_string(),
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
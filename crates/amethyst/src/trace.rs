@@ -0,0 +1,13 @@
+//! Spans around logins, chunk radius negotiation and command executions, exported by
+//! [`otlp`](crate::otlp). They are only compiled in with the `otlp` feature and are otherwise
+//! nothing at all.
+
+/// Enters a span that lasts until the end of the enclosing block.
+macro_rules! span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "otlp")]
+        let _span = tracing::info_span!($($arg)+).entered();
+    };
+}
+
+pub(crate) use span;
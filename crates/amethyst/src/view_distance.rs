@@ -0,0 +1,95 @@
+//! Per-player view distance. Clients ask for a radius with [`RequestChunkRadius`] and are
+//! answered with [`ChunkRadiusUpdated`], carrying the radius they asked for clamped to
+//! `server.view_distance`. Chunks are sent to each player within their own radius.
+
+use crate::config::MIN_VIEW_DISTANCE;
+use crate::protocol::{ChunkRadiusUpdated, RequestChunkRadius};
+use crate::trace;
+use amethyst_plugin::event::{EventPriority, PlayerQuit};
+use amethyst_plugin::EventBus;
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+pub struct ViewDistances {
+    /// `server.view_distance`, the largest radius any player gets.
+    max: AtomicU32,
+    /// Radius negotiated by each player that has asked for one.
+    players: DashMap<SocketAddr, u32>,
+}
+
+impl ViewDistances {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max: AtomicU32::new(max),
+            players: DashMap::new(),
+        }
+    }
+
+    /// Forgets the radius of players as they leave.
+    pub fn register_events(self: &Arc<Self>, events: &EventBus) {
+        let view_distances = Arc::clone(self);
+        events.subscribe(EventPriority::Monitor, move |quit: &mut PlayerQuit| {
+            view_distances.players.remove(&quit.address);
+        });
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Applies a new `server.view_distance`. Players above it are lowered to it, and are
+    /// returned so they can be sent a [`ChunkRadiusUpdated`]. Players below it keep their
+    /// radius until they ask again.
+    pub fn set_max(&self, max: u32) -> Vec<SocketAddr> {
+        self.max.store(max, Ordering::Relaxed);
+        let mut lowered = Vec::new();
+        for mut player in self.players.iter_mut() {
+            if *player > max {
+                *player = max;
+                lowered.push(*player.key());
+            }
+        }
+        lowered
+    }
+
+    /// Answers a player's request, remembering the radius they got.
+    pub fn negotiate(
+        &self,
+        address: SocketAddr,
+        request: &RequestChunkRadius,
+    ) -> ChunkRadiusUpdated {
+        trace::span!("chunk_radius", %address, requested = request.radius);
+        let radius = self.clamp(request.radius);
+        self.players.insert(address, radius);
+        ChunkRadiusUpdated {
+            radius: radius as i32,
+        }
+    }
+
+    /// Radius chunks are sent to `address` in. Players that have not asked yet get the maximum.
+    pub fn get(&self, address: SocketAddr) -> u32 {
+        self.players
+            .get(&address)
+            .map(|radius| *radius)
+            .unwrap_or_else(|| self.max())
+    }
+
+    /// Radius of each player that has asked for one.
+    pub fn players(&self) -> Vec<(SocketAddr, u32)> {
+        self.players
+            .iter()
+            .map(|player| (*player.key(), *player.value()))
+            .collect()
+    }
+
+    /// Clients below the minimum could not see the chunk they stand in, so they are raised to it,
+    /// unless the server maximum is lower still.
+    fn clamp(&self, requested: i32) -> u32 {
+        let max = self.max();
+        u32::try_from(requested)
+            .unwrap_or(0)
+            .clamp(MIN_VIEW_DISTANCE.min(max), max)
+    }
+}
@@ -0,0 +1,134 @@
+//! Detection of ticks that never finish, like the vanilla watchdog.
+
+use crate::config::{WatchdogAction, WatchdogConfig};
+use crate::shutdown::Shutdown;
+use crate::tick::TickStats;
+use amethyst_log::AmethystLogger;
+use amethyst_plugin::Scheduler;
+use log::{error, info, warn};
+use std::fmt::Write as _;
+use std::io;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+/// How often the watchdog looks at the tick in progress.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Exit code for [`WatchdogAction::Exit`].
+const STALL_EXIT_CODE: i32 = 1;
+
+/// Watches the tick loop on the `amethyst-watchdog` thread until a shutdown is requested.
+///
+/// Once a tick has run for longer than the configured timeout, logs what it is stuck on and
+/// takes the configured action. A stalled tick is only reported once.
+pub fn spawn(
+    config: WatchdogConfig,
+    scheduler: Arc<Scheduler>,
+    stats: Arc<TickStats>,
+    shutdown: Arc<Shutdown>,
+    runtime: Handle,
+) -> io::Result<JoinHandle<()>> {
+    let timeout = Duration::from_secs(config.timeout);
+    thread::Builder::new()
+        .name("amethyst-watchdog".into())
+        .spawn(move || {
+            // Tick number of the stalled tick last reported.
+            let mut reported = None;
+            while !shutdown.is_requested() {
+                thread::sleep(CHECK_INTERVAL);
+                let tick = scheduler.current_tick();
+                let Some(running_for) = stats.running_for() else {
+                    if let Some(tick) = reported.take() {
+                        info!("Tick {} finished, the server is responding again", tick);
+                    }
+                    continue;
+                };
+                if running_for < timeout || reported == Some(tick) {
+                    continue;
+                }
+                reported = Some(tick);
+                error!("{}", stall_report(tick, running_for, &scheduler, &runtime));
+                match config.action {
+                    WatchdogAction::Warn => {}
+                    WatchdogAction::Shutdown => {
+                        warn!("Stopping the server because the tick loop is stuck");
+                        shutdown.request();
+                    }
+                    WatchdogAction::Exit => {
+                        error!("Exiting because the tick loop is stuck");
+                        AmethystLogger::flush_blocking(Duration::from_secs(2));
+                        std::process::exit(STALL_EXIT_CODE);
+                    }
+                }
+            }
+        })
+}
+
+/// Describes a stalled tick: the scheduled task it is running, the state of the async
+/// runtime and, on Linux, the state of every thread.
+///
+/// Rust cannot capture the stack of another thread, so the running task is the closest to a
+/// backtrace of the tick thread there is.
+fn stall_report(
+    tick: u64,
+    running_for: Duration,
+    scheduler: &Scheduler,
+    runtime: &Handle,
+) -> String {
+    let mut report = format!(
+        "The server has not responded for {} seconds! Tick {} is still running",
+        running_for.as_secs(),
+        tick
+    );
+    match scheduler.running_task() {
+        Some(task) => {
+            let owner = task.owner.as_deref().unwrap_or("the server");
+            let _ = write!(
+                report,
+                "\n  Running task {:?} scheduled by {}",
+                task.id, owner
+            );
+        }
+        None => report
+            .push_str("\n  No scheduled task is running, the tick is stuck in the server itself"),
+    }
+    let metrics = runtime.metrics();
+    let _ = write!(
+        report,
+        "\n  Async runtime: {} tasks alive, worker threads: {}",
+        metrics.num_alive_tasks(),
+        metrics.num_workers()
+    );
+    #[cfg(target_os = "linux")]
+    report.push_str(&thread_dump());
+    report
+}
+
+/// Name and scheduler state of every thread of the process, read from `/proc`.
+#[cfg(target_os = "linux")]
+fn thread_dump() -> String {
+    let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+        return String::new();
+    };
+    let mut dump = String::from("\n  Threads:");
+    for task in tasks.flatten() {
+        let path = task.path();
+        let name = std::fs::read_to_string(path.join("comm")).unwrap_or_default();
+        // The state follows the name, which is in parentheses and may contain spaces.
+        let stat = std::fs::read_to_string(path.join("stat")).unwrap_or_default();
+        let state = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+            .unwrap_or("?");
+        let _ = write!(
+            dump,
+            "\n    {} {} ({})",
+            task.file_name().to_string_lossy(),
+            name.trim(),
+            state
+        );
+    }
+    dump
+}
@@ -0,0 +1,204 @@
+//! Whitelist, operator, ban and mute lists, backed by files in a temporary directory.
+
+use amethyst::access::{
+    AccessLists, BanDetails, IpBan, LoginDenied, PlayerBan, PlayerEntry, BANNED_PLAYERS_FILE_NAME,
+};
+use amethyst_plugin::event::{PlayerJoin, PlayerLoginDenied};
+use amethyst_plugin::{EventBus, EventPriority};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const IP: &str = "203.0.113.7";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("amethyst-access-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn lists(name: &str, whitelist_enabled: bool) -> AccessLists {
+    AccessLists::load(&temp_dir(name), whitelist_enabled, "Not whitelisted".to_string()).unwrap()
+}
+
+fn player(name: &str, xuid: Option<&str>) -> PlayerEntry {
+    PlayerEntry {
+        name: name.to_string(),
+        xuid: xuid.map(str::to_string),
+    }
+}
+
+fn ban(expires_in: Option<Duration>) -> BanDetails {
+    BanDetails::new(
+        "Server".to_string(),
+        "Griefing".to_string(),
+        expires_in.map(|duration| Utc::now() + duration),
+    )
+}
+
+fn ip() -> IpAddr {
+    IP.parse().unwrap()
+}
+
+#[test]
+fn entries_match_by_xuid_when_both_are_known() {
+    let entry = player("Steve", Some("1"));
+    assert!(entry.matches("Renamed", Some("1")));
+    assert!(!entry.matches("Steve", Some("2")));
+    assert!(entry.matches("STEVE", None));
+    assert!(player("Steve", None).matches("steve", Some("1")));
+    assert!(!player("Steve", None).matches("Alex", None));
+}
+
+#[test]
+fn bans_are_checked_before_the_whitelist() {
+    let lists = lists("ban-order", true);
+    lists.add_to_whitelist(player("Steve", None)).unwrap();
+    lists.op(player("Steve", None), 4).unwrap();
+    lists.ban_player(player("Steve", None), ban(None)).unwrap();
+    let denied = lists.check_login("Steve", None, ip()).unwrap_err();
+    assert!(matches!(denied, LoginDenied::Banned(_)));
+    assert_eq!(denied.reason(), "banned");
+    assert!(denied.message().contains("Reason: Griefing"));
+}
+
+#[test]
+fn ip_bans_apply_to_every_name() {
+    let lists = lists("ip-ban", false);
+    lists.ban_ip(ip(), ban(None)).unwrap();
+    let denied = lists.check_login("Alex", Some("2"), ip()).unwrap_err();
+    assert_eq!(denied.reason(), "ip_banned");
+    assert!(lists.check_login("Alex", Some("2"), "203.0.113.8".parse().unwrap()).is_ok());
+}
+
+#[test]
+fn the_whitelist_lets_in_listed_players_and_operators() {
+    let lists = lists("whitelist", true);
+    lists.add_to_whitelist(player("Steve", Some("1"))).unwrap();
+    lists.op(player("Alex", None), 1).unwrap();
+    assert!(lists.check_login("Steve", Some("1"), ip()).is_ok());
+    assert!(lists.check_login("alex", None, ip()).is_ok());
+    let denied = lists.check_login("Herobrine", None, ip()).unwrap_err();
+    assert_eq!(denied.reason(), "not_whitelisted");
+    assert_eq!(denied.message(), "Not whitelisted");
+
+    lists.set_whitelist_enabled(false);
+    assert!(lists.check_login("Herobrine", None, ip()).is_ok());
+}
+
+#[test]
+fn expired_bans_are_ignored_and_pruned() {
+    let lists = lists("expiry", false);
+    lists
+        .ban_player(player("Steve", None), ban(Some(-Duration::minutes(1))))
+        .unwrap();
+    lists
+        .ban_player(player("Alex", None), ban(Some(Duration::hours(1))))
+        .unwrap();
+    lists.ban_ip(ip(), ban(Some(-Duration::seconds(1)))).unwrap();
+    lists
+        .mute_player(player("Steve", None), ban(Some(-Duration::minutes(1))))
+        .unwrap();
+
+    assert!(!ban(Some(-Duration::seconds(1))).is_active());
+    assert!(lists.check_login("Steve", None, ip()).is_ok());
+    assert!(lists.player_ban("Alex", None).is_some());
+    assert!(lists.player_mute("Steve", None).is_none());
+
+    assert_eq!(lists.remove_expired_bans().unwrap(), 3);
+    assert_eq!(lists.banned_players.entries().len(), 1);
+    assert!(lists.banned_ips.entries().is_empty());
+    assert_eq!(lists.remove_expired_bans().unwrap(), 0);
+}
+
+#[test]
+fn mutes_match_like_bans() {
+    let lists = lists("mutes", false);
+    lists.mute_player(player("Steve", Some("1")), ban(None)).unwrap();
+    assert!(lists.player_mute("Renamed", Some("1")).is_some());
+    assert!(lists.unmute_player(&player("Renamed", Some("1"))).unwrap());
+    assert!(lists.player_mute("Steve", Some("1")).is_none());
+}
+
+#[test]
+fn bans_are_stored_flat() {
+    let player_ban = PlayerBan {
+        player: player("Steve", Some("1")),
+        details: ban(None),
+    };
+    let value = serde_json::to_value(&player_ban).unwrap();
+    assert_eq!(value["name"], "Steve");
+    assert_eq!(value["xuid"], "1");
+    assert_eq!(value["reason"], "Griefing");
+    let parsed: PlayerBan = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed.player, player_ban.player);
+    assert_eq!(parsed.details.created, player_ban.details.created);
+
+    let ip_ban: IpBan = serde_json::from_value(json!({
+        "ip": IP,
+        "created": "2026-01-01T00:00:00Z",
+        "source": "Server",
+        "reason": "",
+    }))
+    .unwrap();
+    assert_eq!(ip_ban.ip, ip());
+    assert_eq!(ip_ban.details.expires, None);
+    let value = serde_json::to_value(&ip_ban).unwrap();
+    assert_eq!(value["ip"], IP);
+    assert_eq!(value["source"], "Server");
+}
+
+#[test]
+fn edits_to_the_files_are_picked_up_on_reload() {
+    let dir = temp_dir("reload");
+    let lists = AccessLists::load(&dir, false, String::new()).unwrap();
+    std::fs::write(
+        dir.join(BANNED_PLAYERS_FILE_NAME),
+        json!([{
+            "name": "Steve",
+            "created": "2026-01-01T00:00:00Z",
+            "source": "Console",
+            "reason": "",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+    lists.banned_players.reload().unwrap();
+    assert!(lists.player_ban("steve", None).is_some());
+
+    std::fs::write(dir.join(BANNED_PLAYERS_FILE_NAME), "not json").unwrap();
+    assert!(lists.banned_players.reload().is_err());
+    assert!(lists.player_ban("steve", None).is_some());
+}
+
+#[test]
+fn refused_joins_are_cancelled_and_reported() {
+    let lists = Arc::new(lists("events", true));
+    let events = Arc::new(EventBus::new());
+    lists.register_events(&events);
+    let denied = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&denied);
+    events.subscribe(EventPriority::Monitor, move |event: &mut PlayerLoginDenied| {
+        seen.lock().unwrap().push(event.reason.clone());
+    });
+
+    let join = |name: &str| PlayerJoin {
+        name: name.to_string(),
+        xuid: None,
+        uuid: String::new(),
+        authenticated: true,
+        address: SocketAddr::new(ip(), 19132),
+        kick_message: String::new(),
+        cancelled: false,
+    };
+    let refused = events.post(join("Herobrine"));
+    assert!(refused.cancelled);
+    assert_eq!(refused.kick_message, "Not whitelisted");
+
+    lists.add_to_whitelist(player("Steve", None)).unwrap();
+    assert!(!events.post(join("Steve")).cancelled);
+    assert_eq!(*denied.lock().unwrap(), ["not_whitelisted"]);
+}
@@ -0,0 +1,80 @@
+//! Game packet batches around the compression threshold.
+
+use amethyst::protocol::batch::{BatchCodec, BatchError, Compression};
+use bytes::Bytes;
+use rakethyst::protocol::GAME_PACKET;
+
+const THRESHOLD: u16 = 256;
+
+fn negotiated(threshold: u16) -> BatchCodec {
+    let mut codec = BatchCodec::new(threshold);
+    codec.negotiated();
+    codec
+}
+
+/// Packets whose length prefixes and contents add up to `size` bytes.
+fn packets_of(size: usize) -> Vec<Bytes> {
+    // A short packet, then one long enough for a two-byte length prefix.
+    vec![
+        Bytes::from_static(&[0x01, 0x02]),
+        Bytes::from(vec![0x03; size - 3 - 2]),
+    ]
+}
+
+#[test]
+fn batches_below_the_threshold_are_sent_raw() {
+    let codec = negotiated(THRESHOLD);
+    let packets = packets_of(usize::from(THRESHOLD) - 1);
+    let batch = codec.encode(&packets).unwrap();
+    assert_eq!(batch[..2], [GAME_PACKET, Compression::None.id()]);
+    assert_eq!(batch.len(), 2 + usize::from(THRESHOLD) - 1);
+    assert_eq!(codec.decode(&batch).unwrap(), packets);
+}
+
+#[test]
+fn batches_at_or_above_the_threshold_are_deflated() {
+    let codec = negotiated(THRESHOLD);
+    for size in [usize::from(THRESHOLD), usize::from(THRESHOLD) + 1] {
+        let packets = packets_of(size);
+        let batch = codec.encode(&packets).unwrap();
+        assert_eq!(batch[..2], [GAME_PACKET, Compression::Flate.id()]);
+        assert!(batch.len() < size, "{} bytes did not shrink", size);
+        assert_eq!(codec.decode(&batch).unwrap(), packets);
+    }
+}
+
+#[test]
+fn a_zero_threshold_never_compresses() {
+    let codec = negotiated(0);
+    let packets = packets_of(4096);
+    let batch = codec.encode(&packets).unwrap();
+    assert_eq!(batch[1], Compression::None.id());
+    assert_eq!(codec.decode(&batch).unwrap(), packets);
+}
+
+#[test]
+fn batches_before_network_settings_have_no_marker() {
+    let codec = BatchCodec::new(THRESHOLD);
+    let packets = packets_of(usize::from(THRESHOLD) + 1);
+    let batch = codec.encode(&packets).unwrap();
+    assert_eq!(batch[..3], [GAME_PACKET, 0x02, 0x01]);
+    assert_eq!(batch.len(), 1 + usize::from(THRESHOLD) + 1);
+    assert_eq!(codec.decode(&batch).unwrap(), packets);
+}
+
+#[test]
+fn the_threshold_is_advertised() {
+    let settings = BatchCodec::new(THRESHOLD).network_settings();
+    assert_eq!(settings.compression_threshold, THRESHOLD);
+    assert_eq!(settings.compression_algorithm, Compression::Flate.algorithm());
+}
+
+#[test]
+fn unknown_markers_are_rejected() {
+    let codec = negotiated(THRESHOLD);
+    for marker in [Compression::Snappy.id(), 0x7f] {
+        let result = codec.decode(&[GAME_PACKET, marker, 0x01, 0x00]);
+        assert!(matches!(result, Err(BatchError::UnsupportedCompression(m)) if m == marker));
+    }
+    assert!(matches!(codec.decode(&[0x00]), Err(BatchError::NotABatch)));
+}
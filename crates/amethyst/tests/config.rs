@@ -0,0 +1,28 @@
+//! Checks the configuration as it is printed and reported.
+
+use amethyst::config::Config;
+
+#[test]
+fn redacted_hides_every_secret() {
+    let mut config = Config::default();
+    config.admin.token = "admin-secret".to_string();
+    config.discord.token = "discord-secret".to_string();
+    config.telemetry.dsn = "https://key@sentry.example/1".to_string();
+    config
+        .otlp
+        .headers
+        .insert("authorization".to_string(), "Bearer otlp-secret".to_string());
+
+    let printed = toml::to_string_pretty(&config.redacted()).unwrap();
+    for secret in ["admin-secret", "discord-secret", "key@sentry", "otlp-secret"] {
+        assert!(!printed.contains(secret), "{} leaked:\n{}", secret, printed);
+    }
+    assert!(printed.contains("authorization = \"<redacted>\""));
+}
+
+#[test]
+fn redacted_leaves_unset_secrets_empty() {
+    let redacted = Config::default().redacted();
+    assert_eq!(redacted.admin.token, "");
+    assert_eq!(redacted.telemetry.dsn, "");
+}
@@ -0,0 +1,151 @@
+//! Applies `AMETHYST_*` environment overrides against the default configuration.
+
+use amethyst::config::env::apply_overrides;
+use amethyst::config::error::ConfigError;
+use amethyst::config::Config;
+use std::ffi::OsString;
+use toml::{Table, Value};
+
+fn schema() -> Table {
+    Table::try_from(Config::default()).unwrap()
+}
+
+fn apply(vars: &[(&str, &str)]) -> Result<(Table, Vec<String>), ConfigError> {
+    apply_to(schema(), vars)
+}
+
+fn apply_to(mut table: Table, vars: &[(&str, &str)]) -> Result<(Table, Vec<String>), ConfigError> {
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)));
+    let applied = apply_overrides(&mut table, &schema(), vars)?;
+    Ok((table, applied))
+}
+
+#[cfg(unix)]
+fn non_utf8(prefix: &str) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    let mut bytes = prefix.as_bytes().to_vec();
+    bytes.push(0xff);
+    OsString::from_vec(bytes)
+}
+
+fn get<'a>(table: &'a Table, section: &str, key: &str) -> &'a Value {
+    &table[section].as_table().unwrap()[key]
+}
+
+#[test]
+fn overrides_a_key_of_a_section() {
+    let (table, applied) = apply(&[("AMETHYST_SERVER__MAX_PLAYERS", "100")]).unwrap();
+    assert_eq!(get(&table, "server", "max_players"), &Value::Integer(100));
+    assert_eq!(applied, ["server.max_players"]);
+}
+
+#[test]
+fn string_keys_take_the_raw_value() {
+    let (table, _) = apply(&[("AMETHYST_SERVER__MOTD", "42")]).unwrap();
+    assert_eq!(get(&table, "server", "motd"), &Value::String("42".to_string()));
+}
+
+#[test]
+fn other_variables_are_ignored() {
+    let (table, applied) = apply(&[
+        ("HOME", "/root"),
+        ("AMETHYSTSERVER", "x"),
+        ("AMETHYST_GIT_HASH", "0123456789ab"),
+    ])
+    .unwrap();
+    assert_eq!(table, schema());
+    assert!(applied.is_empty());
+}
+
+#[test]
+fn string_settings_missing_from_the_file_take_the_raw_value() {
+    let mut file = schema();
+    file.remove("admin");
+    file.remove("discord");
+    let (table, _) = apply_to(
+        file,
+        &[
+            ("AMETHYST_ADMIN__TOKEN", "1234567890123456"),
+            ("AMETHYST_DISCORD__CHANNEL_ID", "987654321098765432"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        get(&table, "admin", "token"),
+        &Value::String("1234567890123456".to_string())
+    );
+    assert_eq!(
+        get(&table, "discord", "channel_id"),
+        &Value::String("987654321098765432".to_string())
+    );
+    let config: Config = table.try_into().unwrap();
+    assert_eq!(config.admin.token, "1234567890123456");
+}
+
+#[cfg(unix)]
+#[test]
+fn only_amethyst_variables_must_be_utf8() {
+    let mut table = schema();
+    let vars = [
+        (OsString::from("FOO"), non_utf8("")),
+        (non_utf8("BAR"), OsString::from("1")),
+    ];
+    assert!(apply_overrides(&mut table, &schema(), vars).unwrap().is_empty());
+
+    for var in [
+        (OsString::from("AMETHYST_SERVER__MOTD"), non_utf8("Title")),
+        (non_utf8("AMETHYST_SERVER__"), OsString::from("1")),
+    ] {
+        let result = apply_overrides(&mut table, &schema(), [var]);
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+}
+
+#[test]
+fn keys_of_maps_can_be_added() {
+    let (table, _) = apply(&[("AMETHYST_OTLP__HEADERS__AUTHORIZATION", "Bearer x")]).unwrap();
+    let headers = get(&table, "otlp", "headers").as_table().unwrap();
+    assert_eq!(headers["authorization"], Value::String("Bearer x".to_string()));
+
+    let (table, _) = apply(&[("AMETHYST_OTLP__HEADERS__X_TENANT", "42")]).unwrap();
+    let headers = get(&table, "otlp", "headers").as_table().unwrap();
+    assert_eq!(headers["x_tenant"], Value::String("42".to_string()));
+}
+
+#[test]
+fn rejects_a_whole_section() {
+    let error = apply(&[("AMETHYST_SERVER", "x")]).unwrap_err();
+    assert!(error.to_string().contains("'server' is a section"), "{}", error);
+}
+
+#[test]
+fn rejects_unknown_keys() {
+    for name in [
+        "AMETHYST_SERVER__NO_SUCH_KEY",
+        "AMETHYST_NO_SUCH_SECTION__KEY",
+        "AMETHYST_SERVER__MAX_PLAYERS__MORE",
+        "AMETHYST_SERVER__",
+        "AMETHYST_OTLP__HEADERS",
+        "AMETHYST_OTLP__HEADERS__A__B",
+    ] {
+        assert!(
+            matches!(apply(&[(name, "1")]), Err(ConfigError::Invalid(_))),
+            "{} was accepted",
+            name
+        );
+    }
+}
+
+#[test]
+fn reports_every_invalid_override() {
+    let Err(ConfigError::Invalid(issues)) = apply(&[
+        ("AMETHYST_SERVER", "x"),
+        ("AMETHYST_SERVER__MAX_PLAYERS", "10"),
+        ("AMETHYST_NETWORK__NOPE", "1"),
+    ]) else {
+        panic!("invalid overrides were accepted");
+    };
+    assert_eq!(issues.len(), 2);
+}
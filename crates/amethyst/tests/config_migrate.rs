@@ -0,0 +1,117 @@
+//! Upgrades configuration files written for older `config_version`s.
+
+use amethyst::config::migrate::{migrate, CURRENT_VERSION};
+use amethyst::config::{self, Config, ConfigOverrides};
+use std::path::PathBuf;
+use toml_edit::DocumentMut;
+
+fn upgrade(content: &str) -> (Option<u32>, String) {
+    let mut document: DocumentMut = content.parse().unwrap();
+    let from = migrate(&mut document).unwrap();
+    (from, document.to_string())
+}
+
+fn parse(content: &str) -> Config {
+    toml::from_str(content).unwrap()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("amethyst-migrate-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("config.toml")
+}
+
+const V0: &str = r#"# My server, do not touch
+[network]
+address = "0.0.0.0:19133" # public port
+
+[server]
+name = "Old server"
+"#;
+
+#[test]
+fn version_0_gets_a_motd_from_the_name() {
+    let (from, upgraded) = upgrade(V0);
+    assert_eq!(from, Some(0));
+    let config = parse(&upgraded);
+    assert_eq!(config.server.motd, "Old server");
+    assert_eq!(config.server.name, "Old server");
+}
+
+#[test]
+fn version_1_moves_the_address_into_a_list() {
+    let (from, upgraded) = upgrade(
+        r#"config_version = 1
+[network]
+address = "0.0.0.0:19133"
+"#,
+    );
+    assert_eq!(from, Some(1));
+    assert_eq!(parse(&upgraded).network.addresses, ["0.0.0.0:19133"]);
+    assert!(upgraded.contains(r#"addresses = ["0.0.0.0:19133"]"#), "{}", upgraded);
+}
+
+#[test]
+fn version_1_keeps_its_motd() {
+    let (_, upgraded) = upgrade(
+        r#"config_version = 1
+[server]
+name = "Name"
+motd = "Title"
+"#,
+    );
+    assert_eq!(parse(&upgraded).server.motd, "Title");
+}
+
+#[test]
+fn upgrades_keep_comments_and_add_documented_defaults() {
+    let (_, upgraded) = upgrade(V0);
+    assert!(upgraded.contains("# My server, do not touch\n[network]\n"), "{}", upgraded);
+    assert!(upgraded.contains("# public port"), "{}", upgraded);
+    assert!(upgraded.contains("# Maximum number of players online at once."));
+    assert!(upgraded.contains(&format!("config_version = {}", CURRENT_VERSION)));
+    // Sections the file lacked come after the ones it had.
+    let server = upgraded.find("[server]").unwrap();
+    let logging = upgraded.find("[logging]").unwrap();
+    assert!(server < logging, "{}", upgraded);
+    assert_eq!(parse(&upgraded).server.max_players, Config::default().server.max_players);
+}
+
+#[test]
+fn current_files_are_left_alone() {
+    let content = format!("config_version = {}\n# comment\n", CURRENT_VERSION);
+    assert_eq!(upgrade(&content), (None, content.clone()));
+}
+
+#[test]
+fn rejects_unknown_versions() {
+    for content in [
+        format!("config_version = {}", CURRENT_VERSION + 1),
+        "config_version = -1".to_string(),
+        "config_version = \"2\"".to_string(),
+    ] {
+        let mut document: DocumentMut = content.parse().unwrap();
+        assert!(migrate(&mut document).is_err(), "{} was accepted", content);
+    }
+}
+
+#[test]
+fn only_startup_rewrites_the_file() {
+    let path = temp_file("startup");
+    std::fs::write(&path, V0).unwrap();
+    let overrides = ConfigOverrides::default();
+
+    let config = config::load(&path, &overrides).unwrap();
+    assert_eq!(config.network.addresses, ["0.0.0.0:19133"]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), V0);
+
+    config::handle(&path, &overrides).unwrap();
+    let upgraded = std::fs::read_to_string(&path).unwrap();
+    assert!(upgraded.contains("# public port"), "{}", upgraded);
+    assert!(upgraded.contains(&format!("config_version = {}", CURRENT_VERSION)));
+    assert_eq!(
+        std::fs::read_to_string(path.with_extension("toml.v0.bak")).unwrap(),
+        V0
+    );
+}
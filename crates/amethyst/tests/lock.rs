@@ -0,0 +1,51 @@
+//! The worlds directory lock, in a temporary directory.
+
+use amethyst::lock::{LockError, ServerLock, LOCK_FILE};
+use std::path::{Path, PathBuf};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("amethyst-lock-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn lock_contents(dir: &Path) -> String {
+    std::fs::read_to_string(dir.join(LOCK_FILE)).unwrap()
+}
+
+#[test]
+fn the_lock_of_a_dead_server_is_taken_over() {
+    let dir = temp_dir("stale");
+    std::fs::create_dir_all(&dir).unwrap();
+    // A server that crashed leaves its PID behind, but its lock died with it.
+    std::fs::write(dir.join(LOCK_FILE), "4194304999").unwrap();
+
+    let lock = ServerLock::acquire(&dir).unwrap();
+    assert_eq!(lock_contents(&dir), std::process::id().to_string());
+    drop(lock);
+    assert_eq!(lock_contents(&dir), "");
+}
+
+#[test]
+fn a_running_server_keeps_its_lock() {
+    let dir = temp_dir("held");
+    let lock = ServerLock::acquire(&dir).unwrap();
+
+    let Err(LockError::Locked { path, pid }) = ServerLock::acquire(&dir) else {
+        panic!("the directory was locked twice");
+    };
+    assert_eq!(path, dir.join(LOCK_FILE));
+    assert_eq!(pid, Some(std::process::id()));
+    // Refusing to start leaves the running server's PID in place.
+    assert_eq!(lock_contents(&dir), std::process::id().to_string());
+
+    drop(lock);
+    assert!(ServerLock::acquire(&dir).is_ok());
+}
+
+#[test]
+fn the_directory_is_created() {
+    let dir = temp_dir("create").join("worlds");
+    let _lock = ServerLock::acquire(&dir).unwrap();
+    assert!(dir.join(LOCK_FILE).is_file());
+}
@@ -0,0 +1,176 @@
+//! Verifies identity chains built with generated keys standing in for Mojang's.
+
+use amethyst::login::{offline_uuid, verify_chain_signed_by, LoginError, LoginIdentity};
+use amethyst_binary::jwt::base64url_encode;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey};
+use p384::pkcs8::EncodePublicKey;
+use serde_json::{json, Value};
+
+const NOW: i64 = 1_700_000_000;
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::from_slice(&[seed; 48]).unwrap()
+}
+
+fn public(key: &SigningKey) -> String {
+    STANDARD.encode(key.verifying_key().to_public_key_der().unwrap().as_bytes())
+}
+
+fn token(signer: &SigningKey, payload: Value) -> String {
+    let header = json!({ "alg": "ES384", "x5u": public(signer) });
+    let input = format!(
+        "{}.{}",
+        base64url_encode(header.to_string().as_bytes()),
+        base64url_encode(payload.to_string().as_bytes())
+    );
+    let signature: Signature = signer.sign(input.as_bytes());
+    format!("{}.{}", input, base64url_encode(&signature.to_bytes()))
+}
+
+fn identity(name: &str, xuid: &str, client: &SigningKey) -> Value {
+    json!({
+        "identityPublicKey": public(client),
+        "nbf": NOW - 60,
+        "exp": NOW + 3600,
+        "extraData": {
+            "displayName": name,
+            "XUID": xuid,
+            "identity": "6f2f5f9e-3c9f-4b43-9a2a-0d2e4c3f1a11",
+        },
+    })
+}
+
+struct Keys {
+    root: SigningKey,
+    xbox: SigningKey,
+    client: SigningKey,
+}
+
+fn keys() -> Keys {
+    Keys {
+        root: key(1),
+        xbox: key(2),
+        client: key(3),
+    }
+}
+
+/// The chain a real client sends: its own token naming the root, the root's certifying the
+/// Xbox key, and the identity token that key signed.
+fn chain(keys: &Keys) -> Vec<String> {
+    vec![
+        token(
+            &keys.client,
+            json!({ "identityPublicKey": public(&keys.root) }),
+        ),
+        token(
+            &keys.root,
+            json!({ "identityPublicKey": public(&keys.xbox), "exp": NOW + 3600 }),
+        ),
+        token(&keys.xbox, identity("Steve", "2535400000000001", &keys.client)),
+    ]
+}
+
+fn verify(chain: &[String], root: &SigningKey) -> Result<LoginIdentity, LoginError> {
+    let chain: Vec<&str> = chain.iter().map(String::as_str).collect();
+    verify_chain_signed_by(&chain, &public(root), true, NOW)
+}
+
+#[test]
+fn accepts_a_chain_signed_by_the_root() {
+    let keys = keys();
+    let identity = verify(&chain(&keys), &keys.root).unwrap();
+    assert_eq!(identity.name, "Steve");
+    assert_eq!(identity.xuid.as_deref(), Some("2535400000000001"));
+    assert_eq!(identity.identity_public_key, public(&keys.client));
+    assert!(identity.authenticated);
+}
+
+#[test]
+fn rejects_a_token_appended_by_the_client() {
+    let keys = keys();
+    let mut chain = chain(&keys);
+    chain.push(token(
+        &keys.client,
+        identity("Alex", "2535400000000002", &keys.client),
+    ));
+    assert!(matches!(
+        verify(&chain, &keys.root),
+        Err(LoginError::ChainTooLong(4))
+    ));
+}
+
+#[test]
+fn rejects_an_identity_not_signed_by_the_certified_key() {
+    let keys = keys();
+    // Fits the appended token in by dropping the client's first one.
+    let mut chain = chain(&keys)[1..].to_vec();
+    chain.push(token(
+        &keys.client,
+        identity("Alex", "2535400000000002", &keys.client),
+    ));
+    assert!(matches!(
+        verify(&chain, &keys.root),
+        Err(LoginError::NotAuthenticated)
+    ));
+}
+
+#[test]
+fn rejects_a_broken_link() {
+    let keys = keys();
+    let mut chain = chain(&keys);
+    chain[2] = token(&key(4), identity("Steve", "2535400000000001", &keys.client));
+    assert!(matches!(
+        verify(&chain, &keys.root),
+        Err(LoginError::BrokenChain)
+    ));
+}
+
+#[test]
+fn rejects_a_chain_without_the_root() {
+    let keys = keys();
+    assert!(matches!(
+        verify(&chain(&keys), &key(5)),
+        Err(LoginError::NotAuthenticated)
+    ));
+}
+
+#[test]
+fn rejects_a_single_self_signed_token() {
+    let keys = keys();
+    let chain = vec![token(
+        &keys.root,
+        identity("Steve", "2535400000000001", &keys.client),
+    )];
+    assert!(matches!(
+        verify(&chain, &keys.root),
+        Err(LoginError::NotAuthenticated)
+    ));
+}
+
+#[test]
+fn rejects_an_expired_token() {
+    let keys = keys();
+    let mut chain = chain(&keys);
+    let mut expired = identity("Steve", "2535400000000001", &keys.client);
+    expired["exp"] = json!(NOW - 3600);
+    chain[2] = token(&keys.xbox, expired);
+    assert!(matches!(verify(&chain, &keys.root), Err(LoginError::Expired)));
+}
+
+#[test]
+fn offline_mode_reads_the_name_without_verifying() {
+    let keys = keys();
+    let chain = [token(
+        &key(6),
+        identity("Steve", "2535400000000001", &keys.client),
+    )];
+    let chain: Vec<&str> = chain.iter().map(String::as_str).collect();
+    let identity = verify_chain_signed_by(&chain, &public(&keys.root), false, NOW).unwrap();
+    assert_eq!(identity.name, "Steve");
+    assert_eq!(identity.xuid, None);
+    assert_eq!(identity.uuid, offline_uuid("Steve"));
+    assert!(!identity.authenticated);
+}
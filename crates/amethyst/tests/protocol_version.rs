@@ -0,0 +1,183 @@
+//! Packets translated to and from each supported protocol version.
+
+use amethyst::protocol::version::{
+    encode_for, ProtocolVersion, UnsupportedVersion, VersionedPacket, V1_21_30, V1_21_40,
+    V1_21_50,
+};
+use amethyst::protocol::{
+    encode, format_uuid, RequestChunkRadius, ResourcePackInfoEntry, ResourcePacksInfo, Transfer,
+};
+use amethyst_binary::io::BinaryReader;
+
+/// Every supported protocol number and the game version it belongs to.
+const VERSIONS: [(u32, &str); 8] = [
+    (662, "1.20.70"),
+    (671, "1.20.80"),
+    (685, "1.21.0"),
+    (686, "1.21.2"),
+    (712, "1.21.20"),
+    (729, "1.21.30"),
+    (748, "1.21.40"),
+    (766, "1.21.50"),
+];
+
+fn versions() -> impl Iterator<Item = ProtocolVersion> {
+    VERSIONS
+        .iter()
+        .map(|&(protocol, _)| ProtocolVersion::resolve(protocol).unwrap())
+}
+
+/// The body of `packet` as `version` expects it, after checking the header.
+fn body_for<P: VersionedPacket>(packet: &P, version: ProtocolVersion) -> BinaryReader {
+    let mut reader = BinaryReader::from(encode_for(packet, version).unwrap());
+    assert_eq!(reader.read_var_u32().unwrap(), P::ID);
+    reader
+}
+
+#[test]
+fn every_supported_version_resolves() {
+    for (protocol, name) in VERSIONS {
+        let version = ProtocolVersion::resolve(protocol).unwrap();
+        assert_eq!(version.protocol(), protocol);
+        assert_eq!(version.minecraft_version(), name);
+        assert_eq!(version.to_string(), format!("{} (protocol {})", name, protocol));
+    }
+    assert!(ProtocolVersion::all().eq(versions()));
+    assert_eq!(ProtocolVersion::OLDEST.protocol(), VERSIONS[0].0);
+    assert_eq!(ProtocolVersion::LATEST.protocol(), VERSIONS[7].0);
+}
+
+#[test]
+fn unsupported_versions_are_rejected() {
+    assert_eq!(ProtocolVersion::resolve(0), Err(UnsupportedVersion::Outdated(0)));
+    assert_eq!(ProtocolVersion::resolve(661), Err(UnsupportedVersion::Outdated(661)));
+    assert_eq!(ProtocolVersion::resolve(767), Err(UnsupportedVersion::TooNew(767)));
+    // Between two supported versions, but not one of them.
+    assert_eq!(ProtocolVersion::resolve(700), Err(UnsupportedVersion::Unknown(700)));
+}
+
+#[test]
+fn unchanged_packets_use_the_latest_layout() {
+    let packet = RequestChunkRadius {
+        radius: 12,
+        max_radius: 16,
+    };
+    for version in versions() {
+        assert_eq!(encode_for(&packet, version).unwrap(), encode(&packet).unwrap());
+        let mut reader = body_for(&packet, version);
+        assert_eq!(RequestChunkRadius::read_for(&mut reader, version).unwrap(), packet);
+    }
+}
+
+#[test]
+fn transfer_has_reload_world_from_1_21_30() {
+    let packet = Transfer {
+        address: "play.example.net".to_string(),
+        port: 19132,
+        reload_world: true,
+    };
+    for version in versions() {
+        let has_flag = version.protocol() >= V1_21_30;
+        let encoded = encode_for(&packet, version).unwrap();
+        let header = 1;
+        let address = 1 + packet.address.len();
+        assert_eq!(
+            encoded.len(),
+            header + address + 2 + usize::from(has_flag),
+            "{}",
+            version
+        );
+
+        let mut reader = body_for(&packet, version);
+        let read = Transfer::read_for(&mut reader, version).unwrap();
+        assert_eq!(read.address, packet.address);
+        assert_eq!(read.port, packet.port);
+        assert_eq!(read.reload_world, has_flag, "{}", version);
+        assert_eq!(reader.remaining(), 0);
+    }
+}
+
+fn resource_packs() -> ResourcePacksInfo {
+    ResourcePacksInfo {
+        must_accept: true,
+        has_addons: false,
+        has_scripts: true,
+        world_template_uuid: [0x11; 16],
+        world_template_version: "1.0.0".to_string(),
+        resource_packs: vec![
+            ResourcePackInfoEntry {
+                uuid: [0x22; 16],
+                version: "1.2.3".to_string(),
+                size: 4096,
+                cdn_url: "https://cdn.example.net/pack.zip".to_string(),
+                ..ResourcePackInfoEntry::default()
+            },
+            ResourcePackInfoEntry {
+                uuid: [0x33; 16],
+                version: "0.0.1".to_string(),
+                ..ResourcePackInfoEntry::default()
+            },
+        ],
+    }
+}
+
+fn read_uuid(reader: &mut BinaryReader) -> [u8; 16] {
+    let mut uuid = [0; 16];
+    uuid[..8].copy_from_slice(&reader.read_u64_le().unwrap().to_be_bytes());
+    uuid[8..].copy_from_slice(&reader.read_u64_le().unwrap().to_be_bytes());
+    uuid
+}
+
+/// Reads the layout used before 1.21.50 field by field, as a client of `version` would.
+fn read_legacy_resource_packs(reader: &mut BinaryReader, version: ProtocolVersion) {
+    let packet = resource_packs();
+    let protocol = version.protocol();
+    assert!(reader.read_bool().unwrap());
+    assert!(!reader.read_bool().unwrap());
+    assert!(reader.read_bool().unwrap());
+    if protocol < V1_21_30 {
+        assert!(!reader.read_bool().unwrap(), "forcing server packs");
+        assert_eq!(reader.read_u16_le().unwrap(), 0, "behaviour packs");
+    }
+    if protocol >= V1_21_40 {
+        assert_eq!(read_uuid(reader), packet.world_template_uuid);
+        assert_eq!(reader.read_string().unwrap(), "1.0.0");
+    }
+    assert_eq!(reader.read_u16_le().unwrap(), 2);
+    for pack in &packet.resource_packs {
+        if protocol >= V1_21_40 {
+            assert_eq!(read_uuid(reader), pack.uuid);
+        } else {
+            assert_eq!(reader.read_string().unwrap(), format_uuid(&pack.uuid));
+        }
+        assert_eq!(reader.read_string().unwrap(), pack.version);
+        assert_eq!(reader.read_u64_le().unwrap(), pack.size);
+        for _ in 0..3 {
+            assert_eq!(reader.read_string().unwrap(), "");
+        }
+        for _ in 0..3 {
+            assert!(!reader.read_bool().unwrap());
+        }
+    }
+    assert_eq!(reader.read_var_u32().unwrap(), 1, "packs with a URL");
+    assert_eq!(
+        reader.read_string().unwrap(),
+        format!("{}_1.2.3", format_uuid(&[0x22; 16]))
+    );
+    assert_eq!(reader.read_string().unwrap(), "https://cdn.example.net/pack.zip");
+    assert_eq!(reader.remaining(), 0);
+}
+
+#[test]
+fn resource_packs_info_is_translated_for_every_version() {
+    let packet = resource_packs();
+    for version in versions() {
+        if version.protocol() >= V1_21_50 {
+            assert_eq!(encode_for(&packet, version).unwrap(), encode(&packet).unwrap());
+            let mut reader = body_for(&packet, version);
+            assert_eq!(ResourcePacksInfo::read_for(&mut reader, version).unwrap(), packet);
+        } else {
+            read_legacy_resource_packs(&mut body_for(&packet, version), version);
+        }
+    }
+}
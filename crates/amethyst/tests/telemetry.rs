@@ -0,0 +1,67 @@
+//! Reports sent to the telemetry server, which must not carry secrets or addresses.
+
+use amethyst::config::Config;
+use amethyst::telemetry::Reporter;
+
+const ADMIN_TOKEN: &str = "0f9c2d7e-admin-token";
+const DISCORD_TOKEN: &str = "discord.bot.token";
+const DSN: &str = "https://publickey123@sentry.example.net/42";
+const OTLP_HEADER: &str = "Bearer otlp-secret";
+const CLIENT_IP: &str = "203.0.113.7";
+const XUID: &str = "2535412345678901";
+
+fn reporter() -> Reporter {
+    let mut config = Config::default();
+    config.admin.token = ADMIN_TOKEN.to_string();
+    config.discord.token = DISCORD_TOKEN.to_string();
+    config.telemetry.dsn = DSN.to_string();
+    config
+        .otlp
+        .headers
+        .insert("authorization".to_string(), OTLP_HEADER.to_string());
+    Reporter::new(&config, "instance".to_string()).unwrap()
+}
+
+fn assert_scrubbed(report: &str) {
+    for leaked in [
+        ADMIN_TOKEN,
+        DISCORD_TOKEN,
+        "publickey123",
+        "otlp-secret",
+        CLIENT_IP,
+        "2001:db8::1",
+        XUID,
+    ] {
+        assert!(!report.contains(leaked), "{} leaked:\n{}", leaked, report);
+    }
+    assert!(report.contains("<redacted>"), "{}", report);
+}
+
+#[test]
+fn errors_leave_out_secrets_and_addresses() {
+    let message = format!(
+        "Rejected admin request from {}:51234 (token {}), player {} at [2001:db8::1]:19132. \
+         Discord answered 401 for {}; OTLP export with {} failed; DSN {}.",
+        CLIENT_IP, ADMIN_TOKEN, XUID, DISCORD_TOKEN, OTLP_HEADER, DSN
+    );
+    let event = reporter().event("error", "amethyst::admin", &message);
+    assert_scrubbed(&event.to_string());
+    assert_eq!(event["level"], "error");
+    assert_eq!(event["tags"]["instance_id"], "instance");
+}
+
+#[test]
+fn panics_leave_out_secrets_and_addresses() {
+    let message = format!("connection {} sent {}", CLIENT_IP, ADMIN_TOKEN);
+    let backtrace = format!("0: amethyst::admin::check_token\n   token = {}\n", ADMIN_TOKEN);
+    let event = reporter().panic_event(&message, "main", &backtrace);
+    assert_scrubbed(&event.to_string());
+    assert_eq!(event["tags"]["thread"], "main");
+}
+
+#[test]
+fn ordinary_text_is_kept() {
+    let message = "Tick took 61ms in std::collections::HashMap at version 1.21.50, port 19132";
+    let event = reporter().event("error", "amethyst::tick", message);
+    assert_eq!(event["message"]["formatted"], message);
+}
@@ -0,0 +1,89 @@
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rakethyst::connection::SequenceNumberRange;
+use rakethyst::protocol::{
+    AckNackPacket, AckNackRecord, EncapsulatedPacket, FrameSetPacket, Reliability,
+};
+use rakethyst::seq::SeqNum;
+use std::hint::black_box;
+
+/// Datagram size the server negotiates.
+const MTU: usize = 1400;
+
+fn encapsulated(index: u32, payload: Bytes) -> EncapsulatedPacket {
+    EncapsulatedPacket {
+        reliability: Reliability::ReliableOrdered,
+        is_split: false,
+        sequence_number: Some(SeqNum::new(index)),
+        ordering_index: Some(SeqNum::new(index)),
+        ordering_channel: Some(0),
+        split_count: None,
+        split_id: None,
+        split_index: None,
+        payload,
+    }
+}
+
+fn encode<T: Writable>(value: &T) -> Bytes {
+    let mut writer = BinaryWriter::with_capacity(MTU);
+    value.write(&mut writer).unwrap();
+    writer.freeze()
+}
+
+fn bench_codec<T: Readable + Writable>(c: &mut Criterion, name: &str, value: T) {
+    let encoded = encode(&value);
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(encoded.len() as u64));
+    group.bench_function("encode", |b| b.iter(|| encode(black_box(&value))));
+    group.bench_function("decode", |b| {
+        b.iter(|| T::read(&mut BinaryReader::new(black_box(encoded.clone()))).unwrap())
+    });
+    group.finish();
+}
+
+fn encapsulated_packet(c: &mut Criterion) {
+    bench_codec(
+        c,
+        "encapsulated_packet",
+        encapsulated(1, Bytes::from(vec![0xfe; 512])),
+    );
+}
+
+/// A datagram filled with small game packets, as sent while a world streams in.
+fn frame_set(c: &mut Criterion) {
+    let payload = Bytes::from(vec![0xfe; 160]);
+    let packets = (0..8)
+        .map(|index| encapsulated(index, payload.clone()))
+        .collect();
+    bench_codec(
+        c,
+        "frame_set",
+        FrameSetPacket {
+            sequence_number: SeqNum::new(1),
+            packets,
+        },
+    );
+}
+
+/// An ACK for a lossy stretch: runs of received datagrams separated by gaps.
+fn ack(c: &mut Criterion) {
+    let records = (0..64)
+        .map(|index| {
+            let start = SeqNum::new(index * 10);
+            if index % 4 == 0 {
+                AckNackRecord::Single(start)
+            } else {
+                AckNackRecord::Range(SequenceNumberRange {
+                    start,
+                    end: start.wrapping_add(7),
+                })
+            }
+        })
+        .collect();
+    bench_codec(c, "ack", AckNackPacket { records });
+}
+
+criterion_group!(benches, encapsulated_packet, frame_set, ack);
+criterion_main!(benches);
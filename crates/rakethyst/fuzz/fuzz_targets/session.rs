@@ -0,0 +1,256 @@
+//! Drives a whole session with sequences of semi-valid datagrams: connection requests, frame
+//! sets with split, ordered and reliable frames, ACKs and NACKs, raw bytes, truncations,
+//! duplicates and reorderings. Inputs go through `RakNetListener::replay` on a paused clock,
+//! so timeouts pass instantly.
+//!
+//! After each input the session must have ended without panicking and taken its connection
+//! with it, and the listener must hold no state for it. Invariants on the reliability windows
+//! belong here too once that layer exists.
+//!
+//! Run with `cargo fuzz run session` from `crates/rakethyst`.
+
+#![no_main]
+
+use amethyst_binary::io::BinaryWriter;
+use amethyst_binary::traits::Writable;
+use arbitrary::Arbitrary;
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use rakethyst::connection::SequenceNumberRange;
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::Motd;
+use rakethyst::protocol::*;
+use rakethyst::recording::{RecordedDatagram, Recording};
+use rakethyst::seq::SeqNum;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    steps: Vec<Step>,
+    /// Pairs of datagram indices swapped after encoding, to reorder them.
+    swaps: Vec<(u8, u8)>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Step {
+    ConnectionRequest { guid: u64, time: u64 },
+    FrameSet { sequence_number: u32, frames: Vec<Frame> },
+    Ack(Vec<Record>),
+    Nack(Vec<Record>),
+    /// A datagram that only starts like a known one.
+    Raw { id: u8, body: Vec<u8> },
+    /// The previous datagram again, or only the first `len` bytes of it.
+    Repeat { len: Option<u16> },
+    Wait { millis: u16 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Frame {
+    reliability: u8,
+    split: Option<Split>,
+    reliable_index: u32,
+    ordering_index: u32,
+    ordering_channel: u8,
+    payload: Payload,
+    /// Added to the payload length in the header, so it can disagree with the payload.
+    length_error: i8,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Split {
+    count: u32,
+    id: u16,
+    index: u32,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Payload {
+    ConnectedPing { time: u64 },
+    Disconnect,
+    Other(Vec<u8>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Record {
+    Single(u32),
+    Range(u32, u32),
+}
+
+struct Server {
+    runtime: Runtime,
+    listener: RakNetListener,
+    replies: UdpSocket,
+}
+
+const CLIENT: &str = "10.0.0.7:50123";
+
+fn server() -> &'static Server {
+    static SERVER: OnceLock<Server> = OnceLock::new();
+    SERVER.get_or_init(|| {
+        rakethyst::utils::init_time();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        let motd = Motd {
+            motd: "Fuzz".to_string(),
+            world_name: "Fuzz".to_string(),
+            game_mode: "Survival".to_string(),
+            max_players: 1,
+        };
+        let (listener, replies) = runtime.block_on(async {
+            let server_info = Arc::new(ServerInfo::new(1, motd));
+            let listener = RakNetListener::bind("127.0.0.1:0", server_info).await.unwrap();
+            (listener, UdpSocket::bind("127.0.0.1:0").await.unwrap())
+        });
+        Server {
+            runtime,
+            listener,
+            replies,
+        }
+    })
+}
+
+fn encode(id: u8, packet: &impl Writable) -> Option<Bytes> {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(id).ok()?;
+    packet.write(&mut writer).ok()?;
+    Some(writer.freeze())
+}
+
+/// Writes a frame by hand, since the protocol writer cannot write split frames and always
+/// writes consistent headers.
+fn write_frame(writer: &mut BinaryWriter, frame: &Frame) {
+    let payload = match &frame.payload {
+        Payload::ConnectedPing { time } => {
+            encode(CONNECTED_PING, &ConnectedPing { time: *time }).unwrap_or_default()
+        }
+        Payload::Disconnect => Bytes::from_static(&[DISCONNECTION_NOTIFICATION]),
+        Payload::Other(bytes) => Bytes::copy_from_slice(bytes),
+    };
+    let reliability = Reliability::from_u8(frame.reliability & 0x07).unwrap();
+    let split_flag = if frame.split.is_some() { 0x10 } else { 0 };
+    let bits = (payload.len() as i64 + frame.length_error as i64).clamp(0, 0x1fff) * 8;
+    let _ = writer.write_u8((frame.reliability & 0x07) << 5 | split_flag);
+    let _ = writer.write_u16(bits as u16);
+    if reliability.is_reliable() {
+        let _ = SeqNum::new(frame.reliable_index).write(writer);
+    }
+    if reliability.is_sequenced() {
+        let _ = SeqNum::new(frame.ordering_index).write(writer);
+        let _ = writer.write_u8(frame.ordering_channel);
+    }
+    if let Some(split) = &frame.split {
+        let _ = writer.write_u32(split.count);
+        let _ = writer.write_u16(split.id);
+        let _ = writer.write_u32(split.index);
+    }
+    let _ = writer.write_bytes(&payload);
+}
+
+fn records(records: &[Record]) -> AckNackPacket {
+    AckNackPacket {
+        records: records
+            .iter()
+            .map(|record| match *record {
+                Record::Single(number) => AckNackRecord::Single(SeqNum::new(number)),
+                Record::Range(start, end) => AckNackRecord::Range(SequenceNumberRange {
+                    start: SeqNum::new(start),
+                    end: SeqNum::new(end),
+                }),
+            })
+            .collect(),
+    }
+}
+
+/// Turns the steps into the datagrams of a recording.
+fn recording(input: &Input) -> Recording {
+    let mut datagrams: Vec<RecordedDatagram> = Vec::new();
+    let mut offset = Duration::ZERO;
+    for step in &input.steps {
+        let payload = match step {
+            Step::ConnectionRequest { guid, time } => encode(
+                CONNECTION_REQUEST,
+                &ConnectionRequest {
+                    client_guid: *guid,
+                    time: *time,
+                    use_security: false,
+                },
+            ),
+            Step::FrameSet {
+                sequence_number,
+                frames,
+            } => {
+                let mut writer = BinaryWriter::new();
+                let _ = writer.write_u8(FRAME_SET);
+                let _ = SeqNum::new(*sequence_number).write(&mut writer);
+                for frame in frames {
+                    write_frame(&mut writer, frame);
+                }
+                Some(writer.freeze())
+            }
+            Step::Ack(list) => encode(ACK, &records(list)),
+            Step::Nack(list) => encode(NACK, &records(list)),
+            Step::Raw { id, body } => {
+                let mut data = vec![*id];
+                data.extend_from_slice(body);
+                Some(Bytes::from(data))
+            }
+            Step::Repeat { len } => datagrams.last().map(|previous| match len {
+                Some(len) => {
+                    let len = (*len as usize).clamp(1, previous.payload.len());
+                    previous.payload.slice(..len)
+                }
+                None => previous.payload.clone(),
+            }),
+            Step::Wait { millis } => {
+                offset += Duration::from_millis(*millis as u64);
+                None
+            }
+        };
+        if let Some(payload) = payload
+            && !payload.is_empty()
+        {
+            datagrams.push(RecordedDatagram { offset, payload });
+        }
+    }
+    // Reordering swaps the contents, but keeps the arrival times increasing.
+    let len = datagrams.len();
+    if len > 1 {
+        for &(a, b) in &input.swaps {
+            let (a, b) = (a as usize % len, b as usize % len);
+            let payload = datagrams[a].payload.clone();
+            datagrams[a].payload = std::mem::replace(&mut datagrams[b].payload, payload);
+        }
+    }
+    Recording {
+        address: CLIENT.parse().unwrap(),
+        datagrams,
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let recording = recording(&input);
+    if recording.datagrams.is_empty() {
+        return;
+    }
+    let server = server();
+    let client: SocketAddr = CLIENT.parse().unwrap();
+    server.runtime.block_on(async {
+        let replies = server.replies.local_addr().unwrap();
+        server.listener.replay(&recording, replies).await;
+    });
+
+    assert!(
+        !server.listener.connections().contains_key(&client),
+        "the session ended but left its connection behind"
+    );
+    assert_eq!(server.listener.server_info().player_count(), 0);
+    let mut buf = [0; 2048];
+    while server.replies.try_recv(&mut buf).is_ok() {}
+});
@@ -1,14 +1,70 @@
+use crate::seq::SeqNum;
 use std::net::SocketAddr;
+use thiserror::Error;
 use tokio::time::Instant;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Sequence numbers from `start` to `end`, both included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceNumberRange {
+    pub start: SeqNum,
+    pub end: SeqNum,
+}
+
+impl SequenceNumberRange {
+    pub fn contains(&self, seq: SeqNum) -> bool {
+        self.start.distance_to(seq) <= self.start.distance_to(self.end)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SeqNum> {
+        self.start.range_inclusive(self.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
+    /// Created for a `CONNECTION_REQUEST` that has not been accepted yet.
     Handshaking,
+    /// `CONNECTION_REQUEST_ACCEPTED` was sent, and the client has not sent data since.
     Connecting,
     Connected,
     Disconnected,
 }
 
+/// What moves a connection from one [`ConnectionState`] to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A `CONNECTION_REQUEST` was accepted.
+    ConnectionRequest,
+    /// A data frame arrived.
+    DataFrame,
+    /// The connection was closed by either side or timed out.
+    Disconnect,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("{event:?} is not valid in state {state:?}")]
+pub struct InvalidTransition {
+    pub state: ConnectionState,
+    pub event: ConnectionEvent,
+}
+
+impl ConnectionState {
+    /// The state `event` leads to from this one. Every transition the connection can make is
+    /// listed here; anything else, such as a repeated `CONNECTION_REQUEST`, is rejected.
+    pub fn advance(self, event: ConnectionEvent) -> Result<Self, InvalidTransition> {
+        use ConnectionEvent as Event;
+        use ConnectionState as State;
+        match (self, event) {
+            (State::Handshaking, Event::ConnectionRequest) => Ok(State::Connecting),
+            (State::Connecting | State::Connected, Event::DataFrame) => Ok(State::Connected),
+            (State::Handshaking | State::Connecting | State::Connected, Event::Disconnect) => {
+                Ok(State::Disconnected)
+            }
+            (state, event) => Err(InvalidTransition { state, event }),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Connection {
     pub address: SocketAddr,
@@ -29,6 +85,13 @@ impl Connection {
         }
     }
 
+    /// Applies [`ConnectionState::advance`], leaving the state as it is if `event` is
+    /// rejected.
+    pub fn advance(&mut self, event: ConnectionEvent) -> Result<(), InvalidTransition> {
+        self.state = self.state.advance(event)?;
+        Ok(())
+    }
+
     pub fn update_last_packet_time(&mut self) {
         self.last_packet_time = Instant::now();
     }
@@ -0,0 +1,23 @@
+use crate::proxy_protocol::ProxyProtocolError;
+use crate::recording::RecordingError;
+use amethyst_binary::error::BinaryError;
+use std::io;
+use thiserror::Error;
+
+/// Everything the RakNet layer can fail with. Errors of the individual modules convert into
+/// it, so callers handle one type whichever part of the stack failed.
+#[derive(Error, Debug)]
+pub enum RakNetError {
+    /// The socket could not be bound, read or written.
+    #[error("transport error: {0}")]
+    Transport(#[from] io::Error),
+    /// A packet could not be encoded or decoded.
+    #[error("protocol error: {0}")]
+    Protocol(#[from] BinaryError),
+    #[error("PROXY protocol error: {0}")]
+    ProxyProtocol(#[from] ProxyProtocolError),
+    #[error("recording error: {0}")]
+    Recording(#[from] RecordingError),
+}
+
+pub type Result<T> = std::result::Result<T, RakNetError>;
@@ -0,0 +1,113 @@
+//! The state of sessions handed over to a new server process on a hot restart, so clients
+//! keep their connection while the server is replaced. See
+//! [`RakNetListener::detach_sessions`](crate::listener::RakNetListener::detach_sessions) and
+//! [`RakNetListener::restore_sessions`](crate::listener::RakNetListener::restore_sessions).
+//!
+//! A handoff starts with [`MAGIC`] and a format version. Each session follows as the index of
+//! its socket, the client and reply addresses as strings, the client GUID, the MTU, whether
+//! the client sent data yet, and the sequence number of the next frame set.
+
+use crate::connection::ConnectionState;
+use crate::seq::SeqNum;
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use bytes::Bytes;
+use std::net::SocketAddr;
+use thiserror::Error;
+
+pub const MAGIC: [u8; 4] = *b"AHOF";
+const VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum HandoffError {
+    #[error("not a session handoff")]
+    NotAHandoff,
+    #[error("unsupported handoff version {0}")]
+    UnsupportedVersion(u8),
+    #[error("malformed handoff: {0}")]
+    Malformed(#[from] BinaryError),
+}
+
+/// What a session needs to carry on in another process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionState {
+    /// Index of the socket the client is connected to, in the order the listener's sockets
+    /// were bound.
+    pub socket: usize,
+    pub address: SocketAddr,
+    /// Where replies go, which differs from `address` behind a PROXY protocol load balancer.
+    pub reply_addr: SocketAddr,
+    pub client_guid: u64,
+    pub mtu: u16,
+    /// [`ConnectionState::Connecting`] or [`ConnectionState::Connected`].
+    pub state: ConnectionState,
+    pub next_sequence_number: SeqNum,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Handoff {
+    pub sessions: Vec<SessionState>,
+}
+
+impl Handoff {
+    pub fn encode(&self) -> Result<Bytes, BinaryError> {
+        let mut writer = BinaryWriter::new();
+        writer.write_bytes(&MAGIC)?;
+        writer.write_u8(VERSION)?;
+        for session in &self.sessions {
+            writer.write_var_u32(session.socket as u32)?;
+            writer.write_string(&session.address.to_string())?;
+            writer.write_string(&session.reply_addr.to_string())?;
+            writer.write_u64(session.client_guid)?;
+            writer.write_u16(session.mtu)?;
+            writer.write_bool(session.state == ConnectionState::Connected)?;
+            writer.write_u32(session.next_sequence_number.value())?;
+        }
+        Ok(writer.freeze())
+    }
+
+    pub fn decode(data: Bytes) -> Result<Self, HandoffError> {
+        let mut reader = BinaryReader::new(data);
+        if reader.remaining() < MAGIC.len() || reader.read_bytes(MAGIC.len())? != MAGIC[..] {
+            return Err(HandoffError::NotAHandoff);
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(HandoffError::UnsupportedVersion(version));
+        }
+        let mut sessions = Vec::new();
+        while reader.remaining() > 0 {
+            let socket = reader.read_var_u32()? as usize;
+            let address = read_address(&mut reader)?;
+            let reply_addr = read_address(&mut reader)?;
+            let client_guid = reader.read_u64()?;
+            let mtu = reader.read_u16()?;
+            let state = if reader.read_bool()? {
+                ConnectionState::Connected
+            } else {
+                ConnectionState::Connecting
+            };
+            let next_sequence_number = reader.read_u32()?;
+            if next_sequence_number > SeqNum::MAX {
+                return Err(BinaryError::InvalidData("invalid sequence number".to_string()).into());
+            }
+            sessions.push(SessionState {
+                socket,
+                address,
+                reply_addr,
+                client_guid,
+                mtu,
+                state,
+                next_sequence_number: SeqNum::new(next_sequence_number),
+            });
+        }
+        Ok(Handoff { sessions })
+    }
+}
+
+fn read_address(reader: &mut BinaryReader) -> Result<SocketAddr, BinaryError> {
+    reader
+        .read_string()?
+        .parse()
+        .map_err(|_| BinaryError::InvalidData("invalid address".to_string()))
+}
@@ -1,4 +1,20 @@
 pub mod protocol;
+pub mod error;
+pub mod handoff;
 pub mod listener;
+pub mod motd;
+pub mod packet_trace;
+pub mod proxy_protocol;
+pub mod recording;
+pub mod seq;
+pub mod session;
+pub mod socket;
+pub mod stats;
+pub mod throttle;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod traffic;
 pub mod connection;
-pub mod utils;
\ No newline at end of file
+mod trace;
+pub mod utils;
+pub mod violations;
\ No newline at end of file
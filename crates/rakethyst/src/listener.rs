@@ -1,59 +1,532 @@
-use crate::connection::{Connection, ConnectionState};
+use crate::connection::Connection;
+use crate::error::Result;
+use crate::handoff::Handoff;
+use crate::motd::{Motd, MotdBuilder};
+use crate::packet_trace::{self, Direction, PacketTrace};
+use crate::proxy_protocol::ProxyClients;
+use crate::recording::Recording;
+use crate::session::{self, Datagram, SessionHandle, Shared, SystemAddresses};
+use crate::socket::{self, SocketOptions};
+use crate::stats::ListenerStats;
+use crate::throttle::{JoinThrottle, Throttler};
+use crate::traffic::TrafficStats;
+use crate::violations::StrictMode;
+use crate::trace::{packet_event, packet_span};
 use crate::protocol;
-use crate::protocol::{ConnectionRequest, ConnectionRequestAccepted, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing, UnconnectedPong, CONNECTION_REQUEST_ACCEPTED, OPEN_CONNECTION_REPLY_2, UNCONNECTED_PONG};
-use amethyst_binary::io::{BinaryReader, BinaryWriter};
-use amethyst_binary::traits::{Readable, Writable};
-use bytes::Bytes;
+use crate::protocol::{OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing, UnconnectedPong, OPEN_CONNECTION_REPLY_2, UNCONNECTED_PONG};
+use amethyst_binary::io::{BinaryRef, BinaryWriter};
+use amethyst_binary::traits::{ReadableRef, Writable};
+use amethyst_log::LogContext;
+use bytes::{Bytes, BytesMut};
 use dashmap::DashMap;
-use log::{debug, error, info, logger, trace, warn};
-use std::net::{SocketAddr, UdpSocket};
-use std::sync::Arc;
+use socket2::SockRef;
+use log::{debug, error, info, trace, warn};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::net::UdpSocket;
 
-const SERVER_GUID: u64 = 12345678909876543212;
-const MINECRAFT_VERSION: &str = "1.20.80";
-const PROTOCOL_VERSION: u32 = 662;
+/// Largest datagram received in full. RakNet never negotiates an MTU above 1500.
+const MAX_DATAGRAM_SIZE: usize = 2048;
+/// Size of the buffer datagrams are received into. A new one is only allocated while
+/// datagrams from the current one are still in use.
+const RECV_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Where clients listen for servers on their local network.
+pub const LAN_BROADCAST_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 19132);
+/// How often the server's pong is broadcast, about as often as clients refresh the list.
+const LAN_BROADCAST_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Server identity and MOTD, shared with the listener so the MOTD can be changed while it is
+/// running.
+///
+/// The pong payload is rebuilt only when one of its fields changes, so answering a ping is a
+/// pointer copy.
+pub struct ServerInfo {
+    guid: u64,
+    builder: Mutex<MotdBuilder>,
+    payload: RwLock<Arc<str>>,
+}
+
+impl ServerInfo {
+    pub fn new(guid: u64, motd: Motd) -> Self {
+        let builder = MotdBuilder::new(guid, motd);
+        let payload = RwLock::new(Arc::from(builder.build()));
+        Self {
+            guid,
+            builder: Mutex::new(builder),
+            payload,
+        }
+    }
+
+    pub fn guid(&self) -> u64 {
+        self.guid
+    }
+
+    pub fn motd(&self) -> Motd {
+        self.builder().motd().clone()
+    }
+
+    pub fn player_count(&self) -> u32 {
+        self.builder().player_count()
+    }
+
+    /// Whether fewer players are connected than the MOTD allows.
+    pub fn has_open_slots(&self) -> bool {
+        let builder = self.builder();
+        builder.player_count() < builder.motd().max_players
+    }
+
+    /// The cached MCPE string sent in every `UNCONNECTED_PONG`.
+    pub fn pong_payload(&self) -> Arc<str> {
+        Arc::clone(&self.payload.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn set_motd(&self, motd: Motd) {
+        self.update(|builder| builder.set_motd(motd));
+    }
+
+    /// Sets the advertised player count to the number of `connections`. Call it whenever one
+    /// is added or removed.
+    ///
+    /// The map is counted under the MOTD lock rather than by the caller, so two connections
+    /// changing at once cannot publish their counts in the wrong order and leave a stale one.
+    pub fn update_player_count(&self, connections: &DashMap<SocketAddr, Connection>) {
+        self.update(|builder| {
+            let player_count = u32::try_from(connections.len()).unwrap_or(u32::MAX);
+            builder.set_player_count(player_count);
+        });
+    }
+
+    fn set_local_address(&self, address: SocketAddr) {
+        self.update(|builder| builder.set_local_address(address));
+    }
+
+    fn builder(&self) -> MutexGuard<'_, MotdBuilder> {
+        self.builder.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Applies `change` and swaps in a new payload if the MOTD string changed. The builder lock
+    /// is held across the swap so concurrent updates cannot publish out of order.
+    fn update(&self, change: impl FnOnce(&mut MotdBuilder)) {
+        let mut builder = self.builder();
+        let before = builder.clone();
+        change(&mut builder);
+        if *builder != before {
+            *self.payload.write().unwrap_or_else(|e| e.into_inner()) = Arc::from(builder.build());
+        }
+    }
+}
+
+/// Inspects datagrams from connected clients before they are handled. Returning `false`
+/// drops the datagram.
+pub type PacketFilter = Arc<dyn Fn(SocketAddr, &[u8]) -> bool + Send + Sync>;
+
+/// Called with the client address whenever a client starts a new session.
+pub type SessionHook = Arc<dyn Fn(SocketAddr) + Send + Sync>;
 
 pub struct RakNetListener {
-    socket: Arc<UdpSocket>,
-    server_name: Arc<String>,
+    /// One per bound address, all feeding the same sessions.
+    sockets: Vec<Arc<UdpSocket>>,
+    server_info: Arc<ServerInfo>,
     connections: Arc<DashMap<SocketAddr, Connection>>,
+    sessions: Arc<DashMap<SocketAddr, SessionHandle>>,
+    stats: Arc<ListenerStats>,
+    packet_trace: Arc<PacketTrace>,
+    traffic: Arc<TrafficStats>,
+    packet_filter: Option<PacketFilter>,
+    session_hook: Option<SessionHook>,
+    handshake_timeout: Duration,
+    record_directory: Option<Arc<Path>>,
+    strict_mode: Option<StrictMode>,
+    /// Set when datagrams carry PROXY protocol headers.
+    proxy_clients: Option<ProxyClients>,
+    join_throttle: Option<Throttler>,
+    system_addresses: Arc<SystemAddresses>,
+    /// Cleared by [`stop_accepting`](Self::stop_accepting).
+    accepting: AtomicBool,
 }
 
 impl RakNetListener {
-    pub async fn bind(addr: &str, server_name: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let socket = UdpSocket::bind(addr)?;
-        info!("RakNet listener bound to {}", addr);
+    pub async fn bind(
+        addr: &str,
+        server_info: Arc<ServerInfo>,
+    ) -> Result<Self> {
+        Self::bind_all(&[addr], server_info).await
+    }
+
+    /// Binds a socket to each of `addrs`, e.g. an internal and an external interface. Clients
+    /// on any of them share the connections, the player count and the server list entry, and
+    /// are answered from the socket they reached.
+    ///
+    /// The server list entry advertises the port of the first IPv4 and the first IPv6 address.
+    pub async fn bind_all(
+        addrs: &[&str],
+        server_info: Arc<ServerInfo>,
+    ) -> Result<Self> {
+        Self::bind_with_options(addrs, &SocketOptions::default(), server_info).await
+    }
+
+    /// Like [`bind_all`](Self::bind_all), applying `options` to every socket.
+    pub async fn bind_with_options(
+        addrs: &[&str],
+        options: &SocketOptions,
+        server_info: Arc<ServerInfo>,
+    ) -> Result<Self> {
+        if addrs.is_empty() {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "no address to bind to").into());
+        }
+        let mut sockets = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            let socket = socket::bind(addr, options).await?;
+            info!("RakNet listener bound to {}", addr);
+            sockets.push(Arc::new(socket));
+        }
+        Self::from_sockets(sockets, server_info)
+    }
+
+    /// A listener on sockets that are already bound, such as those inherited from the
+    /// previous server process on a hot restart. Must be called within a Tokio runtime.
+    pub fn from_std_sockets(
+        sockets: Vec<std::net::UdpSocket>,
+        server_info: Arc<ServerInfo>,
+    ) -> Result<Self> {
+        if sockets.is_empty() {
+            let error = std::io::Error::new(ErrorKind::InvalidInput, "no socket to listen on");
+            return Err(error.into());
+        }
+        let sockets = sockets
+            .into_iter()
+            .map(|socket| {
+                socket.set_nonblocking(true)?;
+                UdpSocket::from_std(socket).map(Arc::new)
+            })
+            .collect::<std::io::Result<_>>()?;
+        Self::from_sockets(sockets, server_info)
+    }
+
+    fn from_sockets(sockets: Vec<Arc<UdpSocket>>, server_info: Arc<ServerInfo>) -> Result<Self> {
+        for socket in sockets.iter().rev() {
+            server_info.set_local_address(socket.local_addr()?);
+        }
         Ok(Self {
-            socket: Arc::new(socket),
-            server_name: Arc::new(server_name.into()),
+            sockets,
+            server_info,
             connections: Arc::new(DashMap::new()),
+            sessions: Arc::new(DashMap::new()),
+            stats: Arc::new(ListenerStats::new()),
+            packet_trace: Arc::new(PacketTrace::new()),
+            traffic: Arc::new(TrafficStats::new()),
+            packet_filter: None,
+            session_hook: None,
+            handshake_timeout: session::DEFAULT_HANDSHAKE_TIMEOUT,
+            record_directory: None,
+            strict_mode: None,
+            proxy_clients: None,
+            join_throttle: None,
+            system_addresses: Arc::new(SystemAddresses::default()),
+            accepting: AtomicBool::new(true),
+        })
+    }
+
+    /// The address the first socket is bound to, with the port filled in when binding to
+    /// port 0.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.sockets[0].local_addr()
+    }
+
+    /// The addresses of every socket, in the order they were given to
+    /// [`bind_all`](Self::bind_all).
+    pub fn local_addrs(&self) -> std::io::Result<Vec<SocketAddr>> {
+        self.sockets.iter().map(|socket| socket.local_addr()).collect()
+    }
+
+    pub fn server_info(&self) -> Arc<ServerInfo> {
+        Arc::clone(&self.server_info)
+    }
+
+    pub fn connections(&self) -> Arc<DashMap<SocketAddr, Connection>> {
+        Arc::clone(&self.connections)
+    }
+
+    pub fn with_packet_filter(mut self, filter: PacketFilter) -> Self {
+        self.packet_filter = Some(filter);
+        self
+    }
+
+    pub fn with_session_hook(mut self, hook: SessionHook) -> Self {
+        self.session_hook = Some(hook);
+        self
+    }
+
+    /// Closes sessions that have not sent a data frame within `timeout` of their
+    /// `CONNECTION_REQUEST`, rather than waiting for them to go quiet. Anything at or above
+    /// [`CONNECTION_TIMEOUT`](session::CONNECTION_TIMEOUT) has no effect.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Records the datagrams every session receives to a file of its own in `directory`, for
+    /// [`replay`](Self::replay). Meant for reproducing bugs, as it costs a file write per
+    /// datagram batch.
+    pub fn with_session_recording(mut self, directory: &Path) -> Self {
+        self.record_directory = Some(Arc::from(directory));
+        self
+    }
+
+    /// Closes sessions that send more than `mode.max_violations` undecodable packets, or
+    /// packets not allowed in their connection state, within `mode.window`. Without it such
+    /// packets are only logged.
+    pub fn with_strict_mode(mut self, mode: StrictMode) -> Self {
+        self.strict_mode = Some(mode);
+        self
+    }
+
+    /// Expects PROXY protocol v2 headers from a load balancer in front of the listener.
+    /// Sessions are keyed and logged by the client address in the headers, while replies go
+    /// back through the load balancer.
+    pub fn with_proxy_protocol(mut self) -> Self {
+        self.proxy_clients = Some(ProxyClients::new());
+        self
+    }
+
+    /// Makes clients from subnets that start too many handshakes, or retry them too fast,
+    /// echo a cookie in an extra handshake round before they may start a session.
+    pub fn with_join_throttle(mut self, throttle: JoinThrottle) -> Self {
+        self.join_throttle = Some(Throttler::new(throttle));
+        self
+    }
+
+    /// Sets the system index and internal addresses sent in `CONNECTION_REQUEST_ACCEPTED`,
+    /// for client versions that expect other values than the Bedrock defaults.
+    pub fn with_system_addresses(mut self, addresses: SystemAddresses) -> Self {
+        self.system_addresses = Arc::new(addresses);
+        self
+    }
+
+    pub fn stats(&self) -> Arc<ListenerStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// The hex trace of the datagrams the listener receives and sends, off until enabled.
+    pub fn packet_trace(&self) -> Arc<PacketTrace> {
+        Arc::clone(&self.packet_trace)
+    }
+
+    /// Packet counts and byte totals per packet ID, in both directions.
+    pub fn traffic(&self) -> Arc<TrafficStats> {
+        Arc::clone(&self.traffic)
+    }
+
+    /// A task that broadcasts the server's pong to [`LAN_BROADCAST_ADDRESS`] from the first
+    /// IPv4 socket, so clients on the local network list the server under Friends/LAN. `None`
+    /// if no socket is bound to an IPv4 address or broadcasting cannot be enabled.
+    pub fn lan_broadcast(&self) -> Option<impl Future<Output = ()> + Send + 'static> {
+        let Some(socket) = self
+            .sockets
+            .iter()
+            .find(|socket| socket.local_addr().is_ok_and(|address| address.is_ipv4()))
+        else {
+            warn!("LAN broadcast needs an IPv4 address to send from");
+            return None;
+        };
+        if let Err(e) = socket.set_broadcast(true) {
+            warn!("Cannot broadcast on the LAN: {}", e);
+            return None;
+        }
+        let socket = Arc::clone(socket);
+        let server_info = Arc::clone(&self.server_info);
+        let trace = Arc::clone(&self.packet_trace);
+        let traffic = Arc::clone(&self.traffic);
+        Some(async move {
+            let mut interval = tokio::time::interval(LAN_BROADCAST_INTERVAL);
+            let mut failing = false;
+            loop {
+                interval.tick().await;
+                let pong = UnconnectedPong {
+                    time: crate::utils::cur_time_millis(),
+                    server_guid: server_info.guid(),
+                    motd: server_info.pong_payload().to_string(),
+                };
+                let mut writer = BinaryWriter::new();
+                if writer.write_u8(UNCONNECTED_PONG).is_err() || pong.write(&mut writer).is_err() {
+                    error!("Failed to serialize the LAN broadcast");
+                    return;
+                }
+                let data = writer.freeze();
+                let target = LAN_BROADCAST_ADDRESS;
+                match packet_trace::send_to(&socket, &trace, &traffic, &data, target, target) {
+                    Ok(_) if failing => {
+                        info!("LAN broadcast works again");
+                        failing = false;
+                    }
+                    Ok(_) => {}
+                    Err(e) if !failing => {
+                        warn!("Failed to broadcast on the LAN: {}", e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+        })
+    }
+
+    /// Refuses new connections from now on, for a shutdown. Existing sessions carry on and the
+    /// server is still listed.
+    pub fn stop_accepting(&self) {
+        self.accepting.store(false, Ordering::Relaxed);
+    }
+
+    /// Disconnects every client and waits up to `timeout` for their sessions to end. Returns
+    /// how many sessions were closed.
+    pub async fn close_all(&self, timeout: Duration) -> usize {
+        let mut closed = 0;
+        for handle in self.sessions.iter() {
+            handle.close();
+            closed += 1;
+        }
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !self.sessions.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        closed
+    }
+
+    /// Ends every session with an accepted connection without disconnecting its client, and
+    /// returns what a new server process needs to continue them with
+    /// [`restore_sessions`](Self::restore_sessions). Sessions still in their handshake are
+    /// dropped, and their clients retry. Waits up to `timeout` for the sessions to end.
+    ///
+    /// Datagrams are no longer handled once [`run`](Self::run) stopped, and wait in the
+    /// socket buffers for the next process instead.
+    pub async fn detach_sessions(&self, timeout: Duration) -> Handoff {
+        self.stop_accepting();
+        let addresses: Vec<SocketAddr> = self.sessions.iter().map(|entry| *entry.key()).collect();
+        let handles: Vec<SessionHandle> = addresses
+            .iter()
+            .filter_map(|address| self.sessions.remove(address))
+            .map(|(_, handle)| handle)
+            .collect();
+        for handle in &handles {
+            handle.detach();
+        }
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut sessions = Vec::with_capacity(handles.len());
+        for handle in &handles {
+            if tokio::time::timeout_at(deadline, handle.closed()).await.is_err() {
+                warn!("Timed out detaching sessions");
+                break;
+            }
+            sessions.extend(handle.detached_state());
+        }
+        Handoff { sessions }
+    }
+
+    /// Continues the sessions of a previous server process. Returns how many were restored.
+    pub fn restore_sessions(&self, handoff: &Handoff) -> usize {
+        let mut restored = 0;
+        for state in &handoff.sessions {
+            let Some(socket) = self.sockets.get(state.socket) else {
+                warn!("No socket {} for the session of {}", state.socket, state.address);
+                continue;
+            };
+            let handle = session::restore(state, self.shared(socket));
+            self.sessions.insert(state.address, handle);
+            restored += 1;
+        }
+        restored
+    }
+
+    /// Duplicates the sockets, so they outlive the listener and can be passed on to a new
+    /// server process.
+    pub fn try_clone_sockets(&self) -> std::io::Result<Vec<std::net::UdpSocket>> {
+        self.sockets
+            .iter()
+            .map(|socket| SockRef::from(socket.as_ref()).try_clone().map(Into::into))
+            .collect()
+    }
+
+    /// Receives and handles packets on every socket until one of them fails. The task only
+    /// wakes when a datagram arrives.
+    pub async fn run(&self) -> Result<()> {
+        let mut receivers: Vec<_> = self
+            .sockets
+            .iter()
+            .map(|socket| Box::pin(self.receive(socket)))
+            .collect();
+        std::future::poll_fn(|cx| {
+            for receiver in &mut receivers {
+                if let Poll::Ready(result) = receiver.as_mut().poll(cx) {
+                    return Poll::Ready(result);
+                }
+            }
+            Poll::Pending
         })
+        .await
     }
 
-    pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut buf = [0u8; 2048];
+    /// Receives and handles the packets of one socket until it fails.
+    ///
+    /// Datagrams are received back to back into one buffer and frozen in place, so sessions
+    /// get slices of it instead of copies. The buffer's memory is reused once every datagram
+    /// in it has been dropped.
+    async fn receive(&self, socket: &Arc<UdpSocket>) -> Result<()> {
+        let mut buf = BytesMut::with_capacity(RECV_BUFFER_SIZE);
         loop {
-            match self.socket.recv_from(&mut buf) {
-                Ok((len, src_addr)) => {
+            if buf.capacity() < MAX_DATAGRAM_SIZE {
+                buf.reserve(RECV_BUFFER_SIZE);
+            }
+            match socket.recv_buf_from(&mut buf).await {
+                Ok((len, peer_addr)) => {
+                    packet_span!("datagram", peer = %peer_addr, len);
+                    let datagram = buf.split().freeze();
                     if len == 0 {
-                        warn!("Received empty packet from {}", src_addr);
+                        warn!("Received empty packet from {}", peer_addr);
                         continue;
                     }
 
-                    let packet_data = Bytes::copy_from_slice(&buf[..len]);
+                    let (src_addr, data) = match &self.proxy_clients {
+                        Some(clients) => match clients.resolve(peer_addr, &datagram) {
+                            Some((client, data)) if !data.is_empty() => {
+                                (client, datagram.slice_ref(data))
+                            }
+                            _ => continue,
+                        },
+                        None => (peer_addr, datagram),
+                    };
+                    self.traffic.record_datagram(Direction::Received, &data);
+                    self.packet_trace.record(Direction::Received, src_addr, &data);
+                    if is_offline_packet(data[0]) {
+                        if data[0] != protocol::UNCONNECTED_PING
+                            && !self.accepting.load(Ordering::Relaxed)
+                        {
+                            trace!("Not accepting connections, ignoring {:#04x}", data[0]);
+                            continue;
+                        }
+                        LogContext::new().with("peer", src_addr).scope(|| {
+                            self.handle_offline_packet(socket, &data, src_addr, peer_addr)
+                        });
+                        continue;
+                    }
 
-                    let socket_clone = Arc::clone(&self.socket);
-                    let server_name_clone = self.server_name.clone();
-                    let connections_clone = Arc::clone(&self.connections);
+                    if let Some(filter) = &self.packet_filter
+                        && !filter(src_addr, &data)
+                    {
+                        trace!("Packet from {} dropped by filter", src_addr);
+                        packet_event!("dropped by filter");
+                        continue;
+                    }
 
-                    tokio::spawn(handle_packet(
-                        socket_clone,
-                        packet_data,
-                        src_addr,
-                        server_name_clone,
-                        connections_clone,
-                    ));
+                    self.dispatch(socket, src_addr, peer_addr, data);
                 }
+                // Windows reports an ICMP port unreachable for an earlier reply this way.
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
                 Err(e) => {
                     error!("Error receiving UDP packet: {}", e);
                     return Err(e.into());
@@ -61,328 +534,323 @@ impl RakNetListener {
             }
         }
     }
-}
 
-async fn handle_packet(
-    socket: Arc<UdpSocket>,
-    packet_data: Bytes,
-    src_addr: SocketAddr,
-    server_name: Arc<String>,
-    connections: Arc<DashMap<SocketAddr, Connection>>,
-) {
-    if packet_data.is_empty() {
-        warn!("handle_packet received empty data from {}", src_addr);
-        return;
+    /// Feeds `recording` into a new session with the recorded gaps between datagrams, then
+    /// waits for the session to end. Replies go to `reply_addr`.
+    ///
+    /// The session sees the datagrams in order and only ever waits on the tokio clock, so on
+    /// a runtime with a paused clock the replay runs instantly and the same way every time.
+    pub async fn replay(&self, recording: &Recording, reply_addr: SocketAddr) {
+        // Sessions send without waiting, which fails until the socket is known to be ready,
+        // and nothing else has polled it when replaying.
+        let socket = &self.sockets[0];
+        if let Err(e) = socket.writable().await {
+            error!("Cannot replay, the socket is not writable: {}", e);
+            return;
+        }
+        let start = tokio::time::Instant::now();
+        let handle = session::spawn(recording.address, self.shared(socket));
+        for datagram in &recording.datagrams {
+            tokio::time::sleep_until(start + datagram.offset).await;
+            let datagram = Datagram {
+                payload: datagram.payload.clone(),
+                reply_addr,
+            };
+            if handle.send(datagram).is_err() {
+                break;
+            }
+        }
+        handle.closed().await;
     }
 
-    let packet_id = packet_data[0];
-    trace!(
-        "Handling packet ID {:#04x} from {} ({} bytes)",
-        packet_id,
-        src_addr,
-        packet_data.len()
-    );
-    logger().flush();
-
-    let mut reader = BinaryReader::new(packet_data);
-
-    if reader.read_u8().is_err() {
-        error!(
-            "Failed to advance reader past packet ID from {} (data len: {})",
-            src_addr,
-            reader.remaining() + 1
+    /// Hands a datagram to the session of `src_addr`. Only a `CONNECTION_REQUEST` starts a
+    /// new session, so stray datagrams do not cost a task each.
+    fn dispatch(
+        &self,
+        socket: &Arc<UdpSocket>,
+        src_addr: SocketAddr,
+        reply_addr: SocketAddr,
+        data: Bytes,
+    ) {
+        let packet_id = data[0];
+        let mut datagram = Datagram {
+            payload: data,
+            reply_addr,
+        };
+        if let Some(handle) = self.sessions.get(&src_addr) {
+            match handle.send(datagram) {
+                Ok(()) => {
+                    packet_event!(id = packet_id, "queued for session");
+                    return;
+                }
+                Err(returned) => datagram = returned,
+            }
+        }
+        if packet_id == protocol::CONNECTION_REQUEST && !self.accepting.load(Ordering::Relaxed) {
+            trace!("Not accepting connections, ignoring request from {}", src_addr);
+            return;
+        }
+        if packet_id == protocol::CONNECTION_REQUEST
+            && let Some(throttle) = &self.join_throttle
+            && !throttle.allows_session(src_addr)
+        {
+            debug!("Ignoring a connection request from {} that skipped the challenge", src_addr);
+            packet_event!("dropped by join throttle");
+            return;
+        }
+        if packet_id != protocol::CONNECTION_REQUEST {
+            LogContext::new().with("peer", src_addr).scope(|| {
+                if (0x80..=0x8F).contains(&packet_id) {
+                    warn!(
+                        "Received data frame {:#04x} from unknown address. Dropping.",
+                        packet_id
+                    );
+                } else if packet_id < 0x80 {
+                    debug!("Received unhandled offline RakNet packet ID {:#04x}", packet_id);
+                } else {
+                    trace!(
+                        "Received potential data packet ID {:#04x} (no connection)",
+                        packet_id
+                    );
+                }
+            });
+            return;
+        }
+        let handle = session::spawn(src_addr, self.shared(socket));
+        packet_event!("session started");
+        if let Some(hook) = &self.session_hook {
+            hook(src_addr);
+        }
+        if handle.send(datagram).is_ok() {
+            self.sessions.insert(src_addr, handle);
+        }
+    }
+
+    /// Handles offline (pre-connection) packets synchronously, decoding them straight out of the
+    /// receive buffer instead of copying each datagram into a `Bytes`. Replies go to `reply_addr`,
+    /// which differs from `src_addr` behind a PROXY protocol load balancer.
+    fn handle_offline_packet(
+        &self,
+        socket: &UdpSocket,
+        data: &[u8],
+        src_addr: SocketAddr,
+        reply_addr: SocketAddr,
+    ) {
+        let server_info = &self.server_info;
+        let trace = &self.packet_trace;
+        let throttle = self.join_throttle.as_ref();
+        let packet_id = data[0];
+        packet_span!("offline_packet", id = packet_id);
+        trace!(
+            "Handling offline packet ID {:#04x} ({} bytes)",
+            packet_id,
+            data.len()
         );
-        logger().flush();
-        return;
-    }
-
-    match packet_id {
-        protocol::UNCONNECTED_PING => {
-            debug!("Received UNCONNECTED_PING from {}", src_addr);
-            logger().flush();
-            match UnconnectedPing::read(&mut reader) {
-                Ok(ping_packet) => {
-                    trace!("Parsed UnconnectedPing: {:?}", ping_packet);
-                    logger().flush();
-
-                    let local_addr = match socket.local_addr() {
-                        Ok(addr) => addr,
-                        Err(e) => {
-                            error!("Failed to get local socket address for MOTD: {}", e);
-                            logger().flush();
-                            return;
-                        }
-                    };
-                    let port_str = local_addr.port().to_string();
-                    let ipv4_port_str = match local_addr {
-                        SocketAddr::V4(v4) if v4.port() == 19132 => port_str.clone(),
-                        _ => "19132".to_string(),
-                    };
-                    let ipv6_port_str = match local_addr {
-                        SocketAddr::V6(v6) if v6.port() == 19133 => port_str.clone(),
-                        _ => "19133".to_string(),
-                    };
 
-                    let motd = format!(
-                        "MCPE;{};{};{};{};{};{};{};{};{};{};{};",
-                        server_name,
-                        PROTOCOL_VERSION,
-                        MINECRAFT_VERSION,
-                        0,
-                        50,
-                        SERVER_GUID,
-                        "Amethyst World",
-                        "Survival",
-                        1,
-                        &ipv4_port_str,
-                        &ipv6_port_str
-                    );
+        let mut reader = BinaryRef::new(&data[1..]);
 
-                    let pong_packet = UnconnectedPong {
-                        time: ping_packet.time,
-                        server_guid: SERVER_GUID,
-                        motd,
-                    };
+        match packet_id {
+            protocol::UNCONNECTED_PING | protocol::UNCONNECTED_PING_OPEN_CONNECTIONS => {
+                debug!("Received {}", protocol::packet_name(packet_id).unwrap_or_default());
+                if packet_id == protocol::UNCONNECTED_PING_OPEN_CONNECTIONS
+                    && !server_info.has_open_slots()
+                {
+                    trace!("Server is full, not answering UNCONNECTED_PING_OPEN_CONNECTIONS");
+                    return;
+                }
+                match UnconnectedPing::read_ref(&mut reader) {
+                    Ok(ping_packet) => {
+                        self.stats.record_ping(src_addr.ip());
+                        packet_event!(?ping_packet, "decoded");
+                        trace!("Parsed UnconnectedPing: {:?}", ping_packet);
 
-                    let mut writer = BinaryWriter::new();
+                        let pong_packet = UnconnectedPong {
+                            time: ping_packet.time,
+                            server_guid: server_info.guid(),
+                            motd: server_info.pong_payload().to_string(),
+                        };
 
-                    if writer.write_u8(UNCONNECTED_PONG).is_ok()
-                        && pong_packet.write(&mut writer).is_ok()
-                    {
-                        let response_bytes = writer.freeze();
-
-                        match socket.send_to(response_bytes.as_ref(), src_addr) {
-                            Ok(sent_len) => {
-                                debug!(
-                                    "Sent UNCONNECTED_PONG ({} bytes) to {}",
-                                    sent_len, src_addr
-                                );
-                                logger().flush();
-                            }
-                            Err(e) => {
-                                error!("Failed to send UNCONNECTED_PONG to {}: {}", src_addr, e);
-                                logger().flush();
+                        let mut writer = BinaryWriter::new();
+
+                        if writer.write_u8(UNCONNECTED_PONG).is_ok()
+                            && pong_packet.write(&mut writer).is_ok()
+                        {
+                            let response_bytes = writer.freeze();
+
+                            match packet_trace::send_to(
+                                socket,
+                                trace,
+                                &self.traffic,
+                                response_bytes.as_ref(),
+                                src_addr,
+                                reply_addr,
+                            ) {
+                                Ok(sent_len) => {
+                                    packet_event!(id = UNCONNECTED_PONG, len = sent_len, "sent");
+                                    debug!("Sent UNCONNECTED_PONG ({} bytes)", sent_len);
+                                }
+                                Err(e) => {
+                                    error!("Failed to send UNCONNECTED_PONG: {}", e);
+                                }
                             }
+                        } else {
+                            error!("Failed to serialize UNCONNECTED_PONG");
                         }
-                    } else {
-                        error!("Failed to serialize UNCONNECTED_PONG for {}", src_addr);
-                        logger().flush();
                     }
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to parse UNCONNECTED_PING payload from {}: {}",
-                        src_addr, e
-                    );
-                    logger().flush();
+                    Err(e) => {
+                        warn!("Failed to parse UNCONNECTED_PING payload: {}", e);
+                    }
                 }
             }
-        }
-        protocol::OPEN_CONNECTION_REQUEST_1 => {
-            debug!("Received OPEN_CONNECTION_REQUEST_1 from {}", src_addr);
-            logger().flush();
-            match OpenConnectionRequest1::read(&mut reader) {
-                Ok(request) => {
-                    trace!("Parsed OpenConnectionRequest1: {:?}", request);
-
-                    if request.protocol_version != protocol::RAKNET_PROTOCOL_VERSION {
-                        warn!(
-                            "Client {} sent unsupported RakNet protocol version: {} (expected: {})",
-                            src_addr,
-                            request.protocol_version,
-                            protocol::RAKNET_PROTOCOL_VERSION
-                        );
-                        return;
-                    }
+            protocol::OPEN_CONNECTION_REQUEST_1 => {
+                debug!("Received OPEN_CONNECTION_REQUEST_1");
+                match OpenConnectionRequest1::read_ref(&mut reader) {
+                    Ok(request) => {
+                        trace!("Parsed OpenConnectionRequest1: {:?}", request);
+                        packet_event!(?request, "decoded");
 
-                    let server_mtu: u16 = 1400;
+                        if request.protocol_version != protocol::RAKNET_PROTOCOL_VERSION {
+                            warn!(
+                                "Client sent unsupported RakNet protocol version: {} \
+                                 (expected: {})",
+                                request.protocol_version,
+                                protocol::RAKNET_PROTOCOL_VERSION
+                            );
+                            return;
+                        }
 
-                    let reply = OpenConnectionReply1 {
-                        server_guid: SERVER_GUID,
-                        use_security: false,
-                        mtu_size: server_mtu,
-                    };
+                        let server_mtu: u16 = 1400;
 
-                    let mut writer = BinaryWriter::new();
-                    if writer.write_u8(protocol::OPEN_CONNECTION_REPLY_1).is_ok()
-                        && reply.write(&mut writer).is_ok()
-                    {
-                        let response_bytes = writer.freeze();
-                        match socket.send_to(response_bytes.as_ref(), src_addr) {
-                            Ok(sent_len) => debug!(
-                                "Sent OPEN_CONNECTION_REPLY_1 ({} bytes, MTU: {}) to {}",
-                                sent_len, server_mtu, src_addr
-                            ),
-                            Err(e) => error!(
-                                "Failed to send OPEN_CONNECTION_REPLY_1 to {}: {}",
-                                src_addr, e
-                            ),
+                        let reply = OpenConnectionReply1 {
+                            server_guid: server_info.guid(),
+                            cookie: throttle.and_then(|throttle| throttle.challenge(src_addr)),
+                            mtu_size: server_mtu,
+                        };
+
+                        let mut writer = BinaryWriter::new();
+                        if writer.write_u8(protocol::OPEN_CONNECTION_REPLY_1).is_ok()
+                            && reply.write(&mut writer).is_ok()
+                        {
+                            let response_bytes = writer.freeze();
+                            match packet_trace::send_to(
+                                socket,
+                                trace,
+                                &self.traffic,
+                                response_bytes.as_ref(),
+                                src_addr,
+                                reply_addr,
+                            ) {
+                                Ok(sent_len) => {
+                                    packet_event!(
+                                        id = protocol::OPEN_CONNECTION_REPLY_1,
+                                        len = sent_len,
+                                        "sent"
+                                    );
+                                    debug!(
+                                        "Sent OPEN_CONNECTION_REPLY_1 ({} bytes, MTU: {})",
+                                        sent_len, server_mtu
+                                    )
+                                }
+                                Err(e) => error!("Failed to send OPEN_CONNECTION_REPLY_1: {}", e),
+                            }
+                        } else {
+                            error!("Failed to serialize OPEN_CONNECTION_REPLY_1");
                         }
-                    } else {
-                        error!(
-                            "Failed to serialize OPEN_CONNECTION_REPLY_1 for {}",
-                            src_addr
-                        );
                     }
+                    Err(e) => warn!("Failed to parse OPEN_CONNECTION_REQUEST_1: {}", e),
                 }
-                Err(e) => warn!(
-                    "Failed to parse OPEN_CONNECTION_REQUEST_1 from {}: {}",
-                    src_addr, e
-                ),
             }
-            logger().flush();
-        }
-        protocol::OPEN_CONNECTION_REQUEST_2 => {
-            debug!("Received OPEN_CONNECTION_REQUEST_2 from {}", src_addr);
-            match OpenConnectionRequest2::read(&mut reader) {
-                Ok(request) => {
-                    trace!("Parsed OpenConnectionRequest2: {:?}", request);
-
-                    let server_max_mtu = 1400; // Must match MTU logic from Reply1 handler
-                    let final_mtu = request.mtu.min(server_max_mtu).max(400); // Ensure a minimum MTU
-
-                    let reply = OpenConnectionReply2 {
-                        server_guid: SERVER_GUID,
-                        client_addr: src_addr,
-                        mtu: final_mtu,
-                        use_encryption: false,
-                    };
+            protocol::OPEN_CONNECTION_REQUEST_2 => {
+                debug!("Received OPEN_CONNECTION_REQUEST_2");
+                match OpenConnectionRequest2::read_ref(&mut reader) {
+                    Ok(request) => {
+                        trace!("Parsed OpenConnectionRequest2: {:?}", request);
+                        packet_event!(?request, "decoded");
 
-                    let mut writer = BinaryWriter::new();
-                    if writer.write_u8(OPEN_CONNECTION_REPLY_2).is_ok()
-                        && reply.write(&mut writer).is_ok()
-                    {
-                        let response_bytes = writer.freeze();
-                        match socket.send_to(response_bytes.as_ref(), src_addr) {
-                            Ok(sent_len) => debug!(
-                                "Sent OPEN_CONNECTION_REPLY_2 ({} bytes, MTU: {}) to {}",
-                                sent_len, final_mtu, src_addr
-                            ),
-                            Err(e) => error!(
-                                "Failed to send OPEN_CONNECTION_REPLY_2 to {}: {}",
-                                src_addr, e
-                            ),
+                        if let Some(throttle) = throttle
+                            && !throttle.check_cookie(src_addr, request.cookie)
+                        {
+                            packet_event!("dropped by join throttle");
+                            return;
                         }
-                    } else {
-                        error!(
-                            "Failed to serialize OPEN_CONNECTION_REPLY_2 for {}",
-                            src_addr
-                        );
-                    }
-                }
-                Err(e) => warn!(
-                    "Failed to parse OPEN_CONNECTION_REQUEST_2 from {}: {}",
-                    src_addr, e
-                ),
-            }
-            logger().flush();
-        }
-        protocol::CONNECTION_REQUEST => {
-            if let Some(mut conn_entry) = connections.get_mut(&src_addr) {
-                if conn_entry.state == ConnectionState::Connected
-                    || conn_entry.state == ConnectionState::Connecting
-                {
-                    debug!(
-                        "Received duplicate CONNECTION_REQUEST from already known address {}",
-                        src_addr
-                    );
-                    conn_entry.update_last_packet_time();
-                    return;
-                }
-            }
 
-            match ConnectionRequest::read(&mut reader) {
-                Ok(request) => {
-                    debug!(
-                        "Received CONNECTION_REQUEST from {} (Client GUID: {}, Time: {}, Security: {})",
-                        src_addr, request.client_guid, request.time, request.use_security
-                    );
-                    trace!("Parsed ConnectionRequest: {:?}", request);
-                    let agreed_mtu = 1400;
-
-                    let mut new_connection =
-                        Connection::new(src_addr, request.client_guid, agreed_mtu);
-                    new_connection.state = ConnectionState::Connecting;
-
-                    let system_address = socket.local_addr().unwrap_or_else(|_| {
-                        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0)
-                    });
-
-                    let reply = ConnectionRequestAccepted {
-                        client_address: src_addr,
-                        system_index: 0,
-                        internal_ids: [system_address; 20],
-                        request_time: request.time,
-                        time: crate::utils::cur_time_millis(),
-                    };
-                    let mut writer = BinaryWriter::new();
-                    if writer.write_u8(CONNECTION_REQUEST_ACCEPTED).is_ok()
-                        && reply.write(&mut writer).is_ok()
-                    {
-                        let response_bytes = writer.freeze();
-                        match socket.send_to(response_bytes.as_ref(), src_addr) {
-                            Ok(sent_len) => {
-                                debug!(
-                                    "Sent CONNECTION_REQUEST_ACCEPTED ({} bytes) to {}",
-                                    sent_len, src_addr
-                                );
-                                // Insert/update the connection state *after* successfully sending the reply
-                                connections.insert(src_addr, new_connection);
+                        let server_max_mtu = 1400; // Must match MTU logic from Reply1 handler
+                        let final_mtu = request.mtu.min(server_max_mtu).max(400); // Ensure a minimum MTU
+
+                        let reply = OpenConnectionReply2 {
+                            server_guid: server_info.guid(),
+                            client_addr: src_addr,
+                            mtu: final_mtu,
+                            use_encryption: false,
+                        };
+
+                        let mut writer = BinaryWriter::new();
+                        if writer.write_u8(OPEN_CONNECTION_REPLY_2).is_ok()
+                            && reply.write(&mut writer).is_ok()
+                        {
+                            let response_bytes = writer.freeze();
+                            match packet_trace::send_to(
+                                socket,
+                                trace,
+                                &self.traffic,
+                                response_bytes.as_ref(),
+                                src_addr,
+                                reply_addr,
+                            ) {
+                                Ok(sent_len) => {
+                                    packet_event!(
+                                        id = OPEN_CONNECTION_REPLY_2,
+                                        len = sent_len,
+                                        "sent"
+                                    );
+                                    debug!(
+                                        "Sent OPEN_CONNECTION_REPLY_2 ({} bytes, MTU: {})",
+                                        sent_len, final_mtu
+                                    )
+                                }
+                                Err(e) => error!("Failed to send OPEN_CONNECTION_REPLY_2: {}", e),
                             }
-                            Err(e) => error!(
-                                "Failed to send CONNECTION_REQUEST_ACCEPTED to {}: {}",
-                                src_addr, e
-                            ),
+                        } else {
+                            error!("Failed to serialize OPEN_CONNECTION_REPLY_2");
                         }
-                    } else {
-                        error!(
-                            "Failed to serialize CONNECTION_REQUEST_ACCEPTED for {}",
-                            src_addr
-                        );
                     }
+                    Err(e) => warn!("Failed to parse OPEN_CONNECTION_REQUEST_2: {}", e),
                 }
-                Err(e) => warn!(
-                    "Failed to parse CONNECTION_REQUEST from {}: {}",
-                    src_addr, e
-                ),
             }
-            logger().flush();
+            _ => unreachable!("is_offline_packet() admitted packet ID {:#04x}", packet_id),
         }
-        0x80..=0x8F => {
-            trace!("Received potential data frame {:#04x} from {}", packet_id, src_addr);
-            if let Some(mut connection_entry) = connections.get_mut(&src_addr) {
-                let connection = connection_entry.value_mut();
-                connection.update_last_packet_time();
-
-                if connection.state == ConnectionState::Connecting {
-                    debug!("Connection from {} promoted to Connected state.", src_addr);
-                    connection.state = ConnectionState::Connected;
-                }
+    }
 
-                if connection.state == ConnectionState::Connected {
-                    warn!("Received data frame {:#04x}, but reliability layer not implemented yet for {}. Dropping.", packet_id, src_addr);
-                } else {
-                    warn!("Received data frame {:#04x} from {} in unexpected state {:?}. Dropping.", packet_id, src_addr, connection.state);
-                }
-            } else {
-                warn!("Received data frame {:#04x} from unknown address {}. Dropping.", packet_id, src_addr);
-            }
-            logger().flush();
-        }
-        _ => {
-            if packet_id < 0x80 {
-                debug!(
-                    "Received unhandled offline RakNet packet ID {:#04x} from {}",
-                    packet_id, src_addr
-                );
-                logger().flush();
-            } else {
-                trace!(
-                    "Received potential data packet ID {:#04x} from {} (no connection)",
-                    packet_id, src_addr
-                );
-                logger().flush();
-            }
+    /// What a session needs, replying through `socket`.
+    fn shared(&self, socket: &Arc<UdpSocket>) -> Shared {
+        Shared {
+            socket: Arc::clone(socket),
+            connections: Arc::clone(&self.connections),
+            sessions: Arc::clone(&self.sessions),
+            server_info: Arc::clone(&self.server_info),
+            stats: Arc::clone(&self.stats),
+            packet_trace: Arc::clone(&self.packet_trace),
+            traffic: Arc::clone(&self.traffic),
+            handshake_timeout: self.handshake_timeout,
+            record_directory: self.record_directory.clone(),
+            strict_mode: self.strict_mode,
+            system_addresses: Arc::clone(&self.system_addresses),
+            socket_index: self
+                .sockets
+                .iter()
+                .position(|candidate| Arc::ptr_eq(candidate, socket))
+                .unwrap_or_default(),
         }
     }
 }
+
+fn is_offline_packet(packet_id: u8) -> bool {
+    matches!(
+        packet_id,
+        protocol::UNCONNECTED_PING
+            | protocol::UNCONNECTED_PING_OPEN_CONNECTIONS
+            | protocol::OPEN_CONNECTION_REQUEST_1
+            | protocol::OPEN_CONNECTION_REQUEST_2
+    )
+}
+
@@ -0,0 +1,179 @@
+use std::net::SocketAddr;
+use thiserror::Error;
+
+pub const MINECRAFT_VERSION: &str = "1.20.80";
+pub const PROTOCOL_VERSION: u32 = 662;
+
+const DEFAULT_IPV4_PORT: u16 = 19132;
+const DEFAULT_IPV6_PORT: u16 = 19133;
+
+/// The configurable part of what the server advertises in its MOTD (the `UNCONNECTED_PONG`
+/// server list entry).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motd {
+    /// First line in the server list. `&` color codes are translated to `§`.
+    pub motd: String,
+    /// Second line in the server list. `&` color codes are translated to `§`.
+    pub world_name: String,
+    /// Game mode name shown in the server list, e.g. "Survival".
+    pub game_mode: String,
+    pub max_players: u32,
+}
+
+/// Composes the MCPE server list string from the configured [`Motd`] and the live state of
+/// the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotdBuilder {
+    motd: Motd,
+    guid: u64,
+    player_count: u32,
+    ipv4_port: u16,
+    ipv6_port: u16,
+}
+
+impl MotdBuilder {
+    pub fn new(guid: u64, motd: Motd) -> Self {
+        Self {
+            motd,
+            guid,
+            player_count: 0,
+            ipv4_port: DEFAULT_IPV4_PORT,
+            ipv6_port: DEFAULT_IPV6_PORT,
+        }
+    }
+
+    pub fn motd(&self) -> &Motd {
+        &self.motd
+    }
+
+    pub fn player_count(&self) -> u32 {
+        self.player_count
+    }
+
+    pub fn set_motd(&mut self, motd: Motd) {
+        self.motd = motd;
+    }
+
+    pub fn set_player_count(&mut self, player_count: u32) {
+        self.player_count = player_count;
+    }
+
+    /// Advertises the port of `address` for its address family.
+    pub fn set_local_address(&mut self, address: SocketAddr) {
+        match address {
+            SocketAddr::V4(v4) => self.ipv4_port = v4.port(),
+            SocketAddr::V6(v6) => self.ipv6_port = v6.port(),
+        }
+    }
+
+    pub fn build(&self) -> String {
+        format!(
+            "MCPE;{};{};{};{};{};{};{};{};{};{};{};",
+            format_text(&self.motd.motd),
+            PROTOCOL_VERSION,
+            MINECRAFT_VERSION,
+            self.player_count,
+            self.motd.max_players,
+            self.guid,
+            format_text(&self.motd.world_name),
+            format_text(&self.motd.game_mode),
+            1,
+            self.ipv4_port,
+            self.ipv6_port
+        )
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MotdParseError {
+    #[error("Not an MCPE server list entry")]
+    Edition,
+    #[error("Missing field {0}")]
+    Missing(&'static str),
+    #[error("Invalid {0}: {1:?}")]
+    Invalid(&'static str, String),
+}
+
+/// A server list entry as read from another server's `UNCONNECTED_PONG`.
+///
+/// Only the first six fields are required; older servers and some proxies stop there. Text
+/// fields keep their `§` codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerListEntry {
+    pub motd: String,
+    pub protocol_version: u32,
+    pub minecraft_version: String,
+    pub player_count: u32,
+    pub max_players: u32,
+    pub guid: Option<u64>,
+    pub world_name: Option<String>,
+    pub game_mode: Option<String>,
+    pub ipv4_port: Option<u16>,
+    pub ipv6_port: Option<u16>,
+}
+
+impl ServerListEntry {
+    /// Parses an MCPE MOTD string such as the one [`MotdBuilder::build`] produces.
+    pub fn parse(motd: &str) -> Result<Self, MotdParseError> {
+        let mut fields = motd.split(';');
+        if fields.next() != Some("MCPE") {
+            return Err(MotdParseError::Edition);
+        }
+        let mut required = |name| fields.next().ok_or(MotdParseError::Missing(name));
+        let motd = required("motd")?.to_string();
+        let protocol_version = parse_field("protocol version", required("protocol version")?)?;
+        let minecraft_version = required("version")?.to_string();
+        let player_count = parse_field("player count", required("player count")?)?;
+        let max_players = parse_field("max players", required("max players")?)?;
+        let mut optional = || fields.next().filter(|field| !field.is_empty());
+        let guid = optional().map(|f| parse_field("GUID", f)).transpose()?;
+        let world_name = optional().map(str::to_string);
+        let game_mode = optional().map(str::to_string);
+        let _game_mode_id = optional();
+        let ipv4_port = optional()
+            .map(|f| parse_field("IPv4 port", f))
+            .transpose()?;
+        let ipv6_port = optional()
+            .map(|f| parse_field("IPv6 port", f))
+            .transpose()?;
+        Ok(Self {
+            motd,
+            protocol_version,
+            minecraft_version,
+            player_count,
+            max_players,
+            guid,
+            world_name,
+            game_mode,
+            ipv4_port,
+            ipv6_port,
+        })
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(name: &'static str, field: &str) -> Result<T, MotdParseError> {
+    field
+        .parse()
+        .map_err(|_| MotdParseError::Invalid(name, field.to_string()))
+}
+
+/// Translates `&` color and formatting codes (e.g. `&a`, `&l`) to the `§` codes the client
+/// renders, and replaces `;`, which would break the field layout of the MOTD.
+pub fn format_text(text: &str) -> String {
+    let mut formatted = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' if chars.peek().is_some_and(|&code| is_format_code(code)) => formatted.push('§'),
+            ';' => formatted.push(':'),
+            _ => formatted.push(c),
+        }
+    }
+    formatted
+}
+
+/// Bedrock uses `0`-`9` and `a`-`v` for colors (including the material colors added in 1.20)
+/// and `k`, `l`, `o`, `r` for formatting.
+fn is_format_code(code: char) -> bool {
+    matches!(code.to_ascii_lowercase(), '0'..='9' | 'a'..='v')
+}
@@ -0,0 +1,349 @@
+//! Hex dumps of the datagrams the listener receives and sends, annotated with the packet ID
+//! and decoded header fields, for debugging the protocol by hand. They go to a file of their
+//! own so the log stays readable, and can be switched on and off while the server runs.
+//!
+//! When tracing is off, each datagram costs a relaxed atomic load.
+
+use crate::protocol::*;
+use crate::traffic::TrafficStats;
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::BinaryReader;
+use amethyst_binary::traits::Readable;
+use bytes::Bytes;
+use chrono::Local;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::{AddrParseError, IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use tokio::net::UdpSocket;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+/// A client, by IP address alone or with its port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerPattern {
+    Ip(IpAddr),
+    Address(SocketAddr),
+}
+
+impl PeerPattern {
+    fn matches(self, peer: SocketAddr) -> bool {
+        match self {
+            PeerPattern::Ip(ip) => peer.ip() == ip,
+            PeerPattern::Address(address) => peer == address,
+        }
+    }
+}
+
+impl FromStr for PeerPattern {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(address) => Ok(PeerPattern::Address(address)),
+            Err(_) => s.parse().map(PeerPattern::Ip),
+        }
+    }
+}
+
+/// Which datagrams are traced. Empty include lists let everything through.
+///
+/// A datagram's IDs are its own and, for frame sets, those of the unsplit packets inside.
+/// It is traced if any of them is included, unless its own ID is excluded or every packet
+/// inside is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PacketTraceFilter {
+    pub include_ids: Vec<u8>,
+    pub exclude_ids: Vec<u8>,
+    pub include_peers: Vec<PeerPattern>,
+    pub exclude_peers: Vec<PeerPattern>,
+}
+
+impl PacketTraceFilter {
+    fn allows_peer(&self, peer: SocketAddr) -> bool {
+        (self.include_peers.is_empty() || self.include_peers.iter().any(|p| p.matches(peer)))
+            && !self.exclude_peers.iter().any(|p| p.matches(peer))
+    }
+
+    fn allows_ids(&self, id: u8, inner: &[u8]) -> bool {
+        if self.exclude_ids.contains(&id)
+            || (!inner.is_empty() && inner.iter().all(|id| self.exclude_ids.contains(id)))
+        {
+            return false;
+        }
+        self.include_ids.is_empty()
+            || std::iter::once(&id)
+                .chain(inner)
+                .any(|id| self.include_ids.contains(id))
+    }
+}
+
+struct TraceState {
+    filter: PacketTraceFilter,
+    file: Option<(PathBuf, BufWriter<File>)>,
+}
+
+pub struct PacketTrace {
+    enabled: AtomicBool,
+    state: Mutex<TraceState>,
+}
+
+impl Default for PacketTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PacketTrace {
+    /// Starts switched off.
+    pub fn new() -> Self {
+        PacketTrace {
+            enabled: AtomicBool::new(false),
+            state: Mutex::new(TraceState {
+                filter: PacketTraceFilter::default(),
+                file: None,
+            }),
+        }
+    }
+
+    /// Starts tracing to `path`, appending if it exists. Switches from the current file if
+    /// tracing was on already.
+    pub fn enable(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.state();
+        if state
+            .file
+            .as_ref()
+            .is_some_and(|(current, _)| current == path)
+        {
+            return Ok(());
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        state.file = Some((path.to_path_buf(), BufWriter::new(file)));
+        self.enabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn disable(&self) {
+        let mut state = self.state();
+        self.enabled.store(false, Ordering::Relaxed);
+        if let Some((_, mut file)) = state.file.take() {
+            let _ = file.flush();
+        }
+    }
+
+    /// The file being traced to, or `None` if tracing is off.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.state().file.as_ref().map(|(path, _)| path.clone())
+    }
+
+    pub fn set_filter(&self, filter: PacketTraceFilter) {
+        self.state().filter = filter;
+    }
+
+    fn state(&self) -> MutexGuard<'_, TraceState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Dumps `data`, exchanged with `peer`, if tracing is on and the filter lets it through.
+    pub fn record(&self, direction: Direction, peer: SocketAddr, data: &[u8]) {
+        if !self.enabled.load(Ordering::Relaxed) || data.is_empty() {
+            return;
+        }
+        let mut state = self.state();
+        if !state.filter.allows_peer(peer) {
+            return;
+        }
+        let dump = Dump::decode(data);
+        if !state.filter.allows_ids(data[0], &dump.inner_ids) {
+            return;
+        }
+        let Some((_, file)) = &mut state.file else {
+            return;
+        };
+        let text = dump.render(direction, peer, data);
+        // A trace that cannot be written is not worth failing over.
+        let _ = file.write_all(text.as_bytes()).and_then(|()| file.flush());
+    }
+}
+
+/// Sends `data` to `reply_addr`, counts it, and traces it as sent to `peer`, which differs
+/// from `reply_addr` behind a PROXY protocol load balancer.
+pub(crate) fn send_to(
+    socket: &UdpSocket,
+    trace: &PacketTrace,
+    traffic: &TrafficStats,
+    data: &[u8],
+    peer: SocketAddr,
+    reply_addr: SocketAddr,
+) -> io::Result<usize> {
+    let sent = socket.try_send_to(data, reply_addr)?;
+    traffic.record_datagram(Direction::Sent, data);
+    trace.record(Direction::Sent, peer, data);
+    Ok(sent)
+}
+
+/// What a datagram says about itself.
+struct Dump {
+    summary: String,
+    inner_ids: Vec<u8>,
+}
+
+impl Dump {
+    fn decode(data: &[u8]) -> Self {
+        let mut inner_ids = Vec::new();
+        let summary =
+            describe(data, &mut inner_ids).unwrap_or_else(|e| format!("malformed: {}", e));
+        Dump { summary, inner_ids }
+    }
+
+    /// A header line, then the bytes 16 to a line with their offset and ASCII.
+    fn render(&self, direction: Direction, peer: SocketAddr, data: &[u8]) -> String {
+        let arrow = match direction {
+            Direction::Received => "<-",
+            Direction::Sent => "->",
+        };
+        let name = packet_name(data[0]).unwrap_or("UNKNOWN");
+        let mut text = format!(
+            "{} {} {} {:#04x} {} ({} bytes)",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            arrow,
+            peer,
+            data[0],
+            name,
+            data.len()
+        );
+        if !self.summary.is_empty() {
+            let _ = write!(text, " {}", self.summary);
+        }
+        text.push('\n');
+        for (i, line) in data.chunks(16).enumerate() {
+            let _ = write!(text, "  {:04x} ", i * 16);
+            for column in 0..16 {
+                match line.get(column) {
+                    Some(byte) => {
+                        let _ = write!(text, " {:02x}", byte);
+                    }
+                    None => text.push_str("   "),
+                }
+            }
+            text.push_str("  |");
+            text.extend(line.iter().map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            }));
+            text.push_str("|\n");
+        }
+        text
+    }
+}
+
+fn read<T: Readable>(body: &[u8]) -> Result<T, BinaryError> {
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(body)))
+}
+
+/// The header fields of `data`, noting the IDs of the packets in a frame set.
+fn describe(data: &[u8], inner_ids: &mut Vec<u8>) -> Result<String, BinaryError> {
+    let body = &data[1..];
+    Ok(match data[0] {
+        UNCONNECTED_PING | UNCONNECTED_PING_OPEN_CONNECTIONS => {
+            let ping: UnconnectedPing = read(body)?;
+            format!("time={} client_guid={}", ping.time, ping.client_guid)
+        }
+        UNCONNECTED_PONG => {
+            let pong: UnconnectedPong = read(body)?;
+            format!("time={} server_guid={}", pong.time, pong.server_guid)
+        }
+        OPEN_CONNECTION_REQUEST_1 => {
+            let request: OpenConnectionRequest1 = read(body)?;
+            format!(
+                "protocol={} mtu={}",
+                request.protocol_version,
+                data.len() + 28
+            )
+        }
+        OPEN_CONNECTION_REPLY_1 => {
+            let reply: OpenConnectionReply1 = read(body)?;
+            format!("server_guid={} mtu={}", reply.server_guid, reply.mtu_size)
+        }
+        OPEN_CONNECTION_REQUEST_2 => {
+            let request: OpenConnectionRequest2 = read(body)?;
+            format!(
+                "server_addr={} mtu={} client_guid={}",
+                request.server_addr, request.mtu, request.client_guid
+            )
+        }
+        OPEN_CONNECTION_REPLY_2 => {
+            let reply: OpenConnectionReply2 = read(body)?;
+            format!(
+                "server_guid={} client_addr={} mtu={}",
+                reply.server_guid, reply.client_addr, reply.mtu
+            )
+        }
+        CONNECTION_REQUEST => {
+            let request: ConnectionRequest = read(body)?;
+            format!("client_guid={} time={}", request.client_guid, request.time)
+        }
+        CONNECTION_REQUEST_ACCEPTED => {
+            let accepted: ConnectionRequestAccepted = read(body)?;
+            format!(
+                "client_address={} request_time={} time={}",
+                accepted.client_address, accepted.request_time, accepted.time
+            )
+        }
+        ACK | NACK => {
+            let packet: AckNackPacket = read(body)?;
+            let records: Vec<String> = packet
+                .records
+                .iter()
+                .map(|record| match record {
+                    AckNackRecord::Single(number) => number.to_string(),
+                    AckNackRecord::Range(range) => format!("{}-{}", range.start, range.end),
+                })
+                .collect();
+            format!("records=[{}]", records.join(","))
+        }
+        0x80..=0x8f => {
+            let frame_set: FrameSetPacket = read(body)?;
+            let mut text = format!("seq={}", frame_set.sequence_number);
+            for frame in &frame_set.packets {
+                let _ = write!(text, " [{:?}", frame.reliability);
+                if let Some(index) = frame.sequence_number {
+                    let _ = write!(text, " rel={}", index);
+                }
+                if let (Some(index), Some(channel)) = (frame.ordering_index, frame.ordering_channel)
+                {
+                    let _ = write!(text, " ord={}@{}", index, channel);
+                }
+                if let (Some(id), Some(index), Some(count)) =
+                    (frame.split_id, frame.split_index, frame.split_count)
+                {
+                    let _ = write!(text, " split={}:{}/{}", id, index + 1, count);
+                }
+                let _ = write!(text, " len={}", frame.payload.len());
+                if let Some(&id) = frame.payload.first()
+                    && !frame.is_split
+                {
+                    inner_ids.push(id);
+                    let _ = write!(text, " {:#04x}", id);
+                    if let Some(name) = packet_name(id) {
+                        let _ = write!(text, " {}", name);
+                    }
+                }
+                text.push(']');
+            }
+            text
+        }
+        _ => String::new(),
+    })
+}
@@ -1,10 +1,11 @@
 use amethyst_binary::error::BinaryError;
 use amethyst_binary::error::BinaryError::{InvalidData, UnexpectedEOF};
-use amethyst_binary::io::{BinaryReader, BinaryWriter};
-use amethyst_binary::traits::{Readable, Writable};
+use amethyst_binary::io::{BinaryReader, BinaryRef, BinaryWriter};
+use amethyst_binary::traits::{Readable, ReadableRef, Writable};
 use bytes::{Bytes};
 use std::net::SocketAddr;
 use crate::connection::SequenceNumberRange;
+use crate::seq::SeqNum;
 
 pub const RAKNET_PROTOCOL_VERSION: u8 = 11;
 
@@ -14,6 +15,8 @@ pub const MAGIC: [u8; 16] = [
 
 pub const CONNECTED_PING: u8 = 0x00;
 pub const UNCONNECTED_PING: u8 = 0x01;
+/// An [`UnconnectedPing`] that only wants an answer if the server has a free connection slot.
+pub const UNCONNECTED_PING_OPEN_CONNECTIONS: u8 = 0x02;
 pub const CONNECTED_PONG: u8 = 0x03;
 pub const OPEN_CONNECTION_REQUEST_1: u8 = 0x05;
 pub const OPEN_CONNECTION_REPLY_1: u8 = 0x06;
@@ -23,10 +26,38 @@ pub const CONNECTION_REQUEST: u8 = 0x09;
 pub const UNCONNECTED_PONG: u8 = 0x1c;
 pub const CONNECTION_REQUEST_ACCEPTED: u8 = 0x10;
 pub const NEW_INCOMING_CONNECTION: u8 = 0x13;
+pub const DISCONNECTION_NOTIFICATION: u8 = 0x15;
+/// A batch of game packets, sent inside frame sets once connected.
+pub const GAME_PACKET: u8 = 0xfe;
+/// Header of the frame sets the server sends: a valid datagram that needs B and AS.
+pub const FRAME_SET: u8 = 0x84;
 pub const ACK: u8 = 0xc0;
 pub const NACK: u8 = 0xa0;
 
-#[derive(Clone, Debug)]
+/// Name of the constant for packet `id`, for logs and traces.
+pub fn packet_name(id: u8) -> Option<&'static str> {
+    Some(match id {
+        CONNECTED_PING => "CONNECTED_PING",
+        UNCONNECTED_PING => "UNCONNECTED_PING",
+        UNCONNECTED_PING_OPEN_CONNECTIONS => "UNCONNECTED_PING_OPEN_CONNECTIONS",
+        CONNECTED_PONG => "CONNECTED_PONG",
+        OPEN_CONNECTION_REQUEST_1 => "OPEN_CONNECTION_REQUEST_1",
+        OPEN_CONNECTION_REPLY_1 => "OPEN_CONNECTION_REPLY_1",
+        OPEN_CONNECTION_REQUEST_2 => "OPEN_CONNECTION_REQUEST_2",
+        OPEN_CONNECTION_REPLY_2 => "OPEN_CONNECTION_REPLY_2",
+        CONNECTION_REQUEST => "CONNECTION_REQUEST",
+        UNCONNECTED_PONG => "UNCONNECTED_PONG",
+        CONNECTION_REQUEST_ACCEPTED => "CONNECTION_REQUEST_ACCEPTED",
+        NEW_INCOMING_CONNECTION => "NEW_INCOMING_CONNECTION",
+        DISCONNECTION_NOTIFICATION => "DISCONNECTION_NOTIFICATION",
+        ACK => "ACK",
+        NACK => "NACK",
+        0x80..=0x8f => "FRAME_SET",
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConnectedPing {
     pub time: u64,
 }
@@ -45,7 +76,7 @@ impl Readable for ConnectedPing {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UnconnectedPing {
     pub time: u64,
     pub client_guid: u64,
@@ -62,10 +93,14 @@ impl Writable for UnconnectedPing {
 
 impl Readable for UnconnectedPing {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        reader.read_borrowed()
+    }
+}
+
+impl ReadableRef for UnconnectedPing {
+    fn read_ref(reader: &mut BinaryRef<'_>) -> Result<Self, BinaryError> {
         let time = reader.read_u64()?;
-        let mut bytes = [0u8; 16];
-        reader.read_exact(&mut bytes)?;
-        if bytes != MAGIC {
+        if reader.read_bytes(MAGIC.len())? != MAGIC {
             Err(InvalidData("Expected magic bytes.".to_string()))?
         }
         if reader.remaining() < 8 {
@@ -78,7 +113,7 @@ impl Readable for UnconnectedPing {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConnectedPong {
     pub ping_time: u64,
     pub pong_time: u64,
@@ -103,7 +138,23 @@ impl Readable for ConnectedPong {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Closes the connection. The packet ID is all there is to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisconnectionNotification;
+
+impl Writable for DisconnectionNotification {
+    fn write(&self, _writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        Ok(())
+    }
+}
+
+impl Readable for DisconnectionNotification {
+    fn read(_reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(Self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UnconnectedPong {
     pub time: u64,
     pub server_guid: u64,
@@ -148,7 +199,7 @@ impl Readable for UnconnectedPong {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpenConnectionRequest1 {
     pub protocol_version: u8,
     //pub payload: Bytes,
@@ -165,6 +216,12 @@ impl Writable for OpenConnectionRequest1 {
 
 impl Readable for OpenConnectionRequest1 {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        reader.read_borrowed()
+    }
+}
+
+impl ReadableRef for OpenConnectionRequest1 {
+    fn read_ref(reader: &mut BinaryRef<'_>) -> Result<Self, BinaryError> {
         let mut bytes = [0u8; 16];
         reader.read_exact(&mut bytes)?;
         if bytes != MAGIC {
@@ -182,10 +239,12 @@ impl Readable for OpenConnectionRequest1 {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpenConnectionReply1 {
     pub server_guid: u64,
-    pub use_security: bool,
+    /// Cookie the client has to echo in its `OPEN_CONNECTION_REQUEST_2`, sent with the
+    /// security flag set.
+    pub cookie: Option<u32>,
     pub mtu_size: u16,
 }
 
@@ -193,7 +252,10 @@ impl Writable for OpenConnectionReply1 {
     fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
         writer.write_bytes(MAGIC.as_slice())?;
         writer.write_u64(self.server_guid)?;
-        writer.write_u8(if self.use_security { 1 } else { 0 })?;
+        writer.write_u8(if self.cookie.is_some() { 1 } else { 0 })?;
+        if let Some(cookie) = self.cookie {
+            writer.write_u32(cookie)?;
+        }
         writer.write_u16(self.mtu_size)?;
         Ok(())
     }
@@ -211,9 +273,9 @@ impl Readable for OpenConnectionReply1 {
         }
         let server_guid = reader.read_u64()?;
         let security_byte = reader.read_u8()?;
-        let use_security = match security_byte {
-            0 => false,
-            1 => true,
+        let cookie = match security_byte {
+            0 => None,
+            1 => Some(reader.read_u32()?),
             _ => {
                 return Err(InvalidData(format!(
                     "Invalid value for use_security: {}",
@@ -224,14 +286,21 @@ impl Readable for OpenConnectionReply1 {
         let mtu_size = reader.read_u16()?;
         Ok(Self {
             server_guid,
-            use_security,
+            cookie,
             mtu_size,
         })
     }
 }
 
-#[derive(Clone, Debug)]
+/// Length of an `OPEN_CONNECTION_REQUEST_2` with a cookie after the magic: the cookie, the
+/// challenge flag, the IPv4 or IPv6 server address, the MTU and the GUID.
+const REQUEST_2_V4_COOKIE_LEN: usize = 4 + 1 + 7 + 2 + 8;
+const REQUEST_2_V6_COOKIE_LEN: usize = 4 + 1 + 29 + 2 + 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpenConnectionRequest2 {
+    /// The cookie of the `OPEN_CONNECTION_REPLY_1`, if it had one.
+    pub cookie: Option<u32>,
     pub server_addr: SocketAddr,
     pub mtu: u16,
     pub client_guid: u64,
@@ -240,6 +309,12 @@ pub struct OpenConnectionRequest2 {
 impl Writable for OpenConnectionRequest2 {
     fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
         writer.write_bytes(MAGIC.as_slice())?;
+        if let Some(cookie) = self.cookie {
+            writer.write_u32(cookie)?;
+            // Whether a challenge for the server's public key follows, which is never the case
+            // without RakNet's encryption.
+            writer.write_u8(0)?;
+        }
         writer.write_raknet_address(self.server_addr)?;
         writer.write_u16(self.mtu)?;
         writer.write_u64(self.client_guid)?;
@@ -249,6 +324,12 @@ impl Writable for OpenConnectionRequest2 {
 
 impl Readable for OpenConnectionRequest2 {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        reader.read_borrowed()
+    }
+}
+
+impl ReadableRef for OpenConnectionRequest2 {
+    fn read_ref(reader: &mut BinaryRef<'_>) -> Result<Self, BinaryError> {
         let mut bytes = [0u8; 16];
         reader.read_exact(&mut bytes)?;
         if bytes != MAGIC {
@@ -257,10 +338,23 @@ impl Readable for OpenConnectionRequest2 {
                 bytes
             )));
         }
+        // Nothing marks the cookie, but it makes the packet longer than it can be without one.
+        let has_cookie = matches!(
+            reader.remaining(),
+            REQUEST_2_V4_COOKIE_LEN | REQUEST_2_V6_COOKIE_LEN
+        );
+        let cookie = if has_cookie {
+            let cookie = reader.read_u32()?;
+            reader.read_u8()?;
+            Some(cookie)
+        } else {
+            None
+        };
         let server_addr = reader.read_raknet_address()?;
         let mtu = reader.read_u16()?;
         let client_guid = reader.read_u64()?;
         Ok(Self {
+            cookie,
             server_addr,
             mtu,
             client_guid,
@@ -268,7 +362,7 @@ impl Readable for OpenConnectionRequest2 {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpenConnectionReply2 {
     pub server_guid: u64,
     pub client_addr: SocketAddr,
@@ -320,7 +414,7 @@ impl Readable for OpenConnectionReply2 {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConnectionRequest {
     pub client_guid: u64,
     pub time: u64,
@@ -359,21 +453,22 @@ impl Readable for ConnectionRequest {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConnectionRequestAccepted {
     pub client_address: SocketAddr,
     pub system_index: u16,
-    pub internal_ids: [SocketAddr; 20],
+    /// The server's own addresses. Bedrock sends 20, other RakNet versions 10.
+    pub internal_ids: Vec<SocketAddr>,
     pub request_time: u64,
     pub time: u64,
 }
 
 impl Writable for ConnectionRequestAccepted {
     fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
-        writer.write_socket_addr(&self.client_address)?;
+        writer.write_raknet_address(self.client_address)?;
         writer.write_u16(self.system_index)?;
         for addr in &self.internal_ids {
-            writer.write_socket_addr(addr)?;
+            writer.write_raknet_address(*addr)?;
         }
         writer.write_u64(self.request_time)?;
         writer.write_u64(self.time)?;
@@ -383,12 +478,12 @@ impl Writable for ConnectionRequestAccepted {
 
 impl Readable for ConnectionRequestAccepted {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
-        let client_address = reader.read_socket_addr()?;
+        let client_address = reader.read_raknet_address()?;
         let system_index = reader.read_u16()?;
-        let mut internal_ids =
-            [SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0); 20];
-        for addr in internal_ids.iter_mut() {
-            *addr = reader.read_socket_addr()?;
+        // The count is not sent, but only the two timestamps follow the addresses.
+        let mut internal_ids = Vec::new();
+        while reader.remaining() > 16 {
+            internal_ids.push(reader.read_raknet_address()?);
         }
         let request_time = reader.read_u64()?;
         let time = reader.read_u64()?;
@@ -402,9 +497,9 @@ impl Readable for ConnectionRequestAccepted {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AckNackRecord {
-    Single(u32),
+    Single(SeqNum),
     Range(SequenceNumberRange),
 }
 
@@ -412,9 +507,10 @@ impl Readable for AckNackRecord {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
         let is_range = reader.read_u8()? != 0;
         if is_range {
-            let start = reader.read_u24_le()?;
-            let end = reader.read_u24_le()?;
-            if start > end {
+            let start = SeqNum::read(reader)?;
+            let end = SeqNum::read(reader)?;
+            // Senders split ranges at the wrap, so a range never runs backwards.
+            if start.value() > end.value() {
                 return Err(InvalidData(format!(
                     "Invalid ACK/NACK range: start ({}) > end ({})",
                     start, end
@@ -422,8 +518,7 @@ impl Readable for AckNackRecord {
             }
             Ok(AckNackRecord::Range(SequenceNumberRange { start, end }))
         } else {
-            let seq_num = reader.read_u24_le()?;
-            Ok(AckNackRecord::Single(seq_num))
+            Ok(AckNackRecord::Single(SeqNum::read(reader)?))
         }
     }
 }
@@ -433,12 +528,12 @@ impl Writable for AckNackRecord {
         match self {
             AckNackRecord::Range(range) => {
                 writer.write_u8(1)?;
-                writer.write_u24_le(range.start)?;
-                writer.write_u24_le(range.end)?;
+                range.start.write(writer)?;
+                range.end.write(writer)?;
             }
             AckNackRecord::Single(seq_num) => {
                 writer.write_u8(0)?;
-                writer.write_u24_le(*seq_num)?;
+                seq_num.write(writer)?;
             }
         }
         Ok(())
@@ -446,7 +541,10 @@ impl Writable for AckNackRecord {
 }
 
 
-#[derive(Debug, Clone, Default)]
+/// Size of the smallest record, a single sequence number.
+const MIN_ACK_NACK_RECORD_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AckNackPacket {
     pub records: Vec<AckNackRecord>,
 }
@@ -454,6 +552,7 @@ pub struct AckNackPacket {
 impl Readable for AckNackPacket {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
         let record_count = reader.read_u16()?;
+        reader.ensure_remaining(record_count as usize * MIN_ACK_NACK_RECORD_SIZE)?;
         let mut records = Vec::with_capacity(record_count as usize);
         for _ in 0..record_count {
             records.push(AckNackRecord::read(reader)?);
@@ -518,12 +617,12 @@ impl Reliability {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EncapsulatedPacket {
     pub reliability: Reliability,
     pub is_split: bool,
-    pub sequence_number: Option<u32>,
-    pub ordering_index: Option<u32>,
+    pub sequence_number: Option<SeqNum>,
+    pub ordering_index: Option<SeqNum>,
     pub ordering_channel: Option<u8>,
     pub split_count: Option<u32>,
     pub split_id: Option<u16>,
@@ -540,18 +639,18 @@ impl Readable for EncapsulatedPacket {
         let is_split = (flags & 0x10) != 0;
 
         let payload_len_bits = reader.read_u16()? as usize;
-        let payload_len_bytes = (payload_len_bits + 7) / 8;
+        let payload_len_bytes = payload_len_bits.div_ceil(8);
 
-        let mut sequence_number: Option<u32> = None;
-        let mut ordering_index: Option<u32> = None;
+        let mut sequence_number: Option<SeqNum> = None;
+        let mut ordering_index: Option<SeqNum> = None;
         let mut ordering_channel: Option<u8> = None;
 
         if reliability.is_reliable() {
-            sequence_number = Some(reader.read_u24_le()?);
+            sequence_number = Some(SeqNum::read(reader)?);
         }
 
         if reliability.is_sequenced() {
-            ordering_index = Some(reader.read_u24_le()?);
+            ordering_index = Some(SeqNum::read(reader)?);
             ordering_channel = Some(reader.read_u8()?);
         }
 
@@ -564,10 +663,16 @@ impl Readable for EncapsulatedPacket {
             split_id = Some(reader.read_u16()?); // BE
             split_index = Some(reader.read_u32()?); // BE
         }
-
-        if reader.remaining() < payload_len_bytes {
-            return Err(UnexpectedEOF);
+        if let (Some(count), Some(index)) = (split_count, split_index)
+            && index >= count
+        {
+            return Err(InvalidData(format!(
+                "Split index {} out of range for {} parts",
+                index, count
+            )));
         }
+
+        reader.ensure_remaining(payload_len_bytes)?;
         let payload = reader.read_bytes(payload_len_bytes)?;
 
         Ok(Self {
@@ -586,26 +691,29 @@ impl Readable for EncapsulatedPacket {
 
 impl Writable for EncapsulatedPacket {
     fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
-        let mut flags = (self.reliability as u8) << 5;
         if self.is_split {
-            flags |= 0x10;
             return Err(InvalidData("Sending split packets not implemented".to_string()));
         }
+        let flags = (self.reliability as u8) << 5;
         writer.write_u8(flags)?;
 
         let payload_len_bits = (self.payload.len() * 8) as u16;
         writer.write_u16(payload_len_bits)?;
 
         if self.reliability.is_reliable() {
-            writer.write_u24_le(self.sequence_number.ok_or_else(|| {
-                InvalidData("Reliable packet missing sequence number for writing".to_string())
-            })?)?;
+            self.sequence_number
+                .ok_or_else(|| {
+                    InvalidData("Reliable packet missing sequence number for writing".to_string())
+                })?
+                .write(writer)?;
         }
 
         if self.reliability.is_sequenced() {
-            writer.write_u24_le(self.ordering_index.ok_or_else(|| {
-                InvalidData("Ordered/Sequenced packet missing ordering index for writing".to_string())
-            })?)?;
+            self.ordering_index
+                .ok_or_else(|| {
+                    InvalidData("Ordered/Sequenced packet missing ordering index for writing".to_string())
+                })?
+                .write(writer)?;
             writer.write_u8(self.ordering_channel.ok_or_else(|| {
                 InvalidData("Ordered/Sequenced packet missing ordering channel for writing".to_string())
             })?)?;
@@ -621,15 +729,15 @@ impl Writable for EncapsulatedPacket {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FrameSetPacket {
-    pub sequence_number: u32,
+    pub sequence_number: SeqNum,
     pub packets: Vec<EncapsulatedPacket>,
 }
 
 impl Readable for FrameSetPacket {
     fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
-        let sequence_number = reader.read_u24_le()?;
+        let sequence_number = SeqNum::read(reader)?;
         let mut packets = Vec::new();
         while reader.remaining() > 0 {
             match EncapsulatedPacket::read(reader) {
@@ -653,7 +761,7 @@ impl Readable for FrameSetPacket {
 
 impl Writable for FrameSetPacket {
     fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
-        writer.write_u24_le(self.sequence_number)?;
+        self.sequence_number.write(writer)?;
         for packet in &self.packets {
             packet.write(writer)?;
         }
@@ -0,0 +1,172 @@
+//! PROXY protocol version 2, which UDP load balancers prepend to datagrams to pass on the
+//! address of the client they forward for.
+
+use dashmap::DashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::Instant;
+
+/// Every version 2 header starts with these bytes.
+pub const SIGNATURE: [u8; 12] = [
+    0x0d, 0x0a, 0x0d, 0x0a, 0x00, 0x0d, 0x0a, 0x51, 0x55, 0x49, 0x54, 0x0a,
+];
+
+/// Signature, version and command, family and protocol, and address length.
+const FIXED_LENGTH: usize = 16;
+const VERSION: u8 = 0x2;
+const COMMAND_LOCAL: u8 = 0x0;
+const COMMAND_PROXY: u8 = 0x1;
+const FAMILY_UNSPEC: u8 = 0x0;
+const FAMILY_INET: u8 = 0x1;
+const FAMILY_INET6: u8 = 0x2;
+
+/// Clients a load balancer forwarded for are forgotten after this long without traffic.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Stale clients are only looked for once this many are known.
+const PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProxyProtocolError {
+    #[error("Header is truncated")]
+    Truncated,
+    #[error("Unsupported version {0}")]
+    Version(u8),
+    #[error("Unsupported command {0}")]
+    Command(u8),
+    #[error("Unsupported address family {0}")]
+    Family(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyHeader {
+    /// Sent by the load balancer itself, e.g. for health checks.
+    Local,
+    /// Forwarded from `source` to `destination`.
+    Proxy {
+        source: SocketAddr,
+        destination: SocketAddr,
+    },
+    /// Forwarded for a client whose address the load balancer does not know or share.
+    Unknown,
+}
+
+/// Parses the header at the start of `data`. Returns `None` if `data` does not start with
+/// one, otherwise the header and its length including TLVs.
+pub fn parse(data: &[u8]) -> Result<Option<(ProxyHeader, usize)>, ProxyProtocolError> {
+    if !data.starts_with(&SIGNATURE) {
+        return Ok(None);
+    }
+    if data.len() < FIXED_LENGTH {
+        return Err(ProxyProtocolError::Truncated);
+    }
+    let version = data[12] >> 4;
+    let command = data[12] & 0x0f;
+    let family = data[13] >> 4;
+    let address_length = u16::from_be_bytes([data[14], data[15]]) as usize;
+    let length = FIXED_LENGTH + address_length;
+    if version != VERSION {
+        return Err(ProxyProtocolError::Version(version));
+    }
+    if data.len() < length {
+        return Err(ProxyProtocolError::Truncated);
+    }
+    let addresses = &data[FIXED_LENGTH..length];
+
+    let header = match command {
+        COMMAND_LOCAL => ProxyHeader::Local,
+        COMMAND_PROXY => match family {
+            FAMILY_UNSPEC => ProxyHeader::Unknown,
+            FAMILY_INET => {
+                let bytes = addresses.get(..12).ok_or(ProxyProtocolError::Truncated)?;
+                let ip = |at: usize| {
+                    IpAddr::V4(Ipv4Addr::new(
+                        bytes[at],
+                        bytes[at + 1],
+                        bytes[at + 2],
+                        bytes[at + 3],
+                    ))
+                };
+                ProxyHeader::Proxy {
+                    source: SocketAddr::new(ip(0), port(bytes, 8)),
+                    destination: SocketAddr::new(ip(4), port(bytes, 10)),
+                }
+            }
+            FAMILY_INET6 => {
+                let bytes = addresses.get(..36).ok_or(ProxyProtocolError::Truncated)?;
+                let ip = |at: usize| {
+                    let mut octets = [0; 16];
+                    octets.copy_from_slice(&bytes[at..at + 16]);
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                };
+                ProxyHeader::Proxy {
+                    source: SocketAddr::new(ip(0), port(bytes, 32)),
+                    destination: SocketAddr::new(ip(16), port(bytes, 34)),
+                }
+            }
+            family => return Err(ProxyProtocolError::Family(family)),
+        },
+        command => return Err(ProxyProtocolError::Command(command)),
+    };
+    Ok(Some((header, length)))
+}
+
+fn port(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+/// Real client address behind each load balancer address, so datagrams the load balancer
+/// sends without a header are still attributed to the right client.
+#[derive(Default)]
+pub struct ProxyClients {
+    clients: DashMap<SocketAddr, (SocketAddr, Instant)>,
+}
+
+impl ProxyClients {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strips the header from a datagram received from `peer`. Returns the client the
+    /// datagram is from and its payload, or `None` if it should be dropped.
+    pub fn resolve<'a>(&self, peer: SocketAddr, data: &'a [u8]) -> Option<(SocketAddr, &'a [u8])> {
+        match parse(data) {
+            Ok(Some((ProxyHeader::Proxy { source, .. }, length))) => {
+                self.remember(peer, source);
+                Some((source, &data[length..]))
+            }
+            Ok(Some((ProxyHeader::Local | ProxyHeader::Unknown, length))) => {
+                Some((peer, &data[length..]))
+            }
+            Ok(None) => match self.clients.get_mut(&peer) {
+                Some(mut entry) => {
+                    entry.1 = Instant::now();
+                    Some((entry.0, data))
+                }
+                None => {
+                    log::debug!(
+                        "Dropping datagram from {} without a PROXY protocol header",
+                        peer
+                    );
+                    None
+                }
+            },
+            Err(e) => {
+                log::debug!(
+                    "Dropping datagram from {} with an invalid PROXY protocol header: {}",
+                    peer,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn remember(&self, peer: SocketAddr, client: SocketAddr) {
+        if self.clients.len() >= PRUNE_THRESHOLD {
+            self.clients
+                .retain(|_, (_, last_seen)| last_seen.elapsed() < CLIENT_TIMEOUT);
+        }
+        self.clients.insert(peer, (client, Instant::now()));
+    }
+}
@@ -0,0 +1,170 @@
+//! Session recordings: the datagrams a session received and when they arrived, so traffic
+//! that triggered a bug on a live server can be replayed locally with
+//! [`RakNetListener::replay`](crate::listener::RakNetListener::replay).
+//!
+//! A recording starts with [`MAGIC`], a format version and the client address as a string.
+//! Each datagram follows as a varint of the microseconds since the previous one, a varint of
+//! its length, and its bytes.
+
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use bytes::Bytes;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::time::Instant;
+
+pub const FILE_EXTENSION: &str = "amrec";
+pub const MAGIC: [u8; 4] = *b"AREC";
+const VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a session recording")]
+    NotARecording,
+    #[error("unsupported recording version {0}")]
+    UnsupportedVersion(u8),
+    #[error("malformed recording: {0}")]
+    Malformed(#[from] BinaryError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedDatagram {
+    /// When the datagram arrived, counted from the first one.
+    pub offset: Duration,
+    pub payload: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// The client the datagrams came from.
+    pub address: SocketAddr,
+    pub datagrams: Vec<RecordedDatagram>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        Self::decode(Bytes::from(fs::read(path)?))
+    }
+
+    pub fn decode(data: Bytes) -> Result<Self, RecordingError> {
+        let mut reader = BinaryReader::new(data);
+        if reader.remaining() < MAGIC.len() || reader.read_bytes(MAGIC.len())? != MAGIC[..] {
+            return Err(RecordingError::NotARecording);
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+        let address = reader
+            .read_string()?
+            .parse()
+            .map_err(|_| BinaryError::InvalidData("invalid client address".to_string()))?;
+
+        let mut datagrams = Vec::new();
+        let mut offset = Duration::ZERO;
+        while reader.remaining() > 0 {
+            offset += Duration::from_micros(reader.read_var_u64()?);
+            let len = reader.read_var_u32()? as usize;
+            if len == 0 {
+                return Err(BinaryError::InvalidData("empty datagram".to_string()).into());
+            }
+            let payload = reader.read_bytes(len)?;
+            datagrams.push(RecordedDatagram { offset, payload });
+        }
+        Ok(Recording { address, datagrams })
+    }
+
+    pub fn encode(&self) -> Result<Bytes, BinaryError> {
+        let mut writer = BinaryWriter::new();
+        write_header(&mut writer, self.address)?;
+        let mut previous = Duration::ZERO;
+        for datagram in &self.datagrams {
+            write_datagram(&mut writer, datagram.offset - previous, &datagram.payload)?;
+            previous = datagram.offset;
+        }
+        Ok(writer.freeze())
+    }
+
+    /// Time from the first datagram to the last.
+    pub fn duration(&self) -> Duration {
+        self.datagrams.last().map_or(Duration::ZERO, |d| d.offset)
+    }
+}
+
+fn write_header(writer: &mut BinaryWriter, address: SocketAddr) -> Result<(), BinaryError> {
+    writer.write_bytes(&MAGIC)?;
+    writer.write_u8(VERSION)?;
+    writer.write_string(&address.to_string())
+}
+
+fn write_datagram(
+    writer: &mut BinaryWriter,
+    gap: Duration,
+    payload: &[u8],
+) -> Result<(), BinaryError> {
+    writer.write_var_u64(gap.as_micros() as u64)?;
+    writer.write_var_u32(payload.len() as u32)?;
+    writer.write_bytes(payload)
+}
+
+/// Writes the datagrams of one session to its own file as they arrive. Writes are buffered
+/// until [`flush`](Self::flush).
+pub(crate) struct Recorder {
+    file: BufWriter<File>,
+    path: PathBuf,
+    /// When the previous datagram arrived, or `None` before the first.
+    last: Option<Instant>,
+}
+
+impl Recorder {
+    /// Creates `<unix millis>-<ip>-<port>.amrec` in `directory`, creating the directory too if
+    /// needed.
+    pub fn create(directory: &Path, address: SocketAddr) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // IPv6 addresses cannot go into Windows file names as they are.
+        let ip = address.ip().to_string().replace(':', "_");
+        let path = directory.join(format!(
+            "{}-{}-{}.{}",
+            millis,
+            ip,
+            address.port(),
+            FILE_EXTENSION
+        ));
+        let mut header = BinaryWriter::new();
+        write_header(&mut header, address).map_err(io::Error::other)?;
+        let mut file = BufWriter::new(File::create_new(&path)?);
+        file.write_all(header.as_bytes())?;
+        Ok(Recorder {
+            file,
+            path,
+            last: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, payload: &[u8]) -> io::Result<()> {
+        let now = Instant::now();
+        let gap = self.last.map_or(Duration::ZERO, |last| now - last);
+        self.last = Some(now);
+        let mut writer = BinaryWriter::with_capacity(payload.len() + 8);
+        write_datagram(&mut writer, gap, payload).map_err(io::Error::other)?;
+        self.file.write_all(writer.as_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,95 @@
+//! 24-bit sequence numbers, as used for frame sets, reliable frames and ordering indices.
+//!
+//! They wrap around after [`SeqNum::MAX`], so plain `<` and `-` give wrong answers near the
+//! wrap. Comparisons follow serial number arithmetic (RFC 1982): a number is before another
+//! if it is less than half the number space behind it.
+
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SeqNum(u32);
+
+impl SeqNum {
+    pub const MAX: u32 = 0xFF_FFFF;
+    pub const ZERO: SeqNum = SeqNum(0);
+    /// Numbers further apart than this cannot be ordered.
+    const HALF: u32 = 1 << 23;
+
+    /// `value`, wrapped into the 24-bit range.
+    pub const fn new(value: u32) -> Self {
+        SeqNum(value & Self::MAX)
+    }
+
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+
+    pub const fn next(self) -> Self {
+        self.wrapping_add(1)
+    }
+
+    pub const fn wrapping_add(self, n: u32) -> Self {
+        SeqNum::new(self.0.wrapping_add(n))
+    }
+
+    pub const fn wrapping_sub(self, n: u32) -> Self {
+        SeqNum::new(self.0.wrapping_sub(n))
+    }
+
+    /// How many steps forward `later` is, counting across the wrap.
+    pub const fn distance_to(self, later: SeqNum) -> u32 {
+        later.0.wrapping_sub(self.0) & Self::MAX
+    }
+
+    /// Orders two numbers by which was sent first. Numbers exactly half the space apart are
+    /// ambiguous and compare by value.
+    pub fn cmp_wrapping(self, other: SeqNum) -> Ordering {
+        match self.distance_to(other) {
+            0 => Ordering::Equal,
+            Self::HALF => self.0.cmp(&other.0),
+            d if d < Self::HALF => Ordering::Less,
+            _ => Ordering::Greater,
+        }
+    }
+
+    pub fn is_before(self, other: SeqNum) -> bool {
+        self.cmp_wrapping(other) == Ordering::Less
+    }
+
+    pub fn is_after(self, other: SeqNum) -> bool {
+        self.cmp_wrapping(other) == Ordering::Greater
+    }
+
+    /// Every number from `self` to `end`, both included, counting across the wrap.
+    pub fn range_inclusive(self, end: SeqNum) -> impl Iterator<Item = SeqNum> {
+        (0..=self.distance_to(end)).map(move |n| self.wrapping_add(n))
+    }
+}
+
+impl From<SeqNum> for u32 {
+    fn from(seq: SeqNum) -> u32 {
+        seq.0
+    }
+}
+
+impl fmt::Display for SeqNum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Readable for SeqNum {
+    fn read(reader: &mut BinaryReader) -> Result<Self, BinaryError> {
+        Ok(SeqNum(reader.read_u24_le()?))
+    }
+}
+
+impl Writable for SeqNum {
+    fn write(&self, writer: &mut BinaryWriter) -> Result<(), BinaryError> {
+        writer.write_u24_le(self.0)
+    }
+}
@@ -0,0 +1,675 @@
+//! Every client gets its own task, fed its datagrams through a channel. Sessions are handled
+//! in parallel without sharing a lock, and each session sees its datagrams in order. A session
+//! only wakes to handle datagrams or when it would time out.
+//!
+//! A session removes its own handle from the listener's table when it ends, so nothing has
+//! to sweep the table for dead sessions. Guards into the table and the connection table are
+//! never held across an `.await`.
+
+use crate::connection::{Connection, ConnectionEvent, ConnectionState};
+use crate::handoff::SessionState;
+use crate::listener::ServerInfo;
+use crate::packet_trace::{self, Direction, PacketTrace};
+use crate::protocol;
+use crate::protocol::{
+    ConnectedPing, ConnectedPong, ConnectionRequest, ConnectionRequestAccepted,
+    DisconnectionNotification, EncapsulatedPacket, FrameSetPacket, Reliability, CONNECTED_PONG,
+    CONNECTION_REQUEST_ACCEPTED, DISCONNECTION_NOTIFICATION, FRAME_SET,
+};
+use crate::recording::Recorder;
+use crate::seq::SeqNum;
+use crate::stats::ListenerStats;
+use crate::trace::{packet_event, packet_span};
+use crate::traffic::TrafficStats;
+use crate::violations::{StrictMode, Violation, ViolationSummary, ViolationTracker};
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use amethyst_log::{LogContext, WithLogContext};
+use bytes::Bytes;
+use dashmap::DashMap;
+use log::{debug, error, info, trace, warn};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+
+/// Sessions that receive nothing for this long are closed.
+pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default for how long a session may take to become connected, see
+/// [`RakNetListener::with_handshake_timeout`](crate::listener::RakNetListener::with_handshake_timeout).
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `CONNECTION_REQUEST_ACCEPTED` tells clients about the server, see
+/// [`RakNetListener::with_system_addresses`](crate::listener::RakNetListener::with_system_addresses).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemAddresses {
+    pub system_index: u16,
+    /// How many internal addresses are sent.
+    pub count: usize,
+    /// The first internal addresses. The rest are the address of the socket the client
+    /// connected to.
+    pub addresses: Vec<SocketAddr>,
+}
+
+impl SystemAddresses {
+    /// The internal addresses of a server whose socket is bound to `local`.
+    fn internal_ids(&self, local: SocketAddr) -> Vec<SocketAddr> {
+        let mut ids: Vec<_> = self.addresses.iter().copied().take(self.count).collect();
+        ids.resize(self.count, local);
+        ids
+    }
+}
+
+impl Default for SystemAddresses {
+    /// What Bedrock servers send: 20 addresses at system index 0.
+    fn default() -> Self {
+        SystemAddresses {
+            system_index: 0,
+            count: 20,
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// Datagrams waiting for a session before new ones are dropped.
+const INBOUND_CAPACITY: usize = 1024;
+
+/// A datagram for a session, and where its replies go.
+pub(crate) struct Datagram {
+    pub payload: Bytes,
+    pub reply_addr: SocketAddr,
+}
+
+/// The listener's end of a session's channel.
+pub(crate) struct SessionHandle {
+    inbound: mpsc::Sender<Datagram>,
+    close: Arc<Notify>,
+    detach: Arc<Detach>,
+}
+
+/// A request to end a session without disconnecting the client, and the state the session
+/// left behind for the next server process.
+#[derive(Default)]
+struct Detach {
+    requested: AtomicBool,
+    state: OnceLock<SessionState>,
+}
+
+impl SessionHandle {
+    /// Queues `datagram`, or gives it back if the session has ended.
+    pub fn send(&self, datagram: Datagram) -> Result<(), Datagram> {
+        match self.inbound.try_send(datagram) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                trace!("Session queue is full, dropping datagram");
+                Ok(())
+            }
+            Err(TrySendError::Closed(datagram)) => Err(datagram),
+        }
+    }
+
+    /// Disconnects the client and ends the session.
+    pub fn close(&self) {
+        self.close.notify_one();
+    }
+
+    /// Ends the session without telling the client, keeping its state for
+    /// [`detached_state`](Self::detached_state).
+    pub fn detach(&self) {
+        self.detach.requested.store(true, Ordering::Relaxed);
+        self.close.notify_one();
+    }
+
+    /// The state of a detached session once it ended, if it had a connection to hand over.
+    pub fn detached_state(&self) -> Option<SessionState> {
+        self.detach.state.get().cloned()
+    }
+
+    /// Waits for the session to end.
+    pub async fn closed(&self) {
+        self.inbound.closed().await
+    }
+}
+
+/// State shared by all sessions of a listener.
+#[derive(Clone)]
+pub(crate) struct Shared {
+    pub socket: Arc<UdpSocket>,
+    pub connections: Arc<DashMap<SocketAddr, Connection>>,
+    pub sessions: Arc<DashMap<SocketAddr, SessionHandle>>,
+    pub server_info: Arc<ServerInfo>,
+    pub stats: Arc<ListenerStats>,
+    pub packet_trace: Arc<PacketTrace>,
+    pub traffic: Arc<TrafficStats>,
+    pub handshake_timeout: Duration,
+    /// Where sessions are recorded to, if anywhere.
+    pub record_directory: Option<Arc<Path>>,
+    pub strict_mode: Option<StrictMode>,
+    pub system_addresses: Arc<SystemAddresses>,
+    /// Index of `socket` among the listener's sockets.
+    pub socket_index: usize,
+}
+
+struct Session {
+    address: SocketAddr,
+    shared: Shared,
+    started: Instant,
+    /// Set once a connection was accepted, so its removal ends the session.
+    established: bool,
+    /// Sequence number of the next frame set sent.
+    next_sequence_number: SeqNum,
+    recorder: Option<Recorder>,
+    /// Set in strict mode.
+    violations: Option<ViolationTracker>,
+    /// Set once the server closed the session, for its violations or when asked to.
+    closed: bool,
+    /// Where replies to the latest datagram went.
+    reply_addr: SocketAddr,
+}
+
+/// Starts a session for `address` and returns the handle to feed it with.
+pub(crate) fn spawn(address: SocketAddr, shared: Shared) -> SessionHandle {
+    let violations = shared.strict_mode.map(ViolationTracker::new);
+    start(Session {
+        address,
+        shared,
+        started: Instant::now(),
+        established: false,
+        next_sequence_number: SeqNum::ZERO,
+        recorder: None,
+        violations,
+        closed: false,
+        reply_addr: address,
+    })
+}
+
+/// Continues a session handed over by another server process, as if its last datagram had
+/// just arrived.
+pub(crate) fn restore(state: &SessionState, shared: Shared) -> SessionHandle {
+    let mut connection = Connection::new(state.address, state.client_guid, state.mtu);
+    connection.state = state.state;
+    shared.connections.insert(state.address, connection);
+    shared.server_info.update_player_count(&shared.connections);
+    let violations = shared.strict_mode.map(ViolationTracker::new);
+    start(Session {
+        address: state.address,
+        shared,
+        started: Instant::now(),
+        established: true,
+        next_sequence_number: state.next_sequence_number,
+        recorder: None,
+        violations,
+        closed: false,
+        reply_addr: state.reply_addr,
+    })
+}
+
+fn start(session: Session) -> SessionHandle {
+    let (inbound, queue) = mpsc::channel(INBOUND_CAPACITY);
+    let close = Arc::new(Notify::new());
+    let detach = Arc::new(Detach::default());
+    let address = session.address;
+    tokio::spawn(
+        session
+            .run(queue, Arc::clone(&close), Arc::clone(&detach))
+            .with_log_context(LogContext::new().with("peer", address)),
+    );
+    SessionHandle {
+        inbound,
+        close,
+        detach,
+    }
+}
+
+impl Session {
+    async fn run(
+        mut self,
+        mut queue: mpsc::Receiver<Datagram>,
+        close: Arc<Notify>,
+        detach: Arc<Detach>,
+    ) {
+        if let Some(directory) = &self.shared.record_directory {
+            match Recorder::create(directory, self.address) {
+                Ok(recorder) => {
+                    info!("Recording session to {}", recorder.path().display());
+                    self.recorder = Some(recorder);
+                }
+                Err(e) => warn!("Failed to start recording session: {}", e),
+            }
+        }
+        while let Some(deadline) = self.deadline() {
+            tokio::select! {
+                datagram = queue.recv() => {
+                    let Some(datagram) = datagram else {
+                        break;
+                    };
+                    self.handle_scoped(datagram);
+                    // Handle what queued up meanwhile before computing the deadline again.
+                    while let Ok(datagram) = queue.try_recv() {
+                        self.handle_scoped(datagram);
+                    }
+                    self.flush_recording();
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    if self.expire() {
+                        break;
+                    }
+                }
+                _ = close.notified() => {
+                    if detach.requested.load(Ordering::Relaxed) {
+                        if let Some(state) = self.state() {
+                            let _ = detach.state.set(state);
+                        }
+                    } else {
+                        self.close_by_server();
+                    }
+                    break;
+                }
+            }
+        }
+        // A newer session may have taken the address over since the queue was closed.
+        queue.close();
+        self.shared
+            .sessions
+            .remove_if(&self.address, |_, handle| handle.inbound.is_closed());
+    }
+
+    /// When the session times out, or `None` if it is already over.
+    fn deadline(&self) -> Option<Instant> {
+        if self.closed {
+            return None;
+        }
+        let handshake_deadline = self.started + self.shared.handshake_timeout;
+        match self.shared.connections.get(&self.address) {
+            Some(connection) if connection.state == ConnectionState::Connected => {
+                Some(connection.last_packet_time + CONNECTION_TIMEOUT)
+            }
+            Some(connection) => {
+                Some((connection.last_packet_time + CONNECTION_TIMEOUT).min(handshake_deadline))
+            }
+            // Closed elsewhere, e.g. kicked.
+            None if self.established => None,
+            None => Some(handshake_deadline),
+        }
+    }
+
+    /// Runs at the deadline. Returns `true` if the session is over, which it is not if a
+    /// datagram arrived in the meantime.
+    fn expire(&self) -> bool {
+        let connections = &self.shared.connections;
+        let handshake_over = self.started.elapsed() >= self.shared.handshake_timeout;
+        let mut reason = "timed out";
+        let timed_out = connections
+            .remove_if(&self.address, |_, connection| {
+                if connection.state != ConnectionState::Connected && handshake_over {
+                    reason = "did not complete the handshake in time";
+                    return true;
+                }
+                connection.last_packet_time.elapsed() >= CONNECTION_TIMEOUT
+            })
+            .is_some();
+        if timed_out {
+            info!("Connection from {} {}", self.address, reason);
+            self.shared.server_info.update_player_count(connections);
+        }
+        timed_out || !connections.contains_key(&self.address)
+    }
+
+    /// Saves what was recorded so far, so a recording survives the server being killed.
+    fn flush_recording(&mut self) {
+        if let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.flush()
+        {
+            warn!("Stopped recording session: {}", e);
+            self.recorder = None;
+        }
+    }
+
+    fn handle_scoped(&mut self, datagram: Datagram) {
+        if self.closed {
+            return;
+        }
+        if let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.record(&datagram.payload)
+        {
+            warn!("Stopped recording session: {}", e);
+            self.recorder = None;
+        }
+        let mut context = LogContext::new();
+        if let Some(connection) = self.shared.connections.get(&self.address) {
+            context = context.with("guid", connection.client_guid);
+        }
+        context.scope(|| self.handle(datagram));
+    }
+
+    fn handle(&mut self, datagram: Datagram) {
+        let Datagram {
+            payload,
+            reply_addr,
+        } = datagram;
+        self.reply_addr = reply_addr;
+        let address = self.address;
+        let Shared {
+            socket,
+            connections,
+            server_info,
+            stats,
+            packet_trace,
+            traffic,
+            system_addresses,
+            ..
+        } = &self.shared;
+
+        let packet_id = payload[0];
+        packet_span!("session_packet", id = packet_id, len = payload.len());
+        trace!(
+            "Handling packet ID {:#04x} ({} bytes)",
+            packet_id,
+            payload.len()
+        );
+
+        let mut reader = BinaryReader::new(payload);
+
+        if reader.read_u8().is_err() {
+            error!(
+                "Failed to advance reader past packet ID (data len: {})",
+                reader.remaining() + 1
+            );
+            return;
+        }
+
+        match packet_id {
+            protocol::CONNECTION_REQUEST => {
+                if let Some(mut conn_entry) = connections.get_mut(&address)
+                    && let Err(e) = conn_entry.state.advance(ConnectionEvent::ConnectionRequest)
+                {
+                    debug!("Ignoring duplicate CONNECTION_REQUEST: {}", e);
+                    conn_entry.update_last_packet_time();
+                    return;
+                }
+
+                match ConnectionRequest::read(&mut reader) {
+                    Ok(request) => {
+                        debug!(
+                            "Received CONNECTION_REQUEST (Client GUID: {}, Time: {}, Security: {})",
+                            request.client_guid, request.time, request.use_security
+                        );
+                        trace!("Parsed ConnectionRequest: {:?}", request);
+                        packet_event!(?request, "decoded");
+                        let agreed_mtu = 1400;
+
+                        let mut new_connection =
+                            Connection::new(address, request.client_guid, agreed_mtu);
+                        new_connection
+                            .advance(ConnectionEvent::ConnectionRequest)
+                            .expect("a new connection accepts its CONNECTION_REQUEST");
+
+                        let system_address = socket.local_addr().unwrap_or_else(|_| {
+                            SocketAddr::new(
+                                std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                                0,
+                            )
+                        });
+
+                        let reply = ConnectionRequestAccepted {
+                            client_address: address,
+                            system_index: system_addresses.system_index,
+                            internal_ids: system_addresses.internal_ids(system_address),
+                            request_time: request.time,
+                            time: crate::utils::cur_time_millis(),
+                        };
+                        let mut writer = BinaryWriter::new();
+                        if writer.write_u8(CONNECTION_REQUEST_ACCEPTED).is_ok()
+                            && reply.write(&mut writer).is_ok()
+                        {
+                            let response_bytes = writer.freeze();
+                            match packet_trace::send_to(
+                                socket,
+                                packet_trace,
+                                traffic,
+                                response_bytes.as_ref(),
+                                address,
+                                reply_addr,
+                            ) {
+                                Ok(sent_len) => {
+                                    packet_event!(
+                                        id = CONNECTION_REQUEST_ACCEPTED,
+                                        len = sent_len,
+                                        "sent"
+                                    );
+                                    debug!("Sent CONNECTION_REQUEST_ACCEPTED ({} bytes)", sent_len);
+                                    // Insert/update the connection state *after* successfully sending the reply
+                                    connections.insert(address, new_connection);
+                                    server_info.update_player_count(connections);
+                                    stats.record_connection();
+                                    self.established = true;
+                                }
+                                Err(e) => {
+                                    error!("Failed to send CONNECTION_REQUEST_ACCEPTED: {}", e)
+                                }
+                            }
+                        } else {
+                            error!("Failed to serialize CONNECTION_REQUEST_ACCEPTED");
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse CONNECTION_REQUEST: {}", e);
+                        self.violation(Violation::Malformed, reply_addr);
+                    }
+                }
+            }
+            0x80..=0x8F => {
+                packet_span!("reliability", id = packet_id);
+                trace!("Received potential data frame {:#04x}", packet_id);
+                let accepted = if let Some(mut connection_entry) = connections.get_mut(&address) {
+                    let connection = connection_entry.value_mut();
+                    connection.update_last_packet_time();
+
+                    let previous = connection.state;
+                    match connection.advance(ConnectionEvent::DataFrame) {
+                        Ok(()) => {
+                            if connection.state != previous {
+                                debug!("Connection promoted to {:?} state.", connection.state);
+                            }
+                            true
+                        }
+                        Err(e) => {
+                            warn!("Dropping data frame {:#04x}: {}", packet_id, e);
+                            false
+                        }
+                    }
+                } else {
+                    warn!(
+                        "Received data frame {:#04x} from unknown address. Dropping.",
+                        packet_id
+                    );
+                    false
+                };
+                if !accepted {
+                    self.violation(Violation::InvalidState, reply_addr);
+                } else {
+                    match FrameSetPacket::read(&mut reader) {
+                        Ok(frame_set) => self.handle_frame_set(frame_set, reply_addr),
+                        Err(e) => {
+                            warn!("Failed to parse data frame {:#04x}: {}", packet_id, e);
+                            self.violation(Violation::Malformed, reply_addr);
+                        }
+                    }
+                }
+            }
+            _ => {
+                if packet_id < 0x80 {
+                    debug!(
+                        "Received unhandled offline RakNet packet ID {:#04x}",
+                        packet_id
+                    );
+                } else {
+                    trace!("Received potential data packet ID {:#04x}", packet_id);
+                }
+            }
+        }
+    }
+
+    /// Answers the connected pings in `frame_set` and closes the connection on a disconnect
+    /// notification. Anything else needs the reliability layer and is dropped.
+    fn handle_frame_set(&mut self, frame_set: FrameSetPacket, reply_addr: SocketAddr) {
+        let mut dropped = 0;
+        for packet in frame_set.packets {
+            if !packet.is_split {
+                self.shared
+                    .traffic
+                    .record_packet(Direction::Received, &packet.payload);
+            }
+            if !packet.is_split && packet.payload.first() == Some(&DISCONNECTION_NOTIFICATION) {
+                self.disconnect();
+                return;
+            }
+            if packet.is_split || packet.payload.first() != Some(&protocol::CONNECTED_PING) {
+                dropped += 1;
+                continue;
+            }
+            match ConnectedPing::read(&mut BinaryReader::new(packet.payload.slice(1..))) {
+                Ok(ping) => {
+                    trace!("Received CONNECTED_PING (Time: {})", ping.time);
+                    let pong = ConnectedPong {
+                        ping_time: ping.time,
+                        pong_time: crate::utils::cur_time_millis(),
+                    };
+                    self.send_unreliable(CONNECTED_PONG, &pong, reply_addr);
+                }
+                Err(e) => {
+                    warn!("Failed to parse CONNECTED_PING: {}", e);
+                    self.violation(Violation::Malformed, reply_addr);
+                }
+            }
+        }
+        if dropped > 0 {
+            warn!(
+                "Received {} packets in data frame #{}, but reliability layer not implemented yet. Dropping.",
+                dropped, frame_set.sequence_number
+            );
+        }
+    }
+
+    /// Counts `violation` in strict mode, closing the session once there are too many.
+    fn violation(&mut self, violation: Violation, reply_addr: SocketAddr) {
+        if let Some(tracker) = &mut self.violations
+            && let Some(summary) = tracker.record(violation)
+        {
+            self.close_for_violations(summary, reply_addr);
+        }
+    }
+
+    /// Closes the session for too many protocol violations.
+    fn close_for_violations(&mut self, summary: ViolationSummary, reply_addr: SocketAddr) {
+        warn!("Closing connection from {}: {}", self.address, summary);
+        self.close(reply_addr);
+    }
+
+    /// What another server process needs to continue the session, if it has an accepted
+    /// connection.
+    fn state(&self) -> Option<SessionState> {
+        let connection = self.shared.connections.get(&self.address)?;
+        matches!(
+            connection.state,
+            ConnectionState::Connecting | ConnectionState::Connected
+        )
+        .then(|| SessionState {
+            socket: self.shared.socket_index,
+            address: self.address,
+            reply_addr: self.reply_addr,
+            client_guid: connection.client_guid,
+            mtu: connection.mtu,
+            state: connection.state,
+            next_sequence_number: self.next_sequence_number,
+        })
+    }
+
+    /// Disconnects the client because the server asked to, e.g. when shutting down.
+    fn close_by_server(&mut self) {
+        if !self.closed {
+            debug!("Closing connection from {}", self.address);
+            self.close(self.reply_addr);
+        }
+    }
+
+    /// Tells the client it is being disconnected and ends the session.
+    fn close(&mut self, reply_addr: SocketAddr) {
+        let connections = Arc::clone(&self.shared.connections);
+        let removed = connections.remove_if_mut(&self.address, |_, connection| {
+            connection.advance(ConnectionEvent::Disconnect).is_ok()
+        });
+        if removed.is_some() {
+            self.send_unreliable(
+                DISCONNECTION_NOTIFICATION,
+                &DisconnectionNotification,
+                reply_addr,
+            );
+            self.shared.server_info.update_player_count(&connections);
+        }
+        self.closed = true;
+    }
+
+    /// Removes the connection after the client said goodbye, which ends the session.
+    fn disconnect(&self) {
+        let connections = &self.shared.connections;
+        let removed = connections.remove_if_mut(&self.address, |_, connection| {
+            connection.advance(ConnectionEvent::Disconnect).is_ok()
+        });
+        if removed.is_some() {
+            info!("Connection from {} closed by the client", self.address);
+            self.shared.server_info.update_player_count(connections);
+        }
+    }
+
+    /// Sends `packet` in a frame set of its own, without asking for an acknowledgement.
+    fn send_unreliable(&mut self, id: u8, packet: &impl Writable, reply_addr: SocketAddr) {
+        let mut payload = BinaryWriter::new();
+        if payload.write_u8(id).is_err() || packet.write(&mut payload).is_err() {
+            error!("Failed to serialize packet {:#04x}", id);
+            return;
+        }
+        let payload = payload.freeze();
+        let frame_set = FrameSetPacket {
+            sequence_number: self.next_sequence_number,
+            packets: vec![EncapsulatedPacket {
+                reliability: Reliability::Unreliable,
+                is_split: false,
+                sequence_number: None,
+                ordering_index: None,
+                ordering_channel: None,
+                split_count: None,
+                split_id: None,
+                split_index: None,
+                payload: payload.clone(),
+            }],
+        };
+        let mut writer = BinaryWriter::new();
+        if writer.write_u8(FRAME_SET).is_err() || frame_set.write(&mut writer).is_err() {
+            error!("Failed to serialize frame set for packet {:#04x}", id);
+            return;
+        }
+        self.next_sequence_number = self.next_sequence_number.next();
+        match packet_trace::send_to(
+            &self.shared.socket,
+            &self.shared.packet_trace,
+            &self.shared.traffic,
+            &writer.freeze(),
+            self.address,
+            reply_addr,
+        ) {
+            Ok(sent_len) => {
+                self.shared.traffic.record_packet(Direction::Sent, &payload);
+                packet_event!(id, len = sent_len, "sent");
+                trace!("Sent packet {:#04x} ({} bytes)", id, sent_len);
+            }
+            Err(e) => error!("Failed to send packet {:#04x}: {}", id, e),
+        }
+    }
+}
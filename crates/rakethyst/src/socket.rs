@@ -0,0 +1,92 @@
+//! Creation of the listener's UDP sockets, with options the system defaults get wrong for
+//! busy servers.
+
+use log::{debug, warn};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+/// Options applied to every listener socket. `None` keeps the system default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Network interface to bind to, e.g. `eth0`, so only datagrams arriving on it are
+    /// received. Linux only, and usually requires `CAP_NET_RAW`.
+    pub bind_device: Option<String>,
+    /// `SO_RCVBUF` in bytes. A full receive buffer drops datagrams during bursts.
+    pub recv_buffer_size: Option<usize>,
+    /// `SO_SNDBUF` in bytes.
+    pub send_buffer_size: Option<usize>,
+    /// DSCP code point, 0 to 63, that outgoing datagrams are marked with.
+    pub dscp: Option<u8>,
+}
+
+/// Binds a UDP socket to `addr` with `options`.
+pub async fn bind(addr: &str, options: &SocketOptions) -> io::Result<UdpSocket> {
+    let address = tokio::net::lookup_host(addr)
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "address did not resolve"))?;
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    socket.set_nonblocking(true)?;
+    if let Some(device) = &options.bind_device {
+        bind_device(&socket, device)?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+        check_buffer_size("receive", size, socket.recv_buffer_size()?);
+    }
+    if let Some(size) = options.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+        check_buffer_size("send", size, socket.send_buffer_size()?);
+    }
+    if let Some(dscp) = options.dscp {
+        set_dscp(&socket, address, dscp)?;
+    }
+    socket.bind(&address.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// The kernel may cap buffer sizes, e.g. at `net.core.rmem_max` on Linux, without failing.
+fn check_buffer_size(kind: &str, requested: usize, actual: usize) {
+    if actual < requested {
+        warn!(
+            "The {} buffer is {} bytes instead of the requested {}, raise the system limit",
+            kind, actual, requested
+        );
+    } else {
+        debug!("The {} buffer is {} bytes", kind, actual);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn bind_device(socket: &Socket, device: &str) -> io::Result<()> {
+    socket.bind_device(Some(device.as_bytes()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_device(_socket: &Socket, _device: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to a network interface is only supported on Linux",
+    ))
+}
+
+/// DSCP takes the upper six bits of the IPv4 type of service and the IPv6 traffic class.
+fn set_dscp(socket: &Socket, address: SocketAddr, dscp: u8) -> io::Result<()> {
+    let value = u32::from(dscp) << 2;
+    match address {
+        SocketAddr::V4(_) => socket.set_tos_v4(value),
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+        SocketAddr::V6(_) => socket.set_tclass_v6(value),
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+        SocketAddr::V6(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "DSCP marking of IPv6 datagrams is not supported on this system",
+        )),
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use tokio::time::Instant;
+
+/// How many one-minute buckets of history are kept.
+const HISTORY_MINUTES: usize = 60;
+/// Upper bound on the unique sources remembered per minute, so a flood of pings with spoofed
+/// addresses cannot grow memory without limit. Counts saturate at this value.
+const MAX_SOURCES_PER_MINUTE: usize = 16384;
+
+/// Counts how often the server is pinged from the server list compared to how often clients
+/// actually connect.
+pub struct ListenerStats {
+    started: Instant,
+    total_pings: AtomicU64,
+    total_connections: AtomicU64,
+    history: Mutex<VecDeque<MinuteBucket>>,
+}
+
+struct MinuteBucket {
+    minute: u64,
+    pings: u64,
+    connections: u64,
+    sources: HashSet<IpAddr>,
+}
+
+/// Point-in-time view of [`ListenerStats`].
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot {
+    pub total_pings: u64,
+    pub total_connections: u64,
+    pub pings_last_minute: u64,
+    pub pings_last_hour: u64,
+    pub connections_last_hour: u64,
+    pub unique_sources_last_hour: usize,
+    /// Pings in each of the last 60 minutes, oldest first. The last entry is the current,
+    /// still running minute.
+    pub pings_per_minute: Vec<u64>,
+}
+
+impl ListenerStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            total_pings: AtomicU64::new(0),
+            total_connections: AtomicU64::new(0),
+            history: Mutex::new(VecDeque::with_capacity(HISTORY_MINUTES)),
+        }
+    }
+
+    pub fn record_ping(&self, source: IpAddr) {
+        self.total_pings.fetch_add(1, Ordering::Relaxed);
+        let mut history = self.history();
+        let bucket = self.current_bucket(&mut history);
+        bucket.pings += 1;
+        if bucket.sources.len() < MAX_SOURCES_PER_MINUTE {
+            bucket.sources.insert(source);
+        }
+    }
+
+    pub fn record_connection(&self) {
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        let mut history = self.history();
+        self.current_bucket(&mut history).connections += 1;
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let mut history = self.history();
+        let current = self.current_minute();
+        prune(&mut history, current);
+
+        let mut pings_per_minute = vec![0; HISTORY_MINUTES];
+        let mut sources = HashSet::new();
+        let mut connections_last_hour = 0;
+        for bucket in history.iter() {
+            let age = (current - bucket.minute) as usize;
+            pings_per_minute[HISTORY_MINUTES - 1 - age] = bucket.pings;
+            connections_last_hour += bucket.connections;
+            sources.extend(bucket.sources.iter().copied());
+        }
+
+        StatsSnapshot {
+            total_pings: self.total_pings.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            pings_last_minute: pings_per_minute[HISTORY_MINUTES - 1],
+            pings_last_hour: pings_per_minute.iter().sum(),
+            connections_last_hour,
+            unique_sources_last_hour: sources.len(),
+            pings_per_minute,
+        }
+    }
+
+    fn history(&self) -> MutexGuard<'_, VecDeque<MinuteBucket>> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn current_minute(&self) -> u64 {
+        self.started.elapsed().as_secs() / 60
+    }
+
+    fn current_bucket<'a>(&self, history: &'a mut VecDeque<MinuteBucket>) -> &'a mut MinuteBucket {
+        let minute = self.current_minute();
+        prune(history, minute);
+        if history.back().is_none_or(|bucket| bucket.minute != minute) {
+            history.push_back(MinuteBucket {
+                minute,
+                pings: 0,
+                connections: 0,
+                sources: HashSet::new(),
+            });
+        }
+        history
+            .back_mut()
+            .expect("bucket for the current minute was just pushed")
+    }
+}
+
+impl Default for ListenerStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drops buckets that fell out of the history window.
+fn prune(history: &mut VecDeque<MinuteBucket>, current: u64) {
+    while history
+        .front()
+        .is_some_and(|bucket| current - bucket.minute >= HISTORY_MINUTES as u64)
+    {
+        history.pop_front();
+    }
+}
@@ -0,0 +1,465 @@
+//! Helpers for testing against the reliability layer from other crates, behind the
+//! `test-support` feature: builders for frame sets and ACK/NACK packets, a [`ScriptedPeer`]
+//! that drives a listener over a real UDP socket, and a [`SequenceWindow`] that tracks the
+//! frame sets a peer received and asserts on gaps and duplicates.
+//!
+//! ```ignore
+//! let mut peer = ScriptedPeer::connect(server_address).await;
+//! peer.handshake().await;
+//! let ping = encode(CONNECTED_PING, &ConnectedPing { time: 1 });
+//! peer.send_framed(ping, Reliability::Unreliable).await;
+//! let (_, _, pong): (_, _, ConnectedPong) = peer.expect_framed().await;
+//! peer.window().assert_complete();
+//! ```
+//!
+//! Everything here panics on unexpected input, with a message saying what was expected.
+
+use crate::connection::SequenceNumberRange;
+use crate::protocol::{
+    AckNackPacket, AckNackRecord, ConnectionRequest, ConnectionRequestAccepted, EncapsulatedPacket,
+    FrameSetPacket, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1,
+    OpenConnectionRequest2, Reliability, ACK, CONNECTION_REQUEST, CONNECTION_REQUEST_ACCEPTED,
+    FRAME_SET, NACK, OPEN_CONNECTION_REPLY_1, OPEN_CONNECTION_REPLY_2, OPEN_CONNECTION_REQUEST_1,
+    OPEN_CONNECTION_REQUEST_2, RAKNET_PROTOCOL_VERSION,
+};
+use crate::seq::SeqNum;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+/// GUID a [`ScriptedPeer`] connects with unless told otherwise.
+pub const CLIENT_GUID: u64 = 0x1a2b_3c4d_5e6f_7081;
+/// MTU a [`ScriptedPeer`] asks for, which the server grants.
+pub const MTU: u16 = 1400;
+/// How long a [`ScriptedPeer`] waits for a datagram unless told otherwise.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Encodes `packet` with its ID in front.
+pub fn encode(id: u8, packet: &impl Writable) -> Bytes {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(id).unwrap();
+    packet.write(&mut writer).unwrap();
+    writer.freeze()
+}
+
+/// Decodes packet `id` from `data`, which starts with the ID.
+pub fn decode<T: Readable>(data: &[u8], id: u8) -> T {
+    assert_eq!(data[0], id, "unexpected packet ID");
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(&data[1..])))
+        .expect("peer sent a malformed packet")
+}
+
+/// Builds the frames of a frame set, numbering reliable and ordered frames as it goes.
+#[derive(Debug, Clone)]
+pub struct FrameSetBuilder {
+    sequence_number: SeqNum,
+    packets: Vec<EncapsulatedPacket>,
+    next_reliable_index: SeqNum,
+    next_ordering_index: SeqNum,
+}
+
+impl FrameSetBuilder {
+    pub fn new(sequence_number: SeqNum) -> Self {
+        Self {
+            sequence_number,
+            packets: Vec::new(),
+            next_reliable_index: SeqNum::ZERO,
+            next_ordering_index: SeqNum::ZERO,
+        }
+    }
+
+    /// Numbers the following reliable frames from `index`.
+    pub fn with_reliable_index(mut self, index: SeqNum) -> Self {
+        self.next_reliable_index = index;
+        self
+    }
+
+    /// Numbers the following ordered frames from `index`.
+    pub fn with_ordering_index(mut self, index: SeqNum) -> Self {
+        self.next_ordering_index = index;
+        self
+    }
+
+    /// Adds `payload` as a frame of its own, on ordering channel 0 if it is ordered.
+    pub fn frame(mut self, reliability: Reliability, payload: Bytes) -> Self {
+        let packet = self.encapsulate(reliability, payload);
+        self.packets.push(packet);
+        self
+    }
+
+    /// Adds a frame exactly as given.
+    pub fn packet(mut self, packet: EncapsulatedPacket) -> Self {
+        self.packets.push(packet);
+        self
+    }
+
+    pub fn build(self) -> FrameSetPacket {
+        FrameSetPacket {
+            sequence_number: self.sequence_number,
+            packets: self.packets,
+        }
+    }
+
+    /// The frame set as a datagram.
+    pub fn encode(self) -> Bytes {
+        encode(FRAME_SET, &self.build())
+    }
+
+    fn encapsulate(&mut self, reliability: Reliability, payload: Bytes) -> EncapsulatedPacket {
+        let sequence_number = reliability.is_reliable().then(|| {
+            let index = self.next_reliable_index;
+            self.next_reliable_index = index.next();
+            index
+        });
+        let ordering_index = reliability.is_ordered().then(|| {
+            let index = self.next_ordering_index;
+            self.next_ordering_index = index.next();
+            index
+        });
+        EncapsulatedPacket {
+            reliability,
+            is_split: false,
+            sequence_number,
+            ordering_index,
+            ordering_channel: ordering_index.map(|_| 0),
+            split_count: None,
+            split_id: None,
+            split_index: None,
+            payload,
+        }
+    }
+}
+
+/// An ACK or NACK for `sequence_numbers`, with runs of consecutive numbers as ranges the way
+/// RakNet sends them. Ranges are split where the numbers wrap.
+pub fn ack_nack(sequence_numbers: impl IntoIterator<Item = SeqNum>) -> AckNackPacket {
+    let mut numbers: Vec<SeqNum> = sequence_numbers.into_iter().collect();
+    numbers.sort_by_key(|seq| seq.value());
+    numbers.dedup();
+    let mut records = Vec::new();
+    let mut numbers = numbers.into_iter().peekable();
+    while let Some(start) = numbers.next() {
+        let mut end = start;
+        while let Some(&next) = numbers.peek() {
+            if next.value() != end.value() + 1 {
+                break;
+            }
+            end = next;
+            numbers.next();
+        }
+        records.push(if start == end {
+            AckNackRecord::Single(start)
+        } else {
+            AckNackRecord::Range(SequenceNumberRange { start, end })
+        });
+    }
+    AckNackPacket { records }
+}
+
+/// Every sequence number an ACK or NACK covers, in the order of its records.
+pub fn acknowledged(packet: &AckNackPacket) -> Vec<SeqNum> {
+    packet
+        .records
+        .iter()
+        .flat_map(|record| match record {
+            AckNackRecord::Single(seq) => vec![*seq],
+            AckNackRecord::Range(range) => range.iter().collect(),
+        })
+        .collect()
+}
+
+/// The frame sets one side received, from the first sequence number it expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceWindow {
+    start: SeqNum,
+    /// Distances from `start` of every frame set received, in arrival order.
+    received: Vec<u32>,
+    duplicates: Vec<SeqNum>,
+}
+
+impl Default for SequenceWindow {
+    fn default() -> Self {
+        Self::starting_at(SeqNum::ZERO)
+    }
+}
+
+impl SequenceWindow {
+    pub fn starting_at(start: SeqNum) -> Self {
+        Self {
+            start,
+            received: Vec::new(),
+            duplicates: Vec::new(),
+        }
+    }
+
+    /// Records a frame set. Returns `false` if it had been received before.
+    pub fn observe(&mut self, sequence_number: SeqNum) -> bool {
+        let distance = self.start.distance_to(sequence_number);
+        if self.received.contains(&distance) {
+            self.duplicates.push(sequence_number);
+            return false;
+        }
+        self.received.push(distance);
+        true
+    }
+
+    /// The sequence number after the highest one received.
+    pub fn next_expected(&self) -> SeqNum {
+        match self.received.iter().max() {
+            Some(&highest) => self.start.wrapping_add(highest + 1),
+            None => self.start,
+        }
+    }
+
+    /// Every sequence number received, in arrival order.
+    pub fn received(&self) -> Vec<SeqNum> {
+        self.received
+            .iter()
+            .map(|&distance| self.start.wrapping_add(distance))
+            .collect()
+    }
+
+    /// Sequence numbers below the highest one received that never arrived.
+    pub fn missing(&self) -> Vec<SeqNum> {
+        let end = self.received.iter().max().map_or(0, |&highest| highest + 1);
+        (0..end)
+            .filter(|distance| !self.received.contains(distance))
+            .map(|distance| self.start.wrapping_add(distance))
+            .collect()
+    }
+
+    /// Sequence numbers that arrived more than once, once per repeat.
+    pub fn duplicates(&self) -> &[SeqNum] {
+        &self.duplicates
+    }
+
+    /// The ACK a receiver sends for this window.
+    pub fn ack(&self) -> AckNackPacket {
+        ack_nack(self.received())
+    }
+
+    /// The NACK a receiver sends for this window.
+    pub fn nack(&self) -> AckNackPacket {
+        ack_nack(self.missing())
+    }
+
+    /// Panics unless every frame set up to the highest one arrived, each exactly once.
+    #[track_caller]
+    pub fn assert_complete(&self) {
+        assert_eq!(self.missing(), Vec::new(), "frame sets are missing");
+        assert_eq!(self.duplicates, Vec::new(), "frame sets arrived twice");
+    }
+
+    /// Panics unless the frame sets arrived in the order they were numbered.
+    #[track_caller]
+    pub fn assert_in_order(&self) {
+        assert!(
+            self.received.is_sorted(),
+            "frame sets arrived out of order: {:?}",
+            self.received()
+        );
+    }
+
+    /// Panics unless exactly `expected` are missing.
+    #[track_caller]
+    pub fn assert_missing(&self, expected: &[SeqNum]) {
+        assert_eq!(self.missing(), expected, "unexpected gaps in the window");
+    }
+}
+
+/// A RakNet client that follows a script, one datagram at a time, over a real UDP socket.
+///
+/// It numbers the frame sets it sends, can drop a share of them to simulate loss, and records
+/// the frame sets it receives in a [`SequenceWindow`].
+pub struct ScriptedPeer {
+    socket: UdpSocket,
+    server: SocketAddr,
+    guid: u64,
+    /// Learned from the first handshake reply.
+    server_guid: Option<u64>,
+    reply_timeout: Duration,
+    /// Drops every `n`th datagram sent, when set.
+    drop_every: Option<u32>,
+    sent: u32,
+    next_sequence_number: SeqNum,
+    next_reliable_index: SeqNum,
+    window: SequenceWindow,
+}
+
+impl ScriptedPeer {
+    /// Binds a socket on localhost for talking to `server`.
+    pub async fn connect(server: SocketAddr) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        Self {
+            socket,
+            server,
+            guid: CLIENT_GUID,
+            server_guid: None,
+            reply_timeout: REPLY_TIMEOUT,
+            drop_every: None,
+            sent: 0,
+            next_sequence_number: SeqNum::ZERO,
+            next_reliable_index: SeqNum::ZERO,
+            window: SequenceWindow::default(),
+        }
+    }
+
+    pub fn with_guid(mut self, guid: u64) -> Self {
+        self.guid = guid;
+        self
+    }
+
+    pub fn with_reply_timeout(mut self, reply_timeout: Duration) -> Self {
+        self.reply_timeout = reply_timeout;
+        self
+    }
+
+    /// Drops every `n`th datagram sent from now on.
+    pub fn set_loss(&mut self, drop_every: u32) {
+        self.drop_every = Some(drop_every);
+        self.sent = 0;
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    /// The GUID the server announced in the handshake, once it got that far.
+    pub fn server_guid(&self) -> Option<u64> {
+        self.server_guid
+    }
+
+    /// The frame sets received so far.
+    pub fn window(&self) -> &SequenceWindow {
+        &self.window
+    }
+
+    /// Sequence number of the next frame set this peer sends.
+    pub fn next_sequence_number(&self) -> SeqNum {
+        self.next_sequence_number
+    }
+
+    /// Sends a datagram, unless the injected loss swallows it. Returns whether it was sent.
+    pub async fn send_datagram(&mut self, data: &[u8]) -> bool {
+        self.sent += 1;
+        if let Some(n) = self.drop_every
+            && self.sent.is_multiple_of(n)
+        {
+            return false;
+        }
+        self.socket.send_to(data, self.server).await.unwrap();
+        true
+    }
+
+    pub async fn send(&mut self, id: u8, packet: &impl Writable) -> bool {
+        let data = encode(id, packet);
+        self.send_datagram(&data).await
+    }
+
+    /// Starts the next frame set, numbered after the ones sent before.
+    pub fn frame_set(&mut self) -> FrameSetBuilder {
+        FrameSetBuilder::new(self.next_sequence_number)
+            .with_reliable_index(self.next_reliable_index)
+    }
+
+    /// Sends a frame set started with [`frame_set`](Self::frame_set).
+    pub async fn send_frame_set(&mut self, frame_set: FrameSetBuilder) -> bool {
+        let frame_set = frame_set.build();
+        self.next_sequence_number = frame_set.sequence_number.next();
+        if let Some(index) = frame_set
+            .packets
+            .iter()
+            .filter_map(|packet| packet.sequence_number)
+            .max_by(|a, b| a.cmp_wrapping(*b))
+        {
+            self.next_reliable_index = index.next();
+        }
+        self.send(FRAME_SET, &frame_set).await
+    }
+
+    /// Sends `payload` in a frame set of its own.
+    pub async fn send_framed(&mut self, payload: Bytes, reliability: Reliability) -> bool {
+        let frame_set = self.frame_set().frame(reliability, payload);
+        self.send_frame_set(frame_set).await
+    }
+
+    /// Acknowledges the frame sets received so far.
+    pub async fn send_ack(&mut self) -> bool {
+        let ack = self.window.ack();
+        self.send(ACK, &ack).await
+    }
+
+    /// Asks for the frame sets missing so far to be resent.
+    pub async fn send_nack(&mut self) -> bool {
+        let nack = self.window.nack();
+        self.send(NACK, &nack).await
+    }
+
+    /// Waits for a datagram from the server.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        let mut buf = vec![0; 2048];
+        let received = timeout(self.reply_timeout, self.socket.recv_from(&mut buf)).await;
+        let (len, from) = received.ok()?.unwrap();
+        assert_eq!(from, self.server, "datagram from an unexpected address");
+        buf.truncate(len);
+        Some(Bytes::from(buf))
+    }
+
+    /// Waits for packet `id` from the server and decodes it.
+    pub async fn expect<T: Readable>(&mut self, id: u8) -> T {
+        let data = self.recv().await.expect("no reply from the server");
+        decode(&data, id)
+    }
+
+    /// Waits for a frame set and records it in the window.
+    pub async fn expect_frame_set(&mut self) -> FrameSetPacket {
+        let frame_set: FrameSetPacket = self.expect(FRAME_SET).await;
+        self.window.observe(frame_set.sequence_number);
+        frame_set
+    }
+
+    /// Waits for a frame set holding a single packet and decodes it. Returns the sequence
+    /// number of the frame set and the packet ID along with the packet.
+    pub async fn expect_framed<T: Readable>(&mut self) -> (SeqNum, u8, T) {
+        let frame_set = self.expect_frame_set().await;
+        let [packet] = <[_; 1]>::try_from(frame_set.packets).expect("expected one frame");
+        let id = packet.payload[0];
+        (frame_set.sequence_number, id, decode(&packet.payload, id))
+    }
+
+    /// Runs the offline handshake and the connection request.
+    pub async fn handshake(&mut self) -> ConnectionRequestAccepted {
+        // Clients pad the first request to the MTU they want, minus the IP and UDP headers.
+        let request = OpenConnectionRequest1 {
+            protocol_version: RAKNET_PROTOCOL_VERSION,
+        };
+        let mut data = encode(OPEN_CONNECTION_REQUEST_1, &request).to_vec();
+        data.resize(MTU as usize - 28, 0);
+        self.send_datagram(&data).await;
+        let reply: OpenConnectionReply1 = self.expect(OPEN_CONNECTION_REPLY_1).await;
+        self.server_guid = Some(reply.server_guid);
+
+        let request = OpenConnectionRequest2 {
+            cookie: reply.cookie,
+            server_addr: self.server,
+            mtu: reply.mtu_size,
+            client_guid: self.guid,
+        };
+        self.send(OPEN_CONNECTION_REQUEST_2, &request).await;
+        let reply: OpenConnectionReply2 = self.expect(OPEN_CONNECTION_REPLY_2).await;
+        assert_eq!(reply.client_addr, self.local_addr());
+        assert_eq!(reply.mtu, MTU);
+
+        let request = ConnectionRequest {
+            client_guid: self.guid,
+            time: 1234,
+            use_security: false,
+        };
+        self.send(CONNECTION_REQUEST, &request).await;
+        self.expect(CONNECTION_REQUEST_ACCEPTED).await
+    }
+}
@@ -0,0 +1,299 @@
+//! Join throttling: during a join flood, clients from suspicious subnets have to echo a cookie
+//! in an extra handshake round before they get a session, like TCP SYN cookies. Bots that
+//! spoof their address or skip the offline handshake never see the cookie, while real clients
+//! answer it without the player noticing.
+
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, RandomState};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::Instant;
+
+/// How long a cookie stays valid, at least. A cookie is valid for the period it was issued in
+/// and the next one.
+const COOKIE_PERIOD: Duration = Duration::from_secs(10);
+/// Upper bound on the subnets and addresses tracked per window, so a flood from spoofed
+/// addresses cannot grow memory without limit. Once full, every new subnet is challenged.
+const MAX_TRACKED: usize = 16384;
+
+/// When clients have to pass the cookie challenge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinThrottle {
+    /// Handshakes one subnet, a /24 for IPv4 and a /64 for IPv6, may start within `window`
+    /// before its clients are challenged.
+    pub max_subnet_handshakes: u32,
+    /// Length of the counting window. A subnet stays challenged for the rest of the window it
+    /// was caught in and the whole next one.
+    pub window: Duration,
+    /// Clients that resend `OPEN_CONNECTION_REQUEST_1` sooner than this after an answered one
+    /// are challenged. Real clients only resend it when no reply arrives.
+    pub min_retry_interval: Duration,
+    /// Networks that are never challenged, e.g. a NAT many players share.
+    pub allowlist: Vec<IpNetwork>,
+}
+
+/// An IP network such as `10.0.0.0/8`, or a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IpNetworkError {
+    #[error("invalid IP address: {0}")]
+    Address(String),
+    #[error("invalid prefix length: {0}")]
+    PrefixLen(String),
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                mask_v4(network, self.prefix_len) == mask_v4(ip, self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                mask_v6(network, self.prefix_len) == mask_v6(ip, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = IpNetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| IpNetworkError::Address(address.to_string()))?;
+        let max_len = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| IpNetworkError::PrefixLen(len.to_string()))?,
+            None => max_len,
+        };
+        Ok(IpNetwork {
+            address: address.to_canonical(),
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+fn mask_v4(ip: Ipv4Addr, prefix_len: u8) -> u32 {
+    u32::from(ip)
+        & u32::MAX
+            .checked_shl(32 - u32::from(prefix_len))
+            .unwrap_or(0)
+}
+
+fn mask_v6(ip: Ipv6Addr, prefix_len: u8) -> u128 {
+    u128::from(ip)
+        & u128::MAX
+            .checked_shl(128 - u32::from(prefix_len))
+            .unwrap_or(0)
+}
+
+/// The subnet `ip` is counted in.
+fn subnet(ip: IpAddr) -> IpNetwork {
+    let (address, prefix_len) = match ip.to_canonical() {
+        IpAddr::V4(ip) => (IpAddr::V4(Ipv4Addr::from(mask_v4(ip, 24))), 24),
+        IpAddr::V6(ip) => (IpAddr::V6(Ipv6Addr::from(mask_v6(ip, 64))), 64),
+    };
+    IpNetwork {
+        address,
+        prefix_len,
+    }
+}
+
+/// Tracks handshakes and issues and checks cookies.
+pub(crate) struct Throttler {
+    config: JoinThrottle,
+    started: Instant,
+    /// Keys the cookies, so they cannot be computed outside this process.
+    cookie_key: RandomState,
+    state: Mutex<ThrottleState>,
+}
+
+struct ThrottleState {
+    window_started: Instant,
+    /// Handshakes per subnet in the current window.
+    handshakes: HashMap<IpNetwork, u32>,
+    /// When each address last got an `OPEN_CONNECTION_REPLY_1` in the current window.
+    last_request: HashMap<SocketAddr, Instant>,
+    /// Subnets that are challenged, until when.
+    suspected: HashMap<IpNetwork, Instant>,
+    /// Addresses that passed the challenge, until when.
+    verified: HashMap<SocketAddr, Instant>,
+    /// Set once `handshakes` is full in the current window.
+    full: bool,
+}
+
+impl ThrottleState {
+    /// Whether clients from the subnet of `addr` have to pass the challenge, either because
+    /// it is suspected or because it joined once too many subnets were being tracked.
+    fn is_challenged(&self, addr: SocketAddr) -> bool {
+        let subnet = subnet(addr.ip());
+        self.suspected.contains_key(&subnet) || self.full && !self.handshakes.contains_key(&subnet)
+    }
+}
+
+impl Throttler {
+    pub fn new(config: JoinThrottle) -> Self {
+        let now = Instant::now();
+        Throttler {
+            config,
+            started: now,
+            cookie_key: RandomState::new(),
+            state: Mutex::new(ThrottleState {
+                window_started: now,
+                handshakes: HashMap::new(),
+                last_request: HashMap::new(),
+                suspected: HashMap::new(),
+                verified: HashMap::new(),
+                full: false,
+            }),
+        }
+    }
+
+    /// Counts an `OPEN_CONNECTION_REQUEST_1` from `addr`, and returns the cookie its reply has
+    /// to carry if `addr` is challenged.
+    pub fn challenge(&self, addr: SocketAddr) -> Option<u32> {
+        if self.is_allowed(addr.ip()) {
+            return None;
+        }
+        let now = Instant::now();
+        let subnet = subnet(addr.ip());
+        let mut state = self.state(now);
+        let tracked = state.handshakes.len() < MAX_TRACKED;
+        let count = match state.handshakes.get_mut(&subnet) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None if tracked => {
+                state.handshakes.insert(subnet, 1);
+                1
+            }
+            None => {
+                if !state.full {
+                    warn!("Too many subnets are joining at once, challenging every new one");
+                    state.full = true;
+                }
+                return Some(self.cookie(addr, self.period(now)));
+            }
+        };
+        let retried = match state.last_request.get(&addr) {
+            Some(last) => now.duration_since(*last) < self.config.min_retry_interval,
+            None => false,
+        };
+        if state.last_request.len() < MAX_TRACKED || state.last_request.contains_key(&addr) {
+            state.last_request.insert(addr, now);
+        }
+        let reason = if count > self.config.max_subnet_handshakes {
+            Some(format!(
+                "{} handshakes within {:?}",
+                count, self.config.window
+            ))
+        } else if retried {
+            Some(format!("{} retried too soon", addr))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            let until = state.window_started + self.config.window * 2;
+            if state.suspected.insert(subnet, until).is_none() {
+                warn!("Challenging joins from {}: {}", subnet, reason);
+            }
+        }
+        state
+            .suspected
+            .contains_key(&subnet)
+            .then(|| self.cookie(addr, self.period(now)))
+    }
+
+    /// Whether the `OPEN_CONNECTION_REQUEST_2` of `addr` may be answered. Challenged clients
+    /// need the cookie they were sent, and any cookie that is sent has to be valid.
+    pub fn check_cookie(&self, addr: SocketAddr, cookie: Option<u32>) -> bool {
+        let now = Instant::now();
+        let mut state = self.state(now);
+        let Some(cookie) = cookie else {
+            if state.is_challenged(addr) && !self.is_allowed(addr.ip()) {
+                debug!("Dropping a join from {} without the cookie", addr);
+                return false;
+            }
+            return true;
+        };
+        let period = self.period(now);
+        let valid = cookie == self.cookie(addr, period)
+            || period > 0 && cookie == self.cookie(addr, period - 1);
+        if !valid {
+            debug!("Dropping a join from {} with a wrong cookie", addr);
+            return false;
+        }
+        if state.verified.len() < MAX_TRACKED || state.verified.contains_key(&addr) {
+            let until = state.window_started + self.config.window * 2;
+            state.verified.insert(addr, until);
+        }
+        true
+    }
+
+    /// Whether `addr` may start a session. Clients of challenged subnets must have passed the
+    /// challenge, so bots cannot skip it by sending `CONNECTION_REQUEST` right away.
+    pub fn allows_session(&self, addr: SocketAddr) -> bool {
+        if self.is_allowed(addr.ip()) {
+            return true;
+        }
+        let state = self.state(Instant::now());
+        !state.is_challenged(addr) || state.verified.contains_key(&addr)
+    }
+
+    fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.config
+            .allowlist
+            .iter()
+            .any(|network| network.contains(ip))
+    }
+
+    fn period(&self, now: Instant) -> u64 {
+        now.duration_since(self.started).as_secs() / COOKIE_PERIOD.as_secs()
+    }
+
+    fn cookie(&self, addr: SocketAddr, period: u64) -> u32 {
+        self.cookie_key.hash_one((addr, period)) as u32
+    }
+
+    /// The state, with the window moved forward and expired entries removed if `window` has
+    /// passed.
+    fn state(&self, now: Instant) -> MutexGuard<'_, ThrottleState> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(state.window_started) >= self.config.window {
+            state.window_started = now;
+            state.handshakes.clear();
+            state.last_request.clear();
+            state.full = false;
+            state.suspected.retain(|_, until| *until > now);
+            state.verified.retain(|_, until| *until > now);
+        }
+        state
+    }
+}
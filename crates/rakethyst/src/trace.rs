@@ -0,0 +1,21 @@
+//! Tracing on the packet hot path, for debugging. Spans and events are only compiled in with
+//! the `trace-packets` feature and are otherwise nothing at all. Without a `tracing`
+//! subscriber they are forwarded to the `log` logger at trace level.
+
+/// Enters a trace span that lasts until the end of the enclosing block.
+macro_rules! packet_span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace-packets")]
+        let _span = tracing::trace_span!($($arg)+).entered();
+    };
+}
+
+/// Records a trace event in the current span.
+macro_rules! packet_event {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace-packets")]
+        tracing::trace!($($arg)+);
+    };
+}
+
+pub(crate) use {packet_event, packet_span};
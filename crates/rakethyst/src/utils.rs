@@ -1,7 +1,8 @@
 // src/utils.rs
 use std::sync::OnceLock;
-use std::time::Instant;
+use tokio::time::Instant;
 
+/// Read from tokio's clock, so the times in packets follow a paused test clock.
 static SERVER_START_TIME: OnceLock<Instant> = OnceLock::new();
 
 pub fn init_time() {
@@ -0,0 +1,83 @@
+//! Strict mode: counting the protocol violations of a session and closing it once a client
+//! keeps sending packets that cannot be decoded or make no sense in its state.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How many violations a session may commit within `window` before it is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictMode {
+    pub max_violations: u32,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// A packet that could not be decoded.
+    Malformed,
+    /// A packet not allowed in the connection's state, e.g. a data frame before the
+    /// connection request.
+    InvalidState,
+}
+
+/// The violations of one session within the last window.
+pub(crate) struct ViolationTracker {
+    mode: StrictMode,
+    recent: VecDeque<(Instant, Violation)>,
+}
+
+impl ViolationTracker {
+    pub fn new(mode: StrictMode) -> Self {
+        ViolationTracker {
+            mode,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Counts `violation`, and returns a summary of the window if that was one too many.
+    pub fn record(&mut self, violation: Violation) -> Option<ViolationSummary> {
+        let now = Instant::now();
+        while let Some(&(at, _)) = self.recent.front()
+            && now.duration_since(at) >= self.mode.window
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, violation));
+        if self.recent.len() < self.mode.max_violations as usize {
+            return None;
+        }
+        let malformed = self
+            .recent
+            .iter()
+            .filter(|(_, violation)| *violation == Violation::Malformed)
+            .count();
+        Some(ViolationSummary {
+            malformed,
+            invalid_state: self.recent.len() - malformed,
+            window: self.mode.window,
+        })
+    }
+}
+
+/// What a session was closed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViolationSummary {
+    pub malformed: usize,
+    pub invalid_state: usize,
+    pub window: Duration,
+}
+
+impl fmt::Display for ViolationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} protocol violations within {:?} ({} malformed, {} invalid for the connection state)",
+            self.malformed + self.invalid_state,
+            self.window,
+            self.malformed,
+            self.invalid_state
+        )
+    }
+}
@@ -0,0 +1,211 @@
+//! Checks the codecs against the wire format the Bedrock client speaks.
+//!
+//! Each fixture in `fixtures/` is one datagram or frame payload as hex, with `#` comments
+//! naming the fields. They were assembled from the RakNet protocol documentation, with the
+//! values a Bedrock client and server exchange during a connection. Captures from a real
+//! client can be added in the same format.
+//!
+//! Packets the client sends must decode to the expected values. Packets the server sends
+//! must also encode to the fixture byte for byte, since their encoding is deterministic.
+
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use rakethyst::connection::SequenceNumberRange;
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+
+const SERVER_GUID: u64 = 0x7ef1_a5b2_c3d4_e5f6;
+const CLIENT_GUID: u64 = 0x1a2b_3c4d_5e6f_7081;
+
+macro_rules! fixture {
+    ($name:literal) => {
+        parse_hex(include_str!(concat!("fixtures/", $name, ".hex")))
+    };
+}
+
+fn parse_hex(text: &str) -> Vec<u8> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|byte| u8::from_str_radix(byte, 16).expect("fixture holds invalid hex"))
+        .collect()
+}
+
+fn address(address: &str) -> SocketAddr {
+    address.parse().unwrap()
+}
+
+/// Decodes `data` as packet `id` followed by a `T`, and returns the bytes left over.
+fn decode<T: Readable>(data: &[u8], id: u8) -> (T, usize) {
+    assert_eq!(data[0], id, "packet ID");
+    let mut reader = BinaryReader::new(Bytes::copy_from_slice(&data[1..]));
+    let packet = T::read(&mut reader).expect("fixture failed to decode");
+    (packet, reader.remaining())
+}
+
+/// Checks a packet the client sends.
+fn assert_decodes<T: Readable + PartialEq + Debug>(data: &[u8], id: u8, expected: T) {
+    let (packet, remaining) = decode::<T>(data, id);
+    assert_eq!(packet, expected);
+    assert_eq!(remaining, 0, "trailing bytes");
+}
+
+/// Checks a packet the server sends, in both directions.
+fn assert_conforms<T: Readable + Writable + PartialEq + Debug>(data: &[u8], id: u8, packet: T) {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(id).unwrap();
+    packet.write(&mut writer).unwrap();
+    assert_eq!(
+        writer.freeze().as_ref(),
+        data,
+        "encoding differs from the fixture"
+    );
+    assert_decodes(data, id, packet);
+}
+
+/// Unwraps the only frame of a frame set.
+fn single_frame(data: &[u8], sequence_number: u32) -> EncapsulatedPacket {
+    let (frame_set, remaining) = decode::<FrameSetPacket>(data, FRAME_SET);
+    assert_eq!(remaining, 0, "trailing bytes");
+    assert_eq!(frame_set.sequence_number, SeqNum::new(sequence_number));
+    let [frame] = <[_; 1]>::try_from(frame_set.packets).expect("expected one frame");
+    assert!(!frame.is_split);
+    frame
+}
+
+#[test]
+fn unconnected_ping() {
+    let ping = UnconnectedPing {
+        time: 0x4d2_0021,
+        client_guid: CLIENT_GUID,
+    };
+    assert_decodes(&fixture!("unconnected_ping"), UNCONNECTED_PING, ping);
+}
+
+#[test]
+fn unconnected_pong() {
+    let pong = UnconnectedPong {
+        time: 0x4d2_0021,
+        server_guid: SERVER_GUID,
+        motd: "MCPE;Amethyst;766;1.21.50;0;50;9147830512342323190;Amethyst World;Survival;1;\
+               19132;19133;"
+            .to_string(),
+    };
+    assert_conforms(&fixture!("unconnected_pong"), UNCONNECTED_PONG, pong);
+}
+
+#[test]
+fn open_connection_request_1_ignores_mtu_padding() {
+    let data = fixture!("open_connection_request_1");
+    assert_eq!(data.len(), 1492 - 28);
+    let (request, padding) = decode::<OpenConnectionRequest1>(&data, OPEN_CONNECTION_REQUEST_1);
+    assert_eq!(request.protocol_version, RAKNET_PROTOCOL_VERSION);
+    assert_eq!(padding, data.len() - 18);
+}
+
+#[test]
+fn open_connection_reply_1() {
+    let reply = OpenConnectionReply1 {
+        server_guid: SERVER_GUID,
+        cookie: None,
+        mtu_size: 1400,
+    };
+    assert_conforms(
+        &fixture!("open_connection_reply_1"),
+        OPEN_CONNECTION_REPLY_1,
+        reply,
+    );
+}
+
+#[test]
+fn open_connection_request_2() {
+    let request = OpenConnectionRequest2 {
+        cookie: None,
+        server_addr: address("127.0.0.1:19132"),
+        mtu: 1400,
+        client_guid: CLIENT_GUID,
+    };
+    assert_decodes(
+        &fixture!("open_connection_request_2"),
+        OPEN_CONNECTION_REQUEST_2,
+        request,
+    );
+}
+
+#[test]
+fn open_connection_reply_2() {
+    let reply = OpenConnectionReply2 {
+        server_guid: SERVER_GUID,
+        client_addr: address("127.0.0.1:54321"),
+        mtu: 1400,
+        use_encryption: false,
+    };
+    assert_conforms(
+        &fixture!("open_connection_reply_2"),
+        OPEN_CONNECTION_REPLY_2,
+        reply,
+    );
+}
+
+#[test]
+fn connection_request() {
+    let frame = single_frame(&fixture!("connection_request"), 0);
+    assert_eq!(frame.reliability, Reliability::Reliable);
+    assert_eq!(frame.sequence_number, Some(SeqNum::ZERO));
+    let request = ConnectionRequest {
+        client_guid: CLIENT_GUID,
+        time: 0x4d2_0400,
+        use_security: false,
+    };
+    assert_decodes(&frame.payload, CONNECTION_REQUEST, request);
+}
+
+#[test]
+fn connection_request_accepted() {
+    let accepted = ConnectionRequestAccepted {
+        client_address: address("127.0.0.1:54321"),
+        system_index: 0,
+        internal_ids: vec![address("0.0.0.0:19132"); 20],
+        request_time: 0x4d2_0400,
+        time: 0x2710,
+    };
+    assert_conforms(
+        &fixture!("connection_request_accepted"),
+        CONNECTION_REQUEST_ACCEPTED,
+        accepted,
+    );
+}
+
+#[test]
+fn connected_ping() {
+    let frame = single_frame(&fixture!("connected_ping"), 1);
+    assert_eq!(frame.reliability, Reliability::Unreliable);
+    let ping = ConnectedPing { time: 0x4d2_1000 };
+    assert_decodes(&frame.payload, CONNECTED_PING, ping);
+}
+
+#[test]
+fn connected_pong() {
+    let pong = ConnectedPong {
+        ping_time: 0x4d2_1000,
+        pong_time: 0x2800,
+    };
+    assert_conforms(&fixture!("connected_pong"), CONNECTED_PONG, pong);
+}
+
+#[test]
+fn ack() {
+    let ack = AckNackPacket {
+        records: vec![
+            AckNackRecord::Range(SequenceNumberRange {
+                start: SeqNum::new(0),
+                end: SeqNum::new(5),
+            }),
+            AckNackRecord::Single(SeqNum::new(7)),
+        ],
+    };
+    assert_conforms(&fixture!("ack"), ACK, ack);
+}
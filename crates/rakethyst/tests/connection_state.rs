@@ -0,0 +1,44 @@
+use rakethyst::connection::{ConnectionEvent, ConnectionState, InvalidTransition};
+
+use ConnectionEvent::*;
+use ConnectionState::*;
+
+const STATES: [ConnectionState; 4] = [Handshaking, Connecting, Connected, Disconnected];
+const EVENTS: [ConnectionEvent; 3] = [ConnectionRequest, DataFrame, Disconnect];
+
+/// Every (state, event) pair and the state it leads to, `None` where it is rejected.
+const TRANSITIONS: [(ConnectionState, ConnectionEvent, Option<ConnectionState>); 12] = [
+    (Handshaking, ConnectionRequest, Some(Connecting)),
+    (Handshaking, DataFrame, None),
+    (Handshaking, Disconnect, Some(Disconnected)),
+    (Connecting, ConnectionRequest, None),
+    (Connecting, DataFrame, Some(Connected)),
+    (Connecting, Disconnect, Some(Disconnected)),
+    (Connected, ConnectionRequest, None),
+    (Connected, DataFrame, Some(Connected)),
+    (Connected, Disconnect, Some(Disconnected)),
+    (Disconnected, ConnectionRequest, None),
+    (Disconnected, DataFrame, None),
+    (Disconnected, Disconnect, None),
+];
+
+#[test]
+fn table_covers_every_pair() {
+    for state in STATES {
+        for event in EVENTS {
+            let rows = TRANSITIONS
+                .iter()
+                .filter(|&&(from, on, _)| from == state && on == event)
+                .count();
+            assert_eq!(rows, 1, "{:?} on {:?}", state, event);
+        }
+    }
+}
+
+#[test]
+fn transitions_match_the_table() {
+    for (state, event, expected) in TRANSITIONS {
+        let expected = expected.ok_or(InvalidTransition { state, event });
+        assert_eq!(state.advance(event), expected, "{:?} on {:?}", state, event);
+    }
+}
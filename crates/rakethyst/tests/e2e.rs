@@ -0,0 +1,608 @@
+//! Boots a listener on an ephemeral port and drives it with a scripted client, the
+//! [`ScriptedPeer`] of `rakethyst::test_support`, over real UDP sockets, checking what the
+//! client can observe at each step of a connection.
+//!
+//! The client can drop a share of the datagrams it sends, to check that the server copes
+//! with lost packets. Acknowledgements and resends of reliable frames are not checked until
+//! the reliability layer exists.
+
+use bytes::Bytes;
+use dashmap::DashMap;
+use rakethyst::connection::{Connection, ConnectionState};
+use rakethyst::handoff::Handoff;
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::{Motd, ServerListEntry};
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+use rakethyst::session::SystemAddresses;
+use rakethyst::test_support::{encode, ScriptedPeer, CLIENT_GUID, MTU, REPLY_TIMEOUT};
+use rakethyst::throttle::JoinThrottle;
+use rakethyst::traffic::{PacketTraffic, TrafficSnapshot};
+use rakethyst::violations::StrictMode;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+
+const SERVER_GUID: u64 = 0x7ef1_a5b2_c3d4_e5f6;
+
+struct Server {
+    /// The first of `addresses`.
+    address: SocketAddr,
+    addresses: Vec<SocketAddr>,
+    connections: Arc<DashMap<SocketAddr, Connection>>,
+    server_info: Arc<ServerInfo>,
+    listener: Arc<RakNetListener>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    async fn start() -> Self {
+        Self::start_with(|listener| listener).await
+    }
+
+    async fn start_with(configure: impl FnOnce(RakNetListener) -> RakNetListener) -> Self {
+        Self::start_on(&["127.0.0.1:0"], configure).await
+    }
+
+    async fn start_on(
+        addrs: &[&str],
+        configure: impl FnOnce(RakNetListener) -> RakNetListener,
+    ) -> Self {
+        rakethyst::utils::init_time();
+        let motd = Motd {
+            motd: "Amethyst".to_string(),
+            world_name: "World".to_string(),
+            game_mode: "Survival".to_string(),
+            max_players: 10,
+        };
+        let server_info = Arc::new(ServerInfo::new(SERVER_GUID, motd));
+        let listener = RakNetListener::bind_all(addrs, Arc::clone(&server_info))
+            .await
+            .expect("failed to bind the listener");
+        Self::serve(configure(listener), server_info)
+    }
+
+    /// Replaces `previous` with a new listener on the same sockets, the way a hot restart
+    /// hands sessions over to a new process.
+    async fn take_over(previous: Server) -> Self {
+        previous.task.abort();
+        let handoff = previous.listener.detach_sessions(REPLY_TIMEOUT).await;
+        let handoff = Handoff::decode(handoff.encode().unwrap()).unwrap();
+        let sockets = previous.listener.try_clone_sockets().unwrap();
+        let server_info = Arc::clone(&previous.server_info);
+        drop(previous);
+        let listener = RakNetListener::from_std_sockets(sockets, Arc::clone(&server_info)).unwrap();
+        assert_eq!(listener.restore_sessions(&handoff), handoff.sessions.len());
+        Self::serve(listener, server_info)
+    }
+
+    fn serve(listener: RakNetListener, server_info: Arc<ServerInfo>) -> Self {
+        let listener = Arc::new(listener);
+        let addresses = listener.local_addrs().unwrap();
+        let connections = listener.connections();
+        let task = tokio::spawn({
+            let listener = Arc::clone(&listener);
+            async move {
+                let _ = listener.run().await;
+            }
+        });
+        Server {
+            address: addresses[0],
+            addresses,
+            connections,
+            server_info,
+            listener,
+            task,
+        }
+    }
+
+    fn state_of(&self, client: &ScriptedPeer) -> Option<ConnectionState> {
+        let address = client.local_addr();
+        self.connections
+            .get(&address)
+            .map(|connection| connection.state)
+    }
+
+    /// Waits until the connection of `client` is gone.
+    async fn wait_closed(&self, client: &ScriptedPeer, within: Duration) {
+        let deadline = Instant::now() + within;
+        while self.state_of(client).is_some() {
+            assert!(
+                Instant::now() < deadline,
+                "connection was not closed in time"
+            );
+            sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[tokio::test]
+async fn unconnected_ping_is_answered_with_the_motd() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+
+    let ping = UnconnectedPing {
+        time: 42,
+        client_guid: CLIENT_GUID,
+    };
+    client.send(UNCONNECTED_PING, &ping).await;
+    let pong: UnconnectedPong = client.expect(UNCONNECTED_PONG).await;
+    assert_eq!(pong.time, 42);
+    assert_eq!(pong.server_guid, SERVER_GUID);
+    assert_eq!(pong.motd, *server.server_info.pong_payload());
+    assert!(pong.motd.starts_with("MCPE;Amethyst;"));
+    assert_eq!(
+        server.state_of(&client),
+        None,
+        "a ping must not open a connection"
+    );
+}
+
+#[tokio::test]
+async fn open_connections_ping_is_only_answered_with_free_slots() {
+    let server = Server::start().await;
+    let mut pinger = ScriptedPeer::connect(server.address).await;
+    let ping = UnconnectedPing {
+        time: 42,
+        client_guid: CLIENT_GUID,
+    };
+    pinger.send(UNCONNECTED_PING_OPEN_CONNECTIONS, &ping).await;
+    let pong: UnconnectedPong = pinger.expect(UNCONNECTED_PONG).await;
+    assert_eq!(pong.time, 42);
+    assert_eq!(pong.motd, *server.server_info.pong_payload());
+
+    server.server_info.set_motd(Motd {
+        max_players: 1,
+        ..server.server_info.motd()
+    });
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    pinger.send(UNCONNECTED_PING_OPEN_CONNECTIONS, &ping).await;
+    assert!(
+        pinger.recv().await.is_none(),
+        "a full server must not answer"
+    );
+    pinger.send(UNCONNECTED_PING, &ping).await;
+    let _: UnconnectedPong = pinger.expect(UNCONNECTED_PONG).await;
+}
+
+#[tokio::test]
+async fn handshake_opens_a_connection() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+
+    let accepted = client.handshake().await;
+    assert_eq!(client.server_guid(), Some(SERVER_GUID));
+    assert_eq!(accepted.client_address, client.local_addr());
+    assert_eq!(accepted.request_time, 1234);
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connecting));
+    assert_eq!(server.server_info.player_count(), 1);
+
+    let ping = ConnectedPing { time: 1 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let _: (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+}
+
+#[tokio::test]
+async fn traffic_is_counted_per_packet_id() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    let ping = ConnectedPing { time: 1 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let _: (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+
+    let traffic = server.listener.traffic().snapshot();
+    let count = |traffic: &[PacketTraffic], id: u8| {
+        traffic
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| (entry.count, entry.bytes))
+    };
+    assert_eq!(
+        count(&traffic.received.datagrams, OPEN_CONNECTION_REQUEST_1).map(|(count, _)| count),
+        Some(1)
+    );
+    assert_eq!(
+        count(&traffic.sent.datagrams, OPEN_CONNECTION_REPLY_1).map(|(count, _)| count),
+        Some(1)
+    );
+    assert_eq!(
+        count(&traffic.received.packets, CONNECTED_PING),
+        Some((1, 9))
+    );
+    assert!(count(&traffic.received.datagrams, FRAME_SET).is_some());
+
+    server.listener.traffic().reset();
+    assert_eq!(
+        server.listener.traffic().snapshot(),
+        TrafficSnapshot::default()
+    );
+}
+
+#[tokio::test]
+async fn connection_request_accepted_carries_the_system_addresses() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    let accepted = client.handshake().await;
+    assert_eq!(accepted.system_index, 0);
+    assert_eq!(accepted.internal_ids, vec![server.address; 20]);
+
+    let addresses = SystemAddresses {
+        system_index: 3,
+        count: 10,
+        addresses: vec!["10.0.0.2:19132".parse().unwrap()],
+    };
+    let server = Server::start_with(|listener| listener.with_system_addresses(addresses)).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    let accepted = client.handshake().await;
+    assert_eq!(accepted.system_index, 3);
+    assert_eq!(accepted.internal_ids.len(), 10);
+    assert_eq!(accepted.internal_ids[0], "10.0.0.2:19132".parse().unwrap());
+    assert_eq!(accepted.internal_ids[1..], [server.address; 9]);
+}
+
+#[tokio::test]
+async fn sessions_survive_a_handoff_to_a_new_listener() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    let ping = ConnectedPing { time: 1 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let (sequence_number, _, ConnectedPong { .. }) = client.expect_framed().await;
+
+    let server = Server::take_over(server).await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+    assert_eq!(server.server_info.player_count(), 1);
+    let ping = ConnectedPing { time: 2 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let (next, _, pong): (_, _, ConnectedPong) = client.expect_framed().await;
+    assert_eq!(next, sequence_number.next());
+    assert_eq!(pong.ping_time, 2);
+}
+
+#[tokio::test]
+async fn data_frames_before_the_handshake_are_ignored() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+
+    let ping = ConnectedPing { time: 1 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    assert!(client.recv().await.is_none());
+    assert_eq!(server.state_of(&client), None);
+}
+
+#[tokio::test]
+async fn connected_pings_survive_packet_loss() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    client.set_loss(3);
+
+    let mut delivered = Vec::new();
+    for time in 100..112 {
+        let ping = ConnectedPing { time };
+        if client
+            .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+            .await
+        {
+            delivered.push(time);
+        }
+    }
+    assert_eq!(delivered.len(), 8);
+
+    // Every delivered ping is answered in order, with consecutive sequence numbers.
+    for (sequence_number, time) in delivered.into_iter().enumerate() {
+        let (number, id, pong): (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+        assert_eq!(id, CONNECTED_PONG);
+        assert_eq!(number, SeqNum::new(sequence_number as u32));
+        assert_eq!(pong.ping_time, time);
+    }
+    assert!(
+        client.recv().await.is_none(),
+        "lost pings must not be answered"
+    );
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+}
+
+#[tokio::test]
+async fn reliable_frames_keep_the_connection_alive() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    client.set_loss(2);
+
+    let ping = ConnectedPing { time: 7 };
+    for _ in 0..6 {
+        client
+            .send_framed(encode(CONNECTED_PING, &ping), Reliability::Reliable)
+            .await;
+    }
+    let _: (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+}
+
+#[tokio::test]
+async fn disconnect_closes_the_connection() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    assert_eq!(server.server_info.player_count(), 1);
+
+    client
+        .send_framed(
+            Bytes::from_static(&[DISCONNECTION_NOTIFICATION]),
+            Reliability::Reliable,
+        )
+        .await;
+    server.wait_closed(&client, REPLY_TIMEOUT).await;
+    assert_eq!(server.server_info.player_count(), 0);
+
+    // The session is over, so its data frames are dropped until a new handshake.
+    let ping = ConnectedPing { time: 1 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    assert!(client.recv().await.is_none());
+    client.handshake().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connecting));
+}
+
+#[tokio::test]
+async fn every_address_serves_the_same_players() {
+    let server = Server::start_on(&["127.0.0.1:0", "127.0.0.1:0"], |listener| listener).await;
+    assert_ne!(server.addresses[0], server.addresses[1]);
+    let mut first = ScriptedPeer::connect(server.addresses[0]).await;
+    first.handshake().await;
+    let mut second = ScriptedPeer::connect(server.addresses[1]).await;
+    second.handshake().await;
+    assert_eq!(server.state_of(&first), Some(ConnectionState::Connecting));
+    assert_eq!(server.state_of(&second), Some(ConnectionState::Connecting));
+
+    // Replies come from the address the client reached, which `recv` checks.
+    let ping = ConnectedPing { time: 9 };
+    second
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let (_, _, pong): (_, _, ConnectedPong) = second.expect_framed().await;
+    assert_eq!(pong.ping_time, 9);
+
+    let mut pinger = ScriptedPeer::connect(server.addresses[0]).await;
+    assert_eq!(advertised_players(&mut pinger).await, 2);
+}
+
+#[tokio::test]
+async fn shutdown_disconnects_clients_and_refuses_new_ones() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+
+    server.listener.stop_accepting();
+    assert_eq!(server.listener.close_all(REPLY_TIMEOUT).await, 1);
+    let (_, id, DisconnectionNotification) = client.expect_framed().await;
+    assert_eq!(id, DISCONNECTION_NOTIFICATION);
+    assert_eq!(server.state_of(&client), None);
+    assert_eq!(server.server_info.player_count(), 0);
+
+    let mut late = ScriptedPeer::connect(server.address).await;
+    let request = OpenConnectionRequest1 {
+        protocol_version: RAKNET_PROTOCOL_VERSION,
+    };
+    late.send(OPEN_CONNECTION_REQUEST_1, &request).await;
+    assert!(late.recv().await.is_none());
+
+    let ping = UnconnectedPing {
+        time: 1,
+        client_guid: CLIENT_GUID,
+    };
+    late.send(UNCONNECTED_PING_OPEN_CONNECTIONS, &ping).await;
+    assert!(late.recv().await.is_none());
+    late.send(UNCONNECTED_PING, &ping).await;
+    let _: UnconnectedPong = late.expect(UNCONNECTED_PONG).await;
+}
+
+/// The player count the server advertises to `pinger` in its pong.
+async fn advertised_players(pinger: &mut ScriptedPeer) -> u32 {
+    let ping = UnconnectedPing {
+        time: 7,
+        client_guid: CLIENT_GUID,
+    };
+    pinger.send(UNCONNECTED_PING, &ping).await;
+    let pong: UnconnectedPong = pinger.expect(UNCONNECTED_PONG).await;
+    ServerListEntry::parse(&pong.motd).unwrap().player_count
+}
+
+#[tokio::test]
+async fn pong_advertises_the_live_player_count() {
+    let server = Server::start().await;
+    let mut pinger = ScriptedPeer::connect(server.address).await;
+    assert_eq!(advertised_players(&mut pinger).await, 0);
+
+    let mut first = ScriptedPeer::connect(server.address).await;
+    first.handshake().await;
+    let mut second = ScriptedPeer::connect(server.address).await;
+    second.handshake().await;
+    assert_eq!(advertised_players(&mut pinger).await, 2);
+
+    first
+        .send_framed(
+            Bytes::from_static(&[DISCONNECTION_NOTIFICATION]),
+            Reliability::Reliable,
+        )
+        .await;
+    server.wait_closed(&first, REPLY_TIMEOUT).await;
+    assert_eq!(advertised_players(&mut pinger).await, 1);
+}
+
+#[tokio::test]
+async fn unfinished_handshake_times_out() {
+    let handshake_timeout = Duration::from_millis(300);
+    let server =
+        Server::start_with(|listener| listener.with_handshake_timeout(handshake_timeout)).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connecting));
+
+    server.wait_closed(&client, handshake_timeout * 4).await;
+    assert_eq!(server.server_info.player_count(), 0);
+}
+
+/// A frame set too short to hold its sequence number.
+const MALFORMED_FRAME_SET: &[u8] = &[FRAME_SET, 0];
+
+#[tokio::test]
+async fn violations_are_only_logged_outside_strict_mode() {
+    let server = Server::start().await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+
+    for _ in 0..20 {
+        client.send_datagram(MALFORMED_FRAME_SET).await;
+    }
+    let ping = ConnectedPing { time: 3 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let _: (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+}
+
+#[tokio::test]
+async fn strict_mode_disconnects_after_repeated_violations() {
+    let mode = StrictMode {
+        max_violations: 3,
+        window: Duration::from_secs(10),
+    };
+    let server = Server::start_with(|listener| listener.with_strict_mode(mode)).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+
+    client.send_datagram(MALFORMED_FRAME_SET).await;
+    client.send_datagram(MALFORMED_FRAME_SET).await;
+    assert!(client.recv().await.is_none());
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+
+    client.send_datagram(MALFORMED_FRAME_SET).await;
+    let (_, id, DisconnectionNotification) = client.expect_framed().await;
+    assert_eq!(id, DISCONNECTION_NOTIFICATION);
+    assert_eq!(server.state_of(&client), None);
+    assert_eq!(server.server_info.player_count(), 0);
+}
+
+#[tokio::test]
+async fn strict_mode_forgets_violations_outside_the_window() {
+    let mode = StrictMode {
+        max_violations: 3,
+        window: Duration::from_millis(300),
+    };
+    let server = Server::start_with(|listener| listener.with_strict_mode(mode)).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    client.handshake().await;
+
+    for _ in 0..3 {
+        client.send_datagram(MALFORMED_FRAME_SET).await;
+        sleep(Duration::from_millis(200)).await;
+    }
+    let ping = ConnectedPing { time: 5 };
+    client
+        .send_framed(encode(CONNECTED_PING, &ping), Reliability::Unreliable)
+        .await;
+    let _: (SeqNum, u8, ConnectedPong) = client.expect_framed().await;
+    assert_eq!(server.state_of(&client), Some(ConnectionState::Connected));
+}
+
+/// A throttle that challenges a subnet once it starts more than `max_subnet_handshakes`.
+fn join_throttle(max_subnet_handshakes: u32) -> JoinThrottle {
+    JoinThrottle {
+        max_subnet_handshakes,
+        window: Duration::from_secs(10),
+        min_retry_interval: Duration::ZERO,
+        allowlist: Vec::new(),
+    }
+}
+
+/// Sends an `OPEN_CONNECTION_REQUEST_1` and returns the cookie of the reply.
+async fn open_connection_cookie(client: &mut ScriptedPeer) -> Option<u32> {
+    let request = OpenConnectionRequest1 {
+        protocol_version: RAKNET_PROTOCOL_VERSION,
+    };
+    client.send(OPEN_CONNECTION_REQUEST_1, &request).await;
+    let reply: OpenConnectionReply1 = client.expect(OPEN_CONNECTION_REPLY_1).await;
+    reply.cookie
+}
+
+#[tokio::test]
+async fn join_throttle_challenges_busy_subnets() {
+    let server = Server::start_with(|listener| listener.with_join_throttle(join_throttle(1))).await;
+    let mut first = ScriptedPeer::connect(server.address).await;
+    assert_eq!(open_connection_cookie(&mut first).await, None);
+    first.handshake().await;
+
+    let mut second = ScriptedPeer::connect(server.address).await;
+    assert!(open_connection_cookie(&mut second).await.is_some());
+    second.handshake().await;
+    assert_eq!(server.state_of(&second), Some(ConnectionState::Connecting));
+}
+
+#[tokio::test]
+async fn join_throttle_drops_clients_that_skip_the_challenge() {
+    let server = Server::start_with(|listener| listener.with_join_throttle(join_throttle(0))).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    assert!(open_connection_cookie(&mut client).await.is_some());
+
+    let request = OpenConnectionRequest2 {
+        cookie: None,
+        server_addr: server.address,
+        mtu: MTU,
+        client_guid: CLIENT_GUID,
+    };
+    client.send(OPEN_CONNECTION_REQUEST_2, &request).await;
+    assert!(client.recv().await.is_none());
+
+    let request = OpenConnectionRequest2 {
+        cookie: Some(0xdead_beef),
+        ..request
+    };
+    client.send(OPEN_CONNECTION_REQUEST_2, &request).await;
+    assert!(client.recv().await.is_none());
+
+    let request = ConnectionRequest {
+        client_guid: CLIENT_GUID,
+        time: 1234,
+        use_security: false,
+    };
+    client.send(CONNECTION_REQUEST, &request).await;
+    assert!(client.recv().await.is_none());
+    assert_eq!(server.state_of(&client), None);
+}
+
+#[tokio::test]
+async fn join_throttle_never_challenges_the_allowlist() {
+    let throttle = JoinThrottle {
+        allowlist: vec!["127.0.0.0/8".parse().unwrap()],
+        ..join_throttle(0)
+    };
+    let server = Server::start_with(|listener| listener.with_join_throttle(throttle)).await;
+    let mut client = ScriptedPeer::connect(server.address).await;
+    assert_eq!(open_connection_cookie(&mut client).await, None);
+    client.handshake().await;
+}
@@ -0,0 +1,54 @@
+//! Encoding of the session state handed over on a hot restart.
+
+use bytes::Bytes;
+use rakethyst::connection::ConnectionState;
+use rakethyst::handoff::{Handoff, HandoffError, SessionState};
+use rakethyst::seq::SeqNum;
+
+#[test]
+fn handoffs_survive_encoding() {
+    let handoff = Handoff {
+        sessions: vec![
+            SessionState {
+                socket: 0,
+                address: "192.0.2.7:51234".parse().unwrap(),
+                reply_addr: "192.0.2.7:51234".parse().unwrap(),
+                client_guid: 0x1a2b_3c4d_5e6f_7081,
+                mtu: 1400,
+                state: ConnectionState::Connected,
+                next_sequence_number: SeqNum::new(SeqNum::MAX),
+            },
+            SessionState {
+                socket: 1,
+                address: "[2001:db8::7]:51234".parse().unwrap(),
+                reply_addr: "[2001:db8::1]:40000".parse().unwrap(),
+                client_guid: 7,
+                mtu: 576,
+                state: ConnectionState::Connecting,
+                next_sequence_number: SeqNum::ZERO,
+            },
+        ],
+    };
+    let decoded = Handoff::decode(handoff.encode().unwrap()).unwrap();
+    assert_eq!(decoded, handoff);
+    assert_eq!(
+        Handoff::decode(Handoff::default().encode().unwrap()).unwrap(),
+        Handoff::default()
+    );
+}
+
+#[test]
+fn other_files_are_rejected() {
+    assert!(matches!(
+        Handoff::decode(Bytes::from_static(b"AREC\x01")),
+        Err(HandoffError::NotAHandoff)
+    ));
+    assert!(matches!(
+        Handoff::decode(Bytes::from_static(b"AHOF\x02")),
+        Err(HandoffError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        Handoff::decode(Bytes::from_static(b"AHOF\x01\x00")),
+        Err(HandoffError::Malformed(_))
+    ));
+}
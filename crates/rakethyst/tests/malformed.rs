@@ -0,0 +1,67 @@
+use amethyst_binary::error::BinaryError;
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::Readable;
+use bytes::Bytes;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+
+fn read<T: Readable>(data: &[u8]) -> Result<T, BinaryError> {
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(data)))
+}
+
+/// A reliable split packet header with a one-byte payload.
+fn split_packet(count: u32, index: u32) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer
+        .write_u8((Reliability::Reliable as u8) << 5 | 0x10)
+        .unwrap();
+    writer.write_u16(8).unwrap();
+    writer.write_u24_le(0).unwrap();
+    writer.write_u32(count).unwrap();
+    writer.write_u16(1).unwrap();
+    writer.write_u32(index).unwrap();
+    writer.write_u8(0xfe).unwrap();
+    writer.freeze().to_vec()
+}
+
+#[test]
+fn ack_record_count_beyond_input_is_rejected() {
+    let result = read::<AckNackPacket>(&[0xff, 0xff, 0, 1, 0, 0]);
+    assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+    let packet = read::<AckNackPacket>(&[0, 1, 0, 1, 0, 0]).unwrap();
+    assert_eq!(packet.records, [AckNackRecord::Single(SeqNum::new(1))]);
+}
+
+#[test]
+fn encapsulated_payload_length_beyond_input_is_rejected() {
+    let mut data = split_packet(2, 0);
+    data[1..3].copy_from_slice(&u16::MAX.to_be_bytes());
+    let result = read::<EncapsulatedPacket>(&data);
+    assert!(matches!(result, Err(BinaryError::UnexpectedEOF)));
+}
+
+#[test]
+fn split_index_must_be_within_split_count() {
+    assert!(read::<EncapsulatedPacket>(&split_packet(2, 1)).is_ok());
+    for (count, index) in [(2, 2), (0, 0), (1, u32::MAX)] {
+        let result = read::<EncapsulatedPacket>(&split_packet(count, index));
+        assert!(matches!(result, Err(BinaryError::InvalidData(_))));
+    }
+}
+
+proptest! {
+    /// Arbitrary datagrams may fail to decode, but must never panic or over-allocate.
+    #[test]
+    fn arbitrary_input_does_not_panic(data in vec(any::<u8>(), 0..256)) {
+        let _ = read::<AckNackPacket>(&data);
+        let _ = read::<EncapsulatedPacket>(&data);
+        let _ = read::<FrameSetPacket>(&data);
+        let _ = read::<UnconnectedPing>(&data);
+        let _ = read::<OpenConnectionRequest1>(&data);
+        let _ = read::<OpenConnectionRequest2>(&data);
+        let _ = read::<ConnectionRequest>(&data);
+        let _ = read::<ConnectionRequestAccepted>(&data);
+    }
+}
@@ -0,0 +1,66 @@
+//! Parses server list entries, including the one this server builds.
+
+use rakethyst::motd::{
+    Motd, MotdBuilder, MotdParseError, ServerListEntry, MINECRAFT_VERSION, PROTOCOL_VERSION,
+};
+
+#[test]
+fn parses_what_the_builder_produces() {
+    let mut builder = MotdBuilder::new(
+        42,
+        Motd {
+            motd: "&aAmethyst; test".to_string(),
+            world_name: "World".to_string(),
+            game_mode: "Creative".to_string(),
+            max_players: 20,
+        },
+    );
+    builder.set_player_count(3);
+    builder.set_local_address("0.0.0.0:19200".parse().unwrap());
+
+    let entry = ServerListEntry::parse(&builder.build()).unwrap();
+    assert_eq!(
+        entry,
+        ServerListEntry {
+            motd: "§aAmethyst: test".to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            minecraft_version: MINECRAFT_VERSION.to_string(),
+            player_count: 3,
+            max_players: 20,
+            guid: Some(42),
+            world_name: Some("World".to_string()),
+            game_mode: Some("Creative".to_string()),
+            ipv4_port: Some(19200),
+            ipv6_port: Some(19133),
+        }
+    );
+}
+
+#[test]
+fn only_the_first_six_fields_are_required() {
+    let entry = ServerListEntry::parse("MCPE;Old server;390;1.14.60;1;10").unwrap();
+    assert_eq!(entry.motd, "Old server");
+    assert_eq!(entry.protocol_version, 390);
+    assert_eq!((entry.player_count, entry.max_players), (1, 10));
+    assert_eq!(entry.guid, None);
+    assert_eq!(entry.world_name, None);
+}
+
+#[test]
+fn rejects_other_entries() {
+    assert_eq!(
+        ServerListEntry::parse("MCEE;Education;390;1.14.60;1;10"),
+        Err(MotdParseError::Edition)
+    );
+    assert_eq!(
+        ServerListEntry::parse("MCPE;Server;662;1.20.80;1"),
+        Err(MotdParseError::Missing("max players"))
+    );
+    assert_eq!(
+        ServerListEntry::parse("MCPE;Server;new;1.20.80;1;10"),
+        Err(MotdParseError::Invalid(
+            "protocol version",
+            "new".to_string()
+        ))
+    );
+}
@@ -0,0 +1,153 @@
+use rakethyst::packet_trace::{Direction, PacketTrace, PacketTraceFilter, PeerPattern};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+const CLIENT: &str = "127.0.0.1:40000";
+const OTHER_CLIENT: &str = "127.0.0.2:40000";
+
+fn temp_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rakethyst-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn peer(address: &str) -> SocketAddr {
+    address.parse().unwrap()
+}
+
+/// A frame set holding one unreliable, unsplit frame with `payload`.
+fn frame_set(payload: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x84, 0, 0, 0, 0x00];
+    data.extend(((payload.len() * 8) as u16).to_be_bytes());
+    data.extend(payload);
+    data
+}
+
+fn connected_ping() -> Vec<u8> {
+    frame_set(&[0x00, 0, 0, 0, 0, 0, 0, 0, 1])
+}
+
+fn connection_request() -> Vec<u8> {
+    let mut data = vec![0x09];
+    data.extend(1234u64.to_be_bytes());
+    data.extend(1i64.to_be_bytes());
+    data.push(0);
+    data
+}
+
+/// The header lines written for `datagrams`, sent by the given clients, under `filter`.
+fn traced(name: &str, filter: PacketTraceFilter, datagrams: &[(&str, Vec<u8>)]) -> Vec<String> {
+    let path = temp_file(name);
+    let trace = PacketTrace::new();
+    trace.set_filter(filter);
+    trace.enable(&path).unwrap();
+    for (client, data) in datagrams {
+        trace.record(Direction::Received, peer(client), data);
+    }
+    trace.disable();
+    let lines = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .map(str::to_string)
+        .collect();
+    let _ = std::fs::remove_file(&path);
+    lines
+}
+
+#[test]
+fn datagrams_are_annotated_and_dumped() {
+    let path = temp_file("annotated");
+    let trace = PacketTrace::new();
+    trace.enable(&path).unwrap();
+    trace.record(Direction::Received, peer(CLIENT), &connected_ping());
+    trace.record(Direction::Sent, peer(CLIENT), &[0xfe, 0x01]);
+    trace.disable();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", text);
+    assert!(lines[0].contains("<- 127.0.0.1:40000 0x84 FRAME_SET (16 bytes)"));
+    assert!(lines[0].ends_with("seq=0 [Unreliable len=9 0x00 CONNECTED_PING]"));
+    assert!(lines[1].starts_with("  0000  84 00 00 00 00 00 48 00"));
+    assert!(lines[2].contains("-> 127.0.0.1:40000 0xfe UNKNOWN (2 bytes)"));
+    assert!(lines[3].ends_with("|..|"));
+}
+
+#[test]
+fn malformed_datagrams_are_still_dumped() {
+    let lines = traced(
+        "malformed",
+        PacketTraceFilter::default(),
+        &[(CLIENT, vec![0x84, 0])],
+    );
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("malformed"), "{}", lines[0]);
+}
+
+#[test]
+fn nothing_is_written_while_disabled() {
+    let path = temp_file("disabled");
+    let trace = PacketTrace::new();
+    trace.record(Direction::Received, peer(CLIENT), &connection_request());
+    trace.enable(&path).unwrap();
+    trace.disable();
+    trace.record(Direction::Received, peer(CLIENT), &connection_request());
+    assert!(trace.path().is_none());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn included_ids_match_the_packets_inside_frame_sets() {
+    let filter = PacketTraceFilter {
+        include_ids: vec![0x00],
+        ..Default::default()
+    };
+    let lines = traced(
+        "include-ids",
+        filter,
+        &[(CLIENT, connection_request()), (CLIENT, connected_ping())],
+    );
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("CONNECTED_PING"));
+}
+
+#[test]
+fn frame_sets_of_excluded_packets_are_left_out() {
+    let filter = PacketTraceFilter {
+        exclude_ids: vec![0x00],
+        ..Default::default()
+    };
+    let lines = traced(
+        "exclude-ids",
+        filter,
+        &[(CLIENT, connected_ping()), (CLIENT, connection_request())],
+    );
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("CONNECTION_REQUEST"));
+}
+
+#[test]
+fn peers_are_matched_by_ip_or_address() {
+    let filter = PacketTraceFilter {
+        include_peers: vec!["127.0.0.1".parse().unwrap()],
+        ..Default::default()
+    };
+    let datagrams = [
+        (CLIENT, connection_request()),
+        (OTHER_CLIENT, connection_request()),
+    ];
+    let lines = traced("include-peers", filter, &datagrams);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains(CLIENT));
+
+    let filter = PacketTraceFilter {
+        exclude_peers: vec![PeerPattern::Address(peer(CLIENT))],
+        ..Default::default()
+    };
+    let lines = traced("exclude-peers", filter, &datagrams);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains(OTHER_CLIENT));
+}
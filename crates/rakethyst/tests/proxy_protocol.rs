@@ -0,0 +1,116 @@
+use rakethyst::proxy_protocol::{parse, ProxyClients, ProxyHeader, ProxyProtocolError, SIGNATURE};
+use std::net::SocketAddr;
+
+/// A PROXY command header for a UDP datagram, followed by `payload`.
+fn header(source: SocketAddr, destination: SocketAddr, tlvs: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut data = SIGNATURE.to_vec();
+    data.push(0x21);
+    let mut addresses = Vec::new();
+    match (source, destination) {
+        (SocketAddr::V4(source), SocketAddr::V4(destination)) => {
+            data.push(0x12);
+            addresses.extend(source.ip().octets());
+            addresses.extend(destination.ip().octets());
+        }
+        (SocketAddr::V6(source), SocketAddr::V6(destination)) => {
+            data.push(0x22);
+            addresses.extend(source.ip().octets());
+            addresses.extend(destination.ip().octets());
+        }
+        _ => panic!("mixed address families"),
+    }
+    addresses.extend(source.port().to_be_bytes());
+    addresses.extend(destination.port().to_be_bytes());
+    addresses.extend(tlvs);
+    data.extend((addresses.len() as u16).to_be_bytes());
+    data.extend(addresses);
+    data.extend(payload);
+    data
+}
+
+fn addr(value: &str) -> SocketAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn parses_ipv4_and_ipv6_headers() {
+    let data = header(
+        addr("203.0.113.7:51234"),
+        addr("10.0.0.2:19132"),
+        &[],
+        &[0x01],
+    );
+    assert_eq!(
+        parse(&data),
+        Ok(Some((
+            ProxyHeader::Proxy {
+                source: addr("203.0.113.7:51234"),
+                destination: addr("10.0.0.2:19132"),
+            },
+            28
+        )))
+    );
+
+    let data = header(
+        addr("[2001:db8::7]:51234"),
+        addr("[2001:db8::2]:19132"),
+        &[],
+        &[],
+    );
+    assert_eq!(
+        parse(&data),
+        Ok(Some((
+            ProxyHeader::Proxy {
+                source: addr("[2001:db8::7]:51234"),
+                destination: addr("[2001:db8::2]:19132"),
+            },
+            52
+        )))
+    );
+}
+
+#[test]
+fn skips_tlvs() {
+    let tlvs = [0x04, 0x00, 0x03, b'a', b'b', b'c'];
+    let data = header(
+        addr("203.0.113.7:51234"),
+        addr("10.0.0.2:19132"),
+        &tlvs,
+        &[0x01],
+    );
+    let (_, length) = parse(&data).unwrap().unwrap();
+    assert_eq!(&data[length..], &[0x01]);
+}
+
+#[test]
+fn rejects_malformed_headers() {
+    assert_eq!(parse(&[0x01, 0x02]), Ok(None));
+
+    let data = header(addr("203.0.113.7:51234"), addr("10.0.0.2:19132"), &[], &[]);
+    assert_eq!(parse(&data[..20]), Err(ProxyProtocolError::Truncated));
+
+    let mut wrong_version = data.clone();
+    wrong_version[12] = 0x11;
+    assert_eq!(parse(&wrong_version), Err(ProxyProtocolError::Version(1)));
+
+    let mut local = data;
+    local[12] = 0x20;
+    assert_eq!(parse(&local), Ok(Some((ProxyHeader::Local, 28))));
+}
+
+#[test]
+fn remembers_clients_behind_a_peer() {
+    let clients = ProxyClients::new();
+    let peer = addr("10.0.0.1:40000");
+    let client = addr("203.0.113.7:51234");
+
+    assert_eq!(clients.resolve(peer, &[0x01]), None);
+
+    let data = header(client, addr("10.0.0.2:19132"), &[], &[0x01, 0x02]);
+    assert_eq!(
+        clients.resolve(peer, &data),
+        Some((client, &[0x01, 0x02][..]))
+    );
+    assert_eq!(clients.resolve(peer, &[0x84]), Some((client, &[0x84][..])));
+    assert_eq!(clients.resolve(addr("10.0.0.9:40000"), &[0x84]), None);
+}
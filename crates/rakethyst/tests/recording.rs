@@ -0,0 +1,199 @@
+use amethyst_binary::io::BinaryWriter;
+use amethyst_binary::traits::Writable;
+use bytes::Bytes;
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::Motd;
+use rakethyst::protocol::*;
+use rakethyst::recording::{RecordedDatagram, Recording, RecordingError, MAGIC};
+use rakethyst::seq::SeqNum;
+use rakethyst::session::CONNECTION_TIMEOUT;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Instant};
+
+const CLIENT_GUID: u64 = 0x1a2b_3c4d_5e6f_7081;
+
+fn encode(id: u8, packet: &impl Writable) -> Bytes {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(id).unwrap();
+    packet.write(&mut writer).unwrap();
+    writer.freeze()
+}
+
+fn connection_request() -> Bytes {
+    let request = ConnectionRequest {
+        client_guid: CLIENT_GUID,
+        time: 1,
+        use_security: false,
+    };
+    encode(CONNECTION_REQUEST, &request)
+}
+
+fn connected_ping(time: u64) -> Bytes {
+    let frame_set = FrameSetPacket {
+        sequence_number: SeqNum::new(time as u32),
+        packets: vec![EncapsulatedPacket {
+            reliability: Reliability::Unreliable,
+            is_split: false,
+            sequence_number: None,
+            ordering_index: None,
+            ordering_channel: None,
+            split_count: None,
+            split_id: None,
+            split_index: None,
+            payload: encode(CONNECTED_PING, &ConnectedPing { time }),
+        }],
+    };
+    encode(FRAME_SET, &frame_set)
+}
+
+fn recording(datagrams: &[(u64, Bytes)]) -> Recording {
+    Recording {
+        address: "10.0.0.7:50123".parse().unwrap(),
+        datagrams: datagrams
+            .iter()
+            .map(|(millis, payload)| RecordedDatagram {
+                offset: Duration::from_millis(*millis),
+                payload: payload.clone(),
+            })
+            .collect(),
+    }
+}
+
+async fn listener() -> RakNetListener {
+    rakethyst::utils::init_time();
+    let motd = Motd {
+        motd: "Amethyst".to_string(),
+        world_name: "World".to_string(),
+        game_mode: "Survival".to_string(),
+        max_players: 10,
+    };
+    let server_info = Arc::new(ServerInfo::new(1, motd));
+    RakNetListener::bind("127.0.0.1:0", server_info)
+        .await
+        .unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rakethyst-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn recordings_survive_encoding() {
+    let recording = recording(&[
+        (0, connection_request()),
+        (250, connected_ping(1)),
+        (250, connected_ping(2)),
+        (90_000, connected_ping(3)),
+    ]);
+    let decoded = Recording::decode(recording.encode().unwrap()).unwrap();
+    assert_eq!(decoded, recording);
+    assert_eq!(decoded.duration(), Duration::from_secs(90));
+}
+
+#[test]
+fn foreign_and_damaged_files_are_rejected() {
+    assert!(matches!(
+        Recording::decode(Bytes::from_static(b"PK\x03\x04 not a recording")),
+        Err(RecordingError::NotARecording)
+    ));
+    assert!(matches!(
+        Recording::decode(Bytes::from_static(b"AR")),
+        Err(RecordingError::NotARecording)
+    ));
+
+    let mut future = MAGIC.to_vec();
+    future.push(99);
+    assert!(matches!(
+        Recording::decode(Bytes::from(future)),
+        Err(RecordingError::UnsupportedVersion(99))
+    ));
+
+    let encoded = recording(&[(0, connection_request())]).encode().unwrap();
+    let truncated = encoded.slice(..encoded.len() - 1);
+    assert!(matches!(
+        Recording::decode(truncated),
+        Err(RecordingError::Malformed(_))
+    ));
+}
+
+#[tokio::test]
+async fn sessions_are_recorded_as_they_arrive() {
+    let directory = temp_dir("record");
+    let listener = listener().await.with_session_recording(&directory);
+    let server = listener.local_addr().unwrap();
+    let task = tokio::spawn(async move {
+        let _ = listener.run().await;
+    });
+
+    let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client.connect(server).await.unwrap();
+    let mut buf = [0; 2048];
+    let sent = [connection_request(), connected_ping(7)];
+    for datagram in &sent {
+        client.send(datagram).await.unwrap();
+        timeout(Duration::from_secs(2), client.recv(&mut buf))
+            .await
+            .expect("no reply from the server")
+            .unwrap();
+    }
+    task.abort();
+
+    // Each batch of datagrams is saved before the session waits for the next.
+    let files: Vec<_> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let recorded = Recording::load(&files[0]).unwrap();
+    assert_eq!(recorded.address, client.local_addr().unwrap());
+    let payloads: Vec<_> = recorded.datagrams.into_iter().map(|d| d.payload).collect();
+    assert_eq!(payloads, sent);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn replays_keep_the_recorded_timing() {
+    let listener = listener().await;
+    let replies = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let recording = recording(&[
+        (0, connection_request()),
+        (3_000, connected_ping(1)),
+        (6_000, connected_ping(2)),
+    ]);
+
+    let start = Instant::now();
+    listener
+        .replay(&recording, replies.local_addr().unwrap())
+        .await;
+    // The session outlives its last datagram by exactly the inactivity timeout.
+    assert_eq!(start.elapsed(), Duration::from_secs(6) + CONNECTION_TIMEOUT);
+    assert!(listener.connections().is_empty());
+
+    let mut ids = Vec::new();
+    let mut buf = [0; 2048];
+    while let Ok((len, from)) = replies.try_recv_from(&mut buf) {
+        assert_eq!(from, listener.local_addr().unwrap());
+        ids.push(buf[..len][0]);
+    }
+    assert_eq!(ids, [CONNECTION_REQUEST_ACCEPTED, FRAME_SET, FRAME_SET]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn replays_end_with_an_unfinished_handshake() {
+    let listener = listener()
+        .await
+        .with_handshake_timeout(Duration::from_secs(2));
+    let replies: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let recording = recording(&[(0, connection_request())]);
+
+    let start = Instant::now();
+    listener.replay(&recording, replies).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(2));
+    assert!(listener.connections().is_empty());
+}
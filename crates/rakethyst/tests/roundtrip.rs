@@ -0,0 +1,291 @@
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+
+/// Generates a proptest per packet type asserting that `read(write(packet)) == packet`
+/// and that decoding consumes every byte the encoder produced.
+///
+/// Every listed type must live in `rakethyst::protocol` and implement
+/// [`strategies::PacketStrategy`].
+macro_rules! roundtrip_tests {
+    ($($packet:ident),+ $(,)?) => {
+        $(
+            #[allow(non_snake_case)]
+            mod $packet {
+                use super::*;
+                use proptest::prelude::*;
+
+                proptest! {
+                    #[test]
+                    fn encode_decode_roundtrip(
+                        packet in <rakethyst::protocol::$packet as crate::strategies::PacketStrategy>::strategy()
+                    ) {
+                        let mut writer = BinaryWriter::new();
+                        packet.write(&mut writer).expect("encoding a generated packet failed");
+
+                        let mut reader = BinaryReader::from(writer);
+                        let decoded = rakethyst::protocol::$packet::read(&mut reader)
+                            .expect("decoding an encoded packet failed");
+
+                        prop_assert_eq!(&decoded, &packet);
+                        prop_assert_eq!(reader.remaining(), 0);
+                    }
+                }
+            }
+        )+
+    };
+}
+
+mod strategies {
+    use bytes::Bytes;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use rakethyst::connection::SequenceNumberRange;
+    use rakethyst::protocol::*;
+    use rakethyst::seq::SeqNum;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+    const U24_MAX: u32 = 0xFF_FFFF;
+
+    /// Produces arbitrary, *valid* instances of a packet.
+    pub trait PacketStrategy: Sized {
+        fn strategy() -> BoxedStrategy<Self>;
+    }
+
+    fn u24() -> impl Strategy<Value = u32> {
+        0..=U24_MAX
+    }
+
+    fn seq() -> impl Strategy<Value = SeqNum> {
+        u24().prop_map(SeqNum::new)
+    }
+
+    /// The plain (non-RakNet) address encoding drops IPv6 flow info and scope id.
+    fn socket_addr() -> impl Strategy<Value = SocketAddr> {
+        prop_oneof![
+            (any::<[u8; 4]>(), any::<u16>())
+                .prop_map(|(ip, port)| SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port))),
+            (any::<[u8; 16]>(), any::<u16>()).prop_map(|(ip, port)| {
+                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, 0))
+            }),
+        ]
+    }
+
+    fn raknet_address() -> impl Strategy<Value = SocketAddr> {
+        prop_oneof![
+            (any::<[u8; 4]>(), any::<u16>())
+                .prop_map(|(ip, port)| SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port))),
+            (any::<[u8; 16]>(), any::<u16>(), any::<u32>(), any::<u32>()).prop_map(
+                |(ip, port, flowinfo, scope_id)| {
+                    SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(ip), port, flowinfo, scope_id))
+                }
+            ),
+        ]
+    }
+
+    fn reliability() -> impl Strategy<Value = Reliability> {
+        (0u8..8).prop_map(|value| Reliability::from_u8(value).expect("reliability in range"))
+    }
+
+    impl PacketStrategy for ConnectedPing {
+        fn strategy() -> BoxedStrategy<Self> {
+            any::<u64>().prop_map(|time| Self { time }).boxed()
+        }
+    }
+
+    impl PacketStrategy for UnconnectedPing {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), any::<u64>())
+                .prop_map(|(time, client_guid)| Self { time, client_guid })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for ConnectedPong {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), any::<u64>())
+                .prop_map(|(ping_time, pong_time)| Self {
+                    ping_time,
+                    pong_time,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for UnconnectedPong {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), any::<u64>(), ".{0,256}")
+                .prop_map(|(time, server_guid, motd)| Self {
+                    time,
+                    server_guid,
+                    motd,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for OpenConnectionRequest1 {
+        fn strategy() -> BoxedStrategy<Self> {
+            any::<u8>()
+                .prop_map(|protocol_version| Self { protocol_version })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for OpenConnectionReply1 {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), any::<Option<u32>>(), any::<u16>())
+                .prop_map(|(server_guid, cookie, mtu_size)| Self {
+                    server_guid,
+                    cookie,
+                    mtu_size,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for OpenConnectionRequest2 {
+        fn strategy() -> BoxedStrategy<Self> {
+            (
+                any::<Option<u32>>(),
+                raknet_address(),
+                any::<u16>(),
+                any::<u64>(),
+            )
+                .prop_map(|(cookie, server_addr, mtu, client_guid)| Self {
+                    cookie,
+                    server_addr,
+                    mtu,
+                    client_guid,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for OpenConnectionReply2 {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), raknet_address(), any::<u16>(), any::<bool>())
+                .prop_map(|(server_guid, client_addr, mtu, use_encryption)| Self {
+                    server_guid,
+                    client_addr,
+                    mtu,
+                    use_encryption,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for ConnectionRequest {
+        fn strategy() -> BoxedStrategy<Self> {
+            (any::<u64>(), any::<u64>(), any::<bool>())
+                .prop_map(|(client_guid, time, use_security)| Self {
+                    client_guid,
+                    time,
+                    use_security,
+                })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for ConnectionRequestAccepted {
+        fn strategy() -> BoxedStrategy<Self> {
+            (
+                socket_addr(),
+                any::<u16>(),
+                proptest::collection::vec(socket_addr(), 0..=20),
+                any::<u64>(),
+                any::<u64>(),
+            )
+                .prop_map(
+                    |(client_address, system_index, internal_ids, request_time, time)| Self {
+                        client_address,
+                        system_index,
+                        internal_ids,
+                        request_time,
+                        time,
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for AckNackRecord {
+        fn strategy() -> BoxedStrategy<Self> {
+            prop_oneof![
+                seq().prop_map(AckNackRecord::Single),
+                (u24(), u24()).prop_map(|(a, b)| AckNackRecord::Range(SequenceNumberRange {
+                    start: SeqNum::new(a.min(b)),
+                    end: SeqNum::new(a.max(b)),
+                })),
+            ]
+            .boxed()
+        }
+    }
+
+    impl PacketStrategy for AckNackPacket {
+        fn strategy() -> BoxedStrategy<Self> {
+            vec(AckNackRecord::strategy(), 0..64)
+                .prop_map(|records| Self { records })
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for EncapsulatedPacket {
+        fn strategy() -> BoxedStrategy<Self> {
+            (
+                reliability(),
+                seq(),
+                seq(),
+                any::<u8>(),
+                vec(any::<u8>(), 0..1500),
+            )
+                .prop_map(
+                    |(reliability, sequence_number, ordering_index, ordering_channel, payload)| {
+                        Self {
+                            reliability,
+                            is_split: false,
+                            sequence_number: reliability
+                                .is_reliable()
+                                .then_some(sequence_number),
+                            ordering_index: reliability.is_sequenced().then_some(ordering_index),
+                            ordering_channel: reliability
+                                .is_sequenced()
+                                .then_some(ordering_channel),
+                            split_count: None,
+                            split_id: None,
+                            split_index: None,
+                            payload: Bytes::from(payload),
+                        }
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    impl PacketStrategy for FrameSetPacket {
+        fn strategy() -> BoxedStrategy<Self> {
+            (seq(), vec(EncapsulatedPacket::strategy(), 0..4))
+                .prop_map(|(sequence_number, packets)| Self {
+                    sequence_number,
+                    packets,
+                })
+                .boxed()
+        }
+    }
+}
+
+roundtrip_tests!(
+    ConnectedPing,
+    UnconnectedPing,
+    ConnectedPong,
+    UnconnectedPong,
+    OpenConnectionRequest1,
+    OpenConnectionReply1,
+    OpenConnectionRequest2,
+    OpenConnectionReply2,
+    ConnectionRequest,
+    ConnectionRequestAccepted,
+    AckNackRecord,
+    AckNackPacket,
+    EncapsulatedPacket,
+    FrameSetPacket,
+);
@@ -0,0 +1,46 @@
+use rakethyst::connection::SequenceNumberRange;
+use rakethyst::seq::SeqNum;
+use std::cmp::Ordering;
+
+const LAST: SeqNum = SeqNum::new(SeqNum::MAX);
+
+#[test]
+fn numbers_wrap_into_24_bits() {
+    assert_eq!(SeqNum::new(SeqNum::MAX + 1), SeqNum::ZERO);
+    assert_eq!(LAST.next(), SeqNum::ZERO);
+    assert_eq!(SeqNum::ZERO.wrapping_sub(1), LAST);
+    assert_eq!(LAST.wrapping_add(5), SeqNum::new(4));
+}
+
+#[test]
+fn comparisons_hold_across_the_wrap() {
+    assert!(LAST.is_before(SeqNum::ZERO));
+    assert!(SeqNum::new(3).is_after(SeqNum::new(SeqNum::MAX - 2)));
+    assert!(SeqNum::new(10).is_before(SeqNum::new(11)));
+    assert_eq!(SeqNum::new(7).cmp_wrapping(SeqNum::new(7)), Ordering::Equal);
+
+    // Half the space behind is as far as a number can be and still come first.
+    let half = 1 << 23;
+    assert!(SeqNum::new(half - 1).is_after(SeqNum::ZERO));
+    assert!(SeqNum::new(half + 1).is_before(SeqNum::ZERO));
+}
+
+#[test]
+fn distances_count_forward_across_the_wrap() {
+    assert_eq!(SeqNum::new(5).distance_to(SeqNum::new(9)), 4);
+    assert_eq!(SeqNum::new(SeqNum::MAX - 1).distance_to(SeqNum::new(2)), 4);
+    assert_eq!(SeqNum::new(9).distance_to(SeqNum::new(5)), SeqNum::MAX - 3);
+}
+
+#[test]
+fn ranges_iterate_across_the_wrap() {
+    let range = SequenceNumberRange {
+        start: SeqNum::new(SeqNum::MAX - 1),
+        end: SeqNum::new(1),
+    };
+    let numbers: Vec<u32> = range.iter().map(u32::from).collect();
+    assert_eq!(numbers, [SeqNum::MAX - 1, SeqNum::MAX, 0, 1]);
+    assert!(range.contains(SeqNum::ZERO));
+    assert!(!range.contains(SeqNum::new(2)));
+    assert_eq!(SeqNum::new(4).range_inclusive(SeqNum::new(4)).count(), 1);
+}
@@ -0,0 +1,29 @@
+//! Socket options, read back from the sockets they were applied to.
+
+use rakethyst::socket::{self, SocketOptions};
+use socket2::SockRef;
+
+#[tokio::test]
+async fn options_are_applied_before_binding() {
+    let options = SocketOptions {
+        recv_buffer_size: Some(64 * 1024),
+        send_buffer_size: Some(64 * 1024),
+        dscp: Some(46),
+        ..SocketOptions::default()
+    };
+    let socket = socket::bind("127.0.0.1:0", &options).await.unwrap();
+    let socket = SockRef::from(&socket);
+    // Linux doubles the sizes for bookkeeping, other systems may round them.
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    assert_eq!(socket.tos_v4().unwrap(), 46 << 2);
+}
+
+#[tokio::test]
+async fn default_options_leave_the_socket_alone() {
+    let socket = socket::bind("127.0.0.1:0", &SocketOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(SockRef::from(&socket).tos_v4().unwrap(), 0);
+    assert_ne!(socket.local_addr().unwrap().port(), 0);
+}
@@ -0,0 +1,102 @@
+use bytes::Bytes;
+use rakethyst::connection::SequenceNumberRange;
+use rakethyst::protocol::*;
+use rakethyst::seq::SeqNum;
+use rakethyst::test_support::{
+    ack_nack, acknowledged, decode, encode, FrameSetBuilder, SequenceWindow,
+};
+
+fn seqs(values: &[u32]) -> Vec<SeqNum> {
+    values.iter().map(|&value| SeqNum::new(value)).collect()
+}
+
+#[test]
+fn frame_set_builder_numbers_reliable_and_ordered_frames() {
+    let payload = Bytes::from_static(&[0xfe, 1, 2, 3]);
+    let datagram = FrameSetBuilder::new(SeqNum::new(7))
+        .with_reliable_index(SeqNum::new(40))
+        .frame(Reliability::Unreliable, payload.clone())
+        .frame(Reliability::Reliable, payload.clone())
+        .frame(Reliability::ReliableOrdered, payload.clone())
+        .encode();
+
+    let frame_set: FrameSetPacket = decode(&datagram, FRAME_SET);
+    assert_eq!(frame_set.sequence_number, SeqNum::new(7));
+    let numbering: Vec<_> = frame_set
+        .packets
+        .iter()
+        .map(|packet| (packet.sequence_number, packet.ordering_index))
+        .collect();
+    assert_eq!(
+        numbering,
+        [
+            (None, None),
+            (Some(SeqNum::new(40)), None),
+            (Some(SeqNum::new(41)), Some(SeqNum::ZERO)),
+        ]
+    );
+    assert!(frame_set
+        .packets
+        .iter()
+        .all(|packet| packet.payload == payload));
+}
+
+#[test]
+fn ack_nack_compresses_runs_into_ranges() {
+    let ack = ack_nack(seqs(&[5, 1, 2, 3, 9, 3]));
+    assert_eq!(
+        ack.records,
+        [
+            AckNackRecord::Range(SequenceNumberRange {
+                start: SeqNum::new(1),
+                end: SeqNum::new(3),
+            }),
+            AckNackRecord::Single(SeqNum::new(5)),
+            AckNackRecord::Single(SeqNum::new(9)),
+        ]
+    );
+    assert_eq!(acknowledged(&ack), seqs(&[1, 2, 3, 5, 9]));
+
+    let decoded: AckNackPacket = decode(&encode(ACK, &ack), ACK);
+    assert_eq!(decoded, ack);
+}
+
+#[test]
+fn window_reports_gaps_and_duplicates() {
+    let mut window = SequenceWindow::default();
+    for value in [0, 1, 4, 2, 4] {
+        window.observe(SeqNum::new(value));
+    }
+    assert_eq!(window.next_expected(), SeqNum::new(5));
+    window.assert_missing(&seqs(&[3]));
+    assert_eq!(window.duplicates(), seqs(&[4]));
+    assert_eq!(acknowledged(&window.ack()), seqs(&[0, 1, 2, 4]));
+    assert_eq!(acknowledged(&window.nack()), seqs(&[3]));
+
+    window.observe(SeqNum::new(3));
+    assert_eq!(window.missing(), []);
+}
+
+#[test]
+#[should_panic(expected = "out of order")]
+fn window_asserts_arrival_order() {
+    let mut window = SequenceWindow::default();
+    window.observe(SeqNum::new(1));
+    window.observe(SeqNum::ZERO);
+    window.assert_complete();
+    window.assert_in_order();
+}
+
+#[test]
+fn window_counts_across_the_wrap() {
+    let mut window = SequenceWindow::starting_at(SeqNum::new(SeqNum::MAX - 1));
+    for value in [SeqNum::MAX - 1, SeqNum::MAX, 1] {
+        assert!(window.observe(SeqNum::new(value)));
+    }
+    window.assert_missing(&[SeqNum::ZERO]);
+    assert_eq!(window.next_expected(), SeqNum::new(2));
+    assert_eq!(
+        acknowledged(&window.ack()),
+        seqs(&[1, SeqNum::MAX - 1, SeqNum::MAX])
+    );
+}
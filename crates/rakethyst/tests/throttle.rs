@@ -0,0 +1,47 @@
+//! Parsing and matching of the networks in the join throttle allowlist.
+
+use rakethyst::throttle::{IpNetwork, IpNetworkError};
+use std::net::IpAddr;
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+#[test]
+fn networks_match_addresses_within_the_prefix() {
+    let network: IpNetwork = "10.1.0.0/16".parse().unwrap();
+    assert!(network.contains(ip("10.1.255.7")));
+    assert!(!network.contains(ip("10.2.0.1")));
+    assert!(!network.contains(ip("::1")));
+    assert_eq!(network.to_string(), "10.1.0.0/16");
+}
+
+#[test]
+fn a_plain_address_is_a_network_of_one() {
+    let network: IpNetwork = "2001:db8::1".parse().unwrap();
+    assert!(network.contains(ip("2001:db8::1")));
+    assert!(!network.contains(ip("2001:db8::2")));
+    assert_eq!(network.to_string(), "2001:db8::1/128");
+}
+
+#[test]
+fn ipv4_mapped_addresses_match_ipv4_networks() {
+    let network: IpNetwork = "192.0.2.0/24".parse().unwrap();
+    assert!(network.contains(ip("::ffff:192.0.2.10")));
+    assert!("0.0.0.0/0"
+        .parse::<IpNetwork>()
+        .unwrap()
+        .contains(ip("203.0.113.1")));
+}
+
+#[test]
+fn invalid_networks_are_rejected() {
+    assert_eq!(
+        "10.0.0.0/33".parse::<IpNetwork>(),
+        Err(IpNetworkError::PrefixLen("33".to_string()))
+    );
+    assert_eq!(
+        "example.com/8".parse::<IpNetwork>(),
+        Err(IpNetworkError::Address("example.com".to_string()))
+    );
+}
@@ -0,0 +1,209 @@
+//! Timeouts, checked on a paused clock. Sessions read the time from tokio, so replaying a
+//! recording on a paused runtime jumps straight to each deadline and every elapsed time is
+//! exact.
+
+use amethyst_binary::io::{BinaryReader, BinaryWriter};
+use amethyst_binary::traits::{Readable, Writable};
+use bytes::Bytes;
+use rakethyst::listener::{RakNetListener, ServerInfo};
+use rakethyst::motd::Motd;
+use rakethyst::protocol::*;
+use rakethyst::recording::{RecordedDatagram, Recording};
+use rakethyst::seq::SeqNum;
+use rakethyst::session::CONNECTION_TIMEOUT;
+use rakethyst::violations::StrictMode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+const CLIENT_GUID: u64 = 0x1a2b_3c4d_5e6f_7081;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// A frame set too short to hold its sequence number.
+const MALFORMED_FRAME_SET: &[u8] = &[FRAME_SET, 0];
+
+fn encode(id: u8, packet: &impl Writable) -> Bytes {
+    let mut writer = BinaryWriter::new();
+    writer.write_u8(id).unwrap();
+    packet.write(&mut writer).unwrap();
+    writer.freeze()
+}
+
+fn decode<T: Readable>(data: &[u8]) -> T {
+    T::read(&mut BinaryReader::new(Bytes::copy_from_slice(&data[1..]))).unwrap()
+}
+
+fn connection_request() -> Bytes {
+    let request = ConnectionRequest {
+        client_guid: CLIENT_GUID,
+        time: 1,
+        use_security: false,
+    };
+    encode(CONNECTION_REQUEST, &request)
+}
+
+fn connected_ping(time: u64) -> Bytes {
+    let frame_set = FrameSetPacket {
+        sequence_number: SeqNum::new(time as u32),
+        packets: vec![EncapsulatedPacket {
+            reliability: Reliability::Unreliable,
+            is_split: false,
+            sequence_number: None,
+            ordering_index: None,
+            ordering_channel: None,
+            split_count: None,
+            split_id: None,
+            split_index: None,
+            payload: encode(CONNECTED_PING, &ConnectedPing { time }),
+        }],
+    };
+    encode(FRAME_SET, &frame_set)
+}
+
+async fn listener() -> RakNetListener {
+    rakethyst::utils::init_time();
+    let motd = Motd {
+        motd: "Amethyst".to_string(),
+        world_name: "World".to_string(),
+        game_mode: "Survival".to_string(),
+        max_players: 10,
+    };
+    let server_info = Arc::new(ServerInfo::new(1, motd));
+    RakNetListener::bind("127.0.0.1:0", server_info)
+        .await
+        .unwrap()
+        .with_handshake_timeout(HANDSHAKE_TIMEOUT)
+}
+
+/// Feeds the datagrams, sent the given number of milliseconds after the first, to a new
+/// session. Returns how long the session lasted and what it sent back.
+async fn run_session(
+    listener: &RakNetListener,
+    datagrams: &[(u64, Bytes)],
+) -> (Duration, Vec<Bytes>) {
+    let replies = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let recording = Recording {
+        address: "10.0.0.7:50123".parse().unwrap(),
+        datagrams: datagrams
+            .iter()
+            .map(|(millis, payload)| RecordedDatagram {
+                offset: Duration::from_millis(*millis),
+                payload: payload.clone(),
+            })
+            .collect(),
+    };
+    let start = Instant::now();
+    listener
+        .replay(&recording, replies.local_addr().unwrap())
+        .await;
+    let elapsed = start.elapsed();
+    assert!(listener.connections().is_empty());
+
+    let mut sent = Vec::new();
+    let mut buf = [0; 2048];
+    while let Ok((len, _)) = replies.try_recv_from(&mut buf) {
+        sent.push(Bytes::copy_from_slice(&buf[..len]));
+    }
+    (elapsed, sent)
+}
+
+fn pong_of(frame_set: &[u8]) -> ConnectedPong {
+    let frame_set: FrameSetPacket = decode(frame_set);
+    decode(&frame_set.packets[0].payload)
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_data_frame_just_before_the_handshake_timeout_connects() {
+    let listener = listener().await;
+    let last = HANDSHAKE_TIMEOUT.as_millis() as u64 - 1;
+    let (elapsed, sent) = run_session(
+        &listener,
+        &[(0, connection_request()), (last, connected_ping(1))],
+    )
+    .await;
+    assert_eq!(elapsed, Duration::from_millis(last) + CONNECTION_TIMEOUT);
+    assert_eq!(sent.len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_data_frame_after_the_handshake_timeout_is_too_late() {
+    let listener = listener().await;
+    let late = HANDSHAKE_TIMEOUT.as_millis() as u64 + 1;
+    let (elapsed, sent) = run_session(
+        &listener,
+        &[(0, connection_request()), (late, connected_ping(1))],
+    )
+    .await;
+    // The session is gone by the time the data frame arrives.
+    assert_eq!(elapsed, Duration::from_millis(late));
+    assert_eq!(sent.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn every_datagram_postpones_the_connection_timeout() {
+    let listener = listener().await;
+    let step = CONNECTION_TIMEOUT.as_millis() as u64 - 1;
+    let datagrams: Vec<_> = [(0, connection_request()), (0, connected_ping(0))]
+        .into_iter()
+        .chain((1..=4).map(|i| (i * step, connected_ping(i))))
+        .collect();
+    let (elapsed, sent) = run_session(&listener, &datagrams).await;
+    assert_eq!(
+        elapsed,
+        Duration::from_millis(4 * step) + CONNECTION_TIMEOUT
+    );
+    assert_eq!(sent.len(), 6);
+}
+
+#[tokio::test(start_paused = true)]
+async fn pong_times_follow_the_clock() {
+    let listener = listener().await;
+    let (_, sent) = run_session(
+        &listener,
+        &[
+            (0, connection_request()),
+            (1_500, connected_ping(1)),
+            (4_000, connected_ping(2)),
+        ],
+    )
+    .await;
+    let accepted: ConnectionRequestAccepted = decode(&sent[0]);
+    let pongs: Vec<_> = sent[1..].iter().map(|data| pong_of(data)).collect();
+    assert_eq!(pongs[0].pong_time - accepted.time, 1_500);
+    assert_eq!(pongs[1].pong_time - accepted.time, 4_000);
+}
+
+#[tokio::test(start_paused = true)]
+async fn strict_mode_counts_violations_within_the_window() {
+    let mode = StrictMode {
+        max_violations: 2,
+        window: Duration::from_secs(5),
+    };
+    let listener = listener().await.with_strict_mode(mode);
+    let malformed = Bytes::from_static(MALFORMED_FRAME_SET);
+
+    // Violations further apart than the window are forgotten.
+    let (elapsed, _) = run_session(
+        &listener,
+        &[
+            (0, connection_request()),
+            (1_000, malformed.clone()),
+            (6_000, malformed.clone()),
+        ],
+    )
+    .await;
+    assert_eq!(elapsed, Duration::from_secs(6) + CONNECTION_TIMEOUT);
+
+    let (elapsed, sent) = run_session(
+        &listener,
+        &[
+            (0, connection_request()),
+            (1_000, malformed.clone()),
+            (5_999, malformed),
+        ],
+    )
+    .await;
+    assert_eq!(elapsed, Duration::from_millis(5_999));
+    let frame_set: FrameSetPacket = decode(sent.last().unwrap());
+    assert_eq!(frame_set.packets[0].payload[0], DISCONNECTION_NOTIFICATION);
+}